@@ -0,0 +1,83 @@
+//! Compare des tranches de taille fixe et le découpage adaptatif des parcours
+//! ([`SearchRuntime::with_adaptive_chunks`]) sur des vecteurs de petite et de grande
+//! dimension.
+//!
+//! `cargo bench --bench adaptive_chunks` ; la taille se règle avec les variables
+//! d'environnement `COORDINATES` (40 000 000 coordonnées par dimension mesurée par
+//! défaut, soit 1 250 000 documents de dimension 32 et 9 765 de dimension 4 096) et
+//! `THREADS` (autant que de cœurs par défaut).
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use embeddingproject::prelude::*;
+use embeddingproject::synthetic;
+use embeddingproject::{AdaptiveChunks, SearchRuntime};
+
+const QUERIES: u32 = 20;
+const DIMENSIONS: [usize; 2] = [32, 4096];
+const CHUNK_SIZES: [usize; 4] = [256, 1_024, 4_096, 16_384];
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Durée moyenne d'une recherche avec le réglage `runtime`.
+fn measure(
+    collection: &Collection,
+    runtime: SearchRuntime,
+    query: &[f32],
+) -> Result<Duration, embeddingproject::Error> {
+    let collection = collection.clone().with_runtime(runtime);
+    let params = SearchParams::new(10);
+    collection.search_with(query, &params)?;
+    let start = Instant::now();
+    for _ in 0..QUERIES {
+        collection.search_with(query, &params)?;
+    }
+    Ok(start.elapsed() / QUERIES)
+}
+
+fn main() -> Result<(), embeddingproject::Error> {
+    let coordinates = env_or("COORDINATES", 40_000_000);
+    let runtime = match env_or("THREADS", 0) {
+        0 => SearchRuntime::default(),
+        threads => SearchRuntime::new(threads),
+    };
+
+    for dimension in DIMENSIONS {
+        let documents = (coordinates / dimension).max(1);
+        let mut collection = Collection::new();
+        let keys = synthetic::populate_collection(&mut collection, documents, dimension, 1);
+        let query = collection
+            .read(keys.first().expect("collection non vide"))
+            .expect("document présent")
+            .to_vec();
+
+        println!(
+            "{} documents de dimension {}, {} thread(s)",
+            documents,
+            dimension,
+            runtime.threads()
+        );
+        let mut best = None;
+        let equal = measure(&collection, runtime, &query)?;
+        println!("  parts égales       : {:>10.2?} par requête", equal);
+        for size in CHUNK_SIZES {
+            let fixed = measure(&collection, runtime.with_chunk_size(size), &query)?;
+            println!("  tranches de {:>6} : {:>10.2?} par requête", size, fixed);
+            best = Some(best.map_or(fixed, |best: Duration| best.min(fixed)));
+        }
+        let adaptive = runtime.with_adaptive_chunks(AdaptiveChunks::default());
+        let adaptive = measure(&collection, adaptive, &query)?;
+        println!(
+            "  adaptatif          : {:>10.2?} par requête (meilleure taille fixe {:.2?})",
+            adaptive,
+            best.unwrap_or(equal)
+        );
+    }
+    Ok(())
+}
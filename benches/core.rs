@@ -0,0 +1,231 @@
+//! Mesures de référence des opérations principales, sur des données générées par le
+//! module `synthetic` : score cosinus, en un passage ou découpé entre threads en très
+//! grande dimension, recherche, boucle de score brute, insertion par
+//! lot comparée au chargement initial, insertion et recherche depuis une matrice contiguë comparées à l'API par
+//! vecteur, recherche cosinus sur des vecteurs déclarés unitaires comparée aux mêmes
+//! vecteurs non déclarés, sauvegarde et chargement.
+//!
+//! `cargo bench --bench core [-- <filtre>]` ne lance que les mesures dont le nom contient
+//! le filtre. Les tailles de collection se règlent avec la variable d'environnement
+//! `SIZES` (`1000,100000,1000000` par défaut) et la dimension avec `DIMENSION` (128 par
+//! défaut). Chaque ligne donne la médiane et le minimum du temps par itération.
+
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use embeddingproject::prelude::*;
+use embeddingproject::similarity;
+use embeddingproject::synthetic::{self, VectorGenerator};
+use embeddingproject::{CollectionLoader, SearchRuntime};
+
+/// Nombre d'échantillons mesurés par opération.
+const SAMPLES: usize = 15;
+
+/// Durée visée pour chaque échantillon ; les opérations rapides y sont répétées.
+const SAMPLE_TARGET: Duration = Duration::from_millis(20);
+
+fn main() -> Result<(), Error> {
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let sizes: Vec<usize> = env::var("SIZES")
+        .unwrap_or_else(|_| "1000,100000,1000000".to_string())
+        .split(',')
+        .filter_map(|size| size.trim().parse().ok())
+        .collect();
+    let dimension = env::var("DIMENSION")
+        .ok()
+        .and_then(|dimension| dimension.parse().ok())
+        .unwrap_or(128);
+    let mut bench = Bench { filter };
+
+    for dim in [128, 768, 1536] {
+        let pair = synthetic::random_vectors(2, dim, 1);
+        bench.run(&format!("cosine/{}", dim), || {
+            similarity::cosine(black_box(&pair[0]), black_box(&pair[1]))
+        });
+    }
+
+    // Point de bascule du découpage d'une comparaison : `cosine_scalar` contre
+    // `cos_chunked`, une tranche par thread.
+    let threads = SearchRuntime::default().threads();
+    for dim in [12_288, 65_536, 262_144, 1_048_576] {
+        let scalar = format!("cosine_scalar/{}", dim);
+        let chunked = format!("cos_chunked/{}", dim);
+        if !bench.enabled(&scalar) && !bench.enabled(&chunked) {
+            continue;
+        }
+        let pair = synthetic::random_vectors(2, dim, 1);
+        bench.run(&scalar, || {
+            similarity::cosine(black_box(&pair[0]), black_box(&pair[1]))
+        });
+        bench.run(&chunked, || {
+            similarity::cos_chunked(
+                black_box(&pair[0]),
+                black_box(&pair[1]),
+                dim.div_ceil(threads),
+            )
+        });
+    }
+
+    let query = synthetic::random_vectors(1, dimension, 2).remove(0);
+    for &size in &sizes {
+        let name = format!("search/{}x{}", size, dimension);
+        let scan = format!("raw_scan/{}x{}", size, dimension);
+        if !bench.enabled(&name) && !bench.enabled(&scan) {
+            continue;
+        }
+        let mut collection = Collection::new();
+        synthetic::populate_collection(&mut collection, size, dimension, 3);
+        bench.run(&name, || collection.search(black_box(&query), 10));
+        bench.run(&scan, || collection.raw_scan(black_box(&query)));
+    }
+
+    for &size in &sizes {
+        let plain = format!("search_cosine/{}x{}", size, dimension);
+        let unit = format!("search_unit/{}x{}", size, dimension);
+        // Les deux collections reçoivent les mêmes vecteurs unitaires, l'une après l'autre
+        // pour ne pas les garder en mémoire deux fois.
+        for (name, config) in [
+            (&plain, CollectionConfig::default()),
+            (
+                &unit,
+                CollectionConfig::builder()
+                    .vectors_are_normalized(true)
+                    .build()?,
+            ),
+        ] {
+            if !bench.enabled(name) {
+                continue;
+            }
+            let mut collection = Collection::from_config(config);
+            let mut generator = VectorGenerator::new(dimension, 7);
+            for _ in 0..size {
+                let mut vector = generator.vector();
+                let norm = similarity::norm(&vector);
+                vector.iter_mut().for_each(|x| *x /= norm);
+                collection.upsert(generator.uuid(), vector)?;
+            }
+            bench.run(name, || collection.search(black_box(&query), 10));
+        }
+    }
+
+    let name = format!("upsert_batch/10000x{}", dimension);
+    if bench.enabled(&name) {
+        let mut generator = VectorGenerator::new(dimension, 4);
+        let batch: Vec<(Uuid, Vec<f32>)> = (0..10_000)
+            .map(|_| (generator.uuid(), generator.vector()))
+            .collect();
+        bench.run(&name, || {
+            let mut collection = Collection::new();
+            collection.upsert_batch(batch.iter().cloned(), None)
+        });
+    }
+
+    let name = format!("loader/10000x{}", dimension);
+    if bench.enabled(&name) {
+        let mut generator = VectorGenerator::new(dimension, 4);
+        let batch: Vec<(Uuid, Vec<f32>)> = (0..10_000)
+            .map(|_| (generator.uuid(), generator.vector()))
+            .collect();
+        bench.run(&name, || {
+            let mut loader = CollectionLoader::with_capacity(CollectionConfig::default(), 10_000);
+            loader.push_chunk(batch.iter().cloned());
+            loader.finish()
+        });
+    }
+
+    let rows = format!("upsert_rows/10000x{}", dimension);
+    let vectors = format!("upsert_vectors/10000x{}", dimension);
+    let search_rows = format!("search_rows/100x10000x{}", dimension);
+    let search_each = format!("search_each/100x10000x{}", dimension);
+    if [&rows, &vectors, &search_rows, &search_each]
+        .iter()
+        .any(|name| bench.enabled(name))
+    {
+        let mut generator = VectorGenerator::new(dimension, 6);
+        let ids: Vec<Uuid> = (0..10_000).map(|_| generator.uuid()).collect();
+        let data: Vec<f32> = generator.vectors(ids.len()).concat();
+        bench.run(&rows, || {
+            let mut collection = Collection::new();
+            collection.upsert_rows(&ids, &data, dimension)
+        });
+        bench.run(&vectors, || {
+            let mut collection = Collection::new();
+            let batch = ids
+                .iter()
+                .copied()
+                .zip(data.chunks_exact(dimension).map(<[f32]>::to_vec));
+            collection.upsert_batch(batch.collect::<Vec<_>>(), None)
+        });
+        let mut collection = Collection::new();
+        collection.upsert_rows(&ids, &data, dimension)?;
+        let queries = &data[..100 * dimension];
+        bench.run(&search_rows, || {
+            collection.search_rows(black_box(queries), dimension, 10)
+        });
+        bench.run(&search_each, || {
+            queries
+                .chunks_exact(dimension)
+                .map(|query| collection.search(query, 10))
+                .collect::<Result<Vec<_>, _>>()
+        });
+    }
+
+    let save = format!("save/100000x{}", dimension);
+    let load = format!("load/100000x{}", dimension);
+    if bench.enabled(&save) || bench.enabled(&load) {
+        let path = env::temp_dir().join("embeddingproject-bench-core.db");
+        let mut bdd = BaseDeDonnees::new();
+        bdd.add("bench".to_string());
+        synthetic::populate_collection(&mut bdd["bench"], 100_000, dimension, 5);
+        bench.run(&save, || bdd.save(&path));
+        bdd.save(&path)?;
+        bench.run(&load, || BaseDeDonnees::load(&path));
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+/// Exécute et affiche les mesures retenues par le filtre.
+struct Bench {
+    filter: Option<String>,
+}
+
+impl Bench {
+    fn enabled(&self, name: &str) -> bool {
+        self.filter
+            .as_deref()
+            .is_none_or(|filter| name.contains(filter))
+    }
+
+    /// Mesure `f` : calibre le nombre d'itérations par échantillon sur `SAMPLE_TARGET`,
+    /// puis affiche la médiane et le minimum de `SAMPLES` échantillons.
+    fn run<T>(&mut self, name: &str, mut f: impl FnMut() -> T) {
+        if !self.enabled(name) {
+            return;
+        }
+        let start = Instant::now();
+        black_box(f());
+        let once = start.elapsed().max(Duration::from_nanos(1));
+        let iterations = (SAMPLE_TARGET.as_nanos() / once.as_nanos()).clamp(1, 1_000_000) as u32;
+
+        let mut samples: Vec<Duration> = (0..SAMPLES)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..iterations {
+                    black_box(f());
+                }
+                start.elapsed() / iterations
+            })
+            .collect();
+        samples.sort_unstable();
+        println!(
+            "{:<28} médiane {:>12.2?}   min {:>12.2?}   ({} × {} itérations)",
+            name,
+            samples[SAMPLES / 2],
+            samples[0],
+            SAMPLES,
+            iterations
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Compare une recherche filtrée sur un champ sélectif avec et sans index de charge
+//! utile.
+//!
+//! `cargo bench --bench filtered_search` ; la taille se règle avec les variables
+//! d'environnement `DOCUMENTS` (100 000 par défaut), `DIMENSION` (128 par défaut) et
+//! `TENANTS` (1 000 valeurs distinctes du champ filtré par défaut).
+
+use std::env;
+use std::time::Instant;
+
+use embeddingproject::prelude::*;
+use embeddingproject::synthetic::VectorGenerator;
+use embeddingproject::{Filter, IndexKind, Payload};
+
+const QUERIES: usize = 20;
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn main() -> Result<(), embeddingproject::Error> {
+    let documents = env_or("DOCUMENTS", 100_000);
+    let dimension = env_or("DIMENSION", 128);
+    let tenants = env_or("TENANTS", 1_000).max(1);
+
+    let mut collection = Collection::new();
+    let mut generator = VectorGenerator::new(dimension, 1);
+    for i in 0..documents {
+        let mut payload = Payload::new();
+        payload.insert("tenant".to_string(), ((i % tenants) as i64).into());
+        collection.upsert_with_payload(generator.uuid(), generator.vector(), payload)?;
+    }
+    let query = vec![0.5; dimension];
+    let filter = Filter::eq("tenant", 7_i64);
+    let params = SearchParams::new(10);
+
+    let start = Instant::now();
+    let mut scanned = None;
+    for _ in 0..QUERIES {
+        scanned = Some(collection.search_filtered(&query, &filter, &params)?);
+    }
+    let scan = start.elapsed() / QUERIES as u32;
+
+    let start = Instant::now();
+    collection.create_payload_index("tenant", IndexKind::Keyword);
+    let built = start.elapsed();
+
+    let start = Instant::now();
+    let mut indexed = None;
+    for _ in 0..QUERIES {
+        indexed = Some(collection.search_filtered(&query, &filter, &params)?);
+    }
+    let index = start.elapsed() / QUERIES as u32;
+    assert_eq!(scanned, indexed, "l'index doit donner les mêmes résultats");
+
+    println!(
+        "{} documents de dimension {}, filtre retenant 1 document sur {}",
+        documents, dimension, tenants
+    );
+    println!("sans index : {:>10.2?} par requête", scan);
+    println!(
+        "avec index : {:>10.2?} par requête (construction {:.2?})",
+        index, built
+    );
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Compare le temps de démarrage d'une collection chargée sur le tas et d'une collection
+//! projetée en mémoire.
+//!
+//! `cargo bench --bench mmap_startup` ; la taille se règle avec les variables
+//! d'environnement `DOCUMENTS` (100 000 par défaut) et `DIMENSION` (256 par défaut).
+
+use std::env;
+use std::time::Instant;
+
+use embeddingproject::prelude::*;
+use embeddingproject::synthetic;
+use embeddingproject::MmapCollection;
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn main() -> Result<(), embeddingproject::Error> {
+    let documents = env_or("DOCUMENTS", 100_000);
+    let dimension = env_or("DIMENSION", 256);
+
+    let mut collection = Collection::new();
+    let keys = synthetic::populate_collection(&mut collection, documents, dimension, 1);
+    let query = collection
+        .read(keys.first().expect("collection non vide"))
+        .expect("document présent")
+        .to_vec();
+
+    let directory = env::temp_dir();
+    let snapshot_path = directory.join("embeddingproject-bench.db");
+    let mmap_path = directory.join("embeddingproject-bench.mmap");
+    MmapCollection::write(&collection, &mmap_path)?;
+    let mut bdd = BaseDeDonnees::new();
+    bdd.replace("bench".to_string(), collection);
+    bdd.save(&snapshot_path)?;
+    drop(bdd);
+
+    let start = Instant::now();
+    let bdd = BaseDeDonnees::load(&snapshot_path)?;
+    let loaded = start.elapsed();
+    bdd.search("bench", &query, 10)?;
+    let heap_first_query = start.elapsed();
+    drop(bdd);
+
+    let start = Instant::now();
+    let mapped = MmapCollection::open(&mmap_path)?;
+    let opened = start.elapsed();
+    VectorStore::search(&mapped, &query, 10)?;
+    let mmap_first_query = start.elapsed();
+
+    println!("{} documents de dimension {}", documents, dimension);
+    println!(
+        "tas   : chargement {:>10.2?}, première requête à {:>10.2?}",
+        loaded, heap_first_query
+    );
+    println!(
+        "mmap  : ouverture  {:>10.2?}, première requête à {:>10.2?}",
+        opened, mmap_first_query
+    );
+
+    let _ = std::fs::remove_file(snapshot_path);
+    let _ = std::fs::remove_file(mmap_path);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Utilise le module `core` d'`embeddingproject` sans la bibliothèque standard, comme le
+//! ferait une cible embarquée ou WebAssembly : la compilation de cette crate vérifie que
+//! le noyau ne demande que `core` et `alloc`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use embeddingproject::core::{self as kernel, DimensionMismatch, Metric, TopK};
+
+/// Retourne les `k` vecteurs de `vectors` les plus proches de `query` selon `metric`,
+/// identifiés par leur position.
+///
+/// # Erreurs
+/// * `DimensionMismatch` - Pour le premier vecteur d'une autre dimension que la requête.
+pub fn nearest(
+    query: &[f32],
+    vectors: &[&[f32]],
+    k: usize,
+    metric: Metric,
+) -> Result<Vec<(usize, f32)>, DimensionMismatch> {
+    let mut top = TopK::for_metric(k, metric);
+    for (position, vector) in vectors.iter().enumerate() {
+        top.push(position, kernel::score(metric, query, vector)?);
+    }
+    Ok(top.into_sorted_hits())
+}
+
+/// Retourne la similarité cosinus, le produit scalaire, les distances euclidienne et de
+/// Manhattan, puis la norme de `a`.
+///
+/// # Erreurs
+/// * `DimensionMismatch` - Si les dimensions diffèrent.
+pub fn all_scores(a: &[f32], b: &[f32]) -> Result<[f32; 5], DimensionMismatch> {
+    Ok([
+        kernel::cosine(a, b)?,
+        kernel::dot(a, b)?,
+        kernel::euclidean(a, b)?,
+        kernel::manhattan(a, b)?,
+        kernel::norm(a),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn nearest_ranks_without_std() {
+        let vectors: [&[f32]; 4] = [&[0.0, 1.0], &[1.0, 0.0], &[0.9, 0.1], &[-1.0, 0.0]];
+        let hits = nearest(&[1.0, 0.0], &vectors, 2, Metric::Euclidean).unwrap();
+        assert_eq!(hits.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(hits[0].1, 0.0);
+        let hits = nearest(&[1.0, 0.0], &vectors, 1, Metric::Cosine).unwrap();
+        assert_eq!(hits, vec![(1, 1.0)]);
+        assert_eq!(
+            nearest(&[1.0], &vectors, 2, Metric::Dot),
+            Err(DimensionMismatch {
+                expected: 1,
+                got: 2
+            })
+        );
+    }
+
+    #[test]
+    fn all_scores_without_std() {
+        assert_eq!(
+            all_scores(&[3.0, 4.0], &[0.0, 4.0]),
+            Ok([0.8, 16.0, 3.0, 3.0, 5.0])
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Mesure le temps d'une recherche exhaustive selon la taille de la collection.
+//!
+//! `cargo run --release --example bench_scan -- [dimension] [requêtes]`
+//!
+//! Les collections, de 1 000 à 1 000 000 de documents, sont générées aléatoirement
+//! avec une graine fixe : deux exécutions mesurent les mêmes données.
+
+use std::env;
+use std::time::Instant;
+
+use embeddingproject::prelude::*;
+use embeddingproject::synthetic::VectorGenerator;
+
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+fn main() -> Result<(), Error> {
+    let mut args = env::args().skip(1);
+    let dimension = args.next().and_then(|d| d.parse().ok()).unwrap_or(128);
+    let queries = args
+        .next()
+        .and_then(|q| q.parse().ok())
+        .unwrap_or(20)
+        .max(1);
+
+    let mut generator = VectorGenerator::new(dimension, 7);
+    let requests = generator.vectors(queries);
+    let mut collection = Collection::new();
+    println!("dimension {}, {} requêtes par taille", dimension, queries);
+    println!(
+        "{:>10}  {:>12}  {:>14}",
+        "documents", "par requête", "documents/s"
+    );
+    for size in SIZES {
+        let missing = size - collection.len();
+        generator.fill(&mut collection, missing);
+
+        let start = Instant::now();
+        for request in &requests {
+            collection.search(request, 10)?;
+        }
+        let per_query = start.elapsed() / queries as u32;
+        println!(
+            "{:>10}  {:>12.2?}  {:>14.0}",
+            size,
+            per_query,
+            size as f64 / per_query.as_secs_f64()
+        );
+    }
+    Ok(())
+}
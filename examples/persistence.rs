@@ -0,0 +1,69 @@
+//! Sauvegarde, rechargement et compactage d'une base de données.
+//!
+//! `cargo run --example persistence`
+//!
+//! La collection utilise la suppression différée : les documents supprimés restent
+//! restaurables jusqu'au compactage, et une sauvegarde n'écrit que les documents vivants.
+
+use std::env;
+use std::fs;
+
+use embeddingproject::prelude::*;
+use embeddingproject::synthetic::VectorGenerator;
+
+fn main() -> Result<(), Error> {
+    let path = env::temp_dir().join("embeddingproject-persistence.db");
+    let mut generator = VectorGenerator::new(64, 42);
+
+    let mut bdd = BaseDeDonnees::new();
+    bdd.replace(
+        "articles".to_string(),
+        Collection::new().with_soft_delete(true),
+    );
+    let keys = generator.fill(&mut bdd["articles"], 10_000);
+    let query = bdd["articles"].expect_read(&keys[0]).clone();
+
+    bdd.save(&path)?;
+    let full_size = fs::metadata(&path)?.len();
+    println!(
+        "sauvegarde : {} documents, {} octets",
+        keys.len(),
+        full_size
+    );
+
+    // Un rechargement retrouve les mêmes documents, dans le même ordre de résultats.
+    let reloaded = BaseDeDonnees::load(&path)?;
+    assert_eq!(
+        reloaded.search("articles", &query, 10)?,
+        bdd.search("articles", &query, 10)?
+    );
+    assert!(reloaded.diff(&bdd).is_empty());
+    println!("rechargement : identique à la base d'origine");
+
+    let articles = &mut bdd["articles"];
+    for key in &keys[..keys.len() / 2] {
+        articles.delete(key);
+    }
+    articles.restore(&keys[0]);
+    println!(
+        "suppression : {} documents vivants, {} en attente de compactage",
+        articles.len(),
+        articles.tombstone_count()
+    );
+    println!("compactage : {} documents libérés", articles.compact());
+
+    bdd.save(&path)?;
+    let compacted_size = fs::metadata(&path)?.len();
+    println!(
+        "sauvegarde compactée : {} documents, {} octets ({:.0} %)",
+        bdd["articles"].len(),
+        compacted_size,
+        100.0 * compacted_size as f64 / full_size as f64
+    );
+
+    let reloaded = BaseDeDonnees::load(&path)?;
+    reloaded["articles"].check_invariants()?;
+    assert_eq!(reloaded["articles"].len(), bdd["articles"].len());
+    fs::remove_file(&path)?;
+    Ok(())
+}
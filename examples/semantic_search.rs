@@ -0,0 +1,81 @@
+//! Recherche sémantique sur des plongements précalculés.
+//!
+//! `cargo run --example semantic_search -- documents.jsonl [k]`
+//!
+//! Chaque ligne de `documents.jsonl` décrit un document :
+//! `{"id": "doc-1", "vector": [0.1, 0.2, ...], "payload": {"title": "..."}}`.
+//! Les requêtes sont lues sur l'entrée standard, une par ligne : soit l'identifiant d'un
+//! document importé (pour trouver ses voisins), soit un vecteur `x1,x2,...`.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use embeddingproject::prelude::*;
+use embeddingproject::{float, ChannelProgress};
+
+fn main() -> Result<(), Error> {
+    let mut args = env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage : semantic_search <documents.jsonl> [k]");
+        std::process::exit(2);
+    };
+    let k = args.next().and_then(|k| k.parse().ok()).unwrap_or(5);
+
+    let (progress, updates) = ChannelProgress::new();
+    let mut collection = Collection::new();
+    let report = collection.import_jsonl(BufReader::new(File::open(&path)?), Some(&progress))?;
+    drop(progress);
+    if let Some(last) = updates.try_iter().last() {
+        eprintln!("progression : {} documents lus", last.done);
+    }
+    eprintln!(
+        "{} documents importés depuis {} (dimension {:?})",
+        report.applied,
+        path,
+        collection.validate().dimension
+    );
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let query = match parse_vector(line) {
+            Some(vector) => vector,
+            None => match collection.read_id(line) {
+                Some(vector) => vector.clone(),
+                None => {
+                    eprintln!("ni vecteur ni document connu : {}", line);
+                    continue;
+                }
+            },
+        };
+        let params = SearchParams::new(k);
+        match collection.search_ids(&query, &params) {
+            Ok(hits) => {
+                for (rank, (id, score)) in hits.iter().enumerate() {
+                    let title = collection
+                        .payload(&id.to_uuid())
+                        .and_then(|payload| payload.get("title"))
+                        .map(|title| {
+                            title
+                                .as_str()
+                                .map_or_else(|| title.to_string(), str::to_string)
+                        })
+                        .unwrap_or_default();
+                    let score = float::significant(f64::from(*score), float::DISPLAY_DIGITS);
+                    println!("{:>3}  {:>8}  {}  {}", rank + 1, score, id, title);
+                }
+            }
+            Err(error) => eprintln!("requête refusée : {}", error),
+        }
+    }
+    Ok(())
+}
+
+/// Lit un vecteur écrit sous la forme `x1,x2,...`.
+fn parse_vector(text: &str) -> Option<Vec<f32>> {
+    text.split(',').map(|x| x.trim().parse().ok()).collect()
+}
@@ -0,0 +1,20 @@
+//! Charge des octets arbitraires comme fichier de sauvegarde.
+//!
+//! `cargo +nightly fuzz run snapshot_load` : le chargement ne doit jamais paniquer ni
+//! allouer de façon démesurée, et une base chargée doit rester utilisable et se
+//! sauvegarder à l'identique.
+
+#![no_main]
+
+use embeddingproject::BaseDeDonnees;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(bdd) = BaseDeDonnees::from_bytes(data) else {
+        return;
+    };
+    let _ = bdd.summary();
+    let bytes = bdd.to_bytes();
+    let reloaded = BaseDeDonnees::from_bytes(&bytes).expect("une base sauvegardée se recharge");
+    assert_eq!(reloaded.to_bytes(), bytes);
+});
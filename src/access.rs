@@ -0,0 +1,254 @@
+//! Statistiques d'accès aux documents : combien de fois chacun a figuré dans les
+//! résultats retournés par les recherches, et quand pour la dernière fois.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::error::Result;
+
+/// Nombre de tables indépendantes entre lesquelles les compteurs sont répartis, pour
+/// que des recherches concurrentes se disputent rarement le même verrou.
+const ACCESS_SHARDS: usize = 16;
+
+/// Accès d'un document par les recherches depuis la création de la collection, ou son
+/// chargement : les compteurs ne sont pas sauvegardés.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AccessStats {
+    /// Nombre de recherches dont le document faisait partie des résultats retournés.
+    pub count: u64,
+    /// Date de la dernière de ces recherches, `None` si le document n'a jamais été
+    /// retourné.
+    pub last_accessed: Option<SystemTime>,
+}
+
+/// Compteurs d'accès d'une collection, répartis par `Uuid` entre plusieurs tables.
+#[derive(Debug)]
+pub(crate) struct AccessLog {
+    shards: Box<[Mutex<HashMap<Uuid, AccessStats>>]>,
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        AccessLog {
+            shards: (0..ACCESS_SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+}
+
+impl Clone for AccessLog {
+    fn clone(&self) -> Self {
+        AccessLog {
+            shards: (0..ACCESS_SHARDS)
+                .map(|shard| Mutex::new(self.shard(shard).clone()))
+                .collect(),
+        }
+    }
+}
+
+impl AccessLog {
+    /// Compte un accès, daté de `now`, pour chaque document de `hits`.
+    fn record(&self, hits: &Document, now: SystemTime) {
+        for (key, _) in hits {
+            let mut shard = self.shard(shard_of(key));
+            let stats = shard.entry(*key).or_default();
+            stats.count += 1;
+            stats.last_accessed = Some(now);
+        }
+    }
+
+    fn get(&self, key: &Uuid) -> AccessStats {
+        self.shard(shard_of(key))
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn remove(&self, key: &Uuid) {
+        self.shard(shard_of(key)).remove(key);
+    }
+
+    pub(crate) fn clear(&self) {
+        (0..ACCESS_SHARDS).for_each(|shard| self.shard(shard).clear());
+    }
+
+    fn shard(&self, shard: usize) -> MutexGuard<'_, HashMap<Uuid, AccessStats>> {
+        self.shards[shard]
+            .lock()
+            .expect("compteurs d'accès empoisonnés")
+    }
+}
+
+fn shard_of(key: &Uuid) -> usize {
+    key.as_u128() as usize % ACCESS_SHARDS
+}
+
+impl Collection {
+    /// Active ou désactive le suivi des accès : chaque recherche de la collection
+    /// compte alors un accès pour chacun des documents qu'elle retourne (voir
+    /// [`Collection::access_stats`]).
+    ///
+    /// Seuls les résultats retournés sont comptés, après la limite à `k`, et non tous
+    /// les documents évalués : les compteurs sont mis à jour une fois la recherche
+    /// terminée, hors de la boucle de score. Sans suivi, une recherche ne fait rien de
+    /// plus. Désactiver le suivi conserve les compteurs déjà relevés.
+    ///
+    /// # Arguments
+    /// * `track` - Vrai pour compter les accès.
+    pub fn with_access_tracking(mut self, track: bool) -> Self {
+        self.config.access_tracking = track;
+        self
+    }
+
+    /// Indique si les accès des recherches sont comptés.
+    pub fn access_tracking(&self) -> bool {
+        self.config.access_tracking
+    }
+
+    /// Retourne les accès du document `key` par les recherches.
+    ///
+    /// # Retourne
+    /// * Option<AccessStats> - Les accès du document, nuls s'il n'a jamais été retourné
+    ///   ou si le suivi est inactif ; `None` s'il n'existe pas.
+    pub fn access_stats(&self, key: &Uuid) -> Option<AccessStats> {
+        self.documents
+            .contains_key(key)
+            .then(|| self.access.get(key))
+    }
+
+    /// Retourne les `n` documents les moins souvent retournés par les recherches, par
+    /// exemple pour choisir ceux à archiver.
+    ///
+    /// Les documents sont classés par nombre d'accès croissant, puis par date du dernier
+    /// accès, les documents jamais retournés en premier, puis par `Uuid`.
+    ///
+    /// # Arguments
+    /// * `n` - Nombre maximal de documents à retourner.
+    pub fn least_accessed(&self, n: usize) -> Vec<(Uuid, AccessStats)> {
+        let mut documents: Vec<(Uuid, AccessStats)> = (self.documents.keys())
+            .map(|key| (*key, self.access.get(key)))
+            .collect();
+        documents.sort_unstable_by(|(a, a_stats), (b, b_stats)| {
+            (a_stats.count, a_stats.last_accessed, a).cmp(&(b_stats.count, b_stats.last_accessed, b))
+        });
+        documents.truncate(n);
+        documents
+    }
+
+    /// Écrit en CSV les accès de chaque document : en-tête `id,count,last_accessed`,
+    /// puis une ligne par document, par `Uuid` croissant. La date du dernier accès est
+    /// en millisecondes depuis l'époque Unix, vide pour un document jamais retourné.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn write_access_csv(&self, mut writer: impl Write) -> Result<()> {
+        let mut keys: Vec<&Uuid> = self.documents.keys().collect();
+        keys.sort_unstable();
+        writeln!(writer, "id,count,last_accessed")?;
+        for key in keys {
+            let stats = self.access.get(key);
+            let last = stats.last_accessed.map(|at| {
+                let since = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+                since.as_millis().to_string()
+            });
+            writeln!(
+                writer,
+                "{},{},{}",
+                key,
+                stats.count,
+                last.unwrap_or_default()
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Compte un accès pour chaque document de `hits`, si le suivi est actif.
+    pub(crate) fn record_access(&self, hits: &Document) {
+        if self.config.access_tracking {
+            self.access.record(hits, SystemTime::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+    use crate::database::BaseDeDonnees;
+
+    /// Collection de dix documents `[1, i]` dont le suivi des accès est actif.
+    fn tracked() -> (Collection, Vec<Uuid>) {
+        let config = CollectionConfig::builder()
+            .access_tracking(true)
+            .build()
+            .unwrap();
+        let mut collection = Collection::from_config(config);
+        let keys: Vec<Uuid> = (1..=10).map(Uuid::from_u128).collect();
+        for (i, key) in keys.iter().enumerate() {
+            collection.upsert(*key, [1.0, i as f32]).unwrap();
+        }
+        (collection, keys)
+    }
+
+    #[test]
+    fn only_returned_hits_are_counted() {
+        let (collection, keys) = tracked();
+        assert!(collection.access_tracking());
+        let hits = collection.search([1.0, 9.0], 2).unwrap();
+        collection.search([1.0, 9.0], 1).unwrap();
+        let returned: Vec<Uuid> = hits.iter().map(|(key, _)| *key).collect();
+        assert_eq!(returned, [keys[9], keys[8]]);
+        assert_eq!(collection.access_stats(&keys[9]).unwrap().count, 2);
+        assert_eq!(collection.access_stats(&keys[8]).unwrap().count, 1);
+        // Les huit autres documents ont été évalués sans être retournés.
+        for key in &keys[..8] {
+            assert_eq!(collection.access_stats(key), Some(AccessStats::default()));
+        }
+        assert_eq!(collection.access_stats(&Uuid::from_u128(99)), None);
+
+        let least = collection.least_accessed(9);
+        assert_eq!(least[..8].iter().map(|(key, _)| *key).collect::<Vec<_>>(), keys[..8]);
+        assert_eq!(least[8].0, keys[8]);
+    }
+
+    #[test]
+    fn disabled_tracking_counts_nothing() {
+        let (collection, keys) = tracked();
+        let mut collection = collection.with_access_tracking(false);
+        collection.search([1.0, 0.0], 3).unwrap();
+        assert!(keys.iter().all(|key| collection.access_stats(key).unwrap().count == 0));
+
+        // Les compteurs d'un document supprimé puis réinséré repartent de zéro.
+        collection = collection.with_access_tracking(true);
+        collection.search([1.0, 0.0], 1).unwrap();
+        collection.delete(&keys[0]);
+        collection.upsert(keys[0], [1.0, 0.0]).unwrap();
+        assert_eq!(collection.access_stats(&keys[0]).unwrap().count, 0);
+    }
+
+    #[test]
+    fn counters_are_exported_but_not_saved() {
+        let (collection, keys) = tracked();
+        collection.search([1.0, 0.0], 1).unwrap();
+        let mut csv = Vec::new();
+        collection.write_access_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!((lines.len(), lines[0]), (11, "id,count,last_accessed"));
+        assert!(lines[1].starts_with(&format!("{},1,", keys[0])));
+        assert!(!lines[1].ends_with(','));
+        assert_eq!(lines[2], format!("{},0,", keys[1]));
+
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&bdd.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap();
+        assert!(loaded.access_tracking());
+        assert_eq!(loaded.access_stats(&keys[0]).unwrap().count, 0);
+    }
+}
@@ -0,0 +1,190 @@
+use std::borrow::Cow;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::projection::Projection;
+
+/// Projection linéaire appliquée aux requêtes d'une autre dimension que celle de la
+/// collection, par exemple pendant la migration vers un nouveau modèle d'embeddings
+/// (voir [`Collection::set_query_adapter`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryAdapter {
+    projection: Projection,
+}
+
+impl QueryAdapter {
+    /// Dimension des requêtes à adapter.
+    pub fn from_dim(&self) -> usize {
+        self.projection.input_dim()
+    }
+
+    /// Dimension des requêtes après adaptation, égale à celle de la collection.
+    pub fn to_dim(&self) -> usize {
+        self.projection.output_dim()
+    }
+
+    /// Coefficients de la matrice `to_dim × from_dim`, ligne par ligne.
+    pub fn matrix(&self) -> &[f32] {
+        self.projection.matrix()
+    }
+
+    pub(crate) fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    pub(crate) fn from_projection(projection: Projection) -> Self {
+        QueryAdapter { projection }
+    }
+}
+
+impl Collection {
+    /// Attache un adaptateur qui projette les requêtes de dimension `from_dim` dans la
+    /// dimension de la collection, en remplaçant le précédent.
+    ///
+    /// Les requêtes de la dimension de la collection ne passent pas par l'adaptateur ;
+    /// les autres sont projetées puis traitées comme des requêtes natives, y compris par
+    /// l'éventuelle projection de la collection. Les vecteurs insérés ne sont jamais
+    /// adaptés. L'adaptateur est sauvegardé avec la collection ; [`Collection::clear`]
+    /// le retire lorsqu'il oublie la dimension.
+    ///
+    /// # Arguments
+    /// * `matrix` - Coefficients `dimension × from_dim`, ligne par ligne, où `dimension`
+    ///   est celle de la collection ([`Collection::dimension`]).
+    /// * `from_dim` - Dimension des requêtes à adapter.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si la collection n'a pas encore de dimension, si
+    ///   `from_dim` est nul ou égal à cette dimension, ou si `matrix` ne compte pas
+    ///   `dimension × from_dim` coefficients ; l'adaptateur précédent est alors conservé.
+    pub fn set_query_adapter(&mut self, matrix: Vec<f32>, from_dim: usize) -> Result<()> {
+        let dimension = self.dimension().ok_or_else(|| {
+            Error::InvalidConfig(
+                "la dimension de la collection doit être connue avant d'adapter les requêtes"
+                    .to_string(),
+            )
+        })?;
+        if from_dim == 0 || from_dim == dimension {
+            return Err(Error::InvalidConfig(format!(
+                "un adaptateur de requêtes ne peut partir de la dimension {}",
+                from_dim
+            )));
+        }
+        if dimension.checked_mul(from_dim) != Some(matrix.len()) {
+            return Err(Error::InvalidConfig(format!(
+                "la matrice de l'adaptateur compte {} coefficients au lieu de {} × {}",
+                matrix.len(),
+                dimension,
+                from_dim
+            )));
+        }
+        self.adapter = Some(QueryAdapter {
+            projection: Projection::from_matrix(from_dim, dimension, matrix)?,
+        });
+        Ok(())
+    }
+
+    /// Retire l'adaptateur de requêtes de la collection, s'il y en a un.
+    pub fn remove_query_adapter(&mut self) -> Option<QueryAdapter> {
+        self.adapter.take()
+    }
+
+    /// Retourne l'adaptateur de requêtes de la collection, s'il y en a un.
+    pub fn query_adapter(&self) -> Option<&QueryAdapter> {
+        self.adapter.as_ref()
+    }
+
+    /// Projette `request` par l'adaptateur si elle a sa dimension d'entrée, qui n'est
+    /// jamais celle de la collection.
+    pub(crate) fn adapt_query<'a>(&self, request: &'a [f32]) -> Result<Cow<'a, [f32]>> {
+        match &self.adapter {
+            Some(adapter) if request.len() == adapter.from_dim() => {
+                Ok(Cow::Owned(adapter.projection.apply(request)?))
+            }
+            _ => Ok(Cow::Borrowed(request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::similarity::Metric;
+    use crate::synthetic::VectorGenerator;
+    use uuid::Uuid;
+
+    const OLD: usize = 6;
+    const NEW: usize = 4;
+
+    /// Applique la matrice `NEW × OLD` rangée ligne par ligne.
+    fn project(matrix: &[f32], vector: &[f32]) -> Vec<f32> {
+        (matrix.chunks_exact(OLD))
+            .map(|row| row.iter().zip(vector).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+
+    #[test]
+    fn adapted_queries_find_the_projected_documents() {
+        let mut generator = VectorGenerator::new(OLD, 161);
+        let matrix: Vec<f32> = generator.vectors(NEW).concat();
+        let old: Vec<Vec<f32>> = generator.vectors(200);
+        let mut collection = Collection::new().with_metric(Metric::Euclidean);
+        let keys: Vec<Uuid> = (0..old.len()).map(|_| generator.uuid()).collect();
+        for (key, vector) in keys.iter().zip(&old) {
+            collection.upsert(*key, project(&matrix, vector)).unwrap();
+        }
+        collection.set_query_adapter(matrix.clone(), OLD).unwrap();
+
+        for (key, vector) in keys.iter().zip(&old).take(20) {
+            let adapted = collection.search(vector, 5).unwrap();
+            assert_eq!(adapted[0].0, *key);
+            // Même résultat qu'une requête native déjà projetée, qui contourne l'adaptateur.
+            assert_eq!(adapted, collection.search(project(&matrix, vector), 5).unwrap());
+        }
+        assert!(matches!(
+            collection.search([1.0; 5], 1),
+            Err(Error::DimensionMismatch { expected: NEW, got: 5 })
+        ));
+    }
+
+    #[test]
+    fn adapters_must_end_in_the_collection_dimension() {
+        let mut collection = Collection::new();
+        assert!(matches!(
+            collection.set_query_adapter(vec![1.0; NEW * OLD], OLD),
+            Err(Error::InvalidConfig(_))
+        ));
+        collection.upsert(Uuid::new_v4(), [1.0; NEW]).unwrap();
+        for (matrix, from_dim) in [
+            (vec![1.0; (NEW + 1) * OLD], OLD),
+            (vec![1.0; NEW * NEW], NEW),
+            (Vec::new(), 0),
+        ] {
+            assert!(matches!(
+                collection.set_query_adapter(matrix, from_dim),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        assert!(collection.query_adapter().is_none());
+    }
+
+    #[test]
+    fn the_adapter_is_saved_and_dropped_by_an_unlocking_clear() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let docs = db.get_mut("docs").unwrap();
+        docs.upsert(Uuid::new_v4(), [1.0; NEW]).unwrap();
+        let matrix: Vec<f32> = (0..NEW * OLD).map(|i| i as f32 / 10.0).collect();
+        docs.set_query_adapter(matrix.clone(), OLD).unwrap();
+
+        let mut loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let docs = loaded.get_mut("docs").unwrap();
+        let adapter = docs.query_adapter().unwrap();
+        assert_eq!((adapter.from_dim(), adapter.to_dim()), (OLD, NEW));
+        assert_eq!(adapter.matrix(), &matrix[..]);
+        docs.clear(false);
+        assert!(docs.query_adapter().is_some());
+        docs.clear(true);
+        assert!(docs.query_adapter().is_none());
+    }
+}
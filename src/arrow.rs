@@ -0,0 +1,1066 @@
+//! Import et export de documents au format de flux IPC d'Apache Arrow.
+//!
+//! Un flux IPC est une suite de messages : un schéma, puis des lots de lignes
+//! (`RecordBatch`) dont les colonnes sont des tampons contigus. Chaque message porte ses
+//! métadonnées sous forme de tampon FlatBuffers, suivies de son corps ; ce module lit et
+//! écrit directement ces deux couches, sans dépendance.
+//!
+//! Les documents sont décrits par deux colonnes, les autres étant ignorées à l'import :
+//! `id`, de type `FixedSizeBinary(16)` (un `Uuid`), `Utf8` ou entier signé (voir
+//! [`DocumentId`]), et `vector`, de type `FixedSizeList<Float32>`. Les tampons
+//! compressés, les flux gros-boutistes et les valeurs nulles dans ces deux colonnes ne
+//! sont pas pris en charge.
+
+use std::io::{self, Read, Write};
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::document_id::DocumentId;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::oplog::{self, OperationKind};
+use crate::progress::{BatchReport, ProgressSink, Tracker};
+
+/// Marqueur qui précède la taille des métadonnées de chaque message.
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+/// Version des métadonnées écrites (`MetadataVersion::V5`) ; `V4` est aussi acceptée.
+const METADATA_V5: i16 = 4;
+const METADATA_V4: i16 = 3;
+/// Taille au-delà de laquelle des métadonnées de message sont tenues pour corrompues.
+const MAX_METADATA_SIZE: usize = 1 << 24;
+
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_DICTIONARY_BATCH: u8 = 2;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+const TYPE_NULL: u8 = 1;
+const TYPE_INT: u8 = 2;
+const TYPE_FLOATING_POINT: u8 = 3;
+const TYPE_BINARY: u8 = 4;
+const TYPE_UTF8: u8 = 5;
+const TYPE_BOOL: u8 = 6;
+const TYPE_DECIMAL: u8 = 7;
+const TYPE_DATE: u8 = 8;
+const TYPE_TIME: u8 = 9;
+const TYPE_TIMESTAMP: u8 = 10;
+const TYPE_INTERVAL: u8 = 11;
+const TYPE_LIST: u8 = 12;
+const TYPE_STRUCT: u8 = 13;
+const TYPE_FIXED_SIZE_BINARY: u8 = 15;
+const TYPE_FIXED_SIZE_LIST: u8 = 16;
+const TYPE_MAP: u8 = 17;
+const TYPE_DURATION: u8 = 18;
+const TYPE_LARGE_BINARY: u8 = 19;
+const TYPE_LARGE_UTF8: u8 = 20;
+const TYPE_LARGE_LIST: u8 = 21;
+
+const PRECISION_SINGLE: i16 = 1;
+
+const ID_TYPES: &str = "FixedSizeBinary(16), Utf8 ou entier signé";
+const VECTOR_TYPES: &str = "FixedSizeList<Float32>";
+
+impl Collection {
+    /// Importe des documents depuis un flux IPC Arrow (format « streaming »), lot par lot.
+    ///
+    /// Le flux doit avoir une colonne `id` (`FixedSizeBinary(16)` pour un `Uuid`, `Utf8`
+    /// lu comme par [`Collection::import_jsonl`], ou entier signé de 32 ou 64 bits) et une
+    /// colonne `vector` de type `FixedSizeList<Float32>` ; les autres colonnes sont
+    /// ignorées. Les coordonnées de chaque lot sont décodées d'un seul bloc, puis copiées
+    /// une fois dans le vecteur stocké de chaque document. Comme pour
+    /// [`Collection::upsert_batch`], l'import s'arrête à la première ligne invalide, les
+    /// précédentes restant insérées.
+    ///
+    /// # Arguments
+    /// * `reader` - Flux à lire.
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<BatchReport> - Nombre de documents importés et indication d'annulation.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le flux ne peut pas être lu.
+    /// * `Error::ArrowSchema` - Si `id` ou `vector` manque ou n'a pas un type accepté.
+    /// * `Error::InvalidArrow` - Si le flux est mal formé, compressé, gros-boutiste, ou
+    ///   si `id` ou `vector` contient une valeur nulle ou non finie.
+    /// * Celles de [`Collection::upsert_id`], enveloppées dans `Error::WithContext` avec
+    ///   l'identifiant du document.
+    pub fn import_arrow_ipc(
+        &mut self,
+        reader: impl Read,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport> {
+        self.logged(
+            OperationKind::Import,
+            |collection| collection.import_messages(reader, progress),
+            oplog::applied,
+        )
+    }
+
+    /// Écrit tous les documents dans un flux IPC Arrow, par lots de `batch_size` lignes,
+    /// dans l'ordre de leurs clés.
+    ///
+    /// Le schéma est celui que lit [`Collection::import_arrow_ipc`] : `id` en
+    /// `FixedSizeBinary(16)` et `vector` en `FixedSizeList<Float32>` de la dimension de
+    /// la collection. Une collection vide donne un flux sans lot, de dimension 0.
+    ///
+    /// # Arguments
+    /// * `writer` - Flux où écrire.
+    /// * `batch_size` - Nombre maximal de lignes par lot, au moins 1.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `batch_size` vaut 0.
+    /// * `Error::DimensionMismatch` - Si les documents n'ont pas tous la même dimension.
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn export_arrow_ipc(&self, mut writer: impl Write, batch_size: usize) -> Result<()> {
+        if batch_size == 0 {
+            return Err(Error::InvalidConfig(
+                "un lot Arrow doit contenir au moins une ligne".to_string(),
+            ));
+        }
+        let mut keys: Vec<&Uuid> = self.documents.keys().collect();
+        keys.sort_unstable();
+        let dimension = keys.first().map_or(0, |key| self.documents[*key].len());
+        if let Some(vector) = (self.documents.values()).find(|vector| vector.len() != dimension) {
+            return Err(Error::DimensionMismatch {
+                expected: dimension,
+                got: vector.len(),
+            });
+        }
+
+        write_message(&mut writer, &schema_message(dimension), &[])?;
+        for chunk in keys.chunks(batch_size) {
+            let ids = chunk.len() * 16;
+            let mut body = Vec::with_capacity(ids + padded(chunk.len() * dimension * 4));
+            for key in chunk {
+                body.extend_from_slice(key.as_bytes());
+            }
+            for key in chunk {
+                for x in self.documents[*key].iter() {
+                    body.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+            body.resize(padded(body.len()), 0);
+            let metadata = batch_message(chunk.len(), dimension, body.len());
+            write_message(&mut writer, &metadata, &body)?;
+        }
+        writer.write_all(&CONTINUATION.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(writer.flush()?)
+    }
+
+    fn import_messages(
+        &mut self,
+        reader: impl Read,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport> {
+        let mut tracker = Tracker::new(progress, None);
+        let mut messages = MessageReader { reader };
+        let mut layout = None;
+        while let Some((metadata, body)) = messages.next()? {
+            let message = Message::parse(&metadata)?;
+            match (message.header_type, &layout) {
+                (HEADER_SCHEMA, None) => layout = Some(Layout::of(&message.header)?),
+                (HEADER_SCHEMA, Some(_)) => return Err(invalid("schéma répété")),
+                (_, None) => return Err(invalid("le flux ne commence pas par un schéma")),
+                (HEADER_DICTIONARY_BATCH, Some(_)) => {}
+                (HEADER_RECORD_BATCH, Some(layout)) => {
+                    let batch = layout.decode(&message.header, &body)?;
+                    for row in 0..batch.rows {
+                        if tracker.cancelled() {
+                            return Ok(tracker.finish(true));
+                        }
+                        let id = batch.id(row)?;
+                        let context = ErrorContext::new(Operation::Import).key(id.to_uuid());
+                        let stored = self
+                            .upsert_id(id, batch.vector(row))
+                            .map_err(|e| e.with_context(context))?;
+                        tracker.record(stored);
+                    }
+                }
+                (other, Some(_)) => {
+                    return Err(invalid(format!(
+                        "type de message {} non pris en charge",
+                        other
+                    )))
+                }
+            }
+        }
+        if layout.is_none() {
+            return Err(invalid("flux vide"));
+        }
+        Ok(tracker.finish(false))
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidArrow(message.into())
+}
+
+/// Arrondit `len` au multiple de 8 supérieur, l'alignement des tampons du format.
+fn padded(len: usize) -> usize {
+    len.div_ceil(8) * 8
+}
+
+// --- Messages encapsulés ---
+
+/// Lit les messages d'un flux : marqueur de continuation facultatif (les flux antérieurs
+/// à la version 0.15 d'Arrow n'en ont pas), taille des métadonnées, métadonnées, corps.
+struct MessageReader<R> {
+    reader: R,
+}
+
+impl<R: Read> MessageReader<R> {
+    /// Retourne les métadonnées et le corps du message suivant, ou `None` à la fin du flux.
+    fn next(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let Some(mut size) = self.word()? else {
+            return Ok(None);
+        };
+        if size == CONTINUATION {
+            size = self.word()?.ok_or_else(|| invalid("flux tronqué"))?;
+        }
+        let size = size as usize;
+        if size == 0 {
+            return Ok(None);
+        }
+        if size > MAX_METADATA_SIZE {
+            return Err(invalid(format!("métadonnées de {} octets", size)));
+        }
+        let mut metadata = vec![0; size];
+        self.exact(&mut metadata)?;
+        let body_length = Message::parse(&metadata)?.body_length;
+        let mut body = Vec::new();
+        (&mut self.reader)
+            .take(body_length as u64)
+            .read_to_end(&mut body)?;
+        if body.len() != body_length {
+            return Err(invalid("flux tronqué"));
+        }
+        Ok(Some((metadata, body)))
+    }
+
+    /// Lit un mot de 32 bits, ou `None` si le flux se termine avant son premier octet.
+    fn word(&mut self) -> Result<Option<u32>> {
+        let mut bytes = [0; 4];
+        let mut filled = 0;
+        while filled < bytes.len() {
+            match self.reader.read(&mut bytes[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(invalid("flux tronqué")),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Some(u32::from_le_bytes(bytes)))
+    }
+
+    fn exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.reader.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("flux tronqué"),
+            _ => e.into(),
+        })
+    }
+}
+
+fn write_message(writer: &mut impl Write, metadata: &[u8], body: &[u8]) -> Result<()> {
+    // Les métadonnées sont complétées pour que le corps commence à un multiple de 8.
+    let size = padded(metadata.len());
+    writer.write_all(&CONTINUATION.to_le_bytes())?;
+    writer.write_all(&(size as u32).to_le_bytes())?;
+    writer.write_all(metadata)?;
+    writer.write_all(&vec![0; size - metadata.len()])?;
+    writer.write_all(body)?;
+    Ok(())
+}
+
+/// En-tête d'un message lu.
+struct Message<'a> {
+    header_type: u8,
+    header: Table<'a>,
+    body_length: usize,
+}
+
+impl<'a> Message<'a> {
+    fn parse(metadata: &'a [u8]) -> Result<Self> {
+        let message = Table::root(metadata)?;
+        let version = message.i16(0, 0)?;
+        if version < METADATA_V4 {
+            return Err(invalid(format!(
+                "version de métadonnées {} non prise en charge",
+                version
+            )));
+        }
+        let body_length = message.i64(3, 0)?;
+        Ok(Message {
+            header_type: message.u8(1, 0)?,
+            header: message
+                .table(2)?
+                .ok_or_else(|| invalid("message sans en-tête"))?,
+            body_length: usize::try_from(body_length)
+                .map_err(|_| invalid(format!("corps de {} octets", body_length)))?,
+        })
+    }
+}
+
+// --- Schéma et lots ---
+
+/// Colonne décrite par un schéma.
+struct Column {
+    name: String,
+    type_code: u8,
+    /// `byteWidth`, `listSize`, `bitWidth` ou précision selon le type, sinon 0.
+    param: i32,
+    signed: bool,
+    dictionary: bool,
+    children: Vec<Column>,
+}
+
+impl Column {
+    fn parse(field: Table<'_>) -> Result<Self> {
+        let type_code = field.u8(2, 0)?;
+        let params = field.table(3)?;
+        let (param, signed) = match (type_code, params) {
+            (TYPE_INT, Some(int)) => (int.i32(0, 0)?, int.bool(1)?),
+            (TYPE_FLOATING_POINT, Some(float)) => (float.i16(0, 0)? as i32, false),
+            (TYPE_FIXED_SIZE_BINARY | TYPE_FIXED_SIZE_LIST, Some(fixed)) => {
+                (fixed.i32(0, 0)?, false)
+            }
+            _ => (0, false),
+        };
+        let children = match field.vector(5, 4)? {
+            Some((start, count)) => (0..count)
+                .map(|i| Table::indirect(field.buf, start + 4 * i).and_then(Column::parse))
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        Ok(Column {
+            name: field.string(0)?.unwrap_or_default().to_string(),
+            type_code,
+            param,
+            signed,
+            dictionary: field.table(4)?.is_some(),
+            children,
+        })
+    }
+
+    fn describe(&self) -> String {
+        let item = || {
+            self.children
+                .first()
+                .map_or("?".to_string(), Column::describe)
+        };
+        let name = match self.type_code {
+            TYPE_NULL => "Null".to_string(),
+            TYPE_INT => format!("{}Int{}", if self.signed { "" } else { "U" }, self.param),
+            TYPE_FLOATING_POINT => format!("Float{}", 16 << self.param),
+            TYPE_BINARY => "Binary".to_string(),
+            TYPE_UTF8 => "Utf8".to_string(),
+            TYPE_BOOL => "Bool".to_string(),
+            TYPE_LIST => format!("List<{}>", item()),
+            TYPE_STRUCT => "Struct".to_string(),
+            TYPE_FIXED_SIZE_BINARY => format!("FixedSizeBinary({})", self.param),
+            TYPE_FIXED_SIZE_LIST => format!("FixedSizeList<{}>({})", item(), self.param),
+            TYPE_LARGE_BINARY => "LargeBinary".to_string(),
+            TYPE_LARGE_UTF8 => "LargeUtf8".to_string(),
+            TYPE_LARGE_LIST => format!("LargeList<{}>", item()),
+            code => format!("type Arrow n° {}", code),
+        };
+        match self.dictionary {
+            true => format!("Dictionary<{}>", name),
+            false => name,
+        }
+    }
+
+    fn schema_error(&self, expected: &str) -> Error {
+        Error::ArrowSchema {
+            column: self.name.clone(),
+            expected: expected.to_string(),
+            found: self.describe(),
+        }
+    }
+
+    /// Nombre de nœuds et de tampons qu'occupe la colonne dans un lot, enfants compris.
+    fn footprint(&self) -> Result<(usize, usize)> {
+        let own_buffers = match self.type_code {
+            _ if self.dictionary => 2,
+            TYPE_NULL => 0,
+            TYPE_INT
+            | TYPE_FLOATING_POINT
+            | TYPE_BOOL
+            | TYPE_DECIMAL
+            | TYPE_DATE
+            | TYPE_TIME
+            | TYPE_TIMESTAMP
+            | TYPE_INTERVAL
+            | TYPE_FIXED_SIZE_BINARY
+            | TYPE_DURATION => 2,
+            TYPE_BINARY | TYPE_UTF8 | TYPE_LARGE_BINARY | TYPE_LARGE_UTF8 => 3,
+            TYPE_LIST | TYPE_LARGE_LIST | TYPE_MAP => 2,
+            TYPE_STRUCT | TYPE_FIXED_SIZE_LIST => 1,
+            _ => return Err(self.schema_error("une colonne ignorée d'un type pris en charge")),
+        };
+        let mut footprint = (1, own_buffers);
+        if !self.dictionary {
+            for child in &self.children {
+                let (nodes, buffers) = child.footprint()?;
+                footprint.0 += nodes;
+                footprint.1 += buffers;
+            }
+        }
+        Ok(footprint)
+    }
+}
+
+/// Forme de la colonne `id`.
+#[derive(Clone, Copy)]
+enum IdKind {
+    Uuid,
+    Utf8,
+    Int(usize),
+}
+
+/// Position des colonnes `id` et `vector` dans les lots d'un schéma.
+struct Layout {
+    id: IdKind,
+    dimension: usize,
+    /// Index du nœud puis du premier tampon de `id`.
+    id_at: (usize, usize),
+    /// Index du nœud puis du premier tampon de `vector`.
+    vector_at: (usize, usize),
+}
+
+impl Layout {
+    fn of(schema: &Table<'_>) -> Result<Self> {
+        if schema.i16(0, 0)? != 0 {
+            return Err(invalid("ordre des octets gros-boutiste"));
+        }
+        let columns: Vec<Column> = match schema.vector(1, 4)? {
+            Some((start, count)) => (0..count)
+                .map(|i| Table::indirect(schema.buf, start + 4 * i).and_then(Column::parse))
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        let missing = |name: &str, expected: &str| Error::ArrowSchema {
+            column: name.to_string(),
+            expected: expected.to_string(),
+            found: "aucune colonne".to_string(),
+        };
+
+        let mut id = None;
+        let mut vector = None;
+        let mut position = (0, 0);
+        for column in &columns {
+            match column.name.as_str() {
+                "id" if id.is_none() => id = Some((column, position)),
+                "vector" if vector.is_none() => vector = Some((column, position)),
+                _ => {}
+            }
+            let (nodes, buffers) = column.footprint()?;
+            position = (position.0 + nodes, position.1 + buffers);
+        }
+        let (id, id_at) = id.ok_or_else(|| missing("id", ID_TYPES))?;
+        let (vector, vector_at) = vector.ok_or_else(|| missing("vector", VECTOR_TYPES))?;
+
+        let id_kind = match (id.type_code, id.param) {
+            _ if id.dictionary => return Err(id.schema_error(ID_TYPES)),
+            (TYPE_FIXED_SIZE_BINARY, 16) => IdKind::Uuid,
+            (TYPE_UTF8, _) => IdKind::Utf8,
+            (TYPE_INT, 32 | 64) if id.signed => IdKind::Int(id.param as usize / 8),
+            _ => return Err(id.schema_error(ID_TYPES)),
+        };
+        let single = |item: &Column| {
+            item.type_code == TYPE_FLOATING_POINT
+                && item.param == PRECISION_SINGLE as i32
+                && !item.dictionary
+        };
+        match (vector.type_code, vector.children.as_slice()) {
+            (TYPE_FIXED_SIZE_LIST, [item])
+                if !vector.dictionary && vector.param >= 0 && single(item) => {}
+            _ => return Err(vector.schema_error(VECTOR_TYPES)),
+        }
+        Ok(Layout {
+            id: id_kind,
+            dimension: vector.param as usize,
+            id_at,
+            vector_at,
+        })
+    }
+
+    /// Repère les colonnes `id` et `vector` dans le corps d'un lot et décode ses
+    /// coordonnées.
+    fn decode<'a>(&self, header: &Table<'_>, body: &'a [u8]) -> Result<Batch<'a>> {
+        if header.table(3)?.is_some() {
+            return Err(invalid("tampons compressés"));
+        }
+        let rows = header.i64(0, 0)?;
+        let rows = usize::try_from(rows).map_err(|_| invalid(format!("{} lignes", rows)))?;
+        let nodes = header.structs(1)?;
+        let buffers = header.structs(2)?;
+        let node = |index: usize, name: &str, expected: usize| -> Result<()> {
+            let [length, nulls] = *nodes
+                .get(index)
+                .ok_or_else(|| invalid("nœud de colonne manquant"))?;
+            if nulls != 0 {
+                return Err(invalid(format!(
+                    "la colonne « {} » contient {} valeurs nulles",
+                    name, nulls
+                )));
+            }
+            if length != expected as i64 {
+                return Err(invalid(format!(
+                    "la colonne « {} » a {} valeurs au lieu de {}",
+                    name, length, expected
+                )));
+            }
+            Ok(())
+        };
+        let buffer = |index: usize, min_len: usize| -> Result<&'a [u8]> {
+            let [offset, length] = *buffers
+                .get(index)
+                .ok_or_else(|| invalid("tampon de colonne manquant"))?;
+            let slice = usize::try_from(offset)
+                .ok()
+                .zip(usize::try_from(length).ok())
+                .and_then(|(offset, length)| body.get(offset..offset.checked_add(length)?))
+                .ok_or_else(|| invalid("tampon hors du corps du message"))?;
+            if slice.len() < min_len {
+                return Err(invalid("tampon trop court"));
+            }
+            Ok(slice)
+        };
+
+        node(self.id_at.0, "id", rows)?;
+        let (ids, offsets) = match self.id {
+            IdKind::Uuid => (buffer(self.id_at.1 + 1, rows * 16)?, &[][..]),
+            IdKind::Int(width) => (buffer(self.id_at.1 + 1, rows * width)?, &[][..]),
+            IdKind::Utf8 => (
+                buffer(self.id_at.1 + 2, 0)?,
+                buffer(self.id_at.1 + 1, (rows + 1) * 4)?,
+            ),
+        };
+        let coordinates = rows
+            .checked_mul(self.dimension)
+            .ok_or_else(|| invalid(format!("{} lignes", rows)))?;
+        node(self.vector_at.0, "vector", rows)?;
+        node(self.vector_at.0 + 1, "vector", coordinates)?;
+        let data = buffer(self.vector_at.1 + 2, coordinates * 4)?;
+        let values: Vec<f32> = data[..coordinates * 4]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        if let Some(index) = values.iter().position(|x| !x.is_finite()) {
+            return Err(invalid(format!(
+                "la ligne {} de « vector » contient une valeur non finie",
+                index / self.dimension
+            )));
+        }
+        Ok(Batch {
+            rows,
+            id: self.id,
+            ids,
+            offsets,
+            dimension: self.dimension,
+            values,
+        })
+    }
+}
+
+/// Colonnes `id` et `vector` d'un lot.
+struct Batch<'a> {
+    rows: usize,
+    id: IdKind,
+    ids: &'a [u8],
+    offsets: &'a [u8],
+    dimension: usize,
+    values: Vec<f32>,
+}
+
+impl Batch<'_> {
+    fn id(&self, row: usize) -> Result<DocumentId> {
+        match self.id {
+            IdKind::Uuid => Ok(DocumentId::Uuid(
+                Uuid::from_slice(&self.ids[row * 16..(row + 1) * 16])
+                    .map_err(|_| invalid("uuid"))?,
+            )),
+            IdKind::Int(width) => {
+                let bytes = &self.ids[row * width..(row + 1) * width];
+                Ok(DocumentId::Int(match width {
+                    4 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+                    _ => i64::from_le_bytes(bytes.try_into().unwrap()),
+                }))
+            }
+            IdKind::Utf8 => {
+                let offset = |i: usize| {
+                    let bytes = &self.offsets[i * 4..(i + 1) * 4];
+                    usize::try_from(i32::from_le_bytes(bytes.try_into().unwrap())).ok()
+                };
+                let id = offset(row)
+                    .zip(offset(row + 1))
+                    .and_then(|(start, end)| self.ids.get(start..end))
+                    .and_then(|bytes| std::str::from_utf8(bytes).ok())
+                    .ok_or_else(|| invalid(format!("identifiant de la ligne {} mal formé", row)))?;
+                Ok(match Uuid::parse_str(id) {
+                    Ok(uuid) => DocumentId::Uuid(uuid),
+                    Err(_) => DocumentId::Str(id.to_string()),
+                })
+            }
+        }
+    }
+
+    fn vector(&self, row: usize) -> &[f32] {
+        &self.values[row * self.dimension..(row + 1) * self.dimension]
+    }
+}
+
+// --- Lecture des tampons FlatBuffers ---
+
+/// Table FlatBuffers : position de la table dans `buf`, dont la table virtuelle donne
+/// la position de chaque champ présent.
+#[derive(Clone, Copy)]
+struct Table<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+fn bytes<const N: usize>(buf: &[u8], pos: usize) -> Result<[u8; N]> {
+    pos.checked_add(N)
+        .and_then(|end| buf.get(pos..end))
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or_else(|| invalid("métadonnées tronquées"))
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> Result<usize> {
+    Ok(u32::from_le_bytes(bytes(buf, pos)?) as usize)
+}
+
+impl<'a> Table<'a> {
+    fn root(buf: &'a [u8]) -> Result<Self> {
+        Table::indirect(buf, 0)
+    }
+
+    /// Suit le décalage non signé écrit à `pos`.
+    fn indirect(buf: &'a [u8], pos: usize) -> Result<Self> {
+        let pos = pos + u32_at(buf, pos)?;
+        bytes::<4>(buf, pos)?;
+        Ok(Table { buf, pos })
+    }
+
+    /// Position du champ `slot`, s'il est présent.
+    fn field(&self, slot: usize) -> Result<Option<usize>> {
+        let offset = i32::from_le_bytes(bytes(self.buf, self.pos)?) as i64;
+        let vtable = usize::try_from(self.pos as i64 - offset)
+            .map_err(|_| invalid("table virtuelle hors des métadonnées"))?;
+        let vtable_size = u16::from_le_bytes(bytes(self.buf, vtable)?) as usize;
+        let entry = 4 + 2 * slot;
+        if entry + 2 > vtable_size {
+            return Ok(None);
+        }
+        let field = u16::from_le_bytes(bytes(self.buf, vtable + entry)?) as usize;
+        Ok((field != 0).then_some(self.pos + field))
+    }
+
+    fn scalar<const N: usize>(&self, slot: usize) -> Result<Option<[u8; N]>> {
+        self.field(slot)?
+            .map(|pos| bytes(self.buf, pos))
+            .transpose()
+    }
+
+    fn u8(&self, slot: usize, default: u8) -> Result<u8> {
+        Ok(self.scalar::<1>(slot)?.map_or(default, |b| b[0]))
+    }
+
+    fn bool(&self, slot: usize) -> Result<bool> {
+        Ok(self.u8(slot, 0)? != 0)
+    }
+
+    fn i16(&self, slot: usize, default: i16) -> Result<i16> {
+        Ok(self.scalar(slot)?.map_or(default, i16::from_le_bytes))
+    }
+
+    fn i32(&self, slot: usize, default: i32) -> Result<i32> {
+        Ok(self.scalar(slot)?.map_or(default, i32::from_le_bytes))
+    }
+
+    fn i64(&self, slot: usize, default: i64) -> Result<i64> {
+        Ok(self.scalar(slot)?.map_or(default, i64::from_le_bytes))
+    }
+
+    fn table(&self, slot: usize) -> Result<Option<Table<'a>>> {
+        self.field(slot)?
+            .map(|pos| Table::indirect(self.buf, pos))
+            .transpose()
+    }
+
+    /// Position du premier élément et nombre d'éléments du vecteur `slot`, chaque
+    /// élément occupant `size` octets.
+    fn vector(&self, slot: usize, size: usize) -> Result<Option<(usize, usize)>> {
+        let Some(pos) = self.field(slot)? else {
+            return Ok(None);
+        };
+        let start = pos + u32_at(self.buf, pos)?;
+        let count = u32_at(self.buf, start)?;
+        let end = (start + 4).saturating_add(count.saturating_mul(size));
+        if end > self.buf.len() {
+            return Err(invalid("vecteur hors des métadonnées"));
+        }
+        Ok(Some((start + 4, count)))
+    }
+
+    fn string(&self, slot: usize) -> Result<Option<&'a str>> {
+        let Some((start, len)) = self.vector(slot, 1)? else {
+            return Ok(None);
+        };
+        std::str::from_utf8(&self.buf[start..start + len])
+            .map(Some)
+            .map_err(|_| invalid("chaîne mal formée"))
+    }
+
+    /// Vecteur de structures de deux entiers de 64 bits (`FieldNode` ou `Buffer`).
+    fn structs(&self, slot: usize) -> Result<Vec<[i64; 2]>> {
+        let Some((start, count)) = self.vector(slot, 16)? else {
+            return Ok(Vec::new());
+        };
+        (0..count)
+            .map(|i| {
+                let pos = start + 16 * i;
+                Ok([
+                    i64::from_le_bytes(bytes(self.buf, pos)?),
+                    i64::from_le_bytes(bytes(self.buf, pos + 8)?),
+                ])
+            })
+            .collect()
+    }
+}
+
+// --- Écriture des tampons FlatBuffers ---
+
+/// Valeur d'un champ de table écrit.
+#[derive(Clone, Copy)]
+enum Value {
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    /// Décalage vers un objet écrit plus loin, fixé par [`Builder::link`].
+    Offset,
+}
+
+impl Value {
+    fn size(self) -> usize {
+        match self {
+            Value::U8(_) => 1,
+            Value::I16(_) => 2,
+            Value::I32(_) | Value::Offset => 4,
+            Value::I64(_) => 8,
+        }
+    }
+}
+
+/// Colonne fille écrite par [`Builder::field`] : nom, nullabilité, type et paramètres du
+/// type.
+type ChildField<'a> = (&'a str, bool, u8, &'a [(usize, Value)]);
+
+/// Écrit un tampon FlatBuffers d'avant en arrière : chaque objet est écrit après celui
+/// qui le désigne, ce qui garde positifs les décalages vers les objets fils.
+struct Builder {
+    buf: Vec<u8>,
+}
+
+impl Builder {
+    /// Commence un tampon par le décalage de sa table racine, fixé par le premier
+    /// [`Builder::link`] vers la position 0.
+    fn new() -> Self {
+        Builder { buf: vec![0; 4] }
+    }
+
+    fn align(&mut self, align: usize, remainder: usize) {
+        while self.buf.len() % align != remainder {
+            self.buf.push(0);
+        }
+    }
+
+    /// Fait désigner l'objet `target` par le décalage écrit à `at`.
+    fn link(&mut self, at: usize, target: usize) {
+        let offset = (target - at) as u32;
+        self.buf[at..at + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    /// Écrit une table et retourne sa position et celles de ses champs `Value::Offset`,
+    /// dans l'ordre de `fields`.
+    fn table(&mut self, fields: &[(usize, Value)]) -> (usize, Vec<usize>) {
+        let slots = fields.iter().map(|(slot, _)| slot + 1).max().unwrap_or(0);
+        let mut order: Vec<usize> = (0..fields.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(fields[i].1.size()));
+        let mut offsets = vec![0; fields.len()];
+        let mut end: usize = 4;
+        for &i in &order {
+            let size = fields[i].1.size();
+            end = end.div_ceil(size) * size;
+            offsets[i] = end;
+            end += size;
+        }
+        let end = end.div_ceil(4) * 4;
+
+        self.align(2, 0);
+        let vtable = self.buf.len();
+        let mut entries = vec![0u16; slots];
+        for (i, (slot, _)) in fields.iter().enumerate() {
+            entries[*slot] = offsets[i] as u16;
+        }
+        self.buf
+            .extend_from_slice(&((4 + 2 * slots) as u16).to_le_bytes());
+        self.buf.extend_from_slice(&(end as u16).to_le_bytes());
+        for entry in entries {
+            self.buf.extend_from_slice(&entry.to_le_bytes());
+        }
+
+        self.align(8, 0);
+        let pos = self.buf.len();
+        self.buf.resize(pos + end, 0);
+        let soffset = (pos - vtable) as i32;
+        self.buf[pos..pos + 4].copy_from_slice(&soffset.to_le_bytes());
+        let mut links = Vec::new();
+        for (i, (_, value)) in fields.iter().enumerate() {
+            let at = pos + offsets[i];
+            let (bytes, len) = match *value {
+                Value::U8(v) => (u64::from(v).to_le_bytes(), 1),
+                Value::I16(v) => ((v as u16 as u64).to_le_bytes(), 2),
+                Value::I32(v) => ((v as u32 as u64).to_le_bytes(), 4),
+                Value::I64(v) => (v.to_le_bytes(), 8),
+                Value::Offset => {
+                    links.push(at);
+                    continue;
+                }
+            };
+            self.buf[at..at + len].copy_from_slice(&bytes[..len]);
+        }
+        (pos, links)
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        self.align(4, 0);
+        let pos = self.buf.len();
+        self.buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+        pos
+    }
+
+    /// Écrit un vecteur de `count` décalages et retourne sa position et celles des
+    /// décalages à fixer.
+    fn offsets(&mut self, count: usize) -> (usize, Vec<usize>) {
+        self.align(4, 0);
+        let pos = self.buf.len();
+        self.buf.extend_from_slice(&(count as u32).to_le_bytes());
+        self.buf.resize(pos + 4 + 4 * count, 0);
+        (pos, (0..count).map(|i| pos + 4 + 4 * i).collect())
+    }
+
+    /// Écrit un vecteur de structures de deux entiers de 64 bits, alignées sur 8 octets.
+    fn structs(&mut self, items: &[[i64; 2]]) -> usize {
+        self.align(8, 4);
+        let pos = self.buf.len();
+        self.buf
+            .extend_from_slice(&(items.len() as u32).to_le_bytes());
+        for item in items {
+            self.buf.extend_from_slice(&item[0].to_le_bytes());
+            self.buf.extend_from_slice(&item[1].to_le_bytes());
+        }
+        pos
+    }
+
+    /// Écrit une colonne de schéma, avec ses enfants.
+    fn field(
+        &mut self,
+        name: &str,
+        nullable: bool,
+        type_code: u8,
+        params: &[(usize, Value)],
+        children: &[ChildField<'_>],
+    ) -> usize {
+        let (pos, links) = self.table(&[
+            (0, Value::Offset),
+            (1, Value::U8(nullable as u8)),
+            (2, Value::U8(type_code)),
+            (3, Value::Offset),
+            (5, Value::Offset),
+        ]);
+        let name = self.string(name);
+        self.link(links[0], name);
+        let params = self.table(params).0;
+        self.link(links[1], params);
+        let (vector, elements) = self.offsets(children.len());
+        self.link(links[2], vector);
+        for ((name, nullable, type_code, params), at) in children.iter().zip(elements) {
+            let child = self.field(name, *nullable, *type_code, params, &[]);
+            self.link(at, child);
+        }
+        pos
+    }
+
+    /// Écrit la table `Message` racine et retourne la position de son décalage d'en-tête.
+    fn message(&mut self, header_type: u8, body_length: usize) -> usize {
+        let (pos, links) = self.table(&[
+            (0, Value::I16(METADATA_V5)),
+            (1, Value::U8(header_type)),
+            (2, Value::Offset),
+            (3, Value::I64(body_length as i64)),
+        ]);
+        self.link(0, pos);
+        links[0]
+    }
+}
+
+/// Métadonnées du schéma écrit par [`Collection::export_arrow_ipc`].
+fn schema_message(dimension: usize) -> Vec<u8> {
+    let mut builder = Builder::new();
+    let header = builder.message(HEADER_SCHEMA, 0);
+    let (schema, links) = builder.table(&[(0, Value::I16(0)), (1, Value::Offset)]);
+    builder.link(header, schema);
+    let (fields, elements) = builder.offsets(2);
+    builder.link(links[0], fields);
+    let id = builder.field(
+        "id",
+        false,
+        TYPE_FIXED_SIZE_BINARY,
+        &[(0, Value::I32(16))],
+        &[],
+    );
+    builder.link(elements[0], id);
+    let single = [(0, Value::I16(PRECISION_SINGLE))];
+    let vector = builder.field(
+        "vector",
+        false,
+        TYPE_FIXED_SIZE_LIST,
+        &[(0, Value::I32(dimension as i32))],
+        &[("item", true, TYPE_FLOATING_POINT, &single)],
+    );
+    builder.link(elements[1], vector);
+    builder.buf
+}
+
+/// Métadonnées d'un lot de `rows` lignes écrit par [`Collection::export_arrow_ipc`] :
+/// les identifiants au début du corps, puis les coordonnées ; les tampons de validité
+/// sont vides, aucune valeur n'étant nulle.
+fn batch_message(rows: usize, dimension: usize, body_length: usize) -> Vec<u8> {
+    let (rows, ids, coordinates) = (rows as i64, rows as i64 * 16, (rows * dimension) as i64);
+    let mut builder = Builder::new();
+    let header = builder.message(HEADER_RECORD_BATCH, body_length);
+    let (batch, links) = builder.table(&[
+        (0, Value::I64(rows)),
+        (1, Value::Offset),
+        (2, Value::Offset),
+    ]);
+    builder.link(header, batch);
+    let nodes = builder.structs(&[[rows, 0], [rows, 0], [coordinates, 0]]);
+    builder.link(links[0], nodes);
+    let buffers = builder.structs(&[[0, 0], [0, ids], [ids, 0], [ids, 0], [ids, coordinates * 4]]);
+    builder.link(links[1], buffers);
+    builder.buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::VectorGenerator;
+
+    /// Flux réduit à un schéma dont `vector` est une liste de `Float64` et, si `with_id`
+    /// est vrai, `id` un `Utf8`.
+    fn schema_stream(with_id: bool) -> Vec<u8> {
+        let mut builder = Builder::new();
+        let header = builder.message(HEADER_SCHEMA, 0);
+        let (schema, links) = builder.table(&[(0, Value::I16(0)), (1, Value::Offset)]);
+        builder.link(header, schema);
+        let (fields, elements) = builder.offsets(1 + usize::from(with_id));
+        builder.link(links[0], fields);
+        let double = [(0, Value::I16(2))];
+        let vector = builder.field(
+            "vector",
+            false,
+            TYPE_FIXED_SIZE_LIST,
+            &[(0, Value::I32(4))],
+            &[("item", true, TYPE_FLOATING_POINT, &double)],
+        );
+        builder.link(elements[0], vector);
+        if with_id {
+            let id = builder.field("id", false, TYPE_UTF8, &[], &[]);
+            builder.link(elements[1], id);
+        }
+        let mut stream = Vec::new();
+        write_message(&mut stream, &builder.buf, &[]).unwrap();
+        stream
+    }
+
+    #[test]
+    fn a_hundred_thousand_vectors_round_trip() {
+        let mut generator = VectorGenerator::new(8, 180);
+        let mut collection = Collection::new();
+        let items: Vec<(Uuid, Vec<f32>)> = (0..100_000)
+            .map(|_| (generator.uuid(), generator.vector()))
+            .collect();
+        collection.upsert_batch(items, None).unwrap();
+        let mut stream = Vec::new();
+        collection.export_arrow_ipc(&mut stream, 4096).unwrap();
+
+        let mut imported = Collection::new();
+        let report = imported.import_arrow_ipc(&stream[..], None).unwrap();
+        assert_eq!(report.applied, 100_000);
+        assert_eq!(imported.len(), collection.len());
+        for (key, vector) in collection.documents.iter() {
+            assert_eq!(imported.documents.get(key), Some(vector));
+        }
+        // Le flux réécrit est identique au premier.
+        let mut again = Vec::new();
+        imported.export_arrow_ipc(&mut again, 4096).unwrap();
+        assert!(again == stream);
+    }
+
+    #[test]
+    fn schema_errors_name_the_column_and_the_expected_type() {
+        let mut collection = Collection::new();
+        match collection.import_arrow_ipc(&schema_stream(true)[..], None) {
+            Err(Error::ArrowSchema {
+                column,
+                expected,
+                found,
+            }) => {
+                assert_eq!((column.as_str(), expected.as_str()), ("vector", VECTOR_TYPES));
+                assert_eq!(found, "FixedSizeList<Float64>(4)");
+            }
+            other => panic!("{:?}", other),
+        }
+        match collection.import_arrow_ipc(&schema_stream(false)[..], None) {
+            Err(Error::ArrowSchema {
+                column,
+                expected,
+                found,
+            }) => {
+                assert_eq!((column.as_str(), expected.as_str()), ("id", ID_TYPES));
+                assert_eq!(found, "aucune colonne");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(collection.is_empty());
+    }
+
+    #[test]
+    fn malformed_streams_and_empty_batches_are_refused() {
+        let mut collection = Collection::new();
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        let mut stream = Vec::new();
+        collection.export_arrow_ipc(&mut stream, 1).unwrap();
+        let mut truncated = Collection::new();
+        assert!(matches!(
+            truncated.import_arrow_ipc(&stream[..stream.len() - 12], None),
+            Err(Error::InvalidArrow(_))
+        ));
+        assert!(matches!(
+            truncated.import_arrow_ipc(&[][..], None),
+            Err(Error::InvalidArrow(_))
+        ));
+        assert!(matches!(
+            collection.export_arrow_ipc(Vec::new(), 0),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,528 @@
+//! Sauvegardes horodatées d'une base, une par répertoire, avec rotation et restauration.
+//!
+//! [`BaseDeDonnees::backup`] écrit chaque collection dans son propre fichier, au format
+//! de [`BaseDeDonnees::save`], puis un manifeste [`BACKUP_MANIFEST`] qui liste les
+//! collections, leurs fichiers, leur taille et leur empreinte, ainsi que les alias :
+//!
+//! ```text
+//! <répertoire>/2024-06-01T12-00-00/manifest.json
+//!                                  000.snap, 001.snap, ...
+//! ```
+//!
+//! La sauvegarde est construite dans un répertoire caché `.<nom>.tmp`, puis renommée
+//! une fois le manifeste écrit : un répertoire de sauvegarde visible est toujours
+//! complet. [`BaseDeDonnees::restore_backup`] vérifie la présence, la taille et
+//! l'empreinte de chaque fichier avant de modifier la base.
+//!
+//! Les fichiers passent par l'accès aux fichiers de la base
+//! ([`BaseDeDonnees::with_storage`]) ; la création, la liste et la suppression des
+//! répertoires utilisent directement le système de fichiers.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::payload::Value;
+use crate::storage;
+
+/// Nom du manifeste d'un répertoire de sauvegarde.
+pub const BACKUP_MANIFEST: &str = "manifest.json";
+
+/// Version du format des manifestes écrits par [`BaseDeDonnees::backup`].
+const MANIFEST_FORMAT: u64 = 1;
+
+/// Fichier d'une collection dans une sauvegarde.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupEntry {
+    /// Nom de la collection.
+    pub collection: String,
+    /// Nom du fichier, relatif au répertoire de la sauvegarde.
+    pub file: String,
+    /// Taille du fichier, en octets.
+    pub bytes: u64,
+    /// Empreinte FNV-1a 64 bits du fichier : elle détecte une corruption accidentelle,
+    /// pas une falsification.
+    pub checksum: u64,
+}
+
+/// Contenu du manifeste d'une sauvegarde.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifest {
+    /// Moment de la sauvegarde, en secondes depuis l'époque Unix.
+    pub created: u64,
+    /// Numéro de séquence de la base sauvegardée ([`BaseDeDonnees::sequence`]).
+    pub sequence: u64,
+    /// Collections sauvegardées, par nom croissant.
+    pub entries: Vec<BackupEntry>,
+    /// Alias de la base, avec le nom de la collection désignée.
+    pub aliases: Vec<(String, String)>,
+}
+
+/// Bilan de [`BaseDeDonnees::backup`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupReport {
+    /// Répertoire de la nouvelle sauvegarde.
+    pub path: PathBuf,
+    /// Manifeste écrit.
+    pub manifest: BackupManifest,
+    /// Sauvegardes supprimées par la rotation, de la plus ancienne à la plus récente.
+    pub pruned: Vec<PathBuf>,
+}
+
+impl fmt::Display for BackupReport {
+    /// Affiche la sauvegarde et les sauvegardes supprimées, une par ligne.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: u64 = self.manifest.entries.iter().map(|entry| entry.bytes).sum();
+        writeln!(
+            f,
+            "{} : {} collection(s), {} octets",
+            self.path.display(),
+            self.manifest.entries.len(),
+            bytes
+        )?;
+        for path in &self.pruned {
+            writeln!(f, "supprimée : {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Options de [`BaseDeDonnees::restore_backup`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RestoreOptions {
+    /// Collection à restaurer seule, avec les alias qui la désignent ; `None` restaure
+    /// toute la base.
+    pub collection: Option<String>,
+    /// Vrai pour remplacer des données existantes : une restauration complète dans une
+    /// base qui n'est pas vide, ou partielle d'une collection qui existe déjà, est
+    /// refusée sans cette option.
+    pub force: bool,
+}
+
+impl BaseDeDonnees {
+    /// Sauvegarde la base dans un nouveau répertoire horodaté de `dir`, puis ne garde
+    /// que les `keep` sauvegardes les plus récentes.
+    ///
+    /// Le répertoire porte la date UTC de la sauvegarde, `2024-06-01T12-00-00`, suivie
+    /// de `-1`, `-2`… pour les sauvegardes suivantes de la même seconde. `dir` est créé s'il n'existe
+    /// pas.
+    ///
+    /// # Arguments
+    /// * `dir` - Répertoire des sauvegardes.
+    /// * `keep` - Nombre de sauvegardes conservées, la nouvelle comprise ; `None` n'en
+    ///   supprime aucune, et 0 est traité comme 1.
+    ///
+    /// # Retourne
+    /// * Result<BackupReport> - La sauvegarde écrite et celles supprimées.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si un fichier ou un répertoire ne peut pas être écrit ou supprimé ;
+    ///   une sauvegarde interrompue ne laisse qu'un répertoire caché, jamais listé.
+    pub fn backup(&self, dir: impl AsRef<Path>, keep: Option<usize>) -> Result<BackupReport> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let stamp = timestamp(created);
+        // Un suffixe plus grand que tous ceux de la même seconde garde l'ordre
+        // chronologique des noms, même après la rotation des premiers.
+        let mut taken = None;
+        for entry in fs::read_dir(dir)? {
+            let (name, n) = sort_key(&entry?.path());
+            if name == stamp {
+                taken = taken.max(Some(n));
+            }
+        }
+        let name = match taken {
+            None => stamp,
+            Some(n) => format!("{}-{}", stamp, n + 1),
+        };
+        let staging = dir.join(format!(".{}.tmp", name));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir(&staging)?;
+
+        let mut entries = Vec::new();
+        for (i, nom) in self.collection_names().into_iter().enumerate() {
+            let bytes = self.collection_bytes(&nom);
+            let file = format!("{:03}.snap", i);
+            storage::replace(self.storage(), &staging.join(&file), &bytes)?;
+            entries.push(BackupEntry {
+                collection: nom,
+                file,
+                bytes: bytes.len() as u64,
+                checksum: checksum(&bytes),
+            });
+        }
+        let manifest = BackupManifest {
+            created,
+            sequence: self.sequence,
+            entries,
+            aliases: (self.aliases().into_iter())
+                .map(|(alias, target)| (alias.to_string(), target.to_string()))
+                .collect(),
+        };
+        storage::replace(
+            self.storage(),
+            &staging.join(BACKUP_MANIFEST),
+            manifest.to_json().as_bytes(),
+        )?;
+        let path = dir.join(&name);
+        self.storage().rename(&staging, &path)?;
+        let pruned = match keep {
+            Some(keep) => prune_backups(dir, keep)?,
+            None => Vec::new(),
+        };
+        Ok(BackupReport {
+            path,
+            manifest,
+            pruned,
+        })
+    }
+
+    /// Restaure une sauvegarde écrite par [`BaseDeDonnees::backup`], en entier ou pour
+    /// une seule collection.
+    ///
+    /// Tous les fichiers nécessaires sont lus et vérifiés contre le manifeste avant que
+    /// la base ne soit modifiée : une sauvegarde incomplète ou corrompue laisse la base
+    /// intacte. Une restauration complète remplace toutes les collections et tous les
+    /// alias ; une restauration partielle remplace la seule collection choisie et rétablit
+    /// les alias qui la désignent.
+    ///
+    /// # Arguments
+    /// * `path` - Répertoire de la sauvegarde, par exemple `/backups/2024-06-01T12-00-00`.
+    /// * `options` - Collection à restaurer et remplacement des données existantes.
+    ///
+    /// # Retourne
+    /// * Result<BackupManifest> - Le manifeste de la sauvegarde restaurée.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidSnapshot` - Si le manifeste est illisible, ou si un fichier est
+    ///   absent, n'a pas la taille ou l'empreinte annoncée, ou ne contient pas la
+    ///   collection attendue.
+    /// * `Error::CollectionNotFound` - Si la collection demandée n'est pas sauvegardée.
+    /// * `Error::InvalidConfig` - Si des données seraient remplacées sans `force`.
+    /// * Celles de [`BaseDeDonnees::from_bytes`].
+    pub fn restore_backup(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: &RestoreOptions,
+    ) -> Result<BackupManifest> {
+        let path = path.as_ref();
+        let manifest = BackupManifest::read_with(self.storage(), path)?;
+        let entries: Vec<&BackupEntry> = match &options.collection {
+            None => manifest.entries.iter().collect(),
+            Some(nom) => vec![(manifest.entries.iter())
+                .find(|entry| entry.collection == *nom)
+                .ok_or_else(|| Error::CollectionNotFound(nom.clone()))?],
+        };
+        if !options.force {
+            let existing = match &options.collection {
+                None => self.collection_names().first().cloned(),
+                Some(nom) => Some(nom.clone()).filter(|nom| self.store(nom).is_some()),
+            };
+            if let Some(nom) = existing {
+                return Err(Error::InvalidConfig(format!(
+                    "la collection '{}' existe déjà ; forcez la restauration pour la remplacer",
+                    nom
+                )));
+            }
+        }
+        let mut restored = Vec::with_capacity(entries.len());
+        for entry in entries {
+            restored.push((entry, self.read_entry(path, entry)?));
+        }
+
+        if options.collection.is_none() {
+            self.collections.clear();
+            self.sharded.clear();
+            self.aliases.clear();
+        }
+        for (entry, mut bdd) in restored {
+            let nom = entry.collection.clone();
+            self.aliases.retain(|alias, _| *alias != nom);
+            match (bdd.collections.remove(&nom), bdd.sharded.remove(&nom)) {
+                (Some(collection), _) => {
+                    self.replace(nom, Arc::unwrap_or_clone(collection));
+                }
+                (None, Some(collection)) => {
+                    self.replace_sharded(nom, Arc::unwrap_or_clone(collection));
+                }
+                (None, None) => unreachable!("collection vérifiée par read_entry"),
+            }
+        }
+        for (alias, target) in &manifest.aliases {
+            if options.collection.as_ref().is_none_or(|nom| nom == target) {
+                self.set_alias(alias.clone(), target)?;
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Sérialise la seule collection `nom`, au format de [`BaseDeDonnees::to_bytes`].
+    fn collection_bytes(&self, nom: &str) -> Vec<u8> {
+        let mut bdd = BaseDeDonnees::new();
+        if let Some(collection) = self.collections.get(nom) {
+            bdd.collections
+                .insert(nom.to_string(), Arc::clone(collection));
+        }
+        if let Some(collection) = self.sharded.get(nom) {
+            bdd.sharded.insert(nom.to_string(), Arc::clone(collection));
+        }
+        bdd.to_bytes()
+    }
+
+    /// Lit le fichier de `entry` et vérifie qu'il correspond au manifeste.
+    fn read_entry(&self, path: &Path, entry: &BackupEntry) -> Result<BaseDeDonnees> {
+        let invalid = |problem: &str| {
+            Error::InvalidSnapshot(format!(
+                "sauvegarde {} : le fichier {} de la collection '{}' {}",
+                path.display(),
+                entry.file,
+                entry.collection,
+                problem
+            ))
+        };
+        let bytes = match self.storage().read(&path.join(&entry.file)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(invalid("est absent"))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if bytes.len() as u64 != entry.bytes || checksum(&bytes) != entry.checksum {
+            return Err(invalid("ne correspond pas à son empreinte"));
+        }
+        let bdd = BaseDeDonnees::from_bytes(&bytes)?;
+        if bdd.collection_names() != [entry.collection.as_str()] {
+            return Err(invalid("ne contient pas cette seule collection"));
+        }
+        Ok(bdd)
+    }
+}
+
+impl BackupManifest {
+    /// Lit le manifeste du répertoire de sauvegarde `path`.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le manifeste ne peut pas être lu.
+    /// * `Error::InvalidSnapshot` - S'il n'a pas la structure attendue.
+    pub fn read(path: impl AsRef<Path>) -> Result<BackupManifest> {
+        Self::read_with(&storage::FileSystem, path.as_ref())
+    }
+
+    fn read_with(storage: &dyn storage::Storage, path: &Path) -> Result<BackupManifest> {
+        let bytes = storage.read(&path.join(BACKUP_MANIFEST))?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid("texte UTF-8 attendu"))?;
+        Self::from_json(&text)
+    }
+
+    /// Écrit le manifeste en JSON, une collection par ligne.
+    fn to_json(&self) -> String {
+        let mut out = String::from("{\"format\":");
+        json::write_u64(&mut out, MANIFEST_FORMAT);
+        out.push_str(",\"created\":");
+        json::write_u64(&mut out, self.created);
+        out.push_str(",\"sequence\":");
+        json::write_u64(&mut out, self.sequence);
+        out.push_str(",\"collections\":[");
+        for (i, entry) in self.entries.iter().enumerate() {
+            out.push_str(if i > 0 { ",\n" } else { "\n" });
+            out.push_str("{\"name\":");
+            json::write_string(&mut out, &entry.collection);
+            out.push_str(",\"file\":");
+            json::write_string(&mut out, &entry.file);
+            out.push_str(",\"bytes\":");
+            json::write_u64(&mut out, entry.bytes);
+            out.push_str(",\"checksum\":");
+            json::write_string(&mut out, &format!("{:016x}", entry.checksum));
+            out.push('}');
+        }
+        out.push_str("\n],\"aliases\":");
+        json::write_array(&mut out, &self.aliases, |out, (alias, target)| {
+            out.push('[');
+            json::write_string(out, alias);
+            out.push(',');
+            json::write_string(out, target);
+            out.push(']');
+        });
+        out.push_str("}\n");
+        out
+    }
+
+    fn from_json(text: &str) -> Result<BackupManifest> {
+        let value = json::parse(text).map_err(|message| invalid(&message))?;
+        let fields = object(&value)?;
+        if integer(fields, "format")? != MANIFEST_FORMAT {
+            return Err(invalid("format de manifeste inconnu"));
+        }
+        let entries = match fields.get("collections") {
+            Some(Value::Array(items)) => items.iter().map(entry).collect::<Result<_>>()?,
+            _ => return Err(invalid("« collections » manquant")),
+        };
+        let aliases = match fields.get("aliases") {
+            Some(Value::Array(items)) => items.iter().map(alias).collect::<Result<_>>()?,
+            _ => return Err(invalid("« aliases » manquant")),
+        };
+        Ok(BackupManifest {
+            created: integer(fields, "created")?,
+            sequence: integer(fields, "sequence")?,
+            entries,
+            aliases,
+        })
+    }
+}
+
+/// Liste les sauvegardes complètes de `dir`, de la plus ancienne à la plus récente.
+///
+/// Seuls les répertoires qui contiennent un manifeste sont retenus ; les noms horodatés
+/// se classent dans l'ordre chronologique.
+///
+/// # Erreurs
+/// * `Error::Io` - Si `dir` ne peut pas être lu.
+pub fn list_backups(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if !hidden && entry.path().join(BACKUP_MANIFEST).is_file() {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort_unstable_by_key(|path| sort_key(path));
+    Ok(backups)
+}
+
+/// Supprime les sauvegardes les plus anciennes de `dir` pour n'en garder que `keep`.
+///
+/// # Arguments
+/// * `dir` - Répertoire des sauvegardes.
+/// * `keep` - Nombre de sauvegardes conservées ; 0 est traité comme 1.
+///
+/// # Retourne
+/// * Result<Vec<PathBuf>> - Les sauvegardes supprimées, de la plus ancienne à la plus
+///   récente.
+///
+/// # Erreurs
+/// * `Error::Io` - Si une sauvegarde ne peut pas être supprimée ; les précédentes
+///   restent supprimées.
+pub fn prune_backups(dir: impl AsRef<Path>, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut backups = list_backups(dir)?;
+    let excess = backups.len().saturating_sub(keep.max(1));
+    backups.truncate(excess);
+    for path in &backups {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(backups)
+}
+
+/// Clé de tri d'une sauvegarde : sa date, puis son numéro de doublon.
+fn sort_key(path: &Path) -> (String, u64) {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    // La date seule compte 19 caractères ; un suffixe `-n` distingue les doublons.
+    match name.get(19..).and_then(|suffix| suffix.strip_prefix('-')) {
+        Some(n) => (name[..19].to_string(), n.parse().unwrap_or(0)),
+        None => (name, 0),
+    }
+}
+
+/// Écrit `secs` secondes depuis l'époque Unix en date UTC `AAAA-MM-JJTHH-MM-SS`.
+fn timestamp(secs: u64) -> String {
+    let (year, month, day, seconds) = utc(secs);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Décompose `secs` secondes depuis l'époque Unix en année, mois, jour et secondes
+/// écoulées dans le jour, en UTC.
+pub(crate) fn utc(secs: u64) -> (i64, i64, i64, u64) {
+    let days = (secs / 86_400) as i64;
+    // Conversion d'un nombre de jours en date du calendrier grégorien proleptique, par
+    // ères de 400 ans (Howard Hinnant, « chrono-Compatible Low-Level Date Algorithms »).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, secs % 86_400)
+}
+
+/// Empreinte FNV-1a 64 bits de `bytes`.
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidSnapshot(format!("manifeste de sauvegarde : {}", message))
+}
+
+fn object(value: &Value) -> Result<&BTreeMap<String, Value>> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(invalid("objet attendu")),
+    }
+}
+
+fn integer(fields: &BTreeMap<String, Value>, key: &str) -> Result<u64> {
+    match fields.get(key) {
+        Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as u64),
+        _ => Err(invalid(&format!("entier « {} » attendu", key))),
+    }
+}
+
+fn string<'a>(fields: &'a BTreeMap<String, Value>, key: &str) -> Result<&'a str> {
+    match fields.get(key) {
+        Some(Value::String(s)) => Ok(s),
+        _ => Err(invalid(&format!("chaîne « {} » attendue", key))),
+    }
+}
+
+fn entry(value: &Value) -> Result<BackupEntry> {
+    let fields = object(value)?;
+    let file = string(fields, "file")?;
+    if file.is_empty() || file.contains(['/', '\\']) || file.starts_with('.') {
+        return Err(invalid(&format!("nom de fichier invalide « {} »", file)));
+    }
+    let checksum = u64::from_str_radix(string(fields, "checksum")?, 16)
+        .map_err(|_| invalid("empreinte hexadécimale attendue"))?;
+    Ok(BackupEntry {
+        collection: string(fields, "name")?.to_string(),
+        file: file.to_string(),
+        bytes: integer(fields, "bytes")?,
+        checksum,
+    })
+}
+
+fn alias(value: &Value) -> Result<(String, String)> {
+    match value {
+        Value::Array(pair) => match pair.as_slice() {
+            [Value::String(alias), Value::String(target)] => Ok((alias.clone(), target.clone())),
+            _ => Err(invalid("alias [alias, collection] attendu")),
+        },
+        _ => Err(invalid("alias [alias, collection] attendu")),
+    }
+}
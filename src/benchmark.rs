@@ -0,0 +1,448 @@
+//! Mesure des performances d'une base sur une charge synthétique.
+
+use std::fmt;
+use std::thread;
+use std::time::Instant;
+
+use crate::collection::Collection;
+use crate::config::CollectionConfig;
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::filter::Filter;
+use crate::float;
+use crate::json;
+use crate::payload::{Payload, Value};
+use crate::payload_index::IndexKind;
+use crate::search::{micros, SearchParams};
+use crate::synthetic::VectorGenerator;
+
+/// Champ de charge utile des documents générés par [`BaseDeDonnees::benchmark`] : un
+/// entier de 0 à [`BENCHMARK_BUCKETS`] - 1, sur lequel portent les recherches filtrées.
+pub const BENCHMARK_FIELD: &str = "bucket";
+
+/// Nombre de valeurs de [`BENCHMARK_FIELD`] : une recherche filtrée retient un
+/// document sur 16.
+pub const BENCHMARK_BUCKETS: usize = 16;
+
+/// Charge mesurée par [`BaseDeDonnees::benchmark`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkOptions {
+    /// Nombre de documents générés.
+    pub documents: usize,
+    /// Dimension des vecteurs générés.
+    pub dimension: usize,
+    /// Nombre de recherches.
+    pub queries: usize,
+    /// Nombre de résultats par recherche.
+    pub k: usize,
+    /// Nombre de recherches exécutées en même temps ; 0 est traité comme 1.
+    pub concurrency: usize,
+    /// Vrai pour filtrer chaque recherche sur une valeur de [`BENCHMARK_FIELD`].
+    pub filtered: bool,
+    /// Index de charge utile créé sur [`BENCHMARK_FIELD`], `None` pour aucun.
+    pub index: Option<IndexKind>,
+    /// Configuration de la collection générée.
+    pub config: CollectionConfig,
+    /// Graine des documents et des requêtes : deux mesures de même graine portent sur
+    /// les mêmes données.
+    pub seed: u64,
+    /// Collection existante de la base à mesurer à la place d'une collection générée ;
+    /// elle n'est jamais modifiée.
+    pub collection: Option<String>,
+    /// Vrai pour accepter de mesurer `collection` : sans cette option, une collection
+    /// existante est refusée.
+    pub force: bool,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        BenchmarkOptions {
+            documents: 10_000,
+            dimension: 128,
+            queries: 1_000,
+            k: 10,
+            concurrency: 1,
+            filtered: false,
+            index: None,
+            config: CollectionConfig::default(),
+            seed: 1,
+            collection: None,
+            force: false,
+        }
+    }
+}
+
+/// Résultat de [`BaseDeDonnees::benchmark`]. Les durées sont en microsecondes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BenchmarkReport {
+    /// Nombre de documents de la collection mesurée.
+    pub documents: usize,
+    /// Dimension des requêtes.
+    pub dimension: usize,
+    /// Nombre de recherches.
+    pub queries: usize,
+    /// Nombre de recherches exécutées en même temps.
+    pub concurrency: usize,
+    /// Vrai si les recherches étaient filtrées.
+    pub filtered: bool,
+    /// Durée de l'insertion des documents générés, 0 pour une collection existante.
+    pub insert_us: u64,
+    /// Durée totale des recherches.
+    pub search_us: u64,
+    /// Recherches par seconde.
+    pub throughput: f64,
+    /// Durée moyenne d'une recherche.
+    pub mean_us: u64,
+    /// Médiane des durées des recherches.
+    pub p50_us: u64,
+    /// 90e centile des durées des recherches.
+    pub p90_us: u64,
+    /// 99e centile des durées des recherches.
+    pub p99_us: u64,
+    /// Plus longue recherche.
+    pub max_us: u64,
+    /// Mémoire occupée par les documents générés, au sens de
+    /// [`Collection::memory_usage`] ; 0 pour une collection existante.
+    pub memory_bytes: usize,
+}
+
+impl BenchmarkReport {
+    /// Sérialise le résultat en un objet JSON sur une ligne, dont les champs sont ceux
+    /// de la structure.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        let fields = [
+            ("documents", self.documents as u64),
+            ("dimension", self.dimension as u64),
+            ("queries", self.queries as u64),
+            ("concurrency", self.concurrency as u64),
+            ("insert_us", self.insert_us),
+            ("search_us", self.search_us),
+            ("mean_us", self.mean_us),
+            ("p50_us", self.p50_us),
+            ("p90_us", self.p90_us),
+            ("p99_us", self.p99_us),
+            ("max_us", self.max_us),
+            ("memory_bytes", self.memory_bytes as u64),
+        ];
+        for (name, value) in fields {
+            json::write_string(&mut out, name);
+            out.push(':');
+            json::write_u64(&mut out, value);
+            out.push(',');
+        }
+        out.push_str("\"filtered\":");
+        out.push_str(if self.filtered { "true" } else { "false" });
+        out.push_str(",\"throughput\":");
+        out.push_str(&float::shortest_f64(self.throughput));
+        out.push('}');
+        out
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    /// Affiche le résultat sur deux lignes : la charge, puis les durées.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} document(s) de dimension {}, {} recherche(s){} par {} thread(s), \
+             insertion {} µs, {} octet(s)",
+            self.documents,
+            self.dimension,
+            self.queries,
+            if self.filtered { " filtrées" } else { "" },
+            self.concurrency,
+            self.insert_us,
+            self.memory_bytes
+        )?;
+        write!(
+            f,
+            "{:.1} recherche(s)/s, moyenne {} µs, p50 {} µs, p90 {} µs, p99 {} µs, max {} µs",
+            self.throughput, self.mean_us, self.p50_us, self.p90_us, self.p99_us, self.max_us
+        )
+    }
+}
+
+impl BaseDeDonnees {
+    /// Mesure les recherches sur une charge synthétique : le débit, les centiles des
+    /// durées et la mémoire occupée, sur le matériel qui exécute la base.
+    ///
+    /// Par défaut, les documents sont générés dans une collection de travail, comme pour
+    /// [`BaseDeDonnees::self_test`] : elle utilise le réglage des parcours de la base
+    /// mais n'y est jamais ajoutée et disparaît à la fin de la mesure, même en cas
+    /// d'erreur. Chaque document reçoit la charge utile `{"bucket": i % 16}`.
+    /// Avec `options.collection` et `options.force`, les requêtes générées interrogent
+    /// une collection existante, sans la modifier.
+    ///
+    /// # Arguments
+    /// * `options` - Charge à mesurer.
+    ///
+    /// # Retourne
+    /// * Result<BenchmarkReport> - Les mesures.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si la charge ne compte aucune requête, si la dimension
+    ///   est nulle, si une collection existante est désignée sans `options.force` ou avec
+    ///   `options.filtered`, ou si sa dimension n'est pas encore connue.
+    /// * `Error::CollectionNotFound` - Si `options.collection` n'existe pas.
+    /// * Celles de [`Collection::search_with`] et de [`Collection::search_filtered`].
+    pub fn benchmark(&self, options: &BenchmarkOptions) -> Result<BenchmarkReport> {
+        if options.queries == 0 {
+            return Err(Error::InvalidConfig(
+                "la mesure demande au moins une requête".to_string(),
+            ));
+        }
+        let concurrency = options.concurrency.max(1);
+        let generated;
+        let (collection, insert_us, memory_bytes) = match &options.collection {
+            Some(nom) => {
+                let collection = self
+                    .get(nom)
+                    .ok_or_else(|| Error::CollectionNotFound(nom.clone()))?;
+                if !options.force {
+                    return Err(Error::InvalidConfig(format!(
+                        "la collection '{}' existe ; mesurez-la avec l'option force",
+                        nom
+                    )));
+                }
+                if options.filtered {
+                    return Err(Error::InvalidConfig(
+                        "les recherches filtrées ne portent que sur une collection générée"
+                            .to_string(),
+                    ));
+                }
+                (collection, 0, 0)
+            }
+            None => {
+                let started = Instant::now();
+                generated = self.generate(options)?;
+                (
+                    &generated,
+                    micros(started.elapsed()),
+                    generated.memory_usage().total(),
+                )
+            }
+        };
+        let dimension = match options.collection {
+            Some(_) => collection.dimension().ok_or_else(|| {
+                Error::InvalidConfig("la dimension de la collection n'est pas connue".to_string())
+            })?,
+            None => options.dimension,
+        };
+
+        let mut generator = VectorGenerator::new(dimension, options.seed.wrapping_add(1));
+        let requests = generator.vectors(options.queries);
+        let params = SearchParams::new(options.k);
+        let search = |index: usize, request: &[f32]| -> Result<u64> {
+            let started = Instant::now();
+            match options.filtered {
+                true => {
+                    let bucket = (index % BENCHMARK_BUCKETS) as i64;
+                    let filter = Filter::eq(BENCHMARK_FIELD, bucket);
+                    collection.search_filtered(request, &filter, &params)?;
+                }
+                false => {
+                    collection.search_with(request, &params)?;
+                }
+            }
+            Ok(micros(started.elapsed()))
+        };
+        let share = requests.len().div_ceil(concurrency);
+        let started = Instant::now();
+        let mut latencies = thread::scope(|scope| {
+            let workers: Vec<_> = (requests.chunks(share).enumerate())
+                .map(|(chunk, requests)| {
+                    scope.spawn(move || {
+                        (requests.iter().enumerate())
+                            .map(|(i, request)| search(chunk * share + i, request))
+                            .collect::<Result<Vec<u64>>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("thread de mesure interrompu"))
+                .collect::<Result<Vec<Vec<u64>>>>()
+        })?
+        .concat();
+        let elapsed = started.elapsed();
+        latencies.sort_unstable();
+        let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+        Ok(BenchmarkReport {
+            documents: collection.len(),
+            dimension,
+            queries: latencies.len(),
+            concurrency,
+            filtered: options.filtered,
+            insert_us,
+            search_us: micros(elapsed),
+            throughput: latencies.len() as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+            mean_us: latencies.iter().sum::<u64>() / latencies.len() as u64,
+            p50_us: percentile(50),
+            p90_us: percentile(90),
+            p99_us: percentile(99),
+            max_us: latencies[latencies.len() - 1],
+            memory_bytes,
+        })
+    }
+
+    /// Génère la collection de travail de [`BaseDeDonnees::benchmark`].
+    fn generate(&self, options: &BenchmarkOptions) -> Result<Collection> {
+        if options.dimension == 0 {
+            return Err(Error::InvalidConfig(
+                "la dimension de la charge doit être positive".to_string(),
+            ));
+        }
+        let mut collection = Collection::from_config(options.config.clone()).with_runtime(self.runtime);
+        if let Some(kind) = options.index {
+            collection.create_payload_index(BENCHMARK_FIELD, kind);
+        }
+        let mut generator = VectorGenerator::new(options.dimension, options.seed);
+        for i in 0..options.documents {
+            let key = generator.uuid();
+            let vector = generator.vector();
+            let bucket = Value::from((i % BENCHMARK_BUCKETS) as i64);
+            let payload = Payload::from([(BENCHMARK_FIELD.to_string(), bucket)]);
+            collection.upsert_with_payload(key, vector, payload)?;
+        }
+        Ok(collection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn tiny() -> BenchmarkOptions {
+        BenchmarkOptions {
+            documents: 200,
+            dimension: 8,
+            queries: 50,
+            k: 5,
+            ..BenchmarkOptions::default()
+        }
+    }
+
+    fn check(report: &BenchmarkReport) {
+        assert!(report.p50_us <= report.p90_us);
+        assert!(report.p90_us <= report.p99_us);
+        assert!(report.p99_us <= report.max_us);
+        assert!(report.mean_us <= report.max_us);
+        assert!(report.throughput > 0.0);
+    }
+
+    #[test]
+    fn generated_workloads_leave_the_database_unchanged() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let (before, sequence) = (db.to_bytes(), db.sequence());
+        for options in [
+            tiny(),
+            BenchmarkOptions {
+                concurrency: 3,
+                filtered: true,
+                index: Some(IndexKind::Numeric),
+                ..tiny()
+            },
+            BenchmarkOptions {
+                filtered: true,
+                ..tiny()
+            },
+        ] {
+            let report = db.benchmark(&options).unwrap();
+            check(&report);
+            assert_eq!(report.documents, 200);
+            assert_eq!((report.dimension, report.queries), (8, 50));
+            assert_eq!(report.concurrency, options.concurrency);
+            assert_eq!(report.filtered, options.filtered);
+            assert!(report.memory_bytes >= 200 * 8 * 4);
+            // La collection de travail n'est pas restée dans la base.
+            assert_eq!(db.to_bytes(), before);
+            assert_eq!(db.sequence(), sequence);
+        }
+    }
+
+    #[test]
+    fn reports_serialize_every_field() {
+        let report = BaseDeDonnees::new().benchmark(&tiny()).unwrap();
+        let Value::Object(fields) = json::parse(&report.to_json()).unwrap() else {
+            panic!("objet attendu");
+        };
+        let names: Vec<&str> = fields.keys().map(String::as_str).collect();
+        let mut expected = [
+            "concurrency",
+            "dimension",
+            "documents",
+            "filtered",
+            "insert_us",
+            "max_us",
+            "mean_us",
+            "memory_bytes",
+            "p50_us",
+            "p90_us",
+            "p99_us",
+            "queries",
+            "search_us",
+            "throughput",
+        ];
+        expected.sort();
+        assert_eq!(names, expected);
+        assert_eq!(fields["documents"].as_f64(), Some(200.0));
+        assert!(matches!(fields["filtered"], Value::Bool(false)));
+        assert_eq!(report.to_string().lines().count(), 2);
+    }
+
+    #[test]
+    fn existing_collections_need_the_force_flag() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        for i in 0..30 {
+            let vector = [i as f32, 1.0, -1.0];
+            db.get_mut("docs").unwrap().upsert(Uuid::new_v4(), vector).unwrap();
+        }
+        let before = db.to_bytes();
+        let named = BenchmarkOptions {
+            collection: Some("docs".to_string()),
+            ..tiny()
+        };
+        assert!(matches!(db.benchmark(&named), Err(Error::InvalidConfig(_))));
+        let forced = BenchmarkOptions {
+            force: true,
+            ..named.clone()
+        };
+        let report = db.benchmark(&forced).unwrap();
+        check(&report);
+        assert_eq!((report.documents, report.dimension), (30, 3));
+        assert_eq!((report.insert_us, report.memory_bytes), (0, 0));
+        assert_eq!(db.to_bytes(), before);
+
+        let filtered = BenchmarkOptions {
+            filtered: true,
+            ..forced.clone()
+        };
+        assert!(matches!(db.benchmark(&filtered), Err(Error::InvalidConfig(_))));
+        let missing = BenchmarkOptions {
+            collection: Some("absente".to_string()),
+            ..forced.clone()
+        };
+        assert!(matches!(db.benchmark(&missing), Err(Error::CollectionNotFound(_))));
+        db.add("vide".to_string());
+        let empty = BenchmarkOptions {
+            collection: Some("vide".to_string()),
+            ..forced
+        };
+        assert!(matches!(db.benchmark(&empty), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn empty_workloads_are_refused() {
+        let db = BaseDeDonnees::new();
+        for options in [
+            BenchmarkOptions { queries: 0, ..tiny() },
+            BenchmarkOptions { dimension: 0, ..tiny() },
+        ] {
+            assert!(matches!(db.benchmark(&options), Err(Error::InvalidConfig(_))));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Réécrit les classements de référence `goldens/rankings.json`, ou les vérifie avec
+//! `--check` : `cargo run --bin regenerate-goldens [-- --check [--exact]]`.
+//!
+//! La vérification échoue, avec la liste des différences, dès qu'un classement s'écarte
+//! de la référence au-delà de l'écart toléré, nul avec `--exact` ; on ne régénère
+//! qu'après un changement voulu des résultats.
+
+use std::fs;
+use std::path::Path;
+
+use embeddingproject::golden::{Goldens, DEFAULT_GOLDEN_EPSILON};
+use embeddingproject::Error;
+
+/// Fichier de référence, relatif à la racine du dépôt.
+const GOLDENS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/goldens/rankings.json");
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (check, epsilon) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => (false, DEFAULT_GOLDEN_EPSILON),
+        ["--check"] => (true, DEFAULT_GOLDEN_EPSILON),
+        ["--check", "--exact"] => (true, 0.0),
+        _ => {
+            eprintln!("usage : regenerate-goldens [--check [--exact]]");
+            std::process::exit(2);
+        }
+    };
+    let goldens = Goldens::generate()?;
+    let path = Path::new(GOLDENS);
+    if !check {
+        fs::write(path, goldens.to_json())?;
+        println!(
+            "{} classements écrits dans {}",
+            goldens.rankings.len(),
+            path.display()
+        );
+        return Ok(());
+    }
+    let expected = Goldens::from_json(&fs::read_to_string(path)?)?;
+    let differences = goldens.compare(&expected, epsilon);
+    if differences.is_empty() {
+        println!(
+            "{} classements conformes à la référence",
+            goldens.rankings.len()
+        );
+        return Ok(());
+    }
+    for difference in &differences {
+        eprintln!("{}", difference);
+    }
+    std::process::exit(1);
+}
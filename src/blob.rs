@@ -0,0 +1,680 @@
+//! Champs volumineux des charges utiles conservés hors de la collection : à l'insertion,
+//! les champs désignés par une [`BlobOffload`] sont écrits dans un [`BlobStore`] et
+//! remplacés par une référence, seule enregistrée dans les sauvegardes, puis relus
+//! quand une recherche ou [`Collection::payload_with_blobs`] les demande.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::payload::{Payload, Value};
+use crate::search::SearchResults;
+use crate::storage::{self, FileSystem};
+
+/// Nom de l'unique champ d'une référence de blob, `{"$blob": <taille>}` : un objet de
+/// cette forme fourni par l'appelant est pris pour une référence.
+pub const BLOB_REFERENCE: &str = "$blob";
+
+/// Magasin des blobs d'une collection, désignés par le document et le nom du champ.
+pub trait BlobStore: Send + Sync {
+    /// Lit le blob du champ `field` du document `key`, `None` s'il n'existe pas.
+    fn get(&self, key: Uuid, field: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Écrit le blob du champ `field` du document `key`, en remplaçant le précédent.
+    fn put(&self, key: Uuid, field: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Supprime le blob du champ `field` du document `key` ; supprimer un blob absent
+    /// n'est pas une erreur.
+    fn delete(&self, key: Uuid, field: &str) -> io::Result<()>;
+
+    /// Retourne les blobs du magasin, utilisé par [`Collection::check_blobs`].
+    fn keys(&self) -> io::Result<Vec<(Uuid, String)>>;
+
+    /// Écrit le blob du champ `field` du document `key` sous un nom provisoire, sans
+    /// toucher au blob en place ; [`BlobStore::publish`] le met ensuite à sa place.
+    ///
+    /// Par défaut, le blob provisoire est le champ `"\u{0}staged:<field>"`, que
+    /// [`BlobStore::keys`] retourne comme un orphelin s'il reste d'une écriture
+    /// interrompue.
+    fn stage(&self, key: Uuid, field: &str, bytes: &[u8]) -> io::Result<()> {
+        self.put(key, field_staged(field).as_str(), bytes)
+    }
+
+    /// Remplace le blob du champ `field` du document `key` par celui écrit par
+    /// [`BlobStore::stage`].
+    ///
+    /// # Erreurs
+    /// * `io::ErrorKind::NotFound` - S'il n'y a pas de blob provisoire.
+    fn publish(&self, key: Uuid, field: &str) -> io::Result<()> {
+        let staged = field_staged(field);
+        let bytes = self.get(key, &staged)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "aucun blob provisoire à publier")
+        })?;
+        self.put(key, field, &bytes)?;
+        self.delete(key, &staged)
+    }
+
+    /// Supprime le blob provisoire du champ `field` du document `key` ; l'absence de
+    /// blob provisoire n'est pas une erreur.
+    fn discard(&self, key: Uuid, field: &str) -> io::Result<()> {
+        self.delete(key, &field_staged(field))
+    }
+}
+
+/// Nom du blob provisoire du champ `field` pour les implémentations par défaut de
+/// [`BlobStore::stage`] et [`BlobStore::publish`].
+fn field_staged(field: &str) -> String {
+    format!("\u{0}staged:{}", field)
+}
+
+/// Magasin de blobs dans un répertoire : le blob du champ `field` du document `key` est
+/// le fichier `<répertoire>/<key>/<field en hexadécimal>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileBlobStore {
+    dir: PathBuf,
+}
+
+impl FileBlobStore {
+    /// Crée un magasin dans le répertoire `dir`, créé à la première écriture.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileBlobStore { dir: dir.into() }
+    }
+
+    /// Retourne le chemin du blob du champ `field` du document `key`.
+    pub fn path(&self, key: Uuid, field: &str) -> PathBuf {
+        let mut name = String::with_capacity(field.len() * 2);
+        for byte in field.bytes() {
+            name.push_str(&format!("{:02x}", byte));
+        }
+        self.dir.join(key.to_string()).join(name)
+    }
+
+    /// Chemin du blob provisoire du champ `field`, ignoré par [`BlobStore::keys`].
+    fn staged_path(&self, key: Uuid, field: &str) -> PathBuf {
+        self.path(key, field).with_extension("staged")
+    }
+}
+
+impl BlobStore for FileBlobStore {
+    fn get(&self, key: Uuid, field: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key, field)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn put(&self, key: Uuid, field: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path(key, field);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        storage::replace(&FileSystem, &path, bytes)
+    }
+
+    fn delete(&self, key: Uuid, field: &str) -> io::Result<()> {
+        let path = self.path(key, field);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        }
+        if let Some(parent) = path.parent() {
+            // Le répertoire du document n'est supprimé qu'une fois vide.
+            let _ = fs::remove_dir(parent);
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> io::Result<Vec<(Uuid, String)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(key) = (entry.file_name().to_str()).and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+            for blob in fs::read_dir(entry.path())? {
+                // Les fichiers temporaires d'une écriture interrompue ne sont pas de
+                // l'hexadécimal et sont ignorés.
+                if let Some(field) = blob?.file_name().to_str().and_then(decode_field) {
+                    keys.push((key, field));
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    fn stage(&self, key: Uuid, field: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.staged_path(key, field);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        storage::replace(&FileSystem, &path, bytes)
+    }
+
+    fn publish(&self, key: Uuid, field: &str) -> io::Result<()> {
+        fs::rename(self.staged_path(key, field), self.path(key, field))
+    }
+
+    fn discard(&self, key: Uuid, field: &str) -> io::Result<()> {
+        let path = self.staged_path(key, field);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        }
+        if let Some(parent) = path.parent() {
+            let _ = fs::remove_dir(parent);
+        }
+        Ok(())
+    }
+}
+
+/// Nom de champ codé par [`FileBlobStore::path`].
+fn decode_field(name: &str) -> Option<String> {
+    if !name.len().is_multiple_of(2) || !name.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes = (0..name.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&name[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Champs conservés dans le [`BlobStore`] d'une collection
+/// ([`Collection::set_blob_store`]).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlobOffload {
+    /// Champs de premier niveau des charges utiles concernés.
+    pub fields: Vec<String>,
+    /// Taille, en octets de sa forme JSON, au-delà de laquelle la valeur d'un champ est
+    /// écrite dans le magasin ; les valeurs plus petites restent dans la charge utile.
+    pub threshold: usize,
+}
+
+/// Magasin d'une collection et champs qui y sont conservés.
+pub(crate) struct Blobs {
+    store: Arc<dyn BlobStore>,
+    offload: BlobOffload,
+    writes: Mutex<BlobWrites>,
+}
+
+impl Clone for Blobs {
+    /// Copie aussi l'écriture en cours, pour qu'une copie de la collection substituée
+    /// à celle-ci la termine ([`Collection::apply`]).
+    fn clone(&self) -> Self {
+        Blobs {
+            store: Arc::clone(&self.store),
+            offload: self.offload.clone(),
+            writes: Mutex::new(self.writes().clone()),
+        }
+    }
+}
+
+impl Blobs {
+    fn writes(&self) -> std::sync::MutexGuard<'_, BlobWrites> {
+        self.writes.lock().expect("écriture de blobs empoisonnée")
+    }
+}
+
+/// Blobs d'une écriture en cours ([`Collection::write_blobs`]).
+#[derive(Debug, Clone, Default)]
+struct BlobWrites {
+    /// Vrai pendant l'écriture.
+    active: bool,
+    /// Blobs écrits sous leur nom provisoire, à publier si l'écriture réussit.
+    staged: Vec<(Uuid, String)>,
+    /// Blobs que l'écriture ne référence plus, à supprimer si elle réussit.
+    released: Vec<(Uuid, String)>,
+}
+
+/// Résultat de [`Collection::check_blobs`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlobReport {
+    /// Blobs du magasin qu'aucune charge utile ne référence, triés.
+    pub orphaned: Vec<(Uuid, String)>,
+    /// Références des charges utiles dont le blob manque dans le magasin, triées.
+    pub missing: Vec<(Uuid, String)>,
+}
+
+impl BlobReport {
+    /// Indique si le magasin correspond exactement aux références des charges utiles.
+    pub fn is_consistent(&self) -> bool {
+        self.orphaned.is_empty() && self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for BlobReport {
+    /// Affiche un résumé sur une ligne.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} blob(s) orphelin(s), {} blob(s) manquant(s)",
+            self.orphaned.len(),
+            self.missing.len()
+        )
+    }
+}
+
+/// Indique si `value` est une référence de blob.
+fn is_reference(value: &Value) -> bool {
+    matches!(value, Value::Object(fields)
+        if fields.len() == 1 && matches!(fields.get(BLOB_REFERENCE), Some(Value::Number(_))))
+}
+
+impl Collection {
+    /// Conserve les champs volumineux désignés par `offload` dans `store`.
+    ///
+    /// À chaque insertion ou remplacement de charge utile, la valeur d'un champ désigné
+    /// dont la forme JSON dépasse `offload.threshold` octets est écrite dans le magasin
+    /// et remplacée par la référence `{"$blob": <taille>}` : les sauvegardes et la
+    /// mémoire de la collection ne contiennent que la référence. Les recherches avec
+    /// [`SearchParams::payload_selector`](crate::SearchParams::payload_selector) et
+    /// [`Collection::payload_with_blobs`] remplacent les références par leur valeur ;
+    /// [`Collection::payload`], les filtres et les index voient la référence.
+    ///
+    /// Le magasin n'est pas enregistré dans les sauvegardes : une collection rechargée le
+    /// reçoit à nouveau par cette méthode, et retourne les références telles quelles
+    /// d'ici là. Les clones de la collection partagent le magasin ; chaque collection
+    /// doit avoir le sien, sans quoi [`Collection::check_blobs`] prend les blobs des
+    /// autres pour des orphelins.
+    ///
+    /// # Arguments
+    /// * `store` - Magasin des blobs.
+    /// * `offload` - Champs conservés dans le magasin et seuil de taille.
+    pub fn set_blob_store(&mut self, store: Arc<dyn BlobStore>, offload: BlobOffload) {
+        self.blobs = Some(Blobs {
+            store,
+            offload,
+            writes: Mutex::default(),
+        });
+    }
+
+    /// Retourne les champs conservés dans le magasin de blobs, s'il y en a un.
+    pub fn blob_offload(&self) -> Option<&BlobOffload> {
+        self.blobs.as_ref().map(|blobs| &blobs.offload)
+    }
+
+    /// Exécute `op`, une écriture de la collection, en ne touchant aux blobs en place
+    /// qu'une fois qu'elle a réussi.
+    ///
+    /// Les blobs des charges utiles admises par [`Collection::admit_payload`] sont écrits
+    /// sous un nom provisoire et ne remplacent les précédents que si `op` réussit ; ils
+    /// sont abandonnés sinon. Les blobs que `op` ne référence plus ne sont supprimés
+    /// qu'en cas de réussite. Dans `op`, un appel imbriqué exécute simplement son
+    /// opération.
+    ///
+    /// # Erreurs
+    /// * Celles de `op`.
+    /// * `Error::Io` - Si un blob n'a pas pu être mis à sa place ; `op` a réussi et
+    ///   [`Collection::check_blobs`] signale le blob manquant.
+    pub(crate) fn write_blobs<T>(&mut self, op: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        match &self.blobs {
+            Some(blobs) if !blobs.writes().active => blobs.writes().active = true,
+            _ => return op(self),
+        }
+        let result = op(self);
+        let Some(blobs) = &self.blobs else {
+            return result;
+        };
+        let writes = std::mem::take(&mut *blobs.writes());
+        if result.is_err() {
+            for (key, field) in &writes.staged {
+                let _ = blobs.store.discard(*key, field);
+            }
+            return result;
+        }
+        // Un même champ peut avoir été libéré puis de nouveau écrit au cours de `op` :
+        // seul l'état final des charges utiles décide.
+        for (key, field) in &writes.released {
+            if !self.references(*key, field) {
+                let _ = blobs.store.delete(*key, field);
+            }
+        }
+        let mut published = Ok(());
+        for (key, field) in &writes.staged {
+            let outcome = match self.references(*key, field) {
+                true => blobs.store.publish(*key, field),
+                false => blobs.store.discard(*key, field),
+            };
+            if let Err(error) = outcome {
+                let _ = blobs.store.discard(*key, field);
+                published = published.and(Err(error));
+            }
+        }
+        published?;
+        result
+    }
+
+    /// Indique si une charge utile du document `key`, vivant, en attente ou supprimé en
+    /// attente de compactage, référence le blob du champ `field`.
+    fn references(&self, key: Uuid, field: &str) -> bool {
+        let tombstone = (self.tombstones.get(&key)).and_then(|tombstone| tombstone.payload.as_ref());
+        (self.payloads.get(&key).or_else(|| self.pending.get(&key)))
+            .or(tombstone)
+            .and_then(|payload| payload.get(field))
+            .is_some_and(is_reference)
+    }
+
+    /// Vérifie `payload` pour le document `key` selon le schéma de la collection, puis
+    /// écrit ses champs volumineux dans le magasin de blobs.
+    ///
+    /// Au sein de [`Collection::write_blobs`], les blobs sont écrits sous un nom
+    /// provisoire, publiés ou abandonnés selon l'issue de l'écriture ; ailleurs, ils
+    /// remplacent aussitôt les précédents.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::conform_payload`].
+    /// * `Error::Io` - Si un blob n'a pas pu être écrit.
+    pub(crate) fn admit_payload(&self, key: Uuid, payload: Payload) -> Result<(Payload, u64)> {
+        let (mut payload, coerced) = self.conform_payload(key, payload)?;
+        let Some(blobs) = &self.blobs else {
+            return Ok((payload, coerced));
+        };
+        for field in &blobs.offload.fields {
+            let Some(value) = payload.get_mut(field) else {
+                continue;
+            };
+            if is_reference(value) {
+                continue;
+            }
+            let mut bytes = String::new();
+            json::write_value(&mut bytes, value);
+            if bytes.len() <= blobs.offload.threshold {
+                continue;
+            }
+            let mut writes = blobs.writes();
+            match writes.active {
+                true => {
+                    blobs.store.stage(key, field, bytes.as_bytes())?;
+                    if !(writes.staged.iter()).any(|staged| staged.0 == key && &staged.1 == field) {
+                        writes.staged.push((key, field.clone()));
+                    }
+                }
+                false => blobs.store.put(key, field, bytes.as_bytes())?,
+            }
+            drop(writes);
+            let size = Value::Number(bytes.len() as f64);
+            *value = Value::Object(BTreeMap::from([(BLOB_REFERENCE.to_string(), size)]));
+        }
+        Ok((payload, coerced))
+    }
+
+    /// Supprime les blobs référencés par `payload`, la charge utile retirée du document
+    /// `key`, sauf ceux que `kept`, celle qui la remplace, référence encore.
+    ///
+    /// Au sein de [`Collection::write_blobs`], la suppression attend la réussite de
+    /// l'écriture. Une suppression qui échoue ne fait pas échouer l'opération : le blob
+    /// reste orphelin et [`Collection::check_blobs`] le signale.
+    pub(crate) fn release_blobs(&self, key: Uuid, payload: &Payload, kept: Option<&Payload>) {
+        let Some(blobs) = &self.blobs else {
+            return;
+        };
+        let mut writes = blobs.writes();
+        for (field, value) in payload {
+            let still_referenced = kept
+                .and_then(|kept| kept.get(field))
+                .is_some_and(is_reference);
+            if !is_reference(value) || still_referenced {
+                continue;
+            }
+            match writes.active {
+                true => writes.released.push((key, field.clone())),
+                false => {
+                    let _ = blobs.store.delete(key, field);
+                }
+            }
+        }
+    }
+
+    /// Remplace les références de blobs de `payload`, la charge utile du document `key`,
+    /// par leur valeur ; sans magasin, les références sont conservées.
+    ///
+    /// # Erreurs
+    /// * `Error::BlobUnavailable` - Si un blob manque ou ne contient pas de JSON valide.
+    /// * `Error::Io` - Si un blob n'a pas pu être lu.
+    fn inflate(&self, key: Uuid, payload: &mut Payload) -> Result<()> {
+        let Some(blobs) = &self.blobs else {
+            return Ok(());
+        };
+        for (field, value) in payload.iter_mut().filter(|(_, value)| is_reference(value)) {
+            let unavailable = |reason: String| Error::BlobUnavailable {
+                key,
+                field: field.clone(),
+                reason,
+            };
+            let bytes = (blobs.store.get(key, field)?)
+                .ok_or_else(|| unavailable("absent du magasin".to_string()))?;
+            let text = String::from_utf8(bytes)
+                .map_err(|_| unavailable("contenu qui n'est pas de l'UTF-8".to_string()))?;
+            *value = json::parse(&text).map_err(unavailable)?;
+        }
+        Ok(())
+    }
+
+    /// Remplace les références de blobs des charges utiles jointes à `results`.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::inflate`].
+    pub(crate) fn inflate_results(&self, results: &mut SearchResults) -> Result<()> {
+        if let Some(payloads) = &mut results.payloads {
+            for ((key, _), payload) in results.hits.iter().zip(payloads) {
+                self.inflate(*key, payload)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lit la charge utile d'un document, avec la valeur des champs conservés dans le
+    /// magasin de blobs ([`Collection::set_blob_store`]).
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    ///
+    /// # Retourne
+    /// * Result<Option<Payload>> - La charge utile complète, comme
+    ///   [`Collection::payload`] sans références.
+    ///
+    /// # Erreurs
+    /// * `Error::BlobUnavailable` - Si un blob manque ou ne contient pas de JSON valide.
+    /// * `Error::Io` - Si un blob n'a pas pu être lu.
+    pub fn payload_with_blobs(&self, key: &Uuid) -> Result<Option<Payload>> {
+        let Some(mut payload) = self.payload(key).cloned() else {
+            return Ok(None);
+        };
+        self.inflate(*key, &mut payload)?;
+        Ok(Some(payload))
+    }
+
+    /// Compare le magasin de blobs aux références des charges utiles, y compris celles
+    /// des documents en attente et des documents supprimés en attente de compactage.
+    ///
+    /// Un blob orphelin reste d'une suppression interrompue entre le retrait du document
+    /// et celui de ses blobs, d'une écriture interrompue avant l'abandon de ses blobs
+    /// provisoires (avec les implémentations par défaut de [`BlobStore::stage`]), ou
+    /// d'un document supprimé puis réinséré avant le compactage avec moins de champs
+    /// volumineux. Il peut être supprimé du magasin sans conséquence.
+    ///
+    /// # Retourne
+    /// * Result<BlobReport> - Les blobs orphelins et les blobs manquants.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si la collection n'a pas de magasin de blobs.
+    /// * `Error::Io` - Si le magasin n'a pas pu être parcouru.
+    pub fn check_blobs(&self) -> Result<BlobReport> {
+        let Some(blobs) = &self.blobs else {
+            return Err(Error::InvalidConfig(
+                "la collection n'a pas de magasin de blobs".to_string(),
+            ));
+        };
+        let tombstones = (self.tombstones.iter())
+            .filter_map(|(key, tombstone)| Some((key, tombstone.payload.as_ref()?)));
+        let referenced: BTreeSet<(Uuid, String)> = (self.payloads.iter())
+            .chain(self.pending.iter())
+            .chain(tombstones)
+            .flat_map(|(key, payload)| {
+                (payload.iter())
+                    .filter(|(_, value)| is_reference(value))
+                    .map(|(field, _)| (*key, field.clone()))
+            })
+            .collect();
+        let stored: BTreeSet<(Uuid, String)> = blobs.store.keys()?.into_iter().collect();
+        Ok(BlobReport {
+            orphaned: stored.difference(&referenced).cloned().collect(),
+            missing: referenced.difference(&stored).cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+
+    fn store() -> (Arc<FileBlobStore>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("embeddingproject-blobs-{}", Uuid::new_v4()));
+        (Arc::new(FileBlobStore::new(&dir)), dir)
+    }
+
+    fn collection(store: Arc<FileBlobStore>) -> Collection {
+        let mut collection = Collection::new();
+        let offload = BlobOffload {
+            fields: vec!["body".to_string()],
+            threshold: 8,
+        };
+        collection.set_blob_store(store, offload);
+        collection
+    }
+
+    fn body(text: &str) -> Payload {
+        Payload::from([("body".to_string(), Value::String(text.to_string()))])
+    }
+
+    #[test]
+    fn offloads_large_fields_only() {
+        let (store, dir) = store();
+        let mut collection = collection(store.clone());
+        let (large, small) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert_with_payload(large, vec![1.0, 0.0], body("un long texte")).unwrap();
+        collection.upsert_with_payload(small, vec![0.0, 1.0], body("court")).unwrap();
+
+        assert!(is_reference(&collection.payload(&large).unwrap()["body"]));
+        assert_eq!(collection.payload(&small).unwrap()["body"], Value::String("court".into()));
+        assert_eq!(collection.payload_with_blobs(&large).unwrap(), Some(body("un long texte")));
+        assert_eq!(store.keys().unwrap(), vec![(large, "body".to_string())]);
+        assert!(collection.check_blobs().unwrap().is_consistent());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rejected_upsert_keeps_existing_blob() {
+        let (store, dir) = store();
+        let mut collection = collection(store.clone());
+        let key = Uuid::new_v4();
+        collection.upsert_with_payload(key, vec![1.0, 0.0], body("version d'origine")).unwrap();
+
+        // La dimension est fixée à 2 : l'insertion est refusée après l'admission de la
+        // charge utile.
+        let rejected = collection.upsert_with_payload(key, vec![1.0, 0.0, 0.0], body("refusée !"));
+        assert!(rejected.is_err());
+        assert_eq!(collection.payload_with_blobs(&key).unwrap(), Some(body("version d'origine")));
+        assert!(collection.check_blobs().unwrap().is_consistent());
+
+        let absent = Uuid::new_v4();
+        assert!(collection.upsert_with_payload(absent, vec![1.0], body("jamais écrite")).is_err());
+        assert!(collection.check_blobs().unwrap().is_consistent());
+        assert!(!store.staged_path(absent, "body").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn replacements_and_deletes_release_blobs() {
+        let (store, dir) = store();
+        let mut collection = collection(store.clone());
+        let key = Uuid::new_v4();
+        collection.upsert_with_payload(key, vec![1.0, 0.0], body("première version")).unwrap();
+        assert!(collection.set_payload(&key, body("seconde version")));
+        assert_eq!(collection.payload_with_blobs(&key).unwrap(), Some(body("seconde version")));
+
+        assert!(collection.set_payload(&key, body("court")));
+        assert!(store.keys().unwrap().is_empty());
+
+        assert!(collection.set_payload(&key, body("de nouveau longue")));
+        collection.delete(&key);
+        assert!(store.keys().unwrap().is_empty());
+        assert!(collection.check_blobs().unwrap().is_consistent());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn reports_orphaned_and_missing_blobs() {
+        let (store, dir) = store();
+        let mut collection = collection(store.clone());
+        let (kept, lost) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert_with_payload(kept, vec![1.0, 0.0], body("un long texte")).unwrap();
+        collection.upsert_with_payload(lost, vec![0.0, 1.0], body("un autre texte")).unwrap();
+        let orphan = Uuid::new_v4();
+        store.put(orphan, "body", b"\"perdu\"").unwrap();
+        store.delete(lost, "body").unwrap();
+
+        let report = collection.check_blobs().unwrap();
+        assert_eq!(report.orphaned, vec![(orphan, "body".to_string())]);
+        assert_eq!(report.missing, vec![(lost, "body".to_string())]);
+        assert!(matches!(
+            collection.payload_with_blobs(&lost),
+            Err(Error::BlobUnavailable { .. })
+        ));
+        assert!(Collection::new().check_blobs().is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn default_staging_publishes_and_discards() {
+        struct Memory(Mutex<BTreeMap<(Uuid, String), Vec<u8>>>);
+        impl BlobStore for Memory {
+            fn get(&self, key: Uuid, field: &str) -> io::Result<Option<Vec<u8>>> {
+                Ok(self.0.lock().unwrap().get(&(key, field.to_string())).cloned())
+            }
+            fn put(&self, key: Uuid, field: &str, bytes: &[u8]) -> io::Result<()> {
+                self.0.lock().unwrap().insert((key, field.to_string()), bytes.to_vec());
+                Ok(())
+            }
+            fn delete(&self, key: Uuid, field: &str) -> io::Result<()> {
+                self.0.lock().unwrap().remove(&(key, field.to_string()));
+                Ok(())
+            }
+            fn keys(&self) -> io::Result<Vec<(Uuid, String)>> {
+                Ok(self.0.lock().unwrap().keys().cloned().collect())
+            }
+        }
+
+        let store = Memory(Mutex::default());
+        let key = Uuid::new_v4();
+        store.put(key, "body", b"avant").unwrap();
+        store.stage(key, "body", b"apres").unwrap();
+        assert_eq!(store.get(key, "body").unwrap(), Some(b"avant".to_vec()));
+        store.publish(key, "body").unwrap();
+        assert_eq!(store.get(key, "body").unwrap(), Some(b"apres".to_vec()));
+        assert_eq!(store.keys().unwrap(), vec![(key, "body".to_string())]);
+
+        store.stage(key, "body", b"abandon").unwrap();
+        store.discard(key, "body").unwrap();
+        assert_eq!(store.get(key, "body").unwrap(), Some(b"apres".to_vec()));
+        assert_eq!(store.publish(key, "body").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
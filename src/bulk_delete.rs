@@ -0,0 +1,259 @@
+//! Suppression d'une liste d'identifiants lue dans un fichier.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::oplog::OperationKind;
+use crate::payload::Value;
+
+/// Nombre de lignes invalides citées par l'affichage d'un [`DeleteReport`].
+const SHOWN_MALFORMED_LINES: usize = 5;
+
+/// Format d'une liste d'identifiants lue par [`Collection::delete_from_reader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdFormat {
+    /// Un `Uuid` par ligne ; les lignes vides sont ignorées.
+    #[default]
+    PlainUuidPerLine,
+    /// Un tableau JSON de `Uuid` écrits sous forme de chaîne : `["…", "…"]`.
+    Json,
+}
+
+/// Bilan de [`Collection::delete_from_reader`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeleteReport {
+    /// Nombre de documents supprimés, ou qui le seraient pour une simulation.
+    pub deleted: usize,
+    /// Nombre d'identifiants absents de la collection, y compris ceux déjà cités plus
+    /// haut dans la liste.
+    pub not_found: usize,
+    /// Numéros, à partir de 1, des lignes qui ne sont pas un `Uuid`, ou des éléments du
+    /// tableau pour [`IdFormat::Json`].
+    pub malformed_lines: Vec<usize>,
+    /// Vrai pour une simulation : la collection n'a pas été modifiée.
+    pub dry_run: bool,
+}
+
+impl fmt::Display for DeleteReport {
+    /// Affiche le bilan sur une ligne :
+    /// `120 document(s) supprimé(s), 3 absent(s), 1 ligne(s) invalide(s) (ligne 7)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} document(s) {}, {} absent(s), {} ligne(s) invalide(s)",
+            self.deleted,
+            if self.dry_run {
+                "à supprimer"
+            } else {
+                "supprimé(s)"
+            },
+            self.not_found,
+            self.malformed_lines.len()
+        )?;
+        if let Some((first, rest)) = self.malformed_lines.split_first() {
+            write!(f, " (ligne {}", first)?;
+            for line in rest.iter().take(SHOWN_MALFORMED_LINES - 1) {
+                write!(f, ", {}", line)?;
+            }
+            if rest.len() >= SHOWN_MALFORMED_LINES {
+                write!(f, ", …")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Supprime les documents dont les identifiants sont lus dans `reader`, comme le
+    /// ferait [`Collection::delete`] pour chacun d'eux.
+    ///
+    /// La suppression est idempotente : un identifiant absent, ou cité plusieurs fois,
+    /// est compté dans [`DeleteReport::not_found`] sans erreur, et une ligne qui n'est
+    /// pas un `Uuid` dans [`DeleteReport::malformed_lines`] sans interrompre la liste.
+    /// Avec `dry_run`, la collection n'est pas modifiée et rien n'est inscrit dans
+    /// l'historique des opérations : le bilan est celui qu'aurait donné la suppression.
+    ///
+    /// # Arguments
+    /// * `reader` - Flux à lire.
+    /// * `format` - Format de la liste.
+    /// * `dry_run` - Simule la suppression sans rien modifier.
+    ///
+    /// # Retourne
+    /// * Result<DeleteReport> - Documents supprimés, absents et lignes invalides.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le flux ne peut pas être lu ; aucun document n'est alors
+    ///   supprimé.
+    /// * `Error::Parse` - Si la liste au format [`IdFormat::Json`] n'est pas un tableau
+    ///   JSON.
+    pub fn delete_from_reader(
+        &mut self,
+        reader: impl BufRead,
+        format: IdFormat,
+        dry_run: bool,
+    ) -> Result<DeleteReport> {
+        let (keys, malformed_lines) = read_ids(reader, format)?;
+        let mut report = DeleteReport {
+            malformed_lines,
+            dry_run,
+            ..Default::default()
+        };
+        let mut seen = HashSet::with_capacity(keys.len());
+        let mut present = Vec::new();
+        for key in keys {
+            match seen.insert(key)
+                && (self.documents.contains_key(&key) || self.pending.contains_key(&key))
+            {
+                true => present.push(key),
+                false => report.not_found += 1,
+            }
+        }
+        report.deleted = present.len();
+        if !dry_run {
+            self.logged(
+                OperationKind::Delete,
+                |collection| {
+                    for key in &present {
+                        collection.remove_document(key);
+                    }
+                },
+                |_| Some(present.len()),
+            );
+        }
+        Ok(report)
+    }
+}
+
+/// Lit les identifiants de la liste et les numéros de ses lignes invalides.
+fn read_ids(mut reader: impl BufRead, format: IdFormat) -> Result<(Vec<Uuid>, Vec<usize>)> {
+    let mut keys = Vec::new();
+    let mut malformed = Vec::new();
+    match format {
+        IdFormat::PlainUuidPerLine => {
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                match Uuid::parse_str(line) {
+                    Ok(key) => keys.push(key),
+                    Err(_) => malformed.push(index + 1),
+                }
+            }
+        }
+        IdFormat::Json => {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            let parse_error = |message: String| Error::Parse { line: 1, message };
+            let Value::Array(items) = json::parse(&text).map_err(parse_error)? else {
+                return Err(parse_error("tableau d'identifiants attendu".to_string()));
+            };
+            for (index, item) in items.iter().enumerate() {
+                match item {
+                    Value::String(s) => match Uuid::parse_str(s) {
+                        Ok(key) => keys.push(key),
+                        Err(_) => malformed.push(index + 1),
+                    },
+                    _ => malformed.push(index + 1),
+                }
+            }
+        }
+    }
+    Ok((keys, malformed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+
+    fn collection(keys: &[Uuid]) -> Collection {
+        let mut collection = Collection::new();
+        for (i, key) in keys.iter().enumerate() {
+            collection.upsert(*key, [1.0, i as f32]).unwrap();
+        }
+        collection
+    }
+
+    fn bytes(collection: &Collection) -> Vec<u8> {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("docs".to_string(), collection.clone());
+        bdd.to_bytes()
+    }
+
+    #[test]
+    fn dry_run_reports_without_touching_the_collection() {
+        let keys: Vec<Uuid> = (1..=4).map(Uuid::from_u128).collect();
+        let mut collection = collection(&keys);
+        let list = format!(
+            "{}\n\npas un uuid\n{}\n{}\n{}\n",
+            keys[0],
+            keys[2],
+            keys[0],
+            Uuid::from_u128(9)
+        );
+        let (before, operations) = (bytes(&collection), collection.recent_operations().len());
+        let report = (collection)
+            .delete_from_reader(list.as_bytes(), IdFormat::PlainUuidPerLine, true)
+            .unwrap();
+        let expected = DeleteReport {
+            deleted: 2,
+            not_found: 2,
+            malformed_lines: vec![3],
+            dry_run: true,
+        };
+        assert_eq!(report, expected);
+        assert_eq!(bytes(&collection), before);
+        assert_eq!(collection.recent_operations().len(), operations);
+        assert_eq!(collection.write_stats().deletes, 0);
+
+        // La suppression réelle donne le même bilan.
+        let report = (collection)
+            .delete_from_reader(list.as_bytes(), IdFormat::PlainUuidPerLine, false)
+            .unwrap();
+        assert_eq!(
+            report,
+            DeleteReport {
+                dry_run: false,
+                ..expected
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "2 document(s) supprimé(s), 2 absent(s), 1 ligne(s) invalide(s) (ligne 3)"
+        );
+    }
+
+    #[test]
+    fn only_actual_deletions_are_accounted() {
+        let keys: Vec<Uuid> = (1..=4).map(Uuid::from_u128).collect();
+        let mut collection = collection(&keys);
+        let list = format!(r#"["{}", "{}", 7, "{}"]"#, keys[1], keys[3], Uuid::from_u128(9));
+        let report = (collection)
+            .delete_from_reader(list.as_bytes(), IdFormat::Json, false)
+            .unwrap();
+        assert_eq!((report.deleted, report.not_found), (2, 1));
+        assert_eq!(report.malformed_lines, [3]);
+        assert_eq!(collection.len(), 2);
+        assert_eq!(collection.write_stats().deletes, 2);
+        assert_eq!(
+            collection.memory_usage(),
+            self::collection(&keys[..2]).memory_usage()
+        );
+
+        let operations = collection.recent_operations().len();
+        assert!(matches!(
+            collection.delete_from_reader(&b"{}"[..], IdFormat::Json, false),
+            Err(Error::Parse { .. })
+        ));
+        assert_eq!(collection.recent_operations().len(), operations);
+    }
+}
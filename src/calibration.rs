@@ -0,0 +1,310 @@
+//! Conversion des scores bruts d'une collection en probabilités de pertinence.
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::search::{ScoreMode, SearchParams};
+use crate::similarity::{portable_exp, Metric};
+
+/// Nombre maximal d'itérations de Newton de l'ajustement.
+const MAX_ITERATIONS: usize = 100;
+/// Pénalité sur la pente, en unités d'écart type des scores : elle garde la pente finie
+/// lorsque les exemples sont parfaitement séparés.
+const RIDGE: f64 = 1e-2;
+
+/// Régression logistique `P(pertinent) = 1 / (1 + e^-(pente × score + ordonnée))`
+/// ajustée sur des exemples étiquetés par [`Collection::fit_calibration`].
+///
+/// La pente a le signe qui rend la probabilité monotone dans le sens de la mesure :
+/// calibrer les scores ne change jamais l'ordre des résultats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    slope: f64,
+    intercept: f64,
+    metric: Metric,
+}
+
+impl Calibration {
+    /// Pente appliquée au score brut.
+    pub fn slope(&self) -> f64 {
+        self.slope
+    }
+
+    /// Ordonnée à l'origine.
+    pub fn intercept(&self) -> f64 {
+        self.intercept
+    }
+
+    /// Mesure des scores sur lesquels la calibration a été ajustée.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Retourne la probabilité de pertinence associée à un score brut.
+    pub fn probability(&self, score: f32) -> f32 {
+        self.probability_with(score, false)
+    }
+
+    /// Comme [`Calibration::probability`], l'exponentielle étant calculée par
+    /// [`portable_exp`] si `deterministic_math` est vrai.
+    pub(crate) fn probability_with(&self, score: f32, deterministic_math: bool) -> f32 {
+        let logit = -(self.slope * f64::from(score) + self.intercept);
+        let exp = match deterministic_math {
+            true => portable_exp(logit),
+            false => logit.exp(),
+        };
+        (1.0 / (1.0 + exp)) as f32
+    }
+
+    pub(crate) fn from_parts(slope: f64, intercept: f64, metric: Metric) -> Self {
+        Calibration {
+            slope,
+            intercept,
+            metric,
+        }
+    }
+}
+
+impl Collection {
+    /// Ajuste une calibration des scores sur des exemples étiquetés et l'enregistre dans
+    /// la collection, en remplaçant la précédente.
+    ///
+    /// Chaque exemple associe une requête, un document de la collection et le fait qu'il
+    /// soit pertinent pour cette requête. Le score brut de chaque couple est calculé comme
+    /// lors d'une recherche, puis une régression logistique à une variable est ajustée
+    /// par la méthode de Newton. La calibration est sauvegardée avec la collection et
+    /// utilisée par les recherches en [`ScoreMode::Calibrated`].
+    ///
+    /// # Arguments
+    /// * `labeled` - Triplets (requête, document, pertinent).
+    ///
+    /// # Retourne
+    /// * Result<Calibration> - La calibration ajustée.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si les exemples ne comptent pas au moins un document
+    ///   pertinent et un non pertinent, si tous les scores sont égaux, ou si les scores
+    ///   les meilleurs sont les moins souvent pertinents.
+    /// * `Error::DocumentNotFound` - Si un document n'existe pas.
+    /// * `Error::DimensionMismatch` - Si une requête ou un document n'a pas la dimension
+    ///   de la collection.
+    /// * Celles de [`Collection::search`] liées à la préparation des requêtes.
+    ///
+    /// En cas d'erreur, la calibration précédente est conservée.
+    pub fn fit_calibration(&mut self, labeled: &[(Vec<f32>, Uuid, bool)]) -> Result<Calibration> {
+        let metric = self.config.metric;
+        let mut scores = Vec::with_capacity(labeled.len());
+        for (query, key, relevant) in labeled {
+            self.check_zero(query, None)?;
+            let query = self.prepare_query(query)?;
+            let vector = self
+                .documents
+                .get(key)
+                .ok_or(Error::DocumentNotFound(*key))?;
+            if vector.len() != query.len() {
+                return Err(Error::DimensionMismatch {
+                    expected: query.len(),
+                    got: vector.len(),
+                });
+            }
+            scores.push((
+                f64::from(self.scorer().score_unchecked(&query, vector)),
+                *relevant,
+            ));
+        }
+        let positives = scores.iter().filter(|(_, relevant)| *relevant).count();
+        if positives == 0 || positives == scores.len() {
+            return Err(Error::InvalidConfig(
+                "la calibration demande des exemples pertinents et non pertinents".to_string(),
+            ));
+        }
+        let (slope, intercept) = fit_logistic(&scores).ok_or_else(|| {
+            Error::InvalidConfig("les scores des exemples sont tous égaux".to_string())
+        })?;
+        if (slope > 0.0) != metric.higher_is_better() {
+            return Err(Error::InvalidConfig(
+                "les meilleurs scores des exemples sont les moins souvent pertinents".to_string(),
+            ));
+        }
+        let calibration = Calibration::from_parts(slope, intercept, metric);
+        self.calibration = Some(calibration);
+        Ok(calibration)
+    }
+
+    /// Retourne la calibration des scores de la collection, s'il y en a une.
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
+    }
+
+    /// Retire la calibration des scores de la collection, s'il y en a une.
+    pub fn remove_calibration(&mut self) -> Option<Calibration> {
+        self.calibration.take()
+    }
+
+    /// Retourne la calibration à appliquer aux résultats d'une recherche selon `params`.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `params` demande des scores calibrés alors que la
+    ///   collection n'a pas de calibration, ou une calibration ajustée pour une autre
+    ///   mesure.
+    pub(crate) fn score_calibration(&self, params: &SearchParams) -> Result<Option<Calibration>> {
+        if params.score_mode != ScoreMode::Calibrated {
+            return Ok(None);
+        }
+        match self.calibration {
+            Some(calibration) if calibration.metric == self.config.metric => Ok(Some(calibration)),
+            Some(calibration) => Err(Error::InvalidConfig(format!(
+                "la calibration a été ajustée pour la mesure {:?} et non {:?}",
+                calibration.metric, self.config.metric
+            ))),
+            None => Err(Error::InvalidConfig(
+                "la collection n'a pas de calibration (voir fit_calibration)".to_string(),
+            )),
+        }
+    }
+}
+
+/// Ajuste `P(pertinent | s) = σ(pente × s + ordonnée)` sur les couples (score, pertinent).
+///
+/// Les scores sont centrés et réduits pendant l'ajustement, ce qui rend la pénalité
+/// [`RIDGE`] indépendante de leur échelle.
+///
+/// # Retourne
+/// * Option<(f64, f64)> - La pente et l'ordonnée, ou `None` si tous les scores sont égaux.
+fn fit_logistic(samples: &[(f64, bool)]) -> Option<(f64, f64)> {
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(|(score, _)| score).sum::<f64>() / n;
+    let variance = samples
+        .iter()
+        .map(|(score, _)| (score - mean).powi(2))
+        .sum::<f64>()
+        / n;
+    let deviation = variance.sqrt();
+    if deviation <= 0.0 || !deviation.is_finite() {
+        return None;
+    }
+    let (mut w, mut c) = (0.0_f64, 0.0_f64);
+    for _ in 0..MAX_ITERATIONS {
+        let (mut gw, mut gc) = (RIDGE * w, 0.0);
+        let (mut hww, mut hwc, mut hcc) = (RIDGE, 0.0, 0.0);
+        for (score, relevant) in samples {
+            let x = (score - mean) / deviation;
+            let p = 1.0 / (1.0 + (-(w * x + c)).exp());
+            let residual = p - f64::from(u8::from(*relevant));
+            let weight = (p * (1.0 - p)).max(1e-12);
+            gw += residual * x;
+            gc += residual;
+            hww += weight * x * x;
+            hwc += weight * x;
+            hcc += weight;
+        }
+        let determinant = hww * hcc - hwc * hwc;
+        if determinant <= 0.0 || determinant.is_nan() {
+            break;
+        }
+        let dw = (hcc * gw - hwc * gc) / determinant;
+        let dc = (hww * gc - hwc * gw) / determinant;
+        w -= dw;
+        c -= dc;
+        if dw.abs() < 1e-10 && dc.abs() < 1e-10 {
+            break;
+        }
+    }
+    Some((w / deviation, c - w * mean / deviation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+
+    const SLOPE: f64 = 8.0;
+    const INTERCEPT: f64 = -4.0;
+
+    /// Collection cosinus dont le document `i` obtient le score `i / count` pour la
+    /// requête `[1.0, 0.0]`, avec des exemples étiquetés selon la loi logistique
+    /// `σ(SLOPE × score + INTERCEPT)` par une suite équirépartie.
+    fn labeled(count: usize) -> (Collection, Vec<(Vec<f32>, Uuid, bool)>) {
+        let mut collection = Collection::new();
+        let mut examples = Vec::with_capacity(count);
+        for i in 0..count {
+            let score = i as f64 / count as f64;
+            let key = Uuid::from_u128(i as u128);
+            let angle = score.acos() as f32;
+            collection.upsert(key, [angle.cos(), angle.sin()]).unwrap();
+            let threshold = (i as f64 * 0.618_033_988_75).fract();
+            let probability = 1.0 / (1.0 + (-(SLOPE * score + INTERCEPT)).exp());
+            examples.push((vec![1.0, 0.0], key, threshold < probability));
+        }
+        (collection, examples)
+    }
+
+    #[test]
+    fn the_fit_recovers_the_generating_curve() {
+        let (mut collection, examples) = labeled(2000);
+        let calibration = collection.fit_calibration(&examples).unwrap();
+        assert!((calibration.slope() - SLOPE).abs() < 1.0, "{:?}", calibration);
+        assert!((calibration.intercept() - INTERCEPT).abs() < 0.5, "{:?}", calibration);
+        assert!((calibration.probability(0.5) - 0.5).abs() < 0.05);
+        assert_eq!(collection.calibration(), Some(&calibration));
+    }
+
+    #[test]
+    fn calibrated_scores_keep_the_raw_order() {
+        let (mut collection, examples) = labeled(300);
+        collection.fit_calibration(&examples).unwrap();
+        let search = |score_mode| {
+            let params = SearchParams {
+                k: 50,
+                score_mode,
+                ..Default::default()
+            };
+            collection.search_with([0.3, 1.0], &params).unwrap().hits
+        };
+        let (raw, calibrated) = (search(ScoreMode::Raw), search(ScoreMode::Calibrated));
+        let keys = |hits: &[(Uuid, f32)]| -> Vec<Uuid> { hits.iter().map(|(key, _)| *key).collect() };
+        assert_eq!(keys(&raw), keys(&calibrated));
+        assert!(calibrated.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert!(calibrated.iter().all(|(_, p)| (0.0..=1.0).contains(p)));
+        for ((_, score), (_, p)) in raw.iter().zip(&calibrated) {
+            assert_eq!(*p, collection.calibration().unwrap().probability(*score));
+        }
+    }
+
+    #[test]
+    fn inverted_or_one_sided_examples_are_refused() {
+        let (mut collection, examples) = labeled(200);
+        let inverted: Vec<_> = (examples.iter().cloned())
+            .map(|(query, key, relevant)| (query, key, !relevant))
+            .collect();
+        let relevant: Vec<_> = (examples.iter().cloned())
+            .map(|(query, key, _)| (query, key, true))
+            .collect();
+        for examples in [inverted, relevant] {
+            assert!(matches!(
+                collection.fit_calibration(&examples),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        assert!(collection.calibration().is_none());
+        let params = SearchParams {
+            score_mode: ScoreMode::Calibrated,
+            ..Default::default()
+        };
+        assert!(matches!(
+            collection.search_with([1.0, 0.0], &params),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn the_calibration_is_saved_with_the_collection() {
+        let (mut collection, examples) = labeled(200);
+        let calibration = collection.fit_calibration(&examples).unwrap();
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        assert_eq!(loaded.get("docs").unwrap().calibration(), Some(&calibration));
+    }
+}
@@ -0,0 +1,347 @@
+//! Lectures et recherches à travers une liste ordonnée de collections, par exemple une
+//! petite collection « chaude » des documents récents devant une grande collection
+//! « froide ».
+
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use crate::database::{self, BaseDeDonnees};
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::payload::Payload;
+use crate::search::SearchParams;
+use crate::store::VectorStore;
+
+/// Résultat de [`CollectionChain::search`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainHit {
+    /// Nom de la collection de la chaîne qui a retourné le document.
+    pub collection: String,
+    /// Identifiant du document.
+    pub key: Uuid,
+    /// Score brut du document.
+    pub score: f32,
+}
+
+/// Résultats de [`CollectionChain::search`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChainResults {
+    /// Documents retenus, du meilleur au moins bon.
+    pub hits: Vec<ChainHit>,
+    /// Vrai si le budget de temps a interrompu le parcours de l'une des collections.
+    pub truncated: bool,
+}
+
+/// Vue en lecture sur une liste ordonnée de collections d'une base : un document est
+/// lu dans la première collection de la chaîne qui le contient, et masque ceux de
+/// même `Uuid` des collections suivantes.
+///
+/// La chaîne ne retient que des noms, résolus à chaque appel par
+/// [`BaseDeDonnees::store`], alias compris : elle ne fait qu'orienter les lectures,
+/// les écritures visant toujours une collection précise. Seul
+/// [`CollectionChain::promote`] écrit, dans la première collection.
+///
+/// `CollectionChain::new(vec!["recents".to_string(), "archives".to_string()])?`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionChain {
+    names: Vec<String>,
+}
+
+impl CollectionChain {
+    /// Crée une chaîne sur les collections `names`, consultées dans cet ordre.
+    ///
+    /// # Arguments
+    /// * `names` - Noms des collections, ou de leurs alias.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `names` est vide ou cite deux fois le même nom.
+    pub fn new(names: Vec<String>) -> Result<Self> {
+        if names.is_empty() {
+            return Err(Error::InvalidConfig(
+                "une chaîne de collections ne peut pas être vide".to_string(),
+            ));
+        }
+        let mut seen = HashSet::new();
+        if let Some(name) = names.iter().find(|name| !seen.insert(name.as_str())) {
+            return Err(Error::InvalidConfig(format!(
+                "la collection '{}' apparaît deux fois dans la chaîne",
+                name
+            )));
+        }
+        Ok(CollectionChain { names })
+    }
+
+    /// Retourne les noms des collections, dans l'ordre de consultation.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Retourne la position dans la chaîne de la première collection qui contient le
+    /// document `key`. Une collection absente de la base est ignorée.
+    fn position(&self, db: &BaseDeDonnees, key: &Uuid) -> Option<usize> {
+        (self.names.iter())
+            .position(|name| db.store(name).is_some_and(|store| store.read(key).is_some()))
+    }
+
+    /// Lit le vecteur du document `key` dans la première collection qui le contient.
+    ///
+    /// # Retourne
+    /// * Option<(&str, &[f32])> - Nom de cette collection et vecteur stocké, `None` si
+    ///   aucune collection de la chaîne ne contient le document.
+    pub fn read<'a>(&'a self, db: &'a BaseDeDonnees, key: &Uuid) -> Option<(&'a str, &'a [f32])> {
+        let name = &self.names[self.position(db, key)?];
+        Some((name, db.store(name)?.read(key)?))
+    }
+
+    /// Lit la charge utile du document `key` dans la première collection qui le
+    /// contient, celle de [`CollectionChain::read`] : la charge utile d'une collection
+    /// suivante n'est jamais retournée à sa place.
+    pub fn payload<'a>(&self, db: &'a BaseDeDonnees, key: &Uuid) -> Option<&'a Payload> {
+        db.store(&self.names[self.position(db, key)?])?.payload(key)
+    }
+
+    /// Recherche les `k` documents les plus proches dans toutes les collections de la
+    /// chaîne et fusionne leurs résultats par score.
+    ///
+    /// Un document d'une collection est écarté si une collection précédente contient
+    /// le même `Uuid`, même si ce dernier n'est pas parmi ses meilleurs résultats :
+    /// seule sa première version est notée. Une collection dont des résultats sont
+    /// ainsi masqués est interrogée de nouveau avec un `k` plus grand, jusqu'à en
+    /// retenir `k` ou épuiser ses documents. À score égal, les documents suivent
+    /// l'ordre de la chaîne, puis l'ordre de leur collection.
+    ///
+    /// Les scores ne sont comparables que dans une même mesure : toutes les
+    /// collections doivent avoir celle de la première. Ils sont bruts, sans les
+    /// paramètres par défaut des collections.
+    ///
+    /// # Arguments
+    /// * `db` - Base qui contient les collections.
+    /// * `request` - Vecteur de requête.
+    /// * `k` - Nombre maximal de résultats.
+    ///
+    /// # Retourne
+    /// * Result<ChainResults> - Documents retenus, avec leur collection.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si une collection de la chaîne n'existe pas.
+    /// * `Error::InvalidConfig` - Si les collections n'ont pas toutes la même mesure.
+    /// * Celles de [`BaseDeDonnees::search_with`], pour la première collection en
+    ///   erreur.
+    pub fn search(
+        &self,
+        db: &BaseDeDonnees,
+        request: impl AsRef<[f32]>,
+        k: usize,
+    ) -> Result<ChainResults> {
+        let request = request.as_ref();
+        let stores = (self.names.iter())
+            .map(|name| {
+                db.store(name)
+                    .ok_or_else(|| Error::CollectionNotFound(name.clone()))
+            })
+            .collect::<Result<Vec<&dyn VectorStore>>>()?;
+        let metric = stores[0].metric();
+        if let Some(index) = stores.iter().position(|store| store.metric() != metric) {
+            return Err(Error::InvalidConfig(format!(
+                "la collection '{}' n'a pas la mesure de '{}' : leurs scores ne sont pas \
+                 comparables",
+                self.names[index], self.names[0]
+            )));
+        }
+        let mut merged = ChainResults::default();
+        for (index, (name, store)) in self.names.iter().zip(&stores).enumerate() {
+            let earlier = &stores[..index];
+            let shadowed = |key: &Uuid| earlier.iter().any(|store| store.read(key).is_some());
+            let mut fetch = k;
+            let hits = loop {
+                let results =
+                    database::search_with(Some(*store), name, request, &SearchParams::new(fetch))?;
+                merged.truncated |= results.truncated;
+                let exhausted = results.hits.len() < fetch;
+                let hits: Vec<(Uuid, f32)> = (results.hits.into_iter())
+                    .filter(|(key, _)| !shadowed(key))
+                    .collect();
+                if hits.len() >= k || exhausted || results.truncated {
+                    break hits;
+                }
+                fetch = fetch.saturating_mul(2);
+            };
+            merged
+                .hits
+                .extend(hits.into_iter().take(k).map(|(key, score)| ChainHit {
+                    collection: name.clone(),
+                    key,
+                    score,
+                }));
+        }
+        // Tri stable : à score égal, les collections restent dans l'ordre de la chaîne.
+        merged.hits.sort_by(|a, b| metric.compare(a.score, b.score));
+        merged.hits.truncate(k);
+        Ok(merged)
+    }
+
+    /// Copie le document `key` de la première collection de la chaîne qui le contient
+    /// dans la première collection de la chaîne, avec sa charge utile, sans le retirer
+    /// de sa collection d'origine.
+    ///
+    /// Le vecteur est copié tel qu'il est stocké : les deux collections doivent le
+    /// stocker de la même façon, sans projection différente.
+    ///
+    /// # Arguments
+    /// * `db` - Base qui contient les collections.
+    /// * `key` - Identifiant du document.
+    ///
+    /// # Retourne
+    /// * Result<bool> - Vrai si le document a été copié, faux s'il était déjà dans la
+    ///   première collection.
+    ///
+    /// # Erreurs
+    /// * `Error::DocumentNotFound` - Si aucune collection de la chaîne ne contient le
+    ///   document.
+    /// * `Error::CollectionNotFound` - Si la première collection n'existe pas.
+    /// * Celles de [`Collection::upsert_with_payload`](crate::Collection::upsert_with_payload),
+    ///   enveloppées dans `Error::WithContext` avec le nom de la première collection et
+    ///   l'identifiant du document.
+    pub fn promote(&self, db: &mut BaseDeDonnees, key: Uuid) -> Result<bool> {
+        let position = self
+            .position(db, &key)
+            .ok_or(Error::DocumentNotFound(key))?;
+        if position == 0 {
+            return Ok(false);
+        }
+        let source = db.store(&self.names[position]).expect("collection de la chaîne");
+        let vector = source.read(&key).expect("document de la chaîne").to_vec();
+        let payload = source.payload(&key).cloned();
+        let first = &self.names[0];
+        let target = db
+            .store_mut(first)
+            .ok_or_else(|| Error::CollectionNotFound(first.clone()))?;
+        let written = match payload {
+            Some(payload) => target.upsert_with_payload(key, vector, payload),
+            None => target.upsert(key, vector),
+        };
+        written.map_err(|e| {
+            e.with_context(ErrorContext::new(Operation::Upsert).collection(first).key(key))
+        })?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::payload::Value;
+    use crate::similarity::Metric;
+
+    fn key(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    /// Base d'une collection `recents` et d'une collection `archives` qui contiennent
+    /// toutes deux le document 1, loin de l'origine dans la première et sur elle dans la
+    /// seconde.
+    fn database() -> (BaseDeDonnees, CollectionChain) {
+        let mut db = BaseDeDonnees::new();
+        for (name, points) in [
+            ("recents", &[(1, 10.0), (2, 1.0)][..]),
+            ("archives", &[(1, 0.0), (3, 0.5), (4, 2.0), (5, 1.0)][..]),
+        ] {
+            let mut collection = Collection::new().with_metric(Metric::Euclidean);
+            for (n, x) in points {
+                collection.upsert(key(*n), [*x]).unwrap();
+            }
+            db.replace(name.to_string(), collection);
+        }
+        let mut payload = Payload::new();
+        payload.insert("source".to_string(), Value::from("archives"));
+        let archives = db.get_mut("archives").unwrap();
+        archives.upsert_with_payload(key(1), [0.0], payload).unwrap();
+        let chain = CollectionChain::new(vec!["recents".to_string(), "archives".to_string()]);
+        (db, chain.unwrap())
+    }
+
+    fn tagged(results: &ChainResults) -> Vec<(&str, Uuid)> {
+        (results.hits.iter())
+            .map(|hit| (hit.collection.as_str(), hit.key))
+            .collect()
+    }
+
+    #[test]
+    fn an_earlier_collection_shadows_the_later_ones() {
+        let (db, chain) = database();
+        assert_eq!(chain.read(&db, &key(1)), Some(("recents", &[10.0][..])));
+        assert_eq!(chain.read(&db, &key(3)), Some(("archives", &[0.5][..])));
+        assert_eq!(chain.read(&db, &key(9)), None);
+        // La charge utile des archives n'est pas lue à la place de celle, absente, du
+        // document récent.
+        assert_eq!(chain.payload(&db, &key(1)), None);
+
+        // Le document 1 des archives, le plus proche, est écarté : il est noté dans sa
+        // version récente, la plus éloignée.
+        let results = chain.search(&db, [0.0], 5).unwrap();
+        assert_eq!(
+            tagged(&results),
+            [
+                ("archives", key(3)),
+                ("recents", key(2)),
+                ("archives", key(5)),
+                ("archives", key(4)),
+                ("recents", key(1)),
+            ]
+        );
+        assert_eq!(results.hits[4].score, 10.0);
+    }
+
+    #[test]
+    fn merged_hits_follow_the_scores_then_the_chain() {
+        let (db, chain) = database();
+        // Les archives sont interrogées de nouveau pour compenser le document masqué ;
+        // à égalité, la collection récente passe en premier.
+        let results = chain.search(&db, [0.0], 3).unwrap();
+        assert_eq!(
+            tagged(&results),
+            [
+                ("archives", key(3)),
+                ("recents", key(2)),
+                ("archives", key(5))
+            ]
+        );
+        assert!(!results.truncated);
+        assert!(chain.search(&db, [0.0], 0).unwrap().hits.is_empty());
+    }
+
+    #[test]
+    fn promote_copies_into_the_first_collection() {
+        let (mut db, chain) = database();
+        assert!(chain.promote(&mut db, key(3)).unwrap());
+        assert!(!chain.promote(&mut db, key(3)).unwrap());
+        assert_eq!(chain.read(&db, &key(3)), Some(("recents", &[0.5][..])));
+        assert!(db.get("archives").unwrap().read(&key(3)).is_some());
+        assert!(matches!(
+            chain.promote(&mut db, key(9)),
+            Err(Error::DocumentNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_chains_are_refused() {
+        let (mut db, chain) = database();
+        for names in [vec![], vec!["a".to_string(), "a".to_string()]] {
+            assert!(matches!(
+                CollectionChain::new(names),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        db.replace("archives".to_string(), Collection::new());
+        assert!(matches!(
+            chain.search(&db, [0.0], 3),
+            Err(Error::InvalidConfig(_))
+        ));
+        let missing = CollectionChain::new(vec!["recents".to_string(), "absente".to_string()]);
+        assert!(matches!(
+            missing.unwrap().search(&db, [0.0], 3),
+            Err(Error::CollectionNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+//! Insertions qui n'écrivent pas un vecteur identique à celui déjà stocké, pour les
+//! réingestions complètes dont la plupart des documents n'ont pas changé.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::oplog::OperationKind;
+use crate::vector_pool;
+
+/// Effet de [`Collection::upsert_if_changed`] sur un document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpsertOutcome {
+    /// Le document n'avait pas de vecteur ; il a été inséré.
+    Inserted,
+    /// Le vecteur stocké différait ; il a été remplacé.
+    Updated,
+    /// Le vecteur stocké était identique à la tolérance près ; rien n'a été écrit.
+    Unchanged,
+}
+
+/// Bilan de [`Collection::upsert_batch_if_changed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChangeSummary {
+    /// Documents insérés.
+    pub inserted: usize,
+    /// Documents dont le vecteur a été remplacé.
+    pub updated: usize,
+    /// Documents laissés tels quels.
+    pub unchanged: usize,
+}
+
+impl ChangeSummary {
+    /// Nombre de documents réellement écrits.
+    pub fn written(&self) -> usize {
+        self.inserted + self.updated
+    }
+
+    fn count(&mut self, outcome: UpsertOutcome) {
+        match outcome {
+            UpsertOutcome::Inserted => self.inserted += 1,
+            UpsertOutcome::Updated => self.updated += 1,
+            UpsertOutcome::Unchanged => self.unchanged += 1,
+        }
+    }
+}
+
+impl fmt::Display for ChangeSummary {
+    /// Affiche le bilan sur une ligne.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inséré(s), {} mis à jour, {} inchangé(s)",
+            self.inserted, self.updated, self.unchanged
+        )
+    }
+}
+
+/// Indique si `stored` et `vector` sont identiques à `epsilon` près sur chaque
+/// coordonnée, ou bit à bit pour une tolérance nulle.
+fn unchanged(stored: &[f32], vector: &[f32], epsilon: f32) -> bool {
+    match epsilon == 0.0 {
+        true => vector_pool::same_bits(stored, vector),
+        false => {
+            stored.len() == vector.len()
+                && stored.iter().zip(vector).all(|(a, b)| (a - b).abs() <= epsilon)
+        }
+    }
+}
+
+impl Collection {
+    /// Insère ou met à jour un document comme [`Collection::upsert`], sauf si son
+    /// vecteur stocké est déjà identique à `vector`.
+    ///
+    /// La comparaison porte sur le vecteur tel qu'il serait stocké, après la projection
+    /// et la normalisation éventuelles de la collection. Un document inchangé n'est pas
+    /// écrit du tout : ni l'historique ([`Collection::recent_operations`]), ni les
+    /// compteurs ([`Collection::write_stats`]), ni les statistiques des vecteurs, ni le
+    /// suivi des requêtes enregistrées ([`Collection::take_new_matches`]), ni l'ordre
+    /// d'éviction ne le voient. La charge utile n'est jamais modifiée.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Nouveau vecteur du document.
+    /// * `epsilon` - Écart maximal toléré sur chaque coordonnée ; 0.0 exige une
+    ///   égalité bit à bit, qui distingue `0.0` de `-0.0`.
+    ///
+    /// # Retourne
+    /// * Result<UpsertOutcome> - L'effet de l'appel sur le document.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `epsilon` est négatif ou NaN.
+    /// * Celles de [`Collection::upsert`] ; le document n'est pas modifié.
+    pub fn upsert_if_changed(
+        &mut self,
+        key: Uuid,
+        vector: impl Into<Vec<f32>>,
+        epsilon: f32,
+    ) -> Result<UpsertOutcome> {
+        check_epsilon(epsilon)?;
+        let vector = vector.into();
+        self.logged(
+            OperationKind::Upsert,
+            |collection| collection.store_if_changed(key, vector, epsilon),
+            |outcome| match outcome {
+                Ok(UpsertOutcome::Unchanged) | Err(_) => None,
+                Ok(_) => Some(1),
+            },
+        )
+    }
+
+    /// Insère ou met à jour un lot de documents comme [`Collection::upsert_if_changed`].
+    ///
+    /// Le lot s'arrête au premier document refusé, les précédents restant écrits. Il
+    /// n'apparaît dans l'historique que s'il a écrit au moins un document.
+    ///
+    /// # Arguments
+    /// * `items` - Documents à insérer, sous forme de couples (Uuid, vecteur).
+    /// * `epsilon` - Écart maximal toléré sur chaque coordonnée ; 0.0 exige une
+    ///   égalité bit à bit.
+    ///
+    /// # Retourne
+    /// * Result<ChangeSummary> - Le nombre de documents insérés, mis à jour et inchangés.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert_if_changed`].
+    pub fn upsert_batch_if_changed<I>(&mut self, items: I, epsilon: f32) -> Result<ChangeSummary>
+    where
+        I: IntoIterator<Item = (Uuid, Vec<f32>)>,
+    {
+        check_epsilon(epsilon)?;
+        self.logged(
+            OperationKind::Batch,
+            |collection| {
+                let mut summary = ChangeSummary::default();
+                for (key, vector) in items {
+                    summary.count(collection.store_if_changed(key, vector, epsilon)?);
+                }
+                Ok(summary)
+            },
+            |summary| {
+                let written = summary.as_ref().ok()?.written();
+                (written > 0).then_some(written)
+            },
+        )
+    }
+
+    /// Indique si [`Collection::upsert_if_changed`] laisserait le document `key` tel quel,
+    /// sans modifier la collection.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert_if_changed`].
+    pub(crate) fn is_unchanged(&self, key: Uuid, vector: Vec<f32>, epsilon: f32) -> Result<bool> {
+        check_epsilon(epsilon)?;
+        let vector = self.prepare_vector(key, vector)?;
+        Ok(self
+            .documents
+            .get(&key)
+            .is_some_and(|stored| unchanged(stored, &vector, epsilon)))
+    }
+
+    /// Prépare `vector` et le stocke s'il diffère du vecteur stocké du document `key`.
+    fn store_if_changed(&mut self, key: Uuid, vector: Vec<f32>, epsilon: f32) -> Result<UpsertOutcome> {
+        let dimension = vector.len();
+        let vector = self.prepare_vector(key, vector)?;
+        let outcome = match self.documents.get(&key) {
+            Some(stored) if unchanged(stored, &vector, epsilon) => {
+                return Ok(UpsertOutcome::Unchanged);
+            }
+            Some(_) => UpsertOutcome::Updated,
+            None => UpsertOutcome::Inserted,
+        };
+        self.store_vector(key, vector)?;
+        self.lock_dimension(dimension);
+        Ok(outcome)
+    }
+}
+
+/// Vérifie la tolérance de [`Collection::upsert_if_changed`].
+fn check_epsilon(epsilon: f32) -> Result<()> {
+    match epsilon >= 0.0 {
+        true => Ok(()),
+        false => Err(Error::InvalidConfig(format!(
+            "la tolérance doit être positive ou nulle, pas {}",
+            epsilon
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchParams;
+
+    fn keys(count: usize) -> Vec<(Uuid, Vec<f32>)> {
+        (0..count)
+            .map(|i| (Uuid::new_v4(), vec![1.0 + i as f32, 0.5, -0.25]))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_reingestion_writes_nothing() {
+        let mut collection = Collection::new();
+        let items = keys(20);
+        let summary = collection.upsert_batch_if_changed(items.clone(), 0.0).unwrap();
+        assert_eq!(summary.inserted, 20);
+        collection
+            .save_query("tout", [1.0, 0.5, -0.25], SearchParams::default())
+            .unwrap();
+        collection.watch_new_documents(true);
+        let (operations, stats) = (collection.recent_operations().len(), collection.write_stats());
+
+        let summary = collection.upsert_batch_if_changed(items.clone(), 0.0).unwrap();
+        assert_eq!((summary.unchanged, summary.written()), (20, 0));
+        for (key, vector) in items {
+            assert_eq!(
+                collection.upsert_if_changed(key, vector, 0.0).unwrap(),
+                UpsertOutcome::Unchanged
+            );
+        }
+        assert_eq!(collection.recent_operations().len(), operations);
+        assert_eq!(collection.write_stats(), stats);
+        assert!(collection.take_new_matches().unwrap().is_empty());
+    }
+
+    #[test]
+    fn outcomes_follow_the_tolerance() {
+        let mut collection = Collection::new();
+        let key = Uuid::new_v4();
+        let upsert = |collection: &mut Collection, vector: [f32; 2], epsilon| {
+            collection.upsert_if_changed(key, vector, epsilon).unwrap()
+        };
+        assert_eq!(upsert(&mut collection, [1.0, 0.0], 0.0), UpsertOutcome::Inserted);
+        assert_eq!(upsert(&mut collection, [1.0, 0.0], 0.0), UpsertOutcome::Unchanged);
+        // Une tolérance nulle compare les bits : -0.0 diffère de 0.0.
+        assert_eq!(upsert(&mut collection, [1.0, -0.0], 0.0), UpsertOutcome::Updated);
+        assert_eq!(upsert(&mut collection, [1.0, 0.001], 0.01), UpsertOutcome::Unchanged);
+        assert_eq!(collection.documents[&key][1].to_bits(), (-0.0f32).to_bits());
+        assert_eq!(upsert(&mut collection, [1.0, 0.1], 0.01), UpsertOutcome::Updated);
+    }
+
+    #[test]
+    fn negative_or_nan_tolerance_is_refused() {
+        let mut collection = Collection::new();
+        for epsilon in [-1.0, f32::NAN] {
+            assert!(matches!(
+                collection.upsert_if_changed(Uuid::new_v4(), [1.0], epsilon),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        assert!(collection.is_empty());
+    }
+}
@@ -0,0 +1,661 @@
+//! Vérification et réparation des sauvegardes.
+//!
+//! Le format ne comporte ni somme de contrôle ni journal : la lecture elle-même valide
+//! la structure du fichier, et les structures dérivées (index de charge utile, décompte
+//! de la mémoire) sont toujours reconstruites au chargement. Restent à vérifier les
+//! documents : dimension, vecteurs nuls ou non normalisés malgré la configuration,
+//! conformité au schéma des charges utiles et répartition entre partitions.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::database::BaseDeDonnees;
+use crate::document_id::DocumentId;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::payload::Value;
+use crate::schema::SchemaMode;
+use crate::sharded::ShardedCollection;
+use crate::similarity;
+use crate::snapshot::{self, Admit};
+
+/// Problème trouvé dans un document d'une sauvegarde.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckIssue {
+    /// Le vecteur n'a pas la dimension de la collection : celle imposée par sa
+    /// configuration, sa projection ou sa première insertion, ou en mode strict la plus
+    /// fréquente.
+    Dimension {
+        key: Uuid,
+        expected: usize,
+        got: usize,
+    },
+    /// Le vecteur est nul alors que la collection refuse les vecteurs nuls.
+    ZeroVector { key: Uuid },
+    /// Le vecteur n'est pas normalisé alors que la collection normalise ses vecteurs ou
+    /// les déclare unitaires.
+    NotNormalized { key: Uuid },
+    /// La charge utile ne respecte pas le schéma de la collection. `coercible` indique
+    /// qu'un schéma souple sait la convertir.
+    Schema { error: Error, coercible: bool },
+    /// Le document est rangé dans la partition `shard` au lieu de `expected`.
+    Misplaced {
+        key: Uuid,
+        shard: usize,
+        expected: usize,
+    },
+    /// Incohérence de la collection qui ne tient à aucun document en particulier.
+    Invariant(String),
+}
+
+impl CheckIssue {
+    /// Retourne le document concerné, s'il y en a un.
+    pub fn key(&self) -> Option<Uuid> {
+        match self {
+            CheckIssue::Dimension { key, .. }
+            | CheckIssue::ZeroVector { key }
+            | CheckIssue::NotNormalized { key }
+            | CheckIssue::Misplaced { key, .. } => Some(*key),
+            CheckIssue::Schema {
+                error: Error::SchemaViolation { key, .. },
+                ..
+            } => Some(*key),
+            CheckIssue::Schema { .. } | CheckIssue::Invariant(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for CheckIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckIssue::Dimension { key, expected, got } => write!(
+                f,
+                "le document {} a la dimension {} au lieu de {}",
+                key, got, expected
+            ),
+            CheckIssue::ZeroVector { key } => {
+                write!(f, "le vecteur du document {} est nul", key)
+            }
+            CheckIssue::NotNormalized { key } => {
+                write!(f, "le vecteur du document {} n'est pas normalisé", key)
+            }
+            CheckIssue::Schema {
+                error,
+                coercible: true,
+            } => write!(f, "{} (convertible)", error),
+            CheckIssue::Schema { error, .. } => write!(f, "{}", error),
+            CheckIssue::Misplaced {
+                key,
+                shard,
+                expected,
+            } => write!(
+                f,
+                "le document {} est dans la partition {} au lieu de {}",
+                key, shard, expected
+            ),
+            CheckIssue::Invariant(description) => f.write_str(description),
+        }
+    }
+}
+
+/// Bilan de la vérification d'une collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionCheck {
+    /// Nom de la collection.
+    pub name: String,
+    /// Vrai pour une collection partitionnée.
+    pub sharded: bool,
+    /// Nombre de documents lus.
+    pub documents: usize,
+    /// Problèmes trouvés, par `Uuid` croissant.
+    pub issues: Vec<CheckIssue>,
+}
+
+/// Bilan de [`BaseDeDonnees::check`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CheckReport {
+    /// Collections lues, dans l'ordre du fichier.
+    pub collections: Vec<CollectionCheck>,
+    /// Erreur qui a interrompu la lecture, s'il y en a une : les collections et alias
+    /// suivants sont illisibles.
+    pub unreadable: Option<Error>,
+}
+
+impl CheckReport {
+    /// Indique si la sauvegarde a été lue entièrement sans problème.
+    pub fn is_ok(&self) -> bool {
+        self.unreadable.is_none() && self.issue_count() == 0
+    }
+
+    /// Retourne le nombre de problèmes trouvés dans les collections lues.
+    pub fn issue_count(&self) -> usize {
+        self.collections.iter().map(|c| c.issues.len()).sum()
+    }
+}
+
+impl fmt::Display for CheckReport {
+    /// Affiche une ligne par collection suivie de ses problèmes, puis l'erreur de lecture.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for collection in &self.collections {
+            write!(f, "collection '{}'", collection.name)?;
+            if collection.sharded {
+                f.write_str(" (partitionnée)")?;
+            }
+            write!(f, " : {} document(s), ", collection.documents)?;
+            match collection.issues.len() {
+                0 => writeln!(f, "aucun problème")?,
+                n => writeln!(f, "{} problème(s)", n)?,
+            }
+            for issue in &collection.issues {
+                writeln!(f, "  - {}", issue)?;
+            }
+        }
+        if let Some(error) = &self.unreadable {
+            writeln!(
+                f,
+                "lecture interrompue, la suite du fichier est illisible : {}",
+                error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Options de [`BaseDeDonnees::repair`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RepairOptions {
+    /// Vrai pour abandonner la partie illisible du fichier et garder les collections lues
+    /// avant elle. Par défaut, une sauvegarde illisible n'est pas réparée.
+    pub drop_unreadable_tail: bool,
+    /// Fichier JSONL où sont ajoutés les documents écartés ; `None` utilise le chemin de
+    /// la sauvegarde suivi de `.quarantine.jsonl`.
+    pub quarantine: Option<PathBuf>,
+}
+
+/// Bilan de [`BaseDeDonnees::repair`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairReport {
+    /// Problèmes trouvés avant la réparation.
+    pub check: CheckReport,
+    /// Nombre de documents écartés dans le fichier de quarantaine.
+    pub quarantined: usize,
+    /// Nombre de charges utiles converties selon le schéma souple de leur collection.
+    pub coerced: usize,
+    /// Vrai si la partie illisible du fichier a été abandonnée.
+    pub dropped_tail: bool,
+    /// Vrai si la sauvegarde a été réécrite ; elle ne l'est pas si elle était saine.
+    pub rewritten: bool,
+}
+
+impl BaseDeDonnees {
+    /// Vérifie une sauvegarde sans la charger pour de bon.
+    ///
+    /// Contrairement à [`BaseDeDonnees::load`], qui échoue au premier problème, la
+    /// vérification lit tout ce qui peut l'être et rapporte chaque document incohérent.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    ///
+    /// # Retourne
+    /// * Result<CheckReport> - Les problèmes trouvés, collection par collection.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    pub fn check(path: impl AsRef<Path>) -> Result<CheckReport> {
+        let bytes = fs::read(path)?;
+        let mut inspect = Inspect::new(false);
+        let result = snapshot::read_database(&bytes, &mut BaseDeDonnees::new(), &mut inspect);
+        Ok(CheckReport {
+            collections: inspect.collections,
+            unreadable: result.err(),
+        })
+    }
+
+    /// Répare une sauvegarde en place.
+    ///
+    /// Les documents signalés par [`BaseDeDonnees::check`] sont retirés de leur collection
+    /// et ajoutés au fichier de quarantaine, une ligne JSON par document au format de
+    /// [`Collection::import_jsonl`] complété des champs `collection` et `issue` ; le
+    /// vecteur écrit est celui stocké, après projection et normalisation éventuelles.
+    /// Les charges utiles qu'un schéma souple sait convertir sont converties sur place.
+    /// La sauvegarde réparée remplace l'ancienne comme avec [`BaseDeDonnees::save`].
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    /// * `options` - Traitement de la partie illisible et fichier de quarantaine.
+    ///
+    /// # Retourne
+    /// * Result<RepairReport> - Les problèmes trouvés et les corrections appliquées.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si un fichier ne peut pas être lu ou écrit.
+    /// * `Error::InvalidSnapshot` - Si une partie du fichier est illisible et que
+    ///   `options.drop_unreadable_tail` est faux ; rien n'est alors modifié.
+    pub fn repair(path: impl AsRef<Path>, options: &RepairOptions) -> Result<RepairReport> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let mut bdd = BaseDeDonnees::new();
+        let mut inspect = Inspect::new(true);
+        let result = snapshot::read_database(&bytes, &mut bdd, &mut inspect);
+        let unreadable = result.err();
+        if let Some(error) = &unreadable {
+            if !options.drop_unreadable_tail {
+                return Err(error.clone());
+            }
+        }
+        if !inspect.quarantine.is_empty() {
+            let quarantine = match &options.quarantine {
+                Some(quarantine) => quarantine.clone(),
+                None => {
+                    let mut quarantine = path.as_os_str().to_owned();
+                    quarantine.push(".quarantine.jsonl");
+                    PathBuf::from(quarantine)
+                }
+            };
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(quarantine)?
+                .write_all(inspect.quarantine.as_bytes())?;
+        }
+        let check = CheckReport {
+            collections: inspect.collections,
+            unreadable,
+        };
+        let rewritten = !check.is_ok();
+        if rewritten {
+            bdd.save(path)?;
+        }
+        Ok(RepairReport {
+            dropped_tail: check.unreadable.is_some(),
+            check,
+            quarantined: inspect.quarantined,
+            coerced: inspect.coerced,
+            rewritten,
+        })
+    }
+}
+
+/// Vérification tolérante des collections lues, qui les répare si `repair` est vrai.
+struct Inspect {
+    repair: bool,
+    collections: Vec<CollectionCheck>,
+    quarantine: String,
+    quarantined: usize,
+    coerced: usize,
+}
+
+impl Inspect {
+    fn new(repair: bool) -> Self {
+        Inspect {
+            repair,
+            collections: Vec::new(),
+            quarantine: String::new(),
+            quarantined: 0,
+            coerced: 0,
+        }
+    }
+
+    /// Corrige les problèmes `issues` de la collection `nom`.
+    fn fix(&mut self, nom: &str, collection: &mut Collection, issues: &[CheckIssue]) {
+        for issue in issues {
+            let Some(key) = issue.key() else {
+                continue;
+            };
+            if !collection.documents.contains_key(&key) {
+                continue;
+            }
+            if let CheckIssue::Schema {
+                coercible: true, ..
+            } = issue
+            {
+                let coerced = collection
+                    .payloads
+                    .get(&key)
+                    .cloned()
+                    .and_then(|payload| collection.conform_payload(key, payload).ok());
+                if let Some((payload, _)) = coerced {
+                    collection.put_payload(key, payload);
+                    self.coerced += 1;
+                    continue;
+                }
+            }
+            self.write_quarantine(nom, collection, key, issue);
+            collection.delete(&key);
+            collection.discard_tombstone(&key);
+            self.quarantined += 1;
+        }
+    }
+
+    /// Ajoute le document `key` au fichier de quarantaine.
+    fn write_quarantine(
+        &mut self,
+        nom: &str,
+        collection: &Collection,
+        key: Uuid,
+        issue: &CheckIssue,
+    ) {
+        let out = &mut self.quarantine;
+        out.push_str("{\"collection\":");
+        json::write_string(out, nom);
+        out.push_str(",\"id\":");
+        match collection.document_id(&key) {
+            DocumentId::Uuid(uuid) => json::write_string(out, &uuid.to_string()),
+            DocumentId::Int(n) => out.push_str(&n.to_string()),
+            DocumentId::Str(s) => json::write_string(out, &s),
+        }
+        out.push_str(",\"vector\":");
+        json::write_array(out, collection.documents[&key].iter(), |out, x| {
+            json::write_f32(out, *x)
+        });
+        if let Some(payload) = collection.payloads.get(&key) {
+            out.push_str(",\"payload\":");
+            json::write_value(out, &Value::Object(payload.clone()));
+        }
+        out.push_str(",\"issue\":");
+        json::write_string(out, &issue.to_string());
+        out.push_str("}\n");
+    }
+}
+
+impl Admit for Inspect {
+    fn collection(&mut self, nom: &str, collection: &mut Collection) -> Result<()> {
+        let mut issues = document_issues(collection);
+        if self.repair {
+            self.fix(nom, collection, &issues);
+        }
+        if issues.is_empty() {
+            if let Err(error) = collection.check_invariants() {
+                issues.push(CheckIssue::Invariant(error.to_string()));
+            }
+        }
+        self.collections.push(CollectionCheck {
+            name: nom.to_string(),
+            sharded: false,
+            documents: collection.len(),
+            issues,
+        });
+        Ok(())
+    }
+
+    fn sharded(&mut self, nom: &str, collection: &mut ShardedCollection) -> Result<()> {
+        let mut issues = Vec::new();
+        let mut documents = 0;
+        let routes: Vec<Vec<(Uuid, usize)>> = collection
+            .shards()
+            .iter()
+            .map(|shard| {
+                shard
+                    .documents
+                    .keys()
+                    .map(|key| (*key, collection.shard_of(key)))
+                    .collect()
+            })
+            .collect();
+        for (index, shard) in collection.shards_mut().iter_mut().enumerate() {
+            documents += shard.len();
+            let mut shard_issues = document_issues(shard);
+            shard_issues.extend(
+                routes[index]
+                    .iter()
+                    .filter(|(_, expected)| *expected != index)
+                    .map(|(key, expected)| CheckIssue::Misplaced {
+                        key: *key,
+                        shard: index,
+                        expected: *expected,
+                    }),
+            );
+            shard_issues.sort_by_key(CheckIssue::key);
+            if self.repair {
+                self.fix(nom, shard, &shard_issues);
+            }
+            issues.extend(shard_issues);
+        }
+        if issues.is_empty() {
+            if let Err(error) = collection.check_invariants() {
+                issues.push(CheckIssue::Invariant(error.to_string()));
+            }
+        }
+        self.collections.push(CollectionCheck {
+            name: nom.to_string(),
+            sharded: true,
+            documents,
+            issues,
+        });
+        Ok(())
+    }
+}
+
+/// Retourne les problèmes des documents de `collection`, par `Uuid` croissant.
+fn document_issues(collection: &Collection) -> Vec<CheckIssue> {
+    let expected = match &collection.projection {
+        Some(projection) => Some(projection.output_dim()),
+        None => collection.dimension(),
+    }
+    .or_else(|| {
+        collection
+            .config
+            .strict_dimensions
+            .then(|| most_common_dimension(collection))
+            .flatten()
+    });
+    let strict = collection
+        .payload_schema()
+        .map(|schema| schema.clone().with_mode(SchemaMode::Strict));
+    let mut keys: Vec<&Uuid> = collection.documents.keys().collect();
+    keys.sort_unstable();
+    let mut issues = Vec::new();
+    for key in keys {
+        let vector = &collection.documents[key];
+        if let Some(expected) = expected.filter(|expected| *expected != vector.len()) {
+            issues.push(CheckIssue::Dimension {
+                key: *key,
+                expected,
+                got: vector.len(),
+            });
+            continue;
+        }
+        if collection.projection.is_none() && collection.check_zero(vector, Some(*key)).is_err() {
+            issues.push(CheckIssue::ZeroVector { key: *key });
+            continue;
+        }
+        if (collection.config.normalized && (similarity::norm(vector) - 1.0).abs() > 1e-3)
+            || (collection.config.unit_vectors && collection.off_unit(vector))
+        {
+            issues.push(CheckIssue::NotNormalized { key: *key });
+            continue;
+        }
+        let (Some(strict), Some(payload)) = (&strict, collection.payloads.get(key)) else {
+            continue;
+        };
+        if let Err(error) = strict.conform(*key, payload.clone()) {
+            let coercible = collection.conform_payload(*key, payload.clone()).is_ok();
+            issues.push(CheckIssue::Schema { error, coercible });
+        }
+    }
+    issues
+}
+
+/// Retourne la dimension la plus fréquente des documents, la plus petite en cas d'égalité.
+fn most_common_dimension(collection: &Collection) -> Option<usize> {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for vector in collection.documents.values() {
+        *counts.entry(vector.len()).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(dimension, count)| (*count, std::cmp::Reverse(*dimension)))
+        .map(|(dimension, _)| dimension)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::payload::Payload;
+    use crate::schema::{FieldType, PayloadSchema};
+    use crate::store::{VectorStore, VectorStoreMut};
+
+    fn note(value: Value) -> Payload {
+        Payload::from([("note".to_string(), value)])
+    }
+
+    /// Sauvegarde abîmée de façon ciblée : dans `docs`, une dimension erronée, un vecteur
+    /// nul, une charge utile convertible et une autre qui ne l'est pas ; dans `partagee`,
+    /// un document rangé dans la mauvaise partition.
+    fn damaged(path: &Path) -> [Uuid; 5] {
+        let mut bdd = BaseDeDonnees::new();
+        let docs = bdd.get_or_create("docs").unwrap();
+        let schema = PayloadSchema::new()
+            .field("note", FieldType::Number, false)
+            .with_mode(SchemaMode::Lenient);
+        docs.set_payload_schema(Some(schema));
+        for i in 0..6u128 {
+            let payload = note(Value::Number(i as f64));
+            docs.upsert_with_payload(Uuid::from_u128(i), [i as f32, 1.0, 0.0], payload)
+                .unwrap();
+        }
+        let [odd, zero, coercible, invalid] = [1, 2, 3, 4].map(Uuid::from_u128);
+        docs.documents.insert(odd, Arc::new(vec![1.0, 1.0]));
+        docs.documents.insert(zero, Arc::new(vec![0.0; 3]));
+        docs.payloads.insert(coercible, note(Value::String("4.5".to_string())));
+        docs.payloads.insert(invalid, note(Value::Array(Vec::new())));
+
+        let mut sharded = ShardedCollection::new(3).unwrap();
+        for i in 0..6u128 {
+            VectorStoreMut::upsert(&mut sharded, Uuid::from_u128(100 + i), vec![1.0, i as f32])
+                .unwrap();
+        }
+        let misplaced = Uuid::from_u128(100);
+        let from = sharded.shard_of(&misplaced);
+        let shards = sharded.shards_mut();
+        let vector = shards[from].documents.remove(&misplaced).unwrap();
+        shards[(from + 1) % 3].documents.insert(misplaced, vector);
+        bdd.replace_sharded("partagee".to_string(), sharded);
+        bdd.save(path).unwrap();
+        [odd, zero, coercible, invalid, misplaced]
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("embeddingproject-check-{}.snap", Uuid::new_v4()))
+    }
+
+    fn issue_keys(check: &CollectionCheck) -> Vec<Option<Uuid>> {
+        check.issues.iter().map(CheckIssue::key).collect()
+    }
+
+    /// Relit une ligne du fichier de quarantaine.
+    fn object(line: &str) -> Payload {
+        match json::parse(line).unwrap() {
+            Value::Object(object) => object,
+            other => panic!("objet attendu : {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_reports_each_damaged_document() {
+        let path = temp_path();
+        let [odd, zero, coercible, invalid, misplaced] = damaged(&path);
+        let report = BaseDeDonnees::check(&path).unwrap();
+        assert!(!report.is_ok());
+        assert!(report.unreadable.is_none());
+        assert_eq!(report.issue_count(), 5);
+
+        let docs = &report.collections[0];
+        assert_eq!((docs.name.as_str(), docs.documents), ("docs", 6));
+        assert_eq!(issue_keys(docs), [Some(odd), Some(zero), Some(coercible), Some(invalid)]);
+        assert_eq!(
+            docs.issues[0],
+            CheckIssue::Dimension {
+                key: odd,
+                expected: 3,
+                got: 2
+            }
+        );
+        assert_eq!(docs.issues[1], CheckIssue::ZeroVector { key: zero });
+        assert!(matches!(docs.issues[2], CheckIssue::Schema { coercible: true, .. }));
+        assert!(matches!(docs.issues[3], CheckIssue::Schema { coercible: false, .. }));
+        let partagee = &report.collections[1];
+        assert!(partagee.sharded);
+        assert!(matches!(
+            partagee.issues[..],
+            [CheckIssue::Misplaced { key, .. }] if key == misplaced
+        ));
+        assert!(report.to_string().contains("collection 'partagee' (partitionnée) : 6 document(s), 1 problème(s)"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn repair_quarantines_what_it_cannot_fix() {
+        let path = temp_path();
+        let quarantine = path.with_extension("quarantaine");
+        let [odd, zero, coercible, invalid, misplaced] = damaged(&path);
+        let options = RepairOptions {
+            quarantine: Some(quarantine.clone()),
+            ..Default::default()
+        };
+        let report = BaseDeDonnees::repair(&path, &options).unwrap();
+        assert_eq!((report.quarantined, report.coerced), (4, 1));
+        assert!(report.rewritten && !report.dropped_tail);
+
+        assert!(BaseDeDonnees::check(&path).unwrap().is_ok());
+        let bdd = BaseDeDonnees::load(&path).unwrap();
+        let docs = bdd.get("docs").unwrap();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs.payload(&coercible).unwrap()["note"], Value::Number(4.5));
+        assert_eq!(VectorStore::len(bdd.get_sharded("partagee").unwrap()), 5);
+
+        let text = fs::read_to_string(&quarantine).unwrap();
+        let lines: Vec<Payload> = text.lines().map(object).collect();
+        assert_eq!(lines.len(), 4);
+        let ids: Vec<String> = lines.iter().map(|line| line["id"].as_str().unwrap().to_string()).collect();
+        assert_eq!(ids, [odd, zero, invalid, misplaced].map(|key| key.to_string()));
+        assert_eq!(lines[3]["collection"], Value::String("partagee".to_string()));
+        assert!(lines.iter().all(|line| line.contains_key("vector") && line.contains_key("issue")));
+
+        // Une sauvegarde saine n'est pas réécrite.
+        let again = BaseDeDonnees::repair(&path, &options).unwrap();
+        assert!(!again.rewritten);
+        assert_eq!(fs::read_to_string(&quarantine).unwrap(), text);
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(quarantine);
+    }
+
+    #[test]
+    fn an_unreadable_tail_is_dropped_only_on_request() {
+        let path = temp_path();
+        let mut bdd = BaseDeDonnees::new();
+        bdd.get_or_create("a").unwrap().upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        let first = bdd.to_bytes().len();
+        bdd.get_or_create("b").unwrap().upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        // Coupure au milieu de la seconde collection.
+        let bytes = bdd.to_bytes();
+        let cut = first + (bytes.len() - first) / 2;
+        fs::write(&path, &bytes[..cut]).unwrap();
+
+        let report = BaseDeDonnees::check(&path).unwrap();
+        let unreadable = report.unreadable.as_ref().unwrap();
+        assert!(matches!(unreadable.root(), Error::InvalidSnapshot(_)));
+        assert_eq!(unreadable.context().unwrap().collection.as_deref(), Some("b"));
+        assert_eq!(report.collections.len(), 1);
+        assert!(BaseDeDonnees::repair(&path, &RepairOptions::default()).is_err());
+        assert_eq!(fs::read(&path).unwrap(), &bytes[..cut]);
+
+        let options = RepairOptions {
+            drop_unreadable_tail: true,
+            ..Default::default()
+        };
+        let report = BaseDeDonnees::repair(&path, &options).unwrap();
+        assert!(report.dropped_tail && report.rewritten);
+        let repaired = BaseDeDonnees::load(&path).unwrap();
+        assert_eq!(repaired.collection_names(), [report.check.collections[0].name.clone()]);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,247 @@
+//! Affectation de vecteurs au plus proche de plusieurs centroïdes, par exemple ceux d'un
+//! k-means calculé ailleurs.
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::parallel::{SearchRuntime, PARALLEL_THRESHOLD};
+use crate::payload::Value;
+use crate::similarity::Metric;
+
+/// Affecte chaque vecteur au centroïde le plus proche selon `metric`.
+///
+/// Les égalités de score vont au centroïde de plus petit indice.
+///
+/// # Arguments
+/// * `vectors` - Vecteurs à affecter.
+/// * `centroids` - Centroïdes, tous de la même dimension.
+/// * `metric` - Mesure de proximité.
+///
+/// # Retourne
+/// * Result<Vec<(usize, f32)>> - Pour chaque vecteur, dans l'ordre, l'indice de son
+///   centroïde et leur score.
+///
+/// # Erreurs
+/// * `Error::InvalidConfig` - Si `centroids` est vide.
+/// * `Error::DimensionMismatch` - Si un centroïde ou un vecteur n'a pas la dimension du
+///   premier centroïde ; rien n'est alors calculé.
+pub fn assign_to_centroids(
+    vectors: &[Vec<f32>],
+    centroids: &[Vec<f32>],
+    metric: Metric,
+) -> Result<Vec<(usize, f32)>> {
+    let vectors: Vec<&[f32]> = vectors.iter().map(Vec::as_slice).collect();
+    assign(SearchRuntime::default(), &vectors, centroids, metric)
+}
+
+/// Affecte les vecteurs de `vectors` à leur centroïde, en vérifiant d'abord toutes les
+/// dimensions.
+fn assign(
+    runtime: SearchRuntime,
+    vectors: &[&[f32]],
+    centroids: &[Vec<f32>],
+    metric: Metric,
+) -> Result<Vec<(usize, f32)>> {
+    let dimension = centroids
+        .first()
+        .ok_or_else(|| Error::InvalidConfig("aucun centroïde fourni".to_string()))?
+        .len();
+    if let Some(got) = centroids
+        .iter()
+        .map(Vec::len)
+        .chain(vectors.iter().map(|vector| vector.len()))
+        .find(|len| *len != dimension)
+    {
+        return Err(Error::DimensionMismatch {
+            expected: dimension,
+            got,
+        });
+    }
+    Ok(runtime.map_chunks(vectors, PARALLEL_THRESHOLD, |chunk| {
+        chunk
+            .iter()
+            .map(|vector| nearest(metric, vector, centroids))
+            .collect()
+    }))
+}
+
+/// Retourne l'indice et le score du centroïde le plus proche de `vector`, de même
+/// dimension que tous les centroïdes.
+fn nearest(metric: Metric, vector: &[f32], centroids: &[Vec<f32>]) -> (usize, f32) {
+    let mut best = (0, metric.score_unchecked(vector, &centroids[0]));
+    for (index, centroid) in centroids.iter().enumerate().skip(1) {
+        let score = metric.score_unchecked(vector, centroid);
+        if metric.compare(score, best.1).is_lt() {
+            best = (index, score);
+        }
+    }
+    best
+}
+
+impl Collection {
+    /// Affecte chaque document de la collection au centroïde le plus proche selon
+    /// `metric`, comme [`assign_to_centroids`].
+    ///
+    /// Les vecteurs comparés sont ceux stockés, après l'éventuelle projection.
+    ///
+    /// # Arguments
+    /// * `centroids` - Centroïdes, tous de la même dimension.
+    /// * `metric` - Mesure de proximité, qui peut différer de celle de la collection.
+    ///
+    /// # Retourne
+    /// * Result<Vec<(Uuid, usize, f32)>> - Pour chaque document, par `Uuid` croissant,
+    ///   l'indice de son centroïde et leur score.
+    ///
+    /// # Erreurs
+    /// * Celles de [`assign_to_centroids`].
+    pub fn assign_all(
+        &self,
+        centroids: &[Vec<f32>],
+        metric: Metric,
+    ) -> Result<Vec<(Uuid, usize, f32)>> {
+        let mut entries: Vec<(&Uuid, &[f32])> = self.scoped_entries(None);
+        entries.sort_unstable_by_key(|(key, _)| **key);
+        let vectors: Vec<&[f32]> = entries.iter().map(|(_, vector)| *vector).collect();
+        let assignments = assign(self.runtime, &vectors, centroids, metric)?;
+        Ok(entries
+            .iter()
+            .zip(assignments)
+            .map(|((key, _), (cluster, score))| (**key, cluster, score))
+            .collect())
+    }
+
+    /// Affecte chaque document comme [`Collection::assign_all`] et écrit l'indice de son
+    /// centroïde dans le champ `field` de sa charge utile, créée si besoin.
+    ///
+    /// Toutes les dimensions et toutes les nouvelles charges utiles sont vérifiées avant
+    /// la première écriture : en cas d'erreur, aucune charge utile n'est modifiée.
+    ///
+    /// # Arguments
+    /// * `centroids` - Centroïdes, tous de la même dimension.
+    /// * `metric` - Mesure de proximité.
+    /// * `field` - Champ qui reçoit l'indice du centroïde, sous forme de nombre.
+    ///
+    /// # Retourne
+    /// * Result<Vec<(Uuid, usize, f32)>> - Les affectations, comme [`Collection::assign_all`].
+    ///
+    /// # Erreurs
+    /// * Celles de [`assign_to_centroids`].
+    /// * `Error::SchemaViolation` - Si une charge utile complétée ne respecte pas le schéma
+    ///   de la collection.
+    pub fn label_clusters(
+        &mut self,
+        centroids: &[Vec<f32>],
+        metric: Metric,
+        field: &str,
+    ) -> Result<Vec<(Uuid, usize, f32)>> {
+        let assignments = self.assign_all(centroids, metric)?;
+        self.write_blobs(|collection| {
+            let mut payloads = Vec::with_capacity(assignments.len());
+            for (key, cluster, _) in &assignments {
+                let mut payload = collection.payloads.get(key).cloned().unwrap_or_default();
+                payload.insert(field.to_string(), Value::Number(*cluster as f64));
+                payloads.push((*key, collection.admit_payload(*key, payload)?));
+            }
+            for (key, (payload, coerced)) in payloads {
+                collection.put_payload(key, payload);
+                collection.schema_warnings += coerced;
+            }
+            Ok(())
+        })?;
+        Ok(assignments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::schema::{FieldType, PayloadSchema};
+    use crate::synthetic::{clustered_vectors, VectorGenerator};
+
+    const CLUSTERS: usize = 6;
+    const DIMENSION: usize = 16;
+    const SEED: u64 = 156;
+
+    /// Jeu regroupé en amas et ses centres, qui sont les premiers vecteurs du générateur.
+    fn dataset() -> (Vec<Vec<f32>>, Vec<usize>, Vec<Vec<f32>>) {
+        let (vectors, labels) = clustered_vectors(CLUSTERS, 30, DIMENSION, 0.05, SEED);
+        let centroids = VectorGenerator::new(DIMENSION, SEED).vectors(CLUSTERS);
+        (vectors, labels, centroids)
+    }
+
+    #[test]
+    fn vectors_are_assigned_to_their_cluster() {
+        let (vectors, labels, centroids) = dataset();
+        for metric in [Metric::Euclidean, Metric::Cosine, Metric::Manhattan] {
+            let assignments = assign_to_centroids(&vectors, &centroids, metric).unwrap();
+            let found: Vec<usize> = assignments.iter().map(|(cluster, _)| *cluster).collect();
+            assert_eq!(found, labels, "{:?}", metric);
+        }
+        // Égalité de score : le plus petit indice l'emporte.
+        let twins = vec![vec![1.0, 0.0], vec![1.0, 0.0]];
+        assert_eq!(
+            assign_to_centroids(&[vec![1.0, 0.0]], &twins, Metric::Euclidean).unwrap(),
+            [(0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn labelling_writes_the_cluster_of_every_document() {
+        let (vectors, labels, centroids) = dataset();
+        let mut collection = Collection::new();
+        let keys: Vec<Uuid> = (0..vectors.len() as u128).map(Uuid::from_u128).collect();
+        for (key, vector) in keys.iter().zip(&vectors) {
+            let payload = Payload::from([("titre".to_string(), Value::String(key.to_string()))]);
+            collection
+                .upsert_with_payload(*key, vector.clone(), payload)
+                .unwrap();
+        }
+        let assignments = collection
+            .label_clusters(&centroids, Metric::Euclidean, "amas")
+            .unwrap();
+        assert_eq!(assignments.len(), keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(assignments[i].0, *key);
+            assert_eq!(assignments[i].1, labels[i]);
+            let payload = collection.payload(key).unwrap();
+            assert_eq!(payload["amas"], Value::Number(labels[i] as f64));
+            assert!(payload.contains_key("titre"));
+        }
+    }
+
+    #[test]
+    fn errors_come_before_any_payload_is_written() {
+        let (vectors, _, centroids) = dataset();
+        let mut collection = Collection::new();
+        for (i, vector) in vectors.iter().enumerate() {
+            collection
+                .upsert(Uuid::from_u128(i as u128), vector.clone())
+                .unwrap();
+        }
+        let mut wrong = centroids.clone();
+        wrong[3].pop();
+        assert!(matches!(
+            collection.label_clusters(&wrong, Metric::Euclidean, "amas"),
+            Err(Error::DimensionMismatch {
+                expected: DIMENSION,
+                got
+            }) if got == DIMENSION - 1
+        ));
+        let narrow: Vec<Vec<f32>> = centroids.iter().map(|c| c[..4].to_vec()).collect();
+        assert!(collection.label_clusters(&narrow, Metric::Euclidean, "amas").is_err());
+        assert!(matches!(
+            collection.assign_all(&[], Metric::Euclidean),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let schema = PayloadSchema::new().field("amas", FieldType::String, false);
+        collection.set_payload_schema(Some(schema));
+        assert!(matches!(
+            collection.label_clusters(&centroids, Metric::Euclidean, "amas"),
+            Err(Error::SchemaViolation { .. })
+        ));
+        assert!(collection.documents.keys().all(|key| collection.payload(key).is_none()));
+    }
+}
@@ -0,0 +1,2220 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::access::AccessLog;
+use crate::adapter::QueryAdapter;
+use crate::blob::Blobs;
+use crate::calibration::Calibration;
+use crate::config::CollectionConfig;
+use crate::dedup::DedupPolicy;
+use crate::document_id::DocumentId;
+use crate::error::{Error, Result};
+use crate::histogram::Histogram;
+use crate::keys::ExternalKeys;
+use crate::memory::{self, BudgetSlot, MemoryUsage, Recency};
+use crate::namespace::Namespaces;
+use crate::oplog::{self, OperationKind, OperationLog};
+use crate::parallel::{SearchRuntime, PARALLEL_THRESHOLD};
+use crate::payload::{Payload, Value};
+use crate::payload_index::PayloadIndex;
+use crate::pca::PcaModel;
+use crate::progress::{BatchReport, ProgressSink, Tracker};
+use crate::projection::Projection;
+use crate::query_pipeline::QueryPipeline;
+use crate::saved_search::SavedSearches;
+use crate::schema::PayloadSchema;
+use crate::search::{
+    Group, LowConfidence, Margin, MissingGroupField, SearchOverrides, SearchParams, SearchResults, Stopwatch,
+};
+use crate::segmented::SegmentedMap;
+use crate::similarity::{self, Metric, Scorer};
+use crate::store;
+use crate::tombstone::Tombstone;
+use crate::unit_norm::{self, DEFAULT_UNIT_NORM_TOLERANCE};
+use crate::vector_pool::VectorPool;
+use crate::vector_stats::VectorStats;
+
+/// Nombre de documents évalués entre deux vérifications du budget de temps d'une recherche.
+const BUDGET_CHECK_INTERVAL: usize = 256;
+
+/// Type alias pour représenter un document sous forme d'une liste de tuples contenant un `Uuid` et une similarité (f32).
+pub type Document = Vec<(Uuid, f32)>;
+
+/// Traitement des vecteurs nuls, dont la similarité cosinus n'est pas définie.
+///
+/// La politique ne s'applique qu'aux collections utilisant [`Metric::Cosine`] : les
+/// autres mesures sont bien définies pour un vecteur nul.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZeroVectorPolicy {
+    /// Refuse les vecteurs nuls à l'insertion comme à la recherche avec [`Error::ZeroVector`].
+    #[default]
+    Reject,
+    /// Accepte les vecteurs nuls et leur attribue une similarité de 0.0.
+    ///
+    /// Une requête nulle donne alors le même score à tous les documents : les résultats
+    /// sont départagés par `Uuid` croissant, comme toutes les égalités de score.
+    ScoreZero,
+}
+
+/// Une structure représentant une collection de documents, chaque document est identifié par un `Uuid` et contient un vecteur de f32.
+#[derive(Clone)]
+pub struct Collection {
+    pub(crate) documents: SegmentedMap<Arc<Vec<f32>>>,
+    pub(crate) payloads: SegmentedMap<Payload>,
+    pub(crate) pending: SegmentedMap<Payload>,
+    pub(crate) config: CollectionConfig,
+    pub(crate) projection: Option<Projection>,
+    pub(crate) aliases: SegmentedMap<DocumentId>,
+    pub(crate) runtime: SearchRuntime,
+    pub(crate) default_params: SearchParams,
+    pub(crate) dedup_threshold: Option<f32>,
+    pub(crate) dedup_policy: DedupPolicy,
+    pub(crate) tombstones: SegmentedMap<Tombstone>,
+    pub(crate) namespaces: Namespaces,
+    pub(crate) indexes: HashMap<String, PayloadIndex>,
+    pub(crate) memory: MemoryUsage,
+    pub(crate) budget: BudgetSlot,
+    pub(crate) recency: Recency,
+    pub(crate) schema: Option<PayloadSchema>,
+    pub(crate) schema_warnings: u64,
+    pub(crate) locked_dimension: Option<usize>,
+    pub(crate) adapter: Option<QueryAdapter>,
+    pub(crate) calibration: Option<Calibration>,
+    pub(crate) pool: VectorPool,
+    pub(crate) operations: OperationLog,
+    pub(crate) unit_tolerance: f32,
+    pub(crate) assume_unit: bool,
+    pub(crate) stats: VectorStats,
+    pub(crate) saved: SavedSearches,
+    pub(crate) external_keys: ExternalKeys,
+    pub(crate) access: AccessLog,
+    pub(crate) blobs: Option<Blobs>,
+}
+
+impl Default for Collection {
+    /// Équivaut à [`Collection::new`].
+    fn default() -> Self {
+        Collection::new()
+    }
+}
+
+impl Collection {
+    /// Crée une nouvelle instance de `Collection`.
+    pub fn new() -> Self {
+        Collection {
+            documents: SegmentedMap::default(),
+            payloads: SegmentedMap::default(),
+            pending: SegmentedMap::default(),
+            config: CollectionConfig::default(),
+            projection: None,
+            aliases: SegmentedMap::default(),
+            runtime: SearchRuntime::default(),
+            default_params: SearchParams::default(),
+            dedup_threshold: None,
+            dedup_policy: DedupPolicy::default(),
+            tombstones: SegmentedMap::default(),
+            namespaces: Namespaces::default(),
+            indexes: HashMap::new(),
+            memory: MemoryUsage::default(),
+            budget: BudgetSlot::default(),
+            recency: Recency::default(),
+            schema: None,
+            schema_warnings: 0,
+            locked_dimension: None,
+            adapter: None,
+            calibration: None,
+            pool: VectorPool::default(),
+            operations: OperationLog::default(),
+            unit_tolerance: DEFAULT_UNIT_NORM_TOLERANCE,
+            assume_unit: false,
+            stats: VectorStats::default(),
+            saved: SavedSearches::default(),
+            external_keys: ExternalKeys::default(),
+            access: AccessLog::default(),
+            blobs: None,
+        }
+    }
+
+    /// Crée une collection vide configurée par `config`.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration obtenue avec [`CollectionConfig::builder`].
+    pub fn from_config(config: CollectionConfig) -> Self {
+        Collection {
+            config,
+            ..Collection::new()
+        }
+    }
+
+    /// Retourne la configuration de la collection.
+    pub fn config(&self) -> &CollectionConfig {
+        &self.config
+    }
+
+    /// Retourne la dimension des vecteurs insérés et des requêtes.
+    ///
+    /// C'est celle de la configuration si elle est fixée ; sinon, la première insertion
+    /// dans une collection vide fixe la dimension, et les insertions suivantes d'une autre
+    /// dimension échouent avec `Error::DimensionLocked`. [`Collection::clear`] peut
+    /// l'oublier.
+    pub fn dimension(&self) -> Option<usize> {
+        self.config.dimension.or(self.locked_dimension)
+    }
+
+    /// Fixe le réglage des parcours parallèles de la collection.
+    ///
+    /// # Arguments
+    /// * `runtime` - Nombre de threads et taille des tranches des parcours.
+    pub fn with_runtime(mut self, runtime: SearchRuntime) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Retourne le réglage des parcours parallèles de la collection.
+    pub fn runtime(&self) -> SearchRuntime {
+        self.runtime
+    }
+
+    /// Fixe les paramètres par défaut des recherches de la collection.
+    ///
+    /// Ils complètent les paramètres que l'appelant ne précise pas dans
+    /// [`Collection::search`] et [`Collection::search_with_defaults`] :
+    /// `collection.set_default_params(SearchParams { k: 20, score_threshold: Some(0.35), ..Default::default() })`.
+    ///
+    /// # Arguments
+    /// * `params` - Paramètres par défaut.
+    pub fn set_default_params(&mut self, params: SearchParams) {
+        self.default_params = params;
+    }
+
+    /// Retourne les paramètres par défaut des recherches de la collection.
+    pub fn default_params(&self) -> &SearchParams {
+        &self.default_params
+    }
+
+    /// Fixe la mesure utilisée par les recherches de la collection.
+    ///
+    /// # Arguments
+    /// * `metric` - Mesure à utiliser.
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.config.metric = metric;
+        self
+    }
+
+    /// Retourne la mesure utilisée par les recherches de la collection.
+    pub fn metric(&self) -> Metric {
+        self.config.metric
+    }
+
+    /// Fixe le traitement des vecteurs nuls de la collection.
+    ///
+    /// # Arguments
+    /// * `policy` - Politique à appliquer.
+    pub fn with_zero_vector_policy(mut self, policy: ZeroVectorPolicy) -> Self {
+        self.config.zero_vector_policy = policy;
+        self
+    }
+
+    /// Retourne le traitement des vecteurs nuls de la collection.
+    pub fn zero_vector_policy(&self) -> ZeroVectorPolicy {
+        self.config.zero_vector_policy
+    }
+
+    /// Choisit le traitement des documents dont la dimension diffère de celle de la requête.
+    ///
+    /// Par défaut, les recherches ignorent ces documents. En mode strict, elles échouent
+    /// avec [`Error::InconsistentDimension`] en désignant le document fautif ;
+    /// [`Collection::validate`] liste tous les documents concernés.
+    ///
+    /// # Arguments
+    /// * `strict` - Vrai pour faire échouer les recherches.
+    pub fn with_strict_dimensions(mut self, strict: bool) -> Self {
+        self.config.strict_dimensions = strict;
+        self
+    }
+
+    /// Indique si les recherches échouent sur les documents de dimension différente.
+    pub fn strict_dimensions(&self) -> bool {
+        self.config.strict_dimensions
+    }
+
+    /// Attache une projection appliquée aux vecteurs insérés et aux requêtes.
+    ///
+    /// Les documents déjà présents sont projetés immédiatement ; ils doivent tous avoir la
+    /// dimension d'entrée de la projection, sinon la collection reste inchangée. Les
+    /// documents supprimés en attente de compactage sont alors définitivement supprimés.
+    ///
+    /// # Arguments
+    /// * `projection` - Projection à attacher.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si une projection est déjà attachée à une collection non
+    ///   vide, si la dimension d'entrée de la projection n'est pas celle de la collection,
+    ///   si sa dimension de sortie n'est pas le nombre de poids des dimensions ou si le
+    ///   pipeline de requêtes ne s'applique pas à cette dimension (voir
+    ///   [`Collection::set_query_pipeline`]).
+    /// * `Error::DimensionMismatch` - Si un document n'a pas la dimension d'entrée de la projection.
+    /// * `Error::ZeroVector` - Si la collection est normalisée et qu'un vecteur projeté est nul.
+    pub fn set_projection(&mut self, projection: Projection) -> Result<()> {
+        if self.projection.is_some() && !self.is_empty() {
+            return Err(Error::InvalidConfig(
+                "les documents ont déjà été projetés par une autre projection".to_string(),
+            ));
+        }
+        if let Some(dimension) = self.dimension() {
+            if projection.input_dim() != dimension {
+                return Err(Error::InvalidConfig(format!(
+                    "la projection attend la dimension {} au lieu de {}",
+                    projection.input_dim(),
+                    dimension
+                )));
+            }
+        }
+        self.config.query_pipeline.check(projection.output_dim())?;
+        if let Some(weights) = self.dimension_weights() {
+            if projection.output_dim() != weights.len() {
+                return Err(Error::InvalidConfig(format!(
+                    "la projection produit la dimension {} alors que la collection pondère {} \
+                     dimensions",
+                    projection.output_dim(),
+                    weights.len()
+                )));
+            }
+        }
+        let mut projected = HashMap::with_capacity(self.documents.len());
+        for (key, vector) in &self.documents {
+            let vector = projection.apply(vector)?;
+            projected.insert(*key, self.normalize(vector, Some(*key))?);
+        }
+        self.documents = projected
+            .into_iter()
+            .map(|(key, vector)| (key, Arc::new(vector)))
+            .collect();
+        self.reshare_vectors();
+        for (key, tombstone) in std::mem::take(&mut self.tombstones) {
+            if let Some(payload) = &tombstone.payload {
+                self.release_blobs(key, payload, None);
+            }
+        }
+        self.projection = Some(projection);
+        self.memory = self.measure_memory();
+        self.recompute_stats();
+        Ok(())
+    }
+
+    /// Retourne la projection attachée à la collection, s'il y en a une.
+    pub fn projection(&self) -> Option<&Projection> {
+        self.projection.as_ref()
+    }
+
+    /// Ajuste une analyse en composantes principales sur les vecteurs stockés.
+    ///
+    /// La moyenne est celle des statistiques tenues à jour ([`Collection::dimension_stats`]).
+    /// Le modèle retourné n'est pas appliqué ; ses parts de variance expliquée permettent
+    /// de choisir `target_dim` avant d'appeler [`Collection::apply_pca`].
+    ///
+    /// # Arguments
+    /// * `target_dim` - Nombre de composantes à conserver.
+    ///
+    /// # Retourne
+    /// * Result<PcaModel> - Le modèle ajusté.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si la collection est vide ou si `target_dim` dépasse la dimension.
+    /// * `Error::DimensionMismatch` - Si les documents n'ont pas tous la même dimension.
+    pub fn fit_pca(&self, target_dim: usize) -> Result<PcaModel> {
+        let vectors: Vec<&[f32]> = self.documents.values().map(|v| v.as_slice()).collect();
+        PcaModel::fit(&vectors, self.stats.mean(), target_dim)
+    }
+
+    /// Réécrit la collection dans l'espace réduit d'une ACP.
+    ///
+    /// Le modèle est conservé comme projection de la collection : les insertions et les
+    /// requêtes suivantes, exprimées dans l'espace d'origine, sont transformées de même.
+    ///
+    /// # Arguments
+    /// * `model` - Modèle obtenu avec [`Collection::fit_pca`].
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si une projection est déjà attachée à la collection.
+    /// * `Error::DimensionMismatch` - Si un document n'a pas la dimension d'origine du modèle.
+    pub fn apply_pca(&mut self, model: &PcaModel) -> Result<()> {
+        self.set_projection(model.to_projection())
+    }
+
+    /// Insère ou met à jour un document identifié par `key` avec le vecteur `vector`.
+    ///
+    /// La charge utile d'un document existant est conservée. Si une projection est attachée,
+    /// c'est le vecteur projeté qui est stocké ; si la collection est normalisée, il est
+    /// ramené à une norme de 1.
+    ///
+    /// Si la déduplication est active ([`Collection::with_dedup`]), un nouveau document
+    /// presque identique à un document existant est refusé, ou ignoré sans erreur avec
+    /// `DedupPolicy::Skip`. La mise à jour d'un document existant n'est jamais vérifiée.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document : `Vec<f32>`, tableau `[f32; N]` ou
+    ///   tranche `&[f32]`.
+    ///
+    /// # Retourne
+    /// * Result<bool> - `false` si le document, nouveau, a été ignoré comme doublon
+    ///   (`DedupPolicy::Skip`) : rien n'a alors été écrit.
+    ///
+    /// # Erreurs
+    /// * `Error::Duplicate` - Si le document est nouveau, qu'un doublon existe et que la
+    ///   politique est `DedupPolicy::Reject`.
+    /// * `Error::ZeroVector` - Si le vecteur est nul et que la collection les refuse.
+    /// * `Error::DimensionMismatch` - Si le vecteur n'a pas la dimension de la configuration
+    ///   ou la dimension d'entrée de la projection.
+    /// * `Error::DimensionLocked` - Si le vecteur n'a pas la dimension fixée par la
+    ///   première insertion (voir [`Collection::dimension`]).
+    pub fn upsert(&mut self, key: Uuid, vector: impl Into<Vec<f32>>) -> Result<bool> {
+        let vector = vector.into();
+        self.logged(
+            OperationKind::Upsert,
+            |collection| collection.upsert_vector(key, vector),
+            |stored| stored.as_ref().ok().map(|&stored| stored as usize),
+        )
+    }
+
+    /// Prépare et stocke `vector` ; retourne `false` si le document, nouveau, a été
+    /// ignoré comme doublon.
+    pub(crate) fn upsert_vector(&mut self, key: Uuid, vector: Vec<f32>) -> Result<bool> {
+        let dimension = vector.len();
+        let vector = self.prepare_vector(key, vector)?;
+        if !self.documents.contains_key(&key) && self.screen_duplicate(key, &vector)?.is_some() {
+            return Ok(false);
+        }
+        self.store_vector(key, vector)?;
+        self.lock_dimension(dimension);
+        Ok(true)
+    }
+
+    /// Insère ou met à jour un document dont le vecteur est donné en `f64`.
+    ///
+    /// Chaque coordonnée est convertie en `f32` ; une valeur finie trop grande pour un
+    /// `f32` est refusée au lieu de devenir infinie.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document.
+    ///
+    /// # Erreurs
+    /// * `Error::ValueOutOfRange` - Si une coordonnée dépasse l'étendue des `f32`.
+    /// * Celles de [`Collection::upsert`].
+    pub fn upsert_f64(&mut self, key: Uuid, vector: &[f64]) -> Result<bool> {
+        self.upsert(key, to_f32(vector)?)
+    }
+
+    /// Remplace en place les coordonnées `[offset, offset + values.len())` du vecteur
+    /// d'un document, sans toucher aux autres ni à sa charge utile.
+    ///
+    /// Les coordonnées sont celles du vecteur stocké, c'est-à-dire après l'éventuelle
+    /// projection de la collection. Les recherches suivantes utilisent le vecteur modifié.
+    /// Un vecteur partagé avec d'autres documents ([`Collection::with_shared_vectors`])
+    /// est d'abord copié : les autres documents gardent l'ancien. Le vecteur modifié est
+    /// soumis aux mêmes contrôles de norme qu'un vecteur inséré.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du document.
+    /// * `offset` - Position de la première coordonnée remplacée.
+    /// * `values` - Nouvelles valeurs des coordonnées.
+    ///
+    /// # Erreurs
+    /// * `Error::DocumentNotFound` - Si le document n'existe pas.
+    /// * `Error::RangeOutOfBounds` - Si la plage dépasse la dimension du vecteur.
+    /// * `Error::ZeroVector` - Si le vecteur devient nul et que la collection les refuse.
+    /// * `Error::NormOutOfRange` - Si la norme du vecteur modifié sort de la plage admise.
+    /// * `Error::NotNormalized` - Si la collection déclare des vecteurs unitaires et que
+    ///   le vecteur modifié ne l'est plus, sauf dans [`Collection::assume_normalized`].
+    /// * `Error::InvalidConfig` - Si la collection est normalisée : une modification
+    ///   partielle ne préserverait pas la norme.
+    ///
+    /// En cas d'erreur, le vecteur n'est pas modifié.
+    pub fn patch_vector(&mut self, key: Uuid, offset: usize, values: &[f32]) -> Result<()> {
+        if self.config.normalized {
+            return Err(Error::InvalidConfig(
+                "les vecteurs d'une collection normalisée ne peuvent être modifiés que \
+                 entièrement"
+                    .to_string(),
+            ));
+        }
+        let vector = self.documents.get(&key).ok_or(Error::DocumentNotFound(key))?;
+        let end = offset
+            .checked_add(values.len())
+            .filter(|end| *end <= vector.len())
+            .ok_or(Error::RangeOutOfBounds {
+                offset,
+                len: values.len(),
+                dimension: vector.len(),
+            })?;
+        let mut patched = vector.to_vec();
+        patched[offset..end].copy_from_slice(values);
+        let becomes_zero = patched.iter().all(|x| *x == 0.0);
+        if becomes_zero && self.config.zero_vector_policy == ZeroVectorPolicy::Reject {
+            return Err(Error::ZeroVector { key: Some(key) });
+        }
+        self.check_norm_range(&patched, key)?;
+        self.check_unit(&patched, key)?;
+        let vector = self.documents.get_mut(&key).ok_or(Error::DocumentNotFound(key))?;
+        self.stats.patch(offset, &vector[offset..end], values);
+        Arc::make_mut(vector)[offset..end].copy_from_slice(values);
+        if self.config.shared_vectors {
+            if let Some(vector) = self.documents.remove(&key) {
+                let vector = self.share_vector(Arc::unwrap_or_clone(vector));
+                self.documents.insert(key, vector);
+            }
+        }
+        Ok(())
+    }
+
+    /// Insère ou met à jour un document avec son vecteur et sa charge utile.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document.
+    /// * `payload` - Charge utile du document, remplaçant la précédente.
+    ///
+    /// # Retourne
+    /// * Result<bool> - `false` si le document, nouveau, a été ignoré comme doublon,
+    ///   comme par [`Collection::upsert`] ; la charge utile n'est alors pas écrite.
+    ///
+    /// # Erreurs
+    /// * `Error::ZeroVector` - Si le vecteur est nul et que la collection les refuse.
+    /// * `Error::SchemaViolation` - Si la charge utile ne respecte pas le schéma de la
+    ///   collection ([`Collection::set_payload_schema`]) ; le document n'est pas modifié.
+    /// * `Error::Io` - Si un champ volumineux n'a pas pu être écrit dans le magasin de
+    ///   blobs ([`Collection::set_blob_store`]) ; le document n'est pas modifié.
+    pub fn upsert_with_payload(
+        &mut self,
+        key: Uuid,
+        vector: impl Into<Vec<f32>>,
+        payload: Payload,
+    ) -> Result<bool> {
+        let vector = vector.into();
+        self.logged(
+            OperationKind::Upsert,
+            |collection| {
+                collection.write_blobs(|collection| {
+                    let (payload, coerced) = collection.admit_payload(key, payload)?;
+                    if !collection.upsert_vector(key, vector)? {
+                        return Ok(false);
+                    }
+                    collection.put_payload(key, payload);
+                    collection.schema_warnings += coerced;
+                    Ok(true)
+                })
+            },
+            |stored| stored.as_ref().ok().map(|&stored| stored as usize),
+        )
+    }
+
+    /// Insère ou met à jour un lot de documents.
+    ///
+    /// Si `progress` est fourni, il est notifié régulièrement et peut interrompre le lot
+    /// entre deux documents ; les documents déjà insérés restent alors en place.
+    /// Le lot s'arrête au premier document refusé, les précédents restant insérés.
+    ///
+    /// # Arguments
+    /// * `items` - Documents à insérer, sous forme de couples (Uuid, vecteur).
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<BatchReport> - Nombre de documents stockés, sans les doublons ignorés
+    ///   (`DedupPolicy::Skip`), et indication d'annulation.
+    pub fn upsert_batch<I>(
+        &mut self,
+        items: I,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport>
+    where
+        I: IntoIterator<Item = (Uuid, Vec<f32>)>,
+    {
+        let items = items.into_iter();
+        self.logged(
+            OperationKind::Batch,
+            |collection| {
+                let mut tracker = Tracker::new(progress, Tracker::exact_len(&items));
+                for (key, vector) in items {
+                    if tracker.cancelled() {
+                        return Ok(tracker.finish(true));
+                    }
+                    let stored = collection.upsert_vector(key, vector)?;
+                    tracker.record(stored);
+                }
+                Ok(tracker.finish(false))
+            },
+            oplog::applied,
+        )
+    }
+
+    /// Lit tous les vecteurs de la collection pour les amener dans les caches du
+    /// processeur avant la première recherche.
+    ///
+    /// Une collection en mémoire ne construit aucune structure à la demande (ni cache de
+    /// normes ni index) : il n'y a rien d'autre à préparer. L'appel ne modifie pas la
+    /// collection et peut être répété sans effet.
+    pub fn warm_up(&self) {
+        for vector in self.documents.values() {
+            store::touch(vector);
+        }
+    }
+
+    /// Lit un document à partir de son `key`.
+    ///
+    /// La référence est liée à l'emprunt de la collection ; pour conserver le vecteur
+    /// au-delà sans le copier, [`Collection::read_arc`] le retourne partagé.
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    ///
+    /// # Retourne
+    /// * Option<&Vec<f32>> - Une référence optionnelle au vecteur du document.
+    pub fn read(&self, key: &Uuid) -> Option<&Vec<f32>> {
+        self.documents.get(key).map(|vector| &**vector)
+    }
+
+    /// Lit le vecteur d'un document sous une forme que l'appelant peut conserver au-delà
+    /// de l'emprunt de la collection, par exemple pour l'envoyer à un autre thread.
+    ///
+    /// Seul le compteur de références est incrémenté : deux lectures retournent le même
+    /// vecteur, sans copie des coordonnées. Une écriture ultérieure dans la collection
+    /// (insertion, [`Collection::patch_vector`]) remplace son vecteur au lieu de modifier
+    /// celui déjà retourné, qui reste inchangé.
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    ///
+    /// # Retourne
+    /// * Option<Arc<Vec<f32>>> - Le vecteur partagé du document, s'il existe.
+    pub fn read_arc(&self, key: &Uuid) -> Option<Arc<Vec<f32>>> {
+        self.documents.get(key).cloned()
+    }
+
+    /// Lit un document qui doit exister, pour les scripts et les tests.
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    ///
+    /// # Panique
+    /// Si le document n'existe pas, avec son `Uuid` dans le message.
+    /// [`Collection::read`] est l'accès sans panique à privilégier.
+    pub fn expect_read(&self, key: &Uuid) -> &Vec<f32> {
+        match self.documents.get(key) {
+            Some(vector) => vector,
+            None => panic!("le document {} n'existe pas dans la collection", key),
+        }
+    }
+
+    /// Lit la charge utile d'un document à partir de son `key`.
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    ///
+    /// # Retourne
+    /// * Option<&Payload> - La charge utile du document, s'il en possède une, qu'il ait
+    ///   un vecteur ou qu'il l'attende ([`Collection::register`]).
+    pub fn payload(&self, key: &Uuid) -> Option<&Payload> {
+        self.payloads.get(key).or_else(|| self.pending.get(key))
+    }
+
+    /// Remplace la charge utile d'un document existant.
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    /// * `payload` - Nouvelle charge utile.
+    ///
+    /// # Retourne
+    /// * bool - `false` si le document n'existe pas, ou si la charge utile ne respecte pas
+    ///   le schéma de la collection ([`Collection::set_payload_schema`]) ou si un champ
+    ///   volumineux n'a pas pu être écrit dans le magasin de blobs
+    ///   ([`Collection::set_blob_store`]) ; la charge utile précédente est alors conservée.
+    pub fn set_payload(&mut self, key: &Uuid, payload: Payload) -> bool {
+        let pending = self.pending.contains_key(key);
+        if !pending && !self.documents.contains_key(key) {
+            return false;
+        }
+        self.write_blobs(|collection| {
+            let (payload, coerced) = collection.admit_payload(*key, payload)?;
+            match pending {
+                true => collection.put_pending(*key, payload),
+                false => collection.put_payload(*key, payload),
+            }
+            collection.schema_warnings += coerced;
+            Ok(())
+        })
+        .is_ok()
+    }
+
+    /// Supprime un document à partir de son `key`.
+    ///
+    /// En suppression différée ([`Collection::with_soft_delete`]), le document est
+    /// conservé à l'écart jusqu'à [`Collection::compact`] ; il n'est plus visible entre-temps.
+    /// Un document qui attend son vecteur ([`Collection::register`]) est toujours
+    /// supprimé immédiatement.
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    pub fn delete(&mut self, key: &Uuid) {
+        self.logged(
+            OperationKind::Delete,
+            |collection| collection.remove_document(key),
+            |removed| Some(usize::from(*removed)),
+        );
+    }
+
+    /// Supprime le document `key` comme [`Collection::delete`], sans l'enregistrer dans
+    /// l'historique, et indique s'il existait.
+    pub(crate) fn remove_document(&mut self, key: &Uuid) -> bool {
+        if let Some(payload) = self.take_pending(key) {
+            self.release_blobs(*key, &payload, None);
+            self.operations.stats.deletes += 1;
+            return true;
+        }
+        let Some(vector) = self.documents.remove(key) else {
+            return false;
+        };
+        self.operations.stats.deletes += 1;
+        self.forget_stats(&vector);
+        let payload = self.take_payload(key);
+        let id = self.aliases.remove(key);
+        let namespace = self.namespaces.remove(key);
+        self.recency.remove(key);
+        self.access.remove(key);
+        if !self.config.soft_delete {
+            self.memory.vectors -= memory::vector_bytes(&vector);
+            if let Some(payload) = &payload {
+                self.release_blobs(*key, payload, None);
+            }
+        } else {
+            if let Some(payload) = &payload {
+                self.memory.payloads += memory::payload_bytes(payload);
+            }
+            self.tombstones.insert(
+                *key,
+                Tombstone {
+                    vector: Arc::unwrap_or_clone(vector),
+                    payload,
+                    id,
+                    namespace,
+                },
+            );
+        }
+        true
+    }
+
+    /// Supprime tous les documents de la collection, en conservant sa configuration.
+    ///
+    /// # Arguments
+    /// * `unlock_dimension` - Vrai pour oublier la dimension fixée par la première
+    ///   insertion (voir [`Collection::dimension`]), ainsi que l'adaptateur de requêtes
+    ///   qui y menait et, sans projection, le pipeline de requêtes qui s'y appliquait ;
+    ///   une dimension imposée par la configuration est toujours conservée.
+    pub fn clear(&mut self, unlock_dimension: bool) {
+        self.logged(
+            OperationKind::Clear,
+            |collection| {
+                let count = collection.len();
+                collection.clear_documents(unlock_dimension);
+                count
+            },
+            |count| Some(*count),
+        );
+    }
+
+    fn clear_documents(&mut self, unlock_dimension: bool) {
+        if unlock_dimension && self.locked_dimension.take().is_some() {
+            self.adapter = None;
+            if self.stored_dimension().is_none() {
+                self.config.query_pipeline = QueryPipeline::default();
+            }
+        }
+        let tombstones = (self.tombstones.iter())
+            .filter_map(|(key, tombstone)| Some((key, tombstone.payload.as_ref()?)));
+        for (key, payload) in self.payloads.iter().chain(&self.pending).chain(tombstones) {
+            self.release_blobs(*key, payload, None);
+        }
+        self.documents.clear();
+        self.pool.clear();
+        self.payloads.clear();
+        self.pending.clear();
+        self.aliases.clear();
+        self.tombstones.clear();
+        self.namespaces.clear();
+        self.clear_payload_indexes();
+        self.recency.clear();
+        self.access.clear();
+        self.memory = MemoryUsage::default();
+        self.stats = VectorStats::default();
+        self.saved.clear_pending();
+    }
+
+    /// Retourne le nombre de documents de la collection, y compris ceux qui attendent
+    /// leur vecteur ([`Collection::register`]).
+    pub fn len(&self) -> usize {
+        self.documents.len() + self.pending.len()
+    }
+
+    /// Retourne le nombre de documents qui ont un vecteur, seuls visibles des recherches.
+    pub fn searchable_len(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Indique si la collection ne contient aucun document, avec ou sans vecteur.
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty() && self.pending.is_empty()
+    }
+
+    /// Recherche les `k` documents les plus proches de la requête selon la mesure de la collection.
+    ///
+    /// Une collection vide ou `k == 0` donnent un résultat vide. Les égalités de score
+    /// sont départagées par `Uuid` croissant. Les documents dont la dimension diffère de
+    /// celle de la requête sont ignorés, sauf en mode strict. Les autres paramètres
+    /// sont les paramètres par défaut de la collection ([`Collection::set_default_params`]).
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `k` - Nombre de résultats à retourner.
+    ///
+    /// # Retourne
+    /// * Result<Document> - Liste des `k` documents les plus similaires avec leur similarité.
+    ///
+    /// # Erreurs
+    /// * `Error::ZeroVector` - Si la requête est nulle et que la collection refuse les vecteurs nuls.
+    /// * `Error::DimensionMismatch` - Si la requête n'a pas la dimension de la collection.
+    /// * `Error::InconsistentDimension` - En mode strict, si un document n'a pas la
+    ///   dimension de la requête.
+    /// * Celles de [`Collection::search_with`] liées à `require_exact_k`, si les
+    ///   paramètres par défaut l'activent.
+    pub fn search(&self, request: impl AsRef<[f32]>, k: usize) -> Result<Document> {
+        let overrides = SearchOverrides {
+            k: Some(k),
+            ..Default::default()
+        };
+        Ok(self.search_with_defaults(request, &overrides)?.hits)
+    }
+
+    /// Recherche selon `overrides`, complétés par les paramètres par défaut de la collection.
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `overrides` - Paramètres précisés par l'appelant.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search_with`].
+    pub fn search_with_defaults(
+        &self,
+        request: impl AsRef<[f32]>,
+        overrides: &SearchOverrides,
+    ) -> Result<SearchResults> {
+        self.search_with(request, &overrides.resolve(&self.default_params))
+    }
+
+    /// Recherche les `k` documents les plus proches d'une requête donnée en `f64`.
+    ///
+    /// # Erreurs
+    /// * `Error::ValueOutOfRange` - Si une coordonnée dépasse l'étendue des `f32`.
+    /// * Celles de [`Collection::search`].
+    pub fn search_f64(&self, request: &[f64], k: usize) -> Result<Document> {
+        self.search(to_f32(request)?, k)
+    }
+
+    /// Recherche les documents les plus similaires à la requête selon `params`, sans
+    /// tenir compte des paramètres par défaut de la collection.
+    ///
+    /// Avec un budget de temps, la recherche s'arrête dès qu'il est épuisé et retourne les
+    /// meilleurs documents trouvés jusque-là, avec `truncated` à `true`.
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `params` - Paramètres de la recherche.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Documents retenus avec leur similarité.
+    ///
+    /// # Erreurs
+    /// * `Error::ZeroVector` - Si la requête est nulle et que la collection refuse les vecteurs nuls.
+    /// * `Error::DimensionMismatch` - Si la requête n'a pas la dimension de la collection.
+    /// * `Error::InconsistentDimension` - En mode strict, si un document n'a pas la
+    ///   dimension de la requête.
+    /// * `Error::InvalidConfig` - Si `k == 0` alors que `params.require_exact_k` est actif.
+    /// * `Error::InsufficientCandidates` - Si `params.require_exact_k` est actif et que
+    ///   moins de `k` documents satisfont la recherche.
+    pub fn search_with(
+        &self,
+        request: impl AsRef<[f32]>,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        let results = self.search_query(request.as_ref(), params)?;
+        self.record_access(&results.hits);
+        Ok(results)
+    }
+
+    /// Recherche comme [`Collection::search_with`], sans compter les accès : pour les
+    /// recherches qui ne retournent qu'une partie des résultats obtenus.
+    pub(crate) fn search_query(
+        &self,
+        request: &[f32],
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        self.check_zero(request, None)?;
+        let request = self.prepare_query(request)?;
+        let mut results = self.search_prepared(&request, params, &[])?;
+        results.query_transforms = self.config.query_pipeline.names();
+        Ok(results)
+    }
+
+    /// Construit la combinaison linéaire `Σ poids × vecteur` de documents de la collection.
+    ///
+    /// Permet les requêtes par analogie (« roi − homme + femme ») sans relire les vecteurs :
+    /// `collection.compose(&[(roi, 1.0), (homme, -1.0), (femme, 1.0)])`. Les vecteurs
+    /// combinés sont ceux stockés, après l'éventuelle projection de la collection.
+    ///
+    /// # Arguments
+    /// * `terms` - Couples (identifiant, poids) à combiner.
+    ///
+    /// # Retourne
+    /// * Result<Vec<f32>> - Le vecteur combiné.
+    ///
+    /// # Erreurs
+    /// * `Error::EmptyQuery` - Si `terms` est vide.
+    /// * `Error::DocumentNotFound` - Si un identifiant n'existe pas.
+    /// * `Error::DimensionMismatch` - Si les vecteurs n'ont pas tous la même dimension.
+    pub fn compose(&self, terms: &[(Uuid, f32)]) -> Result<Vec<f32>> {
+        let (first, _) = terms.first().ok_or(Error::EmptyQuery)?;
+        let dimension = self
+            .documents
+            .get(first)
+            .ok_or(Error::DocumentNotFound(*first))?
+            .len();
+        let mut combined = vec![0.0_f32; dimension];
+        for (key, weight) in terms {
+            let vector = self
+                .documents
+                .get(key)
+                .ok_or(Error::DocumentNotFound(*key))?;
+            if vector.len() != dimension {
+                return Err(Error::DimensionMismatch {
+                    expected: dimension,
+                    got: vector.len(),
+                });
+            }
+            for (c, x) in combined.iter_mut().zip(vector.iter()) {
+                *c += weight * x;
+            }
+        }
+        Ok(combined)
+    }
+
+    /// Recherche les `k` documents les plus proches de la combinaison de documents
+    /// `terms`, en excluant ces documents des résultats.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search_compose_with`].
+    pub fn search_compose(&self, terms: &[(Uuid, f32)], k: usize) -> Result<Document> {
+        Ok(self
+            .search_compose_with(terms, &SearchParams::new(k), true)?
+            .hits)
+    }
+
+    /// Recherche les documents les plus proches de la combinaison de documents `terms`
+    /// selon `params`.
+    ///
+    /// Comme pour [`Collection::search_weighted`], la combinaison est normalisée avant la
+    /// recherche pour la mesure cosinus ; elle l'est toujours dans une collection
+    /// normalisée. Elle n'est pas projetée une seconde fois.
+    ///
+    /// # Arguments
+    /// * `terms` - Couples (identifiant, poids) composant la requête.
+    /// * `params` - Paramètres de la recherche.
+    /// * `exclude_sources` - Vrai pour retirer les documents de `terms` des résultats.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Documents retenus avec leur score.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::compose`].
+    /// * `Error::ZeroVector` - Si la combinaison est nulle et que la collection refuse
+    ///   les vecteurs nuls.
+    /// * `Error::InconsistentDimension` - En mode strict, si un document n'a pas la
+    ///   dimension de la combinaison.
+    pub fn search_compose_with(
+        &self,
+        terms: &[(Uuid, f32)],
+        params: &SearchParams,
+        exclude_sources: bool,
+    ) -> Result<SearchResults> {
+        let combined = self.compose(terms)?;
+        let sources: Vec<Uuid> = match exclude_sources {
+            true => terms.iter().map(|(key, _)| *key).collect(),
+            false => Vec::new(),
+        };
+        self.search_combined(combined, params, &sources)
+    }
+
+    /// Recherche selon `params` les documents les plus proches d'une combinaison de
+    /// vecteurs stockés, normalisée comme pour [`Collection::search_compose_with`] mais
+    /// pas projetée, en excluant `exclude` des résultats.
+    pub(crate) fn search_combined(
+        &self,
+        mut combined: Vec<f32>,
+        params: &SearchParams,
+        exclude: &[Uuid],
+    ) -> Result<SearchResults> {
+        self.check_zero(&combined, None)?;
+        let norm = similarity::norm(&combined);
+        if self.config.metric == Metric::Cosine && norm > 0.0 {
+            combined.iter_mut().for_each(|x| *x /= norm);
+        }
+        let combined = self.normalize(combined, None)?;
+        let results = self.search_prepared(&combined, params, exclude)?;
+        self.record_access(&results.hits);
+        Ok(results)
+    }
+
+    /// Recherche avec une requête composée de plusieurs vecteurs pondérés.
+    ///
+    /// Les vecteurs sont combinés en `Σ poids × vecteur`, puis, pour la mesure cosinus, le
+    /// résultat est normalisé avant la recherche. Un poids négatif éloigne les résultats
+    /// du vecteur correspondant.
+    ///
+    /// # Arguments
+    /// * `queries` - Couples (vecteur, poids) composant la requête.
+    /// * `params` - Paramètres de la recherche.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Documents retenus avec leur similarité.
+    ///
+    /// # Erreurs
+    /// * `Error::EmptyQuery` - Si `queries` est vide.
+    /// * `Error::DimensionMismatch` - Si les vecteurs n'ont pas tous la même dimension.
+    pub fn search_weighted(
+        &self,
+        queries: &[(Vec<f32>, f32)],
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        let dimension = queries.first().ok_or(Error::EmptyQuery)?.0.len();
+        let mut combined = vec![0.0_f32; dimension];
+        for (vector, weight) in queries {
+            if vector.len() != dimension {
+                return Err(Error::DimensionMismatch {
+                    expected: dimension,
+                    got: vector.len(),
+                });
+            }
+            for (c, x) in combined.iter_mut().zip(vector) {
+                *c += weight * x;
+            }
+        }
+        let norm = similarity::norm(&combined);
+        if self.config.metric == Metric::Cosine && norm > 0.0 {
+            combined.iter_mut().for_each(|x| *x /= norm);
+        }
+        self.search_with(&combined, params)
+    }
+
+    /// Recherche en regroupant les résultats par valeur du champ `group_by` de la charge utile.
+    ///
+    /// Les groupes sont classés selon leur meilleur résultat ; chacun contient au plus
+    /// `hits_per_group` résultats. Le seuil de score de `params` s'applique à chaque résultat,
+    /// `params.k` est ignoré.
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `group_by` - Nom du champ de regroupement.
+    /// * `groups` - Nombre maximal de groupes à retourner.
+    /// * `hits_per_group` - Nombre maximal de résultats par groupe.
+    /// * `missing` - Traitement des documents sans le champ.
+    /// * `params` - Paramètres de la recherche.
+    ///
+    /// # Retourne
+    /// * Result<Vec<Group>> - Les groupes retenus.
+    #[allow(clippy::too_many_arguments)]
+    pub fn search_grouped(
+        &self,
+        request: &[f32],
+        group_by: &str,
+        groups: usize,
+        hits_per_group: usize,
+        missing: MissingGroupField,
+        params: &SearchParams,
+    ) -> Result<Vec<Group>> {
+        let all = SearchParams {
+            k: usize::MAX,
+            ..params.clone()
+        };
+        let mut result: Vec<Group> = Vec::new();
+        let mut index: HashMap<Value, usize> = HashMap::new();
+        if groups == 0 || hits_per_group == 0 {
+            return Ok(result);
+        }
+        for (key, score) in self.search_query(request, &all)?.hits {
+            let value = self.field(&key, group_by);
+            if value.is_none() && missing == MissingGroupField::Skip {
+                continue;
+            }
+            match value.and_then(|v| index.get(v).copied()) {
+                Some(i) if result[i].hits.len() < hits_per_group => {
+                    result[i].hits.push((key, score));
+                }
+                Some(_) => {}
+                None if result.len() < groups => {
+                    if let Some(value) = value {
+                        index.insert(value.clone(), result.len());
+                    }
+                    result.push(Group {
+                        value: value.cloned(),
+                        hits: vec![(key, score)],
+                    });
+                }
+                None => {}
+            }
+            if result.len() == groups && result.iter().all(|g| g.hits.len() == hits_per_group) {
+                break;
+            }
+        }
+        for group in &result {
+            self.record_access(&group.hits);
+        }
+        Ok(result)
+    }
+
+    /// Calcule la distribution des scores de la requête sur toute la collection.
+    ///
+    /// Les scores ne sont jamais conservés en mémoire. Pour la mesure cosinus, dont les
+    /// scores sont bornés, les compartiments couvrent `[-1, 1]` et un seul parcours suffit ;
+    /// pour les autres mesures, un premier parcours détermine l'étendue des scores.
+    ///
+    /// Si les agrégats de la collection sont bruités ([`Collection::set_aggregate_noise`]),
+    /// les comptes des compartiments le sont, et seuls leur total et la médiane qui s'en
+    /// déduit sont renseignés. Hors de la mesure cosinus, les bornes des compartiments
+    /// restent celles des scores extrêmes. [`Collection::score_distribution_exact`]
+    /// retourne l'histogramme exact.
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `buckets` - Nombre de compartiments de l'histogramme.
+    ///
+    /// # Retourne
+    /// * Result<Histogram> - Histogramme et statistiques des scores.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `buckets` vaut 0.
+    /// * `Error::ZeroVector` - Si la requête est nulle et que la collection refuse les vecteurs nuls.
+    /// * `Error::InconsistentDimension` - En mode strict, si un document n'a pas la
+    ///   dimension de la requête.
+    pub fn score_distribution(&self, request: &[f32], buckets: usize) -> Result<Histogram> {
+        let mut histogram = self.score_distribution_exact(request, buckets)?;
+        if let Some(mut laplace) = self.laplace() {
+            histogram.add_noise(&mut laplace);
+        }
+        Ok(histogram)
+    }
+
+    /// Calcule la distribution des scores de la requête comme
+    /// [`Collection::score_distribution`], toujours exacte, même si les agrégats de la
+    /// collection sont bruités.
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `buckets` - Nombre de compartiments de l'histogramme.
+    ///
+    /// # Retourne
+    /// * Result<Histogram> - Histogramme et statistiques exactes des scores.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::score_distribution`].
+    pub fn score_distribution_exact(&self, request: &[f32], buckets: usize) -> Result<Histogram> {
+        if buckets == 0 {
+            return Err(Error::InvalidConfig(
+                "un histogramme doit avoir au moins un compartiment".to_string(),
+            ));
+        }
+        self.check_zero(request, None)?;
+        let request = self.prepare_query(request)?;
+        let request = request.as_ref();
+        self.check_dimensions(&self.scoped_entries(None), request.len())?;
+        let metric = self.config.metric;
+
+        let (low, high) = if metric == Metric::Cosine {
+            (-1.0, 1.0)
+        } else {
+            score_entries(self.scorer(), request, self.entries()).fold(
+                (f32::INFINITY, f32::NEG_INFINITY),
+                |(low, high), (_, score)| (low.min(score), high.max(score)),
+            )
+        };
+        if low > high {
+            return Ok(Histogram::with_range(0.0, 0.0, buckets));
+        }
+
+        let mut histogram = Histogram::with_range(low, high, buckets);
+        let (mut count, mut min, mut max, mut sum) = (0, f32::INFINITY, f32::NEG_INFINITY, 0.0);
+        for (_, score) in score_entries(self.scorer(), request, self.entries()) {
+            histogram.record(score);
+            count += 1;
+            min = min.min(score);
+            max = max.max(score);
+            sum += score as f64;
+        }
+        histogram.finish(count, min, max, sum);
+        Ok(histogram)
+    }
+
+    /// Vérifie la cohérence des structures internes de la collection.
+    ///
+    /// Cette vérification parcourt toute la collection ; elle est destinée aux tests et au
+    /// débogage, par exemple dans un `debug_assert!` après une série de modifications.
+    ///
+    /// # Retourne
+    /// * Result<()> - `Ok(())` si la collection est cohérente.
+    ///
+    /// # Erreurs
+    /// * `Error::InvariantViolation` - Décrit la première incohérence trouvée.
+    pub fn check_invariants(&self) -> Result<()> {
+        if let Some(key) = self
+            .payloads
+            .keys()
+            .find(|key| !self.documents.contains_key(key))
+        {
+            return Err(Error::InvariantViolation(format!(
+                "la charge utile {} ne correspond à aucun document",
+                key
+            )));
+        }
+        if let Some(key) = self
+            .pending
+            .keys()
+            .find(|key| self.documents.contains_key(key) || self.payloads.contains_key(key))
+        {
+            return Err(Error::InvariantViolation(format!(
+                "le document {} attend son vecteur alors qu'il en a un",
+                key
+            )));
+        }
+        for (key, id) in &self.aliases {
+            if !self.documents.contains_key(key) || id.to_uuid() != *key {
+                return Err(Error::InvariantViolation(format!(
+                    "l'identifiant {} ne correspond pas au document {}",
+                    id, key
+                )));
+            }
+        }
+        for (key, namespace) in self.namespaces.iter() {
+            let member = self
+                .namespaces
+                .members(namespace)
+                .is_some_and(|members| members.contains(key));
+            if !self.documents.contains_key(key) || !member {
+                return Err(Error::InvariantViolation(format!(
+                    "l'espace de noms {} ne correspond pas au document {}",
+                    namespace, key
+                )));
+            }
+        }
+        for (field, index) in &self.indexes {
+            if !self.payload_index_is_consistent(field, index) {
+                return Err(Error::InvariantViolation(format!(
+                    "l'index du champ {} ne correspond pas aux charges utiles",
+                    field
+                )));
+            }
+        }
+        if !self.memory_is_consistent() {
+            return Err(Error::InvariantViolation(format!(
+                "mémoire comptée {:?} au lieu de {:?}",
+                self.memory,
+                self.measure_memory()
+            )));
+        }
+        self.check_stats()?;
+        if let Some(key) = self
+            .tombstones
+            .keys()
+            .find(|key| self.documents.contains_key(key))
+        {
+            return Err(Error::InvariantViolation(format!(
+                "le document {} est à la fois vivant et supprimé",
+                key
+            )));
+        }
+        // La politique porte sur les vecteurs fournis : un vecteur projeté peut être nul.
+        for (key, vector) in self.documents.iter().filter(|_| self.projection.is_none()) {
+            if self.check_zero(vector, Some(*key)).is_err() {
+                return Err(Error::InvariantViolation(format!(
+                    "le document {} a un vecteur nul malgré la politique Reject",
+                    key
+                )));
+            }
+        }
+        if let Some(adapter) = &self.adapter {
+            if self.dimension() != Some(adapter.to_dim()) {
+                return Err(Error::InvariantViolation(format!(
+                    "l'adaptateur de requêtes produit la dimension {} au lieu de {:?}",
+                    adapter.to_dim(),
+                    self.dimension()
+                )));
+            }
+        }
+        let stored_dimension = self.stored_dimension();
+        if let Some(dimension) = stored_dimension {
+            if let Err(e) = self.config.query_pipeline.check(dimension) {
+                return Err(Error::InvariantViolation(format!(
+                    "le pipeline de requêtes ne s'applique pas à la dimension {} : {}",
+                    dimension, e
+                )));
+            }
+        }
+        for (key, vector) in &self.documents {
+            if stored_dimension.is_some_and(|dimension| vector.len() != dimension) {
+                return Err(Error::InvariantViolation(format!(
+                    "le document {} a la dimension {} malgré la dimension imposée",
+                    key,
+                    vector.len()
+                )));
+            }
+            if self.check_weighted_dimension(vector.len()).is_err() {
+                return Err(Error::InvariantViolation(format!(
+                    "le document {} a la dimension {} au lieu du nombre de poids des dimensions",
+                    key,
+                    vector.len()
+                )));
+            }
+            if self.config.normalized && (similarity::norm(vector) - 1.0).abs() > 1e-3 {
+                return Err(Error::InvariantViolation(format!(
+                    "le document {} n'est pas normalisé",
+                    key
+                )));
+            }
+            if self.config.unit_vectors && self.off_unit(vector) {
+                return Err(Error::InvariantViolation(format!(
+                    "le document {} n'est pas unitaire malgré la déclaration de la collection",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// En mode strict, signale le document de `entries` de plus petit `Uuid` dont la
+    /// dimension n'est pas `dimension`.
+    pub(crate) fn check_dimensions(
+        &self,
+        entries: &[(&Uuid, &[f32])],
+        dimension: usize,
+    ) -> Result<()> {
+        if !self.config.strict_dimensions {
+            return Ok(());
+        }
+        match entries
+            .iter()
+            .filter(|(_, vector)| vector.len() != dimension)
+            .min_by_key(|(key, _)| **key)
+        {
+            Some((key, vector)) => Err(Error::InconsistentDimension {
+                key: **key,
+                expected: dimension,
+                got: vector.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Recherche à partir d'une requête déjà projetée et normalisée, en ignorant les
+    /// documents de `exclude`.
+    fn search_prepared(
+        &self,
+        request: &[f32],
+        params: &SearchParams,
+        exclude: &[Uuid],
+    ) -> Result<SearchResults> {
+        let mut watch = Stopwatch::start(params);
+        let entries = self.scoped_entries(params.namespace.as_deref());
+        if let Some(watch) = &mut watch {
+            watch.timing.filter_us = watch.lap();
+        }
+        self.search_entries(request, &entries, params, exclude, watch)
+    }
+
+    /// Recherche parmi `entries` à partir d'une requête déjà projetée et normalisée, en
+    /// ignorant les documents de `exclude`.
+    ///
+    /// `watch` a déjà mesuré la sélection de `entries`, s'il est fourni.
+    pub(crate) fn search_entries(
+        &self,
+        request: &[f32],
+        entries: &[(&Uuid, &[f32])],
+        params: &SearchParams,
+        exclude: &[Uuid],
+        mut watch: Option<Stopwatch>,
+    ) -> Result<SearchResults> {
+        params.check()?;
+        self.check_search_model(params)?;
+        let calibration = self.score_calibration(params)?;
+        self.check_dimensions(entries, request.len())?;
+        let masked = self.masked_weights(params, request.len())?;
+        let scorer = self.masked_scorer(masked.as_deref());
+        let entries = sample(entries, params);
+        let deadline = params.time_budget.map(|budget| Instant::now() + budget);
+        let (mut hits, truncated, scored) = scan(
+            self.runtime,
+            scorer,
+            request,
+            &entries,
+            params.score_threshold,
+            deadline,
+        );
+        if !exclude.is_empty() {
+            hits.retain(|(key, _)| !exclude.contains(key));
+        }
+        if let Some(decay) = &params.decay {
+            decay.apply(
+                self.config.metric,
+                &mut hits,
+                params.deterministic_math,
+                |key| self.payloads.get(key),
+            );
+        }
+        if let Some(watch) = &mut watch {
+            watch.timing.scoring_us = watch.lap();
+            watch.timing.candidates_scored = scored;
+        }
+        let available = hits.len();
+        let available = deduplicate(scorer, &mut hits, available, params, |key| {
+            self.documents.get(key).map(|vector| vector.as_slice())
+        });
+        let mut results = finish(
+            self.config.metric,
+            hits,
+            available,
+            truncated,
+            params,
+            watch,
+        )?;
+        if let Some(calibration) = calibration {
+            for (_, score) in &mut results.hits {
+                *score = calibration.probability_with(*score, params.deterministic_math);
+            }
+        }
+        results.attach(
+            params,
+            |key| self.payloads.get(key),
+            |key| self.documents.get(key).cloned(),
+        );
+        self.inflate_results(&mut results)?;
+        Ok(results)
+    }
+
+    /// Calcule le score de tous les documents de même dimension que la requête.
+    pub(crate) fn score_all(
+        &self,
+        request: &[f32],
+        threshold: Option<f32>,
+        deadline: Option<Instant>,
+    ) -> (Document, bool) {
+        let entries: Vec<(&Uuid, &[f32])> = self.entries().collect();
+        let (hits, truncated, _) = scan(
+            self.runtime,
+            self.scorer(),
+            request,
+            &entries,
+            threshold,
+            deadline,
+        );
+        (hits, truncated)
+    }
+
+    /// Boucle de score brute des recherches, sans préparation de la requête ni
+    /// classement : point d'entrée des mesures de performance, hors API stable.
+    #[doc(hidden)]
+    pub fn raw_scan(&self, request: &[f32]) -> Document {
+        self.score_all(request, None, None).0
+    }
+
+    /// Parcourt les documents sous forme de couples (clé, vecteur).
+    fn entries(&self) -> impl Iterator<Item = (&Uuid, &[f32])> {
+        self.documents
+            .iter()
+            .map(|(key, vector)| (key, vector.as_slice()))
+    }
+
+    /// Retourne les documents de l'espace de noms `namespace`, ou tous les documents.
+    ///
+    /// Seuls les membres de l'espace de noms sont parcourus, pas toute la collection.
+    pub(crate) fn scoped_entries(&self, namespace: Option<&str>) -> Vec<(&Uuid, &[f32])> {
+        let Some(namespace) = namespace else {
+            return self.entries().collect();
+        };
+        self.namespaces
+            .members(namespace)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| {
+                let vector = self.documents.get(key)?;
+                Some((key, vector.as_slice()))
+            })
+            .collect()
+    }
+
+    /// Vérifie un vecteur à insérer puis lui applique la projection et la normalisation
+    /// éventuelles de la collection.
+    pub(crate) fn prepare_vector(&self, key: Uuid, vector: Vec<f32>) -> Result<Vec<f32>> {
+        if let Some(locked) = self.locked_dimension {
+            if self.config.dimension.is_none() && vector.len() != locked {
+                return Err(Error::DimensionLocked {
+                    key,
+                    locked,
+                    got: vector.len(),
+                });
+            }
+        }
+        self.check_dimension(vector.len())?;
+        self.check_zero(&vector, Some(key))?;
+        self.check_norm_range(&vector, key)?;
+        let vector = match &self.projection {
+            Some(projection) => projection.apply(&vector)?,
+            None => vector,
+        };
+        self.check_weighted_dimension(vector.len())?;
+        let vector = self.normalize(vector, Some(key))?;
+        self.check_unit(&vector, key)?;
+        Ok(vector)
+    }
+
+    /// Adapte une requête d'une autre dimension si la collection le permet, vérifie sa
+    /// dimension puis lui applique la projection, le pipeline de requêtes et la
+    /// normalisation éventuels de la collection.
+    pub(crate) fn prepare_query<'a>(&self, request: &'a [f32]) -> Result<Cow<'a, [f32]>> {
+        let request = self.adapt_query(request)?;
+        self.check_dimension(request.len())?;
+        let request = match &self.projection {
+            Some(projection) => Cow::Owned(projection.apply(&request)?),
+            None => request,
+        };
+        self.check_weighted_dimension(request.len())?;
+        let pipeline = &self.config.query_pipeline;
+        let request = match pipeline.is_empty() {
+            true => request,
+            false => Cow::Owned(pipeline.apply(request.into_owned(), self)?),
+        };
+        if self.config.normalized {
+            return Ok(Cow::Owned(self.normalize(request.into_owned(), None)?));
+        }
+        if self.scoring_metric() != self.config.metric {
+            return Ok(Cow::Owned(unit_norm::unit_query(request.into_owned())));
+        }
+        Ok(request)
+    }
+
+    /// Vérifie qu'un vecteur fourni a la dimension imposée par la configuration ou fixée
+    /// par la première insertion.
+    fn check_dimension(&self, got: usize) -> Result<()> {
+        match self.dimension() {
+            Some(expected) if expected != got => Err(Error::DimensionMismatch { expected, got }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fixe la dimension de la collection à `dimension` si le document qui vient d'être
+    /// inséré est le seul et qu'aucune dimension n'est encore fixée.
+    pub(crate) fn lock_dimension(&mut self, dimension: usize) {
+        if self.dimension().is_none() && self.documents.len() == 1 {
+            self.locked_dimension = Some(dimension);
+        }
+    }
+
+    /// Fixe la dimension d'une collection chargée sans dimension sauvegardée, si tous ses
+    /// documents en ont une seule.
+    pub(crate) fn infer_locked_dimension(&mut self) {
+        if self.config.dimension.is_some() || self.documents.is_empty() {
+            return;
+        }
+        self.locked_dimension = match &self.projection {
+            Some(projection) => Some(projection.input_dim()),
+            None => {
+                let mut dimensions = self.documents.values().map(|vector| vector.len());
+                let first = dimensions.next();
+                first.filter(|first| dimensions.all(|dimension| dimension == *first))
+            }
+        };
+    }
+
+    /// Ramène `vector` à une norme de 1 si la collection est normalisée.
+    fn normalize(&self, mut vector: Vec<f32>, key: Option<Uuid>) -> Result<Vec<f32>> {
+        if !self.config.normalized {
+            return Ok(vector);
+        }
+        let norm = similarity::norm_f64(&vector);
+        if norm == 0.0 {
+            return Err(Error::ZeroVector { key });
+        }
+        (vector.iter_mut()).for_each(|x| *x = (f64::from(*x) / norm) as f32);
+        Ok(vector)
+    }
+
+    /// Vérifie qu'un vecteur est acceptable au regard de la politique sur les vecteurs nuls.
+    pub(crate) fn check_zero(&self, vector: &[f32], key: Option<Uuid>) -> Result<()> {
+        check_zero_vector(
+            self.config.metric,
+            self.config.zero_vector_policy,
+            vector,
+            key,
+        )
+    }
+}
+
+/// Convertit un vecteur `f64` en `f32`, en refusant les valeurs finies hors d'étendue.
+fn to_f32(values: &[f64]) -> Result<Vec<f32>> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let converted = *value as f32;
+            if value.is_finite() && converted.is_infinite() {
+                return Err(Error::ValueOutOfRange {
+                    index,
+                    value: *value,
+                });
+            }
+            Ok(converted)
+        })
+        .collect()
+}
+
+/// Nombre d'identifiants affichés par le `Debug` d'une collection.
+const DEBUG_IDS: usize = 5;
+
+impl fmt::Debug for Collection {
+    /// Affiche un résumé borné de la collection : nombre de documents, dimension, mesure
+    /// et plus petits identifiants, sans jamais afficher les vecteurs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first_ids: Vec<&Uuid> = Vec::with_capacity(DEBUG_IDS + 1);
+        for key in self.documents.keys() {
+            let at = first_ids.partition_point(|id| *id < key);
+            if at < DEBUG_IDS {
+                first_ids.insert(at, key);
+                first_ids.truncate(DEBUG_IDS);
+            }
+        }
+        f.debug_struct("Collection")
+            .field("len", &self.documents.len())
+            .field("dimension", &self.validate().dimension)
+            .field("metric", &self.config.metric)
+            .field("default_params", &self.default_params)
+            .field("tombstones", &self.tombstones.len())
+            .field("namespaces", &self.namespaces().len())
+            .field("first_ids", &first_ids)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Vérifie qu'un vecteur est acceptable pour `metric` au regard de `policy`.
+pub(crate) fn check_zero_vector(
+    metric: Metric,
+    policy: ZeroVectorPolicy,
+    vector: &[f32],
+    key: Option<Uuid>,
+) -> Result<()> {
+    if metric == Metric::Cosine
+        && policy == ZeroVectorPolicy::Reject
+        && vector.iter().all(|x| *x == 0.0)
+    {
+        return Err(Error::ZeroVector { key });
+    }
+    Ok(())
+}
+
+/// Calcule le score de toutes les entrées de même dimension que la requête.
+///
+/// Au-delà de `PARALLEL_THRESHOLD` entrées, le parcours est réparti sur les threads
+/// de `runtime`. Si `deadline` est fourni, chaque thread s'arrête dès qu'elle est
+/// dépassée ; le booléen retourné indique alors que le parcours est incomplet. Le
+/// dernier élément est le nombre d'entrées évaluées.
+pub(crate) fn scan(
+    runtime: SearchRuntime,
+    scorer: Scorer<'_>,
+    request: &[f32],
+    entries: &[(&Uuid, &[f32])],
+    threshold: Option<f32>,
+    deadline: Option<Instant>,
+) -> (Document, bool, usize) {
+    let score_chunk = |entries: &[(&Uuid, &[f32])]| -> (Document, bool, usize) {
+        let mut scored = Document::new();
+        let mut evaluated = 0;
+        for batch in entries.chunks(BUDGET_CHECK_INTERVAL) {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return (scored, true, evaluated);
+            }
+            scored.extend(
+                score_entries(scorer, request, batch.iter().copied())
+                    .filter(|(_, score)| threshold.is_none_or(|t| scorer.metric.passes(*score, t))),
+            );
+            evaluated += batch.len();
+        }
+        (scored, false, evaluated)
+    };
+
+    let parts = runtime.map_chunks(entries, PARALLEL_THRESHOLD, |chunk| {
+        vec![score_chunk(chunk)]
+    });
+    let mut scored = Document::new();
+    let mut truncated = false;
+    let mut evaluated = 0;
+    for (chunk, chunk_truncated, chunk_evaluated) in parts {
+        scored.extend(chunk);
+        truncated |= chunk_truncated;
+        evaluated += chunk_evaluated;
+    }
+    (scored, truncated, evaluated)
+}
+
+/// Retourne les entrées de l'échantillon de `params.sample_fraction`, ou toutes.
+pub(crate) fn sample<'e, 'a>(
+    entries: &'e [(&'a Uuid, &'a [f32])],
+    params: &SearchParams,
+) -> Cow<'e, [(&'a Uuid, &'a [f32])]> {
+    match params.sample_fraction {
+        None => Cow::Borrowed(entries),
+        Some(_) => Cow::Owned(
+            (entries.iter().copied())
+                .filter(|(key, _)| params.samples(key))
+                .collect(),
+        ),
+    }
+}
+
+/// Trie les résultats du meilleur au moins bon selon `metric`, les égalités par `Uuid`
+/// croissant, et ne garde que les `k` premiers.
+pub(crate) fn rank(metric: Metric, hits: &mut Document, k: usize) {
+    hits.sort_by(|a, b| metric.compare(a.1, b.1).then_with(|| a.0.cmp(&b.0)));
+    hits.truncate(k);
+}
+
+/// Écarte de `hits` les quasi-doublons d'un résultat mieux classé, si
+/// `params.dedup_results` le demande, et retourne le nombre de documents disponibles
+/// qui en découle (voir [`SearchParams::dedup_results`]).
+///
+/// Les résultats gardés sont classés et au plus `params.k` ; sans seuil, `hits` et
+/// `available` sont laissés tels quels.
+///
+/// # Arguments
+/// * `scorer` - Score entre deux vecteurs stockés.
+/// * `hits` - Documents retenus par la recherche, avec leur score.
+/// * `available` - Nombre de documents qui satisfaisaient la recherche.
+/// * `params` - Paramètres de la recherche.
+/// * `vector` - Vecteur stocké d'un document.
+pub(crate) fn deduplicate<'a>(
+    scorer: Scorer<'_>,
+    hits: &mut Document,
+    available: usize,
+    params: &SearchParams,
+    vector: impl Fn(&Uuid) -> Option<&'a [f32]>,
+) -> usize {
+    let Some(threshold) = params.dedup_results else {
+        return available;
+    };
+    let pool = params.k.saturating_mul(params.dedup_pool_factor.max(1));
+    rank(scorer.metric, hits, pool);
+    let mut kept = Document::with_capacity(params.k.min(hits.len()));
+    let mut kept_vectors: Vec<&[f32]> = Vec::with_capacity(params.k.min(hits.len()));
+    let mut dropped = 0;
+    for (key, score) in hits.drain(..) {
+        if kept.len() == params.k {
+            break;
+        }
+        let candidate = vector(&key).unwrap_or_default();
+        let duplicate = kept_vectors.iter().any(|other| {
+            other.len() == candidate.len()
+                && scorer
+                    .metric
+                    .passes(scorer.score_unchecked(other, candidate), threshold)
+        });
+        if duplicate {
+            dropped += 1;
+            continue;
+        }
+        kept.push((key, score));
+        kept_vectors.push(candidate);
+    }
+    *hits = kept;
+    match hits.len() < params.k {
+        true => hits.len(),
+        false => available - dropped,
+    }
+}
+
+/// Classe les résultats d'une recherche, ne garde que les `params.k` premiers et leur
+/// applique le mode de score.
+///
+/// C'est la fin commune des recherches de tous les types de collection : `available`
+/// est le nombre de documents qui satisfaisaient la recherche avant la limite à `k`, et
+/// `watch` a mesuré les étapes précédentes s'il est fourni.
+///
+/// # Erreurs
+/// * `Error::InsufficientCandidates` - Si `params.require_exact_k` est actif et que
+///   `available` est inférieur à `params.k`.
+pub(crate) fn finish(
+    metric: Metric,
+    mut hits: Document,
+    available: usize,
+    truncated: bool,
+    params: &SearchParams,
+    mut watch: Option<Stopwatch>,
+) -> Result<SearchResults> {
+    if params.require_exact_k && available < params.k {
+        return Err(Error::InsufficientCandidates {
+            requested: params.k,
+            available,
+        });
+    }
+    rank(metric, &mut hits, params.k);
+    let margin = Margin::of(metric.higher_is_better(), &hits);
+    params
+        .score_mode
+        .apply(metric, &mut hits, params.deterministic_math);
+    if let Some(watch) = &mut watch {
+        watch.timing.topk_us = watch.lap();
+    }
+    Ok(SearchResults {
+        hits,
+        truncated,
+        requested_k: params.k,
+        available,
+        timing: watch.map(Stopwatch::stop),
+        payloads: None,
+        vectors: None,
+        sampled: params.sample_fraction.is_some(),
+        estimated_total_matches: params
+            .sample_fraction
+            .filter(|_| params.score_threshold.is_some())
+            .map(|fraction| (available as f64 / f64::from(fraction)).round() as usize),
+        margin,
+        low_confidence: LowConfidence::check(margin, params.min_margin),
+        plan: None,
+        query_transforms: Vec::new(),
+        context: params.context.clone(),
+    })
+}
+
+/// Calcule le score de chaque document de même dimension que la requête.
+///
+/// C'est la boucle de score commune à la recherche et aux statistiques de scores.
+pub(crate) fn score_entries<'a>(
+    scorer: Scorer<'a>,
+    request: &'a [f32],
+    entries: impl Iterator<Item = (&'a Uuid, &'a [f32])> + 'a,
+) -> impl Iterator<Item = (Uuid, f32)> + 'a {
+    entries
+        .filter(move |(_, vector)| vector.len() == request.len())
+        .map(move |(key, vector)| (*key, scorer.score_unchecked(request, vector)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::loader::CollectionLoader;
+    use crate::sharded::ShardedCollection;
+    use crate::store::{VectorStore, VectorStoreMut};
+    use crate::synthetic::VectorGenerator;
+
+    #[test]
+    fn zero_vectors_are_refused_by_default() {
+        let mut collection = Collection::new();
+        let key = Uuid::new_v4();
+        assert!(matches!(
+            collection.upsert(key, [0.0, 0.0]),
+            Err(Error::ZeroVector { key: Some(k) }) if k == key
+        ));
+        assert!(collection.is_empty());
+        collection.upsert(key, [1.0, 0.0]).unwrap();
+        assert!(matches!(
+            collection.search([0.0, 0.0], 1),
+            Err(Error::ZeroVector { key: None })
+        ));
+    }
+
+    #[test]
+    fn zero_vectors_score_zero_in_uuid_order() {
+        let mut collection = Collection::new().with_zero_vector_policy(ZeroVectorPolicy::ScoreZero);
+        let zero = Uuid::new_v4();
+        collection.upsert(zero, [0.0, 0.0]).unwrap();
+        let mut keys = vec![zero];
+        for i in 0..3 {
+            keys.push(Uuid::new_v4());
+            collection.upsert(keys[i + 1], [1.0, i as f32]).unwrap();
+        }
+        // Une requête nulle donne 0.0 à tous les documents, rangés par Uuid croissant.
+        let hits = collection.search([0.0, 0.0], 4).unwrap();
+        keys.sort();
+        assert_eq!(hits, keys.iter().map(|key| (*key, 0.0)).collect::<Vec<_>>());
+
+        // Le document nul reçoit 0.0 face à une requête non nulle.
+        let hits = collection.search([1.0, 0.0], 4).unwrap();
+        assert_eq!(hits.last(), Some(&(zero, 0.0)));
+    }
+
+    #[test]
+    fn other_metrics_accept_zero_vectors() {
+        let config = CollectionConfig::builder().metric(Metric::Euclidean).build().unwrap();
+        let mut collection = Collection::from_config(config);
+        let key = Uuid::new_v4();
+        collection.upsert(key, [0.0, 0.0]).unwrap();
+        assert_eq!(collection.search([0.0, 0.0], 1).unwrap(), [(key, 0.0)]);
+    }
+
+    #[test]
+    fn empty_collection_and_zero_k_give_empty_results() {
+        let mut collection = Collection::new();
+        assert!(collection.search([1.0, 2.0], 10).unwrap().is_empty());
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        assert!(collection.search([1.0, 2.0], 0).unwrap().is_empty());
+        assert_eq!(collection.search([1.0, 2.0], 10).unwrap().len(), 1);
+    }
+
+    /// Collection de `sources` documents découpés chacun en `chunks` morceaux voisins,
+    /// marqués par le champ `source`, et d'un morceau sans le champ.
+    fn chunked(sources: usize, chunks: usize) -> (Collection, Vec<Vec<f32>>, Uuid) {
+        let mut generator = VectorGenerator::new(8, 3);
+        let mut collection = Collection::new();
+        let mut centers = Vec::new();
+        for source in 0..sources {
+            let center = generator.vector();
+            for _ in 0..chunks {
+                let mut payload = Payload::new();
+                payload.insert("source".to_string(), Value::Number(source as f64));
+                let vector = generator.vector_near(&center, 0.05);
+                (collection.upsert_with_payload(generator.uuid(), vector, payload)).unwrap();
+            }
+            centers.push(center);
+        }
+        let orphan = generator.uuid();
+        collection.upsert(orphan, centers[0].clone()).unwrap();
+        (collection, centers, orphan)
+    }
+
+    #[test]
+    fn grouped_search_keeps_the_best_chunks_of_each_source() {
+        let (collection, centers, orphan) = chunked(4, 6);
+        let params = SearchParams::default();
+        let groups = (collection.search_grouped(&centers[0], "source", 3, 2, MissingGroupField::Skip, &params))
+            .unwrap();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].value, Some(Value::Number(0.0)));
+        let all = collection.search(&centers[0], usize::MAX).unwrap();
+        for (i, group) in groups.iter().enumerate() {
+            // Les meilleurs morceaux de la source, dans l'ordre de la recherche complète.
+            let expected: Vec<(Uuid, f32)> = (all.iter())
+                .filter(|(key, _)| collection.field(key, "source") == group.value.as_ref())
+                .take(2)
+                .copied()
+                .collect();
+            assert_eq!(group.hits, expected);
+            if i > 0 {
+                assert!(groups[i - 1].hits[0].1 >= group.hits[0].1);
+            }
+        }
+        assert!(groups.iter().all(|group| group.hits.iter().all(|(key, _)| *key != orphan)));
+    }
+
+    #[test]
+    fn documents_without_the_field_form_singleton_groups() {
+        let (collection, centers, orphan) = chunked(3, 4);
+        let params = SearchParams::default();
+        let groups = (collection.search_grouped(&centers[0], "source", 10, 10, MissingGroupField::Singleton, &params))
+            .unwrap();
+        // Le morceau orphelin, identique au centre de la source 0, vient en tête.
+        assert_eq!(groups[0].value, None);
+        assert_eq!(groups[0].hits, [(orphan, collection.search(&centers[0], 1).unwrap()[0].1)]);
+        assert_eq!(groups.len(), 4);
+        assert!(groups[1..].iter().all(|group| group.hits.len() == 4));
+        assert!((collection.search_grouped(&centers[0], "source", 0, 3, MissingGroupField::Singleton, &params))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn upsert_and_search_accept_arrays_slices_and_vectors() {
+        let mut collection = Collection::new();
+        let keys: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let slice: &[f32] = &[1.0, 0.0, 4.0];
+        collection.upsert(keys[0], [1.0, 0.0, 0.0]).unwrap();
+        collection.upsert(keys[1], [1.0, 0.0, 1.0].to_vec()).unwrap();
+        collection.upsert(keys[2], vec![1.0, 0.0, 2.0]).unwrap();
+        collection.upsert(keys[3], slice).unwrap();
+        collection.upsert(keys[4], vec![1.0, 0.0, 3.0].into_boxed_slice()).unwrap();
+        assert_eq!(collection.len(), 5);
+
+        let query = vec![1.0, 0.0, 0.0];
+        let expected = collection.search(&query, 5).unwrap();
+        assert_eq!(expected[0].0, keys[0]);
+        assert_eq!(collection.search([1.0, 0.0, 0.0], 5).unwrap(), expected);
+        assert_eq!(collection.search(query.as_slice(), 5).unwrap(), expected);
+        assert_eq!(collection.search(query, 5).unwrap(), expected);
+    }
+
+    #[test]
+    fn f64_inputs_are_converted_with_a_range_check() {
+        let mut collection = Collection::new();
+        let key = Uuid::new_v4();
+        collection.upsert_f64(key, &[0.5, 0.25]).unwrap();
+        assert_eq!(collection.read(&key).unwrap(), &[0.5, 0.25]);
+        assert_eq!(collection.search_f64(&[1.0, 0.5], 1).unwrap()[0].0, key);
+
+        let too_big = f64::from(f32::MAX) * 2.0;
+        assert!(matches!(
+            collection.upsert_f64(Uuid::new_v4(), &[1.0, too_big]),
+            Err(Error::ValueOutOfRange { index: 1, value }) if value == too_big
+        ));
+        assert!(matches!(
+            collection.search_f64(&[-too_big, 1.0], 1),
+            Err(Error::ValueOutOfRange { index: 0, .. })
+        ));
+        assert_eq!(collection.len(), 1);
+    }
+
+    #[test]
+    fn searches_see_the_patched_coordinates() {
+        let mut collection = Collection::new();
+        let (near, far) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert(near, [1.0, 0.0, 0.0, 0.0]).unwrap();
+        collection.upsert(far, [0.0, 1.0, 0.0, 0.0]).unwrap();
+        let query = [0.0, 0.0, 1.0, 1.0];
+        collection.patch_vector(far, 2, &[1.0, 1.0]).unwrap();
+        assert_eq!(collection.read(&far).unwrap(), &[0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(collection.search(query, 1).unwrap()[0].0, far);
+
+        // La plage vide est admise, jusqu'en fin de vecteur.
+        collection.patch_vector(near, 4, &[]).unwrap();
+        collection.patch_vector(near, 1, &[0.0, 2.0, 2.0]).unwrap();
+        assert_eq!(collection.read(&near).unwrap(), &[1.0, 0.0, 2.0, 2.0]);
+        assert_eq!(collection.search(query, 1).unwrap()[0].0, near);
+    }
+
+    #[test]
+    fn patch_errors_leave_the_vector_unchanged() {
+        let mut collection = Collection::new();
+        let key = Uuid::new_v4();
+        collection.upsert(key, [1.0, 2.0, 3.0]).unwrap();
+        let missing = Uuid::new_v4();
+        assert!(matches!(
+            collection.patch_vector(missing, 0, &[1.0]),
+            Err(Error::DocumentNotFound(k)) if k == missing
+        ));
+        assert!(matches!(
+            collection.patch_vector(key, 2, &[1.0, 1.0]),
+            Err(Error::RangeOutOfBounds { offset: 2, len: 2, dimension: 3 })
+        ));
+        assert!(matches!(
+            collection.patch_vector(key, usize::MAX, &[1.0]),
+            Err(Error::RangeOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            collection.patch_vector(key, 0, &[0.0, 0.0, 0.0]),
+            Err(Error::ZeroVector { key: Some(k) }) if k == key
+        ));
+        assert_eq!(collection.read(&key).unwrap(), &[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn patching_a_shared_vector_keeps_the_other_documents() {
+        let mut collection = Collection::new().with_shared_vectors(true);
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert(a, [1.0, 2.0]).unwrap();
+        collection.upsert(b, [1.0, 2.0]).unwrap();
+        collection.patch_vector(a, 1, &[5.0]).unwrap();
+        assert_eq!(collection.read(&a).unwrap(), &[1.0, 5.0]);
+        assert_eq!(collection.read(&b).unwrap(), &[1.0, 2.0]);
+        collection.check_invariants().unwrap();
+    }
+
+    fn locked(result: Result<impl fmt::Debug>, key: Uuid) {
+        match result {
+            Err(error) => assert!(
+                matches!(error.root(), Error::DimensionLocked { key: k, locked: 3, got: 5 } if *k == key),
+                "{}",
+                error
+            ),
+            Ok(value) => panic!("insertion acceptée : {:?}", value),
+        }
+    }
+
+    #[test]
+    fn first_insert_locks_the_dimension_until_an_unlocking_clear() {
+        let mut collection = Collection::new();
+        // Une insertion refusée ne fixe rien.
+        assert!(collection.upsert(Uuid::new_v4(), [0.0; 5]).is_err());
+        assert_eq!(collection.dimension(), None);
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(collection.dimension(), Some(3));
+        let key = Uuid::new_v4();
+        locked(collection.upsert(key, [1.0; 5]), key);
+        locked(collection.insert(key, [1.0; 5]), key);
+        assert!(matches!(
+            collection.search([1.0; 5], 1),
+            Err(Error::DimensionMismatch {
+                expected: 3,
+                got: 5
+            })
+        ));
+
+        collection.clear(false);
+        locked(collection.upsert(key, [1.0; 5]), key);
+        collection.clear(true);
+        collection.upsert(key, [1.0; 5]).unwrap();
+        assert_eq!(collection.dimension(), Some(5));
+
+        // Une dimension configurée reste celle de la configuration.
+        let config = CollectionConfig::builder().dimension(4).build().unwrap();
+        let mut configured = Collection::from_config(config);
+        configured.clear(true);
+        assert!(matches!(
+            configured.upsert(key, [1.0; 5]),
+            Err(Error::DimensionMismatch {
+                expected: 4,
+                got: 5
+            })
+        ));
+        assert_eq!(configured.dimension(), Some(4));
+    }
+
+    #[test]
+    fn batches_and_importers_are_locked_by_their_first_valid_row() {
+        let key = Uuid::new_v4();
+        let rows = |bad: Uuid| vec![(Uuid::new_v4(), vec![1.0; 3]), (Uuid::new_v4(), vec![2.0; 3]), (bad, vec![1.0; 5])];
+
+        let mut collection = Collection::new();
+        locked(collection.upsert_batch(rows(key), None), key);
+        assert_eq!(collection.len(), 2);
+
+        let mut loader = CollectionLoader::new(CollectionConfig::default());
+        for (id, vector) in rows(key) {
+            loader.push_rows(&[id], &vector, vector.len()).unwrap();
+        }
+        locked(loader.finish(), key);
+
+        let lines: String = (rows(key).into_iter())
+            .map(|(id, vector)| format!("{{\"id\": \"{}\", \"vector\": {:?}}}\n", id, vector))
+            .collect();
+        let mut imported = Collection::new();
+        locked(imported.import_jsonl(lines.as_bytes(), None), key);
+        assert_eq!((imported.len(), imported.dimension()), (2, Some(3)));
+
+        let mut sharded = ShardedCollection::new(4).unwrap();
+        for (id, vector) in rows(key) {
+            match vector.len() {
+                3 => VectorStoreMut::upsert(&mut sharded, id, vector).unwrap(),
+                _ => locked(VectorStoreMut::upsert(&mut sharded, id, vector), key),
+            }
+        }
+        assert_eq!(sharded.dimension(), Some(3));
+    }
+
+    #[test]
+    fn the_locked_dimension_survives_a_save() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        db.get_mut("docs").unwrap().upsert(Uuid::new_v4(), [1.0; 3]).unwrap();
+        let mut loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let docs = loaded.get_mut("docs").unwrap();
+        assert_eq!(docs.dimension(), Some(3));
+        let key = Uuid::new_v4();
+        locked(docs.upsert(key, [1.0; 5]), key);
+    }
+
+    #[test]
+    fn read_arc_shares_the_vector_and_writes_replace_it() {
+        let mut collection = Collection::new();
+        let mut sharded = ShardedCollection::new(2).unwrap();
+        let key = Uuid::new_v4();
+        collection.upsert(key, [1.0, 2.0, 3.0]).unwrap();
+        VectorStoreMut::upsert(&mut sharded, key, vec![1.0, 2.0, 3.0]).unwrap();
+
+        let (first, second) = (collection.read_arc(&key).unwrap(), collection.read_arc(&key).unwrap());
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&sharded.read_arc(&key).unwrap(), &sharded.read_arc(&key).unwrap()));
+        assert!(collection.read_arc(&Uuid::new_v4()).is_none());
+
+        collection.patch_vector(key, 1, &[5.0]).unwrap();
+        assert_eq!(*first, [1.0, 2.0, 3.0]);
+        assert_eq!(*collection.read_arc(&key).unwrap(), [1.0, 5.0, 3.0]);
+        let patched = collection.read_arc(&key).unwrap();
+        collection.upsert(key, [7.0, 8.0, 9.0]).unwrap();
+        assert_eq!(*patched, [1.0, 5.0, 3.0]);
+        assert!(!Arc::ptr_eq(&patched, &collection.read_arc(&key).unwrap()));
+    }
+
+    #[test]
+    fn near_duplicates_are_dropped_and_the_pool_bounds_the_survivors() {
+        // Trois groupes de cinq copies presque identiques, de moins en moins proches
+        // de la requête.
+        let mut collection = Collection::new();
+        let mut sharded = ShardedCollection::new(3).unwrap();
+        let mut groups: Vec<Vec<Uuid>> = vec![Vec::new(); 3];
+        for (g, group) in groups.iter_mut().enumerate() {
+            for copy in 0..5 {
+                let (key, vector) = (Uuid::new_v4(), vec![1.0, g as f32, 1e-4 * copy as f32]);
+                collection.upsert(key, vector.clone()).unwrap();
+                VectorStoreMut::upsert(&mut sharded, key, vector).unwrap();
+                group.push(key);
+            }
+        }
+        let group_of = |key: &Uuid| groups.iter().position(|g| g.contains(key)).unwrap();
+        let query = [1.0, 0.0, 0.0];
+        let params = |dedup_pool_factor| SearchParams {
+            dedup_results: Some(0.9999),
+            dedup_pool_factor,
+            ..SearchParams::new(3)
+        };
+
+        let plain = collection.search(query, 3).unwrap();
+        assert!(plain.iter().all(|(key, _)| group_of(key) == 0));
+        for results in [
+            collection.search_with(query, &params(4)).unwrap(),
+            VectorStore::search_with(&sharded, &query, &params(4)).unwrap(),
+        ] {
+            let found: Vec<usize> = results.hits.iter().map(|(key, _)| group_of(key)).collect();
+            assert_eq!(found, [0, 1, 2]);
+            // Les huit doublons écartés ne comptent pas parmi les documents disponibles.
+            assert_eq!(results.available, 15 - 8);
+        }
+        // Une réserve de k candidats, tous du premier groupe, ne laisse qu'un résultat.
+        let results = collection.search_with(query, &params(1)).unwrap();
+        assert_eq!(results.hits.len(), 1);
+        assert_eq!(results.available, 1);
+        assert!(matches!(
+            collection.search_with(query, &SearchParams {
+                dedup_results: Some(f32::NAN),
+                ..SearchParams::new(3)
+            }),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn deduplicating_with_a_huge_k_keeps_every_distinct_document() {
+        let mut collection = Collection::new();
+        let mut sharded = ShardedCollection::new(3).unwrap();
+        for vector in [[1.0, 0.0], [1.0, 1e-5], [0.0, 1.0]] {
+            let key = Uuid::new_v4();
+            collection.upsert(key, vector).unwrap();
+            VectorStoreMut::upsert(&mut sharded, key, vector.to_vec()).unwrap();
+        }
+        let params = SearchParams {
+            dedup_results: Some(0.9999),
+            ..SearchParams::new(usize::MAX)
+        };
+        for results in [
+            collection.search_with([1.0, 0.0], &params).unwrap(),
+            VectorStore::search_with(&sharded, &[1.0, 0.0], &params).unwrap(),
+        ] {
+            assert_eq!(results.hits.len(), 2);
+            assert_eq!(results.available, 2);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use crate::collection::ZeroVectorPolicy;
+use crate::error::{Error, Result};
+use crate::model::ModelFingerprint;
+use crate::privacy::{self, AggregateNoise};
+use crate::query_pipeline::QueryPipeline;
+use crate::similarity::Metric;
+use crate::weights::{self, Weights};
+
+/// Configuration d'une collection, fixée à sa création.
+///
+/// Se construit avec [`CollectionConfig::builder`], qui refuse les combinaisons
+/// incohérentes :
+/// `CollectionConfig::builder().dimension(768).metric(Metric::Cosine).normalized(true).build()`.
+///
+/// La configuration par défaut correspond à [`Collection::new`](crate::Collection::new) :
+/// dimension libre, similarité cosinus, vecteurs conservés tels quels.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CollectionConfig {
+    pub(crate) dimension: Option<usize>,
+    pub(crate) metric: Metric,
+    pub(crate) normalized: bool,
+    pub(crate) zero_vector_policy: ZeroVectorPolicy,
+    pub(crate) strict_dimensions: bool,
+    pub(crate) soft_delete: bool,
+    pub(crate) memory_eviction: bool,
+    pub(crate) shared_vectors: bool,
+    pub(crate) unit_vectors: bool,
+    pub(crate) model: Option<ModelFingerprint>,
+    pub(crate) weights: Option<Weights>,
+    pub(crate) norm_range: Option<NormRange>,
+    pub(crate) access_tracking: bool,
+    pub(crate) aggregate_noise: Option<AggregateNoise>,
+    pub(crate) query_pipeline: QueryPipeline,
+}
+
+/// Plage validée des normes admises à l'insertion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct NormRange {
+    pub(crate) min: f32,
+    pub(crate) max: f32,
+}
+
+// Les bornes ne sont jamais NaN : l'égalité des `f32` est alors une relation
+// d'équivalence.
+impl Eq for NormRange {}
+
+impl CollectionConfig {
+    /// Crée un constructeur partant de la configuration par défaut.
+    pub fn builder() -> CollectionConfigBuilder {
+        CollectionConfigBuilder::default()
+    }
+
+    /// Retourne la dimension imposée aux vecteurs insérés et aux requêtes, si elle est fixée.
+    pub fn dimension(&self) -> Option<usize> {
+        self.dimension
+    }
+
+    /// Retourne la mesure utilisée par les recherches.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Indique si les vecteurs et les requêtes sont ramenés à une norme de 1.
+    pub fn normalized(&self) -> bool {
+        self.normalized
+    }
+
+    /// Retourne le traitement des vecteurs nuls.
+    pub fn zero_vector_policy(&self) -> ZeroVectorPolicy {
+        self.zero_vector_policy
+    }
+
+    /// Indique si les recherches échouent sur les documents de dimension différente.
+    pub fn strict_dimensions(&self) -> bool {
+        self.strict_dimensions
+    }
+
+    /// Indique si les suppressions laissent une pierre tombale jusqu'au compactage.
+    pub fn soft_delete(&self) -> bool {
+        self.soft_delete
+    }
+
+    /// Indique si la collection évince ses documents les plus anciens plutôt que de
+    /// dépasser la limite de mémoire de sa base.
+    pub fn memory_eviction(&self) -> bool {
+        self.memory_eviction
+    }
+
+    /// Indique si les vecteurs identiques des documents ne sont stockés qu'une fois.
+    pub fn shared_vectors(&self) -> bool {
+        self.shared_vectors
+    }
+
+    /// Indique si les vecteurs insérés sont déclarés unitaires.
+    pub fn vectors_are_normalized(&self) -> bool {
+        self.unit_vectors
+    }
+
+    /// Retourne l'empreinte du modèle de plongement des vecteurs, si elle est connue.
+    pub fn model_fingerprint(&self) -> Option<&ModelFingerprint> {
+        self.model.as_ref()
+    }
+
+    /// Retourne les poids des dimensions dans le calcul des scores, s'il y en a.
+    pub fn dimension_weights(&self) -> Option<&[f32]> {
+        self.weights.as_ref().map(|weights| weights.0.as_slice())
+    }
+
+    /// Retourne la plage des normes admises à l'insertion, si elle est bornée.
+    pub fn norm_range(&self) -> Option<(f32, f32)> {
+        self.norm_range.map(|range| (range.min, range.max))
+    }
+
+    /// Indique si les accès des recherches aux documents sont comptés.
+    pub fn access_tracking(&self) -> bool {
+        self.access_tracking
+    }
+
+    /// Retourne le budget de confidentialité des statistiques agrégées, s'il est fixé.
+    pub fn aggregate_noise(&self) -> Option<f64> {
+        self.aggregate_noise.map(|noise| noise.epsilon)
+    }
+
+    /// Retourne le pipeline appliqué aux requêtes, vide s'il n'y en a pas.
+    pub fn query_pipeline(&self) -> &QueryPipeline {
+        &self.query_pipeline
+    }
+}
+
+/// Constructeur de [`CollectionConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct CollectionConfigBuilder {
+    dimension: Option<usize>,
+    metric: Metric,
+    normalized: bool,
+    zero_vector_policy: ZeroVectorPolicy,
+    strict_dimensions: Option<bool>,
+    soft_delete: bool,
+    memory_eviction: bool,
+    shared_vectors: bool,
+    unit_vectors: bool,
+    model: Option<ModelFingerprint>,
+    weights: Option<Vec<f32>>,
+    norm_range: Option<(f32, f32)>,
+    access_tracking: bool,
+    aggregate_noise: Option<f64>,
+    query_pipeline: QueryPipeline,
+}
+
+impl CollectionConfigBuilder {
+    /// Impose la dimension des vecteurs : les insertions et les requêtes d'une autre
+    /// dimension échouent avec `Error::DimensionMismatch`. Avec une projection, il s'agit
+    /// de la dimension d'entrée.
+    ///
+    /// # Arguments
+    /// * `dimension` - Dimension des vecteurs, non nulle.
+    pub fn dimension(mut self, dimension: usize) -> Self {
+        self.dimension = Some(dimension);
+        self
+    }
+
+    /// Fixe la mesure utilisée par les recherches.
+    ///
+    /// # Arguments
+    /// * `metric` - Mesure de similarité ou de distance.
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Ramène chaque vecteur inséré et chaque requête à une norme de 1. Les vecteurs nuls,
+    /// qui ne peuvent pas être normalisés, sont alors toujours refusés.
+    ///
+    /// # Arguments
+    /// * `normalized` - Vrai pour normaliser les vecteurs.
+    pub fn normalized(mut self, normalized: bool) -> Self {
+        self.normalized = normalized;
+        self
+    }
+
+    /// Fixe le traitement des vecteurs nuls.
+    ///
+    /// # Arguments
+    /// * `policy` - Politique à appliquer.
+    pub fn zero_vector_policy(mut self, policy: ZeroVectorPolicy) -> Self {
+        self.zero_vector_policy = policy;
+        self
+    }
+
+    /// Choisit le traitement des documents de dimension différente lors des recherches
+    /// (voir [`Collection::with_strict_dimensions`](crate::Collection::with_strict_dimensions)).
+    /// Par défaut, le mode strict est actif si et seulement si la dimension est fixée.
+    ///
+    /// # Arguments
+    /// * `strict` - Vrai pour faire échouer les recherches.
+    pub fn strict_dimensions(mut self, strict: bool) -> Self {
+        self.strict_dimensions = Some(strict);
+        self
+    }
+
+    /// Choisit le mode de suppression (voir
+    /// [`Collection::with_soft_delete`](crate::Collection::with_soft_delete)).
+    ///
+    /// # Arguments
+    /// * `soft_delete` - Vrai pour conserver les documents supprimés jusqu'au compactage.
+    pub fn soft_delete(mut self, soft_delete: bool) -> Self {
+        self.soft_delete = soft_delete;
+        self
+    }
+
+    /// Choisit le comportement face à la limite de mémoire de la base (voir
+    /// [`Collection::with_memory_eviction`](crate::Collection::with_memory_eviction)).
+    ///
+    /// # Arguments
+    /// * `evict` - Vrai pour évincer des documents plutôt que refuser l'insertion.
+    pub fn memory_eviction(mut self, evict: bool) -> Self {
+        self.memory_eviction = evict;
+        self
+    }
+
+    /// Choisit le stockage des vecteurs identiques (voir
+    /// [`Collection::with_shared_vectors`](crate::Collection::with_shared_vectors)).
+    ///
+    /// # Arguments
+    /// * `shared` - Vrai pour ne stocker qu'une fois les vecteurs identiques.
+    pub fn shared_vectors(mut self, shared: bool) -> Self {
+        self.shared_vectors = shared;
+        self
+    }
+
+    /// Déclare que les vecteurs insérés sont déjà unitaires, comme ceux de la plupart des
+    /// fournisseurs de plongements.
+    ///
+    /// Contrairement à [`CollectionConfigBuilder::normalized`], les vecteurs ne sont pas
+    /// modifiés : une insertion dont la norme s'écarte de 1 de plus que la tolérance
+    /// ([`Collection::with_unit_norm_tolerance`](crate::Collection::with_unit_norm_tolerance))
+    /// échoue avec `Error::NotNormalized`, sauf dans
+    /// [`Collection::assume_normalized`](crate::Collection::assume_normalized). En
+    /// similarité cosinus, les scores sont alors de simples produits scalaires, sans
+    /// division par les normes, et chaque requête est normalisée une fois. Avec une
+    /// projection, c'est le vecteur projeté qui doit être unitaire.
+    ///
+    /// # Arguments
+    /// * `unit` - Vrai pour déclarer les vecteurs unitaires.
+    pub fn vectors_are_normalized(mut self, unit: bool) -> Self {
+        self.unit_vectors = unit;
+        self
+    }
+
+    /// Déclare le modèle de plongement qui produit les vecteurs de la collection : une
+    /// recherche dont [`SearchParams::model`](crate::SearchParams::model) désigne un autre
+    /// modèle échoue avec `Error::ModelMismatch`.
+    ///
+    /// # Arguments
+    /// * `model` - Empreinte du modèle.
+    pub fn model_fingerprint(mut self, model: ModelFingerprint) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Pondère les dimensions dans le calcul des scores : la dimension `i` compte avec le
+    /// poids `weights[i]` pour toutes les mesures (voir [`Metric::score_weighted`]), sans
+    /// que les vecteurs stockés soient modifiés. Les vecteurs insérés et les requêtes
+    /// doivent avoir autant de coordonnées que de poids, après l'éventuelle projection.
+    ///
+    /// En similarité cosinus, les normes sont alors elles aussi pondérées : des vecteurs
+    /// déclarés unitaires ([`CollectionConfigBuilder::vectors_are_normalized`]) ne
+    /// permettent plus de réduire les scores à des produits scalaires. Les poids se
+    /// changent ensuite avec
+    /// [`Collection::set_dimension_weights`](crate::Collection::set_dimension_weights).
+    ///
+    /// # Arguments
+    /// * `weights` - Poids de chaque dimension, finis et positifs ou nuls.
+    pub fn dimension_weights(mut self, weights: Vec<f32>) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Borne la norme des vecteurs insérés, pour signaler des plongements aberrants, par
+    /// exemple multipliés par 10²⁰ en amont : une insertion dont la norme, calculée en
+    /// `f64` sur le vecteur fourni avant toute projection ou normalisation, est hors de
+    /// [`min`, `max`] échoue avec `Error::NormOutOfRange`. Les documents présents ne
+    /// sont pas vérifiés.
+    ///
+    /// # Arguments
+    /// * `min` - Norme minimale, positive ou nulle.
+    /// * `max` - Norme maximale, au moins `min` ; `f32::INFINITY` ne borne que par en bas.
+    pub fn norm_range(mut self, min: f32, max: f32) -> Self {
+        self.norm_range = Some((min, max));
+        self
+    }
+
+    /// Choisit le suivi des accès des recherches (voir
+    /// [`Collection::with_access_tracking`](crate::Collection::with_access_tracking)).
+    ///
+    /// # Arguments
+    /// * `track` - Vrai pour compter les accès.
+    pub fn access_tracking(mut self, track: bool) -> Self {
+        self.access_tracking = track;
+        self
+    }
+
+    /// Bruite les statistiques agrégées de la collection (voir
+    /// [`Collection::set_aggregate_noise`](crate::Collection::set_aggregate_noise)).
+    ///
+    /// # Arguments
+    /// * `epsilon` - Budget de confidentialité, fini et strictement positif.
+    pub fn aggregate_noise(mut self, epsilon: f64) -> Self {
+        self.aggregate_noise = Some(epsilon);
+        self
+    }
+
+    /// Transforme chaque requête avant le calcul des scores (voir
+    /// [`Collection::set_query_pipeline`](crate::Collection::set_query_pipeline)). Un
+    /// pipeline non vide demande une dimension fixée, à laquelle il doit ramener les
+    /// requêtes.
+    ///
+    /// # Arguments
+    /// * `pipeline` - Transformations à appliquer, dans l'ordre.
+    pub fn query_pipeline(mut self, pipeline: QueryPipeline) -> Self {
+        self.query_pipeline = pipeline;
+        self
+    }
+
+    /// Construit la configuration.
+    ///
+    /// # Retourne
+    /// * Result<CollectionConfig> - La configuration validée.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si la dimension vaut 0, si la normalisation est
+    ///   demandée avec `ZeroVectorPolicy::ScoreZero`, si les poids des dimensions sont
+    ///   invalides, si la plage des normes est vide, négative ou NaN, si le budget de
+    ///   confidentialité des agrégats n'est pas fini et strictement positif, ou si un
+    ///   pipeline de requêtes est donné sans dimension ou ne ramène pas les requêtes à
+    ///   celle-ci.
+    /// * `Error::DimensionMismatch` - Si le nombre de poids n'est pas la dimension fixée.
+    /// * Celles de [`QueryTransform::output_dimension`](crate::QueryTransform::output_dimension),
+    ///   pour le premier maillon du pipeline qui ne s'applique pas.
+    pub fn build(self) -> Result<CollectionConfig> {
+        if self.dimension == Some(0) {
+            return Err(Error::InvalidConfig(
+                "la dimension d'une collection doit être non nulle".to_string(),
+            ));
+        }
+        if let Some(weights) = &self.weights {
+            weights::check(weights)?;
+            if let Some(dimension) = self
+                .dimension
+                .filter(|dimension| *dimension != weights.len())
+            {
+                return Err(Error::DimensionMismatch {
+                    expected: dimension,
+                    got: weights.len(),
+                });
+            }
+        }
+        if let Some((min, max)) = self
+            .norm_range
+            .filter(|(min, max)| !(0.0 <= *min && min <= max))
+        {
+            return Err(Error::InvalidConfig(format!(
+                "la plage des normes [{}, {}] est invalide ; il faut 0 <= min <= max",
+                min, max
+            )));
+        }
+        if let Some(epsilon) = self.aggregate_noise {
+            privacy::check(epsilon)?;
+        }
+        if !self.query_pipeline.is_empty() {
+            let dimension = self.dimension.ok_or_else(|| {
+                Error::InvalidConfig(
+                    "un pipeline de requêtes demande une dimension fixée".to_string(),
+                )
+            })?;
+            self.query_pipeline.check(dimension)?;
+        }
+        if self.normalized && self.zero_vector_policy == ZeroVectorPolicy::ScoreZero {
+            return Err(Error::InvalidConfig(
+                "une collection normalisée ne peut pas accepter les vecteurs nuls, \
+                 qui n'ont pas de direction"
+                    .to_string(),
+            ));
+        }
+        Ok(CollectionConfig {
+            dimension: self.dimension,
+            metric: self.metric,
+            normalized: self.normalized,
+            zero_vector_policy: self.zero_vector_policy,
+            strict_dimensions: self.strict_dimensions.unwrap_or(self.dimension.is_some()),
+            soft_delete: self.soft_delete,
+            memory_eviction: self.memory_eviction,
+            shared_vectors: self.shared_vectors,
+            unit_vectors: self.unit_vectors,
+            model: self.model,
+            weights: self.weights.map(Weights),
+            norm_range: self.norm_range.map(|(min, max)| NormRange { min, max }),
+            access_tracking: self.access_tracking,
+            aggregate_noise: (self.aggregate_noise).map(|epsilon| AggregateNoise { epsilon }),
+            query_pipeline: self.query_pipeline,
+        })
+    }
+}
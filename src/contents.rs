@@ -0,0 +1,493 @@
+//! Table des matières des sauvegardes : liste des collections d'un fichier et
+//! chargement de certaines d'entre elles seulement.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::backup;
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::snapshot::{self, invalid, put_len, put_str, Admit, Reader, Verify};
+
+/// Taille de l'en-tête d'une sauvegarde : signature et version.
+const HEADER_LEN: u64 = 8 + 4;
+/// Taille d'une entrée de la table des matières, hors octets du nom.
+const ENTRY_LEN: usize = 8 + 1 + 8 + 8 + 8;
+/// Première version du format à contenir une table des matières.
+const TOC_VERSION: u32 = 38;
+
+/// Entrée de la table des matières d'une sauvegarde ([`list_snapshot_contents`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    /// Nom de la collection.
+    pub name: String,
+    /// Vrai pour une collection partitionnée.
+    pub sharded: bool,
+    /// Position de la collection depuis le début du fichier, en octets.
+    pub offset: u64,
+    /// Longueur de la collection, en octets.
+    pub length: u64,
+    /// Empreinte FNV-1a 64 bits des octets de la collection.
+    pub checksum: u64,
+}
+
+impl SnapshotEntry {
+    /// Vérifie que `section` a l'empreinte de l'entrée.
+    fn check(&self, section: &[u8]) -> Result<()> {
+        match backup::checksum(section) == self.checksum {
+            true => Ok(()),
+            false => Err(invalid(format!(
+                "empreinte incorrecte pour la collection '{}'",
+                self.name
+            ))),
+        }
+    }
+}
+
+/// Table des matières lue d'une sauvegarde.
+pub(crate) struct Toc {
+    entries: Vec<SnapshotEntry>,
+}
+
+impl Toc {
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Prend dans `reader` la collection `nom`, décrite par l'entrée `index`, après avoir
+    /// vérifié sa position et son empreinte.
+    pub(crate) fn section<'a>(
+        &self,
+        index: usize,
+        nom: &str,
+        sharded: bool,
+        reader: &mut Reader<'a>,
+    ) -> Result<&'a [u8]> {
+        let entry = (self.entries.get(index))
+            .filter(|entry| entry.name == nom && entry.sharded == sharded)
+            .filter(|entry| entry.offset == reader.position as u64)
+            .ok_or_else(|| {
+                invalid("la table des matières ne correspond pas aux collections du fichier")
+            })?;
+        let length = usize::try_from(entry.length).map_err(|_| invalid("longueur trop grande"))?;
+        let section = reader.take(length)?;
+        entry.check(section)?;
+        Ok(section)
+    }
+}
+
+/// Écrit la table des matières d'une sauvegarde, puis complète chacune de ses entrées
+/// à mesure que les collections sont écrites.
+pub(crate) struct TocWriter {
+    /// Position de la partie numérique de chaque entrée.
+    slots: Vec<usize>,
+    next: usize,
+}
+
+impl TocWriter {
+    /// Écrit la longueur de la table et ses entrées, sans positions ni empreintes.
+    ///
+    /// # Arguments
+    /// * `out` - Sauvegarde en cours d'écriture, arrêtée après la version.
+    /// * `names` - Nom de chaque collection, et vrai pour une collection partitionnée,
+    ///   dans l'ordre d'écriture.
+    pub(crate) fn new<'a>(out: &mut Vec<u8>, names: impl Iterator<Item = (&'a str, bool)>) -> Self {
+        let start = out.len();
+        put_len(out, 0);
+        let names: Vec<(&str, bool)> = names.collect();
+        put_len(out, names.len());
+        let mut slots = Vec::with_capacity(names.len());
+        for (nom, sharded) in names {
+            put_str(out, nom);
+            out.push(u8::from(sharded));
+            slots.push(out.len());
+            out.extend_from_slice(&[0; 24]);
+        }
+        let len = (out.len() - start - 8) as u64;
+        out[start..start + 8].copy_from_slice(&len.to_le_bytes());
+        TocWriter { slots, next: 0 }
+    }
+
+    /// Complète l'entrée de la collection suivante, écrite de `start` à la fin de `out`.
+    pub(crate) fn fill(&mut self, out: &mut [u8], start: usize) {
+        let slot = self.slots[self.next];
+        self.next += 1;
+        let checksum = backup::checksum(&out[start..]);
+        let length = (out.len() - start) as u64;
+        out[slot..slot + 8].copy_from_slice(&(start as u64).to_le_bytes());
+        out[slot + 8..slot + 16].copy_from_slice(&length.to_le_bytes());
+        out[slot + 16..slot + 24].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+/// Lit une table des matières écrite par [`TocWriter`].
+pub(crate) fn read_toc(bytes: &[u8]) -> Result<Toc> {
+    let mut reader = Reader::new(bytes);
+    let count = reader.len(ENTRY_LEN)?;
+    let mut entries: Vec<SnapshotEntry> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name = reader.name()?;
+        let sharded = match reader.u8()? {
+            0 => false,
+            1 => true,
+            code => return Err(invalid(format!("genre de collection invalide ({})", code))),
+        };
+        if entries.iter().any(|entry| entry.name == name) {
+            return Err(invalid(format!("collection '{}' en double", name)));
+        }
+        entries.push(SnapshotEntry {
+            name,
+            sharded,
+            offset: reader.u64()?,
+            length: reader.u64()?,
+            checksum: reader.u64()?,
+        });
+    }
+    if !reader.is_at_end() {
+        return Err(invalid("octets inattendus après la table des matières"));
+    }
+    Ok(Toc { entries })
+}
+
+/// Lit une collection de `section` avec `read`, qui doit en consommer tous les octets.
+pub(crate) fn read_section<T>(
+    section: &[u8],
+    read: impl FnOnce(&mut Reader) -> Result<T>,
+) -> Result<T> {
+    let mut reader = Reader::new(section);
+    let value = read(&mut reader)?;
+    if !reader.is_at_end() {
+        return Err(invalid("octets inattendus à la fin de la collection"));
+    }
+    Ok(value)
+}
+
+/// Octets d'une sauvegarde, lus à la demande.
+trait Source {
+    /// Lit `length` octets à partir de la position `offset`.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidSnapshot` - Si la sauvegarde s'arrête avant.
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    fn read(&mut self, offset: u64, length: u64) -> Result<Cow<'_, [u8]>>;
+
+    /// Lit l'en-tête et la table des matières.
+    ///
+    /// # Retourne
+    /// * Result<(u32, Toc)> - La version du format et la table des matières.
+    fn contents(&mut self) -> Result<(u32, Toc)> {
+        let header = self.read(0, HEADER_LEN)?;
+        let mut reader = Reader::new(&header);
+        if reader.take(snapshot::MAGIC.len())? != snapshot::MAGIC {
+            return Err(invalid("signature de fichier inconnue"));
+        }
+        let version = reader.u32()?;
+        if version == 0 || version > snapshot::VERSION {
+            return Err(invalid(format!("version {} non prise en charge", version)));
+        }
+        if version < TOC_VERSION {
+            return Err(invalid(format!(
+                "sauvegarde de version {} sans table des matières : enregistrez-la de nouveau",
+                version
+            )));
+        }
+        let len = Reader::new(&self.read(HEADER_LEN, 8)?).u64()?;
+        let toc = read_toc(&self.read(HEADER_LEN + 8, len)?)?;
+        Ok((version, toc))
+    }
+
+    /// Charge les collections `names`.
+    fn load(&mut self, names: &[&str]) -> Result<BaseDeDonnees> {
+        let (version, toc) = self.contents()?;
+        let mut selected: Vec<&SnapshotEntry> = Vec::with_capacity(names.len());
+        for nom in names {
+            let entry = (toc.entries.iter())
+                .find(|entry| entry.name == *nom)
+                .ok_or_else(|| Error::CollectionNotFound(nom.to_string()))?;
+            if !selected.iter().any(|selected| selected.name == entry.name) {
+                selected.push(entry);
+            }
+        }
+        let mut bdd = BaseDeDonnees::new();
+        for entry in selected {
+            let nom = entry.name.as_str();
+            let context = ErrorContext::new(Operation::Load).collection(nom);
+            let section = (self.read(entry.offset, entry.length))
+                .and_then(|section| entry.check(&section).map(|()| section))
+                .map_err(|e| e.with_context(context.clone()))?;
+            if entry.sharded {
+                let collection = read_section(&section, |r| snapshot::read_sharded(r, version, nom))
+                    .and_then(|mut collection| {
+                        Verify.sharded(nom, &mut collection)?;
+                        Ok(collection)
+                    })
+                    .map_err(|e| e.with_context(context))?;
+                bdd.sharded.insert(nom.to_string(), Arc::new(collection));
+            } else {
+                let collection = read_section(&section, |r| snapshot::read_collection(r, version))
+                    .and_then(|mut collection| {
+                        Verify.collection(nom, &mut collection)?;
+                        Ok(collection)
+                    })
+                    .map_err(|e| e.with_context(context))?;
+                bdd.collections.insert(nom.to_string(), Arc::new(collection));
+            }
+        }
+        Ok(bdd)
+    }
+}
+
+/// Sauvegarde lue par positionnement dans son fichier.
+struct FileSource {
+    file: File,
+    size: u64,
+}
+
+impl FileSource {
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Ok(FileSource { file, size })
+    }
+}
+
+impl Source for FileSource {
+    fn read(&mut self, offset: u64, length: u64) -> Result<Cow<'_, [u8]>> {
+        if offset.checked_add(length).is_none_or(|end| end > self.size) {
+            return Err(invalid("fichier tronqué"));
+        }
+        let length = usize::try_from(length).map_err(|_| invalid("longueur trop grande"))?;
+        let mut buffer = vec![0; length];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buffer)?;
+        Ok(Cow::Owned(buffer))
+    }
+}
+
+/// Sauvegarde déjà en mémoire.
+#[cfg(feature = "encryption")]
+impl Source for &[u8] {
+    fn read(&mut self, offset: u64, length: u64) -> Result<Cow<'_, [u8]>> {
+        let start = usize::try_from(offset).ok();
+        let end = offset.checked_add(length).and_then(|end| usize::try_from(end).ok());
+        (start.zip(end))
+            .and_then(|(start, end)| self.get(start..end))
+            .map(Cow::Borrowed)
+            .ok_or_else(|| invalid("fichier tronqué"))
+    }
+}
+
+/// Retourne la table des matières d'une sauvegarde enregistrée avec
+/// [`BaseDeDonnees::save`], sans lire ses collections : une entrée par collection,
+/// simples puis partitionnées, dans l'ordre alphabétique de chaque genre.
+///
+/// # Arguments
+/// * `path` - Chemin du fichier.
+///
+/// # Erreurs
+/// * `Error::Io` - Si le fichier ne peut pas être lu.
+/// * `Error::InvalidSnapshot` - Si le fichier n'est pas une sauvegarde, si sa table des
+///   matières est invalide, ou s'il est d'une version antérieure à la table des
+///   matières : il suffit de le charger et de l'enregistrer de nouveau.
+pub fn list_snapshot_contents(path: impl AsRef<Path>) -> Result<Vec<SnapshotEntry>> {
+    Ok(FileSource::open(path.as_ref())?.contents()?.1.entries)
+}
+
+impl BaseDeDonnees {
+    /// Charge seulement les collections `names` d'une sauvegarde enregistrée avec
+    /// [`BaseDeDonnees::save`].
+    ///
+    /// Seuls l'en-tête, la table des matières ([`list_snapshot_contents`]) et les
+    /// collections demandées sont lus ; l'empreinte de chacune est vérifiée, puis ses
+    /// invariants comme par [`BaseDeDonnees::load`]. Les autres collections ne sont ni
+    /// lues ni vérifiées. Les alias ne sont pas chargés.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    /// * `names` - Noms des collections à charger, simples ou partitionnées ; un nom
+    ///   répété est chargé une fois.
+    ///
+    /// # Retourne
+    /// * Result<BaseDeDonnees> - Une base qui ne contient que ces collections.
+    ///
+    /// # Erreurs
+    /// * Celles de [`list_snapshot_contents`].
+    /// * `Error::CollectionNotFound` - Si un nom est absent de la table des matières ;
+    ///   rien n'est alors chargé.
+    /// * `Error::InvalidSnapshot` - Si une collection demandée n'a pas son empreinte ou
+    ///   est invalide, enveloppée dans `Error::WithContext` avec son nom.
+    pub fn load_collections(path: impl AsRef<Path>, names: &[&str]) -> Result<Self> {
+        FileSource::open(path.as_ref())?.load(names)
+    }
+}
+
+/// Charge seulement les collections `names` d'une sauvegarde déjà en mémoire, comme
+/// [`BaseDeDonnees::load_collections`].
+#[cfg(feature = "encryption")]
+pub(crate) fn load_from_bytes(bytes: &[u8], names: &[&str]) -> Result<BaseDeDonnees> {
+    let mut bytes = bytes;
+    bytes.load(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use crate::collection::Collection;
+    use crate::sharded::ShardedCollection;
+    use crate::store::VectorStoreMut;
+    use crate::synthetic::VectorGenerator;
+
+    /// Fichier temporaire supprimé à la fin du test.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new() -> Self {
+            TempFile(std::env::temp_dir().join(format!(
+                "embeddingproject-contents-{}.snap",
+                Uuid::new_v4()
+            )))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Base de douze collections simples et d'une collection partitionnée.
+    fn database() -> BaseDeDonnees {
+        let mut db = BaseDeDonnees::new();
+        let mut generator = VectorGenerator::new(8, 3);
+        for i in 0..12 {
+            let mut collection = Collection::new();
+            for _ in 0..20 + i {
+                collection.upsert(generator.uuid(), generator.vector()).unwrap();
+            }
+            db.replace(format!("c{:02}", i), collection);
+        }
+        let mut sharded = ShardedCollection::new(3).unwrap();
+        for _ in 0..50 {
+            sharded.upsert(generator.uuid(), generator.vector()).unwrap();
+        }
+        db.replace_sharded("partitions".to_string(), sharded);
+        db.set_alias("alias".to_string(), "c03").unwrap();
+        db
+    }
+
+    /// Base qui ne contient que les collections `names` de `full`.
+    fn selection(full: &BaseDeDonnees, names: &[&str]) -> BaseDeDonnees {
+        let mut db = BaseDeDonnees::new();
+        for name in names {
+            match full.collections.get(*name) {
+                Some(collection) => {
+                    db.collections.insert(name.to_string(), Arc::clone(collection));
+                }
+                None => {
+                    db.sharded.insert(name.to_string(), Arc::clone(&full.sharded[*name]));
+                }
+            }
+        }
+        db
+    }
+
+    #[test]
+    fn partial_loads_match_full_loads() {
+        let file = TempFile::new();
+        database().save(&file.0).unwrap();
+        let full = BaseDeDonnees::load(&file.0).unwrap();
+        for names in [&["c07"][..], &["c00", "partitions", "c11"], &["c05", "c05"]] {
+            let partial = BaseDeDonnees::load_collections(&file.0, names).unwrap();
+            let mut unique = names.to_vec();
+            unique.dedup();
+            assert_eq!(partial.collections.len() + partial.sharded.len(), unique.len());
+            assert!(partial.aliases().is_empty());
+            assert_eq!(partial.to_bytes(), selection(&full, &unique).to_bytes(), "{:?}", names);
+        }
+        assert!(matches!(
+            BaseDeDonnees::load_collections(&file.0, &["c01", "absente"]),
+            Err(Error::CollectionNotFound(name)) if name == "absente"
+        ));
+        // Un alias n'est pas une entrée de la table des matières.
+        assert!(matches!(
+            BaseDeDonnees::load_collections(&file.0, &["alias"]),
+            Err(Error::CollectionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn contents_describe_each_section() {
+        let file = TempFile::new();
+        database().save(&file.0).unwrap();
+        let bytes = std::fs::read(&file.0).unwrap();
+        let entries = list_snapshot_contents(&file.0).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        let mut expected: Vec<String> = (0..12).map(|i| format!("c{:02}", i)).collect();
+        expected.push("partitions".to_string());
+        assert_eq!(names, expected);
+        assert!(entries.iter().all(|entry| entry.sharded == (entry.name == "partitions")));
+        for (entry, next) in entries.iter().zip(&entries[1..]) {
+            assert!(entry.offset + entry.length < next.offset);
+        }
+        for entry in &entries {
+            let (start, end) = (entry.offset as usize, (entry.offset + entry.length) as usize);
+            // Chaque collection suit son nom.
+            assert_eq!(&bytes[start - entry.name.len()..start], entry.name.as_bytes());
+            assert_eq!(backup::checksum(&bytes[start..end]), entry.checksum);
+        }
+    }
+
+    #[test]
+    fn only_loaded_sections_are_verified() {
+        let file = TempFile::new();
+        database().save(&file.0).unwrap();
+        let entries = list_snapshot_contents(&file.0).unwrap();
+        let damaged = &entries[4];
+        let mut bytes = std::fs::read(&file.0).unwrap();
+        bytes[(damaged.offset + damaged.length / 2) as usize] ^= 0x40;
+        std::fs::write(&file.0, &bytes).unwrap();
+
+        let partial = BaseDeDonnees::load_collections(&file.0, &["c03", "partitions"]).unwrap();
+        assert_eq!(partial.get("c03").unwrap().len(), 23);
+        let error = BaseDeDonnees::load_collections(&file.0, &["c04"]).unwrap_err();
+        assert!(matches!(error.root(), Error::InvalidSnapshot(_)));
+        assert_eq!(error.context().unwrap().collection.as_deref(), Some("c04"));
+        assert!(BaseDeDonnees::load(&file.0).is_err());
+
+        // Une sauvegarde d'avant la table des matières se charge seulement en entier.
+        bytes[snapshot::MAGIC.len()..HEADER_LEN as usize]
+            .copy_from_slice(&(TOC_VERSION - 1).to_le_bytes());
+        std::fs::write(&file.0, &bytes).unwrap();
+        assert!(matches!(list_snapshot_contents(&file.0), Err(Error::InvalidSnapshot(_))));
+        std::fs::write(&file.0, &bytes[..HEADER_LEN as usize + 4]).unwrap();
+        assert!(matches!(list_snapshot_contents(&file.0), Err(Error::InvalidSnapshot(_))));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_snapshots_keep_their_contents() {
+        let file = TempFile::new();
+        let key = crate::encryption::derive_key("phrase secrète", b"sel de test");
+        database().save_encrypted(&file.0, &key).unwrap();
+        let full = BaseDeDonnees::load_encrypted(&file.0, &key).unwrap();
+        let names = ["partitions", "c09"];
+        let partial = BaseDeDonnees::load_collections_encrypted(&file.0, &key, &names).unwrap();
+        assert_eq!(partial.to_bytes(), selection(&full, &names).to_bytes());
+        assert!(matches!(
+            BaseDeDonnees::load_collections_encrypted(&file.0, &key, &["absente"]),
+            Err(Error::CollectionNotFound(_))
+        ));
+        let wrong = crate::encryption::derive_key("autre phrase", b"sel de test");
+        assert!(matches!(
+            BaseDeDonnees::load_collections_encrypted(&file.0, &wrong, &names),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+}
@@ -0,0 +1,450 @@
+//! Noyau de calcul des scores, sans dépendance à la bibliothèque standard.
+//!
+//! Ce module ne demande que `core` et `alloc` : sans la fonctionnalité `std`, activée
+//! par défaut (`default-features = false`), la crate se réduit à lui et compile avec
+//! `#![no_std]`, pour un système embarqué ou WebAssembly. Il regroupe les mesures
+//! ([`Metric`]), les fonctions de score et l'accumulateur [`TopK`] des meilleurs
+//! résultats, générique sur le type de l'identifiant. Les collections et la base
+//! ([`similarity`](crate::similarity), [`TopK`](crate::TopK) avec la fonctionnalité
+//! `std`) reposent sur les mêmes fonctions : les scores sont identiques bit à bit dans
+//! les deux configurations.
+//!
+//! Les fonctions de score vérifient que les deux vecteurs ont la même dimension et
+//! retournent [`DimensionMismatch`] sinon ; les produits et les sommes sont calculés en
+//! `f64`, comme l'explique [`similarity`](crate::similarity).
+
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+/// Les deux vecteurs d'une fonction de score n'ont pas la même dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DimensionMismatch {
+    /// Dimension du premier vecteur.
+    pub expected: usize,
+    /// Dimension du second vecteur.
+    pub got: usize,
+}
+
+impl fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "dimension incorrecte : {} attendue, {} reçue",
+            self.expected, self.got
+        )
+    }
+}
+
+/// Mesure utilisée pour comparer la requête aux documents d'une collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Metric {
+    /// Similarité cosinus, dans [-1, 1] ; plus elle est grande, plus les vecteurs sont proches.
+    #[default]
+    Cosine,
+    /// Produit scalaire ; plus il est grand, plus les vecteurs sont proches.
+    Dot,
+    /// Distance euclidienne, dans [0, ∞) ; plus elle est petite, plus les vecteurs sont proches.
+    Euclidean,
+    /// Distance de Manhattan, dans [0, ∞) ; plus elle est petite, plus les vecteurs sont proches.
+    Manhattan,
+}
+
+impl Metric {
+    /// Indique si un score plus élevé signifie des vecteurs plus proches.
+    pub fn higher_is_better(self) -> bool {
+        matches!(self, Metric::Cosine | Metric::Dot)
+    }
+
+    /// Compare deux scores de façon à placer le meilleur en premier.
+    ///
+    /// Les NaN sont considérés comme égaux à tout score.
+    pub fn compare(self, a: f32, b: f32) -> Ordering {
+        let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+        if self.higher_is_better() {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Indique si `score` est au moins aussi bon que `threshold` pour cette mesure.
+    pub fn passes(self, score: f32, threshold: f32) -> bool {
+        if self.higher_is_better() {
+            score >= threshold
+        } else {
+            score <= threshold
+        }
+    }
+
+    /// Calcule le score sans vérifier les dimensions, qui doivent être égales.
+    #[inline]
+    pub(crate) fn score_unchecked(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => cosine_unchecked(a, b),
+            Metric::Dot => dot_unchecked(a, b),
+            Metric::Euclidean => euclidean_unchecked(a, b),
+            Metric::Manhattan => manhattan_unchecked(a, b),
+        }
+    }
+}
+
+/// Calcule le score de `metric` entre deux vecteurs.
+#[inline]
+pub fn score(metric: Metric, a: &[f32], b: &[f32]) -> Result<f32, DimensionMismatch> {
+    check_dimensions(a, b)?;
+    Ok(metric.score_unchecked(a, b))
+}
+
+/// Calcule le score de `metric` entre deux vecteurs, chaque dimension `i` comptant avec
+/// le poids `weights[i]`, comme `Metric::score_weighted` avec la fonctionnalité `std`.
+#[inline]
+pub fn score_weighted(
+    metric: Metric,
+    weights: &[f32],
+    a: &[f32],
+    b: &[f32],
+) -> Result<f32, DimensionMismatch> {
+    check_dimensions(a, b)?;
+    check_dimensions(weights, a)?;
+    Ok(weighted_unchecked(metric, weights, a, b))
+}
+
+/// Calcule la similarité cosinus entre deux vecteurs, 0.0 si l'un des deux est nul.
+#[inline]
+pub fn cosine(a: &[f32], b: &[f32]) -> Result<f32, DimensionMismatch> {
+    score(Metric::Cosine, a, b)
+}
+
+/// Calcule le produit scalaire de deux vecteurs.
+#[inline]
+pub fn dot(a: &[f32], b: &[f32]) -> Result<f32, DimensionMismatch> {
+    score(Metric::Dot, a, b)
+}
+
+/// Calcule la distance euclidienne entre deux vecteurs.
+#[inline]
+pub fn euclidean(a: &[f32], b: &[f32]) -> Result<f32, DimensionMismatch> {
+    score(Metric::Euclidean, a, b)
+}
+
+/// Calcule la distance de Manhattan entre deux vecteurs.
+#[inline]
+pub fn manhattan(a: &[f32], b: &[f32]) -> Result<f32, DimensionMismatch> {
+    score(Metric::Manhattan, a, b)
+}
+
+/// Calcule la norme euclidienne d'un vecteur, `f32::INFINITY` si elle dépasse l'étendue
+/// des `f32`.
+#[inline]
+pub fn norm(a: &[f32]) -> f32 {
+    norm_f64(a) as f32
+}
+
+/// Accumulateur des `k` meilleurs couples identifiant et score, quel que soit le nombre
+/// de scores reçus : sa mémoire est proportionnelle à `k`.
+///
+/// Les résultats sont classés comme ceux d'une recherche : du meilleur au moins bon selon
+/// la mesure, les égalités départagées par identifiant croissant. Les scores NaN sont
+/// ignorés.
+#[derive(Debug, Clone)]
+pub struct TopK<K> {
+    k: usize,
+    metric: Metric,
+    /// Le moins bon des résultats retenus est au sommet.
+    heap: BinaryHeap<Entry<K>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<K> {
+    key: K,
+    score: f32,
+    metric: Metric,
+}
+
+impl<K: Ord> Ord for Entry<K> {
+    /// Ordre du classement : le meilleur résultat est le plus petit.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.metric
+            .compare(self.score, other.score)
+            .then_with(|| self.key.cmp(&other.key))
+    }
+}
+
+impl<K: Ord> PartialOrd for Entry<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> PartialEq for Entry<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K: Ord> Eq for Entry<K> {}
+
+impl<K: Ord> TopK<K> {
+    /// Crée un accumulateur des `k` plus grands scores, comme pour une similarité.
+    ///
+    /// # Arguments
+    /// * `k` - Nombre de résultats conservés.
+    pub fn new(k: usize) -> Self {
+        TopK::for_metric(k, Metric::Dot)
+    }
+
+    /// Crée un accumulateur des `k` meilleurs scores de `metric` : les plus petits pour
+    /// une distance.
+    ///
+    /// # Arguments
+    /// * `k` - Nombre de résultats conservés.
+    /// * `metric` - Mesure qui a produit les scores.
+    pub fn for_metric(k: usize, metric: Metric) -> Self {
+        TopK {
+            k,
+            metric,
+            heap: BinaryHeap::with_capacity(k.min(1 << 16).saturating_add(1)),
+        }
+    }
+
+    /// Ajoute un score, retenu s'il fait partie des `k` meilleurs reçus jusque-là.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du vecteur évalué.
+    /// * `score` - Son score.
+    pub fn push(&mut self, key: K, score: f32) {
+        if self.k == 0 || score.is_nan() {
+            return;
+        }
+        let entry = Entry {
+            key,
+            score,
+            metric: self.metric,
+        };
+        if self.heap.len() < self.k {
+            self.heap.push(entry);
+        } else if self.heap.peek().is_some_and(|worst| entry < *worst) {
+            self.heap.pop();
+            self.heap.push(entry);
+        }
+    }
+
+    /// Retourne le nombre de résultats retenus, au plus `k`.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Indique si aucun résultat n'est retenu.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Retourne le moins bon score retenu une fois `k` résultats atteints : un score qui
+    /// ne le bat pas ne sera pas retenu.
+    pub fn threshold(&self) -> Option<f32> {
+        match self.heap.len() == self.k {
+            true => self.heap.peek().map(|worst| worst.score),
+            false => None,
+        }
+    }
+
+    /// Retourne les résultats retenus, du meilleur au moins bon.
+    pub fn into_sorted_hits(self) -> Vec<(K, f32)> {
+        (self.heap.into_sorted_vec().into_iter())
+            .map(|entry| (entry.key, entry.score))
+            .collect()
+    }
+}
+
+pub(crate) fn check_dimensions(a: &[f32], b: &[f32]) -> Result<(), DimensionMismatch> {
+    if a.len() != b.len() {
+        return Err(DimensionMismatch {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Calcule la norme euclidienne d'un vecteur en `f64`, finie pour tout vecteur fini.
+#[inline]
+pub(crate) fn norm_f64(a: &[f32]) -> f64 {
+    sqrt(dot_f64(a, a))
+}
+
+/// Ramène un score calculé en `f64` en `f32`, borné à `±f32::MAX`.
+#[inline]
+pub(crate) fn to_score(score: f64) -> f32 {
+    (score as f32).clamp(-f32::MAX, f32::MAX)
+}
+
+#[inline]
+pub(crate) fn dot_f64(a: &[f32], b: &[f32]) -> f64 {
+    (a.iter().zip(b))
+        .map(|(x, y)| f64::from(*x) * f64::from(*y))
+        .sum()
+}
+
+#[inline]
+fn dot_unchecked(a: &[f32], b: &[f32]) -> f32 {
+    to_score(dot_f64(a, b))
+}
+
+#[inline]
+fn cosine_unchecked(a: &[f32], b: &[f32]) -> f32 {
+    cosine_from(dot_f64(a, b), dot_f64(a, a), dot_f64(b, b))
+}
+
+/// Similarité cosinus à partir du produit scalaire et des carrés des normes ; 0.0 si
+/// l'une des normes est nulle.
+#[inline]
+pub(crate) fn cosine_from(dot: f64, norm_a: f64, norm_b: f64) -> f32 {
+    let magnitude = sqrt(norm_a) * sqrt(norm_b);
+    if magnitude == 0.0 {
+        0.0
+    } else {
+        (dot / magnitude) as f32
+    }
+}
+
+pub(crate) fn weighted_unchecked(metric: Metric, weights: &[f32], a: &[f32], b: &[f32]) -> f32 {
+    let terms = (weights.iter().zip(a).zip(b))
+        .map(|((w, x), y)| (f64::from(*w), f64::from(*x), f64::from(*y)));
+    match metric {
+        Metric::Dot => to_score(terms.map(|(w, x, y)| w * x * y).sum()),
+        Metric::Cosine => {
+            let [dot, norm_a, norm_b] = terms.fold([0.0f64; 3], |sum, (w, x, y)| {
+                [sum[0] + w * x * y, sum[1] + w * x * x, sum[2] + w * y * y]
+            });
+            cosine_from(dot, norm_a, norm_b)
+        }
+        Metric::Euclidean => to_score(sqrt(
+            terms.map(|(w, x, y)| w * (x - y) * (x - y)).sum::<f64>(),
+        )),
+        Metric::Manhattan => to_score(terms.map(|(w, x, y)| w * (x - y).abs()).sum()),
+    }
+}
+
+#[inline]
+fn euclidean_unchecked(a: &[f32], b: &[f32]) -> f32 {
+    let squares = (a.iter().zip(b)).map(|(x, y)| {
+        let difference = f64::from(*x) - f64::from(*y);
+        difference * difference
+    });
+    to_score(sqrt(squares.sum::<f64>()))
+}
+
+#[inline]
+fn manhattan_unchecked(a: &[f32], b: &[f32]) -> f32 {
+    to_score(
+        (a.iter().zip(b))
+            .map(|(x, y)| (f64::from(*x) - f64::from(*y)).abs())
+            .sum(),
+    )
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+
+/// Racine carrée correctement arrondie, comme `f64::sqrt` (IEEE 754), que `core` ne
+/// fournit pas : la racine entière de la mantisse étendue à 106 bits donne les 53 bits
+/// du résultat, arrondis au plus proche d'après le reste.
+#[cfg(not(feature = "std"))]
+fn sqrt(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 || x == f64::INFINITY {
+        return x;
+    }
+    let bits = x.to_bits();
+    let mut exponent = ((bits >> 52) & 0x7ff) as i64;
+    let mut mantissa = bits & ((1 << 52) - 1);
+    if exponent == 0 {
+        // Nombre sous-normal : la mantisse est décalée jusqu'à son bit implicite.
+        let shift = i64::from(mantissa.leading_zeros()) - 11;
+        mantissa <<= shift;
+        exponent = 1 - shift;
+    } else {
+        mantissa |= 1 << 52;
+    }
+    // x = mantissa × 2^(e - 52), l'exposant rendu pair pour que la racine soit exacte.
+    let mut e = exponent - 1023;
+    if e & 1 != 0 {
+        mantissa <<= 1;
+        e -= 1;
+    }
+    let scaled = u128::from(mantissa) << 52;
+    let mut root = scaled.isqrt();
+    // La racine exacte dépasse root + 1/2 si et seulement si le reste dépasse root.
+    if scaled - root * root > root {
+        root += 1;
+    }
+    // Une retenue de root jusqu'à 2^53 passe dans l'exposant.
+    f64::from_bits((((e / 2 + 1023) as u64) << 52) + (root as u64 - (1 << 52)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn scores_match_hand_computed_values() {
+        let (a, b) = ([3.0, 4.0], [4.0, 3.0]);
+        assert_eq!(dot(&a, &b), Ok(24.0));
+        assert_eq!(cosine(&a, &b), Ok(0.96));
+        assert_eq!(euclidean(&a, &b), Ok(2f32.sqrt()));
+        assert_eq!(manhattan(&a, &b), Ok(2.0));
+        assert_eq!(norm(&a), 5.0);
+        assert_eq!(
+            score(Metric::Dot, &a, &[1.0]),
+            Err(DimensionMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    fn top_k_keeps_the_best_scores_of_each_metric() {
+        let scores = [(3, 0.5), (1, 0.9), (4, f32::NAN), (2, 0.5), (5, 0.1)];
+        let mut best = TopK::new(3);
+        let mut nearest = TopK::for_metric(2, Metric::Euclidean);
+        for (key, score) in scores {
+            best.push(key, score);
+            nearest.push(key, score);
+        }
+        assert_eq!(best.threshold(), Some(0.5));
+        // Les égalités sont départagées par identifiant croissant.
+        assert_eq!(best.into_sorted_hits(), vec![(1, 0.9), (2, 0.5), (3, 0.5)]);
+        assert_eq!(nearest.into_sorted_hits(), vec![(5, 0.1), (2, 0.5)]);
+        let mut none = TopK::new(0);
+        none.push(1, 1.0);
+        assert!(none.is_empty() && none.threshold().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn std_functions_give_the_same_bits() {
+        use crate::synthetic::VectorGenerator;
+
+        let mut generator = VectorGenerator::new(16, 3);
+        for _ in 0..50 {
+            let (a, b) = (generator.vector(), generator.vector());
+            let pairs = [
+                (cosine(&a, &b), crate::similarity::cosine(&a, &b)),
+                (dot(&a, &b), crate::similarity::dot(&a, &b)),
+                (euclidean(&a, &b), crate::similarity::euclidean(&a, &b)),
+            ];
+            for (kernel, std) in pairs {
+                assert_eq!(kernel.unwrap().to_bits(), std.unwrap().to_bits());
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Écriture minimale de CSV (RFC 4180), sans dépendance externe.
+
+/// Écrit un champ, entouré de guillemets s'il contient une virgule, un guillemet ou un
+/// saut de ligne ; les guillemets intérieurs sont doublés.
+pub(crate) fn write_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+/// Écrit une ligne terminée par `\n`.
+pub(crate) fn write_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_field(out, field);
+    }
+    out.push('\n');
+}
@@ -0,0 +1,1071 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::collection::{Collection, Document};
+use crate::config::CollectionConfig;
+use crate::database_snapshot::DatabaseSnapshot;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::memory::{Budget, BudgetSlot, CompactReport, MemoryUsage};
+use crate::parallel::SearchRuntime;
+use crate::progress::{BatchReport, ProgressSink, Tracker};
+use crate::retention::Retention;
+use crate::search::{SearchParams, SearchResults};
+use crate::shadow::{self, Shadow, ShadowQuery};
+use crate::sharded::ShardedCollection;
+use crate::storage::Storage;
+use crate::store::{VectorStore, VectorStoreMut};
+
+/// Une structure représentant une base de données composée de plusieurs collections.
+///
+/// Une collection peut être simple ([`Collection`]) ou partitionnée
+/// ([`ShardedCollection`]) ; un nom ne désigne jamais les deux à la fois.
+///
+/// Un alias ([`BaseDeDonnees::set_alias`]) peut remplacer le nom d'une collection dans
+/// toutes les méthodes qui désignent une collection existante.
+///
+/// Les collections sont partagées avec les vues figées de la base
+/// ([`BaseDeDonnees::snapshot`]) : une collection n'est copiée qu'au premier accès en
+/// écriture qui suit une vue encore vivante, et cette copie partage encore ses tables de
+/// documents, de charges utiles, d'identifiants et de documents supprimés, découpées en
+/// segments : une écriture ne copie que le segment qu'elle touche, 1/256 de la table
+/// au-delà de 4096 documents. Les index de charge utile, les espaces de noms et les
+/// suivis d'éviction et d'accès, quand la collection en a, sont copiés en entier.
+#[derive(Default)]
+pub struct BaseDeDonnees {
+    pub(crate) collections: HashMap<String, Arc<Collection>>,
+    pub(crate) sharded: HashMap<String, Arc<ShardedCollection>>,
+    pub(crate) aliases: HashMap<String, String>,
+    pub(crate) runtime: SearchRuntime,
+    pub(crate) sequence: u64,
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) retention: Retention,
+    /// Accès aux fichiers, [`FileSystem`](crate::FileSystem) si `None`.
+    pub(crate) storage: Option<Arc<dyn Storage>>,
+    /// Requêtes fantômes installées par [`BaseDeDonnees::shadow`].
+    pub(crate) shadow: Option<Arc<Shadow>>,
+}
+
+impl BaseDeDonnees {
+    /// Crée une nouvelle instance de `BaseDeDonnees`.
+    pub fn new() -> Self {
+        BaseDeDonnees {
+            collections: HashMap::new(),
+            sharded: HashMap::new(),
+            aliases: HashMap::new(),
+            runtime: SearchRuntime::default(),
+            sequence: 0,
+            memory_limit: None,
+            retention: Retention::default(),
+            storage: None,
+            shadow: None,
+        }
+    }
+
+    /// Retourne le numéro de séquence de la base.
+    ///
+    /// Il augmente à chaque accès en écriture : ajout ou remplacement d'une collection,
+    /// et chaque référence mutable obtenue sur une collection, même si elle n'est pas
+    /// utilisée pour la modifier. Deux lectures du même numéro voient donc les mêmes données.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Capture une vue figée de la base, en lecture seule.
+    ///
+    /// La capture ne copie aucun vecteur : la vue partage les collections de la base, et
+    /// une collection modifiée ensuite est copiée avant sa première modification, sans
+    /// ses tables de documents, qui restent partagées segment par segment (voir
+    /// [`BaseDeDonnees`]). Les modifications ultérieures de la base ne sont donc jamais
+    /// visibles dans la vue.
+    ///
+    /// # Retourne
+    /// * DatabaseSnapshot - La vue, associée au numéro de séquence courant.
+    pub fn snapshot(&self) -> DatabaseSnapshot {
+        DatabaseSnapshot {
+            collections: self.collections.clone(),
+            sharded: self.sharded.clone(),
+            aliases: self.aliases.clone(),
+            sequence: self.sequence,
+        }
+    }
+
+    /// Fixe le réglage des parcours parallèles de la base : il est appliqué aux
+    /// collections existantes et à celles que la base crée ensuite.
+    ///
+    /// Les collections fournies toutes faites ([`BaseDeDonnees::replace`],
+    /// [`BaseDeDonnees::entry`]) conservent leur propre réglage.
+    ///
+    /// # Arguments
+    /// * `runtime` - Nombre de threads et taille des tranches des parcours, par exemple
+    ///   `SearchRuntime::new(4)`.
+    pub fn with_runtime(mut self, runtime: SearchRuntime) -> Self {
+        self.runtime = runtime;
+        for collection in self.collections.values_mut() {
+            Arc::make_mut(collection).runtime = runtime;
+        }
+        self.sharded = self
+            .sharded
+            .into_iter()
+            .map(|(nom, collection)| {
+                let collection = Arc::unwrap_or_clone(collection).with_runtime(runtime);
+                (nom, Arc::new(collection))
+            })
+            .collect();
+        self
+    }
+
+    /// Retourne le réglage des parcours parallèles de la base.
+    pub fn runtime(&self) -> SearchRuntime {
+        self.runtime
+    }
+
+    /// Ajoute une nouvelle collection vide à la base de données.
+    ///
+    /// Une collection existante, simple ou partitionnée, n'est jamais écrasée : utilisez
+    /// [`BaseDeDonnees::replace`] pour remplacer une collection.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * bool - `true` si la collection a été créée, `false` si le nom était déjà pris.
+    pub fn add(&mut self, nom: String) -> bool {
+        self.add_with_config(nom, CollectionConfig::default())
+    }
+
+    /// Ajoute une nouvelle collection vide configurée par `config`.
+    ///
+    /// Comme [`BaseDeDonnees::add`], n'écrase jamais une collection existante : la
+    /// configuration n'est appliquée que si la collection est créée. Un nom déjà utilisé
+    /// comme alias est lui aussi refusé.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    /// * `config` - Configuration de la collection.
+    ///
+    /// # Retourne
+    /// * bool - `true` si la collection a été créée, `false` si le nom était déjà pris.
+    pub fn add_with_config(&mut self, nom: String, config: CollectionConfig) -> bool {
+        if self.sharded.contains_key(&nom) || self.aliases.contains_key(&nom) {
+            return false;
+        }
+        match self.collections.entry(nom) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(Arc::new(
+                    Collection::from_config(config).with_runtime(self.runtime),
+                ));
+                self.sequence += 1;
+                true
+            }
+        }
+    }
+
+    /// Récupère la collection `nom`, en la créant vide si elle n'existe pas.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * Result<&mut Collection> - Référence mutable à la collection.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `nom` désigne une collection partitionnée.
+    pub fn get_or_create(&mut self, nom: &str) -> Result<&mut Collection> {
+        let runtime = self.runtime;
+        Ok(self
+            .entry(nom.to_string())?
+            .or_insert_with(|| Collection::new().with_runtime(runtime)))
+    }
+
+    /// Donne accès à l'emplacement de la collection `nom`, qu'elle existe ou non, à la
+    /// manière de [`HashMap::entry`].
+    ///
+    /// Une collection n'est ainsi configurée que si elle est réellement créée :
+    /// `bdd.entry(nom)?.or_insert_with(|| Collection::new().with_metric(Metric::Dot))`.
+    /// Si `nom` est un alias, l'emplacement est celui de sa cible.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * Result<CollectionEntry> - Emplacement occupé ou libre de la collection.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `nom` désigne une collection partitionnée.
+    pub fn entry(&mut self, nom: String) -> Result<CollectionEntry<'_>> {
+        let nom = match self.aliases.get(&nom) {
+            Some(target) => target.clone(),
+            None => nom,
+        };
+        if self.sharded.contains_key(&nom) {
+            return Err(Error::InvalidConfig(format!(
+                "la collection '{}' est partitionnée",
+                nom
+            )));
+        }
+        self.sequence += 1;
+        let budget = self.budget(&nom);
+        Ok(CollectionEntry(self.collections.entry(nom), budget))
+    }
+
+    /// Remplace la collection `nom` par `collection`, ou l'ajoute si elle n'existe pas.
+    ///
+    /// Seule la structure `Collection` est déplacée : les vecteurs ne sont pas copiés,
+    /// le remplacement est donc en temps constant quelle que soit la taille des collections.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    /// * `collection` - Nouvelle collection.
+    ///
+    /// # Retourne
+    /// * Option<Collection> - L'ancienne collection si elle existait.
+    pub fn replace(&mut self, nom: String, collection: Collection) -> Option<Collection> {
+        let nom = self.aliases.get(&nom).cloned().unwrap_or(nom);
+        self.sequence += 1;
+        self.sharded.remove(&nom);
+        self.collections
+            .insert(nom, Arc::new(collection))
+            .map(Arc::unwrap_or_clone)
+            .map(|mut collection| {
+                collection.budget = BudgetSlot::default();
+                collection
+            })
+    }
+
+    /// Remplace la collection `nom` par une collection partitionnée, ou l'ajoute.
+    ///
+    /// Une collection simple portant le même nom est retirée.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    /// * `collection` - Nouvelle collection partitionnée.
+    ///
+    /// # Retourne
+    /// * Option<ShardedCollection> - L'ancienne collection partitionnée si elle existait.
+    pub fn replace_sharded(
+        &mut self,
+        nom: String,
+        collection: ShardedCollection,
+    ) -> Option<ShardedCollection> {
+        let nom = self.aliases.get(&nom).cloned().unwrap_or(nom);
+        self.sequence += 1;
+        self.collections.remove(&nom);
+        self.sharded
+            .insert(nom, Arc::new(collection))
+            .map(Arc::unwrap_or_clone)
+            .map(|mut collection| {
+                collection.budget = BudgetSlot::default();
+                collection
+            })
+    }
+
+    /// Récupère une référence immuable à une collection par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * Option<&Collection> - Référence optionnelle à la collection.
+    pub fn get(&self, nom: &str) -> Option<&Collection> {
+        self.collections
+            .get(resolve(&self.aliases, nom))
+            .map(|collection| &**collection)
+    }
+
+    /// Récupère une référence mutable à une collection par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * Option<&mut Collection> - Référence mutable optionnelle à la collection.
+    pub fn get_mut(&mut self, nom: &str) -> Option<&mut Collection> {
+        let nom = resolve(&self.aliases, nom);
+        let budget = self.budget(nom);
+        let collection = self.collections.get_mut(nom)?;
+        self.sequence += 1;
+        let collection = Arc::make_mut(collection);
+        collection.budget = BudgetSlot(budget);
+        Some(collection)
+    }
+
+    /// Récupère une référence immuable à une collection partitionnée par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    pub fn get_sharded(&self, nom: &str) -> Option<&ShardedCollection> {
+        self.sharded
+            .get(resolve(&self.aliases, nom))
+            .map(|collection| &**collection)
+    }
+
+    /// Récupère une référence mutable à une collection partitionnée par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    pub fn get_sharded_mut(&mut self, nom: &str) -> Option<&mut ShardedCollection> {
+        let nom = resolve(&self.aliases, nom);
+        let budget = self.budget(nom);
+        let collection = self.sharded.get_mut(nom)?;
+        self.sequence += 1;
+        let collection = Arc::make_mut(collection);
+        collection.budget = BudgetSlot(budget);
+        Some(collection)
+    }
+
+    /// Récupère une collection, simple ou partitionnée, par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * Option<&dyn VectorStore> - Référence optionnelle à la collection.
+    pub fn store(&self, nom: &str) -> Option<&dyn VectorStore> {
+        store(
+            &self.collections,
+            &self.sharded,
+            resolve(&self.aliases, nom),
+        )
+    }
+
+    /// Récupère une référence mutable à une collection, simple ou partitionnée.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Retourne
+    /// * Option<&mut dyn VectorStoreMut> - Référence mutable optionnelle à la collection.
+    pub fn store_mut(&mut self, nom: &str) -> Option<&mut dyn VectorStoreMut> {
+        let nom = resolve(&self.aliases, nom);
+        if !self.collections.contains_key(nom) && !self.sharded.contains_key(nom) {
+            return None;
+        }
+        self.sequence += 1;
+        let budget = self.budget(nom);
+        match self.collections.get_mut(nom) {
+            Some(collection) => {
+                let collection = Arc::make_mut(collection);
+                collection.budget = BudgetSlot(budget);
+                Some(collection as &mut dyn VectorStoreMut)
+            }
+            None => self.sharded.get_mut(nom).map(|collection| {
+                let collection = Arc::make_mut(collection);
+                collection.budget = BudgetSlot(budget);
+                collection as &mut dyn VectorStoreMut
+            }),
+        }
+    }
+
+    /// Effectue une recherche dans une collection spécifique.
+    ///
+    /// # Arguments
+    /// * `cname` - Nom de la collection.
+    /// * `request` - Vecteur de requête.
+    /// * `k` - Nombre de résultats à retourner.
+    ///
+    /// # Retourne
+    /// * Result<Document> - Résultats de la recherche dans la collection spécifiée.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte ce nom.
+    /// * Celles de [`Collection::search`], enveloppées dans `Error::WithContext` avec le
+    ///   nom de la collection.
+    pub fn search(&self, cname: &str, request: impl AsRef<[f32]>, k: usize) -> Result<Document> {
+        let request = request.as_ref();
+        self.shadowed(
+            cname,
+            request,
+            ShadowQuery::K(k),
+            || search(self.store(cname), cname, request, k),
+            shadow::keys,
+        )
+    }
+
+    /// Effectue une recherche paramétrée dans une collection spécifique.
+    ///
+    /// # Arguments
+    /// * `cname` - Nom de la collection.
+    /// * `request` - Vecteur de requête.
+    /// * `params` - Paramètres de la recherche.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Résultats de la recherche dans la collection spécifiée.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte ce nom.
+    /// * Celles de [`Collection::search_with`], enveloppées dans `Error::WithContext` avec
+    ///   le nom de la collection.
+    pub fn search_with(
+        &self,
+        cname: &str,
+        request: impl AsRef<[f32]>,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        let request = request.as_ref();
+        self.shadowed(
+            cname,
+            request,
+            ShadowQuery::Params(params),
+            || search_with(self.store(cname), cname, request, params),
+            |results| shadow::keys(&results.hits),
+        )
+    }
+
+    /// Importe des documents JSONL dans la collection `nom` (voir
+    /// [`Collection::import_jsonl`]).
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection, qui doit exister.
+    /// * `reader` - Flux à lire.
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<BatchReport> - Nombre de documents importés et indication d'annulation.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection simple ne porte ce nom.
+    /// * Celles de [`Collection::import_jsonl`], enveloppées dans `Error::WithContext`
+    ///   avec le nom de la collection et, pour un document refusé, son identifiant et sa
+    ///   ligne.
+    pub fn import_jsonl(
+        &mut self,
+        nom: &str,
+        reader: impl BufRead,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport> {
+        self.get_mut(nom)
+            .ok_or_else(|| Error::CollectionNotFound(nom.to_string()))?
+            .import_jsonl(reader, progress)
+            .map_err(|e| e.with_context(ErrorContext::new(Operation::Import).collection(nom)))
+    }
+
+    /// Fait de `alias` un autre nom de la collection `collection`, simple ou partitionnée.
+    ///
+    /// Un alias existant est redirigé vers sa nouvelle cible en une seule opération :
+    /// sous le verrou d'une [`BaseDeDonneesPartagee`](crate::BaseDeDonneesPartagee),
+    /// une recherche voit l'ancienne ou la nouvelle cible, jamais une collection absente.
+    /// Un alias désigne toujours une collection réelle, jamais un autre alias.
+    ///
+    /// # Arguments
+    /// * `alias` - Nom de l'alias.
+    /// * `collection` - Nom de la collection visée.
+    ///
+    /// # Retourne
+    /// * Result<Option<String>> - L'ancienne cible de l'alias s'il existait.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte le nom `collection`.
+    /// * `Error::InvalidConfig` - Si `alias` est le nom d'une collection.
+    pub fn set_alias(&mut self, alias: String, collection: &str) -> Result<Option<String>> {
+        if !self.collections.contains_key(collection) && !self.sharded.contains_key(collection) {
+            return Err(Error::CollectionNotFound(collection.to_string()));
+        }
+        if self.collections.contains_key(&alias) || self.sharded.contains_key(&alias) {
+            return Err(Error::InvalidConfig(format!(
+                "'{}' est déjà le nom d'une collection",
+                alias
+            )));
+        }
+        self.sequence += 1;
+        Ok(self.aliases.insert(alias, collection.to_string()))
+    }
+
+    /// Supprime un alias ; la collection visée n'est pas modifiée.
+    ///
+    /// # Arguments
+    /// * `alias` - Nom de l'alias.
+    ///
+    /// # Retourne
+    /// * Option<String> - La cible de l'alias s'il existait.
+    pub fn delete_alias(&mut self, alias: &str) -> Option<String> {
+        let target = self.aliases.remove(alias)?;
+        self.sequence += 1;
+        Some(target)
+    }
+
+    /// Retourne le nom de la collection désignée par un alias ou par son propre nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Alias ou nom de collection.
+    ///
+    /// # Retourne
+    /// * Option<&str> - Nom de la collection, ou `None` si rien ne porte ce nom.
+    pub fn resolve<'a>(&'a self, nom: &'a str) -> Option<&'a str> {
+        let nom = resolve(&self.aliases, nom);
+        (self.collections.contains_key(nom) || self.sharded.contains_key(nom)).then_some(nom)
+    }
+
+    /// Retourne les alias de la base avec leur cible, triés par alias.
+    pub fn aliases(&self) -> Vec<(&str, &str)> {
+        let mut aliases: Vec<(&str, &str)> = self
+            .aliases
+            .iter()
+            .map(|(alias, target)| (alias.as_str(), target.as_str()))
+            .collect();
+        aliases.sort_unstable();
+        aliases
+    }
+
+    /// Prépare toutes les collections à leur première recherche avec
+    /// [`VectorStore::warm_up`], plusieurs collections à la fois selon le réglage de la
+    /// base ([`BaseDeDonnees::with_runtime`]).
+    ///
+    /// La progression compte les collections préparées ; l'annulation est vérifiée
+    /// entre deux groupes de collections. L'appel ne modifie pas la base et peut être
+    /// répété sans effet.
+    ///
+    /// # Arguments
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * BatchReport - Nombre de collections préparées et indication d'annulation.
+    pub fn warm_up_all(&self, progress: Option<&dyn ProgressSink>) -> BatchReport {
+        let mut noms: Vec<&String> = self.collections.keys().chain(self.sharded.keys()).collect();
+        noms.sort_unstable();
+        let stores: Vec<&dyn VectorStore> = noms
+            .into_iter()
+            .map(|nom| self.store(nom).expect("nom issu de la base"))
+            .collect();
+        let mut tracker = Tracker::new(progress, Some(stores.len() as u64));
+        for group in stores.chunks(self.runtime.threads()) {
+            if tracker.cancelled() {
+                return tracker.finish(true);
+            }
+            self.runtime.map_chunks(group, 2, |stores| {
+                stores.iter().for_each(|store| store.warm_up());
+                Vec::<()>::new()
+            });
+            group.iter().for_each(|_| tracker.step());
+        }
+        tracker.finish(false)
+    }
+
+    /// Décrit les collections de la base, une ligne par collection dans l'ordre
+    /// alphabétique, avec leur nombre de documents (et de documents en attente de
+    /// vecteur), leur dimension, leur mesure, la pondération éventuelle de leurs
+    /// dimensions et l'empreinte de leur modèle de plongement, puis une ligne par alias.
+    ///
+    /// La dimension affichée est celle de la configuration, ou à défaut celle d'un
+    /// document quelconque ; [`Collection::validate`] vérifie qu'elle est commune à tous.
+    ///
+    /// # Retourne
+    /// * String - Le résumé, terminé par un saut de ligne.
+    pub fn summary(&self) -> String {
+        let mut noms: Vec<&String> = self.collections.keys().chain(self.sharded.keys()).collect();
+        noms.sort_unstable();
+        let mut out = format!("{} collection(s)\n", noms.len());
+        for nom in noms {
+            let store = self.store(nom).expect("nom issu de la base");
+            let (kind, config) = match self.sharded.get(nom) {
+                Some(sharded) => (
+                    format!(" ({} partitions)", sharded.shard_count()),
+                    sharded.config(),
+                ),
+                None => (String::new(), self.collections[nom].config()),
+            };
+            let dimension = config.dimension().or_else(|| {
+                store
+                    .keys()
+                    .next()
+                    .and_then(|key| store.read(key))
+                    .map(<[f32]>::len)
+            });
+            let dimension = match dimension {
+                Some(dimension) => dimension.to_string(),
+                None => "-".to_string(),
+            };
+            let weights = match config.dimension_weights() {
+                Some(_) => ", dimensions pondérées",
+                None => "",
+            };
+            let model = match config.model_fingerprint() {
+                Some(model) => format!(", modèle {}", model),
+                None => String::new(),
+            };
+            let pending = match self.collections.get(nom).map_or(0, |c| c.pending.len()) {
+                0 => String::new(),
+                pending => format!(" (+{} en attente de vecteur)", pending),
+            };
+            out.push_str(&format!(
+                "  {}{} : {} document(s){}, dimension {}, {:?}{}{}\n",
+                nom,
+                kind,
+                store.len(),
+                pending,
+                dimension,
+                store.metric(),
+                weights,
+                model
+            ));
+        }
+        for (alias, target) in self.aliases() {
+            out.push_str(&format!("  {} : alias de {}\n", alias, target));
+        }
+        out
+    }
+}
+
+impl BaseDeDonnees {
+    /// Fixe la mémoire maximale occupée par l'ensemble des collections de la base, ou
+    /// retire la limite avec `None`.
+    ///
+    /// Une insertion qui porterait [`BaseDeDonnees::memory_usage`] au-delà de la limite
+    /// échoue avec `Error::MemoryLimitExceeded`, sauf dans les collections qui évincent
+    /// leurs documents les plus anciens ([`Collection::with_memory_eviction`]). La
+    /// limite s'applique aux collections modifiées à travers la base ; elle est vérifiée
+    /// à l'insertion des vecteurs, une charge utile plus grande réduisant la place laissée
+    /// aux insertions suivantes. Abaisser la limite ne supprime aucun document, et la
+    /// limite n'est pas sauvegardée avec la base.
+    ///
+    /// # Arguments
+    /// * `bytes` - Limite en octets, au sens de [`MemoryUsage::total`].
+    pub fn set_memory_limit(&mut self, bytes: Option<usize>) {
+        self.memory_limit = bytes;
+    }
+
+    /// Retourne la limite de mémoire de la base, si elle est fixée.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Retourne la mémoire occupée par toutes les collections, simples et partitionnées.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.collections
+            .values()
+            .map(|collection| collection.memory_usage())
+            .chain(
+                self.sharded
+                    .values()
+                    .map(|collection| collection.memory_usage()),
+            )
+            .sum()
+    }
+
+    /// Compacte toutes les collections, simples et partitionnées, dans l'ordre
+    /// alphabétique : les documents supprimés en attente sont effacés
+    /// ([`Collection::compact`]) puis la place réservée inutilisée est rendue
+    /// ([`Collection::shrink_to_fit`]).
+    ///
+    /// La progression compte les collections compactées ; l'annulation est vérifiée
+    /// entre deux collections. Une collection encore partagée avec une vue
+    /// ([`BaseDeDonnees::snapshot`]) est d'abord copiée, comme pour toute modification.
+    ///
+    /// # Arguments
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * CompactReport - Nombre de collections compactées, octets rendus et indication
+    ///   d'annulation.
+    pub fn compact_all(&mut self, progress: Option<&dyn ProgressSink>) -> CompactReport {
+        let noms = self.collection_names();
+        let mut tracker = Tracker::new(progress, Some(noms.len() as u64));
+        let mut bytes_reclaimed = 0;
+        for nom in &noms {
+            if tracker.cancelled() {
+                return compact_report(tracker.finish(true), bytes_reclaimed);
+            }
+            bytes_reclaimed += self.compact_collection(nom);
+            tracker.step();
+        }
+        compact_report(tracker.finish(false), bytes_reclaimed)
+    }
+
+    /// Noms des collections simples et partitionnées, dans l'ordre alphabétique.
+    pub(crate) fn collection_names(&self) -> Vec<String> {
+        let mut noms: Vec<String> = (self.collections.keys())
+            .chain(self.sharded.keys())
+            .cloned()
+            .collect();
+        noms.sort_unstable();
+        noms
+    }
+
+    /// Compacte la collection `nom` et retourne les octets rendus, 0 si elle n'existe
+    /// pas.
+    pub(crate) fn compact_collection(&mut self, nom: &str) -> usize {
+        let compact = |collection: &mut Collection| {
+            let before = collection.memory_usage().total();
+            let discarded = collection.compact();
+            let freed = before - collection.memory_usage().total();
+            (discarded, freed + collection.shrink_to_fit())
+        };
+        let (discarded, reclaimed) =
+            match (self.collections.get_mut(nom), self.sharded.get_mut(nom)) {
+                (Some(collection), _) => compact(Arc::make_mut(collection)),
+                (None, Some(collection)) => Arc::make_mut(collection)
+                    .shards_mut()
+                    .iter_mut()
+                    .map(compact)
+                    .fold((0, 0), |(d, r), (discarded, reclaimed)| {
+                        (d + discarded, r + reclaimed)
+                    }),
+                (None, None) => return 0,
+            };
+        if discarded > 0 {
+            self.sequence += 1;
+        }
+        reclaimed
+    }
+
+    /// Retourne la part de la limite de mémoire laissée à la collection `nom` par les autres.
+    fn budget(&self, nom: &str) -> Option<Budget> {
+        let limit = self.memory_limit?;
+        let total = self.memory_usage().total();
+        let own = match self.collections.get(nom) {
+            Some(collection) => collection.memory_usage().total(),
+            None => self
+                .sharded
+                .get(nom)
+                .map_or(0, |collection| collection.memory_usage().total()),
+        };
+        Some(Budget {
+            limit,
+            others: total - own,
+        })
+    }
+
+    /// Message de panique des accès indexés à une collection absente.
+    fn missing_collection(&self, nom: &str) -> String {
+        if self.get_sharded(nom).is_some() {
+            return format!(
+                "la collection '{}' est partitionnée : utilisez get_sharded",
+                nom
+            );
+        }
+        let mut noms: Vec<&str> = self.collections.keys().map(String::as_str).collect();
+        noms.sort_unstable();
+        format!(
+            "la collection '{}' n'existe pas ; collections disponibles : [{}]",
+            nom,
+            noms.join(", ")
+        )
+    }
+}
+
+/// Accès à une collection par `bdd["nom"]`, pour les scripts et les tests.
+///
+/// # Panique
+/// Si aucune collection simple ne porte ce nom ; le message liste les collections
+/// disponibles. [`BaseDeDonnees::get`] est l'accès sans panique à privilégier.
+impl Index<&str> for BaseDeDonnees {
+    type Output = Collection;
+
+    fn index(&self, nom: &str) -> &Collection {
+        match self.get(nom) {
+            Some(collection) => collection,
+            None => panic!("{}", self.missing_collection(nom)),
+        }
+    }
+}
+
+/// Accès mutable à une collection par `bdd["nom"]`.
+///
+/// # Panique
+/// Comme [`Index`] ; [`BaseDeDonnees::get_mut`] est l'accès sans panique à privilégier.
+impl IndexMut<&str> for BaseDeDonnees {
+    fn index_mut(&mut self, nom: &str) -> &mut Collection {
+        if self.get(nom).is_none() {
+            panic!("{}", self.missing_collection(nom));
+        }
+        self.get_mut(nom).expect("présence vérifiée")
+    }
+}
+
+/// Emplacement d'une collection simple, retourné par [`BaseDeDonnees::entry`].
+pub struct CollectionEntry<'a>(Entry<'a, String, Arc<Collection>>, Option<Budget>);
+
+impl<'a> CollectionEntry<'a> {
+    /// Retourne le nom de la collection.
+    pub fn key(&self) -> &String {
+        self.0.key()
+    }
+
+    /// Retourne la collection, en y insérant `collection` si elle n'existe pas.
+    pub fn or_insert(self, collection: Collection) -> &'a mut Collection {
+        self.or_insert_with(|| collection)
+    }
+
+    /// Retourne la collection, en y insérant le résultat de `f` si elle n'existe pas.
+    pub fn or_insert_with<F: FnOnce() -> Collection>(self, f: F) -> &'a mut Collection {
+        let collection = Arc::make_mut(self.0.or_insert_with(|| Arc::new(f())));
+        collection.budget = BudgetSlot(self.1);
+        collection
+    }
+
+    /// Retourne la collection, en créant une collection vide si elle n'existe pas.
+    pub fn or_default(self) -> &'a mut Collection {
+        self.or_insert_with(Collection::new)
+    }
+}
+
+/// Retourne la cible de `nom` si c'est un alias, `nom` lui-même sinon.
+pub(crate) fn resolve<'a>(aliases: &'a HashMap<String, String>, nom: &'a str) -> &'a str {
+    aliases.get(nom).map_or(nom, String::as_str)
+}
+
+/// Retourne la collection, simple ou partitionnée, nommée `nom`.
+pub(crate) fn store<'a>(
+    collections: &'a HashMap<String, Arc<Collection>>,
+    sharded: &'a HashMap<String, Arc<ShardedCollection>>,
+    nom: &str,
+) -> Option<&'a dyn VectorStore> {
+    match collections.get(nom) {
+        Some(collection) => Some(&**collection),
+        None => sharded.get(nom).map(|c| &**c as &dyn VectorStore),
+    }
+}
+
+/// Recherche les `k` documents les plus proches dans `store`, la collection `cname`.
+pub(crate) fn search(
+    store: Option<&dyn VectorStore>,
+    cname: &str,
+    request: &[f32],
+    k: usize,
+) -> Result<Document> {
+    store
+        .ok_or_else(|| Error::CollectionNotFound(cname.to_string()))?
+        .search(request, k)
+        .map_err(|e| e.with_context(ErrorContext::new(Operation::Search).collection(cname)))
+}
+
+/// Recherche selon `params` dans `store`, la collection `cname`.
+pub(crate) fn search_with(
+    store: Option<&dyn VectorStore>,
+    cname: &str,
+    request: &[f32],
+    params: &SearchParams,
+) -> Result<SearchResults> {
+    store
+        .ok_or_else(|| Error::CollectionNotFound(cname.to_string()))?
+        .search_with(request, params)
+        .map_err(|e| {
+            let context = ErrorContext::new(Operation::Search).collection(cname);
+            e.with_context(context.request(params.context.as_ref()))
+        })
+}
+
+fn compact_report(report: BatchReport, bytes_reclaimed: usize) -> CompactReport {
+    CompactReport {
+        collections: report.applied,
+        bytes_reclaimed,
+        cancelled: report.cancelled,
+    }
+}
+
+impl fmt::Debug for BaseDeDonnees {
+    /// Affiche le nombre de documents de chaque collection, sans leurs vecteurs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut noms: Vec<&String> = self.collections.keys().chain(self.sharded.keys()).collect();
+        noms.sort_unstable();
+        f.debug_map()
+            .entries(
+                noms.into_iter()
+                    .map(|nom| (nom, self.store(nom).map_or(0, |store| store.len()))),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::ChannelProgress;
+    use crate::similarity::Metric;
+    use uuid::Uuid;
+
+    #[test]
+    fn adding_twice_keeps_the_documents() {
+        let mut bdd = BaseDeDonnees::new();
+        assert!(bdd.add("docs".to_string()));
+        let key = Uuid::new_v4();
+        bdd.get_mut("docs").unwrap().upsert(key, [1.0, 2.0]).unwrap();
+        assert!(!bdd.add("docs".to_string()));
+        let config = CollectionConfig::builder().metric(Metric::Dot).build().unwrap();
+        assert!(!bdd.add_with_config("docs".to_string(), config));
+        let docs = bdd.get("docs").unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs.read(&key).unwrap(), &[1.0, 2.0]);
+        assert_eq!(docs.metric(), Metric::Cosine);
+    }
+
+    #[test]
+    fn names_of_sharded_collections_and_aliases_are_taken() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace_sharded("partagee".to_string(), ShardedCollection::new(2).unwrap());
+        assert!(!bdd.add("partagee".to_string()));
+        assert!(matches!(bdd.get_or_create("partagee"), Err(Error::InvalidConfig(_))));
+        bdd.add("docs".to_string());
+        bdd.set_alias("courante".to_string(), "docs").unwrap();
+        assert!(!bdd.add("courante".to_string()));
+    }
+
+    #[test]
+    fn get_or_create_and_entry_configure_only_new_collections() {
+        let mut bdd = BaseDeDonnees::new();
+        let key = Uuid::new_v4();
+        bdd.get_or_create("docs").unwrap().upsert(key, [1.0]).unwrap();
+        assert_eq!(bdd.get_or_create("docs").unwrap().len(), 1);
+
+        let dot = || Collection::new().with_metric(Metric::Dot);
+        assert_eq!(bdd.entry("docs".to_string()).unwrap().or_insert_with(dot).metric(), Metric::Cosine);
+        assert_eq!(bdd.entry("autre".to_string()).unwrap().or_insert_with(dot).metric(), Metric::Dot);
+        // Un alias donne l'emplacement de sa cible.
+        bdd.set_alias("courante".to_string(), "docs").unwrap();
+        let entry = bdd.entry("courante".to_string()).unwrap();
+        assert_eq!(entry.key(), "docs");
+        assert_eq!(entry.or_default().len(), 1);
+        assert!(bdd.get("docs").unwrap().read(&key).is_some());
+    }
+
+    /// Base de trois collections aux identifiants fixes.
+    fn sample() -> BaseDeDonnees {
+        let mut bdd = BaseDeDonnees::new();
+        let docs = bdd.get_or_create("docs").unwrap();
+        for i in 1..=7u128 {
+            docs.upsert(Uuid::from_u128(i), [i as f32, 1.0]).unwrap();
+        }
+        bdd.add_with_config(
+            "vide".to_string(),
+            CollectionConfig::builder().metric(Metric::Euclidean).build().unwrap(),
+        );
+        let mut sharded = ShardedCollection::new(3).unwrap().with_metric(Metric::Dot);
+        for i in 1..=4u128 {
+            VectorStoreMut::upsert(&mut sharded, Uuid::from_u128(100 + i), vec![1.0, 0.0, i as f32]).unwrap();
+        }
+        bdd.replace_sharded("partagee".to_string(), sharded);
+        bdd.set_alias("courante".to_string(), "docs").unwrap();
+        bdd
+    }
+
+    #[test]
+    fn summary_and_debug_output_are_stable() {
+        let bdd = sample();
+        assert_eq!(
+            bdd.summary(),
+            "3 collection(s)\n\
+             \x20 docs : 7 document(s), dimension 2, Cosine\n\
+             \x20 partagee (3 partitions) : 4 document(s), dimension 3, Dot\n\
+             \x20 vide : 0 document(s), dimension -, Euclidean\n\
+             \x20 courante : alias de docs\n"
+        );
+        assert_eq!(format!("{:?}", bdd), r#"{"docs": 7, "partagee": 4, "vide": 0}"#);
+
+        // Le Debug d'une collection borne la liste des identifiants et omet les vecteurs.
+        let debug = format!("{:?}", bdd.get("docs").unwrap());
+        assert!(debug.starts_with("Collection { len: 7, dimension: Some(2), metric: Cosine, "));
+        let ids: Vec<String> = (1..=5u128).map(|i| Uuid::from_u128(i).to_string()).collect();
+        assert!(debug.ends_with(&format!(
+            "tombstones: 0, namespaces: 0, first_ids: [{}], .. }}",
+            ids.join(", ")
+        )));
+        let debug = format!("{:?}", bdd.get("vide").unwrap());
+        assert!(debug.starts_with("Collection { len: 0, dimension: None, metric: Euclidean, "));
+        assert!(debug.ends_with("first_ids: [], .. }"));
+    }
+
+    #[test]
+    fn search_results_display_as_a_table() {
+        let bdd = sample();
+        let results = bdd.search_with("docs", [1.0, 1.0], &SearchParams::new(3)).unwrap();
+        assert_eq!(
+            results.to_string(),
+            "rang  identifiant                                score\n\
+             \x20  1  00000000-0000-0000-0000-000000000001       1.000\n\
+             \x20  2  00000000-0000-0000-0000-000000000002      0.9487\n\
+             \x20  3  00000000-0000-0000-0000-000000000003      0.8944\n"
+        );
+        assert_eq!(
+            format!("{:.3}", results),
+            "rang  identifiant                                score\n\
+             \x20  1  00000000-0000-0000-0000-000000000001        1.00\n\
+             \x20  2  00000000-0000-0000-0000-000000000002       0.949\n\
+             \x20  3  00000000-0000-0000-0000-000000000003       0.894\n"
+        );
+        let empty = bdd.search_with("vide", [1.0, 1.0], &SearchParams::new(3)).unwrap();
+        assert_eq!(empty.to_string(), "aucun résultat\n");
+    }
+
+    /// Message de la panique de `f`.
+    fn panic_message(f: impl FnOnce()) -> String {
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+            .expect_err("panique attendue");
+        match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast::<&str>().expect("message textuel").to_string(),
+        }
+    }
+
+    #[test]
+    fn index_panics_naming_the_missing_and_available_collections() {
+        let bdd = sample();
+        assert_eq!(bdd["docs"].len(), 7);
+        // Un alias désigne sa cible.
+        assert_eq!(bdd["courante"].len(), 7);
+        assert_eq!(
+            panic_message(|| {
+                let _ = bdd["ICC"].len();
+            }),
+            "la collection 'ICC' n'existe pas ; collections disponibles : [docs, vide]"
+        );
+        assert_eq!(
+            panic_message(|| {
+                let _ = bdd["partagee"].len();
+            }),
+            "la collection 'partagee' est partitionnée : utilisez get_sharded"
+        );
+        let mut bdd = sample();
+        bdd["vide"].upsert(Uuid::new_v4(), [1.0]).unwrap();
+        assert_eq!(bdd.get("vide").unwrap().len(), 1);
+        let message = panic_message(move || drop(bdd["absente"].upsert(Uuid::new_v4(), [1.0])));
+        assert!(message.starts_with("la collection 'absente' n'existe pas"));
+    }
+
+    #[test]
+    fn expect_read_panics_with_the_uuid() {
+        let bdd = sample();
+        assert_eq!(bdd["docs"].expect_read(&Uuid::from_u128(3)), &[3.0, 1.0]);
+        let key = Uuid::from_u128(42);
+        assert_eq!(
+            panic_message(|| {
+                bdd["docs"].expect_read(&key);
+            }),
+            format!("le document {} n'existe pas dans la collection", key)
+        );
+    }
+
+    #[test]
+    fn warm_up_all_counts_the_collections_and_changes_nothing() {
+        let bdd = sample().with_runtime(SearchRuntime::new(2));
+        let before = bdd.to_bytes();
+        for _ in 0..2 {
+            let (sink, receiver) = ChannelProgress::new();
+            let report = bdd.warm_up_all(Some(&sink));
+            assert_eq!(
+                report,
+                BatchReport {
+                    applied: 3,
+                    cancelled: false
+                }
+            );
+            let last = receiver.try_iter().last().unwrap();
+            assert_eq!((last.done, last.total), (3, Some(3)));
+        }
+        assert_eq!(bdd.to_bytes(), before);
+
+        let (sink, _receiver) = ChannelProgress::new();
+        sink.cancel();
+        assert_eq!(
+            bdd.warm_up_all(Some(&sink)),
+            BatchReport {
+                applied: 0,
+                cancelled: true
+            }
+        );
+        assert_eq!(bdd.warm_up_all(None).applied, 3);
+    }
+}
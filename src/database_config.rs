@@ -0,0 +1,763 @@
+//! Déclaration des collections d'une base, appliquée au démarrage d'un déploiement.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::collection::{Collection, ZeroVectorPolicy};
+use crate::config::CollectionConfig;
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::model::ModelFingerprint;
+use crate::payload::Value;
+use crate::payload_index::IndexKind;
+use crate::schema::{FieldType, PayloadSchema, SchemaMode};
+use crate::similarity::Metric;
+use crate::toml;
+
+/// Collection attendue par une [`DatabaseConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CollectionDeclaration {
+    /// Configuration complète de la collection.
+    pub config: CollectionConfig,
+    /// Schéma des charges utiles, ou `None` pour ne pas en imposer.
+    pub schema: Option<PayloadSchema>,
+    /// Index de charge utile à créer, par champ.
+    pub indexes: BTreeMap<String, IndexKind>,
+}
+
+/// Collections qu'une base doit contenir, appliquées par [`BaseDeDonnees::apply_config`].
+///
+/// Se lit depuis un fichier TOML ou JSON ([`DatabaseConfig::load`]) : une table
+/// `collections` associe à chaque nom les réglages de [`CollectionConfigBuilder`](crate::CollectionConfigBuilder)
+/// (`dimension`, `metric`, `normalized`, `zero_vector_policy`, `strict_dimensions`,
+/// `soft_delete`, `memory_eviction`, `shared_vectors`, `vectors_are_normalized`,
+/// `model_fingerprint`, `dimension_weights`, `norm_range`, `access_tracking`,
+/// `aggregate_noise`), un `schema` et des `indexes` :
+///
+/// ```toml
+/// [collections.articles]
+/// dimension = 768
+/// metric = "cosine"
+/// model_fingerprint = { name = "text-embedding-3-small", hash = "abc123" }
+///
+/// [collections.articles.schema]
+/// mode = "strict"
+/// fields.annee = { type = "number", required = true }
+///
+/// [collections.articles.indexes]
+/// langue = "keyword"
+/// annee = "numeric"
+/// ```
+///
+/// Une clé inconnue est refusée, pour qu'une faute de frappe ne passe pas inaperçue.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DatabaseConfig {
+    /// Collections déclarées, par nom.
+    pub collections: BTreeMap<String, CollectionDeclaration>,
+}
+
+impl DatabaseConfig {
+    /// Crée une déclaration sans collection.
+    pub fn new() -> Self {
+        DatabaseConfig::default()
+    }
+
+    /// Déclare la collection `nom`, en remplaçant une déclaration précédente.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    /// * `declaration` - Configuration, schéma et index attendus.
+    pub fn collection(
+        mut self,
+        nom: impl Into<String>,
+        declaration: CollectionDeclaration,
+    ) -> Self {
+        self.collections.insert(nom.into(), declaration);
+        self
+    }
+
+    /// Lit une déclaration au format TOML.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si le texte n'est pas du TOML pris en charge, ou si une
+    ///   clé ou une valeur est invalide.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let value = toml::parse(text).map_err(|message| invalid("", &message))?;
+        DatabaseConfig::from_value(&value)
+    }
+
+    /// Lit une déclaration au format JSON, de même structure qu'en TOML.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si le texte n'est pas du JSON, ou si une clé ou une
+    ///   valeur est invalide.
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value = json::parse(text).map_err(|message| invalid("", &message))?;
+        DatabaseConfig::from_value(&value)
+    }
+
+    /// Lit le fichier de déclaration `path`, en JSON si son extension est `.json` et en
+    /// TOML sinon.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    /// * Celles de [`DatabaseConfig::from_toml`] ou [`DatabaseConfig::from_json`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            true => DatabaseConfig::from_json(&text),
+            false => DatabaseConfig::from_toml(&text),
+        }
+    }
+
+    fn from_value(value: &Value) -> Result<Self> {
+        let mut config = DatabaseConfig::new();
+        for (key, value) in object(value, "")? {
+            match key.as_str() {
+                "collections" => {
+                    for (nom, value) in object(value, "collections")? {
+                        let path = format!("collections.{}", nom);
+                        config
+                            .collections
+                            .insert(nom.clone(), declaration(value, &path)?);
+                    }
+                }
+                _ => return Err(unknown_key("", key)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Ce que [`BaseDeDonnees::apply_config`] a modifié ; tout est vide si la base était
+/// déjà conforme.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConfigReport {
+    /// Collections créées, par nom croissant.
+    pub created: Vec<String>,
+    /// Collections existantes qui ont reçu leur schéma de charge utile.
+    pub schemas_set: Vec<String>,
+    /// Index créés, en couples collection et champ.
+    pub indexes_created: Vec<(String, String)>,
+}
+
+impl ConfigReport {
+    /// Indique si l'application n'a rien modifié.
+    pub fn is_unchanged(&self) -> bool {
+        self.created.is_empty() && self.schemas_set.is_empty() && self.indexes_created.is_empty()
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    /// Affiche une ligne par modification, ou une ligne indiquant que la base était
+    /// conforme.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unchanged() {
+            return writeln!(f, "base déjà conforme à la configuration");
+        }
+        for nom in &self.created {
+            writeln!(f, "collection '{}' créée", nom)?;
+        }
+        for nom in &self.schemas_set {
+            writeln!(f, "schéma de la collection '{}' appliqué", nom)?;
+        }
+        for (nom, field) in &self.indexes_created {
+            writeln!(f, "index '{}' de la collection '{}' créé", field, nom)?;
+        }
+        Ok(())
+    }
+}
+
+impl BaseDeDonnees {
+    /// Met la base en conformité avec `config` : crée les collections manquantes avec leur
+    /// configuration, leur schéma et leurs index, et, pour les collections existantes,
+    /// vérifie leur configuration puis leur ajoute le schéma et les index déclarés qui
+    /// leur manquent.
+    ///
+    /// L'opération est idempotente : appliquer deux fois la même déclaration ne modifie
+    /// rien la seconde fois. Une collection existante n'est jamais reconfigurée ; les
+    /// index et schémas qu'elle a en plus de la déclaration sont conservés, et
+    /// l'empreinte de modèle qu'elle a reçue d'une insertion convient à une déclaration
+    /// qui n'en fixe pas.
+    ///
+    /// # Arguments
+    /// * `config` - Collections attendues.
+    ///
+    /// # Retourne
+    /// * Result<ConfigReport> - Ce qui a été créé ou appliqué.
+    ///
+    /// # Erreurs
+    /// * `Error::ConfigConflict` - Si une collection existante diffère de sa déclaration
+    ///   (dimension, mesure, schéma, type d'un index...), ou si un nom déclaré désigne
+    ///   une collection partitionnée ou un alias. Toutes les collections sont vérifiées
+    ///   avant la première modification : la base reste alors inchangée.
+    pub fn apply_config(&mut self, config: DatabaseConfig) -> Result<ConfigReport> {
+        for (nom, declaration) in &config.collections {
+            self.check_declaration(nom, declaration)?;
+        }
+        let mut report = ConfigReport::default();
+        for (nom, declaration) in config.collections {
+            let created = self.add_with_config(nom.clone(), declaration.config);
+            if created {
+                report.created.push(nom.clone());
+            }
+            let current = self.get(&nom).expect("collection vérifiée ou créée");
+            let schema = declaration
+                .schema
+                .filter(|_| current.payload_schema().is_none());
+            let indexed: Vec<&str> = (current.payload_indexes().into_iter())
+                .map(|(field, _)| field)
+                .collect();
+            let indexes: Vec<(String, IndexKind)> = (declaration.indexes.into_iter())
+                .filter(|(field, _)| !indexed.contains(&field.as_str()))
+                .collect();
+            if schema.is_none() && indexes.is_empty() {
+                continue;
+            }
+            let collection = self.get_mut(&nom).expect("collection vérifiée ou créée");
+            if schema.is_some() {
+                collection.set_payload_schema(schema);
+                if !created {
+                    report.schemas_set.push(nom.clone());
+                }
+            }
+            for (field, kind) in indexes {
+                collection.create_payload_index(&field, kind);
+                report.indexes_created.push((nom.clone(), field));
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl BaseDeDonnees {
+    /// Vérifie que la déclaration de `nom` peut être appliquée : `nom` ne désigne ni une
+    /// collection partitionnée ni un alias, et la collection, si elle existe, ne
+    /// contredit pas sa déclaration.
+    ///
+    /// # Erreurs
+    /// * `Error::ConfigConflict` - Décrit le premier réglage en conflit.
+    pub(crate) fn check_declaration(
+        &self,
+        nom: &str,
+        declaration: &CollectionDeclaration,
+    ) -> Result<()> {
+        let existing = match (self.sharded.get(nom), self.aliases.get(nom)) {
+            (Some(_), _) => Some("collection partitionnée"),
+            (None, Some(_)) => Some("alias"),
+            (None, None) => None,
+        };
+        if let Some(existing) = existing {
+            return Err(conflict(nom, "type", "collection simple", existing));
+        }
+        match self.collections.get(nom) {
+            Some(collection) => check_collection(nom, collection, declaration),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Vérifie qu'une collection existante ne contredit pas sa déclaration.
+fn check_collection(
+    nom: &str,
+    collection: &Collection,
+    declaration: &CollectionDeclaration,
+) -> Result<()> {
+    let (declared, existing) = (&declaration.config, collection.config());
+    let settings = [
+        (
+            "dimension",
+            describe(declared.dimension()),
+            describe(existing.dimension()),
+        ),
+        (
+            "metric",
+            metric_name(declared.metric()).to_string(),
+            metric_name(existing.metric()).to_string(),
+        ),
+        (
+            "normalized",
+            declared.normalized().to_string(),
+            existing.normalized().to_string(),
+        ),
+        (
+            "zero_vector_policy",
+            policy_name(declared.zero_vector_policy()).to_string(),
+            policy_name(existing.zero_vector_policy()).to_string(),
+        ),
+        (
+            "strict_dimensions",
+            declared.strict_dimensions().to_string(),
+            existing.strict_dimensions().to_string(),
+        ),
+        (
+            "soft_delete",
+            declared.soft_delete().to_string(),
+            existing.soft_delete().to_string(),
+        ),
+        (
+            "memory_eviction",
+            declared.memory_eviction().to_string(),
+            existing.memory_eviction().to_string(),
+        ),
+        (
+            "shared_vectors",
+            declared.shared_vectors().to_string(),
+            existing.shared_vectors().to_string(),
+        ),
+        (
+            "vectors_are_normalized",
+            declared.vectors_are_normalized().to_string(),
+            existing.vectors_are_normalized().to_string(),
+        ),
+        (
+            "dimension_weights",
+            weights_count(declared.dimension_weights()),
+            weights_count(existing.dimension_weights()),
+        ),
+        (
+            "norm_range",
+            norm_range(declared.norm_range()),
+            norm_range(existing.norm_range()),
+        ),
+        (
+            "access_tracking",
+            declared.access_tracking().to_string(),
+            existing.access_tracking().to_string(),
+        ),
+        (
+            "aggregate_noise",
+            describe(declared.aggregate_noise()),
+            describe(existing.aggregate_noise()),
+        ),
+    ];
+    for (setting, declared, existing) in settings {
+        if declared != existing {
+            return Err(conflict(nom, setting, &declared, &existing));
+        }
+    }
+    if declared.dimension_weights() != existing.dimension_weights() {
+        return Err(conflict(
+            nom,
+            "dimension_weights",
+            "des poids différents",
+            "les poids actuels",
+        ));
+    }
+    if let Some(model) = declared.model_fingerprint() {
+        match existing.model_fingerprint() {
+            Some(current) if current == model => {}
+            current => {
+                return Err(conflict(
+                    nom,
+                    "model_fingerprint",
+                    &model.to_string(),
+                    &describe(current),
+                ))
+            }
+        }
+    }
+    if let (Some(schema), Some(current)) = (&declaration.schema, collection.payload_schema()) {
+        if schema != current {
+            return Err(conflict(
+                nom,
+                "schema",
+                "un schéma différent",
+                "le schéma actuel",
+            ));
+        }
+    }
+    for (field, kind) in &declaration.indexes {
+        let current = (collection.payload_indexes().into_iter()).find(|(name, _)| name == field);
+        if let Some((_, current)) = current.filter(|(_, current)| current != kind) {
+            return Err(conflict(
+                nom,
+                &format!("indexes.{}", field),
+                index_name(*kind),
+                index_name(current),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn conflict(collection: &str, setting: &str, declared: &str, existing: &str) -> Error {
+    Error::ConfigConflict {
+        collection: collection.to_string(),
+        setting: setting.to_string(),
+        declared: declared.to_string(),
+        existing: existing.to_string(),
+    }
+}
+
+fn describe<T: fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "aucune".to_string(), |value| value.to_string())
+}
+
+fn norm_range(range: Option<(f32, f32)>) -> String {
+    range.map_or_else(
+        || "aucune".to_string(),
+        |(min, max)| format!("[{}, {}]", min, max),
+    )
+}
+
+fn weights_count(weights: Option<&[f32]>) -> String {
+    weights.map_or_else(
+        || "aucun poids".to_string(),
+        |w| format!("{} poids", w.len()),
+    )
+}
+
+pub(crate) fn metric_name(metric: Metric) -> &'static str {
+    match metric {
+        Metric::Cosine => "cosine",
+        Metric::Dot => "dot",
+        Metric::Euclidean => "euclidean",
+        Metric::Manhattan => "manhattan",
+    }
+}
+
+fn policy_name(policy: ZeroVectorPolicy) -> &'static str {
+    match policy {
+        ZeroVectorPolicy::Reject => "reject",
+        ZeroVectorPolicy::ScoreZero => "score_zero",
+    }
+}
+
+fn index_name(kind: IndexKind) -> &'static str {
+    match kind {
+        IndexKind::Keyword => "keyword",
+        IndexKind::Numeric => "numeric",
+    }
+}
+
+/// Lit la déclaration d'une collection, située à `path` dans le fichier.
+fn declaration(value: &Value, path: &str) -> Result<CollectionDeclaration> {
+    let mut builder = CollectionConfig::builder();
+    let mut declaration = CollectionDeclaration::default();
+    for (key, value) in object(value, path)? {
+        let at = format!("{}.{}", path, key);
+        builder = match key.as_str() {
+            "dimension" => builder.dimension(integer(value, &at)?),
+            "metric" => builder.metric(choice(
+                value,
+                &at,
+                &[
+                    ("cosine", Metric::Cosine),
+                    ("dot", Metric::Dot),
+                    ("euclidean", Metric::Euclidean),
+                    ("manhattan", Metric::Manhattan),
+                ],
+            )?),
+            "normalized" => builder.normalized(boolean(value, &at)?),
+            "zero_vector_policy" => builder.zero_vector_policy(choice(
+                value,
+                &at,
+                &[
+                    ("reject", ZeroVectorPolicy::Reject),
+                    ("score_zero", ZeroVectorPolicy::ScoreZero),
+                ],
+            )?),
+            "strict_dimensions" => builder.strict_dimensions(boolean(value, &at)?),
+            "soft_delete" => builder.soft_delete(boolean(value, &at)?),
+            "memory_eviction" => builder.memory_eviction(boolean(value, &at)?),
+            "shared_vectors" => builder.shared_vectors(boolean(value, &at)?),
+            "vectors_are_normalized" => builder.vectors_are_normalized(boolean(value, &at)?),
+            "model_fingerprint" => builder.model_fingerprint(model(value, &at)?),
+            "dimension_weights" => {
+                let Value::Array(items) = value else {
+                    return Err(invalid(&at, "tableau de nombres attendu"));
+                };
+                let weights = (items.iter())
+                    .map(|item| item.as_f64().map(|w| w as f32))
+                    .collect::<Option<Vec<f32>>>()
+                    .ok_or_else(|| invalid(&at, "tableau de nombres attendu"))?;
+                builder.dimension_weights(weights)
+            }
+            "norm_range" => {
+                let bounds = match value {
+                    Value::Array(items) => (items.iter())
+                        .map(|item| item.as_f64().map(|bound| bound as f32))
+                        .collect::<Option<Vec<f32>>>(),
+                    _ => None,
+                };
+                let Some([min, max]) = bounds.as_deref() else {
+                    return Err(invalid(&at, "tableau [min, max] attendu"));
+                };
+                builder.norm_range(*min, *max)
+            }
+            "access_tracking" => builder.access_tracking(boolean(value, &at)?),
+            "aggregate_noise" => builder.aggregate_noise(
+                value
+                    .as_f64()
+                    .ok_or_else(|| invalid(&at, "nombre attendu"))?,
+            ),
+            "schema" => {
+                declaration.schema = Some(schema(value, &at)?);
+                builder
+            }
+            "indexes" => {
+                for (field, value) in object(value, &at)? {
+                    let kind = choice(
+                        value,
+                        &format!("{}.{}", at, field),
+                        &[
+                            ("keyword", IndexKind::Keyword),
+                            ("numeric", IndexKind::Numeric),
+                        ],
+                    )?;
+                    declaration.indexes.insert(field.clone(), kind);
+                }
+                builder
+            }
+            _ => return Err(unknown_key(path, key)),
+        };
+    }
+    declaration.config = builder
+        .build()
+        .map_err(|error| invalid(path, &error.to_string()))?;
+    Ok(declaration)
+}
+
+fn schema(value: &Value, path: &str) -> Result<PayloadSchema> {
+    let mut schema = PayloadSchema::new();
+    for (key, value) in object(value, path)? {
+        let at = format!("{}.{}", path, key);
+        match key.as_str() {
+            "mode" => {
+                let modes = [
+                    ("strict", SchemaMode::Strict),
+                    ("lenient", SchemaMode::Lenient),
+                ];
+                schema = schema.with_mode(choice(value, &at, &modes)?);
+            }
+            "fields" => {
+                for (name, value) in object(value, &at)? {
+                    let field_path = format!("{}.{}", at, name);
+                    let (mut kind, mut required) = (None, false);
+                    for (key, value) in object(value, &field_path)? {
+                        let at = format!("{}.{}", field_path, key);
+                        match key.as_str() {
+                            "type" => {
+                                let types = [
+                                    ("bool", FieldType::Bool),
+                                    ("number", FieldType::Number),
+                                    ("string", FieldType::String),
+                                    ("array", FieldType::Array),
+                                    ("object", FieldType::Object),
+                                ];
+                                kind = Some(choice(value, &at, &types)?);
+                            }
+                            "required" => required = boolean(value, &at)?,
+                            _ => return Err(unknown_key(&field_path, key)),
+                        }
+                    }
+                    let kind =
+                        kind.ok_or_else(|| invalid(&field_path, "clé « type » manquante"))?;
+                    schema = schema.field(name.clone(), kind, required);
+                }
+            }
+            _ => return Err(unknown_key(path, key)),
+        }
+    }
+    Ok(schema)
+}
+
+/// Lit une empreinte de modèle : un nom seul, ou une table `{ name, hash }`.
+fn model(value: &Value, path: &str) -> Result<ModelFingerprint> {
+    if let Some(name) = value.as_str() {
+        return Ok(ModelFingerprint::new(name));
+    }
+    let (mut name, mut hash) = (None, None);
+    for (key, value) in object(value, path)? {
+        let at = format!("{}.{}", path, key);
+        let text = value
+            .as_str()
+            .ok_or_else(|| invalid(&at, "chaîne attendue"))?;
+        match key.as_str() {
+            "name" => name = Some(text),
+            "hash" => hash = Some(text),
+            _ => return Err(unknown_key(path, key)),
+        }
+    }
+    let model = ModelFingerprint::new(name.ok_or_else(|| invalid(path, "clé « name » manquante"))?);
+    Ok(match hash {
+        Some(hash) => model.with_hash(hash),
+        None => model,
+    })
+}
+
+fn object<'a>(value: &'a Value, path: &str) -> Result<&'a BTreeMap<String, Value>> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(invalid(path, "table attendue")),
+    }
+}
+
+fn integer(value: &Value, path: &str) -> Result<usize> {
+    value
+        .as_f64()
+        .filter(|n| n.fract() == 0.0 && *n >= 0.0 && *n <= u32::MAX as f64)
+        .map(|n| n as usize)
+        .ok_or_else(|| invalid(path, "entier positif attendu"))
+}
+
+fn boolean(value: &Value, path: &str) -> Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| invalid(path, "booléen attendu"))
+}
+
+fn choice<T: Copy>(value: &Value, path: &str, choices: &[(&str, T)]) -> Result<T> {
+    value
+        .as_str()
+        .and_then(|text| choices.iter().find(|(name, _)| *name == text))
+        .map(|(_, choice)| *choice)
+        .ok_or_else(|| {
+            let names: Vec<String> = choices
+                .iter()
+                .map(|(name, _)| format!("\"{}\"", name))
+                .collect();
+            invalid(path, &format!("valeur parmi {} attendue", names.join(", ")))
+        })
+}
+
+fn unknown_key(path: &str, key: &str) -> Error {
+    invalid(path, &format!("clé inconnue « {} »", key))
+}
+
+fn invalid(path: &str, message: &str) -> Error {
+    match path {
+        "" => Error::InvalidConfig(format!("fichier de configuration : {}", message)),
+        _ => Error::InvalidConfig(format!("fichier de configuration, {} : {}", path, message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    const ARTICLES: &str = r#"
+[collections.articles]
+dimension = 3
+metric = "dot"
+
+[collections.articles.schema]
+mode = "strict"
+fields.annee = { type = "number", required = true }
+
+[collections.articles.indexes]
+langue = "keyword"
+annee = "numeric"
+"#;
+
+    #[test]
+    fn missing_collections_are_created_then_left_alone() {
+        let config = DatabaseConfig::from_toml(ARTICLES).unwrap();
+        let mut db = BaseDeDonnees::new();
+        let report = db.apply_config(config.clone()).unwrap();
+        assert_eq!(report.created, ["articles"]);
+        assert!(report.schemas_set.is_empty());
+        assert_eq!(report.indexes_created.len(), 2);
+
+        let articles = db.get("articles").unwrap();
+        assert_eq!(articles.config().dimension(), Some(3));
+        assert_eq!(articles.config().metric(), Metric::Dot);
+        let schema = articles.payload_schema().unwrap();
+        assert_eq!(schema.mode(), SchemaMode::Strict);
+        assert_eq!(schema.fields().count(), 1);
+        assert_eq!(
+            articles.payload_indexes(),
+            [("annee", IndexKind::Numeric), ("langue", IndexKind::Keyword)]
+        );
+
+        // La seconde application ne modifie rien.
+        let report = db.apply_config(config).unwrap();
+        assert!(report.is_unchanged());
+        assert_eq!(report.to_string(), "base déjà conforme à la configuration\n");
+    }
+
+    #[test]
+    fn existing_collections_only_receive_what_they_lack() {
+        let mut db = BaseDeDonnees::new();
+        let config = CollectionConfig::builder()
+            .dimension(3)
+            .metric(Metric::Dot)
+            .build()
+            .unwrap();
+        db.add_with_config("articles".to_string(), config);
+        let articles = db.get_mut("articles").unwrap();
+        articles.upsert(Uuid::new_v4(), [1.0, 2.0, 3.0]).unwrap();
+        articles.create_payload_index("langue", IndexKind::Keyword);
+        articles.create_payload_index("auteur", IndexKind::Keyword);
+
+        let report = db
+            .apply_config(DatabaseConfig::from_toml(ARTICLES).unwrap())
+            .unwrap();
+        assert!(report.created.is_empty());
+        assert_eq!(report.schemas_set, ["articles"]);
+        assert_eq!(
+            report.indexes_created,
+            [("articles".to_string(), "annee".to_string())]
+        );
+        let articles = db.get("articles").unwrap();
+        assert_eq!(articles.len(), 1);
+        // Les index non déclarés sont conservés.
+        assert_eq!(articles.payload_indexes().len(), 3);
+    }
+
+    #[test]
+    fn conflicts_leave_the_base_unchanged() {
+        let mut db = BaseDeDonnees::new();
+        let config = CollectionConfig::builder().dimension(4).build().unwrap();
+        db.add_with_config("articles".to_string(), config);
+        let declared = ARTICLES.to_string() + "\n[collections.neuve]\ndimension = 2\n";
+        let result = db.apply_config(DatabaseConfig::from_toml(&declared).unwrap());
+        assert!(matches!(
+            result,
+            Err(Error::ConfigConflict { ref collection, ref setting, ref declared, ref existing })
+                if collection == "articles" && setting == "dimension" && declared == "3" && existing == "4"
+        ));
+        // Rien n'est créé avant d'avoir tout vérifié.
+        assert!(db.get("neuve").is_none());
+        assert!(db.get("articles").unwrap().payload_indexes().is_empty());
+
+        let mut db = BaseDeDonnees::new();
+        db.apply_config(DatabaseConfig::from_toml(ARTICLES).unwrap())
+            .unwrap();
+        let retyped = ARTICLES.replace("annee = \"numeric\"", "annee = \"keyword\"");
+        assert!(matches!(
+            db.apply_config(DatabaseConfig::from_toml(&retyped).unwrap()),
+            Err(Error::ConfigConflict { ref setting, .. }) if setting == "indexes.annee"
+        ));
+    }
+
+    #[test]
+    fn unknown_keys_and_bad_values_are_refused() {
+        for text in [
+            "[collections.articles]\ndimensoin = 3\n",
+            "[collections.articles]\nmetric = \"hamming\"\n",
+            "[collection.articles]\ndimension = 3\n",
+        ] {
+            assert!(
+                matches!(DatabaseConfig::from_toml(text), Err(Error::InvalidConfig(_))),
+                "{}",
+                text
+            );
+        }
+        let json = r#"{"collections": {"articles": {"dimension": 3, "metric": "dot"}}}"#;
+        let config = DatabaseConfig::from_json(json).unwrap();
+        assert_eq!(
+            config.collections["articles"].config.metric(),
+            Metric::Dot
+        );
+    }
+}
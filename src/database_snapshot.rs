@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::collection::{Collection, Document};
+use crate::database;
+use crate::error::Result;
+use crate::search::{SearchParams, SearchResults};
+use crate::sharded::ShardedCollection;
+use crate::store::VectorStore;
+
+/// Vue figée d'une [`BaseDeDonnees`](crate::BaseDeDonnees), créée par
+/// [`BaseDeDonnees::snapshot`](crate::BaseDeDonnees::snapshot).
+///
+/// Toutes les lectures d'une même vue portent sur les mêmes données, quelles que soient
+/// les modifications faites depuis dans la base : plusieurs requêtes successives
+/// (recherche puis matrice de distances, par exemple) restent cohérentes entre elles.
+/// La vue est `Send + Sync` et peut être clonée à moindre coût. Elle conserve les
+/// alias de la base au moment de la capture.
+#[derive(Clone)]
+pub struct DatabaseSnapshot {
+    pub(crate) collections: HashMap<String, Arc<Collection>>,
+    pub(crate) sharded: HashMap<String, Arc<ShardedCollection>>,
+    pub(crate) aliases: HashMap<String, String>,
+    pub(crate) sequence: u64,
+}
+
+impl DatabaseSnapshot {
+    /// Retourne le numéro de séquence de la base au moment de la capture.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Retourne les noms des collections, simples et partitionnées, triés.
+    pub fn names(&self) -> Vec<&str> {
+        let mut noms: Vec<&str> = self
+            .collections
+            .keys()
+            .chain(self.sharded.keys())
+            .map(String::as_str)
+            .collect();
+        noms.sort_unstable();
+        noms
+    }
+
+    /// Récupère une collection simple par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    pub fn get(&self, nom: &str) -> Option<&Collection> {
+        self.collections
+            .get(database::resolve(&self.aliases, nom))
+            .map(|collection| &**collection)
+    }
+
+    /// Récupère une collection partitionnée par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    pub fn get_sharded(&self, nom: &str) -> Option<&ShardedCollection> {
+        self.sharded
+            .get(database::resolve(&self.aliases, nom))
+            .map(|collection| &**collection)
+    }
+
+    /// Récupère une collection, simple ou partitionnée, par son nom.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    pub fn store(&self, nom: &str) -> Option<&dyn VectorStore> {
+        let nom = database::resolve(&self.aliases, nom);
+        database::store(&self.collections, &self.sharded, nom)
+    }
+
+    /// Effectue une recherche dans une collection, comme [`BaseDeDonnees::search`](crate::BaseDeDonnees::search).
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte ce nom.
+    pub fn search(&self, cname: &str, request: impl AsRef<[f32]>, k: usize) -> Result<Document> {
+        database::search(self.store(cname), cname, request.as_ref(), k)
+    }
+
+    /// Effectue une recherche paramétrée dans une collection, comme
+    /// [`BaseDeDonnees::search_with`](crate::BaseDeDonnees::search_with).
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte ce nom.
+    pub fn search_with(
+        &self,
+        cname: &str,
+        request: impl AsRef<[f32]>,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        database::search_with(self.store(cname), cname, request.as_ref(), params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::database::BaseDeDonnees;
+    use crate::payload::{Payload, Value};
+
+    fn database(count: usize) -> (BaseDeDonnees, Vec<Uuid>) {
+        let mut collection = crate::Collection::new();
+        let keys: Vec<Uuid> = (0..count).map(|_| Uuid::new_v4()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            let payload = Payload::from([("i".to_string(), Value::Number(i as f64))]);
+            collection.upsert_with_payload(*key, vec![1.0, i as f32], payload).unwrap();
+        }
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), collection);
+        (bdd, keys)
+    }
+
+    #[test]
+    fn mutations_after_the_snapshot_stay_invisible() {
+        let (mut bdd, keys) = database(50);
+        let first = bdd.snapshot();
+        let added = Uuid::new_v4();
+        bdd.get_mut("c").unwrap().upsert(added, vec![1.0, 100.0]).unwrap();
+        bdd.get_mut("c").unwrap().delete(&keys[0]);
+        let second = bdd.snapshot();
+        bdd.get_mut("c").unwrap().patch_vector(keys[1], 1, &[-1.0]).unwrap();
+
+        assert!(first.sequence() < second.sequence() && second.sequence() < bdd.sequence());
+        let (first_c, second_c) = (first.get("c").unwrap(), second.get("c").unwrap());
+        assert_eq!((first_c.len(), second_c.len(), bdd.get("c").unwrap().len()), (50, 50, 50));
+        assert!(first_c.read(&added).is_none() && second_c.read(&added).is_some());
+        assert!(first_c.read(&keys[0]).is_some() && second_c.read(&keys[0]).is_none());
+        assert_eq!(second_c.read(&keys[1]), Some(&vec![1.0, 1.0]));
+        assert_eq!(bdd.get("c").unwrap().read(&keys[1]), Some(&vec![1.0, -1.0]));
+        assert_eq!(first.search("c", [0.0, 1.0], 1).unwrap()[0].0, keys[49]);
+        assert_eq!(second.search("c", [0.0, 1.0], 1).unwrap()[0].0, added);
+    }
+
+    #[test]
+    fn a_write_copies_one_segment() {
+        let (mut bdd, keys) = database(10_000);
+        let snapshot = bdd.snapshot();
+        let payload = Payload::from([("i".to_string(), Value::Number(-1.0))]);
+        assert!(bdd.get_mut("c").unwrap().set_payload(&keys[0], payload));
+
+        let (live, frozen) = (bdd.get("c").unwrap(), snapshot.get("c").unwrap());
+        assert_eq!(live.documents.shared_segments(&frozen.documents), 256);
+        assert_eq!(live.payloads.shared_segments(&frozen.payloads), 255);
+        assert_eq!(frozen.payload(&keys[0]).unwrap()["i"], Value::Number(0.0));
+        assert!(live.check_invariants().is_ok() && frozen.check_invariants().is_ok());
+    }
+}
@@ -0,0 +1,274 @@
+//! Atténuation des scores de recherche selon l'âge des documents.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+use crate::collection::Document;
+use crate::error::{Error, Result};
+use crate::payload::{Payload, Value};
+use crate::similarity::{portable_exp2, Metric};
+
+/// Forme de la décroissance du facteur d'atténuation avec l'âge. Toutes valent 1 pour
+/// un document d'âge nul et 1/2 à l'âge [`DecayParams::half_life`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecayShape {
+    /// `0.5^(âge / demi-vie)` : le score est divisé par deux à chaque demi-vie.
+    #[default]
+    Exponential,
+    /// `1 - âge / (2 × demi-vie)`, nul au-delà de deux demi-vies.
+    Linear,
+    /// `0.5^((âge / demi-vie)²)` : atténuation presque nulle pour les documents
+    /// récents, plus rapide que l'exponentielle au-delà de la demi-vie.
+    Gaussian,
+}
+
+/// Traitement des documents dont la charge utile n'a pas de date exploitable.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MissingTimestamp {
+    /// Score laissé tel quel, comme pour un document d'âge nul.
+    #[default]
+    NoDecay,
+    /// Score atténué par ce facteur, compris entre 0 et 1.
+    Penalize(f32),
+}
+
+/// Atténuation des scores d'une recherche selon l'âge des documents (voir
+/// [`SearchParams::decay`](crate::SearchParams::decay)).
+///
+/// La date d'un document est le nombre de secondes depuis l'époque Unix porté par le
+/// champ numérique [`DecayParams::field`] de sa charge utile ; une date future compte
+/// pour un âge nul. Le score de la mesure est atténué par le facteur de
+/// [`DecayParams::shape`] : multiplié pour une similarité positive, divisé pour une
+/// distance ou une similarité négative, de sorte qu'un document plus ancien soit
+/// toujours moins bien classé ; une distance nulle reste nulle. Un facteur nul relègue
+/// le document en fin de classement, avec le pire score de la mesure (`-inf` ou
+/// `+inf`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecayParams {
+    /// Champ de la charge utile qui porte la date du document.
+    pub field: String,
+    /// Âge auquel le facteur d'atténuation vaut 1/2.
+    pub half_life: Duration,
+    /// Forme de la décroissance.
+    pub shape: DecayShape,
+    /// Traitement des documents sans date.
+    pub missing: MissingTimestamp,
+    /// Date de référence des âges ; `None` lit l'horloge au début de chaque recherche.
+    pub now: Option<SystemTime>,
+}
+
+impl DecayParams {
+    /// Crée une atténuation exponentielle, sans pénalité pour les documents sans date.
+    ///
+    /// # Arguments
+    /// * `field` - Champ de la charge utile qui porte la date du document.
+    /// * `half_life` - Âge auquel le score est divisé par deux.
+    pub fn new(field: impl Into<String>, half_life: Duration) -> Self {
+        DecayParams {
+            field: field.into(),
+            half_life,
+            shape: DecayShape::default(),
+            missing: MissingTimestamp::default(),
+            now: None,
+        }
+    }
+
+    /// Fixe la forme de la décroissance.
+    pub fn with_shape(mut self, shape: DecayShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Fixe le traitement des documents sans date.
+    pub fn with_missing(mut self, missing: MissingTimestamp) -> Self {
+        self.missing = missing;
+        self
+    }
+
+    /// Fixe la date de référence des âges, au lieu de l'horloge.
+    pub fn at(mut self, now: SystemTime) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    /// Calcule le facteur d'atténuation d'un document d'âge `age`.
+    ///
+    /// # Retourne
+    /// * f32 - Facteur compris entre 0 et 1, 1 pour un âge nul.
+    pub fn factor(&self, age: Duration) -> f32 {
+        self.factor_with(age, false)
+    }
+
+    /// Comme [`DecayParams::factor`], les puissances étant calculées par
+    /// [`portable_exp2`] si `deterministic_math` est vrai.
+    fn factor_with(&self, age: Duration, deterministic_math: bool) -> f32 {
+        let ratio = age.as_secs_f64() / self.half_life.as_secs_f64();
+        let half_power = |exponent: f64| match deterministic_math {
+            true => portable_exp2(-exponent),
+            false => 0.5_f64.powf(exponent),
+        };
+        let factor = match self.shape {
+            DecayShape::Exponential => half_power(ratio),
+            DecayShape::Linear => (1.0 - ratio / 2.0).max(0.0),
+            DecayShape::Gaussian => half_power(ratio * ratio),
+        };
+        factor as f32
+    }
+
+    /// Vérifie les paramètres avant la recherche.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si le champ est vide, la demi-vie nulle ou le facteur
+    ///   de pénalité hors de [0, 1].
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.field.is_empty() {
+            return Err(Error::InvalidConfig(
+                "le champ de date de l'atténuation ne peut pas être vide".to_string(),
+            ));
+        }
+        if self.half_life.is_zero() {
+            return Err(Error::InvalidConfig(
+                "la demi-vie de l'atténuation doit être strictement positive".to_string(),
+            ));
+        }
+        if let MissingTimestamp::Penalize(factor) = self.missing {
+            if !(0.0..=1.0).contains(&factor) {
+                return Err(Error::InvalidConfig(format!(
+                    "le facteur de pénalité des documents sans date vaut {} ; il doit être compris entre 0 et 1",
+                    factor
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Atténue les scores de `hits`, avant leur classement.
+    ///
+    /// # Arguments
+    /// * `metric` - Mesure des scores.
+    /// * `hits` - Documents et scores bruts.
+    /// * `deterministic_math` - Voir [`SearchParams::deterministic_math`](crate::SearchParams::deterministic_math).
+    /// * `payload` - Charge utile d'un document.
+    pub(crate) fn apply<'a>(
+        &self,
+        metric: Metric,
+        hits: &mut Document,
+        deterministic_math: bool,
+        payload: impl Fn(&Uuid) -> Option<&'a Payload>,
+    ) {
+        let now = self.now.unwrap_or_else(SystemTime::now);
+        let now = match now.duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        };
+        for (key, score) in hits.iter_mut() {
+            let timestamp = (payload(key).and_then(|p| p.get(&self.field)))
+                .and_then(Value::as_f64)
+                .filter(|t| t.is_finite());
+            let factor = match (timestamp, self.missing) {
+                (Some(timestamp), _) => self.factor_with(
+                    Duration::try_from_secs_f64((now - timestamp).max(0.0))
+                        .unwrap_or(Duration::MAX),
+                    deterministic_math,
+                ),
+                (None, MissingTimestamp::NoDecay) => 1.0,
+                (None, MissingTimestamp::Penalize(factor)) => factor,
+            };
+            *score = attenuate(metric, *score, factor);
+        }
+    }
+}
+
+/// Dégrade `score` selon `factor`, dans le sens de la mesure.
+fn attenuate(metric: Metric, score: f32, factor: f32) -> f32 {
+    match (factor, metric.higher_is_better()) {
+        (f, _) if f >= 1.0 => score,
+        (f, true) if f <= 0.0 => f32::NEG_INFINITY,
+        (f, false) if f <= 0.0 => f32::INFINITY,
+        (f, true) if score >= 0.0 => score * f,
+        (f, _) => score / f,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::search::SearchParams;
+
+    const DAY: u64 = 24 * 3600;
+
+    /// Date de référence figée des tests.
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(20_000 * DAY)
+    }
+
+    fn dated(age_days: u64) -> Payload {
+        let timestamp = (20_000 - age_days) * DAY;
+        Payload::from([("date".to_string(), Value::Number(timestamp as f64))])
+    }
+
+    fn decayed(k: usize, decay: DecayParams) -> SearchParams {
+        SearchParams {
+            decay: Some(decay.at(now())),
+            ..SearchParams::new(k)
+        }
+    }
+
+    #[test]
+    fn fresher_documents_overtake_closer_old_ones() {
+        for metric in [Metric::Dot, Metric::Euclidean] {
+            let mut collection = Collection::new().with_metric(metric);
+            let (old, fresh, undated) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+            collection.upsert_with_payload(old, [0.95, 0.05], dated(30)).unwrap();
+            collection.upsert_with_payload(fresh, [0.8, 0.1], dated(1)).unwrap();
+            collection.upsert(undated, [0.7, 0.2]).unwrap();
+            let query = [1.0, 0.0];
+            let keys = |hits: &Document| hits.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+
+            let raw = collection.search_with(query, &SearchParams::new(3)).unwrap();
+            assert_eq!(keys(&raw.hits), [old, fresh, undated], "{:?}", metric);
+            let decay = DecayParams::new("date", Duration::from_secs(7 * DAY));
+            let results = collection.search_with(query, &decayed(3, decay.clone())).unwrap();
+            assert_eq!(keys(&results.hits), [fresh, undated, old], "{:?}", metric);
+            // La coupure à k porte sur le score atténué.
+            let results = collection.search_with(query, &decayed(1, decay.clone())).unwrap();
+            assert_eq!(keys(&results.hits), [fresh], "{:?}", metric);
+
+            let penalized = decay.with_missing(MissingTimestamp::Penalize(0.0));
+            let results = collection.search_with(query, &decayed(3, penalized)).unwrap();
+            assert_eq!(keys(&results.hits), [fresh, old, undated], "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn every_shape_halves_the_score_at_the_half_life() {
+        let half_life = Duration::from_secs(DAY);
+        for shape in [DecayShape::Exponential, DecayShape::Linear, DecayShape::Gaussian] {
+            let decay = DecayParams::new("date", half_life).with_shape(shape);
+            assert_eq!(decay.factor(Duration::ZERO), 1.0, "{:?}", shape);
+            assert!((decay.factor(half_life) - 0.5).abs() < 1e-6, "{:?}", shape);
+            assert!(decay.factor(half_life * 2) < 0.5, "{:?}", shape);
+        }
+        let linear = DecayParams::new("date", half_life).with_shape(DecayShape::Linear);
+        assert_eq!(linear.factor(half_life * 3), 0.0);
+    }
+
+    #[test]
+    fn invalid_parameters_are_refused() {
+        let mut collection = Collection::new();
+        collection.upsert(Uuid::new_v4(), [1.0]).unwrap();
+        for decay in [
+            DecayParams::new("", Duration::from_secs(1)),
+            DecayParams::new("date", Duration::ZERO),
+            DecayParams::new("date", Duration::from_secs(1))
+                .with_missing(MissingTimestamp::Penalize(1.5)),
+        ] {
+            assert!(matches!(
+                collection.search_with([1.0], &decayed(1, decay)),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+    }
+}
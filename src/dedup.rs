@@ -0,0 +1,350 @@
+use uuid::Uuid;
+
+use crate::collection::{rank, Collection};
+use crate::error::{Error, Result};
+use crate::oplog::OperationKind;
+
+/// Traitement d'un document presque identique à un document existant, détecté par
+/// [`Collection::insert`] et par [`Collection::upsert`] pour un nouveau document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupPolicy {
+    /// Refuse l'insertion avec [`Error::Duplicate`].
+    #[default]
+    Reject,
+    /// Ignore l'insertion et retourne [`DedupOutcome::Skipped`] ; `upsert` retourne
+    /// alors `Ok(())` sans rien écrire.
+    Skip,
+}
+
+/// Effet d'une insertion par [`Collection::insert`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupOutcome {
+    /// Le document a été ajouté.
+    Inserted,
+    /// Le document existait et son vecteur a été remplacé.
+    Updated,
+    /// Le document n'a pas été inséré car `existing` atteint le seuil de déduplication
+    /// avec le score `score`.
+    Skipped { existing: Uuid, score: f32 },
+}
+
+impl Collection {
+    /// Active la déduplication des insertions par [`Collection::insert`], ainsi que des
+    /// insertions de nouveaux documents par [`Collection::upsert`] et ses variantes.
+    ///
+    /// Un document est considéré comme un doublon si un autre document de la collection
+    /// atteint `threshold` selon la mesure de la collection : similarité minimale pour
+    /// les similarités, distance maximale pour les distances, comme
+    /// [`SearchParams::score_threshold`](crate::SearchParams::score_threshold).
+    ///
+    /// # Arguments
+    /// * `threshold` - Seuil de déduplication.
+    /// * `policy` - Traitement des doublons.
+    pub fn with_dedup(mut self, threshold: f32, policy: DedupPolicy) -> Self {
+        self.dedup_threshold = Some(threshold);
+        self.dedup_policy = policy;
+        self
+    }
+
+    /// Désactive la déduplication des insertions.
+    pub fn without_dedup(mut self) -> Self {
+        self.dedup_threshold = None;
+        self
+    }
+
+    /// Retourne le seuil de déduplication, si la déduplication est active.
+    pub fn dedup_threshold(&self) -> Option<f32> {
+        self.dedup_threshold
+    }
+
+    /// Retourne le traitement des doublons.
+    pub fn dedup_policy(&self) -> DedupPolicy {
+        self.dedup_policy
+    }
+
+    /// Insère ou met à jour un document, sauf s'il est presque identique à un autre
+    /// document de la collection (voir [`Collection::with_dedup`]).
+    ///
+    /// Le document `key` lui-même n'est jamais considéré comme un doublon : mettre à
+    /// jour un document avec un vecteur voisin reste possible. Contrairement à
+    /// [`Collection::upsert`], qui ne vérifie que les nouveaux documents, `insert`
+    /// vérifie aussi les mises à jour et indique ce qu'il est advenu du document.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document.
+    ///
+    /// # Retourne
+    /// * Result<DedupOutcome> - Ce qu'il est advenu du document.
+    ///
+    /// # Erreurs
+    /// * `Error::Duplicate` - Si un doublon existe et que la politique est
+    ///   `DedupPolicy::Reject`.
+    /// * Celles de [`Collection::upsert`].
+    pub fn insert(&mut self, key: Uuid, vector: impl Into<Vec<f32>>) -> Result<DedupOutcome> {
+        let vector = vector.into();
+        self.logged(
+            OperationKind::Upsert,
+            |collection| collection.insert_vector(key, vector),
+            |outcome| match outcome {
+                Ok(DedupOutcome::Inserted | DedupOutcome::Updated) => Some(1),
+                Ok(DedupOutcome::Skipped { .. }) => Some(0),
+                Err(_) => None,
+            },
+        )
+    }
+
+    fn insert_vector(&mut self, key: Uuid, vector: Vec<f32>) -> Result<DedupOutcome> {
+        let dimension = vector.len();
+        let vector = self.prepare_vector(key, vector)?;
+        if let Some(skipped) = self.screen_duplicate(key, &vector)? {
+            return Ok(skipped);
+        }
+        let outcome = match self.store_vector(key, vector)? {
+            Some(_) => DedupOutcome::Updated,
+            None => DedupOutcome::Inserted,
+        };
+        self.lock_dimension(dimension);
+        Ok(outcome)
+    }
+
+    /// Applique la politique de déduplication au vecteur préparé `vector` du document
+    /// `key`.
+    ///
+    /// # Retourne
+    /// * Result<Option<DedupOutcome>> - `DedupOutcome::Skipped` si le document doit être
+    ///   ignoré, `None` s'il peut être écrit.
+    ///
+    /// # Erreurs
+    /// * `Error::Duplicate` - Si un doublon existe et que la politique est
+    ///   `DedupPolicy::Reject`.
+    pub(crate) fn screen_duplicate(&self, key: Uuid, vector: &[f32]) -> Result<Option<DedupOutcome>> {
+        let Some((existing, score)) = self.find_duplicate(&key, vector) else {
+            return Ok(None);
+        };
+        match self.dedup_policy {
+            DedupPolicy::Reject => Err(Error::Duplicate {
+                key,
+                existing,
+                score,
+            }),
+            DedupPolicy::Skip => Ok(Some(DedupOutcome::Skipped { existing, score })),
+        }
+    }
+
+    /// Retourne le document autre que `key` le plus proche de `vector` parmi ceux qui
+    /// atteignent le seuil de déduplication.
+    fn find_duplicate(&self, key: &Uuid, vector: &[f32]) -> Option<(Uuid, f32)> {
+        let threshold = self.dedup_threshold?;
+        let (mut hits, _) = self.score_all(vector, Some(threshold), None);
+        hits.retain(|(other, _)| other != key);
+        rank(self.config.metric, &mut hits, 1);
+        hits.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+    use crate::database::BaseDeDonnees;
+    use crate::document_id::DocumentId;
+    use crate::similarity::Metric;
+    use crate::payload::{Payload, Value};
+    use crate::progress::BatchReport;
+    use crate::transaction::Op;
+
+    fn dedup_collection(policy: DedupPolicy) -> (Collection, Uuid) {
+        let config = CollectionConfig::builder().metric(Metric::Cosine).build().unwrap();
+        let mut collection = Collection::from_config(config).with_dedup(0.99, policy);
+        let original = Uuid::new_v4();
+        collection.upsert(original, [1.0, 0.0]).unwrap();
+        (collection, original)
+    }
+
+    #[test]
+    fn insert_and_upsert_reject_a_new_duplicate() {
+        let (mut collection, original) = dedup_collection(DedupPolicy::Reject);
+        let key = Uuid::new_v4();
+        for result in [
+            collection.insert(key, [1.0, 0.01]).map(drop),
+            collection.upsert(key, [1.0, 0.01]).map(drop),
+            collection.upsert_with_payload(key, [1.0, 0.01], Payload::new()).map(drop),
+        ] {
+            match result {
+                Err(Error::Duplicate { existing, .. }) => assert_eq!(existing, original),
+                other => panic!("doublon attendu, obtenu {:?}", other),
+            }
+        }
+        assert_eq!(collection.len(), 1);
+    }
+
+    #[test]
+    fn insert_and_upsert_skip_a_new_duplicate() {
+        let (mut collection, original) = dedup_collection(DedupPolicy::Skip);
+        let key = Uuid::new_v4();
+        match collection.insert(key, [1.0, 0.01]).unwrap() {
+            DedupOutcome::Skipped { existing, .. } => assert_eq!(existing, original),
+            other => panic!("document ignoré attendu, obtenu {:?}", other),
+        }
+        assert!(!collection.upsert(key, [1.0, 0.01]).unwrap());
+        assert!(!collection.upsert_with_payload(key, [1.0, 0.01], titled("copie")).unwrap());
+        assert_eq!(collection.len(), 1);
+        assert!(collection.payload(&key).is_none());
+        let written: Vec<usize> = collection.recent_operations().iter().map(|op| op.items).collect();
+        assert_eq!(written, [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn upsert_of_an_existing_key_is_never_screened() {
+        let (mut collection, original) = dedup_collection(DedupPolicy::Reject);
+        let other = Uuid::new_v4();
+        collection.upsert(other, [0.0, 1.0]).unwrap();
+        collection.upsert(other, [1.0, 0.01]).unwrap();
+        assert!(matches!(
+            collection.insert(other, [1.0, 0.02]),
+            Err(Error::Duplicate { existing, .. }) if existing == original
+        ));
+        assert!(collection.documents[&other][0] > 0.9);
+    }
+
+    #[test]
+    fn distinct_vectors_are_written_by_both_paths() {
+        let (mut collection, _) = dedup_collection(DedupPolicy::Reject);
+        assert_eq!(collection.insert(Uuid::new_v4(), [0.0, 1.0]).unwrap(), DedupOutcome::Inserted);
+        collection.upsert(Uuid::new_v4(), [-1.0, 0.0]).unwrap();
+        assert_eq!(collection.len(), 3);
+    }
+
+    fn titled(title: &str) -> Payload {
+        let mut payload = Payload::new();
+        payload.insert("titre".to_string(), Value::String(title.to_string()));
+        payload
+    }
+
+    /// Vérifie la collection, la sauvegarde et la recharge, et retourne la copie chargée.
+    fn round_trip(collection: Collection) -> Collection {
+        collection.check_invariants().unwrap();
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&bdd.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap().clone();
+        loaded.check_invariants().unwrap();
+        loaded
+    }
+
+    #[test]
+    fn skipped_documents_leave_no_alias_payload_or_namespace() {
+        let (mut collection, _) = dedup_collection(DedupPolicy::Skip);
+        assert!(!collection.upsert_id("copie", [1.0, 0.01]).unwrap());
+        assert!(!collection.upsert_id_with_payload(7i64, [1.0, 0.01], titled("copie")).unwrap());
+        let key = Uuid::new_v4();
+        assert!(!collection.upsert_in("équipe", key, [1.0, 0.01]).unwrap());
+        assert!(!collection.upsert_with_payload(key, [1.0, 0.01], titled("copie")).unwrap());
+        assert!(collection.upsert_id("autre", [0.0, 1.0]).unwrap());
+        let loaded = round_trip(collection);
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.namespaces().is_empty());
+        assert!(loaded.payload(&DocumentId::from(7i64).to_uuid()).is_none());
+        assert_eq!(
+            loaded.document_id(&DocumentId::from("autre").to_uuid()),
+            DocumentId::from("autre")
+        );
+    }
+
+    #[test]
+    fn a_skipped_vector_leaves_its_document_pending() {
+        let (mut collection, _) = dedup_collection(DedupPolicy::Skip);
+        let key = Uuid::new_v4();
+        collection.register(key, titled("en attente")).unwrap();
+        assert!(!collection.attach_vector(key, [1.0, 0.01]).unwrap());
+        assert_eq!(collection.pending_ids(), [key]);
+        let mut loaded = round_trip(collection);
+        assert_eq!(loaded.pending_ids(), [key]);
+        assert!(loaded.attach_vector(key, [0.0, 1.0]).unwrap());
+        assert!(loaded.pending_ids().is_empty());
+    }
+
+    #[test]
+    fn batches_count_only_the_stored_documents() {
+        let (mut collection, _) = dedup_collection(DedupPolicy::Skip);
+        let items = vec![
+            (Uuid::new_v4(), vec![1.0, 0.01]),
+            (Uuid::new_v4(), vec![0.0, 1.0]),
+            (Uuid::new_v4(), vec![0.01, 1.0]),
+        ];
+        let report = collection.upsert_batch(items, None).unwrap();
+        assert_eq!(
+            report,
+            BatchReport {
+                applied: 1,
+                cancelled: false
+            }
+        );
+        assert_eq!(collection.recent_operations().last().unwrap().items, 1);
+        assert_eq!(round_trip(collection).len(), 2);
+    }
+
+    #[test]
+    fn apply_screens_new_documents_like_upsert() {
+        let (mut collection, original) = dedup_collection(DedupPolicy::Skip);
+        let (copy, distinct) = (Uuid::new_v4(), Uuid::new_v4());
+        let report = collection
+            .apply(vec![
+                Op::Upsert {
+                    key: copy,
+                    vector: vec![1.0, 0.01],
+                    payload: Some(titled("copie")),
+                },
+                Op::SetPayload {
+                    key: copy,
+                    payload: titled("copie renommée"),
+                },
+                Op::Upsert {
+                    key: distinct,
+                    vector: vec![0.0, 1.0],
+                    payload: None,
+                },
+                Op::Upsert {
+                    key: Uuid::new_v4(),
+                    vector: vec![0.01, 1.0],
+                    payload: None,
+                },
+                Op::Upsert {
+                    key: original,
+                    vector: vec![1.0, 0.02],
+                    payload: Some(titled("original")),
+                },
+            ])
+            .unwrap();
+        assert_eq!((report.upserted, report.skipped, report.payloads_set), (2, 3, 0));
+        assert!(report.to_string().ends_with(", 3 doublon(s) ignoré(s)"));
+        assert_eq!(collection.recent_operations().last().unwrap().items, 2);
+        let loaded = round_trip(collection);
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.payload(&copy).is_none());
+        assert_eq!(loaded.payload(&original), Some(&titled("original")));
+    }
+
+    #[test]
+    fn apply_rejects_a_duplicate_without_writing_the_batch() {
+        let (mut collection, original) = dedup_collection(DedupPolicy::Reject);
+        let written = Uuid::new_v4();
+        let result = collection.apply(vec![
+            Op::Upsert {
+                key: written,
+                vector: vec![0.0, 1.0],
+                payload: None,
+            },
+            Op::Upsert {
+                key: Uuid::new_v4(),
+                vector: vec![1.0, 0.01],
+                payload: None,
+            },
+        ]);
+        let error = result.unwrap_err();
+        assert!(matches!(error.root(), Error::Duplicate { existing, .. } if *existing == original));
+        assert!(collection.read(&written).is_none());
+        assert_eq!(collection.len(), 1);
+    }
+}
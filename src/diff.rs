@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::database::BaseDeDonnees;
+use crate::json;
+use crate::store::VectorStore;
+
+/// Écart maximal entre deux coordonnées pour que des vecteurs soient considérés égaux.
+pub const DEFAULT_DIFF_EPSILON: f32 = 1e-6;
+
+/// Différences entre deux versions d'une collection.
+///
+/// Chaque liste est triée par `Uuid` croissant.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CollectionDiff {
+    /// Documents présents uniquement dans la nouvelle version.
+    pub added: Vec<Uuid>,
+    /// Documents présents uniquement dans l'ancienne version.
+    pub removed: Vec<Uuid>,
+    /// Documents présents des deux côtés dont le vecteur ou la charge utile diffère.
+    pub changed: Vec<Uuid>,
+}
+
+impl CollectionDiff {
+    /// Indique si les deux versions sont identiques.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn write_json(&self, out: &mut String) {
+        for (i, (name, keys)) in [
+            ("added", &self.added),
+            ("removed", &self.removed),
+            ("changed", &self.changed),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            out.push(if i == 0 { '{' } else { ',' });
+            json::write_string(out, name);
+            out.push(':');
+            json::write_array(out, keys, |out, key| {
+                json::write_string(out, &key.to_string())
+            });
+        }
+        out.push('}');
+    }
+}
+
+/// Différences entre deux bases de données, obtenues avec [`BaseDeDonnees::diff`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DatabaseDiff {
+    /// Collections présentes uniquement dans la nouvelle base, par ordre alphabétique.
+    pub added_collections: Vec<String>,
+    /// Collections présentes uniquement dans l'ancienne base, par ordre alphabétique.
+    pub removed_collections: Vec<String>,
+    /// Différences des collections présentes des deux côtés ; les collections identiques
+    /// n'y figurent pas.
+    pub collections: BTreeMap<String, CollectionDiff>,
+}
+
+impl DatabaseDiff {
+    /// Indique si les deux bases sont identiques.
+    pub fn is_empty(&self) -> bool {
+        self.added_collections.is_empty()
+            && self.removed_collections.is_empty()
+            && self.collections.is_empty()
+    }
+
+    /// Sérialise les différences en JSON.
+    ///
+    /// # Retourne
+    /// * String - Objet JSON avec les champs `added_collections`, `removed_collections` et
+    ///   `collections`, ce dernier associant chaque nom à ses listes `added`, `removed`
+    ///   et `changed`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"added_collections\":");
+        json::write_array(&mut out, &self.added_collections, |out, nom| {
+            json::write_string(out, nom)
+        });
+        out.push_str(",\"removed_collections\":");
+        json::write_array(&mut out, &self.removed_collections, |out, nom| {
+            json::write_string(out, nom)
+        });
+        out.push_str(",\"collections\":{");
+        for (i, (nom, diff)) in self.collections.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            json::write_string(&mut out, nom);
+            out.push(':');
+            diff.write_json(&mut out);
+        }
+        out.push_str("}}");
+        out
+    }
+}
+
+impl fmt::Display for DatabaseDiff {
+    /// Affiche un résumé des différences, une ligne par collection concernée.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "aucune différence");
+        }
+        for nom in &self.added_collections {
+            writeln!(f, "+ collection '{}'", nom)?;
+        }
+        for nom in &self.removed_collections {
+            writeln!(f, "- collection '{}'", nom)?;
+        }
+        for (nom, diff) in &self.collections {
+            writeln!(
+                f,
+                "~ collection '{}' : {} ajoutés, {} supprimés, {} modifiés",
+                nom,
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Compare cette collection, considérée comme l'ancienne version, à `other`.
+    ///
+    /// Un document est modifié si sa dimension change, si l'une de ses coordonnées varie
+    /// de plus de `epsilon` ou si sa charge utile diffère.
+    ///
+    /// # Arguments
+    /// * `other` - Nouvelle version de la collection.
+    /// * `epsilon` - Écart toléré entre deux coordonnées.
+    ///
+    /// # Retourne
+    /// * CollectionDiff - Documents ajoutés, supprimés et modifiés.
+    pub fn diff(&self, other: &Collection, epsilon: f32) -> CollectionDiff {
+        diff_stores(self, other, epsilon)
+    }
+}
+
+/// Compare deux stockages quelconques, par exemple une collection simple et sa version
+/// partitionnée.
+fn diff_stores(old: &dyn VectorStore, new: &dyn VectorStore, epsilon: f32) -> CollectionDiff {
+    let mut diff = CollectionDiff::default();
+    for key in old.keys() {
+        match (old.read(key), new.read(key)) {
+            (Some(vector), Some(new_vector)) => {
+                let vector_changed = vector.len() != new_vector.len()
+                    || vector
+                        .iter()
+                        .zip(new_vector)
+                        .any(|(a, b)| (a - b).abs() > epsilon);
+                if vector_changed || old.payload(key) != new.payload(key) {
+                    diff.changed.push(*key);
+                }
+            }
+            _ => diff.removed.push(*key),
+        }
+    }
+    diff.added = new
+        .keys()
+        .filter(|key| old.read(key).is_none())
+        .copied()
+        .collect();
+    diff.added.sort_unstable();
+    diff.removed.sort_unstable();
+    diff.changed.sort_unstable();
+    diff
+}
+
+impl BaseDeDonnees {
+    /// Compare cette base, considérée comme l'ancienne version, à `other`.
+    ///
+    /// Les vecteurs sont comparés avec la tolérance [`DEFAULT_DIFF_EPSILON`]. Les
+    /// collections simples et partitionnées sont comparées par leur contenu : passer une
+    /// collection à une version partitionnée ne la signale pas comme modifiée.
+    ///
+    /// # Arguments
+    /// * `other` - Nouvelle version de la base.
+    ///
+    /// # Retourne
+    /// * DatabaseDiff - Collections ajoutées et supprimées, et différences des autres.
+    pub fn diff(&self, other: &BaseDeDonnees) -> DatabaseDiff {
+        self.diff_with_epsilon(other, DEFAULT_DIFF_EPSILON)
+    }
+
+    /// Compare cette base à `other` avec une tolérance explicite sur les vecteurs.
+    ///
+    /// # Arguments
+    /// * `other` - Nouvelle version de la base.
+    /// * `epsilon` - Écart toléré entre deux coordonnées.
+    ///
+    /// # Retourne
+    /// * DatabaseDiff - Collections ajoutées et supprimées, et différences des autres.
+    pub fn diff_with_epsilon(&self, other: &BaseDeDonnees, epsilon: f32) -> DatabaseDiff {
+        let mut diff = DatabaseDiff::default();
+        for nom in self.collections.keys().chain(self.sharded.keys()) {
+            let old = self.store(nom).expect("nom issu de la base");
+            match other.store(nom) {
+                None => diff.removed_collections.push(nom.clone()),
+                Some(new) => {
+                    let collection_diff = diff_stores(old, new, epsilon);
+                    if !collection_diff.is_empty() {
+                        diff.collections.insert(nom.clone(), collection_diff);
+                    }
+                }
+            }
+        }
+        diff.added_collections = other
+            .collections
+            .keys()
+            .chain(other.sharded.keys())
+            .filter(|nom| self.store(nom).is_none())
+            .cloned()
+            .collect();
+        diff.added_collections.sort_unstable();
+        diff.removed_collections.sort_unstable();
+        diff
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+
+use uuid::{Builder, Uuid};
+
+use crate::collection::{Collection, Document};
+use crate::error::{Error, Result};
+use crate::payload::Payload;
+use crate::search::SearchParams;
+
+/// Identifiant d'un document fourni par l'application : `Uuid`, entier ou chaîne.
+///
+/// Les collections indexent toujours leurs documents par `Uuid`. Un identifiant entier
+/// ou textuel y est associé à un `Uuid` dérivé de façon déterministe
+/// ([`DocumentId::to_uuid`]) : le même identifiant désigne donc le même document d'une
+/// base à l'autre, et les API par `Uuid` restent utilisables sur ces documents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DocumentId {
+    /// Identifiant `Uuid`, utilisé tel quel.
+    Uuid(Uuid),
+    /// Identifiant entier.
+    Int(i64),
+    /// Identifiant textuel.
+    Str(String),
+}
+
+impl DocumentId {
+    /// Retourne le `Uuid` sous lequel le document est stocké.
+    ///
+    /// Pour un entier ou une chaîne, il s'agit d'un `Uuid` de version 8 obtenu par
+    /// hachage FNV-1a 128 bits du type et de la valeur de l'identifiant.
+    pub fn to_uuid(&self) -> Uuid {
+        let (tag, bytes) = match self {
+            DocumentId::Uuid(uuid) => return *uuid,
+            DocumentId::Int(n) => (1, n.to_le_bytes().to_vec()),
+            DocumentId::Str(s) => (2, s.as_bytes().to_vec()),
+        };
+        let mut hash: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+        for byte in std::iter::once(tag).chain(bytes) {
+            hash ^= byte as u128;
+            hash = hash.wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+        }
+        Builder::from_custom_bytes(hash.to_be_bytes()).into_uuid()
+    }
+}
+
+impl From<Uuid> for DocumentId {
+    fn from(uuid: Uuid) -> Self {
+        DocumentId::Uuid(uuid)
+    }
+}
+
+impl From<i64> for DocumentId {
+    fn from(n: i64) -> Self {
+        DocumentId::Int(n)
+    }
+}
+
+impl From<String> for DocumentId {
+    fn from(s: String) -> Self {
+        DocumentId::Str(s)
+    }
+}
+
+impl From<&str> for DocumentId {
+    fn from(s: &str) -> Self {
+        DocumentId::Str(s.to_string())
+    }
+}
+
+impl fmt::Display for DocumentId {
+    /// Préfixe la valeur par son type (`uuid:`, `int:` ou `str:`), de sorte que deux
+    /// identifiants différents ne s'affichent jamais de la même façon.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentId::Uuid(uuid) => write!(f, "uuid:{}", uuid),
+            DocumentId::Int(n) => write!(f, "int:{}", n),
+            DocumentId::Str(s) => write!(f, "str:{}", s),
+        }
+    }
+}
+
+impl Collection {
+    /// Insère ou met à jour le document `id`, en conservant sa charge utile.
+    ///
+    /// # Arguments
+    /// * `id` - Identifiant du document : `Uuid`, `i64` ou chaîne.
+    /// * `vector` - Vecteur représentant le document.
+    ///
+    /// # Retourne
+    /// * Result<bool> - `false` si le document a été ignoré comme doublon, comme par
+    ///   [`Collection::upsert`] ; l'identifiant n'est alors pas retenu.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert`].
+    /// * `Error::InvalidConfig` - Si `id` a le même `Uuid` dérivé qu'un autre identifiant
+    ///   de la collection.
+    pub fn upsert_id(
+        &mut self,
+        id: impl Into<DocumentId>,
+        vector: impl Into<Vec<f32>>,
+    ) -> Result<bool> {
+        let id = id.into();
+        let key = id.to_uuid();
+        if let Some(existing) = self.aliases.get(&key).filter(|existing| **existing != id) {
+            return Err(Error::InvalidConfig(format!(
+                "les identifiants {} et {} désignent le même document {}",
+                existing, id, key
+            )));
+        }
+        if !self.upsert(key, vector)? {
+            return Ok(false);
+        }
+        if !matches!(id, DocumentId::Uuid(_)) {
+            self.aliases.insert(key, id);
+        }
+        Ok(true)
+    }
+
+    /// Insère ou met à jour le document `id` avec sa charge utile.
+    ///
+    /// # Retourne
+    /// * Result<bool> - Comme [`Collection::upsert_id`] ; la charge utile d'un doublon
+    ///   ignoré n'est pas écrite.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert_id`].
+    /// * `Error::SchemaViolation` - Si la charge utile ne respecte pas le schéma de la
+    ///   collection ; le document n'est pas modifié.
+    pub fn upsert_id_with_payload(
+        &mut self,
+        id: impl Into<DocumentId>,
+        vector: impl Into<Vec<f32>>,
+        payload: Payload,
+    ) -> Result<bool> {
+        let id = id.into();
+        let key = id.to_uuid();
+        self.write_blobs(|collection| {
+            let (payload, coerced) = collection.admit_payload(key, payload)?;
+            if !collection.upsert_id(id, vector)? {
+                return Ok(false);
+            }
+            collection.put_payload(key, payload);
+            collection.schema_warnings += coerced;
+            Ok(true)
+        })
+    }
+
+    /// Lit le vecteur du document `id`.
+    pub fn read_id(&self, id: impl Into<DocumentId>) -> Option<&Vec<f32>> {
+        self.read(&id.into().to_uuid())
+    }
+
+    /// Supprime le document `id` et sa charge utile.
+    pub fn delete_id(&mut self, id: impl Into<DocumentId>) {
+        self.delete(&id.into().to_uuid())
+    }
+
+    /// Retourne l'identifiant sous lequel le document `key` a été inséré, ou
+    /// `DocumentId::Uuid(key)` s'il a été inséré par son `Uuid`.
+    pub fn document_id(&self, key: &Uuid) -> DocumentId {
+        self.aliases
+            .get(key)
+            .cloned()
+            .unwrap_or(DocumentId::Uuid(*key))
+    }
+
+    /// Recherche comme [`Collection::search_with`], en retournant chaque document sous
+    /// l'identifiant avec lequel il a été inséré.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search_with`].
+    pub fn search_ids(
+        &self,
+        request: &[f32],
+        params: &SearchParams,
+    ) -> Result<Vec<(DocumentId, f32)>> {
+        let hits: Document = self.search_with(request, params)?.hits;
+        Ok(hits
+            .into_iter()
+            .map(|(key, score)| (self.document_id(&key), score))
+            .collect())
+    }
+}
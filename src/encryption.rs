@@ -0,0 +1,585 @@
+//! Chiffrement authentifié des sauvegardes et du journal de réplication (fonctionnalité
+//! `encryption`).
+//!
+//! Les données sont chiffrées par ChaCha20-Poly1305 (RFC 8439) avec une clé de 32 octets
+//! et un nonce aléatoire de 12 octets tiré pour chaque écriture. Une clé peut être
+//! dérivée d'une phrase secrète par [`derive_key`] (PBKDF2-HMAC-SHA256).
+//!
+//! Format d'une sauvegarde chiffrée :
+//!
+//! ```text
+//! MAGIC (8 octets) | version: u32 | nonce (12 octets) | sauvegarde chiffrée | tag (16 octets)
+//! ```
+//!
+//! L'en-tête est authentifié avec le contenu. Une trame chiffrée du journal est
+//! `longueur: u32 | nonce | trame chiffrée | tag`, la trame en clair étant celle d'un
+//! journal non chiffré.
+//!
+//! Une clé incorrecte et des données modifiées ne se distinguent pas : dans les deux cas
+//! le tag ne correspond pas et la lecture échoue avec `Error::AuthenticationFailed`,
+//! avant que le moindre octet déchiffré ne soit interprété.
+
+use std::fs;
+use std::path::Path;
+
+use crate::contents;
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::sha256::HmacSha256;
+use crate::snapshot::{self, Reader};
+use crate::storage;
+
+/// En-tête d'une sauvegarde chiffrée.
+const MAGIC: &[u8; 8] = snapshot::ENCRYPTED_MAGIC;
+/// Version du format des sauvegardes chiffrées.
+const FORMAT_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Nombre d'itérations de PBKDF2 utilisé par [`derive_key`].
+pub const KEY_DERIVATION_ITERATIONS: u32 = 600_000;
+
+/// Dérive une clé de chiffrement d'une phrase secrète par PBKDF2-HMAC-SHA256, avec
+/// [`KEY_DERIVATION_ITERATIONS`] itérations.
+///
+/// Le sel n'est pas secret mais doit être conservé pour dériver de nouveau la même clé ;
+/// un sel aléatoire d'au moins 16 octets par base évite qu'une même phrase donne la même
+/// clé partout.
+///
+/// # Arguments
+/// * `passphrase` - Phrase secrète.
+/// * `salt` - Sel de la dérivation.
+///
+/// # Retourne
+/// * [u8; 32] - La clé dérivée.
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    pbkdf2_sha256(passphrase.as_bytes(), salt, KEY_DERIVATION_ITERATIONS)
+}
+
+impl BaseDeDonnees {
+    /// Enregistre la base de données dans un fichier chiffré par `key`, comme
+    /// [`BaseDeDonnees::save`] : un fichier temporaire est écrit puis renommé.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier à créer ou remplacer.
+    /// * `key` - Clé de chiffrement, par exemple issue de [`derive_key`].
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être écrit ou si le système ne fournit
+    ///   pas d'aléa pour le nonce.
+    pub fn save_encrypted(&self, path: impl AsRef<Path>, key: &[u8; 32]) -> Result<()> {
+        let nonce = random_nonce()?;
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        snapshot::put_u32(&mut out, FORMAT_VERSION);
+        out.extend_from_slice(&nonce);
+        let header = out.len();
+        out.extend_from_slice(&self.to_bytes());
+        let (aad, plaintext) = out.split_at_mut(header);
+        let tag = seal(key, &nonce, aad, plaintext);
+        out.extend_from_slice(&tag);
+
+        storage::replace(self.storage(), path.as_ref(), &out)?;
+        Ok(())
+    }
+
+    /// Charge une base de données enregistrée avec [`BaseDeDonnees::save_encrypted`].
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    /// * `key` - Clé utilisée à l'enregistrement.
+    ///
+    /// # Retourne
+    /// * Result<BaseDeDonnees> - La base de données lue.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    /// * `Error::AuthenticationFailed` - Si la clé est incorrecte ou si le fichier a été
+    ///   modifié.
+    /// * `Error::InvalidSnapshot` - Si le fichier n'est pas une sauvegarde chiffrée, ou
+    ///   d'une version inconnue.
+    pub fn load_encrypted(path: impl AsRef<Path>, key: &[u8; 32]) -> Result<Self> {
+        Self::from_bytes(&decrypt(path.as_ref(), key)?)
+    }
+
+    /// Charge seulement les collections `names` d'une sauvegarde enregistrée avec
+    /// [`BaseDeDonnees::save_encrypted`], comme [`BaseDeDonnees::load_collections`].
+    ///
+    /// Le fichier est lu et authentifié en entier, la sauvegarde chiffrée ne se lisant
+    /// pas par morceaux ; seules les collections demandées sont ensuite décodées et
+    /// vérifiées.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    /// * `key` - Clé utilisée à l'enregistrement.
+    /// * `names` - Noms des collections à charger.
+    ///
+    /// # Erreurs
+    /// * Celles de [`BaseDeDonnees::load_encrypted`].
+    /// * Celles de [`BaseDeDonnees::load_collections`].
+    pub fn load_collections_encrypted(
+        path: impl AsRef<Path>,
+        key: &[u8; 32],
+        names: &[&str],
+    ) -> Result<Self> {
+        contents::load_from_bytes(&decrypt(path.as_ref(), key)?, names)
+    }
+}
+
+/// Lit et déchiffre une sauvegarde écrite par [`BaseDeDonnees::save_encrypted`].
+///
+/// # Retourne
+/// * Result<Vec<u8>> - La sauvegarde en clair.
+fn decrypt(path: &Path, key: &[u8; 32]) -> Result<Vec<u8>> {
+    let mut bytes = fs::read(path)?;
+    let header = MAGIC.len() + 4 + NONCE_LEN;
+    if bytes.len() < header + TAG_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(snapshot::invalid(
+            "ce fichier n'est pas une sauvegarde chiffrée",
+        ));
+    }
+    let mut reader = Reader::new(&bytes[MAGIC.len()..header]);
+    let version = reader.u32()?;
+    if version != FORMAT_VERSION {
+        return Err(snapshot::invalid(format!(
+            "version de sauvegarde chiffrée inconnue ({})",
+            version
+        )));
+    }
+    let nonce: [u8; NONCE_LEN] = reader.array()?;
+    let tag_start = bytes.len() - TAG_LEN;
+    let (body, tag) = bytes.split_at_mut(tag_start);
+    let (aad, ciphertext) = body.split_at_mut(header);
+    open(key, &nonce, aad, ciphertext, tag)?;
+    bytes.truncate(tag_start);
+    bytes.drain(..header);
+    Ok(bytes)
+}
+
+/// Chiffre une trame du journal de réplication, longueur comprise.
+pub(crate) fn seal_frame(key: &[u8; 32], frame: &[u8]) -> Result<Vec<u8>> {
+    let nonce = random_nonce()?;
+    let mut out = Vec::with_capacity(4 + NONCE_LEN + frame.len() + TAG_LEN);
+    snapshot::put_u32(&mut out, (NONCE_LEN + frame.len() + TAG_LEN) as u32);
+    out.extend_from_slice(&nonce);
+    let start = out.len();
+    out.extend_from_slice(frame);
+    let tag = seal(key, &nonce, &[], &mut out[start..]);
+    out.extend_from_slice(&tag);
+    Ok(out)
+}
+
+/// Déchiffre le contenu d'une trame écrite par [`seal_frame`], sans sa longueur.
+pub(crate) fn open_frame(key: &[u8; 32], mut sealed: Vec<u8>) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(snapshot::invalid("trame chiffrée trop courte"));
+    }
+    let nonce: [u8; NONCE_LEN] = sealed[..NONCE_LEN].try_into().expect("longueur vérifiée");
+    let tag_start = sealed.len() - TAG_LEN;
+    let (body, tag) = sealed.split_at_mut(tag_start);
+    open(key, &nonce, &[], &mut body[NONCE_LEN..], tag)?;
+    sealed.truncate(tag_start);
+    sealed.drain(..NONCE_LEN);
+    Ok(sealed)
+}
+
+fn random_nonce() -> Result<[u8; NONCE_LEN]> {
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::getrandom(&mut nonce).map_err(|error| Error::Io(error.to_string()))?;
+    Ok(nonce)
+}
+
+/// Chiffre `data` sur place et retourne le tag qui authentifie `aad` et le chiffré.
+fn seal(key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut [u8]) -> [u8; TAG_LEN] {
+    chacha20_xor(key, nonce, 1, data);
+    aead_tag(key, nonce, aad, data)
+}
+
+/// Vérifie le tag puis déchiffre `data` sur place ; `data` est inchangé en cas d'échec.
+fn open(
+    key: &[u8; 32],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8],
+) -> Result<()> {
+    let expected = aead_tag(key, nonce, aad, data);
+    // Comparaison en temps constant : la durée ne révèle pas le premier octet différent.
+    let difference = expected
+        .iter()
+        .zip(tag)
+        .fold(0, |acc, (a, b)| acc | (a ^ b));
+    if tag.len() != TAG_LEN || difference != 0 {
+        return Err(Error::AuthenticationFailed);
+    }
+    chacha20_xor(key, nonce, 1, data);
+    Ok(())
+}
+
+/// Tag Poly1305 de la construction AEAD de la RFC 8439, section 2.8.
+fn aead_tag(key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let block = chacha20_block(key, nonce, 0);
+    let mut mac = Poly1305::new(block[..32].try_into().expect("bloc de 64 octets"));
+    mac.update_padded(aad);
+    mac.update_padded(ciphertext);
+    let mut lengths = [0; 16];
+    lengths[..8].copy_from_slice(&(aad.len() as u64).to_le_bytes());
+    lengths[8..].copy_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    mac.update_padded(&lengths);
+    mac.finish()
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// Bloc de flux de clé ChaCha20 numéro `counter`.
+fn chacha20_block(key: &[u8; 32], nonce: &[u8; NONCE_LEN], counter: u32) -> [u8; 64] {
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for (word, chunk) in initial[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = le32(chunk);
+    }
+    initial[12] = counter;
+    for (word, chunk) in initial[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = le32(chunk);
+    }
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    let mut block = [0; 64];
+    for ((out, word), start) in block.chunks_exact_mut(4).zip(state).zip(initial) {
+        out.copy_from_slice(&word.wrapping_add(start).to_le_bytes());
+    }
+    block
+}
+
+/// Applique le flux de clé ChaCha20 à `data`, à partir du bloc `counter`.
+fn chacha20_xor(key: &[u8; 32], nonce: &[u8; NONCE_LEN], counter: u32, data: &mut [u8]) {
+    for (index, chunk) in data.chunks_mut(64).enumerate() {
+        let block = chacha20_block(key, nonce, counter.wrapping_add(index as u32));
+        for (byte, stream) in chunk.iter_mut().zip(block) {
+            *byte ^= stream;
+        }
+    }
+}
+
+/// Authentificateur Poly1305, en limbes de 26 bits.
+struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8; 32]) -> Self {
+        Poly1305 {
+            r: [
+                le32(&key[0..4]) & 0x3ff_ffff,
+                (le32(&key[3..7]) >> 2) & 0x3ff_ff03,
+                (le32(&key[6..10]) >> 4) & 0x3ff_c0ff,
+                (le32(&key[9..13]) >> 6) & 0x3f0_3fff,
+                (le32(&key[12..16]) >> 8) & 0x00f_ffff,
+            ],
+            h: [0; 5],
+            pad: [
+                le32(&key[16..20]),
+                le32(&key[20..24]),
+                le32(&key[24..28]),
+                le32(&key[28..32]),
+            ],
+        }
+    }
+
+    /// Ajoute `data`, complété par des zéros jusqu'à un multiple de 16 octets.
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut block = [0; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.block(&block);
+        }
+    }
+
+    fn block(&mut self, m: &[u8; 16]) {
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+        let h = &mut self.h;
+        let h0 = u64::from(h[0] + (le32(&m[0..4]) & 0x3ff_ffff));
+        let h1 = u64::from(h[1] + ((le32(&m[3..7]) >> 2) & 0x3ff_ffff));
+        let h2 = u64::from(h[2] + ((le32(&m[6..10]) >> 4) & 0x3ff_ffff));
+        let h3 = u64::from(h[3] + ((le32(&m[9..13]) >> 6) & 0x3ff_ffff));
+        let h4 = u64::from(h[4] + ((le32(&m[12..16]) >> 8) | (1 << 24)));
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        let mut h0 = (d0 & 0x3ff_ffff) as u32 + (d4 >> 26) as u32 * 5;
+        let h1 = (d1 & 0x3ff_ffff) as u32 + (h0 >> 26);
+        h0 &= 0x3ff_ffff;
+        *h = [
+            h0,
+            h1,
+            (d2 & 0x3ff_ffff) as u32,
+            (d3 & 0x3ff_ffff) as u32,
+            (d4 & 0x3ff_ffff) as u32,
+        ];
+    }
+
+    fn finish(self) -> [u8; 16] {
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+        let mut c = h1 >> 26;
+        h1 &= 0x3ff_ffff;
+        h2 += c;
+        c = h2 >> 26;
+        h2 &= 0x3ff_ffff;
+        h3 += c;
+        c = h3 >> 26;
+        h3 &= 0x3ff_ffff;
+        h4 += c;
+        c = h4 >> 26;
+        h4 &= 0x3ff_ffff;
+        h0 += c * 5;
+        c = h0 >> 26;
+        h0 &= 0x3ff_ffff;
+        h1 += c;
+
+        // h - p, retenu si h >= p = 2^130 - 5.
+        let mut g0 = h0 + 5;
+        c = g0 >> 26;
+        g0 &= 0x3ff_ffff;
+        let mut g1 = h1 + c;
+        c = g1 >> 26;
+        g1 &= 0x3ff_ffff;
+        let mut g2 = h2 + c;
+        c = g2 >> 26;
+        g2 &= 0x3ff_ffff;
+        let mut g3 = h3 + c;
+        c = g3 >> 26;
+        g3 &= 0x3ff_ffff;
+        let g4 = (h4 + c).wrapping_sub(1 << 26);
+        let keep_g = (g4 >> 31).wrapping_sub(1);
+        let select = |h: u32, g: u32| (h & !keep_g) | (g & keep_g);
+        let (h0, h1, h2, h3, h4) = (
+            select(h0, g0),
+            select(h1, g1),
+            select(h2, g2),
+            select(h3, g3),
+            select(h4, g4),
+        );
+
+        let words = [
+            h0 | (h1 << 26),
+            (h1 >> 6) | (h2 << 20),
+            (h2 >> 12) | (h3 << 14),
+            (h3 >> 18) | (h4 << 8),
+        ];
+        let mut tag = [0; 16];
+        let mut carry = 0u64;
+        for ((out, word), pad) in tag.chunks_exact_mut(4).zip(words).zip(self.pad) {
+            let sum = u64::from(word) + u64::from(pad) + carry;
+            out.copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+        tag
+    }
+}
+
+/// PBKDF2-HMAC-SHA256 réduit à un bloc, soit une clé de 32 octets.
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let hmac = HmacSha256::new(password);
+    let mut u = hmac.mac(&[salt, &1u32.to_be_bytes()]);
+    let mut key = u;
+    for _ in 1..iterations {
+        u = hmac.mac(&[&u]);
+        for (k, x) in key.iter_mut().zip(u) {
+            *k ^= x;
+        }
+    }
+    key
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("mot de 4 octets"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        let digits: Vec<u8> = hex.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn sequential_key(first: u8) -> [u8; 32] {
+        std::array::from_fn(|i| first + i as u8)
+    }
+
+    #[test]
+    fn chacha20_block_matches_rfc_8439() {
+        // Section 2.3.2.
+        let nonce: [u8; NONCE_LEN] = unhex("000000090000004a00000000").try_into().unwrap();
+        let block = chacha20_block(&sequential_key(0), &nonce, 1);
+        assert_eq!(
+            block.to_vec(),
+            unhex(
+                "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e
+                 d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+            )
+        );
+    }
+
+    #[test]
+    fn aead_matches_rfc_8439() {
+        // Section 2.8.2.
+        let key = sequential_key(0x80);
+        let nonce: [u8; NONCE_LEN] = unhex("070000004041424344454647").try_into().unwrap();
+        let aad = unhex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+                          one tip for the future, sunscreen would be it.";
+        let mut data = plaintext.to_vec();
+        let tag = seal(&key, &nonce, &aad, &mut data);
+        assert_eq!(
+            data,
+            unhex(
+                "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6
+                 3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36
+                 92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc
+                 3ff4def08e4b7a9de576d26586cec64b6116"
+            )
+        );
+        assert_eq!(tag.to_vec(), unhex("1ae10b594f09e26a7e902ecbd0600691"));
+        open(&key, &nonce, &aad, &mut data, &tag).unwrap();
+        assert_eq!(data, plaintext);
+    }
+
+    #[test]
+    fn pbkdf2_matches_published_vectors() {
+        // RFC 7914, section 11.
+        assert_eq!(
+            pbkdf2_sha256(b"passwd", b"salt", 1).to_vec(),
+            unhex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc")
+        );
+        // Entrées de la RFC 6070, avec HMAC-SHA256.
+        assert_eq!(
+            pbkdf2_sha256(b"password", b"salt", 1).to_vec(),
+            unhex("120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b")
+        );
+        assert_eq!(
+            pbkdf2_sha256(b"password", b"salt", 2).to_vec(),
+            unhex("ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43")
+        );
+        assert_eq!(
+            pbkdf2_sha256(b"password", b"salt", 4096).to_vec(),
+            unhex("c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a")
+        );
+    }
+
+    #[test]
+    fn tampered_or_wrongly_keyed_data_is_rejected() {
+        let key = sequential_key(1);
+        let nonce = [7; NONCE_LEN];
+        let mut data = b"trame du journal".to_vec();
+        let tag = seal(&key, &nonce, b"en-tete", &mut data);
+        let sealed = data.clone();
+
+        let mut wrong_key = key;
+        wrong_key[31] ^= 1;
+        for (key, aad, flip_data, flip_tag) in [
+            (wrong_key, &b"en-tete"[..], None, None),
+            (key, &b"en-tetf"[..], None, None),
+            (key, &b"en-tete"[..], Some(5), None),
+            (key, &b"en-tete"[..], None, Some(15)),
+        ] {
+            let (mut data, mut tag) = (sealed.clone(), tag);
+            if let Some(byte) = flip_data {
+                data[byte] ^= 0x10;
+            }
+            if let Some(byte) = flip_tag {
+                tag[byte] ^= 0x80;
+            }
+            let before = data.clone();
+            assert!(matches!(
+                open(&key, &nonce, aad, &mut data, &tag),
+                Err(Error::AuthenticationFailed)
+            ));
+            assert_eq!(data, before);
+        }
+        assert!(open(&key, &nonce, b"en-tete", &mut data, &tag[..15]).is_err());
+    }
+
+    #[test]
+    fn frames_round_trip_and_detect_any_flipped_bit() {
+        let key = sequential_key(9);
+        let frame = b"enregistrement 42".to_vec();
+        let sealed = seal_frame(&key, &frame).unwrap();
+        assert_ne!(&sealed[4 + NONCE_LEN..4 + NONCE_LEN + frame.len()], &frame[..]);
+        assert_ne!(seal_frame(&key, &frame).unwrap(), sealed);
+        assert_eq!(open_frame(&key, sealed[4..].to_vec()).unwrap(), frame);
+        for bit in 0..(sealed.len() - 4) * 8 {
+            let mut tampered = sealed[4..].to_vec();
+            tampered[bit / 8] ^= 1 << (bit % 8);
+            assert!(matches!(
+                open_frame(&key, tampered),
+                Err(Error::AuthenticationFailed)
+            ));
+        }
+        assert!(open_frame(&key, vec![0; NONCE_LEN + TAG_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn encrypted_saves_round_trip_and_reject_wrong_keys_and_changes() {
+        let dir = std::env::temp_dir().join(format!("embeddingproject-encryption-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("base.enc");
+        let key = sequential_key(3);
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let id = uuid::Uuid::new_v4();
+        db.get_mut("docs").unwrap().upsert(id, [0.5, -1.5, 2.0]).unwrap();
+        db.save_encrypted(&path, &key).unwrap();
+
+        let loaded = BaseDeDonnees::load_encrypted(&path, &key).unwrap();
+        assert_eq!(loaded.get("docs").unwrap().len(), 1);
+        assert_eq!(*loaded.get("docs").unwrap().documents[&id], vec![0.5, -1.5, 2.0]);
+        assert!(matches!(
+            BaseDeDonnees::load_encrypted(&path, &sequential_key(4)),
+            Err(Error::AuthenticationFailed)
+        ));
+
+        let bytes = fs::read(&path).unwrap();
+        // Une version modifiée, une sauvegarde modifiée et un tag modifié.
+        for offset in [MAGIC.len(), MAGIC.len() + 4 + NONCE_LEN + 3, bytes.len() - 1] {
+            let mut tampered = bytes.clone();
+            tampered[offset] ^= 1;
+            fs::write(&path, &tampered).unwrap();
+            assert!(BaseDeDonnees::load_encrypted(&path, &key).is_err(), "octet {}", offset);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,626 @@
+use std::fmt;
+use uuid::Uuid;
+
+use crate::core::DimensionMismatch;
+use crate::float;
+use crate::model::ModelFingerprint;
+use crate::payload::Value;
+use crate::request_context::RequestContext;
+use crate::schema::FieldType;
+
+/// Erreurs retournées par les opérations de la base de données.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Un vecteur nul a été fourni alors que la collection les refuse.
+    /// `key` identifie le document concerné, ou vaut `None` pour une requête.
+    ZeroVector { key: Option<Uuid> },
+    /// Aucune collection ne porte ce nom.
+    CollectionNotFound(String),
+    /// La dimension d'un vecteur ne correspond pas à celle attendue.
+    DimensionMismatch { expected: usize, got: usize },
+    /// Une requête composée ne contient aucun vecteur.
+    EmptyQuery,
+    /// La configuration demandée est invalide ou incompatible avec l'état de la collection.
+    InvalidConfig(String),
+    /// Une structure interne d'une collection est incohérente.
+    InvariantViolation(String),
+    /// Une opération d'entrée/sortie a échoué ; contient la description de l'erreur système.
+    Io(String),
+    /// Un fichier de sauvegarde est invalide ou corrompu.
+    InvalidSnapshot(String),
+    /// Une coordonnée `f64` dépasse l'étendue des `f32` ; `index` est sa position dans
+    /// le vecteur.
+    ValueOutOfRange { index: usize, value: f64 },
+    /// Une recherche stricte a rencontré un document dont la dimension diffère de celle
+    /// de la requête.
+    InconsistentDimension {
+        key: Uuid,
+        expected: usize,
+        got: usize,
+    },
+    /// Le document `key` est presque identique au document existant `existing`, avec
+    /// lequel il atteint le seuil de déduplication.
+    Duplicate {
+        key: Uuid,
+        existing: Uuid,
+        score: f32,
+    },
+    /// Aucun document ne porte cet identifiant.
+    DocumentNotFound(Uuid),
+    /// Une plage de coordonnées `[offset, offset + len)` dépasse la dimension du vecteur.
+    RangeOutOfBounds {
+        offset: usize,
+        len: usize,
+        dimension: usize,
+    },
+    /// Un enregistrement de réplication arrive alors que les précédents manquent.
+    ReplicationGap { expected: u64, got: u64 },
+    /// La ligne `line` (à partir de 1) d'un fichier importé est invalide.
+    Parse { line: usize, message: String },
+    /// Une recherche exigeant exactement `requested` résultats n'en a trouvé que
+    /// `available` (voir [`SearchParams::require_exact_k`](crate::SearchParams::require_exact_k)).
+    InsufficientCandidates { requested: usize, available: usize },
+    /// Une insertion porterait la mémoire de la base à `required` octets, au-delà de sa
+    /// limite de `limit` octets (voir
+    /// [`BaseDeDonnees::set_memory_limit`](crate::BaseDeDonnees::set_memory_limit)).
+    MemoryLimitExceeded { limit: usize, required: usize },
+    /// Le primaire a été arrêté par [`Primary::shutdown`](crate::Primary::shutdown) et
+    /// n'accepte plus d'écritures.
+    ShutDown,
+    /// Le champ `field` de la charge utile du document `key` ne respecte pas le schéma de
+    /// la collection : `value` n'est pas du type `expected`, ou vaut `None` si le champ
+    /// obligatoire est absent.
+    SchemaViolation {
+        key: Uuid,
+        field: String,
+        expected: FieldType,
+        value: Option<Value>,
+    },
+    /// Le vecteur du document `key` a `got` coordonnées alors que la collection a fixé
+    /// sa dimension à `locked` lors de sa première insertion (voir
+    /// [`Collection::dimension`](crate::Collection::dimension)).
+    DimensionLocked {
+        key: Uuid,
+        locked: usize,
+        got: usize,
+    },
+    /// Une expression de requête ([`QueryExpr`](crate::QueryExpr)) s'écarte de la
+    /// grammaire à l'octet `offset`, où `expected` était attendu.
+    InvalidExpression { offset: usize, expected: String },
+    /// Aucune vue conservée par
+    /// [`BaseDeDonnees::retain_snapshot`](crate::BaseDeDonnees::retain_snapshot) ne porte
+    /// cette étiquette.
+    SnapshotNotFound(String),
+    /// Aucune requête enregistrée par
+    /// [`Collection::save_query`](crate::Collection::save_query) ne porte ce nom.
+    SavedQueryNotFound(String),
+    /// Le vecteur du document `key` a la norme `norm`, à plus de `tolerance` de 1, alors
+    /// que la collection déclare des vecteurs unitaires (voir
+    /// [`CollectionConfigBuilder::vectors_are_normalized`](crate::CollectionConfigBuilder::vectors_are_normalized)).
+    NotNormalized {
+        key: Uuid,
+        norm: f32,
+        tolerance: f32,
+    },
+    /// Des données chiffrées n'ont pas pu être authentifiées : la clé est incorrecte ou
+    /// les données ont été modifiées.
+    AuthenticationFailed,
+    /// La requête vient d'un autre modèle de plongement que les vecteurs de la collection
+    /// (voir [`SearchParams::model`](crate::SearchParams::model)).
+    ModelMismatch {
+        expected: ModelFingerprint,
+        got: ModelFingerprint,
+    },
+    /// Un flux IPC Arrow est mal formé ou utilise une possibilité du format non prise en
+    /// charge (compression, ordre des octets gros-boutiste...).
+    InvalidArrow(String),
+    /// La colonne `column` d'un flux IPC Arrow n'a pas le type attendu : `expected`
+    /// décrit les types acceptés, `found` celui du flux.
+    ArrowSchema {
+        column: String,
+        expected: String,
+        found: String,
+    },
+    /// La collection `collection` existe déjà avec une valeur de `setting` différente de
+    /// celle de sa déclaration (voir [`BaseDeDonnees::apply_config`](crate::BaseDeDonnees::apply_config)).
+    ConfigConflict {
+        collection: String,
+        setting: String,
+        declared: String,
+        existing: String,
+    },
+    /// Les enregistrements demandés à partir du numéro `requested` ne sont plus conservés
+    /// par le journal d'un [`Primary`](crate::Primary), dont le plus ancien porte le
+    /// numéro `oldest` : le lecteur doit repartir d'une sauvegarde.
+    CursorTooOld { requested: u64, oldest: u64 },
+    /// Le document a déjà un vecteur : il ne peut pas être enregistré sans vecteur
+    /// ([`Collection::register`](crate::Collection::register)).
+    DocumentExists(Uuid),
+    /// Le vecteur du document `key` a la norme `norm`, hors de la plage [`min`, `max`]
+    /// admise par la collection (voir
+    /// [`CollectionConfigBuilder::norm_range`](crate::CollectionConfigBuilder::norm_range)).
+    NormOutOfRange {
+        key: Uuid,
+        norm: f64,
+        min: f32,
+        max: f32,
+    },
+    /// Le nom est déjà celui d'une collection ou d'un alias de la base de destination
+    /// (voir [`Workspace::move_collection`](crate::Workspace::move_collection)).
+    CollectionExists(String),
+    /// Aucune base de l'espace de travail ne porte ce nom
+    /// (voir [`Workspace`](crate::Workspace)).
+    DatabaseNotFound(String),
+    /// La collection `collection` d'une base partagée est en quarantaine, en lecture
+    /// seule : une panique sous le verrou en écriture l'a laissée incohérente, comme le
+    /// décrit `reason` (voir
+    /// [`BaseDeDonneesPartagee::quarantined`](crate::BaseDeDonneesPartagee::quarantined)).
+    CollectionPoisoned { collection: String, reason: String },
+    /// Une fonction fournie par l'appelant, comme un
+    /// [`Rescorer`](crate::Rescorer) ou une [`QueryTransform`](crate::QueryTransform),
+    /// a paniqué ; contient le message de la panique.
+    Panicked(String),
+    /// La valeur du champ `field` du document `key`, conservée dans le magasin de blobs
+    /// de la collection ([`Collection::set_blob_store`](crate::Collection::set_blob_store)),
+    /// n'a pas pu être relue, pour la raison décrite par `reason`.
+    BlobUnavailable {
+        key: Uuid,
+        field: String,
+        reason: String,
+    },
+    /// `source` s'est produite dans les circonstances décrites par `context` ; voir
+    /// [`Error::context`] et [`Error::root`].
+    WithContext {
+        context: ErrorContext,
+        source: Box<Error>,
+    },
+}
+
+/// Opération au cours de laquelle une erreur s'est produite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Recherche dans une collection.
+    Search,
+    /// Insertion ou mise à jour d'un document.
+    Upsert,
+    /// Suppression d'un document.
+    Delete,
+    /// Import d'un fichier de documents.
+    Import,
+    /// Chargement d'une sauvegarde.
+    Load,
+    /// Application d'un lot d'opérations ([`Collection::apply`](crate::Collection::apply)).
+    Apply,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Search => "recherche",
+            Operation::Upsert => "insertion",
+            Operation::Delete => "suppression",
+            Operation::Import => "import",
+            Operation::Load => "chargement",
+            Operation::Apply => "application d'un lot",
+        })
+    }
+}
+
+/// Circonstances d'une erreur : opération, collection et document concernés, lorsqu'ils
+/// sont connus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorContext {
+    /// Opération demandée.
+    pub operation: Operation,
+    /// Nom de la collection, tel que donné par l'appelant.
+    pub collection: Option<String>,
+    /// Document en cause.
+    pub key: Option<Uuid>,
+    /// Ligne en cause d'un fichier importé, ou position de l'opération en cause d'un lot
+    /// ([`Collection::apply`](crate::Collection::apply)), à partir de 1.
+    pub line: Option<usize>,
+    /// Identifiant de la requête de l'appelant
+    /// ([`SearchParams::context`](crate::SearchParams::context)).
+    pub request_id: Option<String>,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(operation: Operation) -> Self {
+        ErrorContext {
+            operation,
+            collection: None,
+            key: None,
+            line: None,
+            request_id: None,
+        }
+    }
+
+    pub(crate) fn collection(mut self, nom: &str) -> Self {
+        self.collection = Some(nom.to_string());
+        self
+    }
+
+    pub(crate) fn key(mut self, key: Uuid) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub(crate) fn line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub(crate) fn request(mut self, context: Option<&RequestContext>) -> Self {
+        self.request_id = context.map(|context| context.request_id().to_string());
+        self
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if let Some(nom) = &self.collection {
+            write!(f, " dans la collection '{}'", nom)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, ", document {}", key)?;
+        }
+        match (self.line, self.operation) {
+            (Some(position), Operation::Apply) => write!(f, ", opération {}", position)?,
+            (Some(line), _) => write!(f, ", ligne {}", line)?,
+            (None, _) => {}
+        }
+        if let Some(id) = &self.request_id {
+            write!(f, ", requête {}", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    /// Retourne les circonstances de l'erreur, si elles sont connues.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Retourne l'erreur sans ses circonstances, pour distinguer les cas d'erreur :
+    /// `matches!(error.root(), Error::DimensionMismatch { .. })`.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::WithContext { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Joint `context` à l'erreur. Les circonstances déjà connues sont conservées, et
+    /// complétées par celles de `context` ; `Error::CollectionNotFound`, qui nomme déjà
+    /// la collection, est retournée telle quelle.
+    pub(crate) fn with_context(self, context: ErrorContext) -> Error {
+        match self {
+            Error::CollectionNotFound(_) => self,
+            Error::WithContext {
+                context: inner,
+                source,
+            } => Error::WithContext {
+                context: ErrorContext {
+                    operation: context.operation,
+                    collection: inner.collection.or(context.collection),
+                    key: inner.key.or(context.key),
+                    line: inner.line.or(context.line),
+                    request_id: inner.request_id.or(context.request_id),
+                },
+                source,
+            },
+            source => Error::WithContext {
+                context,
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ZeroVector { key: Some(key) } => {
+                write!(f, "le vecteur du document {} est nul", key)
+            }
+            Error::ZeroVector { key: None } => write!(f, "le vecteur de requête est nul"),
+            Error::CollectionNotFound(nom) => write!(f, "la collection '{}' n'existe pas", nom),
+            Error::DimensionMismatch { expected, got } => write!(
+                f,
+                "dimension incorrecte : {} attendue, {} reçue",
+                expected, got
+            ),
+            Error::EmptyQuery => write!(f, "la requête ne contient aucun vecteur"),
+            Error::InvalidConfig(description) => {
+                write!(f, "configuration invalide : {}", description)
+            }
+            Error::InvariantViolation(description) => {
+                write!(f, "invariant violé : {}", description)
+            }
+            Error::Io(description) => write!(f, "erreur d'entrée/sortie : {}", description),
+            Error::InvalidSnapshot(description) => {
+                write!(f, "sauvegarde invalide : {}", description)
+            }
+            Error::ValueOutOfRange { index, value } => write!(
+                f,
+                "la coordonnée {} ({}) dépasse l'étendue des f32",
+                index,
+                float::shortest_f64(*value)
+            ),
+            Error::InconsistentDimension { key, expected, got } => write!(
+                f,
+                "le document {} a la dimension {} au lieu de {}",
+                key, got, expected
+            ),
+            Error::Duplicate {
+                key,
+                existing,
+                score,
+            } => write!(
+                f,
+                "le document {} est un doublon de {} (score {})",
+                key,
+                existing,
+                float::significant(f64::from(*score), float::DISPLAY_DIGITS)
+            ),
+            Error::DocumentNotFound(key) => write!(f, "le document {} n'existe pas", key),
+            Error::RangeOutOfBounds {
+                offset,
+                len,
+                dimension,
+            } => write!(
+                f,
+                "les coordonnées {}..{} dépassent la dimension {}",
+                offset,
+                offset.saturating_add(*len),
+                dimension
+            ),
+            Error::ReplicationGap { expected, got } => write!(
+                f,
+                "enregistrement {} reçu alors que {} était attendu",
+                got, expected
+            ),
+            Error::InvalidArrow(message) => write!(f, "flux IPC Arrow invalide : {}", message),
+            Error::ArrowSchema {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "colonne Arrow « {} » : {} attendu, {} trouvé",
+                column, expected, found
+            ),
+            Error::Parse { line, message } => write!(f, "ligne {} invalide : {}", line, message),
+            Error::InsufficientCandidates {
+                requested,
+                available,
+            } => write!(
+                f,
+                "{} résultats demandés, seuls {} documents sont disponibles",
+                requested, available
+            ),
+            Error::MemoryLimitExceeded { limit, required } => write!(
+                f,
+                "l'insertion porterait la mémoire à {} octets, au-delà de la limite de {}",
+                required, limit
+            ),
+            Error::ShutDown => write!(f, "le primaire est arrêté et n'accepte plus d'écritures"),
+            Error::SchemaViolation {
+                key,
+                field,
+                expected,
+                value: Some(value),
+            } => write!(
+                f,
+                "le champ « {} » du document {} vaut {}, le type attendu est {}",
+                field, key, value, expected
+            ),
+            Error::SchemaViolation {
+                key,
+                field,
+                expected,
+                value: None,
+            } => write!(
+                f,
+                "le champ obligatoire « {} » ({}) est absent du document {}",
+                field, expected, key
+            ),
+            Error::DimensionLocked { key, locked, got } => write!(
+                f,
+                "le document {} a la dimension {} alors que la collection est fixée à {}",
+                key, got, locked
+            ),
+            Error::InvalidExpression { offset, expected } => write!(
+                f,
+                "expression de requête invalide à l'octet {} : {} attendu",
+                offset, expected
+            ),
+            Error::SnapshotNotFound(label) => {
+                write!(f, "aucune vue conservée ne porte l'étiquette « {} »", label)
+            }
+            Error::SavedQueryNotFound(name) => {
+                write!(f, "aucune requête enregistrée ne porte le nom « {} »", name)
+            }
+            Error::NotNormalized {
+                key,
+                norm,
+                tolerance,
+            } => write!(
+                f,
+                "le vecteur du document {} a la norme {}, à plus de {} de 1, alors que la \
+                 collection déclare des vecteurs unitaires",
+                key,
+                float::shortest_f32(*norm),
+                float::shortest_f32(*tolerance)
+            ),
+            Error::AuthenticationFailed => write!(
+                f,
+                "échec de l'authentification des données chiffrées : clé incorrecte ou données modifiées"
+            ),
+            Error::ModelMismatch { expected, got } => write!(
+                f,
+                "la requête vient du modèle {} alors que la collection contient des vecteurs \
+                 du modèle {}",
+                got, expected
+            ),
+            Error::ConfigConflict {
+                collection,
+                setting,
+                declared,
+                existing,
+            } => write!(
+                f,
+                "la collection '{}' existe avec {} = {} alors que la configuration déclare {}",
+                collection, setting, existing, declared
+            ),
+            Error::CursorTooOld { requested, oldest } => write!(
+                f,
+                "l'enregistrement {} n'est plus conservé, le plus ancien est le {}",
+                requested, oldest
+            ),
+            Error::DocumentExists(key) => write!(f, "le document {} a déjà un vecteur", key),
+            Error::NormOutOfRange {
+                key,
+                norm,
+                min,
+                max,
+            } => write!(
+                f,
+                "le vecteur du document {} a la norme {}, hors de la plage admise [{}, {}]",
+                key,
+                float::shortest_f64(*norm),
+                float::shortest_f32(*min),
+                float::shortest_f32(*max)
+            ),
+            Error::CollectionExists(nom) => {
+                write!(f, "le nom '{}' est déjà pris dans la base de destination", nom)
+            }
+            Error::DatabaseNotFound(nom) => write!(f, "la base '{}' n'existe pas", nom),
+            Error::CollectionPoisoned { collection, reason } => write!(
+                f,
+                "la collection '{}' est en quarantaine en lecture seule après une panique : {}",
+                collection, reason
+            ),
+            Error::Panicked(message) => {
+                write!(f, "une fonction de l'appelant a paniqué : {}", message)
+            }
+            Error::BlobUnavailable { key, field, reason } => write!(
+                f,
+                "le blob du champ '{}' du document {} est illisible : {}",
+                field, key, reason
+            ),
+            Error::WithContext { context, source } => write!(f, "{} : {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WithContext { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+impl From<DimensionMismatch> for Error {
+    fn from(error: DimensionMismatch) -> Self {
+        Error::DimensionMismatch {
+            expected: error.expected,
+            got: error.got,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.to_string())
+    }
+}
+
+/// Type alias pour les résultats des opérations de la base de données.
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+
+    fn database() -> BaseDeDonnees {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        db.get_mut("docs").unwrap().upsert(Uuid::new_v4(), [1.0, 2.0, 3.0]).unwrap();
+        db
+    }
+
+    #[test]
+    fn search_errors_name_the_collection() {
+        let db = database();
+        let error = db.search("docs", [1.0; 5], 1).unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(context.operation, Operation::Search);
+        assert_eq!(context.collection.as_deref(), Some("docs"));
+        assert_eq!(context.key, None);
+        assert!(matches!(
+            error.root(),
+            Error::DimensionMismatch {
+                expected: 3,
+                got: 5
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "recherche dans la collection 'docs' : dimension incorrecte : 3 attendue, 5 reçue"
+        );
+
+        // Une collection absente est déjà nommée par l'erreur.
+        let missing = db.search("absente", [1.0; 3], 1).unwrap_err();
+        assert!(missing.context().is_none());
+        assert!(matches!(missing, Error::CollectionNotFound(nom) if nom == "absente"));
+    }
+
+    #[test]
+    fn import_errors_name_the_document_and_its_line() {
+        let mut db = database();
+        let key = Uuid::new_v4();
+        let lines = format!(
+            "{{\"id\": \"{}\", \"vector\": [1, 2, 3]}}\n\n{{\"id\": \"{}\", \"vector\": [1, 2]}}\n",
+            Uuid::new_v4(),
+            key
+        );
+        let error = db.import_jsonl("docs", lines.as_bytes(), None).unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(context.operation, Operation::Import);
+        assert_eq!(context.collection.as_deref(), Some("docs"));
+        assert_eq!((context.key, context.line), (Some(key), Some(3)));
+        assert!(matches!(error.root(), Error::DimensionLocked { locked: 3, got: 2, .. }));
+        let message = error.to_string();
+        assert!(message.starts_with(&format!(
+            "import dans la collection 'docs', document {}, ligne 3 : ",
+            key
+        )));
+        assert_eq!(db.get("docs").unwrap().len(), 2);
+
+        let error = db.import_jsonl("docs", &b"pas du json\n"[..], None).unwrap_err();
+        assert_eq!(error.context().unwrap().collection.as_deref(), Some("docs"));
+        assert!(matches!(error.root(), Error::Parse { line: 1, .. }));
+    }
+
+    #[test]
+    fn outer_context_completes_the_inner_one() {
+        let key = Uuid::new_v4();
+        let error = Error::DocumentNotFound(key)
+            .with_context(ErrorContext::new(Operation::Upsert).key(key).line(4))
+            .with_context(ErrorContext::new(Operation::Import).collection("docs").line(9));
+        let context = error.context().unwrap();
+        assert_eq!(context.operation, Operation::Import);
+        assert_eq!(context.collection.as_deref(), Some("docs"));
+        assert_eq!((context.key, context.line), (Some(key), Some(4)));
+        assert!(matches!(error.root(), Error::DocumentNotFound(k) if *k == key));
+    }
+}
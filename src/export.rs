@@ -0,0 +1,372 @@
+//! Export de résultats de recherche en CSV ou en JSON vers n'importe quel `io::Write`.
+//!
+//! Les colonnes de charge utile sont celles demandées par l'appelant, dans l'ordre
+//! demandé ; un champ absent donne un champ CSV vide ou `null` en JSON. En CSV, une
+//! chaîne est écrite telle quelle (avec l'échappement RFC 4180), un tableau ou un objet
+//! sous forme de texte JSON.
+
+use std::io::Write;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::csv;
+use crate::error::Result;
+use crate::float;
+use crate::json;
+use crate::payload::Value;
+use crate::search::{SearchOverrides, SearchResults};
+use crate::store::VectorStore;
+
+/// Format d'export des résultats de recherche.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// CSV avec une ligne d'en-tête.
+    #[default]
+    Csv,
+    /// JSON ; les exports de plusieurs requêtes produisent une ligne JSON par requête.
+    Json,
+}
+
+/// Source des colonnes de charge utile d'un export.
+#[derive(Clone, Copy)]
+struct Columns<'a> {
+    store: Option<&'a dyn VectorStore>,
+    fields: &'a [&'a str],
+}
+
+impl<'a> Columns<'a> {
+    const NONE: Columns<'static> = Columns {
+        store: None,
+        fields: &[],
+    };
+
+    fn value(&self, key: &Uuid, field: &str) -> Option<&'a Value> {
+        self.store?.payload(key)?.get(field)
+    }
+}
+
+impl SearchResults {
+    /// Écrit les résultats en CSV : en-tête `id,score` puis une ligne par résultat.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn write_csv(&self, writer: impl Write) -> Result<()> {
+        write_block(writer, self, None, Columns::NONE, ExportFormat::Csv, true)
+    }
+
+    /// Écrit les résultats en CSV avec une colonne par champ de charge utile de `fields`,
+    /// lus dans `store`.
+    ///
+    /// # Arguments
+    /// * `writer` - Destination de l'export.
+    /// * `store` - Collection d'où proviennent les résultats.
+    /// * `fields` - Champs de charge utile à exporter, dans l'ordre des colonnes.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn write_csv_with_payload(
+        &self,
+        writer: impl Write,
+        store: &dyn VectorStore,
+        fields: &[&str],
+    ) -> Result<()> {
+        let columns = Columns {
+            store: Some(store),
+            fields,
+        };
+        write_block(writer, self, None, columns, ExportFormat::Csv, true)
+    }
+
+    /// Écrit les résultats en JSON, comme [`SearchResults::to_json`].
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn write_json(&self, writer: impl Write) -> Result<()> {
+        write_block(writer, self, None, Columns::NONE, ExportFormat::Json, false)
+    }
+
+    /// Écrit les résultats en JSON en ajoutant à chaque résultat un objet `payload` qui
+    /// contient les champs de `fields`, lus dans `store`.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn write_json_with_payload(
+        &self,
+        writer: impl Write,
+        store: &dyn VectorStore,
+        fields: &[&str],
+    ) -> Result<()> {
+        let columns = Columns {
+            store: Some(store),
+            fields,
+        };
+        write_block(writer, self, None, columns, ExportFormat::Json, false)
+    }
+}
+
+impl Collection {
+    /// Recherche les `k` plus proches documents de chaque requête et écrit les résultats
+    /// au fil de l'eau, un bloc par requête.
+    ///
+    /// En CSV, l'en-tête `query,id,score` suivi des champs de `fields` n'est écrit qu'une
+    /// fois et chaque ligne commence par l'indice de sa requête. En JSON, chaque requête
+    /// donne une ligne `{"query":i,"hits":[...]}`.
+    ///
+    /// # Arguments
+    /// * `queries` - Vecteurs de requête.
+    /// * `k` - Nombre de résultats par requête.
+    /// * `writer` - Destination de l'export.
+    /// * `format` - Format de l'export.
+    /// * `fields` - Champs de charge utile à exporter.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search`], pour la première requête en échec ; les blocs
+    ///   des requêtes précédentes ont déjà été écrits.
+    /// * `Error::Io` - Si l'écriture échoue.
+    pub fn export_results_for_queries<Q: AsRef<[f32]>>(
+        &self,
+        queries: &[Q],
+        k: usize,
+        mut writer: impl Write,
+        format: ExportFormat,
+        fields: &[&str],
+    ) -> Result<()> {
+        let columns = Columns {
+            store: Some(self),
+            fields,
+        };
+        if format == ExportFormat::Csv {
+            let header = SearchResults::default();
+            write_block(&mut writer, &header, Some(0), columns, format, true)?;
+        }
+        for (index, query) in queries.iter().enumerate() {
+            let overrides = SearchOverrides {
+                k: Some(k),
+                ..Default::default()
+            };
+            let results = self.search_with_defaults(query, &overrides)?;
+            write_block(&mut writer, &results, Some(index), columns, format, false)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Écrit un bloc de résultats, précédé de l'en-tête CSV si `header`.
+fn write_block(
+    mut writer: impl Write,
+    results: &SearchResults,
+    query: Option<usize>,
+    columns: Columns<'_>,
+    format: ExportFormat,
+    header: bool,
+) -> Result<()> {
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => write_csv_block(&mut out, results, query, columns, header),
+        ExportFormat::Json => write_json_block(&mut out, results, query, columns),
+    }
+    writer.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+fn write_csv_block(
+    out: &mut String,
+    results: &SearchResults,
+    query: Option<usize>,
+    columns: Columns<'_>,
+    header: bool,
+) {
+    if header {
+        let query_column = query.map(|_| "query");
+        csv::write_record(
+            out,
+            query_column
+                .into_iter()
+                .chain(["id", "score"])
+                .chain(columns.fields.iter().copied()),
+        );
+    }
+    for (key, score) in &results.hits {
+        let mut record: Vec<String> = query.map(|query| query.to_string()).into_iter().collect();
+        record.push(key.to_string());
+        record.push(if score.is_finite() {
+            float::shortest_f32(*score)
+        } else {
+            String::new()
+        });
+        for field in columns.fields {
+            record.push(payload_cell(columns.value(key, field)));
+        }
+        csv::write_record(out, record.iter().map(String::as_str));
+    }
+}
+
+/// Texte d'une colonne de charge utile en CSV : vide pour un champ absent ou `null`,
+/// la chaîne telle quelle, et le texte JSON des autres valeurs.
+pub(crate) fn payload_cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => {
+            let mut text = String::new();
+            json::write_value(&mut text, value);
+            text
+        }
+    }
+}
+
+fn write_json_block(
+    out: &mut String,
+    results: &SearchResults,
+    query: Option<usize>,
+    columns: Columns<'_>,
+) {
+    if let Some(query) = query {
+        out.push_str("{\"query\":");
+        json::write_u64(out, query as u64);
+        out.push_str(",\"hits\":");
+    }
+    json::write_array(out, &results.hits, |out, (key, score)| {
+        out.push_str("{\"id\":");
+        json::write_string(out, &key.to_string());
+        out.push_str(",\"score\":");
+        json::write_f32(out, *score);
+        if !columns.fields.is_empty() {
+            out.push_str(",\"payload\":{");
+            for (i, field) in columns.fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                json::write_string(out, field);
+                out.push(':');
+                match columns.value(key, field) {
+                    Some(value) => json::write_value(out, value),
+                    None => out.push_str("null"),
+                }
+            }
+            out.push('}');
+        }
+        out.push('}');
+    });
+    if query.is_some() {
+        out.push_str("}\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::similarity::Metric;
+
+    /// Trois documents dont les charges utiles contiennent virgule, guillemets et saut de
+    /// ligne ; la requête `[1.0, 0.0]` leur donne les scores 3, 2 et 1.
+    fn collection() -> Collection {
+        let mut collection = Collection::new().with_metric(Metric::Dot);
+        let fields: [(&str, Option<Value>, Option<Value>); 3] = [
+            ("ligne 1\nligne 2", Some(Value::Null), None),
+            ("dit \"oui\"", None, None),
+            (
+                "a, b",
+                Some(Value::Number(4.5)),
+                Some(Value::Array(vec![Value::String("x".into()), Value::String("y".into())])),
+            ),
+        ];
+        for (i, (titre, note, tags)) in fields.into_iter().enumerate() {
+            let mut payload = Payload::new();
+            payload.insert("titre".to_string(), Value::String(titre.to_string()));
+            if let Some(note) = note {
+                payload.insert("note".to_string(), note);
+            }
+            if let Some(tags) = tags {
+                payload.insert("tags".to_string(), tags);
+            }
+            let key = Uuid::from_u128(i as u128 + 1);
+            collection
+                .upsert_with_payload(key, [i as f32 + 1.0, 0.0], payload)
+                .unwrap();
+        }
+        collection
+    }
+
+    fn written(f: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> String {
+        let mut out = Vec::new();
+        f(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    const FIELDS: [&str; 3] = ["titre", "note", "tags"];
+
+    #[test]
+    fn csv_payload_columns_are_escaped_in_the_requested_order() {
+        let collection = collection();
+        let results = collection.search_with([1.0, 0.0], &Default::default()).unwrap();
+        let (a, b, c) = (Uuid::from_u128(3), Uuid::from_u128(2), Uuid::from_u128(1));
+        assert_eq!(
+            written(|out| results.write_csv_with_payload(out, &collection, &FIELDS)),
+            format!(
+                "id,score,titre,note,tags\n\
+                 {a},3,\"a, b\",4.5,\"[\"\"x\"\",\"\"y\"\"]\"\n\
+                 {b},2,\"dit \"\"oui\"\"\",,\n\
+                 {c},1,\"ligne 1\nligne 2\",,\n"
+            )
+        );
+        assert_eq!(
+            written(|out| results.write_csv(out)),
+            format!("id,score\n{a},3\n{b},2\n{c},1\n")
+        );
+        let empty = SearchResults::default();
+        assert_eq!(
+            written(|out| empty.write_csv_with_payload(out, &collection, &["tags", "titre"])),
+            "id,score,tags,titre\n"
+        );
+    }
+
+    #[test]
+    fn json_payload_objects_keep_the_requested_fields() {
+        let collection = collection();
+        let results = collection.search_with([1.0, 0.0], &Default::default()).unwrap();
+        let text = written(|out| results.write_json_with_payload(out, &collection, &FIELDS));
+        let (a, b, c) = (Uuid::from_u128(3), Uuid::from_u128(2), Uuid::from_u128(1));
+        assert_eq!(
+            text,
+            format!(
+                "[{{\"id\":\"{a}\",\"score\":3,\"payload\":{{\"titre\":\"a, b\",\"note\":4.5,\"tags\":[\"x\",\"y\"]}}}},\
+                 {{\"id\":\"{b}\",\"score\":2,\"payload\":{{\"titre\":\"dit \\\"oui\\\"\",\"note\":null,\"tags\":null}}}},\
+                 {{\"id\":\"{c}\",\"score\":1,\"payload\":{{\"titre\":\"ligne 1\\nligne 2\",\"note\":null,\"tags\":null}}}}]"
+            )
+        );
+        let Value::Array(hits) = json::parse(&text).unwrap() else { panic!("tableau attendu") };
+        let Value::Object(hit) = &hits[2] else { panic!("objet attendu") };
+        let Value::Object(payload) = &hit["payload"] else { panic!("objet attendu") };
+        assert_eq!(payload["titre"], Value::String("ligne 1\nligne 2".into()));
+        assert_eq!(written(|out| results.write_json(out)), results.to_json());
+    }
+
+    #[test]
+    fn query_exports_stream_one_block_per_query() {
+        let collection = collection();
+        let queries = [[1.0, 0.0], [-1.0, 0.0]];
+        let (a, c) = (Uuid::from_u128(3), Uuid::from_u128(1));
+        let csv = written(|out| {
+            collection.export_results_for_queries(&queries, 1, out, ExportFormat::Csv, &["titre"])
+        });
+        assert_eq!(csv, format!("query,id,score,titre\n0,{a},3,\"a, b\"\n1,{c},-1,\"ligne 1\nligne 2\"\n"));
+
+        let jsonl = written(|out| {
+            collection.export_results_for_queries(&queries, 2, out, ExportFormat::Json, &[])
+        });
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (index, line) in lines.iter().enumerate() {
+            let Value::Object(block) = json::parse(line).unwrap() else { panic!("objet attendu") };
+            assert_eq!(block["query"], Value::Number(index as f64));
+            assert!(matches!(&block["hits"], Value::Array(hits) if hits.len() == 2));
+        }
+        assert!(collection
+            .export_results_for_queries(&[[1.0, 0.0, 0.0]], 1, Vec::new(), ExportFormat::Csv, &[])
+            .is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::payload::Value;
+
+/// Nombre de documents jusqu'auquel [`Collection::field_cardinality`] compte exactement.
+pub const EXACT_CARDINALITY_LIMIT: usize = 10_000;
+
+/// Nombre de bits de hachage choisissant le registre de l'esquisse HyperLogLog : 2^14
+/// registres, soit une erreur relative typique de 0,8 %.
+const SKETCH_BITS: u32 = 14;
+
+impl Collection {
+    /// Retourne la valeur du champ `field` de la charge utile du document `key`.
+    ///
+    /// C'est l'accès aux charges utiles commun aux regroupements et aux facettes.
+    pub(crate) fn field(&self, key: &Uuid, field: &str) -> Option<&Value> {
+        self.payloads
+            .get(key)
+            .and_then(|payload| payload.get(field))
+    }
+
+    /// Parcourt les valeurs du champ `field` des documents qui en ont un.
+    pub(crate) fn field_values<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a Value> {
+        self.payloads
+            .values()
+            .filter_map(move |payload| payload.get(field))
+    }
+
+    /// Retourne les valeurs les plus fréquentes du champ `field`, avec le nombre de
+    /// documents qui portent chacune.
+    ///
+    /// Les valeurs sont classées par nombre de documents décroissant, les égalités par
+    /// représentation JSON croissante. Un tableau compte comme une seule valeur, comme
+    /// dans [`Collection::search_grouped`]. Les documents sans le champ sont ignorés.
+    ///
+    /// Si les agrégats de la collection sont bruités ([`Collection::set_aggregate_noise`]),
+    /// chaque compte l'est avant le classement et la limite, et les valeurs dont le compte
+    /// bruité est nul sont omises ; seules les valeurs portées par au moins un document
+    /// peuvent toutefois apparaître. [`Collection::facet_exact`] retourne les comptes
+    /// exacts.
+    ///
+    /// # Arguments
+    /// * `field` - Nom du champ.
+    /// * `limit` - Nombre maximal de valeurs à retourner.
+    ///
+    /// # Retourne
+    /// * Vec<(Value, usize)> - Les valeurs retenues avec leur nombre de documents.
+    pub fn facet(&self, field: &str, limit: usize) -> Vec<(Value, usize)> {
+        let mut counts = self.facet_counts(field);
+        if let Some(mut laplace) = self.laplace() {
+            for (_, _, count) in &mut counts {
+                *count = laplace.count(*count as u64) as usize;
+            }
+            counts.retain(|(_, _, count)| *count > 0);
+        }
+        top_facets(counts, limit)
+    }
+
+    /// Retourne les valeurs les plus fréquentes du champ `field` comme
+    /// [`Collection::facet`], avec des comptes toujours exacts, même si les agrégats de
+    /// la collection sont bruités.
+    ///
+    /// # Arguments
+    /// * `field` - Nom du champ.
+    /// * `limit` - Nombre maximal de valeurs à retourner.
+    ///
+    /// # Retourne
+    /// * Vec<(Value, usize)> - Les valeurs retenues avec leur nombre de documents.
+    pub fn facet_exact(&self, field: &str, limit: usize) -> Vec<(Value, usize)> {
+        top_facets(self.facet_counts(field), limit)
+    }
+
+    /// Compte les documents de chaque valeur du champ `field`, avec sa représentation
+    /// JSON.
+    fn facet_counts<'a>(&'a self, field: &'a str) -> Vec<(String, &'a Value, usize)> {
+        let mut counts: HashMap<&Value, usize> = HashMap::new();
+        for value in self.field_values(field) {
+            *counts.entry(value).or_insert(0) += 1;
+        }
+        counts
+            .into_iter()
+            .map(|(value, count)| (value.to_string(), value, count))
+            .collect()
+    }
+
+    /// Retourne le nombre de valeurs distinctes du champ `field`.
+    ///
+    /// Le compte est exact jusqu'à [`EXACT_CARDINALITY_LIMIT`] documents ; au-delà, il est
+    /// estimé par une esquisse HyperLogLog de mémoire bornée, à environ 1 % près.
+    ///
+    /// # Arguments
+    /// * `field` - Nom du champ.
+    pub fn field_cardinality(&self, field: &str) -> usize {
+        if self.payloads.len() <= EXACT_CARDINALITY_LIMIT {
+            return self.field_values(field).collect::<HashSet<_>>().len();
+        }
+        let mut sketch = Sketch::new();
+        self.field_values(field)
+            .for_each(|value| sketch.insert(value));
+        sketch.estimate()
+    }
+}
+
+/// Esquisse HyperLogLog : estime le nombre d'éléments distincts insérés.
+struct Sketch {
+    registers: Vec<u8>,
+}
+
+impl Sketch {
+    fn new() -> Self {
+        Sketch {
+            registers: vec![0; 1 << SKETCH_BITS],
+        }
+    }
+
+    fn insert(&mut self, value: &Value) {
+        // SipHash à clés nulles : le même élément tombe toujours dans le même registre.
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - SKETCH_BITS)) as usize;
+        let rank = ((hash << SKETCH_BITS).leading_zeros() + 1).min(64 - SKETCH_BITS + 1) as u8;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        // Correction des petits effectifs : comptage linéaire des registres vides.
+        let estimate = match raw <= 2.5 * m && empty > 0 {
+            true => m * (m / empty as f64).ln(),
+            false => raw,
+        };
+        estimate.round() as usize
+    }
+}
+
+/// Classe des comptes de [`Collection::facet_counts`] et en garde les `limit` premiers.
+fn top_facets(mut facets: Vec<(String, &Value, usize)>, limit: usize) -> Vec<(Value, usize)> {
+    facets.sort_unstable_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    facets
+        .into_iter()
+        .take(limit)
+        .map(|(_, value, count)| (value.clone(), count))
+        .collect()
+}
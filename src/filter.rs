@@ -0,0 +1,408 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::Result;
+use crate::payload::{Payload, Value};
+use crate::payload_index::PayloadIndex;
+use crate::search::{SearchParams, SearchResults, Stopwatch};
+
+/// Part des documents au-delà de laquelle [`FilterStrategy::Auto`] parcourt toute la
+/// collection plutôt que les candidats des index : réunir un grand ensemble de
+/// candidats puis retrouver chacun coûte alors plus que de vérifier le filtre au fil
+/// du parcours.
+pub const INDEX_FIRST_MAX_SELECTIVITY: f64 = 0.25;
+
+/// Exécution d'une recherche filtrée par [`Collection::search_filtered`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterStrategy {
+    /// Choix selon le nombre de candidats estimé par les index de charge utile : les
+    /// index d'abord jusqu'à [`INDEX_FIRST_MAX_SELECTIVITY`] des documents, le parcours
+    /// au-delà.
+    #[default]
+    Auto,
+    /// Seuls les documents désignés par les index sont évalués. Sans index couvrant le
+    /// filtre, la recherche parcourt toute la collection.
+    IndexFirst,
+    /// Toute la collection est parcourue, le filtre étant vérifié pour chaque document
+    /// sans recours aux index.
+    ScanFirst,
+}
+
+/// Plan d'exécution retenu pour une recherche filtrée, joint à ses résultats dans
+/// [`SearchResults::plan`].
+///
+/// Les deux stratégies retournent les mêmes résultats ; seule la durée change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterPlan {
+    /// Stratégie exécutée, [`FilterStrategy::IndexFirst`] ou
+    /// [`FilterStrategy::ScanFirst`].
+    pub strategy: FilterStrategy,
+    /// Nombre maximal de documents satisfaisant le filtre d'après les index, `None` si
+    /// aucun index ne le couvre.
+    pub estimated_candidates: Option<usize>,
+    /// Nombre de documents de la collection au moment du choix.
+    pub documents: usize,
+    /// Vrai si la stratégie a été imposée par [`SearchParams::filter_strategy`].
+    pub forced: bool,
+}
+
+impl fmt::Display for FilterPlan {
+    /// Affiche le plan sur une ligne : `index d'abord (≈ 50 candidats sur 10000 documents)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.strategy {
+            FilterStrategy::IndexFirst => write!(f, "index d'abord")?,
+            _ => write!(f, "parcours d'abord")?,
+        }
+        match self.estimated_candidates {
+            Some(estimated) => write!(
+                f,
+                " (≈ {} candidats sur {} documents",
+                estimated, self.documents
+            )?,
+            None => write!(f, " (aucun index, {} documents", self.documents)?,
+        }
+        if self.forced {
+            write!(f, ", imposé")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Condition portant sur la charge utile d'un document, utilisée par
+/// [`Collection::search_filtered`] et [`Collection::count_by_filter`].
+///
+/// `Filter::and(vec![Filter::eq("langue", "fr"), Filter::range("annee", Some(2020.0), None)])`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Le champ `field` vaut exactement `value`.
+    Eq { field: String, value: Value },
+    /// Le champ `field` est un nombre compris entre `min` et `max` inclus ; une borne
+    /// absente n'est pas vérifiée.
+    Range {
+        field: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// Toutes les conditions sont satisfaites ; une liste vide accepte tout document.
+    And(Vec<Filter>),
+}
+
+impl Filter {
+    /// Crée la condition « `field` vaut `value` ».
+    pub fn eq(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Eq {
+            field: field.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Crée la condition « `field` est un nombre entre `min` et `max` inclus ».
+    pub fn range(field: impl Into<String>, min: Option<f64>, max: Option<f64>) -> Self {
+        Filter::Range {
+            field: field.into(),
+            min,
+            max,
+        }
+    }
+
+    /// Crée la conjonction de `filters`.
+    pub fn and(filters: Vec<Filter>) -> Self {
+        Filter::And(filters)
+    }
+
+    /// Indique si une charge utile satisfait la condition. Un document sans charge utile
+    /// ne satisfait que la conjonction vide.
+    pub fn matches(&self, payload: Option<&Payload>) -> bool {
+        match self {
+            Filter::Eq { field, value } => payload.and_then(|p| p.get(field)) == Some(value),
+            Filter::Range { field, min, max } => payload
+                .and_then(|p| p.get(field))
+                .and_then(Value::as_f64)
+                .is_some_and(|n| min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)),
+            Filter::And(filters) => filters.iter().all(|filter| filter.matches(payload)),
+        }
+    }
+
+    /// Retourne le nombre maximal de documents satisfaisant la condition d'après les
+    /// index de charge utile, sans les réunir, ou `None` si aucun index ne le borne.
+    ///
+    /// Le compte est exact pour une condition indexée ; une conjonction est bornée par
+    /// la plus restrictive de ses conditions indexées.
+    pub(crate) fn estimate(&self, indexes: &HashMap<String, PayloadIndex>) -> Option<usize> {
+        match self {
+            Filter::Eq { field, value } => indexes.get(field)?.lookup_count(value),
+            Filter::Range { field, min, max } => indexes.get(field)?.range_count(*min, *max),
+            Filter::And(filters) => (filters.iter())
+                .filter_map(|filter| filter.estimate(indexes))
+                .min(),
+        }
+    }
+
+    /// Retourne les documents pouvant satisfaire la condition d'après les index de
+    /// charge utile, ou `None` si aucun index ne permet de les restreindre.
+    ///
+    /// Les candidats doivent encore être vérifiés par [`Filter::matches`] : une
+    /// conjonction n'est restreinte que par ses conditions indexées.
+    pub(crate) fn candidates(
+        &self,
+        indexes: &HashMap<String, PayloadIndex>,
+    ) -> Option<HashSet<Uuid>> {
+        match self {
+            Filter::Eq { field, value } => indexes.get(field)?.lookup(value),
+            Filter::Range { field, min, max } => indexes.get(field)?.range(*min, *max),
+            Filter::And(filters) => {
+                let mut sets: Vec<HashSet<Uuid>> = filters
+                    .iter()
+                    .filter_map(|filter| filter.candidates(indexes))
+                    .collect();
+                sets.sort_unstable_by_key(HashSet::len);
+                let mut sets = sets.into_iter();
+                let mut candidates = sets.next()?;
+                for set in sets {
+                    candidates.retain(|key| set.contains(key));
+                }
+                Some(candidates)
+            }
+        }
+    }
+}
+
+impl Collection {
+    /// Recherche parmi les documents dont la charge utile satisfait `filter`.
+    ///
+    /// Si des index de charge utile couvrent le filtre
+    /// ([`Collection::create_payload_index`]) et qu'ils estiment le filtre sélectif,
+    /// seuls les documents qu'ils désignent sont évalués ; sinon, la charge utile de
+    /// chaque document est vérifiée au fil du parcours (voir [`FilterStrategy`]). Le
+    /// résultat est le même dans les deux cas, et le plan retenu est joint dans
+    /// [`SearchResults::plan`].
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `filter` - Condition sur la charge utile.
+    /// * `params` - Paramètres de la recherche.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Documents retenus avec leur score.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search_with`].
+    pub fn search_filtered(
+        &self,
+        request: impl AsRef<[f32]>,
+        filter: &Filter,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        let request = request.as_ref();
+        self.check_zero(request, None)?;
+        let request = self.prepare_query(request)?;
+        let mut watch = Stopwatch::start(params);
+        let namespace = params.namespace.as_deref();
+        let plan = self.plan_filter(filter, params.filter_strategy);
+        let candidates = match plan.strategy {
+            FilterStrategy::IndexFirst => filter.candidates(&self.indexes),
+            _ => None,
+        };
+        let entries: Vec<(&Uuid, &[f32])> = match candidates {
+            Some(candidates) => candidates
+                .iter()
+                .filter(|key| namespace.is_none_or(|ns| self.namespaces.get(key) == Some(ns)))
+                .filter_map(|key| self.documents.get_key_value(key))
+                .map(|(key, vector)| (key, vector.as_slice()))
+                .collect(),
+            None => self.scoped_entries(namespace),
+        };
+        let entries: Vec<(&Uuid, &[f32])> = entries
+            .into_iter()
+            .filter(|(key, _)| filter.matches(self.payloads.get(key)))
+            .collect();
+        if let Some(watch) = &mut watch {
+            watch.timing.filter_us = watch.lap();
+        }
+        let mut results = self.search_entries(&request, &entries, params, &[], watch)?;
+        results.plan = Some(plan);
+        results.query_transforms = self.config.query_pipeline.names();
+        self.record_access(&results.hits);
+        Ok(results)
+    }
+
+    /// Choisit l'exécution d'une recherche filtrée selon `strategy`.
+    fn plan_filter(&self, filter: &Filter, strategy: FilterStrategy) -> FilterPlan {
+        let estimated_candidates = filter.estimate(&self.indexes);
+        let documents = self.documents.len();
+        let index_first = match (strategy, estimated_candidates) {
+            (_, None) | (FilterStrategy::ScanFirst, _) => false,
+            (FilterStrategy::IndexFirst, Some(_)) => true,
+            (FilterStrategy::Auto, Some(estimated)) => {
+                estimated as f64 <= INDEX_FIRST_MAX_SELECTIVITY * documents as f64
+            }
+        };
+        FilterPlan {
+            strategy: match index_first {
+                true => FilterStrategy::IndexFirst,
+                false => FilterStrategy::ScanFirst,
+            },
+            estimated_candidates,
+            documents,
+            forced: strategy != FilterStrategy::Auto,
+        }
+    }
+
+    /// Compte les documents dont la charge utile satisfait `filter`, en s'appuyant comme
+    /// [`Collection::search_filtered`] sur les index de charge utile qui le couvrent
+    /// lorsqu'ils l'estiment sélectif.
+    ///
+    /// Si les agrégats de la collection sont bruités ([`Collection::set_aggregate_noise`]),
+    /// le compte l'est ; [`Collection::count_by_filter_exact`] retourne le compte exact.
+    ///
+    /// # Arguments
+    /// * `filter` - Condition sur la charge utile.
+    ///
+    /// # Retourne
+    /// * usize - Nombre de documents, jamais négatif une fois bruité.
+    pub fn count_by_filter(&self, filter: &Filter) -> usize {
+        let count = self.count_by_filter_exact(filter);
+        match self.laplace() {
+            Some(mut laplace) => laplace.count(count as u64) as usize,
+            None => count,
+        }
+    }
+
+    /// Compte les documents dont la charge utile satisfait `filter` comme
+    /// [`Collection::count_by_filter`], toujours exactement, même si les agrégats de la
+    /// collection sont bruités.
+    ///
+    /// # Arguments
+    /// * `filter` - Condition sur la charge utile.
+    pub fn count_by_filter_exact(&self, filter: &Filter) -> usize {
+        let candidates = match self.plan_filter(filter, FilterStrategy::Auto).strategy {
+            FilterStrategy::IndexFirst => filter.candidates(&self.indexes),
+            _ => None,
+        };
+        match candidates {
+            Some(candidates) => candidates
+                .iter()
+                .filter(|key| self.documents.contains_key(key))
+                .filter(|key| filter.matches(self.payloads.get(key)))
+                .count(),
+            None => (self.documents.keys())
+                .filter(|key| filter.matches(self.payloads.get(key)))
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Document;
+    use crate::payload_index::IndexKind;
+    use crate::similarity::{self, Metric};
+    use crate::synthetic::VectorGenerator;
+
+    /// Collection de 1000 documents : `rare` vaut vrai pour 50 d'entre eux, `groupe`
+    /// vaut `a` pour 900, et `rang` est leur numéro.
+    fn collection() -> Collection {
+        let mut collection = Collection::new().with_metric(Metric::Euclidean);
+        let mut generator = VectorGenerator::new(8, 220);
+        for i in 0..1000i64 {
+            let mut payload = Payload::new();
+            payload.insert("rare".to_string(), Value::from(i % 20 == 0));
+            let group = if i % 10 == 0 { "b" } else { "a" };
+            payload.insert("groupe".to_string(), Value::from(group));
+            payload.insert("rang".to_string(), Value::from(i));
+            (collection)
+                .upsert_with_payload(generator.uuid(), generator.vector(), payload)
+                .unwrap();
+        }
+        collection.create_payload_index("rare", IndexKind::Keyword);
+        collection.create_payload_index("groupe", IndexKind::Keyword);
+        collection.create_payload_index("rang", IndexKind::Numeric);
+        collection
+    }
+
+    /// Les `k` documents satisfaisant `filter` les plus proches de `query`, par un
+    /// parcours exhaustif.
+    fn brute_force(collection: &Collection, query: &[f32], filter: &Filter, k: usize) -> Document {
+        let mut hits: Document = (collection.documents.iter())
+            .filter(|(key, _)| filter.matches(collection.payloads.get(key)))
+            .map(|(key, vector)| (*key, similarity::euclidean(query, vector).unwrap()))
+            .collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        hits.truncate(k);
+        hits
+    }
+
+    fn plan(strategy: FilterStrategy, estimated: Option<usize>, forced: bool) -> FilterPlan {
+        FilterPlan {
+            strategy,
+            estimated_candidates: estimated,
+            documents: 1000,
+            forced,
+        }
+    }
+
+    #[test]
+    fn selectivity_chooses_the_plan_without_changing_the_results() {
+        let collection = collection();
+        let query = VectorGenerator::new(8, 1).vector();
+        let cases = [
+            (Filter::eq("rare", true), plan(FilterStrategy::IndexFirst, Some(50), false)),
+            (Filter::eq("groupe", "a"), plan(FilterStrategy::ScanFirst, Some(900), false)),
+            (
+                Filter::range("rang", Some(100.0), Some(349.0)),
+                plan(FilterStrategy::IndexFirst, Some(250), false),
+            ),
+            (
+                Filter::and(vec![Filter::eq("groupe", "a"), Filter::eq("rare", true)]),
+                plan(FilterStrategy::IndexFirst, Some(50), false),
+            ),
+            (Filter::eq("absent", 1i64), plan(FilterStrategy::ScanFirst, None, false)),
+        ];
+        for (filter, expected) in cases {
+            let expected_hits = brute_force(&collection, &query, &filter, 10);
+            let results = (collection)
+                .search_filtered(&query, &filter, &SearchParams::new(10))
+                .unwrap();
+            assert_eq!(results.plan, Some(expected), "{:?}", filter);
+            assert_eq!(results.hits, expected_hits, "{:?}", filter);
+            for strategy in [FilterStrategy::IndexFirst, FilterStrategy::ScanFirst] {
+                let params = SearchParams {
+                    k: 10,
+                    filter_strategy: strategy,
+                    ..Default::default()
+                };
+                let forced = collection.search_filtered(&query, &filter, &params).unwrap();
+                assert_eq!(forced.hits, expected_hits, "{:?} {:?}", filter, strategy);
+                assert!(forced.plan.is_some_and(|plan| plan.forced));
+            }
+        }
+    }
+
+    #[test]
+    fn forced_plans_are_reported() {
+        let collection = collection();
+        let params = SearchParams {
+            k: 5,
+            filter_strategy: FilterStrategy::IndexFirst,
+            ..Default::default()
+        };
+        let filter = Filter::eq("groupe", "a");
+        let results = collection.search_filtered([0.0; 8], &filter, &params).unwrap();
+        let expected = plan(FilterStrategy::IndexFirst, Some(900), true);
+        assert_eq!(results.plan, Some(expected));
+        assert_eq!(
+            expected.to_string(),
+            "index d'abord (≈ 900 candidats sur 1000 documents, imposé)"
+        );
+        // Sans index, les index d'abord se replient sur le parcours.
+        let filter = Filter::eq("absent", 1i64);
+        let results = collection.search_filtered([0.0; 8], &filter, &params).unwrap();
+        let expected = plan(FilterStrategy::ScanFirst, None, true);
+        assert_eq!(results.plan, Some(expected));
+        assert_eq!(expected.to_string(), "parcours d'abord (aucun index, 1000 documents, imposé)");
+    }
+}
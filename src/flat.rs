@@ -0,0 +1,200 @@
+//! Échange des vecteurs d'une collection sous forme d'une matrice contiguë de `f32`,
+//! rangée ligne par ligne, avec le tableau des identifiants correspondants : le format
+//! attendu par FAISS ou par un tampon de carte graphique.
+
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::config::CollectionConfig;
+use crate::error::{Error, Result};
+use crate::loader::CollectionLoader;
+#[cfg(unix)]
+use crate::mmap::MmapCollection;
+
+/// Ordre des lignes de [`Collection::export_flat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportOrder {
+    /// Par `Uuid` croissant : deux exports d'une même collection sont identiques, et
+    /// l'ordre est celui de [`MmapCollection::as_flat_view`].
+    #[default]
+    Id,
+    /// Dans l'ordre de la table des documents, sans tri : plus rapide, mais l'ordre
+    /// change d'une collection à l'autre.
+    Storage,
+}
+
+impl Collection {
+    /// Copie les vecteurs stockés dans une matrice contiguë : la ligne `i` de la matrice
+    /// est le vecteur de l'identifiant `i`.
+    ///
+    /// Les vecteurs sont ceux que comparent les recherches, après la projection et la
+    /// normalisation éventuelles de la collection.
+    ///
+    /// # Arguments
+    /// * `order` - Ordre des lignes.
+    ///
+    /// # Retourne
+    /// * Result<(Vec<Uuid>, Vec<f32>, usize)> - Les identifiants, les coordonnées mises
+    ///   bout à bout et la dimension des lignes, 0 pour une collection vide.
+    ///
+    /// # Erreurs
+    /// * `Error::DimensionMismatch` - Si les documents n'ont pas tous la même dimension
+    ///   ([`Collection::validate`]).
+    pub fn export_flat(&self, order: ExportOrder) -> Result<(Vec<Uuid>, Vec<f32>, usize)> {
+        let report = self.validate();
+        if let Some((_, got)) = report.mismatched.first() {
+            return Err(Error::DimensionMismatch {
+                expected: report.dimension.unwrap_or(0),
+                got: *got,
+            });
+        }
+        let dim = report.dimension.unwrap_or(0);
+        let mut documents: Vec<(&Uuid, &Arc<Vec<f32>>)> = self.documents.iter().collect();
+        if order == ExportOrder::Id {
+            documents.sort_unstable_by_key(|(key, _)| **key);
+        }
+        let mut data = Vec::with_capacity(documents.len() * dim);
+        let ids = documents
+            .into_iter()
+            .map(|(key, vector)| {
+                data.extend_from_slice(vector);
+                *key
+            })
+            .collect();
+        Ok((ids, data, dim))
+    }
+
+    /// Construit une collection de configuration par défaut à partir d'une matrice
+    /// contiguë, par exemple obtenue de [`Collection::export_flat`], en passant par
+    /// [`CollectionLoader`] ; pour une autre configuration, utiliser directement
+    /// [`CollectionLoader::push_rows`].
+    ///
+    /// # Arguments
+    /// * `ids` - Identifiants des documents, un par ligne.
+    /// * `data` - Coordonnées des lignes, mises bout à bout.
+    /// * `dim` - Nombre de coordonnées par ligne.
+    ///
+    /// # Retourne
+    /// * Result<Collection> - La collection, prête pour les recherches.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `dim` est nul ou si `data` ne contient pas
+    ///   exactement `ids.len()` lignes.
+    /// * Celles de [`CollectionLoader::finish`].
+    pub fn import_flat(ids: &[Uuid], data: &[f32], dim: usize) -> Result<Collection> {
+        let mut loader = CollectionLoader::with_capacity(CollectionConfig::default(), ids.len());
+        loader.push_rows(ids, data, dim)?;
+        loader.finish()
+    }
+}
+
+#[cfg(unix)]
+impl MmapCollection {
+    /// Retourne les identifiants et les vecteurs de la collection tels qu'ils sont
+    /// projetés en mémoire, sans copie : la ligne `i` de la matrice, de
+    /// [`MmapCollection::dimension`] coordonnées, est le vecteur de l'identifiant `i`,
+    /// et les identifiants sont triés comme avec [`ExportOrder::Id`].
+    ///
+    /// # Retourne
+    /// * (&[Uuid], &[f32]) - Les identifiants et les coordonnées mises bout à bout.
+    pub fn as_flat_view(&self) -> (&[Uuid], &[f32]) {
+        (self.ids(), self.rows())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::VectorGenerator;
+    use crate::VectorStore;
+
+    fn collection(count: usize) -> Collection {
+        let mut collection = Collection::new();
+        let mut generator = VectorGenerator::new(5, 4);
+        for _ in 0..count {
+            collection.upsert(generator.uuid(), generator.vector()).unwrap();
+        }
+        collection
+    }
+
+    #[test]
+    fn rows_follow_their_ids() {
+        let collection = collection(100);
+        for order in [ExportOrder::Id, ExportOrder::Storage] {
+            let (ids, data, dim) = collection.export_flat(order).unwrap();
+            assert_eq!((ids.len(), data.len(), dim), (100, 500, 5));
+            for (id, row) in ids.iter().zip(data.chunks(dim)) {
+                assert_eq!(VectorStore::read(&collection, id), Some(row));
+            }
+        }
+        let (ids, _, _) = collection.export_flat(ExportOrder::Id).unwrap();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        // Le tri par identifiant ne dépend pas de l'ordre de la table.
+        let import = Collection::import_flat(&ids, &collection.export_flat(ExportOrder::Id).unwrap().1, 5);
+        assert_eq!(import.unwrap().export_flat(ExportOrder::Id).unwrap().0, ids);
+        assert_eq!(
+            Collection::new().export_flat(ExportOrder::Id).unwrap(),
+            (Vec::new(), Vec::new(), 0)
+        );
+    }
+
+    #[test]
+    fn exports_reflect_patches_and_stored_vectors() {
+        let mut collection = collection(10);
+        let (ids, _, _) = collection.export_flat(ExportOrder::Id).unwrap();
+        collection.patch_vector(ids[3], 1, &[7.0, 8.0]).unwrap();
+        let (_, data, dim) = collection.export_flat(ExportOrder::Id).unwrap();
+        assert_eq!(&data[3 * dim + 1..3 * dim + 3], [7.0, 8.0]);
+
+        // Une collection normalisée exporte les vecteurs normalisés.
+        let config = CollectionConfig::builder().normalized(true).build().unwrap();
+        let mut normalized = Collection::from_config(config);
+        normalized.upsert(Uuid::new_v4(), [3.0, 4.0]).unwrap();
+        assert_eq!(normalized.export_flat(ExportOrder::Id).unwrap().1, [0.6, 0.8]);
+    }
+
+    #[test]
+    fn imports_round_trip_and_check_lengths() {
+        let collection = collection(50);
+        let (ids, data, dim) = collection.export_flat(ExportOrder::Storage).unwrap();
+        let imported = Collection::import_flat(&ids, &data, dim).unwrap();
+        assert_eq!(imported.len(), 50);
+        assert_eq!(
+            imported.export_flat(ExportOrder::Id).unwrap(),
+            collection.export_flat(ExportOrder::Id).unwrap()
+        );
+        let query = [0.1, 0.2, 0.3, 0.4, 0.5];
+        assert_eq!(imported.search(query, 5).unwrap(), collection.search(query, 5).unwrap());
+
+        for (ids, data, dim) in [
+            (&ids[..], &data[..data.len() - 1], dim),
+            (&ids[..49], &data[..], dim),
+            (&ids[..], &data[..], 0),
+        ] {
+            assert!(matches!(
+                Collection::import_flat(ids, data, dim),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mapped_views_match_the_sorted_export() {
+        let collection = collection(40);
+        let path = std::env::temp_dir().join(format!("embeddingproject-flat-{}", Uuid::new_v4()));
+        MmapCollection::write(&collection, &path).unwrap();
+        let mapped = MmapCollection::open(&path).unwrap();
+        let (view_ids, view_data) = mapped.as_flat_view();
+        let (ids, data, dim) = collection.export_flat(ExportOrder::Id).unwrap();
+        assert_eq!(mapped.dimension(), dim);
+        assert_eq!((view_ids, view_data), (&ids[..], &data[..]));
+        // La vue pointe dans la projection du fichier : aucune copie.
+        let row = VectorStore::read(&mapped, &ids[7]).unwrap();
+        assert_eq!(row.as_ptr(), view_data[7 * dim..].as_ptr());
+        drop(mapped);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,175 @@
+//! Écriture et lecture des nombres à virgule flottante, communes à toutes les sorties.
+//!
+//! Les formats destinés aux programmes (JSON, CSV) utilisent l'écriture la plus courte
+//! qui se relit à l'identique, en notation scientifique hors de [1e-5, 1e16) ;
+//! l'affichage destiné aux personnes utilise un nombre fixe de chiffres significatifs.
+//! Le séparateur décimal est toujours le point, quelle que soit la langue du système.
+
+use std::fmt::{Display, LowerExp};
+
+use crate::error::{Error, Result};
+
+/// Nombre de chiffres significatifs des affichages, sauf précision explicite
+/// (`format!("{:.6}", results)`).
+pub const DISPLAY_DIGITS: usize = 4;
+
+/// Écrit `x` sous sa forme la plus courte qui se relit à l'identique en `f32`.
+///
+/// Les valeurs non finies sont écrites `NaN`, `inf` et `-inf` ; JSON et CSV les
+/// remplacent avant d'appeler cette fonction.
+pub fn shortest_f32(x: f32) -> String {
+    shortest(x, f64::from(x.abs()))
+}
+
+/// Écrit `x` sous sa forme la plus courte qui se relit à l'identique en `f64`.
+pub fn shortest_f64(x: f64) -> String {
+    shortest(x, x.abs())
+}
+
+fn shortest<T: Display + LowerExp>(x: T, magnitude: f64) -> String {
+    if magnitude == 0.0 || !magnitude.is_finite() || (1e-5..1e16).contains(&magnitude) {
+        format!("{}", x)
+    } else {
+        format!("{:e}", x)
+    }
+}
+
+/// Écrit `x` avec `digits` chiffres significatifs, pour l'affichage.
+///
+/// La notation décimale est utilisée tant qu'elle n'exige pas plus de chiffres que
+/// `digits` avant la virgule ni plus de quatre zéros après : `0.9312`, `12.35`,
+/// `1.235e5`, `1.235e-6`.
+///
+/// # Arguments
+/// * `x` - Nombre à afficher.
+/// * `digits` - Nombre de chiffres significatifs, au moins 1.
+pub fn significant(x: f64, digits: usize) -> String {
+    if !x.is_finite() {
+        return x.to_string();
+    }
+    let precision = digits.max(1) - 1;
+    let scientific = format!("{:.*e}", precision, x);
+    // L'exposant est lu après arrondi : 9.9996 à 4 chiffres donne 1.000e1.
+    let exponent: i32 = scientific
+        .rsplit('e')
+        .next()
+        .and_then(|exponent| exponent.parse().ok())
+        .unwrap_or(0);
+    if x != 0.0 && (exponent < -5 || exponent > precision as i32) {
+        return scientific;
+    }
+    let decimals = (precision as i32 - exponent).max(0) as usize;
+    format!("{:.*}", decimals, x)
+}
+
+/// Lit un nombre `f32` écrit avec un point décimal, éventuellement en notation
+/// scientifique : `0.5`, `-.5`, `+2`, `1e-3`, `1.5E+2`. Les espaces autour du nombre
+/// sont ignorés.
+///
+/// # Arguments
+/// * `token` - Texte à lire.
+/// * `line` - Numéro de la ligne (à partir de 1) d'où vient le texte, rapporté dans
+///   l'erreur.
+///
+/// # Erreurs
+/// * `Error::Parse` - Si le texte n'est pas un nombre fini représentable en `f32` ; le
+///   message cite le texte fautif et, pour une virgule décimale, l'écriture attendue.
+pub fn parse_f32(token: &str, line: usize) -> Result<f32> {
+    let text = token.trim();
+    let error = |message: String| Error::Parse { line, message };
+    if text.is_empty() {
+        return Err(error("nombre attendu, texte vide".to_string()));
+    }
+    if text.contains(',') {
+        return Err(error(format!(
+            "« {} » : le séparateur décimal est le point, écrivez « {} »",
+            text,
+            text.replace(',', ".")
+        )));
+    }
+    match text.parse::<f32>() {
+        Ok(x) if x.is_finite() => Ok(x),
+        Ok(_) => Err(error(format!(
+            "« {} » n'est pas un nombre fini représentable en f32",
+            text
+        ))),
+        Err(_) => Err(error(format!("« {} » n'est pas un nombre", text))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::VectorGenerator;
+
+    /// Valeurs limites et ordinaires, des sous-normaux aux plus grands `f32`.
+    fn samples() -> Vec<f32> {
+        let mut generator = VectorGenerator::new(1, 150);
+        let mut values = vec![
+            0.0,
+            -0.0,
+            0.5,
+            -0.1,
+            1.0 / 3.0,
+            1e-5,
+            9.99e-6,
+            1e16,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            f32::EPSILON,
+            1e-45,
+        ];
+        for scale in [1e-8f32, 1e-3, 1.0, 1e4, 1e12, 1e20] {
+            values.extend((0..20).map(|_| generator.vector()[0] * scale));
+        }
+        values
+    }
+
+    #[test]
+    fn shortest_forms_read_back_exactly() {
+        for x in samples() {
+            let text = shortest_f32(x);
+            assert_eq!(parse_f32(&text, 1).unwrap().to_bits(), x.to_bits(), "{}", text);
+            assert!(!text.contains(','), "{}", text);
+            let wide = f64::from(x) * 1.000_000_1;
+            assert_eq!(shortest_f64(wide).parse::<f64>().unwrap(), wide);
+        }
+        assert_eq!(shortest_f32(0.5), "0.5");
+        assert_eq!(shortest_f32(1e-6), "1e-6");
+        assert_eq!(shortest_f32(2e16), "2e16");
+        assert_eq!(shortest_f32(12345.0), "12345");
+    }
+
+    #[test]
+    fn displayed_values_read_back_within_their_precision() {
+        for x in samples() {
+            for digits in [1, 4, 6] {
+                let text = significant(f64::from(x), digits);
+                let read: f64 = text.parse().unwrap();
+                let tolerance = f64::from(x.abs()) * 10f64.powi(1 - digits as i32);
+                assert!((read - f64::from(x)).abs() <= tolerance, "{} -> {}", x, text);
+            }
+        }
+        assert_eq!(significant(0.931_234, 4), "0.9312");
+        assert_eq!(significant(12.345_6, 4), "12.35");
+        assert_eq!(significant(123_456.0, 4), "1.235e5");
+        assert_eq!(significant(0.000_001_234_5, 4), "1.234e-6");
+        assert_eq!(significant(9.9996, 4), "10.00");
+        assert_eq!(significant(0.0, 4), "0.000");
+    }
+
+    #[test]
+    fn parsing_accepts_both_notations_and_points_at_commas() {
+        for (text, expected) in [("0.5", 0.5), (" -.5 ", -0.5), ("+2", 2.0), ("1e-3", 1e-3), ("1.5E+2", 150.0)] {
+            assert_eq!(parse_f32(text, 1).unwrap(), expected, "{}", text);
+        }
+        let Err(Error::Parse { line, message }) = parse_f32("0,25", 7) else {
+            panic!("virgule acceptée");
+        };
+        assert_eq!(line, 7);
+        assert!(message.contains("« 0,25 »") && message.contains("« 0.25 »"), "{}", message);
+        for text in ["", "abc", "1e39", "NaN", "inf"] {
+            assert!(matches!(parse_f32(text, 3), Err(Error::Parse { line: 3, .. })), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Fusion des résultats de recherches dans plusieurs collections.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::collection::Document;
+use crate::database::{self, BaseDeDonnees};
+use crate::error::{Error, Result};
+
+/// Constante de la fusion par rangs réciproques : le document de rang `r` (à partir de
+/// 1) d'une liste y contribue `poids / (RRF_RANK_CONSTANT + r)`.
+pub const RRF_RANK_CONSTANT: f32 = 60.0;
+
+/// Combinaison des listes de résultats de [`BaseDeDonnees::search_fused`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FusionMethod {
+    /// Fusion par rangs réciproques : seuls les rangs comptent, pas les scores, ce qui
+    /// rend comparables des collections de mesures ou d'échelles différentes.
+    #[default]
+    Rrf,
+    /// Somme pondérée des scores, ramenés dans chaque liste à [0, 1] entre le moins bon
+    /// (0) et le meilleur (1) ; une liste dont tous les scores sont égaux vaut 1.
+    WeightedScore,
+}
+
+/// Contribution d'une collection à un résultat fusionné.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusedSource {
+    /// Nom de la collection, tel que donné dans la requête.
+    pub collection: String,
+    /// Rang du document dans les résultats de la collection, à partir de 0.
+    pub rank: usize,
+    /// Score du document dans la collection, selon sa mesure.
+    pub score: f32,
+}
+
+/// Résultat de [`BaseDeDonnees::search_fused`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusedHit {
+    /// Identifiant du document.
+    pub key: Uuid,
+    /// Score fusionné ; plus il est grand, meilleur est le document.
+    pub score: f32,
+    /// Collections qui ont retourné le document, dans l'ordre des requêtes.
+    pub sources: Vec<FusedSource>,
+}
+
+impl BaseDeDonnees {
+    /// Recherche dans plusieurs collections, en parallèle, et fusionne leurs résultats
+    /// en un seul classement.
+    ///
+    /// Chaque requête n'est vérifiée que contre sa propre collection : les collections
+    /// peuvent avoir des dimensions et des mesures différentes. Chacune contribue ses
+    /// `k` meilleurs documents ; un document retourné par plusieurs collections, sous le
+    /// même `Uuid`, n'apparaît qu'une fois, avec la somme de ses contributions. Les
+    /// égalités de score fusionné sont départagées par `Uuid` croissant.
+    ///
+    /// # Arguments
+    /// * `queries` - Nom de la collection, vecteur de requête et poids de chaque liste.
+    /// * `k` - Nombre maximal de résultats, et de documents retenus par collection.
+    /// * `method` - Combinaison des listes.
+    ///
+    /// # Retourne
+    /// * Result<Vec<FusedHit>> - Documents retenus, du meilleur au moins bon.
+    ///
+    /// # Erreurs
+    /// * `Error::EmptyQuery` - Si `queries` est vide.
+    /// * `Error::InvalidConfig` - Si un poids n'est pas fini et positif ou nul.
+    /// * Celles de [`BaseDeDonnees::search`], pour la première requête refusée.
+    pub fn search_fused(
+        &self,
+        queries: &[(&str, &[f32], f32)],
+        k: usize,
+        method: FusionMethod,
+    ) -> Result<Vec<FusedHit>> {
+        if queries.is_empty() {
+            return Err(Error::EmptyQuery);
+        }
+        if let Some((cname, _, weight)) =
+            (queries.iter()).find(|(_, _, w)| !w.is_finite() || *w < 0.0)
+        {
+            return Err(Error::InvalidConfig(format!(
+                "le poids de la collection '{}' vaut {} ; il doit être fini et positif ou nul",
+                cname, weight
+            )));
+        }
+        let lists = self.runtime.map_chunks(queries, 2, |chunk| {
+            (chunk.iter())
+                .map(|(cname, query, _)| database::search(self.store(cname), cname, query, k))
+                .collect()
+        });
+        let mut fused: HashMap<Uuid, FusedHit> = HashMap::new();
+        for ((cname, _, weight), list) in queries.iter().zip(lists) {
+            let hits = list?;
+            let contributions = contributions(&hits, method);
+            for (rank, ((key, score), contribution)) in hits.iter().zip(contributions).enumerate() {
+                let hit = fused.entry(*key).or_insert_with(|| FusedHit {
+                    key: *key,
+                    score: 0.0,
+                    sources: Vec::new(),
+                });
+                hit.score += weight * contribution;
+                hit.sources.push(FusedSource {
+                    collection: cname.to_string(),
+                    rank,
+                    score: *score,
+                });
+            }
+        }
+        let mut hits: Vec<FusedHit> = fused.into_values().collect();
+        hits.sort_unstable_by(|a, b| {
+            (b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        hits.truncate(k);
+        Ok(hits)
+    }
+}
+
+/// Contribution, avant pondération, de chaque document d'une liste classée.
+fn contributions(hits: &Document, method: FusionMethod) -> Vec<f32> {
+    match method {
+        FusionMethod::Rrf => (1..=hits.len())
+            .map(|rank| 1.0 / (RRF_RANK_CONSTANT + rank as f32))
+            .collect(),
+        FusionMethod::WeightedScore => {
+            // La liste est classée : le meilleur score est en tête, le moins bon en queue,
+            // et le rapport vaut 1 pour le meilleur quel que soit le sens de la mesure.
+            let (Some((_, best)), Some((_, worst))) = (hits.first(), hits.last()) else {
+                return Vec::new();
+            };
+            let range = best - worst;
+            (hits.iter())
+                .map(|(_, score)| match range != 0.0 && range.is_finite() {
+                    true => (score - worst) / range,
+                    false => 1.0,
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::similarity::Metric;
+
+    /// Base dont `B` est deuxième dans `titres` comme dans `corps`, `A` premier dans
+    /// `titres` seulement et `C` premier dans `corps` seulement.
+    fn database() -> (BaseDeDonnees, [Uuid; 3]) {
+        let [a, b, c] = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("titres".to_string(), Collection::new().with_metric(Metric::Dot));
+        bdd.replace("corps".to_string(), Collection::new().with_metric(Metric::Dot));
+        let titres = bdd.get_mut("titres").unwrap();
+        titres.upsert(a, [1.0, 0.0]).unwrap();
+        titres.upsert(b, [0.9, 0.0]).unwrap();
+        titres.upsert(Uuid::new_v4(), [0.1, 0.0]).unwrap();
+        let corps = bdd.get_mut("corps").unwrap();
+        corps.upsert(c, [0.0, 0.0, 1.0]).unwrap();
+        corps.upsert(b, [0.0, 0.0, 0.9]).unwrap();
+        corps.upsert(Uuid::new_v4(), [0.0, 0.0, 0.1]).unwrap();
+        (bdd, [a, b, c])
+    }
+
+    #[test]
+    fn a_document_second_in_both_lists_comes_first() {
+        let (bdd, [a, b, c]) = database();
+        let queries: [(&str, &[f32], f32); 2] =
+            [("titres", &[1.0, 0.0], 1.0), ("corps", &[0.0, 0.0, 1.0], 1.0)];
+        for method in [FusionMethod::Rrf, FusionMethod::WeightedScore] {
+            let hits = bdd.search_fused(&queries, 3, method).unwrap();
+            assert_eq!(hits.len(), 3);
+            assert_eq!(hits[0].key, b, "{:?}", method);
+            let sources: Vec<(&str, usize)> = (hits[0].sources.iter())
+                .map(|source| (source.collection.as_str(), source.rank))
+                .collect();
+            assert_eq!(sources, [("titres", 1), ("corps", 1)]);
+            let rest = [hits[1].key, hits[2].key];
+            assert!(rest.contains(&a) && rest.contains(&c), "{:?}", method);
+        }
+        // Le poids d'une liste peut la faire l'emporter.
+        let weighted: [(&str, &[f32], f32); 2] =
+            [("titres", &[1.0, 0.0], 3.0), ("corps", &[0.0, 0.0, 1.0], 1.0)];
+        let hits = bdd.search_fused(&weighted, 1, FusionMethod::Rrf).unwrap();
+        assert_eq!(hits[0].key, a);
+    }
+
+    #[test]
+    fn each_query_is_checked_against_its_own_collection() {
+        let (bdd, _) = database();
+        let swapped: [(&str, &[f32], f32); 2] =
+            [("titres", &[0.0, 0.0, 1.0], 1.0), ("corps", &[1.0, 0.0], 1.0)];
+        let error = bdd.search_fused(&swapped, 3, FusionMethod::Rrf).unwrap_err();
+        assert!(matches!(error.root(), Error::DimensionMismatch { .. }), "{}", error);
+        let negative: [(&str, &[f32], f32); 1] = [("titres", &[1.0, 0.0], -1.0)];
+        assert!(matches!(
+            bdd.search_fused(&negative, 3, FusionMethod::Rrf),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            bdd.search_fused(&[], 3, FusionMethod::Rrf),
+            Err(Error::EmptyQuery)
+        ));
+    }
+}
@@ -0,0 +1,397 @@
+//! Classements de référence sur des données générées, pour détecter un changement des
+//! résultats de recherche lors d'une modification du calcul des scores.
+//!
+//! [`Goldens::generate`] construit, à partir d'une graine fixe, une collection par mesure
+//! et les classements de quelques requêtes ; [`Goldens::compare`] les confronte à des
+//! classements enregistrés. Le binaire `regenerate-goldens` réécrit le fichier de
+//! référence `goldens/rankings.json` après un changement voulu, et le vérifie avec
+//! `--check` ; `cargo test` le vérifie aussi.
+//!
+//! Les identifiants générés ne dépendent que de la graine et les égalités de score sont
+//! départagées par identifiant croissant : deux exécutions produisent les mêmes
+//! classements. Les recherches utilisent le calcul déterministe
+//! ([`SearchParams::deterministic_math`]) : sur toutes les plateformes, les scores sont
+//! identiques bit à bit, et `--check --exact` les compare sans tolérance. Ils peuvent
+//! en revanche varier de quelques ulps d'une implémentation des mesures à l'autre ; la
+//! comparaison par défaut les tolère à `epsilon` près.
+
+use std::collections::BTreeMap;
+
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::config::CollectionConfig;
+use crate::database_config::metric_name;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::payload::Value;
+use crate::search::SearchParams;
+use crate::similarity::Metric;
+use crate::synthetic::VectorGenerator;
+
+/// Graine des données de [`Goldens::generate`].
+pub const GOLDEN_SEED: u64 = 20_240_501;
+
+/// Écart de score toléré par défaut entre un classement et sa référence.
+pub const DEFAULT_GOLDEN_EPSILON: f32 = 1e-5;
+
+/// Nombre de documents de chaque collection de référence.
+const DOCUMENTS: usize = 500;
+
+/// Dimension des vecteurs de référence.
+const DIMENSION: usize = 16;
+
+/// Nombre de requêtes par mesure.
+const QUERIES: usize = 8;
+
+/// Nombre de résultats de chaque classement.
+const K: usize = 10;
+
+/// Mesures couvertes par les classements de référence.
+const METRICS: [Metric; 4] = [
+    Metric::Cosine,
+    Metric::Dot,
+    Metric::Euclidean,
+    Metric::Manhattan,
+];
+
+/// Classement d'une requête de référence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ranking {
+    /// Mesure de la collection interrogée.
+    pub metric: Metric,
+    /// Rang de la requête parmi celles générées.
+    pub query: usize,
+    /// Résultats, du meilleur au moins bon.
+    pub hits: Document,
+}
+
+/// Ensemble des classements de référence, avec les paramètres des données dont ils
+/// sont issus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goldens {
+    /// Graine du générateur.
+    pub seed: u64,
+    /// Nombre de documents de chaque collection.
+    pub documents: usize,
+    /// Dimension des vecteurs.
+    pub dimension: usize,
+    /// Nombre de résultats de chaque classement.
+    pub k: usize,
+    /// Classements, par mesure puis par requête.
+    pub rankings: Vec<Ranking>,
+}
+
+impl Goldens {
+    /// Calcule les classements de référence à partir de [`GOLDEN_SEED`].
+    ///
+    /// Les mêmes documents sont insérés dans une collection par mesure ; la moitié des
+    /// requêtes sont des documents de la collection, l'autre des vecteurs indépendants.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert`] et de [`Collection::search_with`].
+    pub fn generate() -> Result<Goldens> {
+        let mut generator = VectorGenerator::new(DIMENSION, GOLDEN_SEED);
+        let documents: Vec<(Uuid, Vec<f32>)> = (0..DOCUMENTS)
+            .map(|_| (generator.uuid(), generator.vector()))
+            .collect();
+        let queries: Vec<Vec<f32>> = (0..QUERIES)
+            .map(|i| match i % 2 {
+                0 => documents[i * DOCUMENTS / QUERIES].1.clone(),
+                _ => generator.vector(),
+            })
+            .collect();
+        let params = SearchParams {
+            k: K,
+            deterministic_math: true,
+            ..Default::default()
+        };
+        let mut rankings = Vec::with_capacity(METRICS.len() * QUERIES);
+        for metric in METRICS {
+            let config = CollectionConfig::builder().metric(metric).build()?;
+            let mut collection = Collection::from_config(config);
+            collection.upsert_batch(documents.iter().cloned(), None)?;
+            for (query, vector) in queries.iter().enumerate() {
+                rankings.push(Ranking {
+                    metric,
+                    query,
+                    hits: collection.search_with(vector, &params)?.hits,
+                });
+            }
+        }
+        Ok(Goldens {
+            seed: GOLDEN_SEED,
+            documents: DOCUMENTS,
+            dimension: DIMENSION,
+            k: K,
+            rankings,
+        })
+    }
+
+    /// Écrit les classements en JSON, un classement par ligne pour que les différences
+    /// restent lisibles.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"seed\":");
+        json::write_u64(&mut out, self.seed);
+        out.push_str(",\"documents\":");
+        json::write_u64(&mut out, self.documents as u64);
+        out.push_str(",\"dimension\":");
+        json::write_u64(&mut out, self.dimension as u64);
+        out.push_str(",\"k\":");
+        json::write_u64(&mut out, self.k as u64);
+        out.push_str(",\"rankings\":[");
+        for (i, ranking) in self.rankings.iter().enumerate() {
+            out.push_str(if i > 0 { ",\n" } else { "\n" });
+            out.push_str("{\"metric\":");
+            json::write_string(&mut out, metric_name(ranking.metric));
+            out.push_str(",\"query\":");
+            json::write_u64(&mut out, ranking.query as u64);
+            out.push_str(",\"hits\":");
+            json::write_array(&mut out, &ranking.hits, |out, (key, score)| {
+                out.push('[');
+                json::write_string(out, &key.to_string());
+                out.push(',');
+                json::write_f32(out, *score);
+                out.push(']');
+            });
+            out.push('}');
+        }
+        out.push_str("\n]}\n");
+        out
+    }
+
+    /// Lit des classements écrits par [`Goldens::to_json`].
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si le texte n'est pas du JSON ou n'a pas la structure
+    ///   attendue.
+    pub fn from_json(text: &str) -> Result<Goldens> {
+        let value = json::parse(text).map_err(|message| invalid(&message))?;
+        let fields = object(&value)?;
+        let rankings = match fields.get("rankings") {
+            Some(Value::Array(items)) => items.iter().map(ranking).collect::<Result<_>>()?,
+            _ => return Err(invalid("« rankings » manquant")),
+        };
+        Ok(Goldens {
+            seed: integer(fields, "seed")?,
+            documents: integer(fields, "documents")? as usize,
+            dimension: integer(fields, "dimension")? as usize,
+            k: integer(fields, "k")? as usize,
+            rankings,
+        })
+    }
+
+    /// Confronte ces classements à leur référence `expected`.
+    ///
+    /// Un résultat correspond à sa référence si, au même rang, il a le même identifiant
+    /// et un score égal à `epsilon` près, relativement au score de référence quand
+    /// celui-ci dépasse 1 en valeur absolue. Deux documents dont les scores de référence
+    /// sont à `epsilon` l'un de l'autre peuvent échanger leurs rangs : l'écart de calcul
+    /// toléré suffit à inverser leur ordre.
+    ///
+    /// # Arguments
+    /// * `expected` - Classements de référence.
+    /// * `epsilon` - Écart de score toléré, par exemple [`DEFAULT_GOLDEN_EPSILON`] ; 0 exige
+    ///   des scores identiques.
+    ///
+    /// # Retourne
+    /// * Vec<String> - Une description par différence, vide si les classements
+    ///   correspondent.
+    pub fn compare(&self, expected: &Goldens, epsilon: f32) -> Vec<String> {
+        let mut differences = Vec::new();
+        let parameters = |g: &Goldens| (g.seed, g.documents, g.dimension, g.k);
+        if parameters(self) != parameters(expected) {
+            differences.push(format!(
+                "données différentes : graine, documents, dimension, k = {:?} au lieu de {:?}",
+                parameters(self),
+                parameters(expected)
+            ));
+            return differences;
+        }
+        let index: BTreeMap<(&str, usize), &Ranking> = (expected.rankings.iter())
+            .map(|ranking| ((metric_name(ranking.metric), ranking.query), ranking))
+            .collect();
+        for ranking in &self.rankings {
+            let name = metric_name(ranking.metric);
+            let Some(reference) = index.get(&(name, ranking.query)) else {
+                differences.push(format!(
+                    "{} requête {} : absente de la référence",
+                    name, ranking.query
+                ));
+                continue;
+            };
+            compare_hits(name, ranking.query, &ranking.hits, &reference.hits, epsilon)
+                .into_iter()
+                .for_each(|difference| differences.push(difference));
+        }
+        if self.rankings.len() != expected.rankings.len() {
+            differences.push(format!(
+                "{} classements au lieu de {}",
+                self.rankings.len(),
+                expected.rankings.len()
+            ));
+        }
+        differences
+    }
+}
+
+/// Confronte les résultats d'une requête à leur référence, rang par rang.
+fn compare_hits(
+    metric: &str,
+    query: usize,
+    hits: &Document,
+    expected: &Document,
+    epsilon: f32,
+) -> Vec<String> {
+    let mut differences = Vec::new();
+    if hits.len() != expected.len() {
+        differences.push(format!(
+            "{} requête {} : {} résultats au lieu de {}",
+            metric,
+            query,
+            hits.len(),
+            expected.len()
+        ));
+    }
+    for (rank, ((key, score), (expected_key, expected_score))) in
+        hits.iter().zip(expected).enumerate()
+    {
+        if key == expected_key && close(*score, *expected_score, epsilon) {
+            continue;
+        }
+        // Un échange de rangs est admis entre documents quasi ex æquo dans la référence.
+        let swapped = key != expected_key
+            && expected.iter().any(|(other, other_score)| {
+                other == key
+                    && close(*other_score, *expected_score, epsilon)
+                    && close(*score, *other_score, epsilon)
+            });
+        if !swapped {
+            differences.push(format!(
+                "{} requête {} rang {} : {} ({}) au lieu de {} ({})",
+                metric, query, rank, key, score, expected_key, expected_score
+            ));
+        }
+    }
+    differences
+}
+
+/// Indique si `score` vaut `expected` à `epsilon` près, en relatif au-delà de 1.
+fn close(score: f32, expected: f32, epsilon: f32) -> bool {
+    (score - expected).abs() <= epsilon * expected.abs().max(1.0)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidConfig(format!("classements de référence : {}", message))
+}
+
+fn object(value: &Value) -> Result<&BTreeMap<String, Value>> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(invalid("objet attendu")),
+    }
+}
+
+fn integer(fields: &BTreeMap<String, Value>, key: &str) -> Result<u64> {
+    match fields.get(key) {
+        Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as u64),
+        _ => Err(invalid(&format!("entier « {} » attendu", key))),
+    }
+}
+
+fn ranking(value: &Value) -> Result<Ranking> {
+    let fields = object(value)?;
+    let metric = match fields.get("metric") {
+        Some(Value::String(name)) => METRICS.into_iter().find(|m| metric_name(*m) == name),
+        _ => None,
+    }
+    .ok_or_else(|| invalid("mesure inconnue"))?;
+    let hits = match fields.get("hits") {
+        Some(Value::Array(items)) => items.iter().map(hit).collect::<Result<_>>()?,
+        _ => return Err(invalid("« hits » manquant")),
+    };
+    Ok(Ranking {
+        metric,
+        query: integer(fields, "query")? as usize,
+        hits,
+    })
+}
+
+fn hit(value: &Value) -> Result<(Uuid, f32)> {
+    match value {
+        Value::Array(pair) => match pair.as_slice() {
+            [Value::String(key), Value::Number(score)] => {
+                let key = Uuid::parse_str(key).map_err(|_| invalid("identifiant invalide"))?;
+                Ok((key, *score as f32))
+            }
+            _ => Err(invalid("résultat [identifiant, score] attendu")),
+        },
+        _ => Err(invalid("résultat [identifiant, score] attendu")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Classements enregistrés dans le dépôt.
+    const CHECKED_IN: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/goldens/rankings.json"));
+
+    #[test]
+    fn rankings_match_the_checked_in_goldens() {
+        let expected = Goldens::from_json(CHECKED_IN).unwrap();
+        let differences = Goldens::generate().unwrap().compare(&expected, DEFAULT_GOLDEN_EPSILON);
+        assert!(
+            differences.is_empty(),
+            "classements différents de goldens/rankings.json (`cargo run --bin \
+             regenerate-goldens` après un changement voulu) :\n{}",
+            differences.join("\n")
+        );
+    }
+
+    #[test]
+    fn json_round_trips_exactly() {
+        let goldens = Goldens::generate().unwrap();
+        let read = Goldens::from_json(&goldens.to_json()).unwrap();
+        assert_eq!(read, goldens);
+        assert!(goldens.compare(&read, 0.0).is_empty());
+    }
+
+    #[test]
+    fn changed_rankings_are_reported() {
+        let expected = Goldens::from_json(CHECKED_IN).unwrap();
+
+        let mut jittered = expected.clone();
+        jittered.rankings[0].hits[3].1 += DEFAULT_GOLDEN_EPSILON / 2.0;
+        assert!(jittered.compare(&expected, DEFAULT_GOLDEN_EPSILON).is_empty());
+        assert_eq!(jittered.compare(&expected, 0.0).len(), 1);
+
+        let mut rescored = expected.clone();
+        rescored.rankings[1].hits[0].1 -= 0.01;
+        assert_eq!(rescored.compare(&expected, DEFAULT_GOLDEN_EPSILON).len(), 1);
+
+        // Le premier résultat d'une requête tirée de la collection est le document
+        // lui-même : il n'est à égalité avec aucun autre.
+        let mut reordered = expected.clone();
+        reordered.rankings[0].hits.swap(0, 1);
+        assert_eq!(reordered.compare(&expected, DEFAULT_GOLDEN_EPSILON).len(), 2);
+
+        let mut truncated = expected.clone();
+        truncated.rankings[2].hits.pop();
+        truncated.rankings.pop();
+        assert_eq!(truncated.compare(&expected, DEFAULT_GOLDEN_EPSILON).len(), 2);
+
+        let reseeded = Goldens {
+            seed: expected.seed + 1,
+            ..expected.clone()
+        };
+        assert_eq!(reseeded.compare(&expected, DEFAULT_GOLDEN_EPSILON).len(), 1);
+    }
+
+    #[test]
+    fn malformed_goldens_are_refused() {
+        for text in ["", "[]", "{\"seed\": 1}", "{\"seed\":1,\"documents\":1,\"dimension\":1,\"k\":1,\"rankings\":[{\"metric\":\"inconnue\",\"query\":0,\"hits\":[]}]}"] {
+            assert!(matches!(Goldens::from_json(text), Err(Error::InvalidConfig(_))), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::error::Result;
+
+/// Nombre de documents à partir duquel le graphe est calculé sur plusieurs threads.
+///
+/// Chaque document coûte un parcours complet de la collection : le seuil est donc bien
+/// plus bas que pour une recherche.
+pub(crate) const GRAPH_PARALLEL_THRESHOLD: usize = 64;
+
+impl Collection {
+    /// Construit le graphe des `k` plus proches voisins de la collection.
+    ///
+    /// Chaque document est comparé à tous les autres documents de même dimension selon la
+    /// mesure de la collection, lui-même exclu. Le calcul est exact mais coûte O(n² × d) ;
+    /// il est réparti sur les threads du réglage de la collection
+    /// ([`Collection::with_runtime`]), document par document. Les égalités de
+    /// score sont départagées par `Uuid` croissant.
+    ///
+    /// Les collections ne disposent pas encore d'index approché : le graphe retourné est
+    /// donc toujours exact, au prix de ce coût quadratique.
+    ///
+    /// # Arguments
+    /// * `k` - Nombre de voisins par document.
+    ///
+    /// # Retourne
+    /// * HashMap<Uuid, Document> - Pour chaque document, ses voisins du plus proche au moins proche.
+    pub fn knn_graph(&self, k: usize) -> HashMap<Uuid, Document> {
+        let entries: Vec<(&Uuid, &Arc<Vec<f32>>)> = self.documents.iter().collect();
+        let scorer = self.scorer();
+        let metric = scorer.metric;
+        let entries = &entries;
+        self.runtime
+            .map_chunks(entries, GRAPH_PARALLEL_THRESHOLD, |chunk| {
+                chunk
+                    .iter()
+                    .map(|(key, vector)| {
+                        let mut neighbors: Document = entries
+                            .iter()
+                            .filter(|(other, candidate)| {
+                                other != key && candidate.len() == vector.len()
+                            })
+                            .map(|(other, candidate)| {
+                                (**other, scorer.score_unchecked(vector, candidate))
+                            })
+                            .collect();
+                        neighbors
+                            .sort_by(|a, b| metric.compare(a.1, b.1).then_with(|| a.0.cmp(&b.0)));
+                        neighbors.truncate(k);
+                        (**key, neighbors)
+                    })
+                    .collect()
+            })
+            .into_iter()
+            .collect()
+    }
+
+    /// Écrit le graphe des `k` plus proches voisins dans un fichier CSV.
+    ///
+    /// Le fichier commence par l'en-tête `source,target,score`, suivi d'une ligne par arête,
+    /// les sources triées par `Uuid` et leurs voisins du plus proche au moins proche.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier à créer ou écraser.
+    /// * `k` - Nombre de voisins par document.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être écrit.
+    pub fn export_knn_graph_csv(&self, path: impl AsRef<Path>, k: usize) -> Result<()> {
+        let mut graph: Vec<(Uuid, Document)> = self.knn_graph(k).into_iter().collect();
+        graph.sort_unstable_by_key(|(key, _)| *key);
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "source,target,score")?;
+        for (source, neighbors) in graph {
+            for (target, score) in neighbors {
+                writeln!(out, "{},{},{}", source, target, score)?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+    use crate::parallel::SearchRuntime;
+    use crate::similarity::Metric;
+    use crate::synthetic::VectorGenerator;
+
+    fn euclidean() -> Collection {
+        let config = CollectionConfig::builder().metric(Metric::Euclidean).build().unwrap();
+        Collection::from_config(config)
+    }
+
+    #[test]
+    fn neighbors_match_hand_computed_distances() {
+        // Quatre points d'une droite, aux abscisses 0, 1, 3 et 7.
+        let mut collection = euclidean();
+        let keys: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for (key, x) in keys.iter().zip([0.0, 1.0, 3.0, 7.0]) {
+            collection.upsert(*key, [x, 0.0]).unwrap();
+        }
+        let [a, b, c, d] = [keys[0], keys[1], keys[2], keys[3]];
+        let graph = collection.knn_graph(2);
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph[&a], [(b, 1.0), (c, 3.0)]);
+        assert_eq!(graph[&b], [(a, 1.0), (c, 2.0)]);
+        assert_eq!(graph[&c], [(b, 2.0), (a, 3.0)]);
+        assert_eq!(graph[&d], [(c, 4.0), (b, 6.0)]);
+        // Un document n'a jamais que les autres pour voisins.
+        assert_eq!(collection.knn_graph(10)[&a].len(), 3);
+    }
+
+    #[test]
+    fn parallel_graph_matches_a_single_thread() {
+        let mut collection = euclidean().with_runtime(SearchRuntime::new(1));
+        VectorGenerator::new(4, 6).fill(&mut collection, 3 * GRAPH_PARALLEL_THRESHOLD);
+        let serial = collection.knn_graph(5);
+        let parallel = collection.clone().with_runtime(SearchRuntime::new(4)).knn_graph(5);
+        assert_eq!(parallel, serial);
+    }
+
+    #[test]
+    fn csv_export_lists_one_edge_per_line() {
+        let mut collection = euclidean();
+        VectorGenerator::new(3, 2).fill(&mut collection, 10);
+        let path = std::env::temp_dir().join(format!("embeddingproject-graph-{}.csv", Uuid::new_v4()));
+        collection.export_knn_graph_csv(&path, 3).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("source,target,score"));
+        let graph = collection.knn_graph(3);
+        let mut sources: Vec<&Uuid> = graph.keys().collect();
+        sources.sort();
+        let expected: Vec<String> = (sources.into_iter())
+            .flat_map(|source| {
+                (graph[source].iter()).map(move |(target, score)| format!("{},{},{}", source, target, score))
+            })
+            .collect();
+        assert_eq!(lines.collect::<Vec<_>>(), expected);
+    }
+}
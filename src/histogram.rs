@@ -0,0 +1,163 @@
+use std::fmt;
+
+use crate::float;
+use crate::json;
+use crate::privacy::Laplace;
+
+/// Distribution des scores d'une requête sur une collection.
+///
+/// Obtenue avec [`Collection::score_distribution`](crate::Collection::score_distribution).
+/// Le minimum, le maximum et la moyenne sont exacts ; la médiane est estimée par
+/// interpolation linéaire dans le compartiment qui la contient. Un histogramme bruité
+/// ([`Collection::set_aggregate_noise`](crate::Collection::set_aggregate_noise)) n'a
+/// que des comptes bruités et la médiane qui en est estimée.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Histogram {
+    /// Bornes des compartiments, au nombre de `counts.len() + 1`, par ordre croissant.
+    /// Le compartiment `i` couvre `[edges[i], edges[i + 1])`, le dernier inclut sa borne haute.
+    pub edges: Vec<f32>,
+    /// Nombre de scores par compartiment.
+    pub counts: Vec<u64>,
+    /// Nombre total de scores.
+    pub count: u64,
+    /// Plus petit score, `None` si aucun document n'a été évalué.
+    pub min: Option<f32>,
+    /// Plus grand score.
+    pub max: Option<f32>,
+    /// Moyenne des scores.
+    pub mean: Option<f32>,
+    /// Médiane estimée des scores.
+    pub median: Option<f32>,
+}
+
+impl Histogram {
+    /// Crée un histogramme vide à `buckets` compartiments répartis sur `[low, high]`.
+    pub(crate) fn with_range(low: f32, high: f32, buckets: usize) -> Self {
+        let width = (high - low) / buckets as f32;
+        let mut edges: Vec<f32> = (0..buckets).map(|i| low + width * i as f32).collect();
+        edges.push(high);
+        Histogram {
+            edges,
+            counts: vec![0; buckets],
+            ..Default::default()
+        }
+    }
+
+    /// Compte `score` dans son compartiment. Les scores hors bornes vont dans le
+    /// compartiment extrême le plus proche.
+    pub(crate) fn record(&mut self, score: f32) {
+        let buckets = self.counts.len();
+        let low = self.edges[0];
+        let high = self.edges[buckets];
+        let index = if high > low {
+            (((score - low) / (high - low)) * buckets as f32) as usize
+        } else {
+            0
+        };
+        self.counts[index.min(buckets - 1)] += 1;
+    }
+
+    /// Renseigne les statistiques finales à partir des compteurs.
+    pub(crate) fn finish(&mut self, count: u64, min: f32, max: f32, sum: f64) {
+        self.count = count;
+        if count == 0 {
+            return;
+        }
+        self.min = Some(min);
+        self.max = Some(max);
+        self.mean = Some((sum / count as f64) as f32);
+        self.median = Some(self.estimate_quantile(0.5).clamp(min, max));
+    }
+
+    /// Bruite les comptes des compartiments, et remplace les statistiques par celles
+    /// qui s'en déduisent : le total et la médiane estimée. Le minimum, le maximum et la
+    /// moyenne, qui dépendent de scores individuels, sont retirés.
+    pub(crate) fn add_noise(&mut self, laplace: &mut Laplace) {
+        for count in &mut self.counts {
+            *count = laplace.count(*count);
+        }
+        self.count = self.counts.iter().sum();
+        self.min = None;
+        self.max = None;
+        self.mean = None;
+        self.median = Some(self.estimate_quantile(0.5)).filter(|_| self.count > 0);
+    }
+
+    fn estimate_quantile(&self, q: f64) -> f32 {
+        let target = q * self.count as f64;
+        let mut cumulative = 0.0;
+        for (i, &n) in self.counts.iter().enumerate() {
+            let n = n as f64;
+            if n > 0.0 && cumulative + n >= target {
+                let fraction = ((target - cumulative) / n) as f32;
+                return self.edges[i] + fraction * (self.edges[i + 1] - self.edges[i]);
+            }
+            cumulative += n;
+        }
+        self.edges[self.counts.len()]
+    }
+
+    /// Sérialise l'histogramme en JSON.
+    ///
+    /// # Retourne
+    /// * String - Objet JSON avec les champs `edges`, `counts`, `count`, `min`, `max`,
+    ///   `mean` et `median` ; les statistiques absentes valent `null`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"edges\":");
+        json::write_array(&mut out, &self.edges, |out, e| json::write_f32(out, *e));
+        out.push_str(",\"counts\":");
+        json::write_array(&mut out, &self.counts, |out, c| json::write_u64(out, *c));
+        out.push_str(",\"count\":");
+        json::write_u64(&mut out, self.count);
+        for (name, value) in [
+            ("min", self.min),
+            ("max", self.max),
+            ("mean", self.mean),
+            ("median", self.median),
+        ] {
+            out.push_str(",\"");
+            out.push_str(name);
+            out.push_str("\":");
+            json::write_option_f32(&mut out, value);
+        }
+        out.push('}');
+        out
+    }
+}
+
+impl fmt::Display for Histogram {
+    /// Affiche les statistiques puis une ligne par compartiment, avec
+    /// [`float::DISPLAY_DIGITS`] chiffres significatifs ou la précision demandée.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = f.precision().unwrap_or(float::DISPLAY_DIGITS);
+        let show = |x: f32| float::significant(f64::from(x), digits);
+        match (self.min, self.max, self.mean, self.median) {
+            (Some(min), Some(max), Some(mean), Some(median)) => writeln!(
+                f,
+                "{} scores : min {}, max {}, moyenne {}, médiane ≈ {}",
+                self.count,
+                show(min),
+                show(max),
+                show(mean),
+                show(median)
+            )?,
+            (None, None, None, Some(median)) => writeln!(
+                f,
+                "{} scores (bruités) : médiane ≈ {}",
+                self.count,
+                show(median)
+            )?,
+            _ => writeln!(f, "aucun score")?,
+        }
+        for (i, count) in self.counts.iter().enumerate() {
+            writeln!(
+                f,
+                "[{:>9}, {:>9}] {}",
+                show(self.edges[i]),
+                show(self.edges[i + 1]),
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
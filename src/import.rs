@@ -0,0 +1,127 @@
+use std::io::BufRead;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::document_id::DocumentId;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::json;
+use crate::oplog::{self, OperationKind};
+use crate::payload::Value;
+use crate::progress::{BatchReport, ProgressSink, Tracker};
+
+impl Collection {
+    /// Importe des documents depuis un flux JSONL, un objet par ligne :
+    /// `{"id": "doc-1", "vector": [0.1, 0.2], "payload": {"langue": "fr"}}`.
+    ///
+    /// `id` est un `Uuid` écrit sous forme de chaîne, une autre chaîne ou un entier (voir
+    /// [`DocumentId`]) ; `payload` est facultatif et remplace la charge utile du document,
+    /// après vérification du schéma de la collection s'il y en a un.
+    /// Les lignes vides sont ignorées. Comme pour [`Collection::upsert_batch`], l'import
+    /// s'arrête à la première ligne invalide, les précédentes restant insérées.
+    ///
+    /// # Arguments
+    /// * `reader` - Flux à lire.
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<BatchReport> - Nombre de documents importés et indication d'annulation.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le flux ne peut pas être lu.
+    /// * `Error::Parse` - Si une ligne n'est pas un document valide.
+    /// * Celles de [`Collection::upsert_id_with_payload`], dont `Error::SchemaViolation`,
+    ///   enveloppées dans `Error::WithContext` avec l'identifiant du document et sa ligne.
+    pub fn import_jsonl(
+        &mut self,
+        reader: impl BufRead,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport> {
+        self.logged(
+            OperationKind::Import,
+            |collection| collection.import_lines(reader, progress),
+            oplog::applied,
+        )
+    }
+
+    fn import_lines(
+        &mut self,
+        reader: impl BufRead,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport> {
+        let mut tracker = Tracker::new(progress, None);
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if tracker.cancelled() {
+                return Ok(tracker.finish(true));
+            }
+            let parse_error = |message: String| Error::Parse {
+                line: index + 1,
+                message,
+            };
+            let Value::Object(mut fields) = json::parse(&line).map_err(parse_error)? else {
+                return Err(parse_error("objet attendu".to_string()));
+            };
+            let id = fields
+                .remove("id")
+                .ok_or_else(|| parse_error("champ « id » absent".to_string()))
+                .and_then(|id| {
+                    document_id(&id).ok_or_else(|| parse_error("identifiant invalide".to_string()))
+                })?;
+            let vector = match fields.remove("vector") {
+                Some(items @ Value::Array(_)) => vector(&items).ok_or_else(|| {
+                    parse_error("le vecteur doit contenir des nombres f32".to_string())
+                })?,
+                _ => {
+                    return Err(parse_error(
+                        "champ « vector » absent ou invalide".to_string(),
+                    ))
+                }
+            };
+            let context = ErrorContext::new(Operation::Import)
+                .key(id.to_uuid())
+                .line(index + 1);
+            let stored = match fields.remove("payload") {
+                None | Some(Value::Null) => self.upsert_id(id, vector),
+                Some(Value::Object(payload)) => self.upsert_id_with_payload(id, vector, payload),
+                Some(_) => {
+                    return Err(parse_error(
+                        "la charge utile doit être un objet".to_string(),
+                    ))
+                }
+            }
+            .map_err(|e| e.with_context(context))?;
+            tracker.record(stored);
+        }
+        Ok(tracker.finish(false))
+    }
+}
+
+/// Lit l'identifiant d'un document importé : `Uuid` ou chaîne, ou entier exact.
+pub(crate) fn document_id(value: &Value) -> Option<DocumentId> {
+    match value {
+        Value::String(s) => Some(match Uuid::parse_str(s) {
+            Ok(uuid) => DocumentId::Uuid(uuid),
+            Err(_) => DocumentId::Str(s.clone()),
+        }),
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 9.007_199_254_740_992e15 => {
+            Some(DocumentId::Int(*n as i64))
+        }
+        _ => None,
+    }
+}
+
+/// Lit le vecteur d'un document importé : un tableau de nombres finis en `f32`.
+pub(crate) fn vector(value: &Value) -> Option<Vec<f32>> {
+    let Value::Array(items) = value else {
+        return None;
+    };
+    items
+        .iter()
+        .map(|item| item.as_f64().map(|x| x as f32))
+        .collect::<Option<Vec<f32>>>()
+        .filter(|vector| vector.iter().all(|x| x.is_finite()))
+}
@@ -0,0 +1,454 @@
+//! Import des exports d'autres bases vectorielles, pour y migrer sans recalculer les
+//! plongements.
+//!
+//! - **Qdrant** : réponses JSON de l'API `scroll` (`{"result": {"points": [...]}}`),
+//!   tableaux de points ou un point par ligne. Un point porte `id` (entier non signé ou
+//!   `Uuid`), `vector` (tableau, ou objet de vecteurs nommés) et `payload`.
+//! - **Chroma** : résultat de `collection.get(include=["embeddings", "metadatas",
+//!   "documents"])` écrit en JSON, en colonnes `ids`, `embeddings`, `metadatas` et
+//!   `documents`, ou un enregistrement `{"id", "embedding", "metadata", "document"}` par
+//!   ligne. Le texte d'un document est rangé dans le champ [`CHROMA_DOCUMENT_FIELD`] de
+//!   sa charge utile, sauf si ses métadonnées en ont déjà un. Les exports Parquet ne sont
+//!   pas lus : ils doivent d'abord être convertis en JSON.
+//!
+//! Un identifiant entier devient le `Uuid` déterministe de [`DocumentId::Int`], une
+//! chaîne ce `Uuid` si elle en est un, [`DocumentId::Str`] sinon : réimporter le même
+//! export met à jour les mêmes documents.
+//!
+//! Les valeurs scalaires des charges utiles, les tableaux de scalaires et les objets
+//! sont conservés. Les autres valeurs, tableaux d'objets ou de tableaux que les filtres
+//! ne savent pas comparer, sont remplacées par leur texte JSON et comptées dans
+//! [`ImportReport::degraded`] au lieu d'interrompre la migration.
+
+use std::fmt;
+use std::io::{BufReader, Read};
+
+use crate::collection::Collection;
+use crate::document_id::DocumentId;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::import::{document_id, vector};
+use crate::json;
+use crate::oplog::OperationKind;
+use crate::payload::{Payload, Value};
+use crate::progress::{ProgressSink, Tracker};
+
+/// Champ de la charge utile qui reçoit le texte d'un document Chroma.
+pub const CHROMA_DOCUMENT_FIELD: &str = "document";
+
+/// Format d'un fichier importé par [`Collection::import_from`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportFormat {
+    /// Format de [`Collection::import_jsonl`].
+    #[default]
+    Jsonl,
+    /// Export de Qdrant, avec le vecteur non nommé ou le seul vecteur nommé des points.
+    Qdrant,
+    /// Export de Chroma.
+    Chroma,
+}
+
+/// Bilan d'un import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportReport {
+    /// Nombre de documents importés.
+    pub applied: u64,
+    /// Nombre de valeurs de charge utile importées sous forme de texte JSON : les
+    /// tableaux d'objets ou de tableaux, que les filtres ne savent pas comparer.
+    pub degraded: u64,
+    /// Vrai si l'import a été interrompu par
+    /// [`ProgressSink::should_cancel`](crate::ProgressSink::should_cancel).
+    pub cancelled: bool,
+}
+
+impl fmt::Display for ImportReport {
+    /// Affiche le bilan sur une ligne : `120 document(s) importé(s), 3 valeur(s) …`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} document(s) importé(s), {} valeur(s) convertie(s) en texte{}",
+            self.applied,
+            self.degraded,
+            if self.cancelled {
+                ", import interrompu"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// Document lu dans un export, prêt à être inséré.
+struct Record {
+    line: usize,
+    id: DocumentId,
+    vector: Vec<f32>,
+    payload: Payload,
+    degraded: u64,
+}
+
+impl Collection {
+    /// Importe un fichier au format `format`.
+    ///
+    /// # Arguments
+    /// * `reader` - Flux à lire.
+    /// * `format` - Format du flux.
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<ImportReport> - Nombre de documents importés et de valeurs converties.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::import_jsonl`], [`Collection::import_qdrant`] ou
+    ///   [`Collection::import_chroma`], selon le format.
+    pub fn import_from(
+        &mut self,
+        reader: impl Read,
+        format: ImportFormat,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<ImportReport> {
+        match format {
+            ImportFormat::Jsonl => {
+                let report = self.import_jsonl(BufReader::new(reader), progress)?;
+                Ok(ImportReport {
+                    applied: report.applied,
+                    degraded: 0,
+                    cancelled: report.cancelled,
+                })
+            }
+            ImportFormat::Qdrant => self.import_qdrant(reader, None, progress),
+            ImportFormat::Chroma => self.import_chroma(reader, progress),
+        }
+    }
+
+    /// Importe les points d'un export de Qdrant : réponses JSON de l'API `scroll`,
+    /// tableaux de points ou un point par ligne, chacun avec `id`, `vector` et `payload`.
+    /// Un identifiant entier est traduit en [`DocumentId::Int`], une chaîne en `Uuid`.
+    ///
+    /// Tout l'export est lu et vérifié avant la première insertion. Comme pour
+    /// [`Collection::import_jsonl`], l'insertion s'arrête ensuite au premier document
+    /// refusé par la collection, les précédents restant insérés.
+    ///
+    /// # Arguments
+    /// * `reader` - Flux à lire.
+    /// * `vector_name` - Vecteur nommé à importer ; `None` prend le vecteur non nommé de
+    ///   chaque point, ou son seul vecteur nommé.
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<ImportReport> - Nombre de documents importés et de valeurs converties.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le flux ne peut pas être lu.
+    /// * `Error::Parse` - Si le flux n'est pas du JSON, ou si un point n'a pas
+    ///   d'identifiant, de vecteur dense à importer ou de charge utile objet.
+    /// * Celles de [`Collection::upsert_id_with_payload`], enveloppées dans
+    ///   `Error::WithContext` avec l'identifiant du document et sa ligne.
+    pub fn import_qdrant(
+        &mut self,
+        reader: impl Read,
+        vector_name: Option<&str>,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<ImportReport> {
+        let mut records = Vec::new();
+        for (line, value) in documents(reader)? {
+            for point in qdrant_points(value) {
+                let index = records.len() + 1;
+                let record = qdrant_record(point, vector_name, line)
+                    .map_err(|message| parse_error(line, "point", index, message))?;
+                records.push(record);
+            }
+        }
+        self.import_records(records, progress)
+    }
+
+    /// Importe les documents d'un export de Chroma : résultat de `collection.get` en
+    /// colonnes `ids`, `embeddings`, `metadatas` et `documents`, ou un enregistrement
+    /// `{"id", "embedding", "metadata", "document"}` par ligne. Le texte d'un document va
+    /// dans le champ [`CHROMA_DOCUMENT_FIELD`] ; un identifiant qui n'est pas un `Uuid`
+    /// est conservé en [`DocumentId::Str`].
+    ///
+    /// Tout l'export est lu et vérifié avant la première insertion. Comme pour
+    /// [`Collection::import_jsonl`], l'insertion s'arrête ensuite au premier document
+    /// refusé par la collection, les précédents restant insérés.
+    ///
+    /// # Arguments
+    /// * `reader` - Flux à lire.
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<ImportReport> - Nombre de documents importés et de valeurs converties.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le flux ne peut pas être lu.
+    /// * `Error::Parse` - Si le flux n'est pas du JSON, si les colonnes n'ont pas la même
+    ///   longueur, ou si un document n'a pas d'identifiant ou de plongement.
+    /// * Celles de [`Collection::upsert_id_with_payload`], enveloppées dans
+    ///   `Error::WithContext` avec l'identifiant du document et sa ligne.
+    pub fn import_chroma(
+        &mut self,
+        reader: impl Read,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<ImportReport> {
+        let mut records = Vec::new();
+        for (line, value) in documents(reader)? {
+            for entry in chroma_entries(value).map_err(|message| Error::Parse { line, message })? {
+                let index = records.len() + 1;
+                let record = chroma_record(entry, line)
+                    .map_err(|message| parse_error(line, "document", index, message))?;
+                records.push(record);
+            }
+        }
+        self.import_records(records, progress)
+    }
+
+    fn import_records(
+        &mut self,
+        records: Vec<Record>,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<ImportReport> {
+        self.logged(
+            OperationKind::Import,
+            |collection| {
+                let mut tracker = Tracker::new(progress, Some(records.len() as u64));
+                let mut degraded = 0;
+                for record in records {
+                    if tracker.cancelled() {
+                        return Ok(report(tracker.finish(true).applied, degraded, true));
+                    }
+                    let context = ErrorContext::new(Operation::Import)
+                        .key(record.id.to_uuid())
+                        .line(record.line);
+                    let stored = match record.payload.is_empty() {
+                        true => collection.upsert_id(record.id, record.vector),
+                        false => collection.upsert_id_with_payload(
+                            record.id,
+                            record.vector,
+                            record.payload,
+                        ),
+                    }
+                    .map_err(|e| e.with_context(context))?;
+                    degraded += record.degraded;
+                    tracker.record(stored);
+                }
+                Ok(report(tracker.finish(false).applied, degraded, false))
+            },
+            |result: &Result<ImportReport>| result.as_ref().ok().map(|r| r.applied as usize),
+        )
+    }
+}
+
+fn report(applied: u64, degraded: u64, cancelled: bool) -> ImportReport {
+    ImportReport {
+        applied,
+        degraded,
+        cancelled,
+    }
+}
+
+fn parse_error(line: usize, what: &str, index: usize, message: String) -> Error {
+    Error::Parse {
+        line,
+        message: format!("{} {} : {}", what, index, message),
+    }
+}
+
+/// Lit un export : un seul document JSON, ou un document par ligne non vide, chacun
+/// avec le numéro de sa ligne.
+fn documents(mut reader: impl Read) -> Result<Vec<(usize, Value)>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let message = match json::parse(&text) {
+        Ok(value) => return Ok(vec![(1, value)]),
+        Err(message) => message,
+    };
+    let lines = (text.lines().enumerate()).filter(|(_, line)| !line.trim().is_empty());
+    // Un document unique invalide ne se lit pas non plus ligne par ligne : son erreur
+    // est alors plus parlante que celle de sa première ligne.
+    if lines
+        .clone()
+        .next()
+        .is_none_or(|(_, line)| json::parse(line).is_err())
+    {
+        return Err(Error::Parse { line: 1, message });
+    }
+    lines
+        .map(|(index, line)| {
+            json::parse(line)
+                .map(|value| (index + 1, value))
+                .map_err(|message| Error::Parse {
+                    line: index + 1,
+                    message,
+                })
+        })
+        .collect()
+}
+
+/// Déplie une réponse `scroll`, un tableau ou une page en points.
+fn qdrant_points(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.into_iter().flat_map(qdrant_points).collect(),
+        Value::Object(mut fields) if !fields.contains_key("id") => {
+            match fields.remove("result").or_else(|| fields.remove("points")) {
+                Some(inner) => qdrant_points(inner),
+                None => vec![Value::Object(fields)],
+            }
+        }
+        point => vec![point],
+    }
+}
+
+fn qdrant_record(
+    point: Value,
+    vector_name: Option<&str>,
+    line: usize,
+) -> std::result::Result<Record, String> {
+    let Value::Object(mut fields) = point else {
+        return Err("objet attendu".to_string());
+    };
+    let id = fields
+        .remove("id")
+        .and_then(|id| document_id(&id))
+        .ok_or("identifiant absent ou invalide")?;
+    let dense = match (fields.remove("vector"), vector_name) {
+        (Some(Value::Object(mut named)), Some(name)) => named
+            .remove(name)
+            .ok_or_else(|| format!("vecteur nommé « {} » absent", name))?,
+        (Some(Value::Object(named)), None) if named.len() == 1 => {
+            named.into_values().next().expect("un vecteur nommé")
+        }
+        (Some(Value::Object(named)), None) => {
+            let names: Vec<&str> = named.keys().map(String::as_str).collect();
+            return Err(format!(
+                "plusieurs vecteurs nommés ({}) : le vecteur à importer doit être précisé",
+                names.join(", ")
+            ));
+        }
+        (Some(value), None) => value,
+        (Some(_), Some(name)) => return Err(format!("vecteur nommé « {} » absent", name)),
+        (None, _) => return Err("champ « vector » absent".to_string()),
+    };
+    if matches!(&dense, Value::Object(fields) if fields.contains_key("indices")) {
+        return Err("les vecteurs creux ne sont pas pris en charge".to_string());
+    }
+    let vector = vector(&dense).ok_or("le vecteur doit contenir des nombres f32")?;
+    let payload = match fields.remove("payload") {
+        None | Some(Value::Null) => Payload::new(),
+        Some(Value::Object(payload)) => payload,
+        Some(_) => return Err("la charge utile doit être un objet".to_string()),
+    };
+    Ok(conformed(line, id, vector, payload))
+}
+
+/// Déplie un résultat en colonnes ou un tableau en enregistrements d'un document.
+fn chroma_entries(value: Value) -> std::result::Result<Vec<Payload>, String> {
+    let mut fields = match value {
+        Value::Array(items) => {
+            let entries = items.into_iter().map(chroma_entries);
+            return entries
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map(|e| e.concat());
+        }
+        Value::Object(fields) => fields,
+        _ => return Err("objet attendu".to_string()),
+    };
+    let ids = match fields.remove("ids") {
+        None => return Ok(vec![fields]),
+        Some(Value::Array(ids)) => ids,
+        Some(_) => return Err("colonne « ids » invalide".to_string()),
+    };
+    let mut column = |name: &str, required: bool| match fields.remove(name) {
+        Some(Value::Array(items)) => Ok(Some(items)),
+        None | Some(Value::Null) if !required => Ok(None),
+        _ => Err(format!("colonne « {} » absente ou invalide", name)),
+    };
+    let embeddings = column("embeddings", true)?.unwrap_or_default();
+    let metadatas = column("metadatas", false)?;
+    let texts = column("documents", false)?;
+    for (name, len) in [
+        ("embeddings", Some(embeddings.len())),
+        ("metadatas", metadatas.as_ref().map(Vec::len)),
+        ("documents", texts.as_ref().map(Vec::len)),
+    ] {
+        if len.is_some_and(|len| len != ids.len()) {
+            return Err(format!(
+                "la colonne « {} » n'a pas autant de lignes que « ids »",
+                name
+            ));
+        }
+    }
+    let mut metadatas = metadatas.map(Vec::into_iter);
+    let mut texts = texts.map(Vec::into_iter);
+    Ok((ids.into_iter().zip(embeddings))
+        .map(|(id, embedding)| {
+            let mut entry = Payload::new();
+            entry.insert("id".to_string(), id);
+            entry.insert("embedding".to_string(), embedding);
+            let metadata = metadatas.as_mut().and_then(Iterator::next);
+            let text = texts.as_mut().and_then(Iterator::next);
+            entry.insert("metadata".to_string(), metadata.unwrap_or(Value::Null));
+            entry.insert("document".to_string(), text.unwrap_or(Value::Null));
+            entry
+        })
+        .collect())
+}
+
+fn chroma_record(mut fields: Payload, line: usize) -> std::result::Result<Record, String> {
+    let id = fields
+        .remove("id")
+        .and_then(|id| document_id(&id))
+        .ok_or("identifiant absent ou invalide")?;
+    let vector = fields
+        .remove("embedding")
+        .and_then(|embedding| vector(&embedding))
+        .ok_or("plongement absent ou invalide")?;
+    let mut payload = match fields.remove("metadata") {
+        None | Some(Value::Null) => Payload::new(),
+        Some(Value::Object(metadata)) => metadata,
+        Some(_) => return Err("les métadonnées doivent être un objet".to_string()),
+    };
+    match fields.remove("document") {
+        None | Some(Value::Null) => {}
+        Some(text) => {
+            payload
+                .entry(CHROMA_DOCUMENT_FIELD.to_string())
+                .or_insert(text);
+        }
+    }
+    Ok(conformed(line, id, vector, payload))
+}
+
+/// Construit l'enregistrement d'un document, en convertissant en texte les valeurs de
+/// sa charge utile que la collection ne saurait pas filtrer.
+fn conformed(line: usize, id: DocumentId, vector: Vec<f32>, payload: Payload) -> Record {
+    let mut degraded = 0;
+    let payload = (payload.into_iter())
+        .map(|(name, value)| (name, conform(value, &mut degraded)))
+        .collect();
+    Record {
+        line,
+        id,
+        vector,
+        payload,
+        degraded,
+    }
+}
+
+fn conform(value: Value, degraded: &mut u64) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            (fields.into_iter())
+                .map(|(name, value)| (name, conform(value, degraded)))
+                .collect(),
+        ),
+        Value::Array(items)
+            if (items.iter()).any(|item| matches!(item, Value::Array(_) | Value::Object(_))) =>
+        {
+            *degraded += 1;
+            Value::String(Value::Array(items).to_string())
+        }
+        value => value,
+    }
+}
@@ -0,0 +1,290 @@
+//! Écriture minimale de JSON, sans dépendance externe.
+
+use std::fmt::Write;
+
+use crate::float;
+use crate::payload::Value;
+
+/// Écrit `s` sous forme de chaîne JSON, guillemets compris.
+pub(crate) fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Écrit un nombre JSON ; les valeurs non finies, que JSON ne sait pas représenter,
+/// deviennent `null`.
+pub(crate) fn write_f32(out: &mut String, n: f32) {
+    if n.is_finite() {
+        out.push_str(&float::shortest_f32(n));
+    } else {
+        out.push_str("null");
+    }
+}
+
+/// Écrit une valeur de charge utile ; les nombres non finis deviennent `null`.
+pub(crate) fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) if n.is_finite() => out.push_str(&float::shortest_f64(*n)),
+        Value::Number(_) => out.push_str("null"),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => write_array(out, items, write_value),
+        Value::Object(fields) => {
+            out.push('{');
+            for (i, (name, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, name);
+                out.push(':');
+                write_value(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Écrit un entier JSON.
+pub(crate) fn write_u64(out: &mut String, n: u64) {
+    let _ = write!(out, "{}", n);
+}
+
+/// Écrit un nombre optionnel, `None` devenant `null`.
+pub(crate) fn write_option_f32(out: &mut String, n: Option<f32>) {
+    match n {
+        Some(n) => write_f32(out, n),
+        None => out.push_str("null"),
+    }
+}
+
+/// Écrit un tableau JSON en appliquant `write_item` à chaque élément.
+pub(crate) fn write_array<T>(
+    out: &mut String,
+    items: impl IntoIterator<Item = T>,
+    mut write_item: impl FnMut(&mut String, T),
+) {
+    out.push('[');
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_item(out, item);
+    }
+    out.push(']');
+}
+
+/// Profondeur maximale d'imbrication acceptée par [`parse`].
+const MAX_PARSE_DEPTH: usize = 64;
+
+/// Lit un document JSON complet.
+///
+/// # Retourne
+/// * Result<Value, String> - La valeur lue, ou la description de la première erreur.
+pub(crate) fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        position: 0,
+    };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.position < parser.bytes.len() {
+        return Err(parser.error("contenu après la valeur"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} (position {})", message, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("« {} » attendu", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        if !self.bytes[self.position..].starts_with(word.as_bytes()) {
+            return Err(self.error("valeur inconnue"));
+        }
+        self.position += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_PARSE_DEPTH {
+            return Err(self.error("imbrication trop profonde"));
+        }
+        match self.peek() {
+            None => Err(self.error("valeur attendue")),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Value::Array(items))
+            }
+            Some(b'{') => {
+                self.position += 1;
+                let mut fields = std::collections::BTreeMap::new();
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return Err(self.error("nom de champ attendu"));
+                    }
+                    let name = self.string()?;
+                    self.expect(b':')?;
+                    fields.insert(name, self.value(depth + 1)?);
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Value::Object(fields))
+            }
+            Some(_) => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') =
+            self.bytes.get(self.position)
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()
+            .and_then(|text| text.parse::<f64>().ok())
+            .filter(|n| n.is_finite())
+            .map(Value::Number)
+            .ok_or_else(|| {
+                self.position = start;
+                self.error("nombre invalide")
+            })
+    }
+
+    /// Lit une chaîne, guillemet ouvrant compris.
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.position;
+            while let Some(&byte) = self.bytes.get(self.position) {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+                self.position += 1;
+            }
+            // Le texte d'origine est UTF-8 et la coupure tombe sur un octet ASCII.
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or(""));
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = self.bytes.get(self.position).copied();
+                    self.position += 1;
+                    match escaped {
+                        Some(b'"') => out.push('"'),
+                        Some(b'\\') => out.push('\\'),
+                        Some(b'/') => out.push('/'),
+                        Some(b'b') => out.push('\u{8}'),
+                        Some(b'f') => out.push('\u{c}'),
+                        Some(b'n') => out.push('\n'),
+                        Some(b'r') => out.push('\r'),
+                        Some(b't') => out.push('\t'),
+                        Some(b'u') => out.push(self.unicode_escape()?),
+                        _ => return Err(self.error("échappement invalide")),
+                    }
+                }
+                _ => return Err(self.error("chaîne non terminée")),
+            }
+        }
+    }
+
+    /// Lit les chiffres d'un échappement `\uXXXX`, et sa seconde moitié s'il s'agit d'une
+    /// paire de substitution.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                if !self.bytes[self.position..].starts_with(b"\\u") {
+                    return Err(self.error("paire de substitution incomplète"));
+                }
+                self.position += 2;
+                let low = self.hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("paire de substitution invalide"));
+                }
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            }
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| self.error("caractère invalide"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("échappement \\u invalide"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+}
@@ -0,0 +1,262 @@
+//! Clés `Uuid` dérivées des identifiants externes des documents (URL, chemins…).
+//!
+//! [`derive_key`] calcule un `Uuid` de version 5 (RFC 9562) à partir d'un espace de
+//! noms et de l'identifiant externe : le même identifiant donne toujours le même `Uuid`,
+//! d'une exécution et d'une machine à l'autre, si bien qu'une nouvelle ingestion met le
+//! document à jour au lieu de le dupliquer. Ces `Uuid` sont ceux de toute autre
+//! implémentation de la version 5 pour le même espace de noms.
+
+use uuid::{Builder, Uuid};
+
+use crate::collection::Collection;
+use crate::error::Result;
+use crate::payload::{Payload, Value};
+
+/// Espace de noms par défaut des collections : celui des URL de la RFC 9562.
+pub const DEFAULT_KEY_NAMESPACE: Uuid = Uuid::NAMESPACE_URL;
+
+/// Calcule le `Uuid` de version 5 de `external_id` dans l'espace de noms `namespace`.
+///
+/// # Arguments
+/// * `namespace` - Espace de noms, par exemple [`DEFAULT_KEY_NAMESPACE`] ou un `Uuid`
+///   propre à l'application.
+/// * `external_id` - Identifiant externe du document, pris octet par octet.
+pub fn derive_key(namespace: &Uuid, external_id: &str) -> Uuid {
+    let mut name = namespace.as_bytes().to_vec();
+    name.extend_from_slice(external_id.as_bytes());
+    let digest = sha1(&name);
+    let bytes: [u8; 16] = digest[..16].try_into().expect("empreinte de 20 octets");
+    Builder::from_sha1_bytes(bytes).into_uuid()
+}
+
+/// Dérivation des clés externes d'une collection.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExternalKeys {
+    pub(crate) namespace: Uuid,
+    pub(crate) field: Option<String>,
+}
+
+impl Default for ExternalKeys {
+    fn default() -> Self {
+        ExternalKeys {
+            namespace: DEFAULT_KEY_NAMESPACE,
+            field: None,
+        }
+    }
+}
+
+impl Collection {
+    /// Fixe l'espace de noms des clés dérivées par [`Collection::upsert_external`], par
+    /// défaut [`DEFAULT_KEY_NAMESPACE`]. Les documents déjà insérés gardent leur clé :
+    /// changer d'espace de noms en cours de route dupliquerait les documents réinsérés.
+    ///
+    /// # Arguments
+    /// * `namespace` - Espace de noms des clés.
+    pub fn set_key_namespace(&mut self, namespace: Uuid) {
+        self.external_keys.namespace = namespace;
+    }
+
+    /// Retourne l'espace de noms des clés dérivées.
+    pub fn key_namespace(&self) -> Uuid {
+        self.external_keys.namespace
+    }
+
+    /// Fixe le champ de la charge utile dans lequel [`Collection::upsert_external`]
+    /// conserve l'identifiant externe de chaque document, lu par
+    /// [`Collection::external_id`] ; `None`, par défaut, ne le conserve pas. Avec un
+    /// schéma strict, le champ doit y être déclaré comme texte.
+    ///
+    /// # Arguments
+    /// * `field` - Nom du champ, ou `None`.
+    pub fn set_external_id_field(&mut self, field: Option<String>) {
+        self.external_keys.field = field;
+    }
+
+    /// Retourne le champ de la charge utile qui conserve les identifiants externes.
+    pub fn external_id_field(&self) -> Option<&str> {
+        self.external_keys.field.as_deref()
+    }
+
+    /// Insère ou met à jour le document d'identifiant externe `external_id`, sous la
+    /// clé [`derive_key`] de l'espace de noms de la collection, en conservant sa charge
+    /// utile.
+    ///
+    /// # Arguments
+    /// * `external_id` - Identifiant externe du document.
+    /// * `vector` - Vecteur représentant le document.
+    ///
+    /// # Retourne
+    /// * Result<Uuid> - La clé du document.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert_with_payload`] si le champ des identifiants
+    ///   externes est fixé, de [`Collection::upsert`] sinon.
+    pub fn upsert_external(
+        &mut self,
+        external_id: &str,
+        vector: impl Into<Vec<f32>>,
+    ) -> Result<Uuid> {
+        let key = derive_key(&self.external_keys.namespace, external_id);
+        match &self.external_keys.field {
+            None => {
+                self.upsert(key, vector)?;
+            }
+            Some(_) => {
+                let payload = self.payload(&key).cloned().unwrap_or_default();
+                self.upsert_external_with_payload(external_id, vector, payload)?;
+            }
+        }
+        Ok(key)
+    }
+
+    /// Insère ou met à jour le document d'identifiant externe `external_id` avec sa
+    /// charge utile, complétée de l'identifiant si le champ des identifiants externes est
+    /// fixé.
+    ///
+    /// # Retourne
+    /// * Result<Uuid> - La clé du document.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert_with_payload`].
+    pub fn upsert_external_with_payload(
+        &mut self,
+        external_id: &str,
+        vector: impl Into<Vec<f32>>,
+        mut payload: Payload,
+    ) -> Result<Uuid> {
+        let key = derive_key(&self.external_keys.namespace, external_id);
+        if let Some(field) = &self.external_keys.field {
+            payload.insert(field.clone(), Value::String(external_id.to_string()));
+        }
+        self.upsert_with_payload(key, vector, payload)?;
+        Ok(key)
+    }
+
+    /// Retourne la clé du document d'identifiant externe `external_id`, s'il est dans la
+    /// collection, avec ou sans vecteur.
+    ///
+    /// La clé est recalculée et non lue dans un index : elle ne peut pas désigner un
+    /// document supprimé.
+    pub fn find_by_external_id(&self, external_id: &str) -> Option<Uuid> {
+        let key = derive_key(&self.external_keys.namespace, external_id);
+        (self.documents.contains_key(&key) || self.pending.contains_key(&key)).then_some(key)
+    }
+
+    /// Retourne l'identifiant externe conservé dans la charge utile du document `key`,
+    /// si le champ des identifiants externes est fixé et renseigné.
+    pub fn external_id(&self, key: &Uuid) -> Option<&str> {
+        let field = self.external_keys.field.as_deref()?;
+        match self.payload(key)?.get(field)? {
+            Value::String(external_id) => Some(external_id),
+            _ => None,
+        }
+    }
+}
+
+/// Calcule l'empreinte SHA-1 de `data`, qui ne sert qu'aux `Uuid` de version 5.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (word, chunk) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(chunk.try_into().expect("mot de 4 octets"));
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = (a.rotate_left(5))
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+    let mut digest = [0; 20];
+    for (out, word) in digest.chunks_exact_mut(4).zip(state) {
+        out.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn keys_match_the_standard_version_5_uuids() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        // Valeur publiée par la documentation du module uuid de Python.
+        let key = derive_key(&Uuid::NAMESPACE_DNS, "python.org");
+        assert_eq!(key.to_string(), "886313e1-3b8a-5372-9b90-0c9aee199e5d");
+        assert_eq!(key.get_version_num(), 5);
+        assert_ne!(derive_key(&Uuid::NAMESPACE_URL, "python.org"), key);
+    }
+
+    #[test]
+    fn reingesting_an_external_id_updates_the_document() {
+        let mut collection = Collection::new();
+        collection.set_external_id_field(Some("source".to_string()));
+        let url = "https://exemple.fr/article/1";
+        let key = collection.upsert_external(url, [1.0, 0.0]).unwrap();
+        assert_eq!(key, derive_key(&DEFAULT_KEY_NAMESPACE, url));
+        let again = collection.upsert_external(url, [0.0, 1.0]).unwrap();
+        assert_eq!((again, collection.len()), (key, 1));
+        assert_eq!(collection.read(&key), Some(&vec![0.0, 1.0]));
+        assert_eq!(collection.external_id(&key), Some(url));
+        assert_eq!(collection.find_by_external_id(url), Some(key));
+
+        collection.delete(&key);
+        assert_eq!(collection.find_by_external_id(url), None);
+        assert_eq!(collection.external_id(&key), None);
+    }
+
+    #[test]
+    fn the_namespace_and_field_survive_a_save() {
+        let namespace = Uuid::from_u128(0x5e);
+        let mut collection = Collection::new();
+        collection.set_key_namespace(namespace);
+        collection.set_external_id_field(Some("chemin".to_string()));
+        let key = collection.upsert_external("/docs/a.txt", [1.0]).unwrap();
+        assert_eq!(key, derive_key(&namespace, "/docs/a.txt"));
+
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&bdd.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap();
+        assert_eq!(loaded.key_namespace(), namespace);
+        assert_eq!(loaded.external_id_field(), Some("chemin"));
+        assert_eq!(loaded.find_by_external_id("/docs/a.txt"), Some(key));
+    }
+}
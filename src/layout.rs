@@ -0,0 +1,378 @@
+//! Coordonnées planes des documents d'une collection, pour visualiser son espace de
+//! plongement dans un outil externe.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::csv;
+use crate::error::{Error, Result};
+use crate::export;
+use crate::float;
+use crate::graph::GRAPH_PARALLEL_THRESHOLD;
+use crate::outliers;
+use crate::pca::PcaModel;
+use crate::rng::Rng;
+
+/// Nombre de voisins de chaque document dans le graphe que suit
+/// [`Projection2D::RandomThenNeighborRefine`].
+const LAYOUT_NEIGHBORS: usize = 15;
+
+/// Nombre de passes de l'ajustement de [`Projection2D::RandomThenNeighborRefine`].
+const LAYOUT_EPOCHS: usize = 200;
+
+/// Nombre de documents tirés au hasard, par arête et par passe, pour écarter les
+/// documents qui ne sont pas voisins.
+const NEGATIVE_SAMPLES: usize = 5;
+
+/// Plus grande coordonnée, en valeur absolue, de la disposition initiale.
+const LAYOUT_EXTENT: f32 = 10.0;
+
+/// Plus grand déplacement d'une coordonnée par force, pour que deux documents presque
+/// confondus ne s'éjectent pas l'un l'autre.
+const MAX_STEP: f32 = 4.0;
+
+/// Méthode de [`Collection::project_2d`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Projection2D {
+    /// Coordonnées sur les deux premières composantes principales
+    /// ([`Collection::fit_pca`]) : rapide, et fidèle aux grandes directions de
+    /// variance, mais des groupes distincts peuvent s'y recouvrir.
+    #[default]
+    Pca,
+    /// Projection aléatoire gaussienne, puis ajustement par forces sur le graphe des
+    /// plus proches voisins : chaque document est attiré par ses voisins de l'espace
+    /// d'origine et repoussé par des documents tirés au hasard. Les voisinages sont
+    /// mieux préservés et les groupes mieux séparés, au prix du graphe des voisins, en
+    /// O(n² × d) sur l'échantillon.
+    RandomThenNeighborRefine,
+}
+
+impl Collection {
+    /// Calcule des coordonnées planes pour les documents de la collection, ou d'un
+    /// échantillon tiré au hasard.
+    ///
+    /// Le résultat ne dépend que des documents, de `method`, de `sample` et de `seed` :
+    /// deux appels identiques produisent les mêmes coordonnées, quel que soit le nombre
+    /// de threads. Les coordonnées n'ont pas d'unité et seules les positions relatives
+    /// comptent. `sample` borne le coût : l'ACP coûte O(n × d) par itération et
+    /// l'ajustement O(n² × d) pour le graphe des voisins, avec n la taille de
+    /// l'échantillon.
+    ///
+    /// # Arguments
+    /// * `method` - Méthode de projection.
+    /// * `sample` - Nombre de documents tirés sans remise ; `None` les projette tous.
+    /// * `seed` - Graine du tirage de l'échantillon et de l'ajustement.
+    ///
+    /// # Retourne
+    /// * Result<Vec<(Uuid, [f32; 2])>> - Coordonnées de chaque document projeté, par
+    ///   `Uuid` croissant ; vide pour une collection vide.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `sample` vaut 0, ou pour [`Projection2D::Pca`] si
+    ///   les vecteurs n'ont qu'une dimension.
+    /// * `Error::DimensionMismatch` - Si les documents n'ont pas tous la même dimension.
+    pub fn project_2d(
+        &self,
+        method: Projection2D,
+        sample: Option<usize>,
+        seed: u64,
+    ) -> Result<Vec<(Uuid, [f32; 2])>> {
+        if sample == Some(0) {
+            return Err(Error::InvalidConfig(
+                "la taille d'échantillon doit être strictement positive".into(),
+            ));
+        }
+        let mut entries: Vec<(&Uuid, &Vec<f32>)> = self
+            .documents
+            .iter()
+            .map(|(key, vector)| (key, &**vector))
+            .collect();
+        entries.sort_unstable_by_key(|(key, _)| **key);
+        if let Some((_, first)) = entries.first() {
+            if let Some((_, other)) = entries.iter().find(|(_, v)| v.len() != first.len()) {
+                return Err(Error::DimensionMismatch {
+                    expected: first.len(),
+                    got: other.len(),
+                });
+            }
+        }
+        let mut picked = outliers::sample(&entries, sample, seed);
+        if picked.is_empty() {
+            return Ok(Vec::new());
+        }
+        picked.sort_unstable_by_key(|(key, _)| **key);
+        let vectors: Vec<&[f32]> = picked.iter().map(|(_, vector)| vector.as_slice()).collect();
+        let points = match method {
+            Projection2D::Pca => {
+                // Sans échantillon, la moyenne est celle tenue à jour.
+                let mean = match picked.len() == entries.len() {
+                    true => self.stats.mean(),
+                    false => None,
+                };
+                let model = PcaModel::fit(&vectors, mean, 2)?;
+                (vectors.iter())
+                    .map(|vector| {
+                        let reduced = model.transform(vector)?;
+                        Ok([reduced[0], reduced[1]])
+                    })
+                    .collect::<Result<Vec<[f32; 2]>>>()?
+            }
+            Projection2D::RandomThenNeighborRefine => {
+                let mut rng = Rng::new(seed);
+                let mut points = random_layout(&vectors, &mut rng);
+                let edges = self.neighbor_edges(&vectors);
+                refine(&mut points, &edges, &mut rng);
+                points
+            }
+        };
+        Ok((picked.iter().map(|(key, _)| **key)).zip(points).collect())
+    }
+
+    /// Écrit des coordonnées planes dans un fichier CSV, avec des champs de charge
+    /// utile pour colorier les documents dans un outil externe.
+    ///
+    /// Le fichier commence par l'en-tête `id,x,y` suivi des champs de `fields`, puis
+    /// une ligne par document dans l'ordre de `points`. Les champs suivent les règles
+    /// de [`SearchResults::write_csv_with_payload`](crate::SearchResults::write_csv_with_payload) :
+    /// un champ absent est vide, une chaîne est écrite telle quelle et les autres
+    /// valeurs sous forme de texte JSON.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier à créer ou écraser.
+    /// * `points` - Coordonnées obtenues avec [`Collection::project_2d`].
+    /// * `fields` - Champs de charge utile à exporter, dans l'ordre des colonnes.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être écrit.
+    pub fn export_projection_csv(
+        &self,
+        path: impl AsRef<Path>,
+        points: &[(Uuid, [f32; 2])],
+        fields: &[&str],
+    ) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let mut line = String::new();
+        csv::write_record(&mut line, ["id", "x", "y"].into_iter().chain(fields.iter().copied()));
+        out.write_all(line.as_bytes())?;
+        for (key, [x, y]) in points {
+            let payload = self.payload(key);
+            let mut record = vec![key.to_string(), float::shortest_f32(*x), float::shortest_f32(*y)];
+            for field in fields {
+                record.push(export::payload_cell(payload.and_then(|p| p.get(*field))));
+            }
+            line.clear();
+            csv::write_record(&mut line, record.iter().map(String::as_str));
+            out.write_all(line.as_bytes())?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Retourne les arêtes `(i, j)` du graphe des [`LAYOUT_NEIGHBORS`] plus proches
+    /// voisins de `vectors` selon la mesure de la collection, les égalités de score
+    /// départagées par position.
+    fn neighbor_edges(&self, vectors: &[&[f32]]) -> Vec<(usize, usize)> {
+        let scorer = self.scorer();
+        let metric = scorer.metric;
+        let k = LAYOUT_NEIGHBORS.min(vectors.len() - 1);
+        let indices: Vec<usize> = (0..vectors.len()).collect();
+        self.runtime
+            .map_chunks(&indices, GRAPH_PARALLEL_THRESHOLD, |chunk| {
+                chunk
+                    .iter()
+                    .flat_map(|&i| {
+                        let mut neighbors: Vec<(usize, f32)> = (0..vectors.len())
+                            .filter(|&j| j != i)
+                            .map(|j| (j, scorer.score_unchecked(vectors[i], vectors[j])))
+                            .collect();
+                        neighbors.sort_by(|a, b| metric.compare(a.1, b.1).then(a.0.cmp(&b.0)));
+                        neighbors.into_iter().take(k).map(move |(j, _)| (i, j))
+                    })
+                    .collect()
+            })
+    }
+}
+
+/// Projette `vectors`, centrés, sur deux directions gaussiennes tirées de `rng`, puis
+/// met les coordonnées à l'échelle de [`LAYOUT_EXTENT`].
+fn random_layout(vectors: &[&[f32]], rng: &mut Rng) -> Vec<[f32; 2]> {
+    let dimension = vectors[0].len();
+    let mut mean = vec![0.0_f64; dimension];
+    for vector in vectors {
+        for (m, x) in mean.iter_mut().zip(vector.iter()) {
+            *m += *x as f64;
+        }
+    }
+    mean.iter_mut().for_each(|m| *m /= vectors.len() as f64);
+    let axes: [Vec<f64>; 2] = [
+        (0..dimension).map(|_| rng.gaussian()).collect(),
+        (0..dimension).map(|_| rng.gaussian()).collect(),
+    ];
+    let mut points: Vec<[f32; 2]> = (vectors.iter())
+        .map(|vector| {
+            axes.each_ref().map(|axis| {
+                let projected: f64 = (vector.iter().zip(&mean).zip(axis))
+                    .map(|((x, m), a)| (*x as f64 - m) * a)
+                    .sum();
+                projected as f32
+            })
+        })
+        .collect();
+    let extent = points.iter().flatten().fold(0.0_f32, |max, x| max.max(x.abs()));
+    if extent > 0.0 {
+        let scale = LAYOUT_EXTENT / extent;
+        points.iter_mut().flatten().for_each(|x| *x *= scale);
+    }
+    points
+}
+
+/// Ajuste `points` par descente de gradient stochastique : à chaque passe, les deux
+/// extrémités de chaque arête se rapprochent, et la première s'écarte de
+/// [`NEGATIVE_SAMPLES`] documents tirés au hasard, avec un pas décroissant jusqu'à 0.
+///
+/// Les forces sont celles d'un noyau de Cauchy `1 / (1 + d²)` sur la distance plane.
+fn refine(points: &mut [[f32; 2]], edges: &[(usize, usize)], rng: &mut Rng) {
+    let n = points.len();
+    for epoch in 0..LAYOUT_EPOCHS {
+        let rate = 1.0 - epoch as f32 / LAYOUT_EPOCHS as f32;
+        for &(i, j) in edges {
+            let (dx, dy, d2) = offset(points[i], points[j]);
+            let pull = -2.0 / (1.0 + d2);
+            let step = [clip(pull * dx) * rate, clip(pull * dy) * rate];
+            points[i][0] += step[0];
+            points[i][1] += step[1];
+            points[j][0] -= step[0];
+            points[j][1] -= step[1];
+            for _ in 0..NEGATIVE_SAMPLES {
+                let other = (rng.next_u64() % n as u64) as usize;
+                if other == i {
+                    continue;
+                }
+                let (dx, dy, d2) = offset(points[i], points[other]);
+                let push = 2.0 / ((0.001 + d2) * (1.0 + d2));
+                points[i][0] += clip(push * dx) * rate;
+                points[i][1] += clip(push * dy) * rate;
+            }
+        }
+    }
+}
+
+/// Retourne l'écart de `a` à `b` et le carré de leur distance.
+fn offset(a: [f32; 2], b: [f32; 2]) -> (f32, f32, f32) {
+    let (dx, dy) = (a[0] - b[0], a[1] - b[1]);
+    (dx, dy, dx * dx + dy * dy)
+}
+
+fn clip(step: f32) -> f32 {
+    step.clamp(-MAX_STEP, MAX_STEP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel::SearchRuntime;
+    use crate::payload::{Payload, Value};
+    use crate::similarity::Metric;
+    use crate::synthetic::VectorGenerator;
+
+    const METHODS: [Projection2D; 2] = [Projection2D::Pca, Projection2D::RandomThenNeighborRefine];
+
+    /// Deux groupes de 60 documents de dimension 16, autour de centres opposés ; le
+    /// champ `groupe` de chaque document indique le sien.
+    fn clusters(runtime: SearchRuntime) -> Collection {
+        let mut collection = Collection::new()
+            .with_metric(Metric::Euclidean)
+            .with_runtime(runtime);
+        let mut generator = VectorGenerator::new(16, 222);
+        for i in 0..120 {
+            let offset = if i % 2 == 0 { 3.0 } else { -3.0 };
+            let vector: Vec<f32> = generator.vector().iter().map(|x| x + offset).collect();
+            let mut payload = Payload::new();
+            payload.insert("groupe".to_string(), Value::from(i64::from(i % 2)));
+            (collection)
+                .upsert_with_payload(generator.uuid(), vector, payload)
+                .unwrap();
+        }
+        collection
+    }
+
+    fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn projections_have_one_finite_point_per_document() {
+        let collection = clusters(SearchRuntime::new(1));
+        for method in METHODS {
+            let points = collection.project_2d(method, None, 7).unwrap();
+            assert_eq!(points.len(), 120, "{:?}", method);
+            assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert!(points.iter().all(|(_, p)| p.iter().all(|x| x.is_finite())));
+            let sample = collection.project_2d(method, Some(40), 7).unwrap();
+            assert_eq!(sample.len(), 40);
+            assert!(sample.iter().all(|(key, _)| collection.read(key).is_some()));
+        }
+        assert!(Collection::new().project_2d(Projection2D::Pca, None, 7).unwrap().is_empty());
+        assert!(matches!(
+            collection.project_2d(Projection2D::Pca, Some(0), 7),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn projections_are_reproducible_under_a_seed() {
+        let (single, parallel) = (clusters(SearchRuntime::new(1)), clusters(SearchRuntime::new(4)));
+        for method in METHODS {
+            let points = single.project_2d(method, Some(80), 3).unwrap();
+            assert_eq!(single.project_2d(method, Some(80), 3).unwrap(), points);
+            assert_eq!(parallel.project_2d(method, Some(80), 3).unwrap(), points);
+            let other = single.project_2d(method, Some(80), 4).unwrap();
+            assert_ne!(other, points, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn separate_clusters_stay_separate() {
+        let collection = clusters(SearchRuntime::new(1));
+        let group = |key: &Uuid| collection.payload(key).and_then(|p| p.get("groupe")).cloned();
+        for method in METHODS {
+            let points = collection.project_2d(method, None, 11).unwrap();
+            let (mut intra, mut inter) = ((0.0, 0), (0.0, 0));
+            for (i, (a, p)) in points.iter().enumerate() {
+                for (b, q) in &points[i + 1..] {
+                    let sum = match group(a) == group(b) {
+                        true => &mut intra,
+                        false => &mut inter,
+                    };
+                    sum.0 += distance(*p, *q);
+                    sum.1 += 1;
+                }
+            }
+            let (intra, inter) = (intra.0 / intra.1 as f32, inter.0 / inter.1 as f32);
+            assert!(inter > 2.0 * intra, "{:?} : {} {}", method, inter, intra);
+        }
+    }
+
+    #[test]
+    fn projection_csv_lists_coordinates_and_fields() {
+        let collection = clusters(SearchRuntime::new(1));
+        let points = collection.project_2d(Projection2D::Pca, Some(3), 1).unwrap();
+        let path = std::env::temp_dir().join(format!("embeddingproject-layout-{}.csv", Uuid::new_v4()));
+        (collection)
+            .export_projection_csv(&path, &points, &["groupe", "absent"])
+            .unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!((lines.len(), lines[0]), (4, "id,x,y,groupe,absent"));
+        for ((key, [x, y]), line) in points.iter().zip(&lines[1..]) {
+            let fields: Vec<&str> = line.split(',').collect();
+            assert_eq!(fields[..3], [key.to_string(), x.to_string(), y.to_string()]);
+            assert!(matches!(fields[3], "0" | "1") && fields[4].is_empty());
+        }
+    }
+}
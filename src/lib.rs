@@ -0,0 +1,225 @@
+//! Base de données de vecteurs avec recherche par similarité cosinus.
+//!
+//! Une [`BaseDeDonnees`] regroupe des [`Collection`] nommées, chacune associant des
+//! `Uuid` à des vecteurs de `f32`. [`BaseDeDonneesPartagee`] permet de partager une
+//! base entre plusieurs threads. Les fonctions de score sont exposées dans le module
+//! [`similarity`].
+//!
+//! Le module [`prelude`] regroupe les types d'usage courant, le module [`synthetic`]
+//! génère des données de test reproductibles, le module [`golden`] en tire des
+//! classements de référence, le module [`float`] écrit et lit les nombres de toutes les
+//! sorties, le module [`clustering`] affecte des vecteurs à des centroïdes et le module
+//! [`keys`] dérive des clés `Uuid` stables d'identifiants externes. La crate réexporte [`Uuid`] : la
+//! version de `uuid` utilisée (1.x) fait partie de son API publique, et un changement
+//! de version majeure de `uuid` sera traité comme un changement incompatible.
+//!
+//! Tout ce qui précède demande la fonctionnalité `std`, activée par défaut. Sans elle,
+//! la crate se réduit au module [`core`] : les fonctions de score et l'accumulateur des
+//! meilleurs résultats, compilables avec `#![no_std]` et `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod core;
+
+/// Déclare des éléments qui demandent la fonctionnalité `std`.
+macro_rules! with_std {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "std")]
+            $item
+        )*
+    };
+}
+
+with_std! {
+    mod access;
+    mod adapter;
+    #[cfg(feature = "arrow")]
+    mod arrow;
+    mod backup;
+    mod benchmark;
+    mod blob;
+    mod bulk_delete;
+    mod calibration;
+    mod chain;
+    mod changed;
+    mod check;
+    pub mod clustering;
+    mod collection;
+    mod config;
+    mod contents;
+    mod csv;
+    mod database;
+    mod database_config;
+    mod database_snapshot;
+    mod decay;
+    mod dedup;
+    mod diff;
+    mod document_id;
+    #[cfg(feature = "encryption")]
+    mod encryption;
+    mod error;
+    mod export;
+    mod facet;
+    mod filter;
+    mod flat;
+    pub mod float;
+    mod fusion;
+    pub mod golden;
+    mod graph;
+    mod histogram;
+    mod import;
+    mod interop;
+    mod json;
+    mod layout;
+    pub mod keys;
+    mod loader;
+    mod memory;
+    #[cfg(unix)]
+    mod mmap;
+    mod model;
+    mod multi;
+    mod namespace;
+    #[cfg(feature = "object-store")]
+    mod object_store;
+    mod oplog;
+    mod outliers;
+    mod parallel;
+    mod payload;
+    mod payload_index;
+    mod pca;
+    mod pending;
+    pub mod prelude;
+    mod poison;
+    mod preflight;
+    mod privacy;
+    mod progress;
+    mod projection;
+    mod query_expr;
+    mod query_pipeline;
+    mod query_stream;
+    mod relaxed;
+    mod replication;
+    mod request_context;
+    mod rescore;
+    mod retention;
+    mod rng;
+    mod routing;
+    mod rows;
+    mod saved_search;
+    mod schema;
+    mod search;
+    mod segmented;
+    mod self_test;
+    mod shadow;
+    #[cfg(any(feature = "encryption", feature = "object-store"))]
+    mod sha256;
+    mod sharded;
+    mod shared;
+    pub mod similarity;
+    mod snapshot;
+    mod storage;
+    mod store;
+    pub mod synthetic;
+    mod tombstone;
+    mod toml;
+    mod topk;
+    mod transaction;
+    mod unit_norm;
+    mod validation;
+    mod vector_pool;
+    mod vector_stats;
+    mod weights;
+    mod workspace;
+    mod writer;
+
+    pub use access::AccessStats;
+    pub use adapter::QueryAdapter;
+    pub use backup::{
+        list_backups, prune_backups, BackupEntry, BackupManifest, BackupReport, RestoreOptions,
+        BACKUP_MANIFEST,
+    };
+    pub use benchmark::{BenchmarkOptions, BenchmarkReport, BENCHMARK_BUCKETS, BENCHMARK_FIELD};
+    pub use blob::{BlobOffload, BlobReport, BlobStore, FileBlobStore, BLOB_REFERENCE};
+    pub use bulk_delete::{DeleteReport, IdFormat};
+    pub use calibration::Calibration;
+    pub use chain::{ChainHit, ChainResults, CollectionChain};
+    pub use changed::{ChangeSummary, UpsertOutcome};
+    pub use check::{CheckIssue, CheckReport, CollectionCheck, RepairOptions, RepairReport};
+    pub use collection::{Collection, Document, ZeroVectorPolicy};
+    pub use config::{CollectionConfig, CollectionConfigBuilder};
+    pub use contents::{list_snapshot_contents, SnapshotEntry};
+    pub use database::{BaseDeDonnees, CollectionEntry};
+    pub use database_config::{CollectionDeclaration, ConfigReport, DatabaseConfig};
+    pub use database_snapshot::DatabaseSnapshot;
+    pub use decay::{DecayParams, DecayShape, MissingTimestamp};
+    pub use dedup::{DedupOutcome, DedupPolicy};
+    pub use diff::{CollectionDiff, DatabaseDiff, DEFAULT_DIFF_EPSILON};
+    pub use document_id::DocumentId;
+    #[cfg(feature = "encryption")]
+    pub use encryption::{derive_key, KEY_DERIVATION_ITERATIONS};
+    pub use error::{Error, ErrorContext, Operation, Result};
+    pub use export::ExportFormat;
+    pub use facet::EXACT_CARDINALITY_LIMIT;
+    pub use filter::{Filter, FilterPlan, FilterStrategy, INDEX_FIRST_MAX_SELECTIVITY};
+    pub use flat::ExportOrder;
+    pub use fusion::{FusedHit, FusedSource, FusionMethod, RRF_RANK_CONSTANT};
+    pub use histogram::Histogram;
+    pub use interop::{ImportFormat, ImportReport, CHROMA_DOCUMENT_FIELD};
+    pub use layout::Projection2D;
+    pub use loader::CollectionLoader;
+    pub use memory::{CompactReport, MemoryUsage};
+    #[cfg(unix)]
+    pub use mmap::MmapCollection;
+    pub use model::ModelFingerprint;
+    pub use multi::MultiHit;
+    #[cfg(feature = "object-store")]
+    pub use object_store::{ObjectStoreCredentials, UploadReport, OBJECT_PART_BYTES};
+    pub use oplog::{OperationKind, OperationRecord, WriteStats, DEFAULT_OPERATION_LOG_CAPACITY};
+    pub use outliers::{OutlierMethod, OutlierParams};
+    pub use parallel::{AdaptiveChunks, SearchRuntime, DEFAULT_CHUNKED_DIMENSION};
+    pub use payload::{Payload, Value};
+    pub use payload_index::IndexKind;
+    pub use pca::PcaModel;
+    pub use preflight::{Diagnostic, PreflightOptions, Severity};
+    pub use progress::{BatchReport, ChannelProgress, Progress, ProgressSink};
+    pub use projection::Projection;
+    pub use query_expr::{QueryExpr, QueryTerm};
+    pub use query_pipeline::{BuiltinTransform, QueryPipeline, QueryTransform};
+    pub use query_stream::StreamReport;
+    pub use relaxed::{RelaxedResults, Staleness, DEFAULT_MAX_STALE_AGE, DEFAULT_MAX_STALE_OPS};
+    pub use replication::{
+        ChangeOp, ChangeRecord, ChangeRetention, Primary, Replica, ReplicationStatus, ShutdownReport,
+    };
+    pub use request_context::{RequestContext, MAX_REQUEST_ID_LEN};
+    pub use rescore::Rescorer;
+    pub use retention::{RetainedSnapshot, DEFAULT_MAX_RETAINED_SNAPSHOTS};
+    pub use routing::{RoutedHit, RoutedResults};
+    pub use schema::{FieldSchema, FieldType, PayloadSchema, SchemaMode};
+    pub use search::{
+        Group, LowConfidence, Margin, MissingGroupField, ScoreMode, SearchOverrides, SearchParams,
+        SearchResults, SearchTiming, DEFAULT_DEDUP_POOL_FACTOR,
+    };
+    pub use self_test::SelfTestReport;
+    pub use shadow::ShadowReport;
+    pub use sharded::ShardedCollection;
+    pub use shared::{BaseDeDonneesPartagee, CollectionBuilder};
+    pub use similarity::Metric;
+    pub use snapshot::LoadOverrides;
+    #[cfg(feature = "fault-injection")]
+    pub use storage::{Fault, FaultInjector, StorageOp};
+    pub use storage::{FileSystem, Storage, StorageFile};
+    pub use store::{VectorStore, VectorStoreMut};
+    pub use topk::{search_stream, TopK};
+    pub use transaction::{ApplyReport, Op};
+    pub use unit_norm::DEFAULT_UNIT_NORM_TOLERANCE;
+    pub use validation::ValidationReport;
+    pub use vector_pool::StorageStats;
+    pub use vector_stats::DimStats;
+    pub use workspace::{DatabaseStats, Workspace, WorkspaceStats, WORKSPACE_FILE_EXTENSION};
+    pub use writer::{WriteHandle, WriteReport};
+
+    pub use uuid::Uuid;
+}
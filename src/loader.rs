@@ -0,0 +1,256 @@
+//! Chargement initial d'une collection en une fois.
+
+use std::mem;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::config::CollectionConfig;
+use crate::database::BaseDeDonnees;
+use crate::error::Result;
+use crate::oplog::OperationKind;
+use crate::parallel::{SearchRuntime, PARALLEL_THRESHOLD};
+use crate::rows::check_rows;
+
+/// Construit une collection à partir de tous ses documents, plus vite que des
+/// [`Collection::upsert_batch`] successifs.
+///
+/// Les vecteurs sont seulement accumulés, par morceaux de toute taille ; rien n'est
+/// vérifié avant [`CollectionLoader::finish`]. Celle-ci contrôle, projette et normalise
+/// tous les vecteurs en parallèle sur les threads du réglage
+/// ([`CollectionLoader::with_runtime`]), puis range les documents dans une table
+/// dimensionnée une seule fois. La collection obtenue est identique à celle qu'aurait
+/// produite `upsert_batch` sur les mêmes documents, dans le même ordre : mêmes vecteurs
+/// stockés, doublons résolus en faveur du dernier, mêmes résultats de recherche.
+///
+/// Le chargement est tout ou rien : s'il échoue, aucune collection n'est produite.
+#[derive(Debug, Clone)]
+pub struct CollectionLoader {
+    collection: Collection,
+    items: Vec<(Uuid, Vec<f32>)>,
+}
+
+impl CollectionLoader {
+    /// Crée un chargement vide pour une collection configurée par `config`.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration de la collection construite.
+    pub fn new(config: CollectionConfig) -> Self {
+        CollectionLoader::with_capacity(config, 0)
+    }
+
+    /// Crée un chargement vide, avec de la place pour `documents` vecteurs.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration de la collection construite.
+    /// * `documents` - Nombre de documents attendus.
+    pub fn with_capacity(config: CollectionConfig, documents: usize) -> Self {
+        CollectionLoader {
+            collection: Collection::from_config(config),
+            items: Vec::with_capacity(documents),
+        }
+    }
+
+    /// Fixe le réglage des parcours parallèles, utilisé par
+    /// [`CollectionLoader::finish`] puis par la collection construite.
+    ///
+    /// # Arguments
+    /// * `runtime` - Nombre de threads et taille des tranches des parcours.
+    pub fn with_runtime(mut self, runtime: SearchRuntime) -> Self {
+        self.collection.runtime = runtime;
+        self
+    }
+
+    /// Retourne le nombre de vecteurs accumulés, doublons compris.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Indique si aucun vecteur n'a été accumulé.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Ajoute un document.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du document ; un identifiant répété remplace le précédent.
+    /// * `vector` - Vecteur du document.
+    pub fn push(&mut self, key: Uuid, vector: Vec<f32>) {
+        self.items.push((key, vector));
+    }
+
+    /// Ajoute un morceau de documents.
+    ///
+    /// # Arguments
+    /// * `items` - Couples identifiant et vecteur.
+    pub fn push_chunk(&mut self, items: impl IntoIterator<Item = (Uuid, Vec<f32>)>) {
+        self.items.extend(items);
+    }
+
+    /// Ajoute des documents dont les vecteurs sont les lignes d'une matrice contiguë,
+    /// rangée comme pour [`Collection::upsert_rows`].
+    ///
+    /// # Arguments
+    /// * `ids` - Identifiants des documents, un par ligne.
+    /// * `data` - Coordonnées des lignes, mises bout à bout.
+    /// * `dim` - Nombre de coordonnées par ligne.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `dim` est nul ou si `data` ne contient pas
+    ///   exactement `ids.len()` lignes ; rien n'est alors ajouté.
+    pub fn push_rows(&mut self, ids: &[Uuid], data: &[f32], dim: usize) -> Result<()> {
+        check_rows(data, dim, Some(ids.len()))?;
+        let rows = data.chunks_exact(dim).map(<[f32]>::to_vec);
+        self.items.extend(ids.iter().copied().zip(rows));
+        Ok(())
+    }
+
+    /// Construit la collection à partir des documents accumulés.
+    ///
+    /// # Retourne
+    /// * Result<Collection> - La collection, prête pour les recherches.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert`], pour le premier document refusé dans l'ordre
+    ///   d'ajout.
+    pub fn finish(self) -> Result<Collection> {
+        let CollectionLoader {
+            mut collection,
+            mut items,
+        } = self;
+        let count = items.len();
+        collection.logged(
+            OperationKind::Batch,
+            |collection| {
+                if collection.dimension().is_none() {
+                    collection.locked_dimension = items.first().map(|(_, vector)| vector.len());
+                }
+                // Chaque tranche s'arrête à son premier refus : le premier des refus
+                // concaténés dans l'ordre des tranches est celui du premier document.
+                let refused = {
+                    let collection = &*collection;
+                    (collection.runtime).map_chunks_mut(&mut items, PARALLEL_THRESHOLD, |chunk| {
+                        for (key, vector) in chunk {
+                            match collection.prepare_vector(*key, mem::take(vector)) {
+                                Ok(prepared) => *vector = prepared,
+                                Err(error) => return vec![error],
+                            }
+                        }
+                        Vec::new()
+                    })
+                };
+                if let Some(error) = refused.into_iter().next() {
+                    return Err(error);
+                }
+                collection.documents.reserve(count);
+                for (key, vector) in items {
+                    collection.store_vector(key, vector)?;
+                }
+                Ok(())
+            },
+            |result: &Result<()>| result.as_ref().ok().map(|_| count),
+        )?;
+        Ok(collection)
+    }
+
+    /// Construit la collection comme [`CollectionLoader::finish`] et l'installe dans
+    /// `bdd` sous le nom `nom`, avec le réglage des parcours de la base.
+    ///
+    /// # Arguments
+    /// * `bdd` - Base de données qui reçoit la collection.
+    /// * `nom` - Nom de la collection ; une collection existante est remplacée.
+    ///
+    /// # Retourne
+    /// * Result<Option<Collection>> - L'ancienne collection si elle existait.
+    ///
+    /// # Erreurs
+    /// * Celles de [`CollectionLoader::finish`] ; la base n'est alors pas modifiée.
+    pub fn install(self, bdd: &mut BaseDeDonnees, nom: String) -> Result<Option<Collection>> {
+        let collection = self.with_runtime(bdd.runtime()).finish()?;
+        Ok(bdd.replace(nom, collection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::similarity::Metric;
+    use crate::synthetic::VectorGenerator;
+
+    fn normalized() -> CollectionConfig {
+        CollectionConfig::builder()
+            .metric(Metric::Cosine)
+            .normalized(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn loaded_collection_matches_upsert_batch() {
+        let mut generator = VectorGenerator::new(16, 186);
+        let mut items: Vec<(Uuid, Vec<f32>)> = (0..2 * PARALLEL_THRESHOLD)
+            .map(|_| (generator.uuid(), generator.vector()))
+            .collect();
+        // Un doublon se résout en faveur du dernier, comme pour `upsert_batch`.
+        items.push((items[7].0, generator.vector()));
+
+        let mut expected = Collection::from_config(normalized());
+        expected.upsert_batch(items.clone(), None).unwrap();
+        let mut loader = CollectionLoader::with_capacity(normalized(), items.len())
+            .with_runtime(SearchRuntime::new(4));
+        let (head, tail) = items.split_at(1000);
+        loader.push_chunk(head.to_vec());
+        for (key, vector) in tail {
+            loader.push(*key, vector.clone());
+        }
+        let loaded = loader.finish().unwrap();
+        loaded.check_invariants().unwrap();
+
+        assert_eq!(loaded.len(), expected.len());
+        for (key, vector) in &expected.documents {
+            assert!(crate::vector_pool::same_bits(&loaded.documents[key], vector));
+        }
+        for _ in 0..10 {
+            let query = generator.vector();
+            assert_eq!(
+                loaded.search(&query, 20).unwrap(),
+                expected.search(&query, 20).unwrap()
+            );
+        }
+        assert_eq!(loaded.recent_operations().len(), 1);
+    }
+
+    #[test]
+    fn a_refused_document_produces_no_collection() {
+        let mut loader = CollectionLoader::new(CollectionConfig::default());
+        loader.push(Uuid::new_v4(), vec![1.0, 2.0]);
+        loader.push(Uuid::new_v4(), vec![1.0, 2.0, 3.0]);
+        assert!(matches!(
+            loader.finish(),
+            Err(Error::DimensionLocked { .. })
+        ));
+
+        let mut loader = CollectionLoader::new(CollectionConfig::default());
+        assert!(matches!(
+            loader.push_rows(&[Uuid::new_v4()], &[1.0, 2.0, 3.0], 2),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(loader.is_empty());
+    }
+
+    #[test]
+    fn install_replaces_the_named_collection() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.add("docs".to_string());
+        let keys = [Uuid::new_v4(), Uuid::new_v4()];
+        let mut loader = CollectionLoader::new(CollectionConfig::default());
+        loader
+            .push_rows(&keys, &[1.0, 0.0, 0.0, 1.0], 2)
+            .unwrap();
+        let previous = loader.install(&mut bdd, "docs".to_string()).unwrap();
+        assert!(previous.unwrap().is_empty());
+        assert_eq!(bdd.get("docs").unwrap().len(), 2);
+    }
+}
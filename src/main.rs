@@ -1,222 +1,779 @@
-use std::collections::HashMap;
-use std::thread;
-use uuid::Uuid;
-
-/// Type alias pour représenter un document sous forme d'une liste de tuples contenant un `Uuid` et une similarité (f32).
-type Document = Vec<(Uuid, f32)>;
-
-/// Une structure représentant une collection de documents, chaque document est identifié par un `Uuid` et contient un vecteur de f32.
-struct Collection {
-    documents: HashMap<Uuid, Vec<f32>>,
-}
-
-impl Collection {
-    /// Crée une nouvelle instance de `Collection`.
-    fn new() -> Self {
-        Collection {
-            documents: HashMap::new(),
-        }
-    }
-
-    /// Insère ou met à jour un document identifié par `key` avec le vecteur `vector`.
-    ///
-    /// # Arguments
-    /// * `key` - Identifiant unique du document (Uuid).
-    /// * `vector` - Vecteur représentant le document.
-    fn upsert(&mut self, key: Uuid, vector: Vec<f32>) {
-        self.documents.insert(key, vector);
-    }
-
-    /// Lit un document à partir de son `key`.
-    ///
-    /// # Arguments
-    /// * `key` - Référence à l'identifiant unique du document.
-    ///
-    /// # Retourne
-    /// * Option<&Vec<f32>> - Une référence optionnelle au vecteur du document.
-    #[allow(unused)]
-    fn read(&self, key: &Uuid) -> Option<&Vec<f32>> {
-        return self.documents.get(key);
-    }
-
-    /// Supprime un document à partir de son `key`.
-    ///
-    /// # Arguments
-    /// * `key` - Référence à l'identifiant unique du document.
-    #[allow(unused)]
-    fn delete(&mut self, key: &Uuid) {
-        self.documents.remove(key);
-    }
-
-    /// Recherche les `k` documents les plus similaires à la requête donnée en utilisant la similarité cosinus.
-    ///
-    /// # Arguments
-    /// * `request` - Vecteur de requête.
-    /// * `k` - Nombre de résultats à retourner.
-    ///
-    /// # Retourne
-    /// * Document - Liste des `k` documents les plus similaires avec leur similarité.
-    fn search(&self, request: &[f32], k: usize) -> Document {
-        let mut results: Document = self
-            .documents
-            .iter()
-            .filter_map(|(key, vector)| {
-                if vector.len() != request.len() {
-                    return None;
-                }
-                let similarity = cos(request, vector);
-                Some((*key, similarity))
-            })
-            .collect();
-
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        return results.into_iter().take(k).collect();
-    }
-}
-
-/// Une structure représentant une base de données composée de plusieurs collections.
-struct BaseDeDonnees {
-    collections: HashMap<String, Collection>,
-}
-
-impl BaseDeDonnees {
-    /// Crée une nouvelle instance de `BaseDeDonnees`.
-    fn new() -> Self {
-        BaseDeDonnees {
-            collections: HashMap::new(),
-        }
-    }
-
-    /// Ajoute une nouvelle collection à la base de données.
-    ///
-    /// # Arguments
-    /// * `nom` - Nom de la collection.
-    fn add(&mut self, nom: String) {
-        self.collections.insert(nom, Collection::new());
-    }
-
-    /// Récupère une référence immuable à une collection par son nom.
-    ///
-    /// # Arguments
-    /// * `nom` - Nom de la collection.
-    ///
-    /// # Retourne
-    /// * Option<&Collection> - Référence optionnelle à la collection.
-    #[allow(unused)]
-    fn get(&self, nom: &str) -> Option<&Collection> {
-        return self.collections.get(nom);
-    }
-
-    /// Récupère une référence mutable à une collection par son nom.
-    ///
-    /// # Arguments
-    /// * `nom` - Nom de la collection.
-    ///
-    /// # Retourne
-    /// * Option<&mut Collection> - Référence mutable optionnelle à la collection.
-    fn get_mut(&mut self, nom: &str) -> Option<&mut Collection> {
-        return self.collections.get_mut(nom);
-    }
-
-    /// Effectue une recherche dans une collection spécifique.
-    ///
-    /// # Arguments
-    /// * `cname` - Nom de la collection.
-    /// * `request` - Vecteur de requête.
-    /// * `k` - Nombre de résultats à retourner.
-    ///
-    /// # Retourne
-    /// * Option<Document> - Résultats de la recherche dans la collection spécifiée.
-    fn search(&self, cname: &str, request: &[f32], k: usize) -> Option<Document> {
-        return self
-            .collections
-            .get(cname)
-            .map(|collection| collection.search(request, k));
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use embeddingproject::prelude::*;
+use embeddingproject::{
+    float, synthetic, BenchmarkOptions, DatabaseConfig, IdFormat, ImportFormat, IndexKind,
+    PreflightOptions, QueryExpr, RepairOptions, RestoreOptions,
+};
+
+const USAGE: &str = "usage :
+  embeddingProject [--output <format>]          exécute la démonstration
+  embeddingProject diff <fichier_a> <fichier_b> [--json]
+                                                compare deux sauvegardes
+  embeddingProject search <fichier> <collection> <k> <x1,x2,...> [--timing] [--output <format>]
+                                                recherche dans une sauvegarde
+  embeddingProject search <fichier> <collection> <k> --expr <expression> [--timing] [--output <format>]
+                                                recherche par une expression, par exemple
+                                                \"0.7*id:<uuid> + 0.3*[0.1,0.2] - id:<uuid>\"
+  embeddingProject search <fichier> <collection> <k> --stream [--parallel <n>] [--errors <sortie>]
+                                                recherche chaque requête lue sur l'entrée
+                                                standard, un vecteur JSON par ligne, et
+                                                écrit une ligne JSON par requête, dans
+                                                l'ordre, dès qu'elle est calculée
+  embeddingProject facet <fichier> <collection> <champ> <limite> [--output <format>]
+                                                valeurs les plus fréquentes d'un champ
+  embeddingProject check <fichier>              vérifie une sauvegarde
+  embeddingProject self-test <fichier>          charge une sauvegarde puis insère, recherche
+                                                et supprime un document témoin
+  embeddingProject bench [--documents <n>] [--dimension <d>] [--queries <n>] [--k <k>]
+                         [--concurrency <n>] [--filtered] [--index <index>] [--seed <graine>]
+                         [--from <fichier> --collection <nom> --force] [--output json]
+                                                mesure le débit et les centiles des durées
+                                                des recherches sur des documents générés,
+                                                ou sur une collection d'une sauvegarde
+  embeddingProject repair <fichier> [--drop-tail] [--quarantine <fichier>]
+                                                répare une sauvegarde en place
+  embeddingProject import <fichier> <collection> <export> [--format <format d'import>] [--vector <nom>]
+                                                importe un export dans une collection,
+                                                créée si besoin, et enregistre la sauvegarde
+  embeddingProject delete <fichier> <collection> --ids-file <liste> [--ids-format <format de liste>] [--dry-run]
+                                                supprime les documents d'une liste
+                                                d'identifiants et enregistre la sauvegarde ;
+                                                --dry-run affiche le bilan sans rien modifier
+  embeddingProject backup <fichier> --dir <répertoire> [--keep <n>]
+                                                sauvegarde chaque collection dans un
+                                                répertoire horodaté et ne garde que les n
+                                                plus récents
+  embeddingProject restore <fichier> --from <sauvegarde> [--collection <nom>] [--force]
+                                                restaure une sauvegarde, ou une seule de
+                                                ses collections, dans le fichier ; --force
+                                                remplace des collections existantes
+  embeddingProject apply <fichier> --config <déclaration> [--ignore-preflight]
+                                                crée ou met à jour une sauvegarde selon une
+                                                déclaration de collections (TOML ou JSON),
+                                                après les vérifications de doctor
+  embeddingProject doctor <fichier> [--journal <fichier>] [--config <déclaration>]
+                                                vérifie que la sauvegarde pourra être
+                                                chargée et enregistrée, et propose une
+                                                correction pour chaque problème
+
+formats : table (par défaut), json, csv
+formats d'import : jsonl (par défaut), qdrant, chroma ; --vector choisit le vecteur nommé
+                   d'un export qdrant
+sorties des erreurs : stdout, parmi les résultats (par défaut), ou stderr ; une ligne
+                      invalide n'interrompt pas --stream, dont la sortie est toujours JSON
+index : keyword, numeric ; --index indexe le champ « bucket » des documents générés,
+        sur lequel --filtered filtre chaque recherche
+formats de liste : lines, un Uuid par ligne (par défaut), json, un tableau de Uuid
+--config <déclaration> : avec search et facet, vérifie d'abord la sauvegarde contre la
+                         déclaration et crée en mémoire les index déclarés ; avec doctor,
+                         vérifie sa compatibilité avec la sauvegarde
+--ignore-preflight : démarre malgré les problèmes fatals de doctor";
+
+/// Requête de la commande `search`.
+enum Query {
+    /// Vecteur écrit `x1,x2,...`.
+    Vector(Vec<f32>),
+    /// Expression résolue contre la collection.
+    Expr(QueryExpr),
+}
+
+/// Format d'affichage des résultats de recherche.
+#[derive(Clone, Copy)]
+enum Output {
+    Table,
+    Json,
+    Csv,
+}
+
+fn main() -> Result<(), Error> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let output = match take_output(&mut args) {
+        Some(output) => output,
+        None => usage(),
+    };
+    let config = match take_config(&mut args) {
+        Some(config) => config.map(DatabaseConfig::load).transpose()?,
+        None => usage(),
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match (args.as_slice(), config) {
+        ([], None) => demo(output),
+        (["apply", path], Some(config)) => apply(path, config, false),
+        (["apply", path, "--ignore-preflight"], Some(config)) => apply(path, config, true),
+        (_, Some(_)) if !matches!(args.first(), Some(&"search" | &"facet" | &"doctor")) => usage(),
+        (args, config) => run(args, config, output),
+    }
+}
+
+/// Exécute une commande qui accepte l'option `--config`.
+fn run(args: &[&str], config: Option<DatabaseConfig>, output: Output) -> Result<(), Error> {
+    match args {
+        ["diff", a, b] => diff(a, b, false),
+        ["diff", a, b, "--json"] => diff(a, b, true),
+        ["search", path, cname, k, "--expr", expr] => {
+            let Ok(k) = k.parse() else { usage() };
+            search(
+                open(path, config)?,
+                cname,
+                k,
+                Query::Expr(QueryExpr::parse(expr)?),
+                false,
+                output,
+            )
+        }
+        ["search", path, cname, k, "--expr", expr, "--timing"] => {
+            let Ok(k) = k.parse() else { usage() };
+            search(
+                open(path, config)?,
+                cname,
+                k,
+                Query::Expr(QueryExpr::parse(expr)?),
+                true,
+                output,
+            )
+        }
+        ["search", path, cname, k, "--stream", options @ ..] => {
+            let (Ok(k), Some((threads, to_stderr))) = (k.parse(), stream_options(options)) else {
+                usage()
+            };
+            stream(&open(path, config)?, cname, k, threads, to_stderr)
+        }
+        ["search", path, cname, k, vector] => {
+            let Ok(k) = k.parse() else { usage() };
+            search(
+                open(path, config)?,
+                cname,
+                k,
+                Query::Vector(parse_vector(vector)?),
+                false,
+                output,
+            )
+        }
+        ["search", path, cname, k, vector, "--timing"] => {
+            let Ok(k) = k.parse() else { usage() };
+            search(
+                open(path, config)?,
+                cname,
+                k,
+                Query::Vector(parse_vector(vector)?),
+                true,
+                output,
+            )
+        }
+        ["facet", path, cname, field, limit] => {
+            let Ok(limit) = limit.parse() else { usage() };
+            facet(&open(path, config)?, cname, field, limit, output)
+        }
+        ["check", path] => check(path),
+        ["doctor", path] => doctor(path, None, config),
+        ["doctor", path, "--journal", journal] => doctor(path, Some(journal), config),
+        ["self-test", path] => self_test(path),
+        ["bench", options @ ..] => {
+            let Some((from, options)) = bench_options(options) else {
+                usage()
+            };
+            bench(from, &options, output)
+        }
+        ["import", path, cname, source, options @ ..] => {
+            let Some((format, vector)) = import_options(options) else {
+                usage()
+            };
+            import(path, cname, source, format, vector)
+        }
+        ["delete", path, cname, options @ ..] => {
+            let Some((ids, format, dry_run)) = delete_options(options) else {
+                usage()
+            };
+            delete(path, cname, ids, format, dry_run)
+        }
+        ["backup", path, options @ ..] => {
+            let Some((dir, keep)) = backup_options(options) else {
+                usage()
+            };
+            backup(path, dir, keep)
+        }
+        ["restore", path, options @ ..] => {
+            let Some((from, options)) = restore_options(options) else {
+                usage()
+            };
+            restore(path, from, &options)
+        }
+        ["repair", path, options @ ..] => {
+            let Some(options) = repair_options(options) else {
+                usage()
+            };
+            repair(path, &options)
+        }
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+/// Retire l'option `--output <format>` des arguments.
+///
+/// # Retourne
+/// * Option<Output> - Le format demandé, `Output::Table` par défaut, ou `None` si le
+///   format est absent ou inconnu.
+fn take_output(args: &mut Vec<String>) -> Option<Output> {
+    let Some(at) = args.iter().position(|arg| arg == "--output") else {
+        return Some(Output::Table);
+    };
+    let format = args.get(at + 1)?.clone();
+    args.drain(at..at + 2);
+    match format.as_str() {
+        "table" => Some(Output::Table),
+        "json" => Some(Output::Json),
+        "csv" => Some(Output::Csv),
+        _ => None,
+    }
+}
+
+/// Retire l'option `--config <déclaration>` des arguments.
+///
+/// # Retourne
+/// * Option<Option<PathBuf>> - Le chemin de la déclaration s'il y en a une, ou `None` si
+///   l'option n'a pas de valeur.
+fn take_config(args: &mut Vec<String>) -> Option<Option<PathBuf>> {
+    let Some(at) = args.iter().position(|arg| arg == "--config") else {
+        return Some(None);
+    };
+    let path = PathBuf::from(args.get(at + 1)?);
+    args.drain(at..at + 2);
+    Some(Some(path))
+}
+
+/// Charge une sauvegarde et lui applique la déclaration `config`, sans l'enregistrer.
+fn open(path: &str, config: Option<DatabaseConfig>) -> Result<BaseDeDonnees, Error> {
+    let mut bdd = BaseDeDonnees::load(path)?;
+    if let Some(config) = config {
+        bdd.apply_config(config)?;
+    }
+    Ok(bdd)
+}
+
+/// Applique une déclaration de collections à une sauvegarde, créée si elle n'existe
+/// pas, et l'enregistre si elle a changé.
+///
+/// Les vérifications de `doctor` sont affichées d'abord ; un problème fatal arrête la
+/// commande avec le code 1, sauf avec `ignore_preflight`.
+fn apply(path: &str, config: DatabaseConfig, ignore_preflight: bool) -> Result<(), Error> {
+    let diagnostics = BaseDeDonnees::preflight(&PreflightOptions {
+        snapshot: PathBuf::from(path),
+        journal: None,
+        config: Some(config.clone()),
+    });
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if !ignore_preflight && diagnostics.iter().any(|diagnostic| diagnostic.is_fatal()) {
+        eprintln!("démarrage refusé ; --ignore-preflight passe outre");
+        std::process::exit(1);
+    }
+    let exists = Path::new(path).exists();
+    let mut bdd = match exists {
+        true => BaseDeDonnees::load(path)?,
+        false => BaseDeDonnees::new(),
+    };
+    let report = bdd.apply_config(config)?;
+    print!("{}", report);
+    if !exists || !report.is_unchanged() {
+        bdd.save(path)?;
+    }
+    Ok(())
+}
+
+/// Lit un vecteur écrit sous la forme `x1,x2,...`.
+///
+/// # Erreurs
+/// * `Error::Parse` - Si une coordonnée n'est pas un nombre.
+fn parse_vector(text: &str) -> Result<Vec<f32>, Error> {
+    text.split(',').map(|x| float::parse_f32(x, 1)).collect()
+}
+
+/// Affiche des résultats de recherche dans le format demandé.
+fn print_results(results: &SearchResults, output: Output) {
+    match output {
+        Output::Table => print!("{}", results),
+        Output::Json => println!("{}", results.to_json()),
+        Output::Csv => print!("{}", results.to_csv()),
     }
 }
 
-/// Calcule la similarité cosinus entre deux vecteurs parallèlement.
+/// Recherche les `k` documents les plus proches de `query` dans une base sauvegardée.
 ///
 /// # Arguments
-/// * `vector1` - Premier vecteur.
-/// * `vector2` - Deuxième vecteur.
+/// * `bdd` - Base chargée de la sauvegarde.
+/// * `cname` - Nom de la collection, simple pour une expression.
+/// * `k` - Nombre de résultats.
+/// * `query` - Vecteur ou expression de requête.
+/// * `timing` - Vrai pour afficher la durée de chaque étape sur la sortie d'erreur.
+/// * `output` - Format d'affichage.
+fn search(
+    bdd: BaseDeDonnees,
+    cname: &str,
+    k: usize,
+    query: Query,
+    timing: bool,
+    output: Output,
+) -> Result<(), Error> {
+    let params = SearchParams {
+        with_timing: timing,
+        ..SearchParams::new(k)
+    };
+    let results = match query {
+        Query::Vector(vector) => bdd.search_with(cname, vector, &params)?,
+        Query::Expr(expr) => bdd
+            .get(cname)
+            .ok_or_else(|| Error::CollectionNotFound(cname.to_string()))?
+            .search_expr_with(&expr, &params)?,
+    };
+    print_results(&results, output);
+    if let Some(timing) = results.timing {
+        eprintln!("{}", timing);
+    }
+    Ok(())
+}
+
+/// Lit les options de `search --stream`, ou `None` si elles sont invalides.
 ///
 /// # Retourne
-/// * f32 - Similarité cosinus entre les deux vecteurs.
-fn cos(vector1: &[f32], vector2: &[f32]) -> f32 {
-    let produit_scalaire_handle = thread::spawn({
-        let vector1 = vector1.to_vec();
-        let vector2 = vector2.to_vec();
-        move || {
-            vector1
-                .iter()
-                .zip(vector2.iter())
-                .map(|(x, y)| x * y)
-                .sum::<f32>()
+/// * Option<(usize, bool)> - Le nombre de threads, 1 par défaut, et vrai pour écrire
+///   les erreurs sur la sortie d'erreur.
+fn stream_options(args: &[&str]) -> Option<(usize, bool)> {
+    let mut threads = 1;
+    let mut to_stderr = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--parallel" => threads = args.next()?.parse().ok().filter(|n| *n > 0)?,
+            "--errors" => {
+                to_stderr = match *args.next()? {
+                    "stdout" => false,
+                    "stderr" => true,
+                    _ => return None,
+                }
+            }
+            _ => return None,
         }
-    });
+    }
+    Some((threads, to_stderr))
+}
+
+/// Recherche les `k` documents les plus proches de chaque requête lue sur l'entrée
+/// standard et écrit les résultats au fil de l'eau, puis le bilan sur la sortie
+/// d'erreur.
+///
+/// # Arguments
+/// * `bdd` - Base chargée de la sauvegarde.
+/// * `cname` - Nom de la collection.
+/// * `k` - Nombre de résultats par requête.
+/// * `threads` - Nombre de recherches calculées en parallèle.
+/// * `to_stderr` - Vrai pour écrire les lignes en erreur sur la sortie d'erreur.
+fn stream(
+    bdd: &BaseDeDonnees,
+    cname: &str,
+    k: usize,
+    threads: usize,
+    to_stderr: bool,
+) -> Result<(), Error> {
+    let input = BufReader::new(io::stdin());
+    let mut output = BufWriter::new(io::stdout().lock());
+    let mut stderr = io::stderr();
+    let errors: Option<&mut dyn Write> = match to_stderr {
+        true => Some(&mut stderr),
+        false => None,
+    };
+    let report = bdd.search_jsonl(
+        cname,
+        input,
+        &mut output,
+        errors,
+        &SearchParams::new(k),
+        threads,
+    )?;
+    eprintln!("{}", report);
+    Ok(())
+}
 
-    let magnitude1_handle = thread::spawn({
-        let vector1 = vector1.to_vec();
-        move || {
-            let somme_carre1: f32 = vector1.iter().map(|x| x * x).sum();
-            somme_carre1.sqrt()
+/// Affiche les valeurs les plus fréquentes d'un champ de charge utile d'une collection
+/// sauvegardée, avec leur nombre de documents et le nombre de valeurs distinctes.
+///
+/// # Arguments
+/// * `bdd` - Base chargée de la sauvegarde.
+/// * `cname` - Nom de la collection.
+/// * `field` - Nom du champ.
+/// * `limit` - Nombre maximal de valeurs.
+/// * `output` - Format d'affichage.
+fn facet(
+    bdd: &BaseDeDonnees,
+    cname: &str,
+    field: &str,
+    limit: usize,
+    output: Output,
+) -> Result<(), Error> {
+    let collection = bdd
+        .get(cname)
+        .ok_or_else(|| Error::CollectionNotFound(cname.to_string()))?;
+    let facets = collection.facet(field, limit);
+    match output {
+        Output::Table => {
+            println!(
+                "{} valeur(s) distincte(s) pour '{}'",
+                collection.field_cardinality(field),
+                field
+            );
+            for (value, count) in &facets {
+                println!("{:>8}  {}", count, value);
+            }
         }
+        Output::Json => {
+            let items: Vec<String> = facets
+                .iter()
+                .map(|(value, count)| format!("{{\"value\":{},\"count\":{}}}", value, count))
+                .collect();
+            println!("[{}]", items.join(","));
+        }
+        Output::Csv => {
+            println!("value,count");
+            for (value, count) in &facets {
+                let text = value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), str::to_string);
+                println!("{},{}", csv_field(&text), count);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Entoure un champ CSV de guillemets s'il contient un séparateur, un guillemet ou un
+/// saut de ligne.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// Affiche les différences entre deux bases sauvegardées.
+///
+/// # Arguments
+/// * `a` - Chemin de l'ancienne sauvegarde.
+/// * `b` - Chemin de la nouvelle sauvegarde.
+/// * `as_json` - Affiche le résultat en JSON plutôt qu'en résumé lisible.
+fn diff(a: &str, b: &str, as_json: bool) -> Result<(), Error> {
+    let diff = BaseDeDonnees::load(a)?.diff(&BaseDeDonnees::load(b)?);
+    if as_json {
+        println!("{}", diff.to_json());
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+/// Vérifie une sauvegarde ; le processus se termine avec le code 1 si elle a un problème.
+fn check(path: &str) -> Result<(), Error> {
+    let report = BaseDeDonnees::check(path)?;
+    print!("{}", report);
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Affiche les vérifications de démarrage d'une sauvegarde ; le processus se termine
+/// avec le code 1 si l'une d'elles est fatale.
+fn doctor(path: &str, journal: Option<&str>, config: Option<DatabaseConfig>) -> Result<(), Error> {
+    let diagnostics = BaseDeDonnees::preflight(&PreflightOptions {
+        snapshot: PathBuf::from(path),
+        journal: journal.map(PathBuf::from),
+        config,
     });
+    if diagnostics.is_empty() {
+        println!("aucun problème trouvé");
+    }
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_fatal()) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    let magnitude2_handle = thread::spawn({
-        let vector2 = vector2.to_vec();
-        move || {
-            let somme_carre2: f32 = vector2.iter().map(|y| y * y).sum();
-            somme_carre2.sqrt()
+fn self_test(path: &str) -> Result<(), Error> {
+    let report = BaseDeDonnees::load(path)?.self_test()?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// Lit les options de `bench`, ou `None` si elles sont invalides : `--collection`
+/// demande `--from`.
+fn bench_options<'a>(args: &[&'a str]) -> Option<(Option<&'a str>, BenchmarkOptions)> {
+    let mut from = None;
+    let mut options = BenchmarkOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--documents" => options.documents = args.next()?.parse().ok()?,
+            "--dimension" => options.dimension = args.next()?.parse().ok()?,
+            "--queries" => options.queries = args.next()?.parse().ok()?,
+            "--k" => options.k = args.next()?.parse().ok()?,
+            "--concurrency" => options.concurrency = args.next()?.parse().ok()?,
+            "--seed" => options.seed = args.next()?.parse().ok()?,
+            "--filtered" => options.filtered = true,
+            "--index" => {
+                options.index = match *args.next()? {
+                    "keyword" => Some(IndexKind::Keyword),
+                    "numeric" => Some(IndexKind::Numeric),
+                    _ => return None,
+                }
+            }
+            "--from" => from = Some(*args.next()?),
+            "--collection" => options.collection = Some(args.next()?.to_string()),
+            "--force" => options.force = true,
+            _ => return None,
         }
-    });
+    }
+    match (&from, &options.collection) {
+        (None, Some(_)) => None,
+        _ => Some((from, options)),
+    }
+}
 
-    let produit_scalaire = produit_scalaire_handle.join().unwrap();
-    let magnitude1 = magnitude1_handle.join().unwrap();
-    let magnitude2 = magnitude2_handle.join().unwrap();
+/// Mesure les recherches, dans une base vide ou dans la sauvegarde `from`, et affiche
+/// les mesures : en JSON avec `--output json`, sinon en texte.
+fn bench(from: Option<&str>, options: &BenchmarkOptions, output: Output) -> Result<(), Error> {
+    let bdd = match from {
+        Some(path) => BaseDeDonnees::load(path)?,
+        None => BaseDeDonnees::new(),
+    };
+    let report = bdd.benchmark(options)?;
+    match output {
+        Output::Json => println!("{}", report.to_json()),
+        Output::Table | Output::Csv => println!("{}", report),
+    }
+    Ok(())
+}
 
-    if magnitude1 == 0.0 || magnitude2 == 0.0 {
-        0.0
-    } else {
-        produit_scalaire / (magnitude1 * magnitude2)
+/// Lit les options de `import`, ou `None` si elles sont invalides.
+fn import_options<'a>(args: &[&'a str]) -> Option<(ImportFormat, Option<&'a str>)> {
+    let mut format = ImportFormat::Jsonl;
+    let mut vector = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--format" => {
+                format = match *args.next()? {
+                    "jsonl" => ImportFormat::Jsonl,
+                    "qdrant" => ImportFormat::Qdrant,
+                    "chroma" => ImportFormat::Chroma,
+                    _ => return None,
+                }
+            }
+            "--vector" => vector = Some(*args.next()?),
+            _ => return None,
+        }
+    }
+    match (format, vector) {
+        (ImportFormat::Qdrant, _) | (_, None) => Some((format, vector)),
+        _ => None,
     }
 }
 
-fn main() {
-    let mut bdd = BaseDeDonnees::new();
+/// Importe le fichier `source` dans la collection `cname` d'une sauvegarde, créées si
+/// elles n'existent pas, puis enregistre la sauvegarde.
+///
+/// # Arguments
+/// * `path` - Chemin de la sauvegarde.
+/// * `cname` - Nom de la collection.
+/// * `source` - Chemin du fichier importé.
+/// * `format` - Format du fichier importé.
+/// * `vector` - Vecteur nommé d'un export Qdrant.
+fn import(
+    path: &str,
+    cname: &str,
+    source: &str,
+    format: ImportFormat,
+    vector: Option<&str>,
+) -> Result<(), Error> {
+    let mut bdd = match Path::new(path).exists() {
+        true => BaseDeDonnees::load(path)?,
+        false => BaseDeDonnees::new(),
+    };
+    let collection = bdd.get_or_create(cname)?;
+    let file = std::fs::File::open(source)?;
+    let report = match vector {
+        Some(name) => collection.import_qdrant(file, Some(name), None)?,
+        None => collection.import_from(file, format, None)?,
+    };
+    println!("{}", report);
+    bdd.save(path)?;
+    Ok(())
+}
 
-    bdd.add("ICC".to_string());
-    bdd.add("IA".to_string());
+/// Lit les options de `delete`, ou `None` si elles sont invalides.
+fn delete_options<'a>(args: &[&'a str]) -> Option<(&'a str, IdFormat, bool)> {
+    let mut ids = None;
+    let mut format = IdFormat::PlainUuidPerLine;
+    let mut dry_run = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--ids-file" => ids = Some(*args.next()?),
+            "--ids-format" => {
+                format = match *args.next()? {
+                    "lines" => IdFormat::PlainUuidPerLine,
+                    "json" => IdFormat::Json,
+                    _ => return None,
+                }
+            }
+            "--dry-run" => dry_run = true,
+            _ => return None,
+        }
+    }
+    Some((ids?, format, dry_run))
+}
 
-    if let Some(collection) = bdd.get_mut("ICC") {
-        collection.upsert(Uuid::new_v4(), vec![12.0, 72.0, 63.0]);
-        collection.upsert(Uuid::new_v4(), vec![24.0, 45.0, 36.0]);
+/// Supprime de la collection `cname` d'une sauvegarde les documents listés dans le
+/// fichier `ids`, puis enregistre la sauvegarde, sauf pour une simulation.
+fn delete(
+    path: &str,
+    cname: &str,
+    ids: &str,
+    format: IdFormat,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut bdd = BaseDeDonnees::load(path)?;
+    let collection = bdd
+        .get_mut(cname)
+        .ok_or_else(|| Error::CollectionNotFound(cname.to_string()))?;
+    let reader = BufReader::new(std::fs::File::open(ids)?);
+    let report = collection.delete_from_reader(reader, format, dry_run)?;
+    println!("{}", report);
+    if !dry_run {
+        bdd.save(path)?;
     }
+    Ok(())
+}
 
-    if let Some(collection) = bdd.get_mut("IA") {
-        collection.upsert(Uuid::new_v4(), vec![14.0, 30.0, 60.0]);
-        collection.upsert(Uuid::new_v4(), vec![10.0, 12.0, 100.0]);
+/// Lit les options de `backup`, ou `None` si elles sont invalides.
+fn backup_options<'a>(args: &[&'a str]) -> Option<(&'a str, Option<usize>)> {
+    let mut dir = None;
+    let mut keep = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--dir" => dir = Some(*args.next()?),
+            "--keep" => keep = Some(args.next()?.parse().ok()?),
+            _ => return None,
+        }
     }
+    Some((dir?, keep))
+}
 
-    let requeste = vec![41.0, 51.0, 31.0];
-    if let Some(results) = bdd.search("ICC", &requeste, 3) {
-        display(results, "ICC");
+fn backup(path: &str, dir: &str, keep: Option<usize>) -> Result<(), Error> {
+    let report = BaseDeDonnees::load(path)?.backup(dir, keep)?;
+    print!("{}", report);
+    Ok(())
+}
+
+/// Lit les options de `restore`, ou `None` si elles sont invalides.
+fn restore_options<'a>(args: &[&'a str]) -> Option<(&'a str, RestoreOptions)> {
+    let mut from = None;
+    let mut options = RestoreOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--from" => from = Some(*args.next()?),
+            "--collection" => options.collection = Some(args.next()?.to_string()),
+            "--force" => options.force = true,
+            _ => return None,
+        }
     }
+    Some((from?, options))
+}
 
-    if let Some(results) = bdd.search("IA", &requeste, 3) {
-        display(results, "IA");
+/// Restaure la sauvegarde `from` dans la sauvegarde `path`, créée si elle n'existe pas,
+/// puis l'enregistre.
+fn restore(path: &str, from: &str, options: &RestoreOptions) -> Result<(), Error> {
+    let mut bdd = match Path::new(path).exists() {
+        true => BaseDeDonnees::load(path)?,
+        false => BaseDeDonnees::new(),
+    };
+    let manifest = bdd.restore_backup(from, options)?;
+    match &options.collection {
+        Some(nom) => println!("collection '{}' restaurée depuis {}", nom, from),
+        None => println!(
+            "{} collection(s) restaurée(s) depuis {}",
+            manifest.entries.len(),
+            from
+        ),
     }
+    bdd.save(path)?;
+    Ok(())
 }
 
-/// Affiche les résultats de la recherche.
+/// Lit les options de `repair`, ou `None` si elles sont invalides.
+fn repair_options(args: &[&str]) -> Option<RepairOptions> {
+    let mut options = RepairOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--drop-tail" => options.drop_unreadable_tail = true,
+            "--quarantine" => options.quarantine = Some(PathBuf::from(args.next()?)),
+            _ => return None,
+        }
+    }
+    Some(options)
+}
+
+fn repair(path: &str, options: &RepairOptions) -> Result<(), Error> {
+    let report = BaseDeDonnees::repair(path, options)?;
+    print!("{}", report.check);
+    println!(
+        "{} document(s) en quarantaine, {} charge(s) utile(s) convertie(s){}",
+        report.quarantined,
+        report.coerced,
+        match (report.rewritten, report.dropped_tail) {
+            (false, _) => ", sauvegarde inchangée",
+            (true, true) => ", partie illisible abandonnée",
+            (true, false) => "",
+        }
+    );
+    Ok(())
+}
+
+/// Exécute la démonstration : deux collections de documents aléatoires reproductibles
+/// et une recherche dans chacune.
 ///
-/// # Arguments
-/// * `results` - Liste des documents avec leur similarité.
-/// * `cname` - Nom de la collection où la recherche a été effectuée.
-fn display(results: Document, cname: &str) {
-    println!("Résultats de la recherche dans la collection '{}' :", cname);
-    for (key, similarity) in results {
-        println!("UUID : {}, Similarité : {}", key, similarity);
+/// Hors du format tableau, seuls les résultats sont affichés, une recherche après
+/// l'autre : la sortie JSON compte une ligne par collection.
+fn demo(output: Output) -> Result<(), Error> {
+    let mut bdd = BaseDeDonnees::new();
+
+    bdd.add("ICC".to_string());
+    bdd.add("IA".to_string());
+
+    synthetic::populate_collection(&mut bdd["ICC"], 100, 3, 1);
+    synthetic::populate_collection(&mut bdd["IA"], 100, 3, 2);
+
+    let requeste = synthetic::random_vectors(1, 3, 3).remove(0);
+    if let Output::Table = output {
+        print!("{}", bdd.summary());
+    }
+    for cname in ["ICC", "IA"] {
+        let results = bdd.search_with(cname, &requeste, &SearchParams::new(3))?;
+        if let Output::Table = output {
+            println!("Résultats de la recherche dans la collection '{}' :", cname);
+        }
+        print_results(&results, output);
     }
+    Ok(())
 }
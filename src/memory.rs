@@ -0,0 +1,535 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::iter::Sum;
+use std::mem;
+use std::ops::Add;
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::payload::{Payload, Value};
+use crate::segmented::SegmentedMap;
+use crate::tombstone::Tombstone;
+
+/// Mémoire occupée par une collection, en octets, par type de données.
+///
+/// Les tailles sont calculées à partir du contenu : coordonnées et identifiants des
+/// vecteurs, clés et valeurs des charges utiles, entrées des index. La surcharge des
+/// tables de hachage et de l'allocateur n'est pas comptée ; l'occupation réelle du
+/// processus est donc un peu supérieure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    /// Vecteurs des documents avec leur `Uuid`, y compris ceux supprimés en attente de
+    /// compactage.
+    pub vectors: usize,
+    /// Charges utiles, y compris celles des documents supprimés en attente de compactage.
+    pub payloads: usize,
+    /// Index de charge utile.
+    pub indexes: usize,
+}
+
+impl MemoryUsage {
+    /// Retourne le nombre total d'octets.
+    pub fn total(&self) -> usize {
+        self.vectors + self.payloads + self.indexes
+    }
+}
+
+/// Bilan de [`BaseDeDonnees::compact_all`](crate::BaseDeDonnees::compact_all).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompactReport {
+    /// Nombre de collections compactées.
+    pub collections: u64,
+    /// Octets rendus : documents supprimés en attente de compactage et place réservée
+    /// inutilisée des tables ([`Collection::unused_capacity_bytes`]).
+    pub bytes_reclaimed: usize,
+    /// Vrai si l'opération a été interrompue par
+    /// [`ProgressSink::should_cancel`](crate::ProgressSink::should_cancel).
+    pub cancelled: bool,
+}
+
+impl Add for MemoryUsage {
+    type Output = MemoryUsage;
+
+    fn add(self, other: MemoryUsage) -> MemoryUsage {
+        MemoryUsage {
+            vectors: self.vectors + other.vectors,
+            payloads: self.payloads + other.payloads,
+            indexes: self.indexes + other.indexes,
+        }
+    }
+}
+
+impl Sum for MemoryUsage {
+    fn sum<I: Iterator<Item = MemoryUsage>>(iter: I) -> MemoryUsage {
+        iter.fold(MemoryUsage::default(), Add::add)
+    }
+}
+
+/// Limite de mémoire d'une base appliquée à l'une de ses collections : `limit` octets
+/// pour toute la base, dont `others` occupés par les autres collections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Budget {
+    pub(crate) limit: usize,
+    pub(crate) others: usize,
+}
+
+/// Part de la limite de mémoire laissée à une collection rangée dans une base.
+///
+/// Une copie de la collection, qui ne fait plus partie de la base, n'en hérite pas.
+#[derive(Debug, Default)]
+pub(crate) struct BudgetSlot(pub(crate) Option<Budget>);
+
+impl Clone for BudgetSlot {
+    fn clone(&self) -> Self {
+        BudgetSlot(None)
+    }
+}
+
+/// Ordre des dernières écritures des documents, pour évincer le moins récemment écrit.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Recency {
+    stamps: HashMap<Uuid, u64>,
+    order: BTreeMap<u64, Uuid>,
+    next: u64,
+}
+
+impl Recency {
+    /// Marque le document `key` comme le plus récemment écrit.
+    pub(crate) fn touch(&mut self, key: Uuid) {
+        self.remove(&key);
+        self.stamps.insert(key, self.next);
+        self.order.insert(self.next, key);
+        self.next += 1;
+    }
+
+    pub(crate) fn remove(&mut self, key: &Uuid) {
+        if let Some(stamp) = self.stamps.remove(key) {
+            self.order.remove(&stamp);
+        }
+    }
+
+    /// Retourne le document le moins récemment écrit autre que `keep`.
+    fn oldest(&self, keep: &Uuid) -> Option<Uuid> {
+        self.order.values().find(|key| *key != keep).copied()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.stamps.clear();
+        self.order.clear();
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.stamps.shrink_to_fit();
+    }
+
+    /// Indique si exactement les documents de `documents` sont suivis.
+    fn tracks(&self, documents: &SegmentedMap<Arc<Vec<f32>>>) -> bool {
+        self.stamps.len() == documents.len()
+            && self.order.len() == self.stamps.len()
+            && self.stamps.keys().all(|key| documents.contains_key(key))
+    }
+}
+
+/// Octets réservés par une table de hachage au-delà de ses entrées.
+pub(crate) fn unused_table_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    (map.capacity() - map.len()) * mem::size_of::<(K, V)>()
+}
+
+/// Octets réservés par un ensemble au-delà de ses éléments.
+pub(crate) fn unused_set_bytes<T>(set: &HashSet<T>) -> usize {
+    (set.capacity() - set.len()) * mem::size_of::<T>()
+}
+
+/// Octets d'un vecteur stocké avec son identifiant.
+pub(crate) fn vector_bytes(vector: &[f32]) -> usize {
+    mem::size_of::<Uuid>() + mem::size_of_val(vector)
+}
+
+/// Octets des clés et valeurs d'une charge utile.
+pub(crate) fn payload_bytes(payload: &Payload) -> usize {
+    payload
+        .iter()
+        .map(|(field, value)| field.len() + value_bytes(value))
+        .sum()
+}
+
+/// Octets d'une valeur de charge utile : 1 pour `null` et les booléens, 8 pour les
+/// nombres, la longueur UTF-8 des chaînes et la somme des éléments des conteneurs.
+pub(crate) fn value_bytes(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) => 1,
+        Value::Number(_) => mem::size_of::<f64>(),
+        Value::String(s) => s.len(),
+        Value::Array(items) => items.iter().map(value_bytes).sum(),
+        Value::Object(fields) => payload_bytes(fields),
+    }
+}
+
+impl Collection {
+    /// Retourne la mémoire occupée par la collection.
+    ///
+    /// Le décompte est tenu à jour à chaque modification, sans parcourir la collection.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory
+    }
+
+    /// Choisit le comportement de la collection lorsqu'une insertion dépasserait la
+    /// limite de mémoire de sa base ([`BaseDeDonnees::set_memory_limit`]).
+    ///
+    /// Par défaut, l'insertion échoue avec `Error::MemoryLimitExceeded`. Avec l'éviction,
+    /// la collection compacte d'abord ses documents supprimés en attente, puis supprime
+    /// ses documents les moins récemment insérés ou mis à jour jusqu'à ce que le nouveau
+    /// vecteur tienne ; elle ne touche jamais aux autres collections. L'ordre des
+    /// écritures n'est pas sauvegardé : après un chargement, les documents sont évincés
+    /// par `Uuid` croissant avant ceux écrits depuis.
+    ///
+    /// # Arguments
+    /// * `evict` - Vrai pour évincer des documents plutôt que refuser l'insertion.
+    ///
+    /// [`BaseDeDonnees::set_memory_limit`]: crate::BaseDeDonnees::set_memory_limit
+    pub fn with_memory_eviction(mut self, evict: bool) -> Self {
+        self.config.memory_eviction = evict;
+        self.reset_recency();
+        self
+    }
+
+    /// Indique si la collection évince des documents pour respecter la limite de mémoire.
+    pub fn memory_eviction(&self) -> bool {
+        self.config.memory_eviction
+    }
+
+    /// Rend à l'allocateur la place réservée inutilisée de la collection, par exemple
+    /// après la suppression d'une grande partie de ses documents : les tables sont
+    /// reconstruites à leur taille actuelle et les vecteurs ramenés à leur longueur.
+    ///
+    /// Les index de charge utile sont réduits en place et restent à jour. Les documents
+    /// supprimés en attente de compactage sont conservés ([`Collection::compact`] les
+    /// efface). Le contenu de la collection et [`Collection::memory_usage`], qui ne
+    /// compte que le contenu, ne changent pas.
+    ///
+    /// # Retourne
+    /// * usize - Octets rendus, au sens de [`Collection::unused_capacity_bytes`].
+    pub fn shrink_to_fit(&mut self) -> usize {
+        let before = self.unused_capacity_bytes();
+        for vector in self.documents.values_mut() {
+            // Un vecteur partagé avec une copie de la collection ou un appelant n'est
+            // pas réalloué.
+            if let Some(vector) = Arc::get_mut(vector) {
+                vector.shrink_to_fit();
+            }
+        }
+        self.documents.shrink_to_fit();
+        self.payloads.shrink_to_fit();
+        self.aliases.shrink_to_fit();
+        self.tombstones.shrink_to_fit();
+        self.indexes
+            .values_mut()
+            .for_each(|index| index.shrink_to_fit());
+        self.indexes.shrink_to_fit();
+        self.namespaces.shrink_to_fit();
+        self.recency.shrink_to_fit();
+        self.pool.shrink_to_fit();
+        before.saturating_sub(self.unused_capacity_bytes())
+    }
+
+    /// Retourne les octets réservés par la collection sans être utilisés : place libre
+    /// de ses tables de hachage et capacité de ses vecteurs au-delà de leur longueur.
+    ///
+    /// Comme [`MemoryUsage`], le décompte ignore la surcharge propre des tables et de
+    /// l'allocateur ; il parcourt toute la collection.
+    pub fn unused_capacity_bytes(&self) -> usize {
+        let vectors: usize = (self.documents.values())
+            .map(|vector| (vector.capacity() - vector.len()) * mem::size_of::<f32>())
+            .sum();
+        vectors
+            + self.documents.unused_bytes()
+            + self.payloads.unused_bytes()
+            + self.aliases.unused_bytes()
+            + self.tombstones.unused_bytes()
+            + unused_table_bytes(&self.indexes)
+            + (self.indexes.values())
+                .map(|index| index.unused_bytes())
+                .sum::<usize>()
+            + self.namespaces.unused_bytes()
+            + unused_table_bytes(&self.recency.stamps)
+            + self.pool.unused_bytes()
+    }
+
+    /// Recalcule la mémoire occupée en parcourant toute la collection.
+    pub(crate) fn measure_memory(&self) -> MemoryUsage {
+        let tombstones = self.tombstones.values();
+        MemoryUsage {
+            vectors: self
+                .documents
+                .values()
+                .map(|v| vector_bytes(v))
+                .sum::<usize>()
+                + tombstones
+                    .clone()
+                    .map(|t| vector_bytes(&t.vector))
+                    .sum::<usize>(),
+            payloads: self.payloads.values().map(payload_bytes).sum::<usize>()
+                + self.pending.values().map(payload_bytes).sum::<usize>()
+                + tombstones
+                    .filter_map(|t| t.payload.as_ref())
+                    .map(payload_bytes)
+                    .sum::<usize>(),
+            indexes: self.indexes.values().map(|index| index.bytes()).sum(),
+        }
+    }
+
+    /// Reprend l'ordre des écritures à zéro : les documents présents sont classés par
+    /// `Uuid` croissant.
+    pub(crate) fn reset_recency(&mut self) {
+        self.recency.clear();
+        if !self.config.memory_eviction {
+            return;
+        }
+        let mut keys: Vec<Uuid> = self.documents.keys().copied().collect();
+        keys.sort_unstable();
+        keys.into_iter().for_each(|key| self.recency.touch(key));
+    }
+
+    /// Stocke le vecteur déjà préparé du document `key`, en respectant la limite de
+    /// mémoire de la base.
+    ///
+    /// # Retourne
+    /// * Result<Option<Vec<f32>>> - L'ancien vecteur du document, s'il existait.
+    ///
+    /// # Erreurs
+    /// * `Error::MemoryLimitExceeded` - Si le vecteur ne tient pas dans la limite et que
+    ///   la collection n'évince pas de documents, ou n'en a plus à évincer.
+    pub(crate) fn store_vector(
+        &mut self,
+        key: Uuid,
+        vector: Vec<f32>,
+    ) -> Result<Option<Arc<Vec<f32>>>> {
+        if let Some(budget) = self.budget.0 {
+            while let Some(required) = self.required_bytes(&key, &vector, budget) {
+                if !self.config.memory_eviction {
+                    return Err(Error::MemoryLimitExceeded {
+                        limit: budget.limit,
+                        required,
+                    });
+                }
+                if !self.tombstones.is_empty() {
+                    self.compact();
+                    continue;
+                }
+                let Some(oldest) = self.recency.oldest(&key) else {
+                    return Err(Error::MemoryLimitExceeded {
+                        limit: budget.limit,
+                        required,
+                    });
+                };
+                self.delete(&oldest);
+                self.discard_tombstone(&oldest);
+            }
+        }
+        self.discard_tombstone(&key);
+        self.memory.vectors += vector_bytes(&vector);
+        self.stats.add(&vector);
+        let vector = self.share_vector(vector);
+        let previous = self.documents.insert(key, vector);
+        if let Some(payload) = self.take_pending(&key) {
+            self.put_payload(key, payload);
+        }
+        self.operations.stats.upserts += 1;
+        self.saved.record(key);
+        if let Some(previous) = &previous {
+            self.memory.vectors -= vector_bytes(previous);
+            self.forget_stats(previous);
+        }
+        if self.config.memory_eviction {
+            self.recency.touch(key);
+        }
+        Ok(previous)
+    }
+
+    /// Retourne l'occupation de la base si le vecteur de `key` était remplacé par
+    /// `vector`, ou `None` si elle respecte la limite.
+    fn required_bytes(&self, key: &Uuid, vector: &[f32], budget: Budget) -> Option<usize> {
+        let freed = self.documents.get(key).map_or(0, |v| vector_bytes(v))
+            + self.tombstones.get(key).map_or(0, tombstone_bytes);
+        let required =
+            (budget.others + self.memory.total() + vector_bytes(vector)).saturating_sub(freed);
+        (required > budget.limit).then_some(required)
+    }
+
+    /// Supprime définitivement la pierre tombale du document `key`, s'il en a une.
+    pub(crate) fn discard_tombstone(&mut self, key: &Uuid) -> Option<Tombstone> {
+        let tombstone = self.tombstones.remove(key)?;
+        self.memory.vectors -= vector_bytes(&tombstone.vector);
+        if let Some(payload) = &tombstone.payload {
+            self.memory.payloads -= payload_bytes(payload);
+        }
+        Some(tombstone)
+    }
+
+    /// Indique si le décompte de la mémoire et l'ordre des écritures correspondent au
+    /// contenu de la collection.
+    pub(crate) fn memory_is_consistent(&self) -> bool {
+        self.memory == self.measure_memory()
+            && match self.config.memory_eviction {
+                true => self.recency.tracks(&self.documents),
+                false => self.recency.stamps.is_empty(),
+            }
+    }
+}
+
+/// Octets d'un document supprimé en attente de compactage.
+fn tombstone_bytes(tombstone: &Tombstone) -> usize {
+    vector_bytes(&tombstone.vector) + tombstone.payload.as_ref().map_or(0, payload_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::filter::Filter;
+    use crate::payload_index::IndexKind;
+    use crate::search::SearchParams;
+    use crate::shared::BaseDeDonneesPartagee;
+
+    fn payload(titre: &str) -> Payload {
+        let mut payload = Payload::new();
+        payload.insert("titre".to_string(), Value::String(titre.to_string()));
+        payload
+    }
+
+    /// Vérifie que le décompte tenu au fil des écritures égale le décompte recalculé.
+    fn check(collection: &Collection, step: &str) {
+        assert_eq!(collection.memory_usage(), collection.measure_memory(), "{}", step);
+        assert!(collection.memory_is_consistent(), "{}", step);
+    }
+
+    #[test]
+    fn accounting_follows_every_write() {
+        for soft_delete in [false, true] {
+            let mut collection = Collection::new()
+                .with_soft_delete(soft_delete)
+                .with_memory_eviction(true);
+            collection.create_payload_index("titre", IndexKind::Keyword);
+            let keys: Vec<Uuid> = (0..6).map(Uuid::from_u128).collect();
+            for (i, key) in keys.iter().enumerate() {
+                collection
+                    .upsert_with_payload(*key, [1.0, i as f32], payload(&"x".repeat(i)))
+                    .unwrap();
+            }
+            check(&collection, "insertions");
+            assert_eq!(collection.memory_usage().vectors, 6 * vector_bytes(&[0.0; 2]));
+            collection.upsert(keys[0], [2.0, 2.0]).unwrap();
+            collection.set_payload(&keys[1], payload("un titre plus long"));
+            collection.patch_vector(keys[2], 1, &[5.0]).unwrap();
+            check(&collection, "mises à jour");
+            collection.delete(&keys[3]);
+            collection.delete(&keys[4]);
+            check(&collection, "suppressions");
+            collection.restore(&keys[4]);
+            collection.compact();
+            check(&collection, "compactage");
+            collection.drop_payload_index("titre");
+            assert_eq!(collection.memory_usage().indexes, 0);
+            collection.shrink_to_fit();
+            check(&collection, "index retiré");
+            collection.clear(false);
+            check(&collection, "vidage");
+            assert_eq!(collection.memory_usage().total(), 0);
+        }
+    }
+
+    #[test]
+    fn the_database_limit_refuses_or_evicts() {
+        let per_document = vector_bytes(&[0.0; 4]);
+        let mut bdd = BaseDeDonnees::new();
+        bdd.set_memory_limit(Some(3 * per_document + 10));
+        bdd.add("docs".to_string());
+        let docs = bdd.get_mut("docs").unwrap();
+        for i in 0..3 {
+            docs.upsert(Uuid::from_u128(i), [1.0; 4]).unwrap();
+        }
+        assert!(matches!(
+            docs.upsert(Uuid::from_u128(3), [1.0; 4]),
+            Err(Error::MemoryLimitExceeded { limit, required })
+                if limit == 3 * per_document + 10 && required == 4 * per_document
+        ));
+        assert_eq!(docs.len(), 3);
+        // Remplacer un vecteur ne change pas l'occupation.
+        docs.upsert(Uuid::from_u128(0), [2.0; 4]).unwrap();
+        assert_eq!(bdd.memory_usage().vectors, 3 * per_document);
+
+        let mut bdd = BaseDeDonnees::new();
+        bdd.set_memory_limit(Some(3 * per_document));
+        bdd.replace("lru".to_string(), Collection::new().with_memory_eviction(true));
+        let lru = bdd.get_mut("lru").unwrap();
+        for i in 0..5 {
+            lru.upsert(Uuid::from_u128(i), [1.0; 4]).unwrap();
+        }
+        assert_eq!(lru.len(), 3);
+        assert!(lru.read(&Uuid::from_u128(1)).is_none());
+        assert!(lru.read(&Uuid::from_u128(2)).is_some());
+        lru.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn shrinking_after_bulk_deletes_returns_most_of_the_reserve() {
+        let mut collection = Collection::new();
+        collection.create_payload_index("titre", IndexKind::Keyword);
+        let keys: Vec<Uuid> = (0..10_000).map(Uuid::from_u128).collect();
+        for (i, key) in keys.iter().enumerate() {
+            let titre = format!("t{}", i % 10);
+            collection
+                .upsert_with_payload(*key, [1.0, i as f32], payload(&titre))
+                .unwrap();
+        }
+        let full = collection.unused_capacity_bytes() + collection.memory_usage().total();
+        for key in &keys[..8000] {
+            collection.delete(key);
+        }
+        let usage = collection.memory_usage();
+        let unused = collection.unused_capacity_bytes();
+        assert!(unused > usage.total() / 2, "{} octets inutilisés", unused);
+
+        let reclaimed = collection.shrink_to_fit();
+        assert_eq!(reclaimed, unused - collection.unused_capacity_bytes());
+        assert!(collection.unused_capacity_bytes() < unused / 4);
+        assert!(usage.total() + collection.unused_capacity_bytes() < full / 3);
+        check(&collection, "réduction");
+        collection.check_invariants().unwrap();
+        // L'index réduit reste à jour.
+        let filter = Filter::eq("titre", "t3");
+        let params = SearchParams::new(1000);
+        let hits = collection.search_filtered([1.0, 0.0], &filter, &params).unwrap().hits;
+        assert_eq!(hits.len(), 200);
+    }
+
+    #[test]
+    fn compact_all_reports_the_reclaimed_bytes() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("a".to_string(), Collection::new().with_soft_delete(true));
+        bdd.add("b".to_string());
+        for nom in ["a", "b"] {
+            let collection = bdd.get_mut(nom).unwrap();
+            for i in 0..2000 {
+                collection.upsert(Uuid::from_u128(i), [1.0, i as f32]).unwrap();
+            }
+            for i in 0..1800 {
+                collection.delete(&Uuid::from_u128(i));
+            }
+        }
+        let shared = BaseDeDonneesPartagee::new(bdd);
+        let sequence = shared.read().sequence();
+        let report = shared.compact_all(None);
+        assert_eq!((report.collections, report.cancelled), (2, false));
+        assert!(report.bytes_reclaimed > 1800 * vector_bytes(&[0.0; 2]));
+        let bdd = shared.read();
+        // Seul le compactage des documents supprimés de `a` modifie le contenu.
+        assert_eq!(bdd.sequence(), sequence + 1);
+        assert_eq!(bdd.get("a").unwrap().tombstone_count(), 0);
+        assert_eq!(bdd.get("b").unwrap().len(), 200);
+        // Les tables gardent au plus la marge de leur capacité arrondie.
+        assert!(bdd.get("a").unwrap().unused_capacity_bytes() < 200 * vector_bytes(&[0.0; 2]));
+    }
+}
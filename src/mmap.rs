@@ -0,0 +1,453 @@
+//! Collections en lecture seule projetées en mémoire depuis un fichier.
+//!
+//! Format (en-tête de 64 octets, entiers dans le boutisme de la machine qui a écrit le
+//! fichier, indiqué par le marqueur d'ordre des octets) :
+//!
+//! ```text
+//!  0  MAGIC (8 octets)
+//!  8  version: u32
+//! 12  marqueur d'ordre des octets: u32 = 0x01020304
+//! 16  mesure: u8 | politique des vecteurs nuls: u8 | normalisation: u8 | 5 octets réservés
+//! 24  nombre de documents: u64
+//! 32  dimension: u64
+//! 40  position de la table des identifiants: u64
+//! 48  position du bloc de vecteurs: u64, multiple de 64
+//! 56  réservé: u64
+//! ```
+//!
+//! La table des identifiants contient les `Uuid` triés par ordre croissant, 16 octets
+//! chacun. Le bloc de vecteurs contient, dans le même ordre, `dimension` flottants `f32`
+//! par document. Le bloc étant aligné sur 64 octets dans un fichier projeté à une
+//! adresse alignée sur une page, les vecteurs sont lus directement sans copie.
+
+use std::borrow::Cow;
+use std::ffi::c_void;
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::collection::{self, Collection, ZeroVectorPolicy};
+use crate::error::{Error, Result};
+use crate::parallel::SearchRuntime;
+use crate::payload::Payload;
+use crate::search::{SearchParams, SearchResults, Stopwatch};
+use crate::similarity::{self, Metric, Scorer};
+use crate::snapshot;
+use crate::storage::{self, FileSystem};
+use crate::store::VectorStore;
+use crate::weights;
+
+const MAGIC: &[u8; 8] = b"EMBMMAP\0";
+const VERSION: u32 = 1;
+/// Pas de lecture de [`MmapCollection::warm_up`], au plus la taille d'une page.
+const PAGE_STEP: usize = 4096;
+const BYTE_ORDER_MARK: u32 = 0x0102_0304;
+const HEADER_LEN: usize = 64;
+const VECTOR_ALIGNMENT: usize = 64;
+
+/// Collection en lecture seule dont les vecteurs restent dans un fichier projeté en mémoire.
+///
+/// L'ouverture ne lit que l'en-tête et la table des identifiants : le système charge les
+/// vecteurs à la demande, et ils ne sont jamais copiés sur le tas. Les charges utiles et
+/// les identifiants entiers ou textuels ([`DocumentId`](crate::DocumentId)) ne sont pas
+/// conservés dans ce format : les documents n'y sont connus que par leur `Uuid`. Pour modifier la collection, il faut la convertir
+/// avec [`MmapCollection::to_collection`].
+pub struct MmapCollection {
+    ptr: *mut c_void,
+    mapped_len: usize,
+    count: usize,
+    dimension: usize,
+    ids_offset: usize,
+    vectors_offset: usize,
+    metric: Metric,
+    zero_vector_policy: ZeroVectorPolicy,
+    normalized: bool,
+    runtime: SearchRuntime,
+}
+
+// La projection est en lecture seule et n'est libérée qu'au `Drop` : elle peut être
+// partagée et lue depuis plusieurs threads.
+unsafe impl Send for MmapCollection {}
+unsafe impl Sync for MmapCollection {}
+
+impl MmapCollection {
+    /// Écrit une collection au format projetable en mémoire.
+    ///
+    /// # Arguments
+    /// * `collection` - Collection à écrire ; ses charges utiles et ses identifiants non
+    ///   `Uuid` sont ignorés.
+    /// * `path` - Chemin du fichier à créer ou remplacer.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si une projection ou un pipeline de requêtes est attaché à
+    ///   la collection, ou si elle pondère ses dimensions.
+    /// * `Error::DimensionMismatch` - Si les documents n'ont pas tous la même dimension.
+    /// * `Error::Io` - Si le fichier ne peut pas être écrit.
+    pub fn write(collection: &Collection, path: impl AsRef<Path>) -> Result<()> {
+        if collection.projection.is_some() {
+            return Err(Error::InvalidConfig(
+                "une collection projetée ne peut pas être écrite en lecture seule".to_string(),
+            ));
+        }
+        if !collection.query_pipeline().is_empty() {
+            return Err(Error::InvalidConfig(
+                "une collection qui transforme ses requêtes ne peut pas être écrite en lecture \
+                 seule"
+                    .to_string(),
+            ));
+        }
+        if collection.dimension_weights().is_some() {
+            return Err(Error::InvalidConfig(
+                "une collection aux dimensions pondérées ne peut pas être écrite en lecture seule"
+                    .to_string(),
+            ));
+        }
+        let mut documents: Vec<(&Uuid, &Arc<Vec<f32>>)> = collection.documents.iter().collect();
+        documents.sort_unstable_by_key(|(key, _)| **key);
+        let report = collection.validate();
+        let dimension = report.dimension.unwrap_or(0);
+        if let Some((_, got)) = report.mismatched.first() {
+            return Err(Error::DimensionMismatch {
+                expected: dimension,
+                got: *got,
+            });
+        }
+
+        let ids_offset = HEADER_LEN;
+        let vectors_offset = (ids_offset + documents.len() * 16).next_multiple_of(VECTOR_ALIGNMENT);
+        let mut out = Vec::with_capacity(vectors_offset + documents.len() * dimension * 4);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_ne_bytes());
+        out.extend_from_slice(&BYTE_ORDER_MARK.to_ne_bytes());
+        out.push(snapshot::metric_code(collection.config.metric));
+        out.push(snapshot::policy_code(collection.config.zero_vector_policy));
+        out.push(u8::from(collection.config.normalized));
+        out.resize(24, 0);
+        for n in [documents.len(), dimension, ids_offset, vectors_offset, 0] {
+            out.extend_from_slice(&(n as u64).to_ne_bytes());
+        }
+        for (key, _) in &documents {
+            out.extend_from_slice(key.as_bytes());
+        }
+        out.resize(vectors_offset, 0);
+        for (_, vector) in &documents {
+            for x in vector.iter() {
+                out.extend_from_slice(&x.to_ne_bytes());
+            }
+        }
+
+        storage::replace(&FileSystem, path.as_ref(), &out)?;
+        Ok(())
+    }
+
+    /// Ouvre un fichier écrit par [`MmapCollection::write`] et le projette en mémoire.
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être ouvert ou projeté.
+    /// * `Error::InvalidSnapshot` - Si le fichier est invalide, ou s'il a été écrit sur une
+    ///   machine de boutisme différent : il faut alors le régénérer sur la machine cible.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| invalid("fichier trop grand pour cette machine"))?;
+        if len < HEADER_LEN {
+            return Err(invalid("fichier tronqué"));
+        }
+        // SAFETY: projection en lecture seule et privée d'un fichier ouvert, de longueur
+        // non nulle ; le résultat est vérifié avant usage.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // Construite tout de suite pour que `Drop` libère la projection en cas d'erreur.
+        let mut collection = MmapCollection {
+            ptr,
+            mapped_len: len,
+            count: 0,
+            dimension: 0,
+            ids_offset: HEADER_LEN,
+            vectors_offset: HEADER_LEN,
+            metric: Metric::default(),
+            zero_vector_policy: ZeroVectorPolicy::default(),
+            normalized: false,
+            runtime: SearchRuntime::default(),
+        };
+        collection.parse_header()?;
+        Ok(collection)
+    }
+
+    /// Fixe le réglage des parcours parallèles des recherches.
+    ///
+    /// # Arguments
+    /// * `runtime` - Nombre de threads et taille des tranches des parcours.
+    pub fn with_runtime(mut self, runtime: SearchRuntime) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Charge toute la projection en mémoire pour que la première recherche ne paie pas
+    /// les défauts de page : le noyau est prévenu par `madvise`, puis un octet de chaque
+    /// page est lu. Un second appel ne fait que relire des pages déjà présentes.
+    pub fn warm_up(&self) {
+        // SAFETY: `ptr` et `mapped_len` décrivent la projection, valide jusqu'au `Drop` ;
+        // le conseil n'est qu'une indication et son échec éventuel est sans conséquence.
+        unsafe {
+            libc::madvise(self.ptr, self.mapped_len, libc::MADV_WILLNEED);
+        }
+        let bytes = self.bytes();
+        for offset in (0..bytes.len()).step_by(PAGE_STEP) {
+            std::hint::black_box(bytes[offset]);
+        }
+    }
+
+    /// Retourne la dimension commune des vecteurs.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Retourne le traitement des vecteurs nuls de la collection.
+    pub fn zero_vector_policy(&self) -> ZeroVectorPolicy {
+        self.zero_vector_policy
+    }
+
+    /// Copie la collection en mémoire pour pouvoir la modifier.
+    pub fn to_collection(&self) -> Collection {
+        let mut collection = Collection::new()
+            .with_metric(self.metric)
+            .with_zero_vector_policy(self.zero_vector_policy)
+            .with_runtime(self.runtime);
+        collection.config.normalized = self.normalized;
+        collection.documents = (0..self.count)
+            .map(|i| (self.ids()[i], Arc::new(self.vector(i).to_vec())))
+            .collect();
+        collection.infer_locked_dimension();
+        collection.memory = collection.measure_memory();
+        collection.recompute_stats();
+        collection
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `ptr` désigne une projection de `mapped_len` octets, valide jusqu'au `Drop`.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.mapped_len) }
+    }
+
+    pub(crate) fn ids(&self) -> &[Uuid] {
+        let bytes = &self.bytes()[self.ids_offset..self.ids_offset + self.count * 16];
+        // SAFETY: `Uuid` est `repr(transparent)` sur `[u8; 16]`, sans contrainte
+        // d'alignement ; la tranche contient exactement `count` identifiants.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const Uuid, self.count) }
+    }
+
+    /// Retourne le bloc de vecteurs, `count` lignes de `dimension` flottants.
+    pub(crate) fn rows(&self) -> &[f32] {
+        let bytes = &self.bytes()[self.vectors_offset..self.vectors_offset + self.count * self.dimension * 4];
+        // SAFETY: comme pour `vector`, dont les lignes sont des tranches de ce bloc.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const f32, self.count * self.dimension) }
+    }
+
+    fn vector(&self, index: usize) -> &[f32] {
+        let start = self.vectors_offset + index * self.dimension * 4;
+        let bytes = &self.bytes()[start..start + self.dimension * 4];
+        // SAFETY: la projection commence sur une page et `vectors_offset` est un multiple
+        // de 64 : les flottants sont alignés. `parse_header` a vérifié qu'ils sont dans
+        // le fichier et écrits dans le boutisme de cette machine.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const f32, self.dimension) }
+    }
+
+    fn parse_header(&mut self) -> Result<()> {
+        let bytes = self.bytes();
+        let u32_at = |at: usize| u32::from_ne_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_ne_bytes(bytes[at..at + 8].try_into().unwrap());
+        if &bytes[..8] != MAGIC {
+            return Err(invalid("signature de fichier inconnue"));
+        }
+        match u32_at(12) {
+            BYTE_ORDER_MARK => {}
+            mark if mark == BYTE_ORDER_MARK.swap_bytes() => {
+                return Err(invalid(
+                    "fichier écrit sur une machine de boutisme différent ; \
+                     régénérez-le sur cette machine",
+                ))
+            }
+            _ => return Err(invalid("marqueur d'ordre des octets invalide")),
+        }
+        if u32_at(8) != VERSION {
+            return Err(invalid(format!(
+                "version {} non prise en charge",
+                u32_at(8)
+            )));
+        }
+        let metric = snapshot::metric_from_code(bytes[16])?;
+        let zero_vector_policy = snapshot::policy_from_code(bytes[17])?;
+        let normalized = match bytes[18] {
+            0 => false,
+            1 => true,
+            code => {
+                return Err(invalid(format!(
+                    "marqueur de normalisation invalide ({})",
+                    code
+                )))
+            }
+        };
+        let to_usize = |n: u64| usize::try_from(n).map_err(|_| invalid("en-tête incohérent"));
+        let count = to_usize(u64_at(24))?;
+        let dimension = to_usize(u64_at(32))?;
+        let ids_offset = to_usize(u64_at(40))?;
+        let vectors_offset = to_usize(u64_at(48))?;
+
+        let ids_end = count
+            .checked_mul(16)
+            .and_then(|n| n.checked_add(ids_offset));
+        let vectors_len = count.checked_mul(dimension).and_then(|n| n.checked_mul(4));
+        match (ids_end, vectors_len) {
+            (Some(ids_end), Some(vectors_len))
+                if ids_offset >= HEADER_LEN
+                    && ids_end <= vectors_offset
+                    && vectors_offset % VECTOR_ALIGNMENT == 0
+                    && vectors_offset.checked_add(vectors_len) == Some(self.mapped_len) => {}
+            _ => return Err(invalid("tailles de l'en-tête incohérentes avec le fichier")),
+        }
+
+        self.count = count;
+        self.dimension = dimension;
+        self.ids_offset = ids_offset;
+        self.vectors_offset = vectors_offset;
+        self.metric = metric;
+        self.zero_vector_policy = zero_vector_policy;
+        self.normalized = normalized;
+        if self.ids().windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(invalid("table des identifiants non triée"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MmapCollection {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` et `mapped_len` proviennent d'un appel réussi à `mmap`.
+        unsafe {
+            libc::munmap(self.ptr, self.mapped_len);
+        }
+    }
+}
+
+impl VectorStore for MmapCollection {
+    fn read(&self, key: &Uuid) -> Option<&[f32]> {
+        self.ids()
+            .binary_search(key)
+            .ok()
+            .map(|index| self.vector(index))
+    }
+
+    /// Toujours `None` : ce format ne conserve pas les charges utiles.
+    fn payload(&self, _key: &Uuid) -> Option<&Payload> {
+        None
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &Uuid> + '_> {
+        Box::new(self.ids().iter())
+    }
+
+    fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    fn warm_up(&self) {
+        MmapCollection::warm_up(self)
+    }
+
+    fn search_with(&self, request: &[f32], params: &SearchParams) -> Result<SearchResults> {
+        params.check_uncalibrated()?;
+        collection::check_zero_vector(self.metric, self.zero_vector_policy, request, None)?;
+        let request = match self.normalized {
+            false => Cow::Borrowed(request),
+            true => {
+                let norm = similarity::norm_f64(request);
+                if norm == 0.0 {
+                    return Err(Error::ZeroVector { key: None });
+                }
+                Cow::Owned(
+                    (request.iter())
+                        .map(|x| (f64::from(*x) / norm) as f32)
+                        .collect(),
+                )
+            }
+        };
+        // Les documents d'un fichier projeté n'appartiennent à aucun espace de noms.
+        let mut watch = Stopwatch::start(params);
+        if params.namespace.is_some() {
+            return collection::finish(self.metric, Vec::new(), 0, false, params, watch);
+        }
+        let masked = (params.dimension_mask.as_deref())
+            .map(|mask| weights::masked(None, mask, request.len()))
+            .transpose()?;
+        let scorer = Scorer {
+            metric: self.metric,
+            weights: masked.as_deref(),
+        };
+        let deadline = params.time_budget.map(|budget| Instant::now() + budget);
+        let entries: Vec<(&Uuid, &[f32])> = self
+            .ids()
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key, self.vector(index)))
+            .collect();
+        let entries = collection::sample(&entries, params);
+        if let Some(watch) = &mut watch {
+            watch.timing.filter_us = watch.lap();
+        }
+        let (mut hits, truncated, scored) = collection::scan(
+            self.runtime,
+            scorer,
+            &request,
+            &entries,
+            params.score_threshold,
+            deadline,
+        );
+        // Sans charges utiles, aucun document d'un fichier projeté n'a de date.
+        if let Some(decay) = &params.decay {
+            decay.apply(self.metric, &mut hits, params.deterministic_math, |_| None);
+        }
+        if let Some(watch) = &mut watch {
+            watch.timing.scoring_us = watch.lap();
+            watch.timing.candidates_scored = scored;
+        }
+        let available = hits.len();
+        let available =
+            collection::deduplicate(scorer, &mut hits, available, params, |key| {
+                self.read(key)
+            });
+        let mut results =
+            collection::finish(self.metric, hits, available, truncated, params, watch)?;
+        results.attach(
+            params,
+            |_| None,
+            |key| Some(Arc::new(self.read(key)?.to_vec())),
+        );
+        Ok(results)
+    }
+}
+
+fn invalid(description: impl Into<String>) -> Error {
+    Error::InvalidSnapshot(description.into())
+}
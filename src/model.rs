@@ -0,0 +1,199 @@
+//! Empreinte du modèle de plongement qui a produit les vecteurs d'une collection.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::search::SearchParams;
+
+/// Identité d'un modèle de plongement : un nom libre, par exemple
+/// `"text-embedding-3-small@1536"`, et éventuellement l'empreinte de ses poids.
+///
+/// Deux modèles de même dimension produisent des vecteurs comparables en apparence mais
+/// sans rapport entre eux ; comparer les empreintes détecte ce mélange (voir
+/// [`SearchParams::model`]).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModelFingerprint {
+    name: String,
+    hash: Option<String>,
+}
+
+impl ModelFingerprint {
+    /// Crée l'empreinte d'un modèle désigné par `name`, sans empreinte de ses poids.
+    ///
+    /// # Arguments
+    /// * `name` - Nom du modèle, comparé tel quel.
+    pub fn new(name: impl Into<String>) -> Self {
+        ModelFingerprint {
+            name: name.into(),
+            hash: None,
+        }
+    }
+
+    /// Ajoute l'empreinte des poids du modèle, pour distinguer deux versions de même nom.
+    ///
+    /// # Arguments
+    /// * `hash` - Empreinte des poids, comparée telle quelle.
+    pub fn with_hash(mut self, hash: impl Into<String>) -> Self {
+        self.hash = Some(hash.into());
+        self
+    }
+
+    /// Retourne le nom du modèle.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retourne l'empreinte des poids du modèle, si elle est connue.
+    pub fn hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    /// Indique si `other` désigne le même modèle : même nom et, si les deux empreintes
+    /// des poids sont connues, même empreinte.
+    pub fn matches(&self, other: &ModelFingerprint) -> bool {
+        self.name == other.name
+            && match (&self.hash, &other.hash) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+    }
+}
+
+impl fmt::Display for ModelFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.hash {
+            Some(hash) => write!(f, "{} ({})", self.name, hash),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+impl Collection {
+    /// Retourne l'empreinte du modèle de plongement de la collection, fixée par la
+    /// configuration ([`CollectionConfigBuilder::model_fingerprint`](crate::CollectionConfigBuilder::model_fingerprint))
+    /// ou par la première insertion de [`Collection::upsert_from_model`].
+    pub fn model_fingerprint(&self) -> Option<&ModelFingerprint> {
+        self.config.model.as_ref()
+    }
+
+    /// Insère ou met à jour un document dont le vecteur vient du modèle `model`, comme
+    /// [`Collection::upsert`], en marquant la collection de l'empreinte du modèle si elle
+    /// n'en a pas encore.
+    ///
+    /// C'est le point d'entrée d'une intégration qui calcule elle-même les plongements :
+    /// la collection retient ainsi le modèle de ses vecteurs sans configuration préalable.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document.
+    /// * `vector` - Vecteur produit par le modèle.
+    /// * `model` - Empreinte du modèle qui a produit `vector`.
+    ///
+    /// # Retourne
+    /// * Result<bool> - `false` si le document a été ignoré comme doublon, comme par
+    ///   [`Collection::upsert`] ; l'empreinte n'est alors pas retenue.
+    ///
+    /// # Erreurs
+    /// * `Error::ModelMismatch` - Si la collection porte l'empreinte d'un autre modèle ;
+    ///   ni le document ni l'empreinte ne sont alors modifiés.
+    /// * Celles de [`Collection::upsert`].
+    pub fn upsert_from_model(
+        &mut self,
+        key: Uuid,
+        vector: impl Into<Vec<f32>>,
+        model: &ModelFingerprint,
+    ) -> Result<bool> {
+        self.check_model(Some(model), false)?;
+        if !self.upsert(key, vector)? {
+            return Ok(false);
+        }
+        if self.config.model.is_none() {
+            self.config.model = Some(model.clone());
+        }
+        Ok(true)
+    }
+
+    /// Vérifie l'empreinte du modèle d'une requête contre celle de la collection.
+    pub(crate) fn check_search_model(&self, params: &SearchParams) -> Result<()> {
+        self.check_model(params.model.as_ref(), params.allow_model_mismatch)
+    }
+
+    fn check_model(&self, model: Option<&ModelFingerprint>, allow_mismatch: bool) -> Result<()> {
+        match (&self.config.model, model) {
+            (Some(expected), Some(got)) if !allow_mismatch && !expected.matches(got) => {
+                Err(Error::ModelMismatch {
+                    expected: expected.clone(),
+                    got: got.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+    use crate::database::BaseDeDonnees;
+
+    fn small() -> ModelFingerprint {
+        ModelFingerprint::new("text-embedding-3-small@1536").with_hash("a1b2")
+    }
+
+    fn params(model: ModelFingerprint, allow_model_mismatch: bool) -> SearchParams {
+        SearchParams {
+            model: Some(model),
+            allow_model_mismatch,
+            ..SearchParams::new(1)
+        }
+    }
+
+    #[test]
+    fn the_first_model_insert_stamps_the_collection() {
+        let mut collection = Collection::new();
+        let key = Uuid::new_v4();
+        collection.upsert_from_model(key, [1.0, 0.0], &small()).unwrap();
+        assert_eq!(collection.model_fingerprint(), Some(&small()));
+        // Un autre modèle est refusé sans rien modifier.
+        let other = ModelFingerprint::new("all-MiniLM-L6-v2");
+        assert!(matches!(
+            collection.upsert_from_model(key, [0.0, 1.0], &other),
+            Err(Error::ModelMismatch { expected, got }) if expected == small() && got == other
+        ));
+        assert_eq!(collection.documents[&key][..], [1.0, 0.0]);
+        assert_eq!(collection.model_fingerprint(), Some(&small()));
+    }
+
+    #[test]
+    fn queries_from_another_model_need_the_override() {
+        let config = CollectionConfig::builder().model_fingerprint(small()).build().unwrap();
+        let mut collection = Collection::from_config(config);
+        collection.upsert(Uuid::new_v4(), [1.0, 0.0]).unwrap();
+        let query = [1.0, 0.0];
+        // Même nom : une empreinte des poids absente d'un côté ne distingue rien.
+        for model in [small(), ModelFingerprint::new("text-embedding-3-small@1536")] {
+            assert_eq!(collection.search_with(query, &params(model, false)).unwrap().hits.len(), 1);
+        }
+        assert!(collection.search_with(query, &SearchParams::new(1)).is_ok());
+
+        let other = ModelFingerprint::new("text-embedding-3-small@1536").with_hash("ffff");
+        let error = collection.search_with(query, &params(other.clone(), false)).unwrap_err();
+        assert!(matches!(error, Error::ModelMismatch { .. }));
+        assert!(error.to_string().contains("ffff"), "{}", error);
+        assert_eq!(collection.search_with(query, &params(other, true)).unwrap().hits.len(), 1);
+    }
+
+    #[test]
+    fn the_fingerprint_is_saved_and_summarized() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let docs = db.get_mut("docs").unwrap();
+        docs.upsert_from_model(Uuid::new_v4(), [1.0, 0.0], &small()).unwrap();
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        assert_eq!(loaded.get("docs").unwrap().model_fingerprint(), Some(&small()));
+        assert!(loaded.summary().contains(", modèle text-embedding-3-small@1536 (a1b2)"));
+    }
+}
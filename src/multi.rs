@@ -0,0 +1,116 @@
+use std::borrow::Cow;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::parallel::PARALLEL_THRESHOLD;
+
+/// Résultat d'une recherche à plusieurs requêtes ([`Collection::search_multi`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiHit {
+    /// Identifiant du document.
+    pub key: Uuid,
+    /// Meilleur score du document parmi toutes les requêtes.
+    pub score: f32,
+    /// Indice de la requête qui a donné `score` ; la plus petite en cas d'égalité.
+    pub best_query_index: usize,
+    /// Score du document pour chaque requête, dans l'ordre des requêtes, si demandé avec
+    /// [`Collection::search_multi_scores`].
+    pub scores: Option<Vec<f32>>,
+}
+
+impl Collection {
+    /// Recherche les `k` documents les plus proches de l'une quelconque des requêtes,
+    /// par exemple les reformulations d'une même question.
+    ///
+    /// Chaque document apparaît au plus une fois, avec son meilleur score et la requête
+    /// qui l'a donné. Les documents sont parcourus une seule fois pour toutes les
+    /// requêtes ; le classement suit la mesure de la collection, les égalités par `Uuid`
+    /// croissant.
+    ///
+    /// # Arguments
+    /// * `queries` - Vecteurs de requête.
+    /// * `k` - Nombre maximal de résultats.
+    ///
+    /// # Retourne
+    /// * Result<Vec<MultiHit>> - Documents retenus, du meilleur au moins bon, sans le
+    ///   détail des scores.
+    ///
+    /// # Erreurs
+    /// * `Error::EmptyQuery` - Si `queries` est vide.
+    /// * Celles de [`Collection::search`], pour la première requête refusée ; toutes les
+    ///   requêtes sont vérifiées avant le parcours.
+    pub fn search_multi(&self, queries: &[Vec<f32>], k: usize) -> Result<Vec<MultiHit>> {
+        self.search_multi_impl(queries, k, false)
+    }
+
+    /// Recherche comme [`Collection::search_multi`] en conservant le score de chaque
+    /// résultat pour toutes les requêtes dans [`MultiHit::scores`].
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search_multi`].
+    pub fn search_multi_scores(&self, queries: &[Vec<f32>], k: usize) -> Result<Vec<MultiHit>> {
+        self.search_multi_impl(queries, k, true)
+    }
+
+    /// Parcourt les documents une fois pour toutes les requêtes, en gardant le détail des
+    /// scores si `keep_scores` est vrai.
+    fn search_multi_impl(
+        &self,
+        queries: &[Vec<f32>],
+        k: usize,
+        keep_scores: bool,
+    ) -> Result<Vec<MultiHit>> {
+        if queries.is_empty() {
+            return Err(Error::EmptyQuery);
+        }
+        let mut prepared: Vec<Cow<[f32]>> = Vec::with_capacity(queries.len());
+        for query in queries {
+            self.check_zero(query, None)?;
+            prepared.push(self.prepare_query(query)?);
+        }
+        let dimension = prepared[0].len();
+        if let Some(query) = prepared.iter().find(|query| query.len() != dimension) {
+            return Err(Error::DimensionMismatch {
+                expected: dimension,
+                got: query.len(),
+            });
+        }
+        let entries = self.scoped_entries(None);
+        self.check_dimensions(&entries, dimension)?;
+        let scorer = self.scorer();
+        let metric = scorer.metric;
+        let mut hits = self
+            .runtime
+            .map_chunks(&entries, PARALLEL_THRESHOLD, |chunk| {
+                let mut scores = vec![0.0; prepared.len()];
+                chunk
+                    .iter()
+                    .filter(|(_, vector)| vector.len() == dimension)
+                    .map(|(key, vector)| {
+                        let mut best = 0;
+                        for (index, query) in prepared.iter().enumerate() {
+                            scores[index] = scorer.score_unchecked(query, vector);
+                            if metric.compare(scores[index], scores[best]).is_lt() {
+                                best = index;
+                            }
+                        }
+                        MultiHit {
+                            key: **key,
+                            score: scores[best],
+                            best_query_index: best,
+                            scores: keep_scores.then(|| scores.clone()),
+                        }
+                    })
+                    .collect()
+            });
+        hits.sort_by(|a, b| {
+            metric
+                .compare(a.score, b.score)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        hits.truncate(k);
+        Ok(hits)
+    }
+}
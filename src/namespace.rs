@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::memory;
+use crate::oplog::OperationKind;
+
+/// Rattachement des documents d'une collection à leur espace de noms.
+///
+/// Les deux tables sont tenues à jour ensemble : une recherche limitée à un espace de
+/// noms ne parcourt que ses membres.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Namespaces {
+    of: HashMap<Uuid, String>,
+    members: HashMap<String, HashSet<Uuid>>,
+}
+
+impl Namespaces {
+    /// Rend la place inutilisée des deux tables.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.of.shrink_to_fit();
+        self.members.values_mut().for_each(HashSet::shrink_to_fit);
+        self.members.shrink_to_fit();
+    }
+
+    /// Octets réservés par les deux tables sans être utilisés.
+    pub(crate) fn unused_bytes(&self) -> usize {
+        memory::unused_table_bytes(&self.of)
+            + memory::unused_table_bytes(&self.members)
+            + self
+                .members
+                .values()
+                .map(memory::unused_set_bytes)
+                .sum::<usize>()
+    }
+
+    /// Retourne l'espace de noms du document `key`.
+    pub(crate) fn get(&self, key: &Uuid) -> Option<&str> {
+        self.of.get(key).map(String::as_str)
+    }
+
+    /// Retourne les membres de l'espace de noms `namespace`.
+    pub(crate) fn members(&self, namespace: &str) -> Option<&HashSet<Uuid>> {
+        self.members.get(namespace)
+    }
+
+    /// Parcourt les couples (document, espace de noms).
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Uuid, &str)> {
+        self.of
+            .iter()
+            .map(|(key, namespace)| (key, namespace.as_str()))
+    }
+
+    /// Rattache le document `key` à `namespace`.
+    pub(crate) fn insert(&mut self, key: Uuid, namespace: String) {
+        self.remove(&key);
+        self.members
+            .entry(namespace.clone())
+            .or_default()
+            .insert(key);
+        self.of.insert(key, namespace);
+    }
+
+    /// Détache le document `key` de son espace de noms, qui disparaît s'il devient vide.
+    pub(crate) fn remove(&mut self, key: &Uuid) -> Option<String> {
+        let namespace = self.of.remove(key)?;
+        if let Some(members) = self.members.get_mut(&namespace) {
+            members.remove(key);
+            if members.is_empty() {
+                self.members.remove(&namespace);
+            }
+        }
+        Some(namespace)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.of.clear();
+        self.members.clear();
+    }
+}
+
+impl Collection {
+    /// Insère ou met à jour un document dans l'espace de noms `namespace`.
+    ///
+    /// Les espaces de noms isolent les documents de plusieurs locataires partageant le
+    /// même espace de plongements : une recherche dont
+    /// [`SearchParams::namespace`](crate::SearchParams::namespace) est renseigné ne
+    /// parcourt et ne retourne que les documents de cet espace. Une mise à jour par
+    /// [`Collection::upsert`] conserve l'espace de noms du document.
+    ///
+    /// # Arguments
+    /// * `namespace` - Espace de noms du document.
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si le document existe déjà dans un autre espace de noms
+    ///   ou hors de tout espace de noms.
+    /// * Celles de [`Collection::upsert`].
+    pub fn upsert_in(
+        &mut self,
+        namespace: &str,
+        key: Uuid,
+        vector: impl Into<Vec<f32>>,
+    ) -> Result<bool> {
+        match self.namespaces.get(&key) {
+            Some(current) if current != namespace => {
+                return Err(Error::InvalidConfig(format!(
+                    "le document {} appartient à l'espace de noms {}",
+                    key, current
+                )))
+            }
+            None if self.documents.contains_key(&key) => {
+                return Err(Error::InvalidConfig(format!(
+                    "le document {} existe déjà hors de tout espace de noms",
+                    key
+                )))
+            }
+            _ => {}
+        }
+        if !self.upsert(key, vector)? {
+            return Ok(false);
+        }
+        self.namespaces.insert(key, namespace.to_string());
+        Ok(true)
+    }
+
+    /// Retourne l'espace de noms du document `key`, s'il en a un.
+    pub fn namespace(&self, key: &Uuid) -> Option<&str> {
+        self.namespaces.get(key)
+    }
+
+    /// Retourne les espaces de noms non vides de la collection, triés.
+    pub fn namespaces(&self) -> Vec<&str> {
+        let mut namespaces: Vec<&str> =
+            self.namespaces.members.keys().map(String::as_str).collect();
+        namespaces.sort_unstable();
+        namespaces
+    }
+
+    /// Retourne le nombre de documents de l'espace de noms `namespace`.
+    pub fn namespace_len(&self, namespace: &str) -> usize {
+        self.namespaces.members(namespace).map_or(0, HashSet::len)
+    }
+
+    /// Supprime tous les documents de l'espace de noms `namespace`, avec leur charge
+    /// utile, comme le ferait [`Collection::delete`] pour chacun d'eux.
+    ///
+    /// # Arguments
+    /// * `namespace` - Espace de noms à supprimer.
+    ///
+    /// # Retourne
+    /// * usize - Nombre de documents supprimés.
+    pub fn delete_namespace(&mut self, namespace: &str) -> usize {
+        let members: Vec<Uuid> = self
+            .namespaces
+            .members(namespace)
+            .map(|members| members.iter().copied().collect())
+            .unwrap_or_default();
+        self.logged(
+            OperationKind::Delete,
+            |collection| {
+                for key in &members {
+                    collection.remove_document(key);
+                }
+                members.len()
+            },
+            |count| Some(*count),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::search::SearchParams;
+    use crate::synthetic::VectorGenerator;
+
+    /// Collection de deux locataires dont les documents sont tirés dans le même espace.
+    fn tenants() -> (Collection, VectorGenerator) {
+        let mut generator = VectorGenerator::new(8, 140);
+        let mut collection = Collection::new();
+        for i in 0..200 {
+            let namespace = if i % 4 == 0 { "a" } else { "b" };
+            collection
+                .upsert_in(namespace, generator.uuid(), generator.vector())
+                .unwrap();
+        }
+        (collection, generator)
+    }
+
+    fn in_namespace(namespace: &str, k: usize) -> SearchParams {
+        SearchParams {
+            k,
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_query_never_returns_another_namespace() {
+        let (collection, mut generator) = tenants();
+        assert_eq!(collection.namespaces(), ["a", "b"]);
+        assert_eq!((collection.namespace_len("a"), collection.namespace_len("b")), (50, 150));
+        for _ in 0..20 {
+            let query = generator.vector();
+            // k dépasse la taille de l'espace : tout l'espace a, et rien d'autre.
+            let results = collection.search_with(&query, &in_namespace("a", 100)).unwrap();
+            assert_eq!(results.hits.len(), 50);
+            assert!(results.hits.iter().all(|(key, _)| collection.namespace(key) == Some("a")));
+            let best = collection.search_with(&query, &in_namespace("b", 5)).unwrap();
+            assert!(best.hits.iter().all(|(key, _)| collection.namespace(key) == Some("b")));
+            assert_eq!(collection.search(&query, 300).unwrap().len(), 200);
+        }
+        let unknown = collection.search_with(generator.vector(), &in_namespace("c", 10)).unwrap();
+        assert!(unknown.hits.is_empty());
+    }
+
+    #[test]
+    fn documents_stay_in_their_namespace() {
+        let mut collection = Collection::new();
+        let (key, outside) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert_in("a", key, [1.0, 0.0]).unwrap();
+        collection.upsert(key, [0.0, 1.0]).unwrap();
+        assert_eq!(collection.namespace(&key), Some("a"));
+        assert!(matches!(
+            collection.upsert_in("b", key, [1.0, 1.0]),
+            Err(Error::InvalidConfig(_))
+        ));
+        collection.upsert(outside, [1.0, 1.0]).unwrap();
+        assert!(matches!(
+            collection.upsert_in("a", outside, [1.0, 1.0]),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert_eq!(collection.namespace(&outside), None);
+        collection.delete(&key);
+        assert!(collection.namespaces().is_empty());
+    }
+
+    #[test]
+    fn deleting_a_namespace_keeps_the_others() {
+        let (mut collection, mut generator) = tenants();
+        assert_eq!(collection.delete_namespace("b"), 150);
+        assert_eq!(collection.delete_namespace("b"), 0);
+        assert_eq!(collection.len(), 50);
+        assert_eq!(collection.namespaces(), ["a"]);
+        let results = collection.search_with(generator.vector(), &in_namespace("b", 10)).unwrap();
+        assert!(results.hits.is_empty());
+        collection.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn namespaces_survive_a_save() {
+        let (collection, mut generator) = tenants();
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection.clone());
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap();
+        assert_eq!(loaded.namespace_len("a"), 50);
+        let query = generator.vector();
+        assert_eq!(
+            loaded.search_with(&query, &in_namespace("a", 10)).unwrap().hits,
+            collection.search_with(&query, &in_namespace("a", 10)).unwrap().hits
+        );
+    }
+}
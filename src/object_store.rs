@@ -0,0 +1,887 @@
+//! Sauvegarde d'une base dans un stockage objet compatible S3, et chargement depuis
+//! celui-ci (fonctionnalité `object-store`).
+//!
+//! Une sauvegarde à l'adresse `http://hôte:port/seau/chemin` occupe jusqu'à trois
+//! objets du seau :
+//!
+//! ```text
+//! chemin                          manifeste JSON, écrit en dernier
+//! chemin.<empreinte>.data         sauvegarde au format de BaseDeDonnees::save,
+//!                                 envoyée en parties de OBJECT_PART_BYTES octets
+//! chemin.upload                   envoi en cours, supprimé une fois le manifeste écrit
+//! ```
+//!
+//! Le manifeste désigne l'objet de données et donne l'empreinte SHA-256 de chacune de
+//! ses parties. Tant qu'il n'est pas écrit, l'adresse désigne la sauvegarde précédente,
+//! ou aucune : un envoi interrompu n'est jamais pris pour une sauvegarde valide. Le nom
+//! de l'objet de données contient l'empreinte de son contenu ; un nouvel envoi ne
+//! touche donc pas celui du manifeste en place, supprimé seulement une fois le nouveau
+//! manifeste écrit.
+//!
+//! Les requêtes sont signées (AWS Signature Version 4) et envoyées en HTTP/1.1 sans
+//! chiffrement : TLS n'est pas pris en charge, et le point d'accès doit être local ou
+//! placé derrière un mandataire qui chiffre les échanges.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backup;
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::json;
+use crate::payload::Value;
+use crate::sha256::{HmacSha256, Sha256};
+
+/// Taille des parties envoyées par [`BaseDeDonnees::save_to_object_store`], sauf la
+/// dernière ; S3 impose au moins 5 Mio.
+pub const OBJECT_PART_BYTES: usize = 8 * 1024 * 1024;
+
+/// Version du format des manifestes écrits dans le stockage objet.
+const MANIFEST_FORMAT: u64 = 1;
+
+/// Nombre d'essais d'une requête qui échoue de façon passagère : connexion perdue,
+/// erreur 5xx du serveur ou demande de ralentir (429).
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Attente avant le deuxième essai d'une requête, doublée à chaque essai suivant.
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Délai de lecture et d'écriture sur la connexion au-delà duquel un essai échoue.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Suffixe de l'objet qui décrit un envoi en cours.
+const UPLOAD_SUFFIX: &str = ".upload";
+
+/// Identifiants d'accès au stockage objet.
+///
+/// `ObjectStoreCredentials::new("AKIA…", "secret").with_region("eu-west-3")`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ObjectStoreCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    region: String,
+}
+
+impl ObjectStoreCredentials {
+    /// Crée des identifiants pour la région `us-east-1`.
+    ///
+    /// # Arguments
+    /// * `access_key_id` - Identifiant de la clé d'accès.
+    /// * `secret_access_key` - Clé secrète.
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        ObjectStoreCredentials {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+            region: "us-east-1".to_string(),
+        }
+    }
+
+    /// Fixe la région qui entre dans la signature des requêtes.
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    /// Joint un jeton de session à des identifiants temporaires.
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// Retourne l'identifiant de la clé d'accès.
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+
+    /// Retourne la région.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+}
+
+impl fmt::Debug for ObjectStoreCredentials {
+    /// N'affiche ni la clé secrète ni le jeton de session.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjectStoreCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("region", &self.region)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Bilan de [`BaseDeDonnees::save_to_object_store`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UploadReport {
+    /// Taille de la sauvegarde, en octets.
+    pub bytes: u64,
+    /// Nombre de parties de la sauvegarde.
+    pub parts: usize,
+    /// Parties déjà reçues par le stockage lors d'un envoi précédent interrompu, et
+    /// reprises sans être renvoyées.
+    pub resumed_parts: usize,
+}
+
+impl fmt::Display for UploadReport {
+    /// Affiche le bilan sur une ligne : `52428800 octets en 7 partie(s), 3 reprise(s)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} octets en {} partie(s), {} reprise(s)",
+            self.bytes, self.parts, self.resumed_parts
+        )
+    }
+}
+
+impl BaseDeDonnees {
+    /// Enregistre la base dans un stockage objet compatible S3, au format de
+    /// [`BaseDeDonnees::save`].
+    ///
+    /// La sauvegarde est envoyée en plusieurs parties dont le stockage vérifie
+    /// l'empreinte SHA-256, jointe à la signature de chaque requête. Une requête qui
+    /// échoue de façon passagère est retentée, avec une attente croissante. Si l'envoi
+    /// échoue malgré tout, un nouvel appel sur la même adresse, avec le même contenu,
+    /// reprend l'envoi sans renvoyer les parties déjà reçues ; un contenu différent
+    /// abandonne l'envoi précédent et recommence. Le manifeste n'est écrit qu'une fois
+    /// toutes les parties assemblées (voir le [module](crate::object_store)).
+    ///
+    /// # Arguments
+    /// * `uri` - Adresse `http://hôte[:port]/seau/chemin` du manifeste.
+    /// * `credentials` - Identifiants d'accès.
+    ///
+    /// # Retourne
+    /// * Result<UploadReport> - Taille, nombre de parties et parties reprises.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `uri` n'est pas une adresse `http://` avec un seau
+    ///   et un chemin.
+    /// * `Error::Io` - Si le stockage reste injoignable après plusieurs essais, ou
+    ///   refuse une requête ; la sauvegarde précédente reste en place.
+    pub fn save_to_object_store(
+        &self,
+        uri: &str,
+        credentials: &ObjectStoreCredentials,
+    ) -> Result<UploadReport> {
+        Client::new(uri, credentials)?.upload(&self.to_bytes())
+    }
+
+    /// Charge une base enregistrée avec [`BaseDeDonnees::save_to_object_store`].
+    ///
+    /// Le manifeste est lu d'abord ; chaque partie de la sauvegarde est ensuite lue par
+    /// une requête distincte, retentée si elle échoue ou si son contenu ne correspond
+    /// pas à son empreinte. La sauvegarde est assemblée en mémoire, sans fichier
+    /// temporaire.
+    ///
+    /// # Arguments
+    /// * `uri` - Adresse `http://hôte[:port]/seau/chemin` du manifeste.
+    /// * `credentials` - Identifiants d'accès.
+    ///
+    /// # Retourne
+    /// * Result<BaseDeDonnees> - La base de données lue.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `uri` n'est pas une adresse `http://` avec un seau
+    ///   et un chemin.
+    /// * `Error::Io` - Si le stockage reste injoignable après plusieurs essais, ou
+    ///   refuse une requête.
+    /// * `Error::InvalidSnapshot` - Si aucun manifeste n'est écrit à `uri`, si le
+    ///   manifeste est invalide ou si une partie ne correspond pas à son empreinte.
+    pub fn load_from_object_store(uri: &str, credentials: &ObjectStoreCredentials) -> Result<Self> {
+        Self::from_bytes(&Client::new(uri, credentials)?.download()?)
+    }
+}
+
+/// Contenu du manifeste d'une sauvegarde dans le stockage objet.
+struct Manifest {
+    /// Nom de l'objet de données.
+    data: String,
+    bytes: u64,
+    part_bytes: u64,
+    /// Empreinte SHA-256 de toute la sauvegarde, en hexadécimal.
+    sha256: String,
+    /// Empreinte SHA-256 de chaque partie, en hexadécimal.
+    parts: Vec<String>,
+}
+
+impl Manifest {
+    fn to_json(&self) -> String {
+        let mut out = String::from("{\"format\":");
+        json::write_u64(&mut out, MANIFEST_FORMAT);
+        out.push_str(",\"data\":");
+        json::write_string(&mut out, &self.data);
+        out.push_str(",\"bytes\":");
+        json::write_u64(&mut out, self.bytes);
+        out.push_str(",\"part_bytes\":");
+        json::write_u64(&mut out, self.part_bytes);
+        out.push_str(",\"sha256\":");
+        json::write_string(&mut out, &self.sha256);
+        out.push_str(",\"parts\":");
+        json::write_array(&mut out, &self.parts, |out, part| json::write_string(out, part));
+        out.push_str("}\n");
+        out
+    }
+
+    fn from_json(text: &str) -> Result<Manifest> {
+        let fields = fields(text)?;
+        if integer(&fields, "format")? != MANIFEST_FORMAT {
+            return Err(invalid("format de manifeste inconnu"));
+        }
+        let parts = match fields.get("parts") {
+            Some(Value::Array(items)) => (items.iter())
+                .map(|item| match item {
+                    Value::String(part) => Ok(part.clone()),
+                    _ => Err(invalid("empreinte de partie attendue")),
+                })
+                .collect::<Result<Vec<String>>>()?,
+            _ => return Err(invalid("« parts » manquant")),
+        };
+        let manifest = Manifest {
+            data: string(&fields, "data")?.to_string(),
+            bytes: integer(&fields, "bytes")?,
+            part_bytes: integer(&fields, "part_bytes")?,
+            sha256: string(&fields, "sha256")?.to_string(),
+            parts,
+        };
+        let expected = manifest.bytes.div_ceil(manifest.part_bytes.max(1)).max(1);
+        if manifest.data.is_empty() || manifest.part_bytes == 0 {
+            return Err(invalid("objet de données ou taille des parties manquant"));
+        }
+        if manifest.parts.len() as u64 != expected {
+            return Err(invalid(&format!(
+                "{} parties annoncées pour {} octets en parties de {}",
+                manifest.parts.len(),
+                manifest.bytes,
+                manifest.part_bytes
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+/// Envoi en cours, décrit par l'objet `chemin.upload`.
+struct PendingUpload {
+    data: String,
+    upload_id: String,
+    part_bytes: u64,
+}
+
+impl PendingUpload {
+    fn to_json(&self) -> String {
+        let mut out = String::from("{\"format\":");
+        json::write_u64(&mut out, MANIFEST_FORMAT);
+        out.push_str(",\"data\":");
+        json::write_string(&mut out, &self.data);
+        out.push_str(",\"upload_id\":");
+        json::write_string(&mut out, &self.upload_id);
+        out.push_str(",\"part_bytes\":");
+        json::write_u64(&mut out, self.part_bytes);
+        out.push_str("}\n");
+        out
+    }
+
+    fn from_json(text: &str) -> Result<PendingUpload> {
+        let fields = fields(text)?;
+        if integer(&fields, "format")? != MANIFEST_FORMAT {
+            return Err(invalid("format d'envoi inconnu"));
+        }
+        Ok(PendingUpload {
+            data: string(&fields, "data")?.to_string(),
+            upload_id: string(&fields, "upload_id")?.to_string(),
+            part_bytes: integer(&fields, "part_bytes")?,
+        })
+    }
+}
+
+/// Réponse HTTP lue en entier.
+struct Response {
+    status: u16,
+    /// En-têtes, noms en minuscules.
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        (self.headers.iter())
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Accès à l'objet d'une adresse, et aux objets voisins du même seau.
+struct Client<'a> {
+    /// Valeur de l'en-tête `Host`.
+    host: String,
+    /// Adresse de connexion, port compris.
+    address: String,
+    bucket: String,
+    key: String,
+    credentials: &'a ObjectStoreCredentials,
+}
+
+impl<'a> Client<'a> {
+    fn new(uri: &str, credentials: &'a ObjectStoreCredentials) -> Result<Self> {
+        let malformed = || {
+            Error::InvalidConfig(format!(
+                "adresse de stockage objet invalide '{}' : http://hôte[:port]/seau/chemin attendu",
+                uri
+            ))
+        };
+        let Some(rest) = uri.strip_prefix("http://") else {
+            if uri.starts_with("https://") {
+                return Err(Error::InvalidConfig(format!(
+                    "'{}' : TLS n'est pas pris en charge, passez par un point d'accès http \
+                     local ou un mandataire",
+                    uri
+                )));
+            }
+            return Err(malformed());
+        };
+        let (host, path) = rest.split_once('/').ok_or_else(malformed)?;
+        let (bucket, key) = path.split_once('/').ok_or_else(malformed)?;
+        if host.is_empty() || bucket.is_empty() || key.is_empty() || key.ends_with('/') {
+            return Err(malformed());
+        }
+        let address = match host.contains(':') {
+            true => host.to_string(),
+            false => format!("{}:80", host),
+        };
+        Ok(Client {
+            host: host.to_string(),
+            address,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            credentials,
+        })
+    }
+
+    /// Envoie la sauvegarde `bytes`, en reprenant un envoi interrompu du même contenu.
+    fn upload(&self, bytes: &[u8]) -> Result<UploadReport> {
+        let digest = hex(&sha256(bytes));
+        let data = format!("{}.{}.data", self.key, &digest[..16]);
+        let parts: Vec<&[u8]> = match bytes.is_empty() {
+            true => vec![bytes],
+            false => bytes.chunks(OBJECT_PART_BYTES).collect(),
+        };
+        let previous = self.manifest().ok().map(|manifest| manifest.data);
+        let pending = format!("{}{}", self.key, UPLOAD_SUFFIX);
+
+        let mut etags: BTreeMap<usize, String> = BTreeMap::new();
+        let resumable = self.pending(&pending)?.and_then(|state| {
+            match state.data == data && state.part_bytes == OBJECT_PART_BYTES as u64 {
+                true => Some(state),
+                false => {
+                    // L'envoi d'un autre contenu est abandonné ; un échec ne laisse que
+                    // des parties que le stockage finit par effacer.
+                    let _ = self.send("DELETE", &state.data, &[("uploadId", &state.upload_id)], &[], &[]);
+                    None
+                }
+            }
+        });
+        let listed = match &resumable {
+            Some(state) => self.list_parts(&data, &state.upload_id)?,
+            None => None,
+        };
+        let upload_id = match (resumable, listed) {
+            (Some(state), Some(listed)) => {
+                for (number, size, etag) in listed {
+                    if (1..=parts.len()).contains(&number) && parts[number - 1].len() as u64 == size {
+                        etags.insert(number, etag);
+                    }
+                }
+                state.upload_id
+            }
+            _ => self.start(&data, &pending)?,
+        };
+
+        let resumed_parts = etags.len();
+        for (index, part) in parts.iter().enumerate() {
+            let number = index + 1;
+            if etags.contains_key(&number) {
+                continue;
+            }
+            let query = [("partNumber", &*number.to_string()), ("uploadId", &*upload_id)];
+            let response = self.expect("PUT", &data, &query, &[], part)?;
+            let etag = response
+                .header("etag")
+                .ok_or_else(|| Error::Io(format!("partie {} de {} reçue sans ETag", number, data)))?;
+            etags.insert(number, etag.to_string());
+        }
+
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (number, etag) in &etags {
+            body.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                number,
+                xml_escape(etag)
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+        let response = self.expect("POST", &data, &[("uploadId", &upload_id)], &[], body.as_bytes())?;
+        // S3 peut signaler l'échec de l'assemblage dans une réponse 200.
+        if response.text().contains("<Error>") {
+            return Err(failure("POST", &data, &response));
+        }
+
+        let manifest = Manifest {
+            data: data.clone(),
+            bytes: bytes.len() as u64,
+            part_bytes: OBJECT_PART_BYTES as u64,
+            sha256: digest,
+            parts: parts.iter().map(|part| hex(&sha256(part))).collect(),
+        };
+        self.expect("PUT", &self.key, &[], &[], manifest.to_json().as_bytes())?;
+        // Le manifeste est écrit : le ménage peut échouer sans rien invalider.
+        let _ = self.send("DELETE", &pending, &[], &[], &[]);
+        if let Some(previous) = previous.filter(|previous| *previous != data) {
+            let _ = self.send("DELETE", &previous, &[], &[], &[]);
+        }
+        Ok(UploadReport {
+            bytes: bytes.len() as u64,
+            parts: parts.len(),
+            resumed_parts,
+        })
+    }
+
+    /// Lit la sauvegarde désignée par le manifeste et vérifie chacune de ses parties.
+    fn download(&self) -> Result<Vec<u8>> {
+        let manifest = self.manifest()?;
+        let mut bytes = Vec::with_capacity(manifest.bytes as usize);
+        for (index, expected) in manifest.parts.iter().enumerate() {
+            let start = index as u64 * manifest.part_bytes;
+            let end = (start + manifest.part_bytes).min(manifest.bytes);
+            let range = format!("bytes={}-{}", start, end.saturating_sub(1));
+            let mut attempt = 1;
+            let part = loop {
+                let part = match end > start {
+                    true => self.expect("GET", &manifest.data, &[], &[("range", &range)], &[])?.body,
+                    false => Vec::new(),
+                };
+                if part.len() as u64 == end - start && hex(&sha256(&part)) == *expected {
+                    break part;
+                }
+                if attempt == MAX_ATTEMPTS {
+                    return Err(Error::InvalidSnapshot(format!(
+                        "la partie {} de '{}' ne correspond pas à son empreinte",
+                        index + 1,
+                        manifest.data
+                    )));
+                }
+                thread::sleep(RETRY_DELAY * 2u32.pow(attempt - 1));
+                attempt += 1;
+            };
+            bytes.extend_from_slice(&part);
+        }
+        if hex(&sha256(&bytes)) != manifest.sha256 {
+            return Err(invalid("la sauvegarde assemblée ne correspond pas à son empreinte"));
+        }
+        Ok(bytes)
+    }
+
+    /// Lit le manifeste de l'adresse.
+    fn manifest(&self) -> Result<Manifest> {
+        let response = self.send("GET", &self.key, &[], &[], &[])?;
+        match response.status {
+            404 => Err(Error::InvalidSnapshot(format!(
+                "aucune sauvegarde à '{}/{}'",
+                self.bucket, self.key
+            ))),
+            _ if response.is_success() => Manifest::from_json(&response.text()),
+            _ => Err(failure("GET", &self.key, &response)),
+        }
+    }
+
+    /// Lit l'envoi en cours décrit par l'objet `pending`, s'il existe et se lit.
+    fn pending(&self, pending: &str) -> Result<Option<PendingUpload>> {
+        let response = self.send("GET", pending, &[], &[], &[])?;
+        match response.status {
+            404 => Ok(None),
+            _ if response.is_success() => Ok(PendingUpload::from_json(&response.text()).ok()),
+            _ => Err(failure("GET", pending, &response)),
+        }
+    }
+
+    /// Commence l'envoi en parties de l'objet `data` et le décrit dans `pending`.
+    fn start(&self, data: &str, pending: &str) -> Result<String> {
+        let response = self.expect("POST", data, &[("uploads", "")], &[], &[])?;
+        let upload_id = xml_values(&response.text(), "UploadId")
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::Io(format!("envoi de {} commencé sans identifiant", data)))?;
+        let state = PendingUpload {
+            data: data.to_string(),
+            upload_id: upload_id.clone(),
+            part_bytes: OBJECT_PART_BYTES as u64,
+        };
+        self.expect("PUT", pending, &[], &[], state.to_json().as_bytes())?;
+        Ok(upload_id)
+    }
+
+    /// Liste les parties `(numéro, taille, ETag)` déjà reçues d'un envoi, `None` si le
+    /// stockage ne le connaît plus.
+    fn list_parts(&self, data: &str, upload_id: &str) -> Result<Option<Vec<(usize, u64, String)>>> {
+        let mut parts = Vec::new();
+        let mut marker = String::new();
+        loop {
+            let mut query = vec![("uploadId", upload_id)];
+            if !marker.is_empty() {
+                query.push(("part-number-marker", &marker));
+            }
+            let response = self.send("GET", data, &query, &[], &[])?;
+            if response.status == 404 {
+                return Ok(None);
+            }
+            if !response.is_success() {
+                return Err(failure("GET", data, &response));
+            }
+            let text = response.text();
+            for part in xml_values(&text, "Part") {
+                let field = |tag| xml_values(&part, tag).into_iter().next();
+                let number = field("PartNumber").and_then(|n| n.parse().ok());
+                let size = field("Size").and_then(|n| n.parse().ok());
+                if let (Some(number), Some(size), Some(etag)) = (number, size, field("ETag")) {
+                    parts.push((number, size, etag));
+                }
+            }
+            let truncated = xml_values(&text, "IsTruncated").first().map(String::as_str) == Some("true");
+            match xml_values(&text, "NextPartNumberMarker").into_iter().next() {
+                Some(next) if truncated && next != marker => marker = next,
+                _ => return Ok(Some(parts)),
+            }
+        }
+    }
+
+    /// Envoie une requête comme [`Client::send`] et exige une réponse 2xx.
+    fn expect(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Response> {
+        let response = self.send(method, key, query, headers, body)?;
+        match response.is_success() {
+            true => Ok(response),
+            false => Err(failure(method, key, &response)),
+        }
+    }
+
+    /// Envoie une requête signée sur l'objet `key` du seau, retentée tant qu'elle échoue
+    /// de façon passagère et qu'il reste des essais ; la dernière réponse est retournée
+    /// quel que soit son statut.
+    fn send(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            match self.attempt(method, key, query, headers, body) {
+                Ok(response) if response.status < 500 && response.status != 429 => {
+                    return Ok(response)
+                }
+                Ok(response) if attempt == MAX_ATTEMPTS => return Ok(response),
+                Err(e) if attempt == MAX_ATTEMPTS => {
+                    return Err(Error::Io(format!(
+                        "stockage objet {} injoignable après {} essais : {}",
+                        self.address, MAX_ATTEMPTS, e
+                    )))
+                }
+                _ => {}
+            }
+            thread::sleep(RETRY_DELAY * 2u32.pow(attempt - 1));
+            attempt += 1;
+        }
+    }
+
+    /// Envoie une seule fois une requête signée et lit sa réponse.
+    fn attempt(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> io::Result<Response> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = format!("/{}/{}", encode(&self.bucket, false), encode(key, true));
+        let mut query: Vec<(String, String)> = (query.iter())
+            .map(|(name, value)| (encode(name, false), encode(value, false)))
+            .collect();
+        query.sort_unstable();
+        let query: Vec<String> = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        let query = query.join("&");
+        let payload = hex(&sha256(body));
+        let stamp = amz_date(now);
+        let mut signed: Vec<(&str, &str)> = vec![
+            ("host", &self.host),
+            ("x-amz-content-sha256", &payload),
+            ("x-amz-date", &stamp),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            signed.push(("x-amz-security-token", token));
+        }
+        signed.extend_from_slice(headers);
+        signed.sort_unstable();
+        let authorization = authorization(self.credentials, method, &path, &query, &signed, &payload, &stamp);
+
+        let mut request = format!("{} {}", method, path);
+        if !query.is_empty() {
+            request.push('?');
+            request.push_str(&query);
+        }
+        request.push_str(" HTTP/1.1\r\n");
+        for (name, value) in &signed {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str(&format!(
+            "authorization: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            authorization,
+            body.len()
+        ));
+        let mut stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+        read_response(BufReader::new(stream))
+    }
+}
+
+/// Calcule l'en-tête `Authorization` d'une requête selon AWS Signature Version 4.
+///
+/// # Arguments
+/// * `path` - Chemin déjà encodé.
+/// * `query` - Paramètres déjà encodés et triés, `nom=valeur` séparés par `&`.
+/// * `headers` - En-têtes signés, noms en minuscules, triés par nom.
+/// * `payload` - Empreinte SHA-256 du corps, en hexadécimal.
+/// * `stamp` - Date de la requête, `AAAAMMJJTHHMMSSZ`.
+fn authorization(
+    credentials: &ObjectStoreCredentials,
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(&str, &str)],
+    payload: &str,
+    stamp: &str,
+) -> String {
+    let names: Vec<&str> = headers.iter().map(|(name, _)| *name).collect();
+    let names = names.join(";");
+    let mut canonical = format!("{}\n{}\n{}\n", method, path, query);
+    for (name, value) in headers {
+        canonical.push_str(&format!("{}:{}\n", name, value.trim()));
+    }
+    canonical.push_str(&format!("\n{}\n{}", names, payload));
+    let date = &stamp[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        stamp,
+        scope,
+        hex(&sha256(canonical.as_bytes()))
+    );
+    let secret = format!("AWS4{}", credentials.secret_access_key).into_bytes();
+    let key = [date, &credentials.region, "s3", "aws4_request"]
+        .iter()
+        .fold(secret, |key, part| HmacSha256::new(&key).mac(&[part.as_bytes()]).to_vec());
+    let signature = hex(&HmacSha256::new(&key).mac(&[to_sign.as_bytes()]));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, names, signature
+    )
+}
+
+/// Lit une réponse HTTP/1.1 dont le corps est délimité par `Content-Length`, découpé
+/// en tranches (`Transfer-Encoding: chunked`) ou terminé par la fermeture de la
+/// connexion.
+fn read_response(mut reader: impl BufRead) -> io::Result<Response> {
+    let malformed = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connexion fermée sans réponse",
+        ));
+    }
+    let status = (line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| malformed("ligne de statut HTTP invalide"))?;
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(malformed("réponse HTTP interrompue"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let find = |name: &str| {
+        (headers.iter())
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.clone())
+    };
+    let mut body = Vec::new();
+    if find("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked")) {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = line.trim().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size, 16).map_err(|_| malformed("tranche HTTP invalide"))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = find("content-length") {
+        let length = length.parse().map_err(|_| malformed("Content-Length invalide"))?;
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Erreur d'une requête refusée par le stockage, avec le code S3 de la réponse.
+fn failure(method: &str, key: &str, response: &Response) -> Error {
+    let code = xml_values(&response.text(), "Code").into_iter().next();
+    Error::Io(format!(
+        "stockage objet : {} {} refusé ({}{})",
+        method,
+        key,
+        response.status,
+        code.map(|code| format!(" {}", code)).unwrap_or_default()
+    ))
+}
+
+/// Retourne le contenu des éléments `<tag>…</tag>` de `text`, entités décodées.
+fn xml_values(text: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut values = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else {
+            break;
+        };
+        values.push(
+            after[..end]
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&"),
+        );
+        rest = &after[end + close.len()..];
+    }
+    values
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Encode `text` pour un chemin ou un paramètre signé : seuls les caractères non
+/// réservés de l'URI, et `/` si `keep_slash`, restent tels quels.
+fn encode(text: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Date `AAAAMMJJTHHMMSSZ` de `secs` secondes depuis l'époque Unix.
+fn amz_date(secs: u64) -> String {
+    let (year, month, day, seconds) = backup::utc(secs);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(bytes);
+    hash.finish()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidSnapshot(format!("manifeste du stockage objet : {}", message))
+}
+
+fn fields(text: &str) -> Result<BTreeMap<String, Value>> {
+    match json::parse(text).map_err(|message| invalid(&message))? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(invalid("objet attendu")),
+    }
+}
+
+fn integer(fields: &BTreeMap<String, Value>, key: &str) -> Result<u64> {
+    match fields.get(key) {
+        Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as u64),
+        _ => Err(invalid(&format!("entier « {} » attendu", key))),
+    }
+}
+
+fn string<'a>(fields: &'a BTreeMap<String, Value>, key: &str) -> Result<&'a str> {
+    match fields.get(key) {
+        Some(Value::String(s)) => Ok(s),
+        _ => Err(invalid(&format!("chaîne « {} » attendue", key))),
+    }
+}
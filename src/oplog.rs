@@ -0,0 +1,262 @@
+//! Historique des dernières écritures d'une collection.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::collection::Collection;
+use crate::error::Result;
+use crate::progress::BatchReport;
+
+/// Nombre d'opérations conservées par défaut dans l'historique d'une collection.
+pub const DEFAULT_OPERATION_LOG_CAPACITY: usize = 64;
+
+/// Type d'une opération d'écriture de l'historique.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    /// Insertion ou mise à jour d'un document, y compris par [`Collection::insert`].
+    Upsert,
+    /// Insertion d'un lot de documents ([`Collection::upsert_batch`],
+    /// [`Collection::upsert_rows`]).
+    Batch,
+    /// Import d'un flux JSONL ([`Collection::import_jsonl`]).
+    Import,
+    /// Suppression d'un document ou d'un espace de noms.
+    Delete,
+    /// Compactage des documents supprimés ([`Collection::compact`]).
+    Compact,
+    /// Suppression de tous les documents ([`Collection::clear`]).
+    Clear,
+    /// Lot d'opérations appliqué en bloc ([`Collection::apply`]).
+    Apply,
+}
+
+/// Opération d'écriture réussie, conservée par l'historique d'une collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationRecord {
+    /// Type de l'opération.
+    pub kind: OperationKind,
+    /// Nombre de documents écrits ou supprimés.
+    pub items: usize,
+    /// Durée de l'opération.
+    pub duration: Duration,
+    /// Début de l'opération.
+    pub timestamp: SystemTime,
+    /// Vrai si la durée atteint le seuil de
+    /// [`Collection::with_slow_operation_threshold`].
+    pub slow: bool,
+}
+
+/// Compteurs cumulés des écritures d'une collection depuis sa création ou son
+/// chargement ([`Collection::write_stats`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteStats {
+    /// Vecteurs insérés ou mis à jour.
+    pub upserts: u64,
+    /// Documents supprimés, y compris par éviction.
+    pub deletes: u64,
+    /// Opérations lentes.
+    pub slow_operations: u64,
+}
+
+/// Dernières opérations d'écriture d'une collection, dans un tampon circulaire.
+#[derive(Debug, Clone)]
+pub(crate) struct OperationLog {
+    records: VecDeque<OperationRecord>,
+    capacity: usize,
+    slow_threshold: Option<Duration>,
+    pub(crate) stats: WriteStats,
+    /// Vrai pendant une opération suivie : les opérations qu'elle appelle ne sont pas
+    /// enregistrées séparément.
+    active: bool,
+}
+
+impl Default for OperationLog {
+    fn default() -> Self {
+        OperationLog {
+            records: VecDeque::new(),
+            capacity: DEFAULT_OPERATION_LOG_CAPACITY,
+            slow_threshold: None,
+            stats: WriteStats::default(),
+            active: false,
+        }
+    }
+}
+
+impl OperationLog {
+    fn push(&mut self, record: OperationRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        } else if self.records.capacity() == 0 {
+            self.records.reserve_exact(self.capacity);
+        }
+        self.records.push_back(record);
+    }
+}
+
+impl Collection {
+    /// Fixe le nombre d'opérations d'écriture conservées par l'historique
+    /// ([`Collection::recent_operations`]), par défaut
+    /// [`DEFAULT_OPERATION_LOG_CAPACITY`] ; 0 désactive l'historique. Les opérations les
+    /// plus anciennes au-delà de la nouvelle capacité sont oubliées.
+    ///
+    /// # Arguments
+    /// * `capacity` - Nombre maximal d'opérations conservées.
+    pub fn with_operation_log(mut self, capacity: usize) -> Self {
+        let log = &mut self.operations;
+        let excess = log.records.len().saturating_sub(capacity);
+        log.records.drain(..excess);
+        log.records.shrink_to(capacity);
+        log.capacity = capacity;
+        self
+    }
+
+    /// Marque comme lentes les opérations d'écriture qui durent au moins `threshold`
+    /// ([`OperationRecord::slow`], [`WriteStats::slow_operations`]), ou aucune avec `None`.
+    ///
+    /// # Arguments
+    /// * `threshold` - Durée à partir de laquelle une opération est lente.
+    pub fn with_slow_operation_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.operations.slow_threshold = threshold;
+        self
+    }
+
+    /// Retourne les dernières opérations d'écriture réussies, de la plus ancienne à la
+    /// plus récente.
+    ///
+    /// L'historique n'est pas sauvegardé ; une collection chargée commence sans
+    /// historique.
+    pub fn recent_operations(&self) -> Vec<OperationRecord> {
+        self.operations.records.iter().copied().collect()
+    }
+
+    /// Retourne les compteurs cumulés des écritures de la collection.
+    pub fn write_stats(&self) -> WriteStats {
+        self.operations.stats
+    }
+
+    /// Exécute `op` et l'enregistre dans l'historique avec le nombre de documents donné
+    /// par `items`, ou pas du tout si `items` retourne `None`.
+    ///
+    /// Une opération appelée par une autre opération suivie n'est pas enregistrée.
+    pub(crate) fn logged<T>(
+        &mut self,
+        kind: OperationKind,
+        op: impl FnOnce(&mut Self) -> T,
+        items: impl FnOnce(&T) -> Option<usize>,
+    ) -> T {
+        if self.operations.active {
+            return op(self);
+        }
+        self.operations.active = true;
+        let timestamp = SystemTime::now();
+        let started = Instant::now();
+        let result = op(self);
+        let duration = started.elapsed();
+        let log = &mut self.operations;
+        log.active = false;
+        if let Some(items) = items(&result) {
+            let slow = log
+                .slow_threshold
+                .is_some_and(|threshold| duration >= threshold);
+            log.stats.slow_operations += u64::from(slow);
+            log.push(OperationRecord {
+                kind,
+                items,
+                duration,
+                timestamp,
+                slow,
+            });
+        }
+        result
+    }
+}
+
+/// Compte un document pour une écriture réussie.
+pub(crate) fn one_if_ok<T>(result: &Result<T>) -> Option<usize> {
+    result.as_ref().ok().map(|_| 1)
+}
+
+/// Compte les documents appliqués par un lot réussi.
+pub(crate) fn applied(result: &Result<BatchReport>) -> Option<usize> {
+    result.as_ref().ok().map(|report| report.applied as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn kinds(collection: &Collection) -> Vec<(OperationKind, usize)> {
+        (collection.recent_operations().iter())
+            .map(|record| (record.kind, record.items))
+            .collect()
+    }
+
+    #[test]
+    fn the_oldest_operations_are_evicted_at_capacity() {
+        let mut collection = Collection::new().with_operation_log(3);
+        let keys: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            collection.upsert(*key, [1.0, i as f32]).unwrap();
+        }
+        collection.delete(&keys[0]);
+        assert_eq!(
+            kinds(&collection),
+            [(OperationKind::Upsert, 1), (OperationKind::Upsert, 1), (OperationKind::Delete, 1)]
+        );
+        // Réduire la capacité oublie les plus anciennes, 0 désactive l'historique.
+        let mut collection = collection.with_operation_log(1);
+        assert_eq!(kinds(&collection), [(OperationKind::Delete, 1)]);
+        collection.delete(&keys[1]);
+        assert_eq!(kinds(&collection), [(OperationKind::Delete, 1)]);
+        let mut collection = collection.with_operation_log(0);
+        collection.upsert(keys[0], [1.0, 0.0]).unwrap();
+        assert!(collection.recent_operations().is_empty());
+        let stats = collection.write_stats();
+        assert_eq!((stats.upserts, stats.deletes), (6, 2));
+    }
+
+    #[test]
+    fn records_describe_each_operation() {
+        let mut collection = Collection::new();
+        let items: Vec<(Uuid, Vec<f32>)> = (0..10).map(|i| (Uuid::new_v4(), vec![1.0, i as f32])).collect();
+        let before = SystemTime::now();
+        let started = Instant::now();
+        collection.upsert_batch(items.clone(), None).unwrap();
+        let elapsed = started.elapsed();
+        // Les écritures refusées et les suppressions sans effet comptent 0 ou rien.
+        assert!(collection.upsert(Uuid::new_v4(), [1.0]).is_err());
+        collection.delete(&Uuid::new_v4());
+        collection.clear(false);
+
+        let records = collection.recent_operations();
+        assert_eq!(
+            kinds(&collection),
+            [(OperationKind::Batch, 10), (OperationKind::Delete, 0), (OperationKind::Clear, 10)]
+        );
+        let batch = records[0];
+        assert!(batch.timestamp >= before && batch.timestamp <= SystemTime::now());
+        assert!(batch.duration <= elapsed);
+        assert!(records.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert!(!records.iter().any(|record| record.slow));
+        let stats = collection.write_stats();
+        assert_eq!((stats.upserts, stats.deletes, stats.slow_operations), (10, 0, 0));
+    }
+
+    #[test]
+    fn operations_above_the_threshold_are_slow() {
+        let mut collection = Collection::new().with_slow_operation_threshold(Some(Duration::ZERO));
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        collection.upsert(Uuid::new_v4(), [2.0, 1.0]).unwrap();
+        assert!(collection.recent_operations().iter().all(|record| record.slow));
+        assert_eq!(collection.write_stats().slow_operations, 2);
+
+        let mut collection = collection.with_slow_operation_threshold(Some(Duration::from_secs(3600)));
+        collection.upsert(Uuid::new_v4(), [1.0, 1.0]).unwrap();
+        assert!(!collection.recent_operations().last().unwrap().slow);
+        assert_eq!(collection.write_stats().slow_operations, 2);
+    }
+}
@@ -0,0 +1,298 @@
+use std::cmp::Ordering;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::parallel::{SearchRuntime, PARALLEL_THRESHOLD};
+use crate::rng::Rng;
+use crate::similarity::{Metric, Scorer};
+
+/// Méthode utilisée par [`Collection::outliers`] pour attribuer un score d'anomalie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlierMethod {
+    /// Écart, en nombre d'écarts-types, entre l'éloignement d'un document au centroïde
+    /// et l'éloignement moyen de la collection.
+    CentroidZScore,
+    /// Éloignement moyen d'un document à ses `k` plus proches voisins.
+    KnnDistance { k: usize },
+}
+
+/// Paramètres de [`Collection::outliers`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OutlierParams {
+    /// Nombre de documents tirés au hasard pour servir de référence (centroïde ou
+    /// voisins). `None` utilise toute la collection.
+    pub sample_size: Option<usize>,
+    /// Nombre maximal de documents retournés. `None` les retourne tous.
+    pub limit: Option<usize>,
+    /// Graine du tirage de l'échantillon.
+    pub seed: u64,
+}
+
+impl Collection {
+    /// Attribue un score d'anomalie à chaque document de la collection.
+    ///
+    /// L'éloignement entre deux vecteurs suit la mesure de la collection : `1 - cos`
+    /// pour `Cosine`, l'opposé du produit scalaire pour `Dot` et la distance elle-même
+    /// pour `Euclidean` et `Manhattan`. Tous les documents sont notés, mais seuls ceux de
+    /// l'échantillon servent de référence, ce qui borne le coût sur les grandes collections.
+    ///
+    /// # Arguments
+    /// * `method` - Méthode de calcul du score.
+    /// * `params` - Taille d'échantillon, nombre de résultats et graine.
+    ///
+    /// # Retourne
+    /// * Result<Vec<(Uuid, f32)>> - Les documents triés du plus anormal au moins anormal.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `k` ou la taille d'échantillon vaut 0.
+    /// * `Error::DimensionMismatch` - Si les documents n'ont pas tous la même dimension.
+    pub fn outliers(
+        &self,
+        method: OutlierMethod,
+        params: &OutlierParams,
+    ) -> Result<Vec<(Uuid, f32)>> {
+        if method == (OutlierMethod::KnnDistance { k: 0 }) {
+            return Err(Error::InvalidConfig(
+                "k doit être strictement positif".into(),
+            ));
+        }
+        if params.sample_size == Some(0) {
+            return Err(Error::InvalidConfig(
+                "la taille d'échantillon doit être strictement positive".into(),
+            ));
+        }
+
+        let mut entries: Vec<(&Uuid, &Vec<f32>)> = self
+            .documents
+            .iter()
+            .map(|(key, vector)| (key, &**vector))
+            .collect();
+        entries.sort_unstable_by_key(|(key, _)| **key);
+        if let Some((_, first)) = entries.first() {
+            if let Some((_, other)) = entries.iter().find(|(_, v)| v.len() != first.len()) {
+                return Err(Error::DimensionMismatch {
+                    expected: first.len(),
+                    got: other.len(),
+                });
+            }
+        }
+        let reference = sample(&entries, params.sample_size, params.seed);
+
+        let metric = Scorer {
+            metric: self.config.metric,
+            weights: self.dimension_weights(),
+        };
+        let mut scored = match method {
+            OutlierMethod::CentroidZScore => {
+                // Sans échantillon, le centroïde est la moyenne tenue à jour.
+                let centroid = match reference.len() == entries.len() {
+                    true => self.stats.mean(),
+                    false => None,
+                };
+                centroid_z_scores(self.runtime, metric, &entries, &reference, centroid)
+            }
+            OutlierMethod::KnnDistance { k } => {
+                self.runtime
+                    .map_chunks(&entries, PARALLEL_THRESHOLD, |chunk| {
+                        chunk
+                            .iter()
+                            .map(|(key, vector)| {
+                                (**key, knn_distance(metric, key, vector, &reference, k))
+                            })
+                            .collect()
+                    })
+            }
+        };
+
+        scored.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        if let Some(limit) = params.limit {
+            scored.truncate(limit);
+        }
+        Ok(scored)
+    }
+}
+
+/// Éloignement entre deux vecteurs selon `metric`, d'autant plus grand qu'ils diffèrent.
+fn dissimilarity(metric: Scorer<'_>, a: &[f32], b: &[f32]) -> f32 {
+    let score = metric.score_unchecked(a, b);
+    match metric.metric {
+        Metric::Cosine => 1.0 - score,
+        Metric::Dot => -score,
+        Metric::Euclidean | Metric::Manhattan => score,
+    }
+}
+
+/// Tire sans remise `size` entrées au hasard (Fisher-Yates partiel), ou les garde toutes.
+pub(crate) fn sample<'a>(
+    entries: &[(&'a Uuid, &'a Vec<f32>)],
+    size: Option<usize>,
+    seed: u64,
+) -> Vec<(&'a Uuid, &'a Vec<f32>)> {
+    let mut pool = entries.to_vec();
+    let Some(size) = size.filter(|&size| size < pool.len()) else {
+        return pool;
+    };
+    let mut rng = Rng::new(seed);
+    for i in 0..size {
+        let j = i + (rng.next_u64() % (pool.len() - i) as u64) as usize;
+        pool.swap(i, j);
+    }
+    pool.truncate(size);
+    pool
+}
+
+fn centroid_z_scores(
+    runtime: SearchRuntime,
+    metric: Scorer<'_>,
+    entries: &[(&Uuid, &Vec<f32>)],
+    reference: &[(&Uuid, &Vec<f32>)],
+    mean: Option<Vec<f64>>,
+) -> Vec<(Uuid, f32)> {
+    let Some((_, first)) = reference.first() else {
+        return Vec::new();
+    };
+    let mean = mean.unwrap_or_else(|| {
+        let mut sum = vec![0.0f64; first.len()];
+        for (_, vector) in reference {
+            for (s, x) in sum.iter_mut().zip(vector.iter()) {
+                *s += *x as f64;
+            }
+        }
+        sum.iter().map(|s| s / reference.len() as f64).collect()
+    });
+    let centroid: Vec<f32> = mean.iter().map(|m| *m as f32).collect();
+
+    let distances = runtime.map_chunks(entries, PARALLEL_THRESHOLD, |chunk| {
+        chunk
+            .iter()
+            .map(|(key, vector)| (**key, dissimilarity(metric, vector, &centroid) as f64))
+            .collect()
+    });
+    let count = distances.len() as f64;
+    let mean = distances.iter().map(|(_, d)| d).sum::<f64>() / count;
+    let variance = distances
+        .iter()
+        .map(|(_, d)| (d - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    let std_dev = variance.sqrt();
+    distances
+        .into_iter()
+        .map(|(key, d)| {
+            let z = if std_dev > 0.0 {
+                (d - mean) / std_dev
+            } else {
+                0.0
+            };
+            (key, z as f32)
+        })
+        .collect()
+}
+
+/// Éloignement moyen de `vector` à ses `k` plus proches voisins de `reference`,
+/// le document lui-même exclu. Vaut 0.0 si la référence ne contient aucun autre document.
+fn knn_distance(
+    metric: Scorer<'_>,
+    key: &Uuid,
+    vector: &[f32],
+    reference: &[(&Uuid, &Vec<f32>)],
+    k: usize,
+) -> f32 {
+    let mut distances: Vec<f32> = reference
+        .iter()
+        .filter(|(other, _)| *other != key)
+        .map(|(_, other)| dissimilarity(metric, vector, other))
+        .collect();
+    if distances.is_empty() {
+        return 0.0;
+    }
+    let k = k.min(distances.len());
+    if k < distances.len() {
+        distances.select_nth_unstable_by(k - 1, |a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    }
+    distances[..k].iter().sum::<f32>() / k as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+    use crate::synthetic::VectorGenerator;
+
+    /// Collection de 300 documents proches d'un même centre, plus un document planté loin
+    /// d'eux.
+    fn planted(metric: Metric, far: &[f32]) -> (Collection, Uuid) {
+        let config = CollectionConfig::builder().metric(metric).build().unwrap();
+        let mut collection = Collection::from_config(config);
+        let mut generator = VectorGenerator::new(far.len(), 8);
+        let center = vec![1.0; far.len()];
+        for _ in 0..300 {
+            let vector = generator.vector_near(&center, 0.1);
+            collection.upsert(generator.uuid(), vector).unwrap();
+        }
+        let outlier = generator.uuid();
+        collection.upsert(outlier, far.to_vec()).unwrap();
+        (collection, outlier)
+    }
+
+    #[test]
+    fn planted_vector_ranks_first() {
+        for (metric, far) in [
+            (Metric::Euclidean, [9.0; 6]),
+            (Metric::Manhattan, [9.0; 6]),
+            (Metric::Cosine, [-1.0, 1.0, -1.0, 1.0, -1.0, 1.0]),
+        ] {
+            let (collection, outlier) = planted(metric, &far);
+            for method in [OutlierMethod::CentroidZScore, OutlierMethod::KnnDistance { k: 5 }] {
+                let scores = collection.outliers(method, &OutlierParams::default()).unwrap();
+                assert_eq!(scores.len(), collection.len());
+                assert_eq!(scores[0].0, outlier, "{:?} {:?}", metric, method);
+                assert!(scores.windows(2).all(|w| w[0].1 >= w[1].1));
+            }
+        }
+    }
+
+    #[test]
+    fn sample_and_limit_bound_the_result() {
+        let (collection, outlier) = planted(Metric::Euclidean, &[9.0; 6]);
+        let params = OutlierParams {
+            sample_size: Some(50),
+            limit: Some(3),
+            seed: 4,
+        };
+        for method in [OutlierMethod::CentroidZScore, OutlierMethod::KnnDistance { k: 5 }] {
+            let scores = collection.outliers(method, &params).unwrap();
+            assert_eq!(scores.len(), 3);
+            assert_eq!(scores[0].0, outlier);
+            // Le même tirage donne les mêmes scores.
+            assert_eq!(scores, collection.outliers(method, &params).unwrap());
+        }
+    }
+
+    #[test]
+    fn zero_k_or_sample_is_refused() {
+        let (collection, _) = planted(Metric::Euclidean, &[9.0; 2]);
+        let knn = OutlierMethod::KnnDistance { k: 0 };
+        assert!(matches!(
+            collection.outliers(knn, &OutlierParams::default()),
+            Err(Error::InvalidConfig(_))
+        ));
+        let params = OutlierParams {
+            sample_size: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            collection.outliers(OutlierMethod::CentroidZScore, &params),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!((Collection::new().outliers(OutlierMethod::CentroidZScore, &OutlierParams::default()))
+            .unwrap()
+            .is_empty());
+    }
+}
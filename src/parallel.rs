@@ -0,0 +1,445 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::similarity;
+
+/// Nombre d'éléments à partir duquel un traitement est réparti sur plusieurs threads.
+pub(crate) const PARALLEL_THRESHOLD: usize = 4096;
+
+/// Dimension par défaut à partir de laquelle [`SearchRuntime::cosine`] répartit une
+/// comparaison sur plusieurs threads. Un passage coûte environ 2 ns par coordonnée et
+/// chaque thread créé quelques dizaines de microsecondes : en deçà, le découpage est
+/// plus lent que le calcul en un passage.
+pub const DEFAULT_CHUNKED_DIMENSION: usize = 65_536;
+
+/// Nombre de tranches mesurées par un parcours adaptatif avant que la taille des
+/// suivantes ne soit figée.
+const ADAPTIVE_SAMPLES: u32 = 8;
+
+/// Découpage adaptatif des parcours ([`SearchRuntime::with_adaptive_chunks`]).
+///
+/// Chaque parcours commence par des tranches de `min_size` éléments, mesure la durée de
+/// ses premières tranches et rapproche la taille des suivantes de celle qui prendrait
+/// `target`, entre `min_size` et `max_size`. La mesure repart de zéro à chaque parcours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveChunks {
+    /// Durée visée du traitement d'une tranche.
+    pub target: Duration,
+    /// Taille minimale des tranches, celle des premières de chaque parcours.
+    pub min_size: usize,
+    /// Taille maximale des tranches.
+    pub max_size: usize,
+}
+
+impl Default for AdaptiveChunks {
+    fn default() -> Self {
+        AdaptiveChunks {
+            target: Duration::from_millis(1),
+            min_size: 256,
+            max_size: 65_536,
+        }
+    }
+}
+
+/// Réglage des parcours parallèles d'une collection : recherches exhaustives, graphe
+/// des plus proches voisins et détection d'anomalies.
+///
+/// Les threads sont créés pour chaque parcours et terminés à son issue : la bibliothèque
+/// ne maintient aucun pool global susceptible d'entrer en concurrence avec celui de
+/// l'application. Par défaut, un parcours utilise autant de threads que de cœurs
+/// disponibles et découpe les données en autant de tranches égales ; des tranches de
+/// taille fixe ([`SearchRuntime::with_chunk_size`]) ou mesurée
+/// ([`SearchRuntime::with_adaptive_chunks`]) équilibrent mieux les threads lorsque le
+/// coût des éléments varie, par exemple avec un filtre.
+///
+/// Le réglage ne change jamais les résultats : avec `SearchRuntime::new(1)`, les mêmes
+/// documents sont retournés dans le même ordre, calculés dans le thread appelant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchRuntime {
+    threads: Option<usize>,
+    chunk_size: Option<usize>,
+    adaptive: Option<AdaptiveChunks>,
+    chunked_dimension: Option<usize>,
+}
+
+impl SearchRuntime {
+    /// Crée un réglage utilisant au plus `threads` threads par parcours.
+    ///
+    /// # Arguments
+    /// * `threads` - Nombre de threads ; 0 est traité comme 1.
+    pub fn new(threads: usize) -> Self {
+        SearchRuntime {
+            threads: Some(threads.max(1)),
+            chunk_size: None,
+            adaptive: None,
+            chunked_dimension: None,
+        }
+    }
+
+    /// Fixe le nombre maximal d'éléments traités d'un seul tenant par un thread.
+    ///
+    /// Des tranches plus petites que la part de chaque thread sont réparties entre les
+    /// threads par blocs contigus. Remplace le découpage adaptatif.
+    ///
+    /// # Arguments
+    /// * `chunk_size` - Taille des tranches ; 0 est traité comme 1.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size.max(1));
+        self.adaptive = None;
+        self
+    }
+
+    /// Adapte la taille des tranches de chaque parcours à leur coût mesuré, plutôt que
+    /// de la fixer : les vecteurs de petite dimension sont traités par grandes tranches,
+    /// qui limitent le coût de la répartition, et ceux de grande dimension par petites
+    /// tranches, qui équilibrent les threads. Remplace une taille fixée par
+    /// [`SearchRuntime::with_chunk_size`].
+    ///
+    /// Les threads prennent les tranches au fur et à mesure plutôt que par blocs fixés
+    /// à l'avance. Seule [`CollectionLoader`](crate::CollectionLoader) garde des
+    /// tranches égales. Les résultats ne changent pas.
+    ///
+    /// # Arguments
+    /// * `adaptive` - Durée visée et bornes des tranches ; des bornes nulles sont
+    ///   traitées comme 1, et `max_size` est porté au moins à `min_size`.
+    pub fn with_adaptive_chunks(mut self, adaptive: AdaptiveChunks) -> Self {
+        let min_size = adaptive.min_size.max(1);
+        self.adaptive = Some(AdaptiveChunks {
+            min_size,
+            max_size: adaptive.max_size.max(min_size),
+            ..adaptive
+        });
+        self.chunk_size = None;
+        self
+    }
+
+    /// Fixe la dimension à partir de laquelle [`SearchRuntime::cosine`] répartit une
+    /// comparaison sur plusieurs threads, par défaut [`DEFAULT_CHUNKED_DIMENSION`].
+    ///
+    /// # Arguments
+    /// * `dimension` - Dimension minimale du découpage ; `usize::MAX` ne découpe jamais.
+    pub fn with_chunked_dimension(mut self, dimension: usize) -> Self {
+        self.chunked_dimension = Some(dimension);
+        self
+    }
+
+    /// Retourne la dimension à partir de laquelle une comparaison est découpée.
+    pub fn chunked_dimension(&self) -> usize {
+        self.chunked_dimension.unwrap_or(DEFAULT_CHUNKED_DIMENSION)
+    }
+
+    /// Calcule la similarité cosinus de deux vecteurs, par tranches réparties sur les
+    /// threads du réglage ([`similarity::cos_chunked`]) à partir de
+    /// [`SearchRuntime::chunked_dimension`], et en un seul passage
+    /// ([`similarity::cosine`]) en deçà.
+    ///
+    /// # Erreurs
+    /// * `Error::DimensionMismatch` - Si les dimensions diffèrent.
+    pub fn cosine(&self, a: &[f32], b: &[f32]) -> Result<f32> {
+        let threads = self.threads();
+        if a.len() < self.chunked_dimension() || a.len() != b.len() || threads == 1 {
+            return similarity::cosine(a, b);
+        }
+        Ok(similarity::cosine_chunked(
+            *self,
+            a,
+            b,
+            a.len().div_ceil(threads),
+        ))
+    }
+
+    /// Retourne le nombre de threads utilisés par un parcours.
+    pub fn threads(&self) -> usize {
+        self.threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    /// Retourne la taille des tranches, si elle est fixée.
+    pub fn chunk_size(&self) -> Option<usize> {
+        self.chunk_size
+    }
+
+    /// Retourne le découpage adaptatif, s'il est activé.
+    pub fn adaptive_chunks(&self) -> Option<AdaptiveChunks> {
+        self.adaptive
+    }
+
+    /// Applique `f` à des tranches de `items` réparties sur les threads du réglage et
+    /// concatène les résultats dans l'ordre des tranches.
+    ///
+    /// En deçà de `min_len` éléments, ou avec un seul thread, `f` est appelée une seule
+    /// fois sur toute la tranche dans le thread courant.
+    pub(crate) fn map_chunks<T, R, F>(&self, items: &[T], min_len: usize, f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&[T]) -> Vec<R> + Sync,
+    {
+        let threads = self.threads();
+        if items.len() < min_len || threads == 1 {
+            return f(items);
+        }
+        let share = items.len().div_ceil(threads);
+        if let Some(adaptive) = self.adaptive {
+            return map_adaptive(items, threads, share, adaptive, f);
+        }
+        let chunk_size = self.chunk_size.map_or(share, |size| size.min(share));
+        let chunks: Vec<&[T]> = items.chunks(chunk_size).collect();
+        let per_thread = chunks.len().div_ceil(threads);
+        let f = &f;
+        thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .chunks(per_thread)
+                .map(|group| {
+                    scope.spawn(move || group.iter().flat_map(|chunk| f(chunk)).collect::<Vec<R>>())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("thread de calcul interrompu"))
+                .collect()
+        })
+    }
+
+    /// Comme [`SearchRuntime::map_chunks`], mais `f` reçoit chaque tranche en écriture.
+    pub(crate) fn map_chunks_mut<T, R, F>(&self, items: &mut [T], min_len: usize, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(&mut [T]) -> Vec<R> + Sync,
+    {
+        let threads = self.threads();
+        if items.len() < min_len || threads == 1 {
+            return f(items);
+        }
+        let share = items.len().div_ceil(threads);
+        let chunk_size = self.chunk_size.map_or(share, |size| size.min(share));
+        let mut chunks: Vec<&mut [T]> = items.chunks_mut(chunk_size).collect();
+        let per_thread = chunks.len().div_ceil(threads);
+        let f = &f;
+        thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .chunks_mut(per_thread)
+                .map(|group| {
+                    scope.spawn(move || {
+                        group
+                            .iter_mut()
+                            .flat_map(|chunk| f(chunk))
+                            .collect::<Vec<R>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("thread de calcul interrompu"))
+                .collect()
+        })
+    }
+}
+
+/// Tranches restant à distribuer par [`map_adaptive`], et mesure des premières.
+struct Schedule {
+    next: usize,
+    size: usize,
+    samples: u32,
+    measured: usize,
+    elapsed: Duration,
+}
+
+impl Schedule {
+    /// Réserve la prochaine tranche de `len` éléments au plus.
+    fn take(&mut self, len: usize) -> Option<(usize, usize)> {
+        let start = self.next;
+        let end = (start + self.size).min(len);
+        self.next = end;
+        (start < end).then_some((start, end))
+    }
+
+    /// Prend en compte la durée d'une tranche de `items` éléments, tant que les
+    /// [`ADAPTIVE_SAMPLES`] premières ne sont pas mesurées.
+    fn record(&mut self, items: usize, elapsed: Duration, adaptive: AdaptiveChunks, share: usize) {
+        if self.samples >= ADAPTIVE_SAMPLES {
+            return;
+        }
+        self.samples += 1;
+        self.measured += items;
+        self.elapsed += elapsed;
+        let per_item = self.elapsed.as_secs_f64() / self.measured as f64;
+        let size = match per_item > 0.0 {
+            true => (adaptive.target.as_secs_f64() / per_item) as usize,
+            false => adaptive.max_size,
+        };
+        // Une tranche plus grande que la part d'un thread laisserait les autres inoccupés.
+        self.size = size.clamp(adaptive.min_size, adaptive.max_size).min(share);
+    }
+}
+
+/// Applique `f` à des tranches de `items` de taille adaptée à leur coût
+/// ([`SearchRuntime::with_adaptive_chunks`]), prises au fur et à mesure par `threads`
+/// threads, et concatène les résultats dans l'ordre des tranches.
+fn map_adaptive<T, R, F>(
+    items: &[T],
+    threads: usize,
+    share: usize,
+    adaptive: AdaptiveChunks,
+    f: F,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&[T]) -> Vec<R> + Sync,
+{
+    let schedule = Mutex::new(Schedule {
+        next: 0,
+        size: adaptive.min_size.min(share),
+        samples: 0,
+        measured: 0,
+        elapsed: Duration::ZERO,
+    });
+    let (f, schedule) = (&f, &schedule);
+    let mut parts: Vec<(usize, Vec<R>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut parts = Vec::new();
+                    loop {
+                        let taken = schedule.lock().expect("découpage empoisonné").take(items.len());
+                        let Some((start, end)) = taken else {
+                            return parts;
+                        };
+                        let started = Instant::now();
+                        parts.push((start, f(&items[start..end])));
+                        (schedule.lock().expect("découpage empoisonné")).record(
+                            end - start,
+                            started.elapsed(),
+                            adaptive,
+                            share,
+                        );
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("thread de calcul interrompu"))
+            .collect()
+    });
+    parts.sort_unstable_by_key(|(start, _)| *start);
+    parts.into_iter().flat_map(|(_, part)| part).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::database::BaseDeDonnees;
+    use crate::sharded::ShardedCollection;
+    use crate::synthetic::VectorGenerator;
+    use crate::store::{VectorStore, VectorStoreMut};
+
+    #[test]
+    fn a_single_thread_runs_serially_in_the_calling_thread() {
+        let items: Vec<u32> = (0..10_000).collect();
+        let caller = thread::current().id();
+        let calls = Mutex::new(0);
+        let mapped = SearchRuntime::new(1).with_chunk_size(16).map_chunks(&items, 0, |chunk| {
+            assert_eq!(thread::current().id(), caller);
+            *calls.lock().unwrap() += 1;
+            chunk.to_vec()
+        });
+        assert_eq!(mapped, items);
+        assert_eq!(calls.into_inner().unwrap(), 1);
+        assert_eq!(SearchRuntime::new(0).threads(), 1);
+    }
+
+    #[test]
+    fn one_thread_gives_the_results_of_the_parallel_runtime() {
+        let mut generator = VectorGenerator::new(16, 129);
+        let mut parallel = Collection::new().with_runtime(SearchRuntime::new(4).with_chunk_size(500));
+        let mut sharded = ShardedCollection::new(3).unwrap().with_runtime(SearchRuntime::new(4));
+        for _ in 0..PARALLEL_THRESHOLD + 500 {
+            let (key, vector) = (generator.uuid(), generator.vector());
+            parallel.upsert(key, vector.clone()).unwrap();
+            VectorStoreMut::upsert(&mut sharded, key, vector).unwrap();
+        }
+        let serial = parallel.clone().with_runtime(SearchRuntime::new(1));
+        let serial_sharded = sharded.clone().with_runtime(SearchRuntime::new(1));
+        for _ in 0..5 {
+            let query = generator.vector();
+            let expected = serial.search(&query, 25).unwrap();
+            assert_eq!(parallel.search(&query, 25).unwrap(), expected);
+            assert_eq!(VectorStore::search(&sharded, &query, 25).unwrap(), expected);
+            assert_eq!(VectorStore::search(&serial_sharded, &query, 25).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn database_runtime_reaches_existing_and_new_collections() {
+        let mut db = BaseDeDonnees::new();
+        db.add("avant".to_string());
+        let runtime = SearchRuntime::new(2).with_chunk_size(64);
+        let mut db = db.with_runtime(runtime);
+        db.add("apres".to_string());
+        assert_eq!(db.runtime(), runtime);
+        assert_eq!(db.get("avant").unwrap().runtime(), runtime);
+        assert_eq!(db.get("apres").unwrap().runtime(), runtime);
+    }
+
+    /// Parcourt `items` par tranches adaptatives et retourne la taille de chaque tranche,
+    /// après avoir vérifié que le résultat respecte l'ordre des éléments.
+    fn chunk_sizes(items: &[u32], adaptive: AdaptiveChunks, delay: Duration) -> Vec<usize> {
+        let sizes = Mutex::new(Vec::new());
+        let runtime = SearchRuntime::new(4).with_adaptive_chunks(adaptive);
+        let mapped = runtime.map_chunks(items, 0, |chunk| {
+            sizes.lock().unwrap().push(chunk.len());
+            for _ in chunk {
+                thread::sleep(delay);
+            }
+            chunk.to_vec()
+        });
+        assert_eq!(mapped, items);
+        sizes.into_inner().unwrap()
+    }
+
+    #[test]
+    fn slow_elements_keep_chunks_small() {
+        let items: Vec<u32> = (0..400).collect();
+        let adaptive = AdaptiveChunks {
+            target: Duration::from_millis(1),
+            min_size: 4,
+            max_size: 1024,
+        };
+        let sizes = chunk_sizes(&items, adaptive, Duration::from_micros(100));
+        // 100 µs par élément pour une tranche visée de 1 ms : au plus 10 éléments.
+        assert!(sizes.iter().all(|&size| (1..=10).contains(&size)), "{:?}", sizes);
+        assert_eq!(sizes.iter().sum::<usize>(), items.len());
+    }
+
+    #[test]
+    fn cheap_elements_grow_chunks_up_to_the_maximum() {
+        let items: Vec<u32> = (0..200_000).collect();
+        let adaptive = AdaptiveChunks {
+            target: Duration::from_millis(1),
+            min_size: 4,
+            max_size: 1024,
+        };
+        let sizes = chunk_sizes(&items, adaptive, Duration::ZERO);
+        assert_eq!(sizes.iter().max(), Some(&1024));
+        assert!(sizes.iter().all(|&size| size <= 1024));
+        assert_eq!(sizes.iter().sum::<usize>(), items.len());
+    }
+
+    #[test]
+    fn chunks_never_exceed_a_thread_share() {
+        let items: Vec<u32> = (0..64).collect();
+        let adaptive = AdaptiveChunks {
+            target: Duration::from_secs(1),
+            min_size: 1,
+            max_size: 1024,
+        };
+        let sizes = chunk_sizes(&items, adaptive, Duration::ZERO);
+        assert!(sizes.iter().all(|&size| size <= 16), "{:?}", sizes);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::json;
+
+/// Charge utile d'un document : des champs nommés associés à des valeurs.
+pub type Payload = BTreeMap<String, Value>;
+
+/// Valeur d'un champ de charge utile, sur le modèle des valeurs JSON.
+///
+/// Contrairement à `f64`, les nombres sont comparés par leur représentation binaire
+/// (après avoir confondu `-0.0` avec `0.0` et tous les NaN entre eux), ce qui permet
+/// d'utiliser une `Value` comme clé de `HashMap`.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Retourne la chaîne contenue, si la valeur en est une.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Retourne le nombre contenu, si la valeur en est un.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Retourne le booléen contenu, si la valeur en est un.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Représentation binaire canonique d'un nombre, utilisée pour l'égalité et le hachage.
+    fn number_bits(n: f64) -> u64 {
+        if n.is_nan() {
+            f64::NAN.to_bits()
+        } else if n == 0.0 {
+            0.0_f64.to_bits()
+        } else {
+            n.to_bits()
+        }
+    }
+}
+
+/// Réduit une charge utile aux champs de `selector`, en conservant l'imbrication des noms
+/// pointés (voir [`SearchParams::payload_selector`](crate::SearchParams::payload_selector)).
+pub(crate) fn select(payload: &Payload, selector: &[String]) -> Payload {
+    let mut selected = Payload::new();
+    for name in selector {
+        if let Some(value) = payload.get(name) {
+            selected.insert(name.clone(), value.clone());
+            continue;
+        }
+        let path: Vec<&str> = name.split('.').collect();
+        let mut value = payload.get(path[0]);
+        for part in &path[1..] {
+            value = match value {
+                Some(Value::Object(fields)) => fields.get(*part),
+                _ => None,
+            };
+        }
+        if let Some(value) = value {
+            insert_path(&mut selected, &path, value.clone());
+        }
+    }
+    selected
+}
+
+/// Range `value` sous le chemin `path`, en créant les objets intermédiaires.
+fn insert_path(fields: &mut BTreeMap<String, Value>, path: &[&str], value: Value) {
+    let (last, parents) = path.split_last().expect("chemin non vide");
+    let mut fields = fields;
+    for part in parents {
+        let entry = fields
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(BTreeMap::new()));
+        match entry {
+            Value::Object(inner) => fields = inner,
+            _ => return,
+        }
+    }
+    fields.entry(last.to_string()).or_insert(value);
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => {
+                Value::number_bits(*a) == Value::number_bits(*b)
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Null => {}
+            Value::Bool(b) => b.hash(state),
+            Value::Number(n) => Value::number_bits(*n).hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Array(a) => a.hash(state),
+            Value::Object(o) => o.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Affiche la valeur en JSON compact ; les nombres non finis deviennent `null`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        json::write_value(&mut out, self);
+        f.write_str(&out)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::Array(values.into_iter().map(Into::into).collect())
+    }
+}
@@ -0,0 +1,350 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::memory;
+use crate::payload::{Payload, Value};
+
+/// Type d'index de charge utile créé par [`Collection::create_payload_index`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    /// Index par valeur exacte, pour les conditions [`Filter::Eq`](crate::Filter::Eq).
+    Keyword,
+    /// Index trié des valeurs numériques, pour les conditions
+    /// [`Filter::Range`](crate::Filter::Range) et [`Filter::Eq`](crate::Filter::Eq) sur
+    /// un nombre.
+    Numeric,
+}
+
+/// Nombre ordonné selon `f64::total_cmp`, `-0.0` étant confondu avec `0.0`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Number(f64);
+
+impl Number {
+    /// Retourne le nombre indexable correspondant à `n`, sauf pour NaN.
+    fn new(n: f64) -> Option<Self> {
+        if n.is_nan() {
+            return None;
+        }
+        Some(Number(if n == 0.0 { 0.0 } else { n }))
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Index des documents selon la valeur d'un champ de leur charge utile.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PayloadIndex {
+    Keyword(HashMap<Value, HashSet<Uuid>>),
+    Numeric(BTreeMap<Number, HashSet<Uuid>>),
+}
+
+impl PayloadIndex {
+    fn new(kind: IndexKind) -> Self {
+        match kind {
+            IndexKind::Keyword => PayloadIndex::Keyword(HashMap::new()),
+            IndexKind::Numeric => PayloadIndex::Numeric(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> IndexKind {
+        match self {
+            PayloadIndex::Keyword(_) => IndexKind::Keyword,
+            PayloadIndex::Numeric(_) => IndexKind::Numeric,
+        }
+    }
+
+    /// Octets d'une entrée de l'index : l'identifiant du document et la valeur indexée.
+    fn entry_bytes(&self, value: &Value) -> usize {
+        mem::size_of::<Uuid>()
+            + match self {
+                PayloadIndex::Keyword(_) => memory::value_bytes(value),
+                PayloadIndex::Numeric(_) => mem::size_of::<Number>(),
+            }
+    }
+
+    /// Octets de toutes les entrées de l'index.
+    /// Rend la place inutilisée de l'index.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        match self {
+            PayloadIndex::Keyword(map) => {
+                map.values_mut().for_each(HashSet::shrink_to_fit);
+                map.shrink_to_fit();
+            }
+            PayloadIndex::Numeric(map) => map.values_mut().for_each(HashSet::shrink_to_fit),
+        }
+    }
+
+    /// Octets réservés par l'index sans être utilisés.
+    pub(crate) fn unused_bytes(&self) -> usize {
+        match self {
+            PayloadIndex::Keyword(map) => {
+                memory::unused_table_bytes(map)
+                    + map.values().map(memory::unused_set_bytes).sum::<usize>()
+            }
+            PayloadIndex::Numeric(map) => map.values().map(memory::unused_set_bytes).sum(),
+        }
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        match self {
+            PayloadIndex::Keyword(map) => map
+                .iter()
+                .map(|(value, keys)| keys.len() * self.entry_bytes(value))
+                .sum(),
+            PayloadIndex::Numeric(map) => {
+                map.values().map(HashSet::len).sum::<usize>()
+                    * (mem::size_of::<Uuid>() + mem::size_of::<Number>())
+            }
+        }
+    }
+
+    /// Indexe le document `key` sous `value`.
+    ///
+    /// # Retourne
+    /// * usize - Octets ajoutés à l'index, 0 si la valeur n'est pas indexable.
+    fn insert(&mut self, key: Uuid, value: &Value) -> usize {
+        let bytes = self.entry_bytes(value);
+        let inserted = match self {
+            PayloadIndex::Keyword(map) => map.entry(value.clone()).or_default().insert(key),
+            PayloadIndex::Numeric(map) => match value.as_f64().and_then(Number::new) {
+                Some(n) => map.entry(n).or_default().insert(key),
+                None => false,
+            },
+        };
+        if inserted {
+            bytes
+        } else {
+            0
+        }
+    }
+
+    /// Retire le document `key` indexé sous `value`.
+    ///
+    /// # Retourne
+    /// * usize - Octets libérés, 0 si le document n'était pas indexé sous cette valeur.
+    fn remove(&mut self, key: &Uuid, value: &Value) -> usize {
+        let bytes = self.entry_bytes(value);
+        let removed = match self {
+            PayloadIndex::Keyword(map) => match map.get_mut(value) {
+                Some(keys) => {
+                    let removed = keys.remove(key);
+                    if keys.is_empty() {
+                        map.remove(value);
+                    }
+                    removed
+                }
+                None => false,
+            },
+            PayloadIndex::Numeric(map) => {
+                let Some(n) = value.as_f64().and_then(Number::new) else {
+                    return 0;
+                };
+                match map.get_mut(&n) {
+                    Some(keys) => {
+                        let removed = keys.remove(key);
+                        if keys.is_empty() {
+                            map.remove(&n);
+                        }
+                        removed
+                    }
+                    None => false,
+                }
+            }
+        };
+        if removed {
+            bytes
+        } else {
+            0
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            PayloadIndex::Keyword(map) => map.clear(),
+            PayloadIndex::Numeric(map) => map.clear(),
+        }
+    }
+
+    /// Retourne les documents dont le champ vaut `value`, ou `None` si l'index ne
+    /// contient pas ce genre de valeur.
+    pub(crate) fn lookup(&self, value: &Value) -> Option<HashSet<Uuid>> {
+        match self {
+            PayloadIndex::Keyword(map) => Some(map.get(value).cloned().unwrap_or_default()),
+            PayloadIndex::Numeric(map) => {
+                let n = value.as_f64().and_then(Number::new)?;
+                Some(map.get(&n).cloned().unwrap_or_default())
+            }
+        }
+    }
+
+    /// Retourne le nombre de documents que retournerait [`PayloadIndex::lookup`], sans
+    /// les copier.
+    pub(crate) fn lookup_count(&self, value: &Value) -> Option<usize> {
+        match self {
+            PayloadIndex::Keyword(map) => Some(map.get(value).map_or(0, HashSet::len)),
+            PayloadIndex::Numeric(map) => {
+                let n = value.as_f64().and_then(Number::new)?;
+                Some(map.get(&n).map_or(0, HashSet::len))
+            }
+        }
+    }
+
+    /// Retourne le nombre de documents que retournerait [`PayloadIndex::range`], sans
+    /// les copier : un document n'a qu'une valeur par champ, et n'est donc compté
+    /// qu'une fois.
+    pub(crate) fn range_count(&self, min: Option<f64>, max: Option<f64>) -> Option<usize> {
+        let PayloadIndex::Numeric(map) = self else {
+            return None;
+        };
+        let (low, high) = match bounds(min, max) {
+            Some(bounds) => bounds,
+            None => return Some(0),
+        };
+        Some(map.range(low..=high).map(|(_, set)| set.len()).sum())
+    }
+
+    /// Retourne les documents dont le champ est un nombre entre `min` et `max` inclus,
+    /// ou `None` pour un index par valeur exacte.
+    pub(crate) fn range(&self, min: Option<f64>, max: Option<f64>) -> Option<HashSet<Uuid>> {
+        let PayloadIndex::Numeric(map) = self else {
+            return None;
+        };
+        let mut keys = HashSet::new();
+        let Some((low, high)) = bounds(min, max) else {
+            return Some(keys);
+        };
+        for set in map.range(low..=high).map(|(_, set)| set) {
+            keys.extend(set);
+        }
+        Some(keys)
+    }
+}
+
+/// Bornes indexables d'une condition d'intervalle, `None` si elle ne peut retenir aucun
+/// nombre : une borne NaN ou un intervalle vide.
+fn bounds(min: Option<f64>, max: Option<f64>) -> Option<(Number, Number)> {
+    if min.is_some_and(f64::is_nan) || max.is_some_and(f64::is_nan) {
+        return None;
+    }
+    let low = min
+        .and_then(Number::new)
+        .map(|n| n.0)
+        .unwrap_or(f64::NEG_INFINITY);
+    let high = max
+        .and_then(Number::new)
+        .map(|n| n.0)
+        .unwrap_or(f64::INFINITY);
+    (low <= high).then_some((Number(low), Number(high)))
+}
+
+impl Collection {
+    /// Crée un index sur le champ `field` des charges utiles, qui accélère
+    /// [`Collection::search_filtered`] lorsque le filtre porte sur ce champ.
+    ///
+    /// L'index est construit à partir des documents existants puis tenu à jour à chaque
+    /// insertion, modification de charge utile et suppression. Un index existant sur le
+    /// même champ est remplacé.
+    ///
+    /// # Arguments
+    /// * `field` - Nom du champ à indexer.
+    /// * `kind` - Type d'index.
+    pub fn create_payload_index(&mut self, field: &str, kind: IndexKind) {
+        let mut index = PayloadIndex::new(kind);
+        for (key, payload) in &self.payloads {
+            if let Some(value) = payload.get(field) {
+                self.memory.indexes += index.insert(*key, value);
+            }
+        }
+        if let Some(previous) = self.indexes.insert(field.to_string(), index) {
+            self.memory.indexes -= previous.bytes();
+        }
+    }
+
+    /// Supprime l'index du champ `field`.
+    ///
+    /// # Retourne
+    /// * bool - `false` si le champ n'était pas indexé.
+    pub fn drop_payload_index(&mut self, field: &str) -> bool {
+        let Some(index) = self.indexes.remove(field) else {
+            return false;
+        };
+        self.memory.indexes -= index.bytes();
+        true
+    }
+
+    /// Retourne les champs indexés avec leur type d'index, triés par nom.
+    pub fn payload_indexes(&self) -> Vec<(&str, IndexKind)> {
+        let mut indexes: Vec<(&str, IndexKind)> = self
+            .indexes
+            .iter()
+            .map(|(field, index)| (field.as_str(), index.kind()))
+            .collect();
+        indexes.sort_unstable_by_key(|(field, _)| *field);
+        indexes
+    }
+
+    /// Remplace la charge utile du document `key` en tenant les index à jour.
+    pub(crate) fn put_payload(&mut self, key: Uuid, payload: Payload) {
+        if let Some(previous) = self.take_payload(&key) {
+            self.release_blobs(key, &previous, Some(&payload));
+        }
+        for (field, index) in &mut self.indexes {
+            if let Some(value) = payload.get(field) {
+                self.memory.indexes += index.insert(key, value);
+            }
+        }
+        self.memory.payloads += memory::payload_bytes(&payload);
+        self.payloads.insert(key, payload);
+    }
+
+    /// Retire la charge utile du document `key` en tenant les index à jour.
+    pub(crate) fn take_payload(&mut self, key: &Uuid) -> Option<Payload> {
+        let payload = self.payloads.remove(key)?;
+        for (field, index) in &mut self.indexes {
+            if let Some(value) = payload.get(field) {
+                self.memory.indexes -= index.remove(key, value);
+            }
+        }
+        self.memory.payloads -= memory::payload_bytes(&payload);
+        Some(payload)
+    }
+
+    /// Vide les index, en conservant les champs indexés.
+    pub(crate) fn clear_payload_indexes(&mut self) {
+        self.indexes.values_mut().for_each(PayloadIndex::clear);
+        self.memory.indexes = 0;
+    }
+
+    /// Indique si l'index du champ `field` correspond aux charges utiles.
+    pub(crate) fn payload_index_is_consistent(&self, field: &str, index: &PayloadIndex) -> bool {
+        let mut rebuilt = PayloadIndex::new(index.kind());
+        for (key, payload) in &self.payloads {
+            if let Some(value) = payload.get(field) {
+                rebuilt.insert(*key, value);
+            }
+        }
+        rebuilt == *index
+    }
+}
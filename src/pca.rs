@@ -0,0 +1,306 @@
+use crate::error::{Error, Result};
+use crate::projection::Projection;
+use crate::rng::Rng;
+
+/// Nombre maximal d'itérations de la méthode de la puissance par composante.
+const MAX_ITERATIONS: usize = 200;
+
+/// Écart entre deux itérations en deçà duquel une composante est considérée stable.
+const TOLERANCE: f64 = 1e-9;
+
+/// Modèle d'analyse en composantes principales ajusté sur une collection.
+///
+/// Obtenu avec [`Collection::fit_pca`](crate::Collection::fit_pca) et appliqué avec
+/// [`Collection::apply_pca`](crate::Collection::apply_pca).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcaModel {
+    mean: Vec<f32>,
+    /// Composantes principales, de norme 1, par variance expliquée décroissante.
+    components: Vec<Vec<f32>>,
+    explained_variance_ratio: Vec<f32>,
+}
+
+impl PcaModel {
+    /// Ajuste un modèle à `target_dim` composantes sur `vectors`.
+    ///
+    /// Les composantes sont obtenues par la méthode de la puissance avec déflation, sans
+    /// former la matrice de covariance : chaque itération coûte O(n × d).
+    ///
+    /// # Arguments
+    /// * `vectors` - Vecteurs d'apprentissage, tous de même dimension.
+    /// * `mean` - Moyenne déjà connue de `vectors`, calculée sinon.
+    /// * `target_dim` - Nombre de composantes à conserver.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `vectors` est vide ou si `target_dim` dépasse la dimension.
+    /// * `Error::DimensionMismatch` - Si les vecteurs n'ont pas tous la même dimension.
+    pub(crate) fn fit(
+        vectors: &[&[f32]],
+        mean: Option<Vec<f64>>,
+        target_dim: usize,
+    ) -> Result<Self> {
+        let dimension = vectors
+            .first()
+            .ok_or_else(|| Error::InvalidConfig("aucun vecteur pour ajuster l'ACP".to_string()))?
+            .len();
+        if target_dim > dimension {
+            return Err(Error::InvalidConfig(format!(
+                "l'ACP ne peut pas produire {} composantes en dimension {}",
+                target_dim, dimension
+            )));
+        }
+        if let Some(v) = vectors.iter().find(|v| v.len() != dimension) {
+            return Err(Error::DimensionMismatch {
+                expected: dimension,
+                got: v.len(),
+            });
+        }
+
+        let n = vectors.len() as f64;
+        let mean = match mean.filter(|mean| mean.len() == dimension) {
+            Some(mean) => mean,
+            None => {
+                let mut mean = vec![0.0_f64; dimension];
+                for v in vectors {
+                    for (m, x) in mean.iter_mut().zip(v.iter()) {
+                        *m += *x as f64;
+                    }
+                }
+                mean.iter_mut().for_each(|m| *m /= n);
+                mean
+            }
+        };
+        let centered: Vec<Vec<f64>> = vectors
+            .iter()
+            .map(|v| v.iter().zip(&mean).map(|(x, m)| *x as f64 - m).collect())
+            .collect();
+        let total_variance: f64 = centered
+            .iter()
+            .map(|v| v.iter().map(|x| x * x).sum::<f64>())
+            .sum::<f64>()
+            / n;
+
+        let mut rng = Rng::new(0);
+        let mut components: Vec<Vec<f64>> = Vec::with_capacity(target_dim);
+        let mut variances = Vec::with_capacity(target_dim);
+        for _ in 0..target_dim {
+            let mut v: Vec<f64> = (0..dimension).map(|_| rng.gaussian()).collect();
+            orthonormalize(&mut v, &components);
+            let mut eigenvalue = 0.0;
+            for _ in 0..MAX_ITERATIONS {
+                let mut next = covariance_times(&centered, &v, n);
+                orthonormalize(&mut next, &components);
+                eigenvalue = dot(&next, &covariance_times(&centered, &next, n));
+                let delta: f64 = next.iter().zip(&v).map(|(a, b)| (a - b).abs()).sum();
+                v = next;
+                if delta < TOLERANCE {
+                    break;
+                }
+            }
+            components.push(v);
+            variances.push(eigenvalue.max(0.0));
+        }
+
+        Ok(PcaModel {
+            mean: mean.iter().map(|m| *m as f32).collect(),
+            components: components
+                .iter()
+                .map(|c| c.iter().map(|x| *x as f32).collect())
+                .collect(),
+            explained_variance_ratio: variances
+                .iter()
+                .map(|v| {
+                    if total_variance > 0.0 {
+                        (v / total_variance) as f32
+                    } else {
+                        0.0
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Dimension des vecteurs d'origine.
+    pub fn input_dim(&self) -> usize {
+        self.mean.len()
+    }
+
+    /// Nombre de composantes conservées.
+    pub fn target_dim(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Moyenne des vecteurs d'apprentissage.
+    pub fn mean(&self) -> &[f32] {
+        &self.mean
+    }
+
+    /// Composantes principales, par variance expliquée décroissante.
+    pub fn components(&self) -> &[Vec<f32>] {
+        &self.components
+    }
+
+    /// Part de la variance totale expliquée par chaque composante.
+    ///
+    /// Leur somme cumulée aide à choisir `target_dim` : la somme des `m` premières
+    /// valeurs est la part de variance conservée avec `m` composantes.
+    pub fn explained_variance_ratio(&self) -> &[f32] {
+        &self.explained_variance_ratio
+    }
+
+    /// Projette un vecteur dans l'espace réduit.
+    ///
+    /// # Erreurs
+    /// * `Error::DimensionMismatch` - Si le vecteur n'a pas la dimension d'origine.
+    pub fn transform(&self, vector: &[f32]) -> Result<Vec<f32>> {
+        self.to_projection().apply(vector)
+    }
+
+    /// Reconstruit un vecteur de l'espace d'origine à partir de ses coordonnées réduites.
+    ///
+    /// # Erreurs
+    /// * `Error::DimensionMismatch` - Si `reduced` n'a pas `target_dim` coordonnées.
+    pub fn inverse_transform(&self, reduced: &[f32]) -> Result<Vec<f32>> {
+        if reduced.len() != self.target_dim() {
+            return Err(Error::DimensionMismatch {
+                expected: self.target_dim(),
+                got: reduced.len(),
+            });
+        }
+        let mut vector = self.mean.clone();
+        for (component, coordinate) in self.components.iter().zip(reduced) {
+            for (x, c) in vector.iter_mut().zip(component) {
+                *x += coordinate * c;
+            }
+        }
+        Ok(vector)
+    }
+
+    /// Convertit le modèle en projection centrée, telle qu'attachée à une collection.
+    pub fn to_projection(&self) -> Projection {
+        let matrix = self.components.iter().flatten().copied().collect();
+        Projection::from_matrix(self.input_dim(), self.target_dim(), matrix)
+            .and_then(|projection| projection.with_center(self.mean.clone()))
+            .expect("dimensions du modèle cohérentes")
+    }
+}
+
+/// Calcule `C × v` où `C` est la covariance des vecteurs centrés.
+fn covariance_times(centered: &[Vec<f64>], v: &[f64], n: f64) -> Vec<f64> {
+    let mut result = vec![0.0; v.len()];
+    for x in centered {
+        let projection = dot(x, v);
+        for (r, xi) in result.iter_mut().zip(x) {
+            *r += projection * xi;
+        }
+    }
+    result.iter_mut().for_each(|r| *r /= n);
+    result
+}
+
+/// Orthogonalise `v` par rapport aux composantes déjà trouvées, puis le normalise.
+fn orthonormalize(v: &mut [f64], components: &[Vec<f64>]) {
+    for component in components {
+        let projection = dot(v, component);
+        for (x, c) in v.iter_mut().zip(component) {
+            *x -= projection * c;
+        }
+    }
+    let norm = dot(v, v).sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::database::BaseDeDonnees;
+    use crate::synthetic::VectorGenerator;
+
+    const DIMENSION: usize = 16;
+    const RANK: usize = 3;
+
+    /// Collection de vecteurs `offset + Σ aᵢ × baseᵢ` sur `RANK` directions, de poids
+    /// décroissants.
+    fn low_rank(count: usize) -> Collection {
+        let mut generator = VectorGenerator::new(DIMENSION, 21);
+        let offset = generator.vector();
+        let basis = generator.vectors(RANK);
+        let mut coordinates = VectorGenerator::new(RANK, 22);
+        let mut collection = Collection::new();
+        for _ in 0..count {
+            let a = coordinates.vector();
+            let mut vector = offset.clone();
+            for (i, direction) in basis.iter().enumerate() {
+                let weight = a[i] * (RANK - i) as f32;
+                vector.iter_mut().zip(direction).for_each(|(x, d)| *x += weight * d);
+            }
+            collection.upsert(generator.uuid(), vector).unwrap();
+        }
+        collection
+    }
+
+    /// Plus grand écart entre les vecteurs de la collection et leur reconstruction.
+    fn reconstruction_error(collection: &Collection, model: &PcaModel) -> f32 {
+        let mut error: f32 = 0.0;
+        for vector in collection.documents.values() {
+            let restored = model.inverse_transform(&model.transform(vector).unwrap()).unwrap();
+            for (x, y) in vector.iter().zip(&restored) {
+                error = error.max((x - y).abs());
+            }
+        }
+        error
+    }
+
+    #[test]
+    fn low_rank_data_is_reconstructed_from_its_rank() {
+        let collection = low_rank(200);
+        let model = collection.fit_pca(RANK).unwrap();
+        assert_eq!((model.input_dim(), model.target_dim()), (DIMENSION, RANK));
+        assert!(reconstruction_error(&collection, &model) < 1e-3);
+        let explained: f32 = model.explained_variance_ratio().iter().sum();
+        assert!((explained - 1.0).abs() < 1e-4, "{}", explained);
+        for component in model.components() {
+            assert!((crate::similarity::norm(component) - 1.0).abs() < 1e-4);
+        }
+
+        // Une composante de moins perd la moins importante des directions.
+        let truncated = collection.fit_pca(RANK - 1).unwrap();
+        assert!(reconstruction_error(&collection, &truncated) > 0.1);
+        let ratios = truncated.explained_variance_ratio();
+        assert!(ratios.windows(2).all(|w| w[0] >= w[1]));
+        assert!(ratios.iter().sum::<f32>() < 0.99);
+    }
+
+    #[test]
+    fn applied_model_transforms_later_upserts_and_queries() {
+        let mut collection = low_rank(100);
+        let model = collection.fit_pca(RANK).unwrap();
+        let query = collection.documents.values().next().unwrap().to_vec();
+        let before = collection.search(&query, 5).unwrap();
+        collection.apply_pca(&model).unwrap();
+        assert!(collection.documents.values().all(|v| v.len() == RANK));
+        assert_eq!(collection.dimension(), Some(DIMENSION));
+        let after = collection.search(&query, 5).unwrap();
+        assert_eq!(after[0].0, before[0].0);
+
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let docs = loaded.get("docs").unwrap();
+        assert_eq!(docs.projection(), Some(&model.to_projection()));
+        assert_eq!(docs.search(&query, 5).unwrap(), after);
+    }
+
+    #[test]
+    fn too_many_components_are_refused() {
+        assert!(matches!(low_rank(10).fit_pca(DIMENSION + 1), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Collection::new().fit_pca(1), Err(Error::InvalidConfig(_))));
+    }
+}
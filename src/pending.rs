@@ -0,0 +1,179 @@
+//! Documents enregistrés avant leur vecteur.
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::memory;
+use crate::oplog::{self, OperationKind};
+use crate::payload::Payload;
+
+impl Collection {
+    /// Enregistre un document sans vecteur, avec sa charge utile, en attendant
+    /// [`Collection::attach_vector`].
+    ///
+    /// Le document compte dans [`Collection::len`] et sa charge utile est lisible par
+    /// [`Collection::payload`], mais aucune recherche ne le retourne et il n'entre ni
+    /// dans [`Collection::searchable_len`], ni dans les index de charge utile, tant
+    /// qu'il n'a pas de vecteur. Enregistrer de nouveau un document en attente remplace
+    /// sa charge utile.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document.
+    /// * `payload` - Charge utile du document.
+    ///
+    /// # Erreurs
+    /// * `Error::DocumentExists` - Si le document a déjà un vecteur.
+    /// * `Error::SchemaViolation` - Si la charge utile ne respecte pas le schéma de la
+    ///   collection ([`Collection::set_payload_schema`]).
+    pub fn register(&mut self, key: Uuid, payload: Payload) -> Result<()> {
+        self.logged(
+            OperationKind::Upsert,
+            |collection| {
+                if collection.documents.contains_key(&key) {
+                    return Err(Error::DocumentExists(key));
+                }
+                collection.write_blobs(|collection| {
+                    let (payload, coerced) = collection.admit_payload(key, payload)?;
+                    collection.put_pending(key, payload);
+                    collection.schema_warnings += coerced;
+                    Ok(())
+                })
+            },
+            oplog::one_if_ok,
+        )
+    }
+
+    /// Joint son vecteur à un document, qui devient visible des recherches.
+    ///
+    /// Le document est le plus souvent en attente ([`Collection::register`]) ; le
+    /// vecteur d'un document complet est remplacé, comme par [`Collection::upsert`].
+    /// La charge utile est conservée.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du document.
+    /// * `vector` - Vecteur du document.
+    ///
+    /// # Retourne
+    /// * Result<bool> - `false` si le vecteur a été écarté comme doublon
+    ///   (`DedupPolicy::Skip`) : le document reste alors en attente.
+    ///
+    /// # Erreurs
+    /// * `Error::DocumentNotFound` - Si le document n'existe pas.
+    /// * Celles de [`Collection::upsert`] ; le document reste alors en attente.
+    pub fn attach_vector(&mut self, key: Uuid, vector: impl Into<Vec<f32>>) -> Result<bool> {
+        if !self.pending.contains_key(&key) && !self.documents.contains_key(&key) {
+            return Err(Error::DocumentNotFound(key));
+        }
+        self.upsert(key, vector)
+    }
+
+    /// Retourne les identifiants des documents qui attendent leur vecteur, par `Uuid`
+    /// croissant.
+    pub fn pending_ids(&self) -> Vec<Uuid> {
+        let mut keys: Vec<Uuid> = self.pending.keys().copied().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Indique si le document `key` attend son vecteur.
+    pub fn is_pending(&self, key: &Uuid) -> bool {
+        self.pending.contains_key(key)
+    }
+
+    /// Remplace la charge utile du document en attente `key`.
+    pub(crate) fn put_pending(&mut self, key: Uuid, payload: Payload) {
+        self.memory.payloads += memory::payload_bytes(&payload);
+        if let Some(previous) = self.pending.insert(key, payload) {
+            self.memory.payloads -= memory::payload_bytes(&previous);
+            self.release_blobs(key, &previous, self.pending.get(&key));
+        }
+    }
+
+    /// Retire le document en attente `key` et retourne sa charge utile.
+    pub(crate) fn take_pending(&mut self, key: &Uuid) -> Option<Payload> {
+        let payload = self.pending.remove(key)?;
+        self.memory.payloads -= memory::payload_bytes(&payload);
+        Some(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::filter::Filter;
+    use crate::payload::Value;
+    use crate::payload_index::IndexKind;
+    use crate::search::SearchParams;
+
+    fn titled(titre: &str) -> Payload {
+        Payload::from([("titre".to_string(), Value::String(titre.to_string()))])
+    }
+
+    #[test]
+    fn registered_documents_become_searchable_once_attached() {
+        let mut collection = Collection::new();
+        collection.create_payload_index("titre", IndexKind::Keyword);
+        let (ready, waiting) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        collection.upsert(ready, [1.0, 0.0]).unwrap();
+        collection.register(waiting, titled("b")).unwrap();
+
+        assert_eq!((collection.len(), collection.searchable_len()), (2, 1));
+        assert_eq!(collection.pending_ids(), [waiting]);
+        assert!(collection.is_pending(&waiting));
+        assert_eq!(collection.payload(&waiting), Some(&titled("b")));
+        let hits = collection.search([0.0, 1.0], 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        let filter = Filter::eq("titre", "b");
+        let filtered = collection.search_filtered([0.0, 1.0], &filter, &SearchParams::new(10));
+        assert!(filtered.unwrap().hits.is_empty());
+
+        // Les documents en attente sont sauvegardés comme tels.
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("docs".to_string(), collection);
+        let mut collection = BaseDeDonnees::from_bytes(&bdd.to_bytes())
+            .unwrap()
+            .get("docs")
+            .unwrap()
+            .clone();
+        assert_eq!(collection.pending_ids(), [waiting]);
+
+        collection.attach_vector(waiting, [0.0, 1.0]).unwrap();
+        assert!(collection.pending_ids().is_empty());
+        assert_eq!((collection.len(), collection.searchable_len()), (2, 2));
+        assert_eq!(collection.search([0.0, 1.0], 1).unwrap()[0].0, waiting);
+        let filtered = collection.search_filtered([0.0, 1.0], &filter, &SearchParams::new(10));
+        assert_eq!(filtered.unwrap().hits.len(), 1);
+        assert_eq!(collection.payload(&waiting), Some(&titled("b")));
+        collection.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn attaching_to_an_unknown_id_or_with_a_wrong_dimension_fails() {
+        let mut collection = Collection::new();
+        collection.upsert(Uuid::new_v4(), [1.0, 0.0]).unwrap();
+        let unknown = Uuid::new_v4();
+        assert!(matches!(
+            collection.attach_vector(unknown, [1.0, 0.0]),
+            Err(Error::DocumentNotFound(key)) if key == unknown
+        ));
+        assert_eq!(collection.len(), 1);
+
+        let waiting = Uuid::new_v4();
+        collection.register(waiting, titled("a")).unwrap();
+        assert!(matches!(
+            collection.attach_vector(waiting, [1.0, 0.0, 0.0]),
+            Err(Error::DimensionLocked { locked: 2, got: 3, .. })
+        ));
+        assert!(collection.is_pending(&waiting));
+        assert_eq!(collection.searchable_len(), 1);
+
+        // Un document complet ne peut pas être enregistré de nouveau.
+        collection.attach_vector(waiting, [0.0, 1.0]).unwrap();
+        assert!(matches!(
+            collection.register(waiting, titled("c")),
+            Err(Error::DocumentExists(key)) if key == waiting
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+//! Reprise après une panique : fonctions de l'appelant exécutées sous `catch_unwind`,
+//! et quarantaine des collections d'une base partagée laissées incohérentes par une
+//! panique sous le verrou en écriture.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, LockResult, Mutex};
+
+use crate::database::{self, BaseDeDonnees};
+use crate::error::{Error, Result};
+
+/// Exécute `f`, fournie par l'appelant, et transforme sa panique éventuelle en erreur.
+///
+/// # Erreurs
+/// * `Error::Panicked` - Si `f` panique ; contient le message de la panique.
+pub(crate) fn catch<T>(f: impl FnOnce() -> T) -> Result<T> {
+    // Les fonctions de l'appelant ne reçoivent que des références partagées : une
+    // panique ne peut pas laisser la collection à moitié modifiée.
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| Error::Panicked(message(&*payload)))
+}
+
+/// Message d'une panique, si elle en porte un.
+fn message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "panique sans message".to_string(),
+    }
+}
+
+/// Collections d'une base partagée mises en quarantaine, avec l'incohérence trouvée ;
+/// partagée par les clones de la poignée.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Quarantine(Arc<Mutex<BTreeMap<String, String>>>);
+
+impl Quarantine {
+    /// Retourne le gardien du verrou de `bdd`, même empoisonné.
+    ///
+    /// Un verrou empoisonné par une panique sous le verrou en écriture est repris : les
+    /// invariants de chaque collection sont vérifiés ([`Collection::check_invariants`]),
+    /// celles qui ne les respectent plus sont mises en quarantaine, et le verrou est
+    /// remis en service pour les appels suivants.
+    ///
+    /// [`Collection::check_invariants`]: crate::Collection::check_invariants
+    pub(crate) fn recover<G: std::ops::Deref<Target = BaseDeDonnees>>(
+        &self,
+        lock: LockResult<G>,
+        clear_poison: impl FnOnce(),
+    ) -> G {
+        lock.unwrap_or_else(|poisoned| {
+            let guard = poisoned.into_inner();
+            self.inspect(&guard);
+            clear_poison();
+            guard
+        })
+    }
+
+    /// Met en quarantaine les collections de `bdd` dont les invariants sont violés.
+    fn inspect(&self, bdd: &BaseDeDonnees) {
+        let broken: Vec<(String, String)> = (bdd.collections.iter())
+            .map(|(nom, collection)| (nom, collection.check_invariants()))
+            .chain((bdd.sharded.iter()).map(|(nom, sharded)| (nom, sharded.check_invariants())))
+            .filter_map(|(nom, checked)| Some((nom.clone(), checked.err()?.to_string())))
+            .collect();
+        self.lock().extend(broken);
+    }
+
+    /// Vérifie que la collection `nom` de `bdd`, ou l'alias `nom`, peut être modifiée.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionPoisoned` - Si la collection est en quarantaine.
+    pub(crate) fn check(&self, bdd: &BaseDeDonnees, nom: &str) -> Result<()> {
+        let nom = database::resolve(&bdd.aliases, nom);
+        match self.lock().get(nom) {
+            Some(reason) => Err(Error::CollectionPoisoned {
+                collection: nom.to_string(),
+                reason: reason.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Retourne les collections en quarantaine et l'incohérence de chacune.
+    pub(crate) fn list(&self) -> BTreeMap<String, String> {
+        self.lock().clone()
+    }
+
+    /// Sort la collection `nom` de quarantaine.
+    pub(crate) fn release(&self, nom: &str) {
+        self.lock().remove(nom);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.0.lock().expect("quarantaine empoisonnée")
+    }
+}
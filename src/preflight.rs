@@ -0,0 +1,499 @@
+//! Vérifications de l'environnement avant le démarrage d'une base : droits sur les
+//! fichiers, place disponible et compatibilité de la sauvegarde avec sa déclaration.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::database::BaseDeDonnees;
+use crate::database_config::DatabaseConfig;
+use crate::snapshot;
+
+/// Gravité d'un [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// La base démarre, mais risque d'échouer plus tard, par exemple faute de place.
+    Warning,
+    /// La base ne peut pas démarrer ou échouera à son premier enregistrement.
+    Fatal,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "avertissement",
+            Severity::Fatal => "fatal",
+        })
+    }
+}
+
+/// Problème trouvé par [`BaseDeDonnees::preflight`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Gravité du problème.
+    pub severity: Severity,
+    /// Description du problème.
+    pub message: String,
+    /// Correction proposée.
+    pub fix: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: String, fix: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            message,
+            fix: fix.into(),
+        }
+    }
+
+    /// Indique si le problème empêche le démarrage.
+    pub fn is_fatal(&self) -> bool {
+        self.severity == Severity::Fatal
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Affiche le diagnostic sur une ligne : `fatal : <problème> ; correction : <fix>`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} : {} ; correction : {}",
+            self.severity, self.message, self.fix
+        )
+    }
+}
+
+/// Environnement vérifié par [`BaseDeDonnees::preflight`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PreflightOptions {
+    /// Sauvegarde chargée au démarrage et réécrite par [`BaseDeDonnees::save`] ; elle
+    /// peut ne pas exister encore.
+    pub snapshot: PathBuf,
+    /// Journal de réplication d'un [`Primary`](crate::Primary)
+    /// ([`Primary::with_log_file`](crate::Primary::with_log_file)), `None` s'il n'y en a
+    /// pas.
+    pub journal: Option<PathBuf>,
+    /// Déclaration appliquée au démarrage ([`BaseDeDonnees::apply_config`]), `None` s'il
+    /// n'y en a pas.
+    pub config: Option<DatabaseConfig>,
+}
+
+impl BaseDeDonnees {
+    /// Vérifie, avant le démarrage, que la base pourra charger et enregistrer sa
+    /// sauvegarde et appliquer sa déclaration, sans rien modifier.
+    ///
+    /// Sont vérifiés : l'existence et les droits d'écriture des répertoires de la
+    /// sauvegarde et du journal, par l'écriture d'un fichier témoin aussitôt supprimé,
+    /// ce qui détecte aussi un montage en lecture seule ; la lecture de la sauvegarde et
+    /// la fonctionnalité `encryption` si elle est chiffrée ; la place libre, au moins la
+    /// taille de la sauvegarde actuelle puisque l'enregistrement en écrit une copie
+    /// complète avant de remplacer l'ancienne ; et la compatibilité de la déclaration
+    /// avec les collections de la sauvegarde, comme [`BaseDeDonnees::apply_config`].
+    ///
+    /// # Arguments
+    /// * `options` - Fichiers et déclaration de la base.
+    ///
+    /// # Retourne
+    /// * Vec<Diagnostic> - Les problèmes trouvés, les plus graves en premier ; vide si
+    ///   l'environnement est prêt.
+    pub fn preflight(options: &PreflightOptions) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        check_directory(&options.snapshot, "de la sauvegarde", &mut diagnostics);
+        if let Some(journal) = &options.journal {
+            check_directory(journal, "du journal", &mut diagnostics);
+            check_journal(journal, &mut diagnostics);
+        }
+        if let Some(bdd) = check_snapshot(&options.snapshot, &mut diagnostics) {
+            let declared = options.config.iter().flat_map(|config| &config.collections);
+            for (nom, declaration) in declared {
+                if let Err(error) = bdd.check_declaration(nom, declaration) {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Fatal,
+                        format!("la déclaration contredit la sauvegarde : {}", error),
+                        format!(
+                            "alignez la déclaration de '{}' sur la collection existante, ou \
+                             recréez la collection",
+                            nom
+                        ),
+                    ));
+                }
+            }
+        }
+        diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity));
+        diagnostics
+    }
+}
+
+/// Répertoire d'un fichier, `.` pour un nom sans répertoire.
+fn directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Vérifie que le répertoire de `path` existe et accepte l'écriture d'un fichier.
+fn check_directory(path: &Path, role: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let dir = directory(path);
+    if !dir.is_dir() {
+        diagnostics.push(Diagnostic::new(
+            Severity::Fatal,
+            format!("le répertoire {} {} n'existe pas", role, dir.display()),
+            format!("créez-le : mkdir -p {}", dir.display()),
+        ));
+        return;
+    }
+    let mut probe = path.as_os_str().to_owned();
+    probe.push(".preflight");
+    let probe = PathBuf::from(probe);
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|_| fs::remove_file(&probe));
+    match written {
+        Ok(()) => {}
+        // Un témoin oublié par une vérification interrompue prouve que l'écriture a réussi.
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(error) => diagnostics.push(Diagnostic::new(
+            Severity::Fatal,
+            format!(
+                "le répertoire {} {} n'accepte pas l'écriture : {}",
+                role,
+                dir.display(),
+                error
+            ),
+            "donnez le droit d'écriture à l'utilisateur du processus, ou choisissez un \
+             répertoire sur un montage en lecture-écriture",
+        )),
+    }
+}
+
+/// Vérifie qu'un journal existant peut être complété.
+fn check_journal(journal: &Path, diagnostics: &mut Vec<Diagnostic>) {
+    if !journal.exists() {
+        return;
+    }
+    if let Err(error) = OpenOptions::new().append(true).open(journal) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Fatal,
+            format!(
+                "le journal {} ne peut pas être complété : {}",
+                journal.display(),
+                error
+            ),
+            "donnez le droit d'écriture sur le journal à l'utilisateur du processus",
+        ));
+    }
+}
+
+/// Vérifie que la sauvegarde existante se lit et qu'il reste de quoi la réécrire.
+///
+/// # Retourne
+/// * Option<BaseDeDonnees> - La base lue, ou `None` si la sauvegarde n'existe pas ou
+///   n'a pas pu être lue.
+fn check_snapshot(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Option<BaseDeDonnees> {
+    if !path.exists() {
+        return None;
+    }
+    let mut magic = [0; 8];
+    let read = File::open(path).and_then(|mut file| {
+        let size = file.metadata()?.len();
+        file.read_exact(&mut magic).or_else(|error| match error.kind() {
+            // Un fichier plus court que la signature est signalé par le chargement.
+            io::ErrorKind::UnexpectedEof => Ok(()),
+            _ => Err(error),
+        })?;
+        Ok(size)
+    });
+    let size = match read {
+        Ok(size) => size,
+        Err(error) => {
+            diagnostics.push(Diagnostic::new(
+                Severity::Fatal,
+                format!("la sauvegarde {} est illisible : {}", path.display(), error),
+                "donnez le droit de lecture sur la sauvegarde à l'utilisateur du processus",
+            ));
+            return None;
+        }
+    };
+    check_space(path, size, diagnostics);
+    if &magic == snapshot::ENCRYPTED_MAGIC {
+        diagnostics.push(encrypted(path));
+        return None;
+    }
+    match BaseDeDonnees::load(path) {
+        Ok(bdd) => Some(bdd),
+        Err(error) => {
+            diagnostics.push(Diagnostic::new(
+                Severity::Fatal,
+                format!("la sauvegarde {} ne se charge pas : {}", path.display(), error),
+                format!(
+                    "vérifiez-la avec `embeddingProject check {0}`, puis réparez-la avec \
+                     `embeddingProject repair {0}`",
+                    path.display()
+                ),
+            ));
+            None
+        }
+    }
+}
+
+/// Diagnostic d'une sauvegarde chiffrée, qui ne se lit pas sans sa clé.
+#[cfg(feature = "encryption")]
+fn encrypted(path: &Path) -> Diagnostic {
+    Diagnostic::new(
+        Severity::Warning,
+        format!(
+            "la sauvegarde {} est chiffrée : son contenu et sa déclaration ne sont pas vérifiés",
+            path.display()
+        ),
+        "chargez-la avec BaseDeDonnees::load_encrypted et sa clé pour la vérifier",
+    )
+}
+
+/// Diagnostic d'une sauvegarde chiffrée, qui ne se lit pas sans sa clé.
+#[cfg(not(feature = "encryption"))]
+fn encrypted(path: &Path) -> Diagnostic {
+    Diagnostic::new(
+        Severity::Fatal,
+        format!(
+            "la sauvegarde {} est chiffrée, mais la fonctionnalité `encryption` n'est pas \
+             compilée",
+            path.display()
+        ),
+        "recompilez avec `--features encryption`",
+    )
+}
+
+/// Vérifie que le système de fichiers de `path` peut recevoir une copie de `size`
+/// octets, et de quoi grandir : moins du double est signalé.
+fn check_space(path: &Path, size: u64, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(available) = available_space(directory(path)) else {
+        return;
+    };
+    let severity = match available {
+        available if available < size => Severity::Fatal,
+        available if available < size.saturating_mul(2) => Severity::Warning,
+        _ => return,
+    };
+    diagnostics.push(Diagnostic::new(
+        severity,
+        format!(
+            "{} octet(s) libre(s) près de la sauvegarde, qui en occupe {} et en écrit une \
+             copie complète à chaque enregistrement",
+            available, size
+        ),
+        "libérez de la place ou déplacez la sauvegarde sur un autre volume",
+    ));
+}
+
+/// Place disponible pour l'utilisateur du processus sur le système de fichiers de `dir`.
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `dir` est une chaîne C valide et `stat` une zone de la taille attendue,
+    // lue seulement si l'appel réussit.
+    let stat = unsafe {
+        if libc::statvfs(dir.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+/// Place disponible, inconnue hors des systèmes Unix.
+#[cfg(not(unix))]
+fn available_space(_: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    use crate::collection::Collection;
+    use crate::config::CollectionConfig;
+    use crate::database_config::CollectionDeclaration;
+
+    /// Répertoire temporaire supprimé à la fin du test.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("embeddingproject-preflight-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn options(snapshot: PathBuf) -> PreflightOptions {
+        PreflightOptions {
+            snapshot,
+            ..PreflightOptions::default()
+        }
+    }
+
+    /// Sauvegarde une collection `docs` de dimension 2 à `path`.
+    fn save(path: &Path) {
+        let config = CollectionConfig::builder().dimension(2).build().unwrap();
+        let mut collection = Collection::from_config(config);
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        db.save(path).unwrap();
+    }
+
+    fn declaration(dimension: usize) -> DatabaseConfig {
+        let config = CollectionConfig::builder().dimension(dimension).build().unwrap();
+        DatabaseConfig::new().collection(
+            "docs",
+            CollectionDeclaration {
+                config,
+                ..CollectionDeclaration::default()
+            },
+        )
+    }
+
+    #[test]
+    fn ready_environments_produce_no_diagnostic() {
+        let dir = TempDir::new();
+        let path = dir.path("base.snap");
+        assert_eq!(BaseDeDonnees::preflight(&options(path.clone())), []);
+        save(&path);
+        let ready = PreflightOptions {
+            journal: Some(dir.path("journal.log")),
+            config: Some(declaration(2)),
+            ..options(path.clone())
+        };
+        assert_eq!(BaseDeDonnees::preflight(&ready), []);
+        // Les vérifications ne laissent aucun fichier.
+        let mut files: Vec<_> = fs::read_dir(&dir.0).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        files.sort();
+        assert_eq!(files, ["base.snap"]);
+    }
+
+    #[test]
+    fn missing_and_read_only_directories_are_fatal() {
+        let dir = TempDir::new();
+        let missing = BaseDeDonnees::preflight(&options(dir.path("absent/base.snap")));
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].is_fatal());
+        assert!(missing[0].fix.starts_with("créez-le : mkdir -p "), "{}", missing[0]);
+
+        // Un répertoire du journal qui est un fichier.
+        fs::write(dir.path("fichier"), b"").unwrap();
+        let journal = PreflightOptions {
+            journal: Some(dir.path("fichier/journal.log")),
+            ..options(dir.path("base.snap"))
+        };
+        let diagnostics = BaseDeDonnees::preflight(&journal);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("du journal"));
+
+        // Un journal qui ne peut pas être complété.
+        fs::create_dir(dir.path("journal.log")).unwrap();
+        let journal = PreflightOptions {
+            journal: Some(dir.path("journal.log")),
+            ..options(dir.path("base.snap"))
+        };
+        let diagnostics = BaseDeDonnees::preflight(&journal);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_fatal());
+        assert!(diagnostics[0].message.contains("ne peut pas être complété"));
+    }
+
+    // procfs refuse la création de fichiers, même au superutilisateur : c'est un montage
+    // en lecture seule pour la vérification.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unwritable_mounts_are_fatal() {
+        let diagnostics = BaseDeDonnees::preflight(&options(PathBuf::from("/proc/base.snap")));
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_fatal());
+        assert!(diagnostics[0].message.contains("n'accepte pas l'écriture"), "{}", diagnostics[0]);
+        assert!(diagnostics[0].fix.contains("lecture-écriture"));
+    }
+
+    #[test]
+    fn broken_snapshots_and_contradicting_declarations_are_fatal() {
+        let dir = TempDir::new();
+        let path = dir.path("base.snap");
+        fs::write(&path, b"pas une sauvegarde").unwrap();
+        let diagnostics = BaseDeDonnees::preflight(&options(path.clone()));
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_fatal());
+        assert!(diagnostics[0].fix.contains("embeddingProject repair"));
+        fs::write(&path, b"EMB").unwrap();
+        assert!(BaseDeDonnees::preflight(&options(path.clone()))[0].is_fatal());
+
+        save(&path);
+        let contradicting = PreflightOptions {
+            config: Some(declaration(3)),
+            ..options(path.clone())
+        };
+        let diagnostics = BaseDeDonnees::preflight(&contradicting);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_fatal());
+        assert!(diagnostics[0].message.contains("dimension"), "{}", diagnostics[0]);
+        assert!(diagnostics[0].fix.contains("'docs'"));
+        let text = diagnostics[0].to_string();
+        assert!(text.starts_with("fatal : la déclaration contredit la sauvegarde"));
+        assert!(text.contains(" ; correction : "));
+    }
+
+    #[test]
+    fn encrypted_snapshots_depend_on_the_feature() {
+        let dir = TempDir::new();
+        let path = dir.path("base.snap");
+        let mut bytes = snapshot::ENCRYPTED_MAGIC.to_vec();
+        bytes.extend([0; 64]);
+        fs::write(&path, bytes).unwrap();
+        let diagnostics = BaseDeDonnees::preflight(&PreflightOptions {
+            config: Some(declaration(3)),
+            ..options(path)
+        });
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("chiffrée"));
+        let expected = match cfg!(feature = "encryption") {
+            true => Severity::Warning,
+            false => Severity::Fatal,
+        };
+        assert_eq!(diagnostics[0].severity, expected);
+    }
+
+    #[test]
+    fn fatal_diagnostics_come_first() {
+        let dir = TempDir::new();
+        let path = dir.path("base.snap");
+        let mut bytes = snapshot::ENCRYPTED_MAGIC.to_vec();
+        bytes.extend([0; 64]);
+        fs::write(&path, bytes).unwrap();
+        fs::create_dir(dir.path("journal.log")).unwrap();
+        let diagnostics = BaseDeDonnees::preflight(&PreflightOptions {
+            journal: Some(dir.path("journal.log")),
+            ..options(path)
+        });
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].is_fatal());
+        assert!(diagnostics.windows(2).all(|pair| pair[0].severity >= pair[1].severity));
+    }
+}
@@ -0,0 +1,34 @@
+//! Types et traits d'usage courant, à importer d'un coup :
+//!
+//! ```
+//! use embeddingproject::prelude::*;
+//!
+//! # fn main() -> Result<(), Error> {
+//! let mut bdd = BaseDeDonnees::new();
+//! let articles = bdd.get_or_create("articles")?;
+//! let key = Uuid::new_v4();
+//! let mut payload = Payload::new();
+//! payload.insert("titre".to_string(), Value::String("Premier".to_string()));
+//! articles.upsert_with_payload(key, [1.0, 0.0, 0.5], payload)?;
+//! articles.upsert(Uuid::new_v4(), [0.0, 1.0, 0.5])?;
+//!
+//! let params = SearchParams {
+//!     score_mode: ScoreMode::Normalized,
+//!     ..SearchParams::new(1)
+//! };
+//! let results: SearchResults = bdd.search_with("articles", [1.0, 0.0, 0.4], &params)?;
+//! assert_eq!(results.hits[0].0, key);
+//! assert_eq!(bdd["articles"].metric(), Metric::Cosine);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Le prélude contient aussi [`Uuid`](crate::Uuid), pour construire des clés sans
+//! dépendre directement de la crate `uuid`. L'alias [`Result`](crate::Result) n'en fait
+//! pas partie pour ne pas masquer celui de la bibliothèque standard.
+
+pub use crate::{
+    BaseDeDonnees, BaseDeDonneesPartagee, Collection, CollectionConfig, Document, DocumentId,
+    Error, Metric, Payload, ScoreMode, SearchParams, SearchResults, ShardedCollection, Uuid, Value,
+    VectorStore, VectorStoreMut, ZeroVectorPolicy,
+};
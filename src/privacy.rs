@@ -0,0 +1,196 @@
+//! Bruit de Laplace ajouté aux statistiques agrégées d'une collection, pour qu'elles ne
+//! révèlent pas la présence d'un document particulier.
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::rng::Rng;
+
+/// Budget de confidentialité validé des agrégats d'une configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AggregateNoise {
+    pub(crate) epsilon: f64,
+}
+
+// Epsilon est fini : l'égalité des `f64` est alors une relation d'équivalence.
+impl Eq for AggregateNoise {}
+
+/// Vérifie qu'un budget de confidentialité est utilisable.
+pub(crate) fn check(epsilon: f64) -> Result<()> {
+    if !(epsilon.is_finite() && epsilon > 0.0) {
+        return Err(Error::InvalidConfig(format!(
+            "epsilon vaut {} ; il doit être fini et strictement positif",
+            epsilon
+        )));
+    }
+    Ok(())
+}
+
+/// Tirages de bruit de Laplace d'échelle `1 / epsilon`, pour des comptes dont un
+/// document ne change qu'une unité.
+pub(crate) struct Laplace {
+    rng: Rng,
+    scale: f64,
+}
+
+impl Laplace {
+    /// Crée un générateur dont la graine est tirée du système : elle n'est ni conservée
+    /// ni retournée, et deux appels ne bruitent pas de la même façon.
+    fn new(epsilon: f64) -> Self {
+        let (high, low) = Uuid::new_v4().as_u64_pair();
+        Laplace {
+            rng: Rng::new(high ^ low.rotate_left(32)),
+            scale: 1.0 / epsilon,
+        }
+    }
+
+    /// Retourne `exact` bruité, arrondi à l'entier le plus proche et jamais négatif.
+    pub(crate) fn count(&mut self, exact: u64) -> u64 {
+        let u = self.rng.next_f64() - 0.5;
+        let noise = -self.scale * u.signum() * (1.0 - 2.0 * u.abs()).ln();
+        (exact as f64 + noise).round().max(0.0) as u64
+    }
+}
+
+impl Collection {
+    /// Retourne le budget de confidentialité des statistiques agrégées, s'il est fixé
+    /// (voir [`Collection::set_aggregate_noise`]).
+    pub fn aggregate_noise(&self) -> Option<f64> {
+        self.config.aggregate_noise()
+    }
+
+    /// Bruite les statistiques agrégées de la collection, ou les rend exactes avec
+    /// `None`.
+    ///
+    /// Avec un budget `epsilon`, [`Collection::facet`], [`Collection::score_distribution`]
+    /// et [`Collection::count_by_filter`] ajoutent à chacun de leurs comptes un bruit de
+    /// Laplace d'échelle `1 / epsilon`, puis l'arrondissent sans le laisser devenir
+    /// négatif : chaque appel est ε-différentiellement confidentiel pour l'ajout ou le
+    /// retrait d'un document. Le bruit est tiré après le calcul exact, d'une graine
+    /// jamais exposée ; des appels répétés sur les mêmes données consomment chacun le
+    /// budget, leur moyenne se rapprochant du compte exact.
+    ///
+    /// Les variantes [`Collection::facet_exact`],
+    /// [`Collection::score_distribution_exact`] et [`Collection::count_by_filter_exact`]
+    /// restent exactes : elles sont réservées aux appelants privilégiés, auxquels une
+    /// couche d'accès doit seule les exposer.
+    ///
+    /// # Arguments
+    /// * `epsilon` - Budget de confidentialité, fini et strictement positif ; plus il est
+    ///   petit, plus le bruit est fort.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `epsilon` n'est pas fini et strictement positif ; le
+    ///   réglage précédent est alors conservé.
+    pub fn set_aggregate_noise(&mut self, epsilon: Option<f64>) -> Result<()> {
+        if let Some(epsilon) = epsilon {
+            check(epsilon)?;
+        }
+        self.config.aggregate_noise = epsilon.map(|epsilon| AggregateNoise { epsilon });
+        Ok(())
+    }
+
+    /// Retourne un générateur de bruit si les agrégats de la collection sont bruités.
+    pub(crate) fn laplace(&self) -> Option<Laplace> {
+        self.aggregate_noise().map(Laplace::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::payload::{Payload, Value};
+
+    /// Moyenne et écart absolu moyen de `draws` comptes bruités de `exact`.
+    fn moments(epsilon: f64, exact: u64, draws: usize) -> (f64, f64) {
+        let mut laplace = Laplace::new(epsilon);
+        let counts: Vec<f64> = (0..draws).map(|_| laplace.count(exact) as f64).collect();
+        let mean = counts.iter().sum::<f64>() / draws as f64;
+        let deviation = counts.iter().map(|c| (c - exact as f64).abs()).sum::<f64>();
+        (mean, deviation / draws as f64)
+    }
+
+    #[test]
+    fn noise_is_centred_with_the_laplace_scale() {
+        for epsilon in [0.5, 1.0, 2.0] {
+            let (mean, deviation) = moments(epsilon, 100, 40_000);
+            assert!((mean - 100.0).abs() < 0.1, "{} : {}", epsilon, mean);
+            // L'écart absolu moyen d'une loi de Laplace vaut son échelle ; l'arrondi
+            // l'écarte d'au plus 0.25.
+            assert!((deviation - 1.0 / epsilon).abs() < 0.3, "{} : {}", epsilon, deviation);
+        }
+        // Deux générateurs ne tirent pas le même bruit.
+        let (mut a, mut b) = (Laplace::new(0.1), Laplace::new(0.1));
+        let draws = |l: &mut Laplace| (0..20).map(|_| l.count(1000)).collect::<Vec<_>>();
+        assert_ne!(draws(&mut a), draws(&mut b));
+    }
+
+    #[test]
+    fn noisy_counts_never_go_negative() {
+        let mut laplace = Laplace::new(0.2);
+        let counts: Vec<u64> = (0..10_000).map(|_| laplace.count(0)).collect();
+        // Un bruit négatif est ramené à zéro : la moyenne reste positive.
+        assert!(counts.contains(&0) && counts.iter().any(|&c| c > 0));
+        assert!(counts.iter().sum::<u64>() > 0);
+    }
+
+    /// Collection de 200 documents dont la moitié porte `groupe = "a"`.
+    fn collection() -> Collection {
+        let mut collection = Collection::new();
+        for i in 0..200u128 {
+            let mut payload = Payload::new();
+            let group = if i % 2 == 0 { "a" } else { "b" };
+            payload.insert("groupe".to_string(), Value::String(group.to_string()));
+            let vector = [1.0, i as f32 / 200.0];
+            (collection)
+                .upsert_with_payload(Uuid::from_u128(i), vector, payload)
+                .unwrap();
+        }
+        collection
+    }
+
+    #[test]
+    fn exact_variants_bypass_the_noise() {
+        let mut collection = collection();
+        let filter = Filter::eq("groupe", "a");
+        let histogram = collection.score_distribution(&[1.0, 0.5], 4).unwrap();
+        assert_eq!(histogram.count, 200);
+        collection.set_aggregate_noise(Some(0.5)).unwrap();
+
+        let noisy: Vec<usize> = (0..200).map(|_| collection.count_by_filter(&filter)).collect();
+        assert!(noisy.iter().any(|&count| count != 100));
+        let mean = noisy.iter().sum::<usize>() as f64 / noisy.len() as f64;
+        assert!((mean - 100.0).abs() < 1.0, "{}", mean);
+        for _ in 0..20 {
+            assert_eq!(collection.count_by_filter_exact(&filter), 100);
+            assert_eq!(
+                collection.facet_exact("groupe", 5),
+                [(Value::from("a"), 100), (Value::from("b"), 100)]
+            );
+            assert_eq!(
+                collection.score_distribution_exact(&[1.0, 0.5], 4).unwrap(),
+                histogram
+            );
+        }
+        let noisy = collection.score_distribution(&[1.0, 0.5], 4).unwrap();
+        assert_eq!(noisy.count, noisy.counts.iter().sum::<u64>());
+        assert_eq!((noisy.min, noisy.max, noisy.mean), (None, None, None));
+    }
+
+    #[test]
+    fn invalid_epsilon_keeps_the_previous_setting() {
+        let mut collection = collection();
+        collection.set_aggregate_noise(Some(1.0)).unwrap();
+        for epsilon in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            assert!(matches!(
+                collection.set_aggregate_noise(Some(epsilon)),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        assert_eq!(collection.aggregate_noise(), Some(1.0));
+        collection.set_aggregate_noise(None).unwrap();
+        assert_eq!(collection.count_by_filter(&Filter::eq("groupe", "b")), 100);
+    }
+}
@@ -0,0 +1,288 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::poison;
+
+/// Nombre d'éléments traités entre deux notifications de progression.
+const PROGRESS_INTERVAL: u64 = 1024;
+
+/// Reçoit la progression des opérations de masse et peut demander leur annulation.
+///
+/// L'annulation est vérifiée entre deux éléments : un élément est soit entièrement
+/// appliqué, soit pas du tout, et l'opération s'arrête donc toujours dans un état cohérent.
+/// Une panique du puits est rattrapée : elle interrompt l'opération comme une annulation,
+/// et le puits n'est plus appelé ensuite.
+pub trait ProgressSink {
+    /// Appelé régulièrement pendant l'opération, puis une dernière fois à la fin.
+    ///
+    /// # Arguments
+    /// * `done` - Nombre d'éléments déjà appliqués.
+    /// * `total` - Nombre total d'éléments, s'il est connu à l'avance.
+    fn on_progress(&self, done: u64, total: Option<u64>);
+
+    /// Indique si l'opération doit s'arrêter au plus tôt.
+    fn should_cancel(&self) -> bool {
+        false
+    }
+}
+
+/// Bilan d'une opération de masse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchReport {
+    /// Nombre d'éléments effectivement appliqués.
+    pub applied: u64,
+    /// Vrai si l'opération a été interrompue par [`ProgressSink::should_cancel`].
+    pub cancelled: bool,
+}
+
+/// Message envoyé par [`ChannelProgress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Nombre d'éléments déjà traités, y compris ceux ignorés.
+    pub done: u64,
+    /// Nombre total d'éléments, s'il est connu à l'avance.
+    pub total: Option<u64>,
+}
+
+/// Implémentation de [`ProgressSink`] qui transmet la progression sur un canal.
+///
+/// L'annulation se demande depuis n'importe quel thread avec [`ChannelProgress::cancel`]
+/// ou via le drapeau retourné par [`ChannelProgress::cancel_flag`].
+#[derive(Clone)]
+pub struct ChannelProgress {
+    sender: Sender<Progress>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl ChannelProgress {
+    /// Crée un `ChannelProgress` et le récepteur associé.
+    ///
+    /// # Retourne
+    /// * (ChannelProgress, Receiver<Progress>) - Le puits de progression et l'extrémité de lecture du canal.
+    pub fn new() -> (Self, Receiver<Progress>) {
+        let (sender, receiver) = mpsc::channel();
+        let sink = ChannelProgress {
+            sender,
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        (sink, receiver)
+    }
+
+    /// Demande l'annulation de l'opération en cours.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Retourne le drapeau d'annulation partagé, pour annuler depuis un autre thread.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancel)
+    }
+}
+
+impl ProgressSink for ChannelProgress {
+    fn on_progress(&self, done: u64, total: Option<u64>) {
+        // Un récepteur abandonné ne doit pas faire échouer l'opération.
+        let _ = self.sender.send(Progress { done, total });
+    }
+
+    fn should_cancel(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+/// Suivi interne partagé par toutes les opérations de masse.
+pub(crate) struct Tracker<'a> {
+    sink: Option<&'a dyn ProgressSink>,
+    total: Option<u64>,
+    done: u64,
+    /// Éléments traités mais ignorés, exclus de `applied`.
+    skipped: u64,
+    /// Vrai dès que le puits a paniqué.
+    panicked: Cell<bool>,
+}
+
+impl<'a> Tracker<'a> {
+    pub(crate) fn new(sink: Option<&'a dyn ProgressSink>, total: Option<u64>) -> Self {
+        Tracker {
+            sink,
+            total,
+            done: 0,
+            skipped: 0,
+            panicked: Cell::new(false),
+        }
+    }
+
+    /// Total connu à l'avance d'un itérateur dont la taille est exacte.
+    pub(crate) fn exact_len<I: Iterator>(iter: &I) -> Option<u64> {
+        match iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower as u64),
+            _ => None,
+        }
+    }
+
+    /// Indique si l'opération doit s'arrêter avant le prochain élément.
+    pub(crate) fn cancelled(&self) -> bool {
+        self.call(|sink| sink.should_cancel()).unwrap_or(false) || self.panicked.get()
+    }
+
+    /// Comptabilise un élément appliqué.
+    pub(crate) fn step(&mut self) {
+        self.done += 1;
+        if self.done.is_multiple_of(PROGRESS_INTERVAL) {
+            self.call(|sink| sink.on_progress(self.done, self.total));
+        }
+    }
+
+    /// Comptabilise un élément traité, appliqué ou ignoré (doublon écarté par exemple) :
+    /// un élément ignoré avance la progression sans compter parmi les éléments appliqués.
+    pub(crate) fn record(&mut self, applied: bool) {
+        self.skipped += u64::from(!applied);
+        self.step();
+    }
+
+    /// Envoie la notification finale et produit le bilan.
+    pub(crate) fn finish(self, cancelled: bool) -> BatchReport {
+        self.call(|sink| sink.on_progress(self.done, self.total));
+        BatchReport {
+            applied: self.done - self.skipped,
+            cancelled: cancelled || self.panicked.get(),
+        }
+    }
+
+    /// Appelle le puits, sauf s'il a déjà paniqué, en rattrapant sa panique.
+    fn call<T>(&self, f: impl FnOnce(&dyn ProgressSink) -> T) -> Option<T> {
+        let sink = self.sink.filter(|_| !self.panicked.get())?;
+        let result = poison::catch(|| f(sink)).ok();
+        self.panicked.set(result.is_none());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::collection::Collection;
+
+    /// Puits qui panique à la première notification.
+    struct Panicking;
+
+    impl ProgressSink for Panicking {
+        fn on_progress(&self, _: u64, _: Option<u64>) {
+            panic!("puits de progression défaillant");
+        }
+    }
+
+    #[test]
+    fn panicking_sink_cancels_the_batch() {
+        let mut collection = Collection::new();
+        let items: Vec<_> = (0..3 * PROGRESS_INTERVAL)
+            .map(|i| (Uuid::new_v4(), vec![1.0, i as f32]))
+            .collect();
+        let report = collection.upsert_batch(items, Some(&Panicking)).unwrap();
+        assert!(report.cancelled);
+        assert_eq!(report.applied, PROGRESS_INTERVAL);
+        assert_eq!(collection.len() as u64, PROGRESS_INTERVAL);
+        assert!(collection.check_invariants().is_ok());
+    }
+
+    /// Puits qui demande l'annulation dès qu'au moins `limit` éléments sont appliqués.
+    struct CancelAfter {
+        limit: u64,
+        done: Cell<u64>,
+    }
+
+    impl ProgressSink for CancelAfter {
+        fn on_progress(&self, done: u64, _: Option<u64>) {
+            self.done.set(done);
+        }
+
+        fn should_cancel(&self) -> bool {
+            self.done.get() >= self.limit
+        }
+    }
+
+    #[test]
+    fn cancelling_mid_import_stops_early_with_the_applied_prefix() {
+        let keys: Vec<Uuid> = (0..10 * PROGRESS_INTERVAL).map(|_| Uuid::new_v4()).collect();
+        let jsonl: String = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| format!("{{\"id\": \"{}\", \"vector\": [1.0, {}]}}\n", key, i))
+            .collect();
+        let sink = CancelAfter {
+            limit: 2 * PROGRESS_INTERVAL,
+            done: Cell::new(0),
+        };
+        let mut reader = Cursor::new(jsonl.as_bytes());
+        let mut collection = Collection::new();
+        let report = collection.import_jsonl(&mut reader, Some(&sink)).unwrap();
+
+        assert_eq!(
+            report,
+            BatchReport {
+                applied: 2 * PROGRESS_INTERVAL,
+                cancelled: true,
+            }
+        );
+        assert!((reader.position() as usize) < jsonl.len() / 2);
+        let (applied, skipped) = keys.split_at(report.applied as usize);
+        assert!(applied.iter().all(|key| collection.documents.contains_key(key)));
+        assert!(skipped.iter().all(|key| !collection.documents.contains_key(key)));
+        assert_eq!(collection.len(), applied.len());
+        assert!(collection.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn cancel_from_another_thread_interrupts_a_running_batch() {
+        let (sink, receiver) = ChannelProgress::new();
+        let (remote, flag) = (sink.clone(), sink.cancel_flag());
+        let canceller = thread::spawn(move || {
+            let first = receiver.recv().unwrap();
+            remote.cancel();
+            first.done
+        });
+        // Le document qui suit la première notification attend l'annulation, pour que
+        // le lot s'interrompe toujours au même endroit.
+        let items = (0..10 * PROGRESS_INTERVAL).map(|i| {
+            if i == PROGRESS_INTERVAL {
+                let started = Instant::now();
+                while !flag.load(Ordering::Relaxed) && started.elapsed() < Duration::from_secs(10) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+            (Uuid::new_v4(), vec![1.0, i as f32])
+        });
+        let mut collection = Collection::new();
+        let report = collection.upsert_batch(items, Some(&sink)).unwrap();
+        assert_eq!(canceller.join().unwrap(), PROGRESS_INTERVAL);
+        assert!(report.cancelled);
+        assert_eq!(report.applied, PROGRESS_INTERVAL);
+        assert_eq!(collection.len() as u64, PROGRESS_INTERVAL);
+    }
+
+    #[test]
+    fn channel_reports_progress_and_cancels() {
+        let (sink, receiver) = ChannelProgress::new();
+        let mut collection = Collection::new();
+        let items: Vec<_> = (0..2 * PROGRESS_INTERVAL + 5)
+            .map(|i| (Uuid::new_v4(), vec![1.0, i as f32]))
+            .collect();
+        let report = collection.upsert_batch(items.clone(), Some(&sink)).unwrap();
+        assert!(!report.cancelled);
+        let done: Vec<u64> = receiver.try_iter().map(|progress| progress.done).collect();
+        assert_eq!(done, vec![PROGRESS_INTERVAL, 2 * PROGRESS_INTERVAL, 2 * PROGRESS_INTERVAL + 5]);
+
+        sink.cancel();
+        let report = Collection::new().upsert_batch(items, Some(&sink)).unwrap();
+        assert!(report.cancelled);
+        assert_eq!(report.applied, 0);
+    }
+}
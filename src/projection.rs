@@ -0,0 +1,218 @@
+use crate::error::{Error, Result};
+use crate::rng::Rng;
+
+/// Projection linéaire fixe réduisant la dimension des vecteurs.
+///
+/// Un centre optionnel est soustrait avant la projection, ce qui permet de représenter
+/// une ACP ([`PcaModel`](crate::PcaModel)). Attachée à une collection avec [`Collection::set_projection`](crate::Collection::set_projection),
+/// elle est appliquée automatiquement aux vecteurs insérés et aux requêtes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    input_dim: usize,
+    output_dim: usize,
+    /// Matrice `output_dim × input_dim`, stockée ligne par ligne.
+    matrix: Vec<f32>,
+    /// Vecteur soustrait avant la projection.
+    center: Option<Vec<f32>>,
+}
+
+impl Projection {
+    /// Crée une projection aléatoire gaussienne (lemme de Johnson-Lindenstrauss).
+    ///
+    /// Les coefficients suivent une loi normale de variance `1 / output_dim`, ce qui
+    /// préserve en moyenne les normes et les produits scalaires. La matrice ne dépend
+    /// que des dimensions et de `seed`.
+    ///
+    /// # Arguments
+    /// * `input_dim` - Dimension des vecteurs d'origine.
+    /// * `output_dim` - Dimension des vecteurs projetés.
+    /// * `seed` - Graine du générateur aléatoire.
+    pub fn random_gaussian(input_dim: usize, output_dim: usize, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let scale = 1.0 / (output_dim.max(1) as f64).sqrt();
+        let matrix = (0..input_dim * output_dim)
+            .map(|_| (rng.gaussian() * scale) as f32)
+            .collect();
+        Projection {
+            input_dim,
+            output_dim,
+            matrix,
+            center: None,
+        }
+    }
+
+    /// Crée une projection à partir d'une matrice explicite.
+    ///
+    /// # Arguments
+    /// * `input_dim` - Dimension des vecteurs d'origine.
+    /// * `output_dim` - Dimension des vecteurs projetés.
+    /// * `matrix` - Coefficients `output_dim × input_dim`, ligne par ligne.
+    ///
+    /// # Erreurs
+    /// * `Error::DimensionMismatch` - Si `matrix` ne contient pas `input_dim × output_dim` coefficients.
+    pub fn from_matrix(input_dim: usize, output_dim: usize, matrix: Vec<f32>) -> Result<Self> {
+        if matrix.len() != input_dim * output_dim {
+            return Err(Error::DimensionMismatch {
+                expected: input_dim * output_dim,
+                got: matrix.len(),
+            });
+        }
+        Ok(Projection {
+            input_dim,
+            output_dim,
+            matrix,
+            center: None,
+        })
+    }
+
+    /// Ajoute un centre soustrait des vecteurs avant la projection.
+    ///
+    /// # Arguments
+    /// * `center` - Vecteur de dimension `input_dim`.
+    ///
+    /// # Erreurs
+    /// * `Error::DimensionMismatch` - Si `center` n'a pas la dimension d'entrée.
+    pub fn with_center(mut self, center: Vec<f32>) -> Result<Self> {
+        if center.len() != self.input_dim {
+            return Err(Error::DimensionMismatch {
+                expected: self.input_dim,
+                got: center.len(),
+            });
+        }
+        self.center = Some(center);
+        Ok(self)
+    }
+
+    /// Dimension des vecteurs d'origine.
+    pub fn input_dim(&self) -> usize {
+        self.input_dim
+    }
+
+    /// Dimension des vecteurs projetés.
+    pub fn output_dim(&self) -> usize {
+        self.output_dim
+    }
+
+    /// Coefficients de la matrice, ligne par ligne.
+    pub fn matrix(&self) -> &[f32] {
+        &self.matrix
+    }
+
+    /// Centre soustrait avant la projection, s'il y en a un.
+    pub fn center(&self) -> Option<&[f32]> {
+        self.center.as_deref()
+    }
+
+    /// Projette un vecteur.
+    ///
+    /// # Arguments
+    /// * `vector` - Vecteur de dimension `input_dim`.
+    ///
+    /// # Retourne
+    /// * Result<Vec<f32>> - Vecteur de dimension `output_dim`.
+    pub fn apply(&self, vector: &[f32]) -> Result<Vec<f32>> {
+        if vector.len() != self.input_dim {
+            return Err(Error::DimensionMismatch {
+                expected: self.input_dim,
+                got: vector.len(),
+            });
+        }
+        if self.input_dim == 0 {
+            return Ok(vec![0.0; self.output_dim]);
+        }
+        let centered: Vec<f32>;
+        let vector = match &self.center {
+            Some(center) => {
+                centered = vector.iter().zip(center).map(|(x, c)| x - c).collect();
+                &centered
+            }
+            None => vector,
+        };
+        Ok(self
+            .matrix
+            .chunks(self.input_dim)
+            .map(|row| row.iter().zip(vector).map(|(a, x)| a * x).sum())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::database::BaseDeDonnees;
+    use crate::synthetic::{clustered_vectors, VectorGenerator};
+    use uuid::Uuid;
+
+    const INPUT_DIM: usize = 256;
+const CLUSTERS: usize = 100;
+
+    /// Collection pleine dimension et collection projetée des mêmes documents.
+    fn collections(output_dim: usize) -> (Collection, Collection) {
+        let (vectors, _) = clustered_vectors(CLUSTERS, 10, INPUT_DIM, 0.3, 11);
+        let mut generator = VectorGenerator::new(INPUT_DIM, 12);
+        let mut full = Collection::new();
+        let mut projected = Collection::new();
+        projected
+            .set_projection(Projection::random_gaussian(INPUT_DIM, output_dim, 5))
+            .unwrap();
+        for vector in vectors {
+            let key = generator.uuid();
+            full.upsert(key, vector.clone()).unwrap();
+            projected.upsert(key, vector).unwrap();
+        }
+        (full, projected)
+    }
+
+    /// Part des 10 plus proches voisins exacts retrouvés par la collection projetée.
+    fn recall_at_10(full: &Collection, projected: &Collection) -> f32 {
+        let (vectors, _) = clustered_vectors(CLUSTERS, 1, INPUT_DIM, 0.3, 11);
+        let mut found = 0;
+        for query in &vectors {
+            let exact: Vec<Uuid> = full.search(query, 10).unwrap().iter().map(|(k, _)| *k).collect();
+            let hits = projected.search(query, 10).unwrap();
+            found += hits.iter().filter(|(key, _)| exact.contains(key)).count();
+        }
+        found as f32 / (10 * vectors.len()) as f32
+    }
+
+    #[test]
+    fn projected_search_keeps_the_nearest_neighbors() {
+        // 256 dimensions ramenées à 32.
+        let (full, projected) = collections(32);
+        assert_eq!(projected.dimension(), Some(INPUT_DIM));
+        assert!(recall_at_10(&full, &projected) >= 0.95);
+    }
+
+    #[test]
+    fn projection_is_saved_with_the_collection() {
+        let mut generator = VectorGenerator::new(INPUT_DIM, 4);
+        let mut db = BaseDeDonnees::new();
+        let collection = db.get_or_create("docs").unwrap();
+        collection
+            .set_projection(Projection::random_gaussian(INPUT_DIM, 16, 9))
+            .unwrap();
+        generator.fill(collection, 50);
+        let query = generator.vector();
+        let before = collection.search(&query, 5).unwrap();
+
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let docs = loaded.get("docs").unwrap();
+        assert_eq!(docs.projection(), db.get("docs").unwrap().projection());
+        assert_eq!(docs.search(&query, 5).unwrap(), before);
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_matrix() {
+        let projection = Projection::random_gaussian(8, 3, 1);
+        assert_eq!(projection, Projection::random_gaussian(8, 3, 1));
+        assert_ne!(projection, Projection::random_gaussian(8, 3, 2));
+        assert!(matches!(
+            projection.apply(&[1.0; 7]),
+            Err(Error::DimensionMismatch {
+                expected: 8,
+                got: 7
+            })
+        ));
+    }
+}
@@ -0,0 +1,314 @@
+//! Expressions de requête : sommes pondérées de vecteurs et de documents stockés,
+//! écrites par exemple `0.7*id:3f2a… + 0.3*[0.1, 0.2] - 0.2*id:9bc4…`.
+//!
+//! Grammaire, les espaces étant ignorés entre les éléments :
+//!
+//! ```text
+//! expression = signe? terme (signe terme)*
+//! terme      = (nombre "*")? (document | vecteur)
+//! document   = "id:" uuid
+//! vecteur    = "[" nombre ("," nombre)* "]"
+//! signe      = "+" | "-"
+//! ```
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::search::{SearchParams, SearchResults};
+
+/// Élément d'une [`QueryExpr`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryTerm {
+    /// Vecteur stocké d'un document de la collection.
+    Document(Uuid),
+    /// Vecteur écrit dans l'expression.
+    Vector(Vec<f32>),
+}
+
+/// Somme pondérée de vecteurs et de documents stockés, résolue contre une collection par
+/// [`Collection::resolve_expr`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryExpr {
+    terms: Vec<(f32, QueryTerm)>,
+}
+
+impl QueryExpr {
+    /// Lit une expression (voir la grammaire du module).
+    ///
+    /// # Arguments
+    /// * `text` - Texte de l'expression.
+    ///
+    /// # Retourne
+    /// * Result<QueryExpr> - L'expression, avec ses termes dans l'ordre du texte.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidExpression` - À la première position où le texte s'écarte de la
+    ///   grammaire, avec ce qui y était attendu.
+    pub fn parse(text: &str) -> Result<QueryExpr> {
+        Parser { text, pos: 0 }.expression()
+    }
+
+    /// Termes de l'expression, chacun avec son poids signé.
+    pub fn terms(&self) -> &[(f32, QueryTerm)] {
+        &self.terms
+    }
+}
+
+/// Analyseur d'une expression, qui avance octet par octet dans `text`.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn expression(mut self) -> Result<QueryExpr> {
+        let mut terms = Vec::new();
+        let mut sign = match self.peek() {
+            Some('-') => -1.0,
+            _ => 1.0,
+        };
+        if matches!(self.peek(), Some('+' | '-')) {
+            self.pos += 1;
+        }
+        loop {
+            let (weight, term) = self.term()?;
+            terms.push((sign * weight, term));
+            sign = match self.peek() {
+                None => return Ok(QueryExpr { terms }),
+                Some('+') => 1.0,
+                Some('-') => -1.0,
+                Some(_) => return Err(self.expected("« + », « - » ou la fin de l'expression")),
+            };
+            self.pos += 1;
+        }
+    }
+
+    fn term(&mut self) -> Result<(f32, QueryTerm)> {
+        let weight = match self.peek() {
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let weight = self.number()?;
+                if self.peek() != Some('*') {
+                    return Err(self.expected("« * »"));
+                }
+                self.pos += 1;
+                weight
+            }
+            _ => 1.0,
+        };
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let mut vector = vec![self.number()?];
+                loop {
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => break,
+                        _ => return Err(self.expected("« , » ou « ] »")),
+                    }
+                    vector.push(self.number()?);
+                }
+                self.pos += 1;
+                Ok((weight, QueryTerm::Vector(vector)))
+            }
+            Some('i') if self.rest().starts_with("id:") => {
+                self.pos += "id:".len();
+                self.skip_spaces();
+                let token = self.token(|c| c.is_ascii_hexdigit() || c == '-');
+                let key = Uuid::parse_str(token).map_err(|_| self.expected("un Uuid"))?;
+                self.pos += token.len();
+                Ok((weight, QueryTerm::Document(key)))
+            }
+            _ => Err(self.expected("un poids, « id: » ou « [ »")),
+        }
+    }
+
+    /// Lit un nombre fini, éventuellement signé et en notation scientifique.
+    fn number(&mut self) -> Result<f32> {
+        self.skip_spaces();
+        let mut previous = 'e';
+        let token = self.token(|c| {
+            let accepted = c.is_ascii_digit()
+                || matches!(c, '.' | 'e' | 'E')
+                || (matches!(c, '+' | '-') && matches!(previous, 'e' | 'E'));
+            previous = c;
+            accepted
+        });
+        match token.parse::<f32>() {
+            Ok(x) if x.is_finite() => {
+                self.pos += token.len();
+                Ok(x)
+            }
+            _ => Err(self.expected("un nombre fini")),
+        }
+    }
+
+    /// Retourne le plus long préfixe du reste du texte dont les caractères vérifient
+    /// `accept`, sans avancer.
+    fn token(&self, mut accept: impl FnMut(char) -> bool) -> &str {
+        let rest = self.rest();
+        let len = rest.find(|c| !accept(c)).unwrap_or(rest.len());
+        &rest[..len]
+    }
+
+    /// Passe les espaces et retourne le caractère suivant, sans l'avancer.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.rest().chars().next()
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn expected(&self, expected: &str) -> Error {
+        Error::InvalidExpression {
+            offset: self.pos,
+            expected: expected.to_string(),
+        }
+    }
+}
+
+impl Collection {
+    /// Calcule le vecteur d'une expression de requête : `Σ poids × vecteur`, où les
+    /// documents sont remplacés par leur vecteur stocké (voir [`Collection::compose`]).
+    ///
+    /// Les vecteurs stockés suivant l'éventuelle projection de la collection, les vecteurs
+    /// écrits dans l'expression doivent avoir la même dimension qu'eux.
+    ///
+    /// # Arguments
+    /// * `expr` - Expression à résoudre.
+    ///
+    /// # Retourne
+    /// * Result<Vec<f32>> - Le vecteur combiné.
+    ///
+    /// # Erreurs
+    /// * `Error::DocumentNotFound` - Si un document de l'expression n'existe pas.
+    /// * `Error::DimensionMismatch` - Si les vecteurs n'ont pas tous la même dimension.
+    pub fn resolve_expr(&self, expr: &QueryExpr) -> Result<Vec<f32>> {
+        let mut combined: Vec<f32> = Vec::new();
+        for (index, (weight, term)) in expr.terms.iter().enumerate() {
+            let vector = match term {
+                QueryTerm::Document(key) => self
+                    .documents
+                    .get(key)
+                    .map(|vector| vector.as_slice())
+                    .ok_or(Error::DocumentNotFound(*key))?,
+                QueryTerm::Vector(vector) => vector.as_slice(),
+            };
+            if index == 0 {
+                combined = vec![0.0; vector.len()];
+            } else if vector.len() != combined.len() {
+                return Err(Error::DimensionMismatch {
+                    expected: combined.len(),
+                    got: vector.len(),
+                });
+            }
+            for (c, x) in combined.iter_mut().zip(vector) {
+                *c += weight * x;
+            }
+        }
+        Ok(combined)
+    }
+
+    /// Recherche les documents les plus proches d'une expression de requête selon
+    /// `params`, comme [`Collection::search_compose_with`] pour le vecteur combiné ; les
+    /// documents de l'expression peuvent figurer dans les résultats.
+    ///
+    /// # Arguments
+    /// * `expr` - Expression de la requête.
+    /// * `params` - Paramètres de la recherche.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Documents retenus avec leur score.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::resolve_expr`].
+    /// * Celles de [`Collection::search_compose_with`].
+    pub fn search_expr_with(
+        &self,
+        expr: &QueryExpr,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        let combined = self.resolve_expr(expr)?;
+        self.search_combined(combined, params, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: Uuid = Uuid::from_u128(0x3f2a);
+    const B: Uuid = Uuid::from_u128(0x9bc4);
+
+    #[test]
+    fn weights_signs_and_spaces_are_read() {
+        let text = format!("0.7*id:{} + 0.3 * [0.1, -2e-1] -  id: {}", A, B);
+        let expr = QueryExpr::parse(&text).unwrap();
+        assert_eq!(
+            expr.terms(),
+            [
+                (0.7, QueryTerm::Document(A)),
+                (0.3, QueryTerm::Vector(vec![0.1, -0.2])),
+                (-1.0, QueryTerm::Document(B)),
+            ]
+        );
+        let expr = QueryExpr::parse(" -[1] ").unwrap();
+        assert_eq!(expr.terms(), [(-1.0, QueryTerm::Vector(vec![1.0]))]);
+    }
+
+    #[test]
+    fn errors_give_the_offset_and_what_was_expected() {
+        let expected = |text: &str| match QueryExpr::parse(text) {
+            Err(Error::InvalidExpression { offset, expected }) => (offset, expected),
+            other => panic!("{:?} : {:?}", text, other),
+        };
+        assert_eq!(expected(""), (0, "un poids, « id: » ou « [ »".to_string()));
+        assert_eq!(expected("0.7 [1]"), (4, "« * »".to_string()));
+        assert_eq!(expected("[1, 2"), (5, "« , » ou « ] »".to_string()));
+        assert_eq!(expected("[1, 1e99]"), (4, "un nombre fini".to_string()));
+        assert_eq!(expected("2*id:xyz"), (5, "un Uuid".to_string()));
+        assert_eq!(
+            expected("[1] [2]"),
+            (4, "« + », « - » ou la fin de l'expression".to_string())
+        );
+        assert_eq!(
+            QueryExpr::parse("[1] +").unwrap_err().to_string(),
+            "expression de requête invalide à l'octet 5 : un poids, « id: » ou « [ » attendu"
+        );
+    }
+
+    #[test]
+    fn expressions_resolve_against_the_stored_vectors() {
+        let mut collection = Collection::new();
+        collection.upsert(A, [1.0, 0.0, 2.0]).unwrap();
+        collection.upsert(B, [0.0, 1.0, 0.0]).unwrap();
+        let expr = QueryExpr::parse(&format!("2*id:{} - 0.5*id:{} + [0, 1, -4]", A, B)).unwrap();
+        assert_eq!(collection.resolve_expr(&expr).unwrap(), [2.0, 0.5, 0.0]);
+
+        let unknown = Uuid::from_u128(7);
+        let expr = QueryExpr::parse(&format!("id:{} + id:{}", A, unknown)).unwrap();
+        assert!(matches!(
+            collection.resolve_expr(&expr),
+            Err(Error::DocumentNotFound(key)) if key == unknown
+        ));
+        let expr = QueryExpr::parse(&format!("id:{} + [1, 2]", A)).unwrap();
+        assert!(matches!(
+            collection.resolve_expr(&expr),
+            Err(Error::DimensionMismatch {
+                expected: 3,
+                got: 2
+            })
+        ));
+
+        let expr = QueryExpr::parse(&format!("id:{} - 0.9*id:{}", B, A)).unwrap();
+        let results = collection.search_expr_with(&expr, &SearchParams::new(1)).unwrap();
+        assert_eq!(results.hits[0].0, B);
+    }
+}
@@ -0,0 +1,468 @@
+//! Transformations appliquées à chaque requête d'une collection avant le calcul des
+//! scores : normalisation, centrage sur le centroïde, masquage de dimensions,
+//! projection, ou toute transformation définie par l'appelant.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::poison;
+use crate::projection::Projection;
+use crate::similarity;
+
+/// Transformation d'une requête, maillon d'un [`QueryPipeline`].
+///
+/// Elle s'applique dans l'espace des vecteurs stockés : après l'adaptateur
+/// ([`Collection::set_query_adapter`]) et la projection de la collection, avant sa
+/// normalisation éventuelle. Les transformations prédéfinies sont les variantes de
+/// [`BuiltinTransform`] ; une transformation définie par l'appelant n'est pas
+/// sauvegardée avec la collection (voir [`Collection::set_query_pipeline`]). Une
+/// panique de l'une de ses méthodes devient une erreur `Error::Panicked` de l'appel qui
+/// l'a provoquée.
+pub trait QueryTransform: Any + fmt::Debug + Send + Sync {
+    /// Retourne le nom de la transformation, tel que le rapporte
+    /// [`SearchResults::query_transforms`](crate::SearchResults::query_transforms).
+    fn name(&self) -> String;
+
+    /// Retourne la dimension d'une requête de dimension `input` après la transformation.
+    ///
+    /// Appelée pour chaque maillon lorsque le pipeline est attaché à une collection :
+    /// une transformation qui ne s'applique pas à `input` doit échouer ici plutôt qu'à
+    /// la première recherche.
+    fn output_dimension(&self, input: usize) -> Result<usize>;
+
+    /// Transforme une requête de la dimension validée par
+    /// [`QueryTransform::output_dimension`].
+    ///
+    /// # Arguments
+    /// * `query` - Requête produite par le maillon précédent.
+    /// * `collection` - Collection interrogée.
+    fn apply(&self, query: Vec<f32>, collection: &Collection) -> Result<Vec<f32>>;
+}
+
+/// Transformations prédéfinies d'un [`QueryPipeline`], sauvegardées avec la collection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuiltinTransform {
+    /// Ramène la requête à une norme de 1 ; une requête nulle échoue avec
+    /// `Error::ZeroVector`.
+    Normalize,
+    /// Soustrait de la requête la moyenne des vecteurs stockés, tenue à jour à chaque
+    /// écriture ([`Collection::dimension_stats`]) ; sans effet sur une collection vide
+    /// ou dont les documents n'ont pas tous la même dimension.
+    CenterOnCentroid,
+    /// Annule les coordonnées de la requête aux dimensions données. Contrairement à
+    /// [`SearchParams::dimension_mask`](crate::SearchParams::dimension_mask), les
+    /// vecteurs stockés gardent ces coordonnées, qui comptent encore dans leur norme.
+    Mask(Vec<usize>),
+    /// Applique une projection linéaire, éventuellement centrée ; le pipeline doit
+    /// finir dans la dimension des vecteurs stockés.
+    Project(Projection),
+}
+
+impl QueryTransform for BuiltinTransform {
+    fn name(&self) -> String {
+        match self {
+            BuiltinTransform::Normalize => "normalize".to_string(),
+            BuiltinTransform::CenterOnCentroid => "center_on_centroid".to_string(),
+            BuiltinTransform::Mask(dimensions) => format!("mask{:?}", dimensions),
+            BuiltinTransform::Project(projection) => format!(
+                "project({} → {})",
+                projection.input_dim(),
+                projection.output_dim()
+            ),
+        }
+    }
+
+    fn output_dimension(&self, input: usize) -> Result<usize> {
+        match self {
+            BuiltinTransform::Normalize | BuiltinTransform::CenterOnCentroid => Ok(input),
+            BuiltinTransform::Mask(dimensions) => {
+                match dimensions.iter().find(|index| **index >= input) {
+                    Some(index) => Err(Error::RangeOutOfBounds {
+                        offset: *index,
+                        len: 1,
+                        dimension: input,
+                    }),
+                    None => Ok(input),
+                }
+            }
+            BuiltinTransform::Project(projection) if projection.input_dim() != input => {
+                Err(Error::DimensionMismatch {
+                    expected: projection.input_dim(),
+                    got: input,
+                })
+            }
+            BuiltinTransform::Project(projection) => Ok(projection.output_dim()),
+        }
+    }
+
+    fn apply(&self, mut query: Vec<f32>, collection: &Collection) -> Result<Vec<f32>> {
+        match self {
+            BuiltinTransform::Normalize => {
+                let norm = similarity::norm_f64(&query);
+                if norm == 0.0 {
+                    return Err(Error::ZeroVector { key: None });
+                }
+                (query.iter_mut()).for_each(|x| *x = (f64::from(*x) / norm) as f32);
+            }
+            BuiltinTransform::CenterOnCentroid => {
+                if let Some(mean) = collection.stats.mean().filter(|m| m.len() == query.len()) {
+                    (query.iter_mut().zip(mean)).for_each(|(x, m)| *x = (f64::from(*x) - m) as f32);
+                }
+            }
+            BuiltinTransform::Mask(dimensions) => {
+                for index in dimensions {
+                    query[*index] = 0.0;
+                }
+            }
+            BuiltinTransform::Project(projection) => return projection.apply(&query),
+        }
+        Ok(query)
+    }
+}
+
+/// Transformation définie par l'appelant, lue d'une sauvegarde qui n'en a gardé que le
+/// nom : elle fait échouer les recherches jusqu'à ce que le pipeline soit rattaché.
+#[derive(Debug)]
+pub(crate) struct Unresolved {
+    pub(crate) name: String,
+}
+
+impl QueryTransform for Unresolved {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn output_dimension(&self, input: usize) -> Result<usize> {
+        Ok(input)
+    }
+
+    fn apply(&self, _: Vec<f32>, _: &Collection) -> Result<Vec<f32>> {
+        Err(Error::InvalidConfig(format!(
+            "la transformation de requête '{}' n'est pas sauvegardée avec la collection ; \
+             rattachez-la avec Collection::set_query_pipeline",
+            self.name
+        )))
+    }
+}
+
+/// Suite ordonnée de transformations appliquées à chaque requête d'une collection
+/// (voir [`Collection::set_query_pipeline`]).
+///
+/// `QueryPipeline::new().then(BuiltinTransform::CenterOnCentroid).then(BuiltinTransform::Normalize)`.
+#[derive(Debug, Clone, Default)]
+pub struct QueryPipeline {
+    transforms: Vec<Arc<dyn QueryTransform>>,
+}
+
+impl QueryPipeline {
+    /// Crée un pipeline vide, qui laisse les requêtes inchangées.
+    pub fn new() -> Self {
+        QueryPipeline::default()
+    }
+
+    /// Ajoute `transform` à la fin du pipeline.
+    pub fn then(self, transform: impl QueryTransform) -> Self {
+        self.then_shared(Arc::new(transform))
+    }
+
+    /// Ajoute une transformation partagée à la fin du pipeline.
+    pub fn then_shared(mut self, transform: Arc<dyn QueryTransform>) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Retourne les transformations, dans l'ordre d'application.
+    pub fn transforms(&self) -> &[Arc<dyn QueryTransform>] {
+        &self.transforms
+    }
+
+    /// Retourne le nombre de transformations.
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Indique si le pipeline est vide.
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Retourne les noms des transformations, dans l'ordre d'application.
+    pub fn names(&self) -> Vec<String> {
+        self.transforms.iter().map(|transform| transform.name()).collect()
+    }
+
+    /// Vérifie que chaque maillon s'applique à la sortie du précédent et que le
+    /// pipeline ramène une requête de dimension `dimension` à cette même dimension.
+    pub(crate) fn check(&self, dimension: usize) -> Result<()> {
+        let mut current = dimension;
+        for transform in &self.transforms {
+            current = poison::catch(|| transform.output_dimension(current))??;
+        }
+        if current != dimension {
+            return Err(Error::InvalidConfig(format!(
+                "le pipeline de requêtes produit la dimension {} au lieu de {}",
+                current, dimension
+            )));
+        }
+        Ok(())
+    }
+
+    /// Applique les transformations dans l'ordre ; une transformation qui panique
+    /// échoue avec `Error::Panicked`.
+    pub(crate) fn apply(&self, query: Vec<f32>, collection: &Collection) -> Result<Vec<f32>> {
+        (self.transforms.iter()).try_fold(query, |query, transform| {
+            poison::catch(|| transform.apply(query, collection))?
+        })
+    }
+
+    /// Retourne la transformation de rang `index` si elle est prédéfinie.
+    pub(crate) fn builtin(&self, index: usize) -> Option<&BuiltinTransform> {
+        let transform: &dyn Any = &*self.transforms[index];
+        transform.downcast_ref()
+    }
+}
+
+/// Deux pipelines sont égaux si leurs transformations prédéfinies sont égales une à une
+/// et leurs autres transformations partagées.
+impl PartialEq for QueryPipeline {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && (0..self.len()).all(|index| match (self.builtin(index), other.builtin(index)) {
+                (Some(a), Some(b)) => a == b,
+                (None, None) => Arc::ptr_eq(&self.transforms[index], &other.transforms[index]),
+                _ => false,
+            })
+    }
+}
+
+// Les projections n'admettent pas de coefficient NaN : l'égalité est une relation
+// d'équivalence.
+impl Eq for QueryPipeline {}
+
+impl Collection {
+    /// Attache un pipeline de transformations appliqué à chaque requête de la
+    /// collection, en remplaçant le précédent ; un pipeline vide le retire.
+    ///
+    /// Les transformations s'appliquent dans l'ordre, à chaque recherche par vecteur, y
+    /// compris filtrée, multiple ou enregistrée, et aux statistiques de scores : après
+    /// l'adaptateur et la projection de la collection, avant sa normalisation. Les
+    /// vecteurs insérés ne sont jamais transformés. Les noms des transformations
+    /// appliquées sont rapportés par
+    /// [`SearchResults::query_transforms`](crate::SearchResults::query_transforms).
+    ///
+    /// Le pipeline fait partie de la configuration et est sauvegardé avec la
+    /// collection ; une transformation définie par l'appelant n'en garde que le nom, et
+    /// la collection rechargée refuse les recherches jusqu'à ce qu'un nouveau pipeline
+    /// soit attaché.
+    ///
+    /// # Arguments
+    /// * `pipeline` - Transformations à appliquer.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si la dimension des vecteurs stockés n'est pas encore
+    ///   connue, ou si le pipeline ne ramène pas les requêtes à cette dimension.
+    /// * Celles de [`QueryTransform::output_dimension`], pour le premier maillon qui ne
+    ///   s'applique pas à la sortie du précédent.
+    ///
+    /// Le pipeline précédent est conservé en cas d'erreur.
+    pub fn set_query_pipeline(&mut self, pipeline: QueryPipeline) -> Result<()> {
+        if !pipeline.is_empty() {
+            let dimension = self.stored_dimension().ok_or_else(|| {
+                Error::InvalidConfig(
+                    "la dimension de la collection doit être connue avant d'attacher un \
+                     pipeline de requêtes"
+                        .to_string(),
+                )
+            })?;
+            pipeline.check(dimension)?;
+        }
+        self.config.query_pipeline = pipeline;
+        Ok(())
+    }
+
+    /// Retire le pipeline de requêtes de la collection et le retourne.
+    pub fn remove_query_pipeline(&mut self) -> QueryPipeline {
+        std::mem::take(&mut self.config.query_pipeline)
+    }
+
+    /// Retourne le pipeline de requêtes de la collection, vide s'il n'y en a pas.
+    pub fn query_pipeline(&self) -> &QueryPipeline {
+        &self.config.query_pipeline
+    }
+
+    /// Dimension des vecteurs stockés : celle de sortie de la projection, ou celle de la
+    /// collection.
+    pub(crate) fn stored_dimension(&self) -> Option<usize> {
+        match &self.projection {
+            Some(projection) => Some(projection.output_dim()),
+            None => self.dimension(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::search::SearchParams;
+    use crate::similarity::Metric;
+    use crate::synthetic::VectorGenerator;
+
+    fn collection(seed: u64) -> Collection {
+        let mut collection = Collection::new().with_metric(Metric::Euclidean);
+        let mut generator = VectorGenerator::new(6, seed);
+        for _ in 0..200 {
+            let vector: Vec<f32> = generator.vector().iter().map(|x| x + 2.0).collect();
+            collection.upsert(generator.uuid(), vector).unwrap();
+        }
+        collection
+    }
+
+    /// Centre puis normalise `query` comme le ferait le pipeline.
+    fn centered_and_normalized(query: &[f32], collection: &Collection) -> Vec<f32> {
+        let mean = collection.stats.mean().unwrap();
+        let centered: Vec<f32> = (query.iter().zip(&mean))
+            .map(|(x, m)| (f64::from(*x) - m) as f32)
+            .collect();
+        let norm = similarity::norm_f64(&centered);
+        centered.iter().map(|x| (f64::from(*x) / norm) as f32).collect()
+    }
+
+    #[test]
+    fn composed_transforms_equal_manual_application() {
+        let plain = collection(1);
+        let mut transformed = plain.clone();
+        let pipeline = QueryPipeline::new()
+            .then(BuiltinTransform::CenterOnCentroid)
+            .then(BuiltinTransform::Normalize);
+        transformed.set_query_pipeline(pipeline).unwrap();
+
+        let mut generator = VectorGenerator::new(6, 99);
+        for _ in 0..10 {
+            let query = generator.vector();
+            let expected = plain
+                .search(centered_and_normalized(&query, &plain), 10)
+                .unwrap();
+            let results = transformed
+                .search_with(&query, &SearchParams { k: 10, ..Default::default() })
+                .unwrap();
+            assert_eq!(results.hits, expected);
+            assert_eq!(results.query_transforms, ["center_on_centroid", "normalize"]);
+            assert!(results.to_string().contains("center_on_centroid → normalize"));
+        }
+
+        // L'ordre compte : normaliser puis centrer donne une autre requête.
+        let reversed = QueryPipeline::new()
+            .then(BuiltinTransform::Normalize)
+            .then(BuiltinTransform::CenterOnCentroid);
+        let query = vec![3.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let forward = transformed.query_pipeline().apply(query.clone(), &plain).unwrap();
+        let backward = reversed.apply(query, &plain).unwrap();
+        assert!((similarity::norm_f64(&forward) - 1.0).abs() < 1e-6);
+        assert_ne!(forward, backward);
+    }
+
+    #[test]
+    fn mask_and_projection_compose_in_order() {
+        let mut collection = Collection::new();
+        collection.upsert(uuid::Uuid::new_v4(), [1.0, 2.0, 3.0]).unwrap();
+        // Échange des deux premières coordonnées.
+        let swap = Projection::from_matrix(3, 3, vec![0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0])
+            .unwrap();
+        let pipeline = QueryPipeline::new()
+            .then(BuiltinTransform::Mask(vec![0]))
+            .then(BuiltinTransform::Project(swap));
+        collection.set_query_pipeline(pipeline).unwrap();
+        let query = collection.query_pipeline().apply(vec![5.0, 6.0, 7.0], &collection);
+        assert_eq!(query.unwrap(), [6.0, 0.0, 7.0]);
+        assert_eq!(
+            collection.query_pipeline().names(),
+            ["mask[0]", "project(3 → 3)"]
+        );
+    }
+
+    #[test]
+    fn dimensions_are_checked_when_the_pipeline_is_attached() {
+        let mut empty = Collection::new();
+        let normalize = QueryPipeline::new().then(BuiltinTransform::Normalize);
+        assert!(matches!(
+            empty.set_query_pipeline(normalize.clone()),
+            Err(Error::InvalidConfig(_))
+        ));
+        empty.set_query_pipeline(QueryPipeline::new()).unwrap();
+
+        let mut collection = collection(2);
+        collection.set_query_pipeline(normalize.clone()).unwrap();
+        let masked = QueryPipeline::new().then(BuiltinTransform::Mask(vec![6]));
+        assert!(matches!(
+            collection.set_query_pipeline(masked),
+            Err(Error::RangeOutOfBounds { offset: 6, .. })
+        ));
+        let wrong_input = QueryPipeline::new()
+            .then(BuiltinTransform::Project(Projection::random_gaussian(4, 6, 1)));
+        assert!(matches!(
+            collection.set_query_pipeline(wrong_input),
+            Err(Error::DimensionMismatch { expected: 4, got: 6 })
+        ));
+        let wrong_output = QueryPipeline::new()
+            .then(BuiltinTransform::Project(Projection::random_gaussian(6, 4, 1)));
+        assert!(matches!(
+            collection.set_query_pipeline(wrong_output),
+            Err(Error::InvalidConfig(_))
+        ));
+        // Le pipeline précédent est conservé.
+        assert_eq!(collection.query_pipeline(), &normalize);
+        assert_eq!(collection.remove_query_pipeline(), normalize);
+        assert!(collection.query_pipeline().is_empty());
+    }
+
+    #[derive(Debug)]
+    struct Double;
+
+    impl QueryTransform for Double {
+        fn name(&self) -> String {
+            "double".to_string()
+        }
+
+        fn output_dimension(&self, input: usize) -> Result<usize> {
+            Ok(input)
+        }
+
+        fn apply(&self, query: Vec<f32>, _: &Collection) -> Result<Vec<f32>> {
+            Ok(query.iter().map(|x| x * 2.0).collect())
+        }
+    }
+
+    #[test]
+    fn pipelines_are_saved_with_the_collection() {
+        let mut collection = collection(3);
+        let pipeline = QueryPipeline::new()
+            .then(BuiltinTransform::CenterOnCentroid)
+            .then(BuiltinTransform::Mask(vec![1, 4]))
+            .then(BuiltinTransform::Normalize);
+        collection.set_query_pipeline(pipeline.clone()).unwrap();
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection.clone());
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap();
+        assert_eq!(loaded.query_pipeline(), &pipeline);
+        let query = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(loaded.search(query, 5).unwrap(), collection.search(query, 5).unwrap());
+
+        // Une transformation de l'appelant ne garde que son nom et doit être rattachée.
+        let custom = QueryPipeline::new()
+            .then(BuiltinTransform::Normalize)
+            .then(Double);
+        collection.set_query_pipeline(custom.clone()).unwrap();
+        db.replace("docs".to_string(), collection);
+        let mut loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let loaded = loaded.get_mut("docs").unwrap();
+        assert_eq!(loaded.query_pipeline().names(), ["normalize", "double"]);
+        assert!(matches!(loaded.search(query, 5), Err(Error::InvalidConfig(_))));
+        loaded.set_query_pipeline(custom).unwrap();
+        assert_eq!(loaded.search(query, 5).unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,497 @@
+//! Recherches lues d'un flux JSONL, une requête par ligne, et écrites au fil de l'eau.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::import;
+use crate::json;
+use crate::payload::Value;
+use crate::search::SearchParams;
+
+/// Nombre de requêtes lues mais pas encore écrites, par thread de calcul.
+const IN_FLIGHT_PER_THREAD: usize = 16;
+
+/// Bilan de [`BaseDeDonnees::search_jsonl`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StreamReport {
+    /// Nombre de lignes non vides lues, une requête chacune.
+    pub queries: usize,
+    /// Nombre de requêtes en erreur : ligne invalide ou recherche refusée.
+    pub failed: usize,
+}
+
+impl fmt::Display for StreamReport {
+    /// Affiche le bilan sur une ligne : `1000 requête(s), 3 en erreur`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} requête(s), {} en erreur", self.queries, self.failed)
+    }
+}
+
+/// Requête lue d'une ligne du flux.
+struct Query {
+    /// Numéro de la ligne, à partir de 1.
+    line: usize,
+    /// Champ `id` de la ligne, recopié dans la sortie.
+    id: Option<Value>,
+    vector: Vec<f32>,
+}
+
+/// Ligne de sortie d'une requête.
+enum Outcome {
+    /// Résultats de la recherche.
+    Hits(String),
+    /// Description de l'erreur.
+    Failed(String),
+}
+
+/// Destination des lignes de sortie, dans l'ordre des requêtes.
+struct Sink<'a> {
+    output: &'a mut dyn Write,
+    errors: Option<&'a mut dyn Write>,
+    report: StreamReport,
+}
+
+impl Sink<'_> {
+    fn write(&mut self, outcome: Outcome) -> Result<()> {
+        self.report.queries += 1;
+        let (out, text) = match outcome {
+            Outcome::Hits(text) => (&mut *self.output, text),
+            Outcome::Failed(text) => {
+                self.report.failed += 1;
+                match &mut self.errors {
+                    Some(errors) => (&mut **errors, text),
+                    None => (&mut *self.output, text),
+                }
+            }
+        };
+        writeln!(out, "{}", text)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.output.flush()?;
+        if let Some(errors) = &mut self.errors {
+            errors.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl BaseDeDonnees {
+    /// Recherche dans la collection `cname` chaque requête d'un flux JSONL et écrit une
+    /// ligne JSON par requête, dès qu'elle est calculée.
+    ///
+    /// Une ligne est un tableau de nombres, `[0.1, 0.2]`, ou un objet
+    /// `{"id": "q-1", "vector": [0.1, 0.2]}` dont le champ `id`, facultatif et de type
+    /// quelconque, est recopié dans la sortie. Les lignes vides sont ignorées. Chaque
+    /// requête donne, dans l'ordre du flux, `{"line": 1, "id": "q-1", "hits": [...]}`,
+    /// `hits` ayant le format de [`SearchResults::to_json`](crate::SearchResults::to_json),
+    /// ou `{"line": 1, "id": "q-1", "error": "..."}` si la ligne est invalide ou si la
+    /// recherche échoue : une ligne en erreur n'interrompt jamais le flux.
+    ///
+    /// La mémoire utilisée ne dépend pas de la longueur du flux : avec plusieurs threads,
+    /// au plus 16 requêtes par thread sont lues sans avoir encore été écrites, et la
+    /// lecture attend que les plus anciennes le soient.
+    ///
+    /// # Arguments
+    /// * `cname` - Nom de la collection.
+    /// * `reader` - Flux des requêtes.
+    /// * `output` - Destination des résultats, vidée après chaque écriture.
+    /// * `errors` - Destination des lignes en erreur ; `None` les écrit dans `output`.
+    /// * `params` - Paramètres de chaque recherche.
+    /// * `threads` - Nombre de recherches calculées en parallèle ; 0 est traité comme 1,
+    ///   qui calcule tout dans le thread appelant.
+    ///
+    /// # Retourne
+    /// * Result<StreamReport> - Nombre de requêtes lues et de requêtes en erreur.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte ce nom.
+    /// * `Error::Io` - Si le flux ne peut pas être lu ou si une sortie ne peut pas être
+    ///   écrite ; les lignes déjà écrites le restent.
+    pub fn search_jsonl<'a>(
+        &self,
+        cname: &str,
+        mut reader: impl BufRead + Send,
+        output: &'a mut dyn Write,
+        errors: Option<&'a mut dyn Write>,
+        params: &SearchParams,
+        threads: usize,
+    ) -> Result<StreamReport> {
+        if self.store(cname).is_none() {
+            return Err(Error::CollectionNotFound(cname.to_string()));
+        }
+        let mut sink = Sink {
+            output,
+            errors,
+            report: StreamReport::default(),
+        };
+        if threads <= 1 {
+            let mut buffer = Vec::new();
+            let mut line = 0;
+            while let Some(query) = next_query(&mut reader, &mut buffer, &mut line)? {
+                sink.write(self.answer(cname, query, params))?;
+                sink.flush()?;
+            }
+            return Ok(sink.report);
+        }
+        let window = threads * IN_FLIGHT_PER_THREAD;
+        // Les jetons bornent les requêtes en cours : l'envoi d'une requête lue n'attend
+        // jamais, et la lecture s'arrête dès que l'écriture abandonne ses jetons.
+        let (jobs, queue) = mpsc::sync_channel::<(usize, Query)>(window);
+        let queue = Mutex::new(queue);
+        let (done, results) = mpsc::channel::<(usize, Outcome)>();
+        let (credit, credits) = mpsc::sync_channel::<()>(window);
+        (0..window).for_each(|_| credit.send(()).expect("jetons disponibles"));
+        thread::scope(|scope| {
+            let failures = done.clone();
+            let reading = scope.spawn(move || -> Result<()> {
+                let mut buffer = Vec::new();
+                let mut line = 0;
+                let mut sequence = 0;
+                while let Some(query) = next_query(&mut reader, &mut buffer, &mut line)? {
+                    if credits.recv().is_err() {
+                        break;
+                    }
+                    let sent = match query {
+                        Ok(query) => jobs.send((sequence, query)).is_ok(),
+                        Err(outcome) => failures.send((sequence, outcome)).is_ok(),
+                    };
+                    if !sent {
+                        break;
+                    }
+                    sequence += 1;
+                }
+                Ok(())
+            });
+            for _ in 0..threads {
+                let done = done.clone();
+                let queue = &queue;
+                scope.spawn(move || loop {
+                    let job = queue.lock().expect("file des requêtes empoisonnée").recv();
+                    let Ok((sequence, query)) = job else {
+                        break;
+                    };
+                    if done.send((sequence, self.answer(cname, Ok(query), params))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(done);
+            write_in_order(&mut sink, results, credit)?;
+            reading.join().expect("lecture des requêtes interrompue")
+        })?;
+        Ok(sink.report)
+    }
+
+    /// Calcule la ligne de sortie d'une requête.
+    fn answer(
+        &self,
+        cname: &str,
+        query: std::result::Result<Query, Outcome>,
+        params: &SearchParams,
+    ) -> Outcome {
+        let query = match query {
+            Ok(query) => query,
+            Err(outcome) => return outcome,
+        };
+        match self.search_with(cname, &query.vector, params) {
+            Ok(results) => Outcome::Hits(line_json(query.line, &query.id, "hits", |out| {
+                out.push_str(&results.to_json())
+            })),
+            Err(e) => failed(query.line, &query.id, &e.to_string()),
+        }
+    }
+}
+
+/// Écrit les résultats dans l'ordre des requêtes et rend un jeton de lecture pour
+/// chaque ligne écrite.
+///
+/// Retourne lorsque tous les calculs sont terminés, ou à la première erreur
+/// d'écriture : `results` et `credit` sont alors abandonnés, ce qui arrête la lecture et
+/// les calculs.
+fn write_in_order(
+    sink: &mut Sink,
+    results: Receiver<(usize, Outcome)>,
+    credit: SyncSender<()>,
+) -> Result<()> {
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (sequence, outcome) in results {
+        pending.insert(sequence, outcome);
+        while let Some(outcome) = pending.remove(&next) {
+            sink.write(outcome)?;
+            next += 1;
+            // Il y a toujours de la place : chaque jeton rendu a été pris par la lecture.
+            let _ = credit.try_send(());
+        }
+        sink.flush()?;
+    }
+    Ok(())
+}
+
+/// Lit la prochaine ligne non vide du flux.
+///
+/// # Retourne
+/// * Result<Option<...>> - La requête lue, ou la ligne d'erreur si elle est invalide ;
+///   `None` à la fin du flux.
+///
+/// # Erreurs
+/// * `Error::Io` - Si le flux ne peut pas être lu.
+fn next_query(
+    reader: &mut impl BufRead,
+    buffer: &mut Vec<u8>,
+    line: &mut usize,
+) -> Result<Option<std::result::Result<Query, Outcome>>> {
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', buffer)? == 0 {
+            return Ok(None);
+        }
+        *line += 1;
+        let Ok(text) = std::str::from_utf8(buffer) else {
+            return Ok(Some(Err(failed(*line, &None, "ligne non UTF-8"))));
+        };
+        if !text.trim().is_empty() {
+            return Ok(Some(parse_query(text, *line)));
+        }
+    }
+}
+
+/// Lit une requête : un tableau de nombres, ou un objet avec `vector` et `id`.
+fn parse_query(text: &str, line: usize) -> std::result::Result<Query, Outcome> {
+    let (id, vector) = match json::parse(text).map_err(|e| failed(line, &None, &e))? {
+        Value::Object(mut fields) => (fields.remove("id"), fields.remove("vector")),
+        value => (None, Some(value)),
+    };
+    let Some(vector) = vector else {
+        return Err(failed(line, &id, "champ « vector » absent"));
+    };
+    match import::vector(&vector) {
+        Some(vector) => Ok(Query { line, id, vector }),
+        None => Err(failed(line, &id, "le vecteur doit contenir des nombres f32")),
+    }
+}
+
+/// Ligne de sortie d'une requête en erreur.
+fn failed(line: usize, id: &Option<Value>, message: &str) -> Outcome {
+    Outcome::Failed(line_json(line, id, "error", |out| {
+        json::write_string(out, message)
+    }))
+}
+
+/// Écrit `{"line": line, "id": id, <field>: ...}`, sans `id` s'il est absent.
+fn line_json(
+    line: usize,
+    id: &Option<Value>,
+    field: &str,
+    write_field: impl FnOnce(&mut String),
+) -> String {
+    let mut out = String::from("{\"line\":");
+    json::write_u64(&mut out, line as u64);
+    if let Some(id) = id {
+        out.push_str(",\"id\":");
+        json::write_value(&mut out, id);
+    }
+    out.push(',');
+    json::write_string(&mut out, field);
+    out.push(':');
+    write_field(&mut out);
+    out.push('}');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::synthetic::VectorGenerator;
+
+    fn database() -> BaseDeDonnees {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let docs = db.get_mut("docs").unwrap();
+        let mut generator = VectorGenerator::new(4, 1);
+        for _ in 0..300 {
+            docs.upsert(generator.uuid(), generator.vector()).unwrap();
+        }
+        db
+    }
+
+    /// Flux de `count` requêtes dont une sur cent est invalide.
+    fn queries(count: usize) -> String {
+        let mut generator = VectorGenerator::new(4, 2);
+        let mut text = String::new();
+        for i in 0..count {
+            let v = generator.vector();
+            match i % 100 {
+                7 => text.push_str("{\"id\": 7, \"vecteur\": [1]}\n"),
+                42 => text.push_str("[1.0, \n"),
+                66 => text.push_str("[1.0, 2.0]\n"),
+                _ => text.push_str(&format!("{{\"id\": \"q{}\", \"vector\": {:?}}}\n", i, v)),
+            }
+            if i % 500 == 0 {
+                text.push_str("\n  \n");
+            }
+        }
+        text
+    }
+
+    fn run(db: &BaseDeDonnees, input: &str, threads: usize, separate: bool) -> (String, String, StreamReport) {
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        let errors_out: Option<&mut dyn Write> = match separate {
+            true => Some(&mut errors),
+            false => None,
+        };
+        let report = db
+            .search_jsonl("docs", Cursor::new(input), &mut output, errors_out, &SearchParams::new(3), threads)
+            .unwrap();
+        (String::from_utf8(output).unwrap(), String::from_utf8(errors).unwrap(), report)
+    }
+
+    /// Numéro de ligne d'une ligne de sortie.
+    fn line_number(text: &str) -> usize {
+        match json::parse(text).unwrap() {
+            Value::Object(fields) => match fields["line"] {
+                Value::Number(line) => line as usize,
+                ref value => panic!("{:?}", value),
+            },
+            value => panic!("{:?}", value),
+        }
+    }
+
+    #[test]
+    fn parallel_streams_keep_the_input_order() {
+        let db = database();
+        let input = queries(3000);
+        let (sequential, _, report) = run(&db, &input, 1, false);
+        assert_eq!(report, StreamReport { queries: 3000, failed: 90 });
+        let lines: Vec<usize> = sequential.lines().map(line_number).collect();
+        assert_eq!(lines.len(), 3000);
+        assert!(lines.windows(2).all(|pair| pair[0] < pair[1]));
+        for threads in [2, 4, 8] {
+            let (parallel, _, parallel_report) = run(&db, &input, threads, false);
+            assert_eq!(parallel, sequential, "{} threads", threads);
+            assert_eq!(parallel_report, report);
+        }
+        let first = sequential.lines().next().unwrap();
+        assert!(first.starts_with("{\"line\":1,\"id\":\"q0\",\"hits\":[{\"id\":"), "{}", first);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_without_stopping_the_stream() {
+        let db = database();
+        let input = queries(300);
+        for threads in [1, 4] {
+            let (output, errors, report) = run(&db, &input, threads, true);
+            assert_eq!(report, StreamReport { queries: 300, failed: 9 });
+            assert_eq!(output.lines().count(), 291);
+            assert!(output.lines().all(|line| line.contains("\"hits\":")));
+            let errors: Vec<&str> = errors.lines().collect();
+            assert_eq!(errors.len(), 9);
+            // Les 2 lignes vides qui suivent la première requête décalent les numéros.
+            assert!(errors[0].starts_with("{\"line\":10,\"id\":7,\"error\":"), "{}", errors[0]);
+            assert!(errors[0].contains("vector"));
+            assert!(errors[1].starts_with("{\"line\":45,\"error\":"), "{}", errors[1]);
+            assert!(errors[2].starts_with("{\"line\":69,\"error\":"), "{}", errors[2]);
+            assert!(errors.iter().all(|line| line.contains("\"error\":")));
+        }
+        let (_, _, report) = run(&db, "\n\n", 4, false);
+        assert_eq!(report, StreamReport::default());
+        let mut output = Vec::new();
+        assert!(matches!(
+            db.search_jsonl("absente", Cursor::new(""), &mut output, None, &SearchParams::new(1), 1),
+            Err(Error::CollectionNotFound(_))
+        ));
+    }
+
+    /// Flux de requêtes qui compte les lignes déjà lues.
+    struct CountingReader {
+        line: Vec<u8>,
+        offset: usize,
+        remaining: usize,
+        read: Arc<AtomicUsize>,
+    }
+
+    impl std::io::Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for CountingReader {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            match self.remaining {
+                0 => Ok(&[]),
+                _ => Ok(&self.line[self.offset..]),
+            }
+        }
+
+        fn consume(&mut self, amount: usize) {
+            self.offset += amount;
+            if self.offset == self.line.len() {
+                self.offset = 0;
+                self.remaining -= 1;
+                self.read.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Sortie qui vérifie, à chaque ligne écrite, l'avance de la lecture.
+    struct CheckedWriter {
+        written: usize,
+        read: Arc<AtomicUsize>,
+        max_ahead: usize,
+    }
+
+    impl Write for CheckedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written += buf.iter().filter(|byte| **byte == b'\n').count();
+            let ahead = self.read.load(Ordering::SeqCst) - self.written;
+            self.max_ahead = self.max_ahead.max(ahead);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reading_stays_a_bounded_window_ahead_of_writing() {
+        let db = database();
+        let threads = 2;
+        let read = Arc::new(AtomicUsize::new(0));
+        let reader = CountingReader {
+            line: b"[0.5, -0.25, 1.0, 0.0]\n".to_vec(),
+            offset: 0,
+            remaining: 5000,
+            read: Arc::clone(&read),
+        };
+        let mut writer = CheckedWriter {
+            written: 0,
+            read: Arc::clone(&read),
+            max_ahead: 0,
+        };
+        let report = db
+            .search_jsonl("docs", reader, &mut writer, None, &SearchParams::new(2), threads)
+            .unwrap();
+        assert_eq!(report.queries, 5000);
+        assert_eq!(writer.written, 5000);
+        // La fenêtre, plus la ligne lue qui attend un jeton.
+        assert!(writer.max_ahead <= threads * IN_FLIGHT_PER_THREAD + 1, "{}", writer.max_ahead);
+    }
+}
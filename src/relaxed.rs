@@ -0,0 +1,313 @@
+//! Lectures tolérant un léger retard, servies depuis une vue figée de la base rafraîchie
+//! sans jamais attendre un écrivain.
+
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+
+use crate::collection::Document;
+use crate::database_snapshot::DatabaseSnapshot;
+use crate::error::Result;
+use crate::shared::BaseDeDonneesPartagee;
+
+/// Nombre d'écritures de retard au-delà duquel [`BaseDeDonneesPartagee::search_relaxed`]
+/// rafraîchit sa vue, par défaut.
+pub const DEFAULT_MAX_STALE_OPS: u64 = 1000;
+
+/// Âge au-delà duquel [`BaseDeDonneesPartagee::search_relaxed`] rafraîchit sa vue, par
+/// défaut.
+pub const DEFAULT_MAX_STALE_AGE: Duration = Duration::from_millis(100);
+
+/// Retard de la vue qui a servi une lecture relâchée sur la base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Staleness {
+    /// Numéro de séquence de la base dans la vue ([`BaseDeDonnees::sequence`]).
+    ///
+    /// [`BaseDeDonnees::sequence`]: crate::BaseDeDonnees::sequence
+    pub sequence: u64,
+    /// Nombre d'accès en écriture de retard sur la base, `None` si un écrivain tenait
+    /// le verrou : le retard n'a pas pu être mesuré sans l'attendre.
+    pub ops_behind: Option<u64>,
+    /// Temps écoulé depuis que la vue a été vue pour la dernière fois égale à la base.
+    pub age: Duration,
+}
+
+impl Staleness {
+    /// Indique si la vue était à jour au moment de la lecture.
+    pub fn is_current(&self) -> bool {
+        self.ops_behind == Some(0)
+    }
+}
+
+/// Résultats de [`BaseDeDonneesPartagee::search_relaxed`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelaxedResults {
+    /// Documents retenus, comme ceux de [`BaseDeDonnees::search`].
+    ///
+    /// [`BaseDeDonnees::search`]: crate::BaseDeDonnees::search
+    pub hits: Document,
+    /// Retard de la vue interrogée.
+    pub staleness: Staleness,
+}
+
+/// Limites de retard au-delà desquelles la vue est rafraîchie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StalenessBound {
+    pub(crate) max_ops: u64,
+    pub(crate) max_age: Duration,
+}
+
+impl Default for StalenessBound {
+    fn default() -> Self {
+        StalenessBound {
+            max_ops: DEFAULT_MAX_STALE_OPS,
+            max_age: DEFAULT_MAX_STALE_AGE,
+        }
+    }
+}
+
+/// Vue partagée par les clones d'une poignée.
+pub(crate) struct RelaxedView {
+    snapshot: Arc<DatabaseSnapshot>,
+    /// Dernier instant où la vue a été vue égale à la base.
+    verified: Instant,
+}
+
+/// Vue des lectures relâchées, créée à la première d'entre elles.
+pub(crate) type SharedView = Arc<Mutex<Option<RelaxedView>>>;
+
+impl BaseDeDonneesPartagee {
+    /// Fixe les limites de retard de [`BaseDeDonneesPartagee::search_relaxed`], par
+    /// défaut [`DEFAULT_MAX_STALE_OPS`] et [`DEFAULT_MAX_STALE_AGE`], et capture
+    /// aussitôt la vue des lectures relâchées : la première d'entre elles n'a alors pas
+    /// à prendre le verrou en lecture.
+    ///
+    /// # Arguments
+    /// * `max_ops` - Nombre d'accès en écriture de retard au-delà duquel la vue est
+    ///   rafraîchie ; 0 la rafraîchit après chaque écriture.
+    /// * `max_age` - Âge au-delà duquel la vue est rafraîchie.
+    pub fn with_relaxed_reads(mut self, max_ops: u64, max_age: Duration) -> Self {
+        self.bound = StalenessBound { max_ops, max_age };
+        let snapshot = Arc::new(self.read().snapshot());
+        *self.view() = Some(RelaxedView {
+            snapshot,
+            verified: Instant::now(),
+        });
+        self
+    }
+
+    /// Capture la vue des lectures relâchées et retourne son retard, en la
+    /// rafraîchissant si elle dépasse les limites de
+    /// [`BaseDeDonneesPartagee::with_relaxed_reads`].
+    ///
+    /// Le rafraîchissement ne fait qu'essayer de prendre le verrou en lecture : si un
+    /// écrivain le tient, la vue précédente est retournée, plus en retard que les
+    /// limites, sans l'attendre. Seule la toute première capture, si
+    /// [`BaseDeDonneesPartagee::with_relaxed_reads`] ne l'a pas faite, attend le verrou.
+    ///
+    /// La vue partage les collections de la base ([`BaseDeDonnees::snapshot`]) : à la
+    /// première écriture qui suit un rafraîchissement, une collection modifiée est copiée
+    /// sans ses tables de documents, dont seul le segment touché l'est, et l'ancienne
+    /// version est libérée au rafraîchissement suivant. La vue ne retient donc en propre
+    /// que les segments modifiés depuis sa capture, et au plus une ancienne version de
+    /// chaque collection modifiée.
+    ///
+    /// [`BaseDeDonnees::snapshot`]: crate::BaseDeDonnees::snapshot
+    pub fn relaxed_snapshot(&self) -> (Arc<DatabaseSnapshot>, Staleness) {
+        let cached = (self.view().as_ref()).map(|view| (Arc::clone(&view.snapshot), view.verified));
+        let (snapshot, verified) = match cached {
+            Some(cached) => cached,
+            // Le verrou de la vue n'est pas tenu pendant la capture : un écrivain en
+            // attente du verrou de la base bloquerait sinon les autres lecteurs.
+            None => {
+                let snapshot = Arc::new(self.read().snapshot());
+                let mut view = self.view();
+                let view = view.get_or_insert_with(|| RelaxedView {
+                    snapshot,
+                    verified: Instant::now(),
+                });
+                (Arc::clone(&view.snapshot), view.verified)
+            }
+        };
+        let db = match self.inner.try_read() {
+            Ok(db) => db,
+            Err(TryLockError::WouldBlock) => {
+                let staleness = Staleness {
+                    sequence: snapshot.sequence(),
+                    ops_behind: None,
+                    age: verified.elapsed(),
+                };
+                return (snapshot, staleness);
+            }
+            Err(TryLockError::Poisoned(_)) => panic!("verrou de la base empoisonné"),
+        };
+        let behind = db.sequence() - snapshot.sequence();
+        let now = Instant::now();
+        if behind == 0 {
+            self.store_view(&snapshot, Arc::clone(&snapshot), now);
+        } else if behind > self.bound.max_ops || now - verified > self.bound.max_age {
+            let fresh = Arc::new(db.snapshot());
+            drop(db);
+            let staleness = Staleness {
+                sequence: fresh.sequence(),
+                ops_behind: Some(0),
+                age: Duration::ZERO,
+            };
+            self.store_view(&snapshot, Arc::clone(&fresh), now);
+            return (fresh, staleness);
+        }
+        let staleness = Staleness {
+            sequence: snapshot.sequence(),
+            ops_behind: Some(behind),
+            age: if behind == 0 {
+                Duration::ZERO
+            } else {
+                now - verified
+            },
+        };
+        (snapshot, staleness)
+    }
+
+    /// Recherche les `k` documents les plus proches dans une collection, depuis la vue
+    /// de [`BaseDeDonneesPartagee::relaxed_snapshot`] : sans jamais attendre un
+    /// écrivain, au prix d'un retard borné lorsque le verrou est libre et indiqué dans
+    /// les résultats.
+    ///
+    /// [`BaseDeDonneesPartagee::search`] reste à jour, mais attend les écrivains.
+    ///
+    /// # Arguments
+    /// * `cname` - Nom de la collection.
+    /// * `request` - Vecteur de requête.
+    /// * `k` - Nombre de résultats à retourner.
+    ///
+    /// # Retourne
+    /// * Result<RelaxedResults> - Résultats de la recherche et retard de la vue.
+    ///
+    /// # Erreurs
+    /// * Celles de [`DatabaseSnapshot::search`], y compris pour une collection créée
+    ///   depuis la capture de la vue.
+    pub fn search_relaxed(
+        &self,
+        cname: &str,
+        request: impl AsRef<[f32]>,
+        k: usize,
+    ) -> Result<RelaxedResults> {
+        let (snapshot, staleness) = self.relaxed_snapshot();
+        Ok(RelaxedResults {
+            hits: snapshot.search(cname, request, k)?,
+            staleness,
+        })
+    }
+
+    fn view(&self) -> MutexGuard<'_, Option<RelaxedView>> {
+        self.relaxed
+            .lock()
+            .expect("vue des lectures relâchées empoisonnée")
+    }
+
+    /// Remplace la vue par `next` si elle est encore `previous`, et la date de `now` :
+    /// un thread concurrent a pu la rafraîchir entre-temps.
+    fn store_view(
+        &self,
+        previous: &Arc<DatabaseSnapshot>,
+        next: Arc<DatabaseSnapshot>,
+        now: Instant,
+    ) {
+        if let Some(view) = self.view().as_mut() {
+            if Arc::ptr_eq(&view.snapshot, previous) {
+                view.snapshot = next;
+                view.verified = now;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::collection::Collection;
+    use crate::database::BaseDeDonnees;
+
+    fn shared(count: usize, max_ops: u64, max_age: Duration) -> BaseDeDonneesPartagee {
+        let mut collection = Collection::new();
+        for i in 0..count {
+            collection.upsert(Uuid::new_v4(), vec![1.0, i as f32]).unwrap();
+        }
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), collection);
+        BaseDeDonneesPartagee::new(bdd).with_relaxed_reads(max_ops, max_age)
+    }
+
+    fn write(shared: &BaseDeDonneesPartagee) {
+        let mut bdd = shared.write();
+        bdd.get_mut("c").unwrap().upsert(Uuid::new_v4(), vec![1.0, -1.0]).unwrap();
+    }
+
+    #[test]
+    fn relaxed_reads_do_not_wait_for_a_long_write() {
+        let shared = shared(100, 0, Duration::ZERO);
+        let (locked, wait) = mpsc::channel();
+        let writer = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let mut bdd = shared.write();
+                locked.send(()).unwrap();
+                thread::sleep(Duration::from_millis(500));
+                bdd.get_mut("c").unwrap().upsert(Uuid::new_v4(), vec![1.0, -1.0]).unwrap();
+            })
+        };
+        wait.recv().unwrap();
+        let started = Instant::now();
+        for _ in 0..10 {
+            let results = shared.search_relaxed("c", [1.0, 0.0], 3).unwrap();
+            assert_eq!(results.hits.len(), 3);
+            assert_eq!(results.staleness.ops_behind, None);
+        }
+        assert!(started.elapsed() < Duration::from_millis(250));
+        writer.join().unwrap();
+        assert!(shared.search_relaxed("c", [1.0, 0.0], 3).unwrap().staleness.is_current());
+    }
+
+    #[test]
+    fn refreshes_after_max_ops() {
+        let shared = shared(10, 2, Duration::from_secs(3600));
+        let sequence = shared.relaxed_snapshot().1.sequence;
+        write(&shared);
+        write(&shared);
+        let (snapshot, staleness) = shared.relaxed_snapshot();
+        assert_eq!((staleness.sequence, staleness.ops_behind), (sequence, Some(2)));
+        assert_eq!(snapshot.get("c").unwrap().len(), 10);
+
+        write(&shared);
+        let (snapshot, staleness) = shared.relaxed_snapshot();
+        assert!(staleness.is_current());
+        assert_eq!(snapshot.get("c").unwrap().len(), 13);
+    }
+
+    #[test]
+    fn refreshes_after_max_age() {
+        let shared = shared(10, 1000, Duration::from_millis(20));
+        write(&shared);
+        assert_eq!(shared.relaxed_snapshot().1.ops_behind, Some(1));
+        thread::sleep(Duration::from_millis(40));
+        let (snapshot, staleness) = shared.relaxed_snapshot();
+        assert!(staleness.is_current());
+        assert_eq!(snapshot.get("c").unwrap().len(), 11);
+    }
+
+    #[test]
+    fn view_does_not_duplicate_the_collection() {
+        let shared = shared(20_000, 1000, Duration::from_secs(3600));
+        let (view, _) = shared.relaxed_snapshot();
+        write(&shared);
+        write(&shared);
+        let bdd = shared.read();
+        let (live, frozen) = (bdd.get("c").unwrap(), view.get("c").unwrap());
+        assert!(live.documents.shared_segments(&frozen.documents) >= 254);
+        assert_eq!(frozen.len(), 20_000);
+    }
+}
@@ -0,0 +1,1192 @@
+//! Réplication d'une base primaire vers des réplicas.
+//!
+//! Chaque écriture passant par un [`Primary`] est appliquée à sa base puis ajoutée à son
+//! journal sous forme d'un [`ChangeRecord`] numéroté. Un [`Replica`] rejoue ces
+//! enregistrements dans l'ordre, depuis une connexion TCP ou un fichier de journal, et
+//! mémorise le dernier numéro appliqué pour reprendre après une déconnexion.
+//!
+//! Sur le fil comme dans un fichier, chaque enregistrement est une trame
+//! `longueur: u32 | numéro: u64 | dernier numéro du primaire: u64 | opération`, avec le
+//! même encodage que les sauvegardes. À la connexion, le réplica envoie le numéro du
+//! premier enregistrement qu'il attend (`u64`). Une création de collection porte sa
+//! configuration, précédée de la version de sauvegarde qui l'a encodée.
+//!
+//! [`Primary::shutdown`] arrête proprement un primaire : plus d'écriture, journal écrit
+//! sur le disque, sauvegarde finale et fin des threads de diffusion.
+//!
+//! Le même journal sert à recopier les changements dans un autre système :
+//! [`Primary::changes_since`] retourne les enregistrements qui suivent un curseur, par
+//! pages, tant que [`ChangeRetention`] les conserve en mémoire.
+
+use std::collections::VecDeque;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::changed::UpsertOutcome;
+use crate::config::CollectionConfig;
+use crate::database::BaseDeDonnees;
+#[cfg(feature = "encryption")]
+use crate::encryption;
+use crate::error::{Error, Result};
+use crate::payload::Payload;
+use crate::shared::BaseDeDonneesPartagee;
+use crate::snapshot::{self, Reader};
+use crate::storage::StorageFile;
+use crate::store::VectorStoreMut;
+use crate::transaction::Op;
+
+/// Taille maximale d'une trame acceptée à la lecture.
+const MAX_FRAME_LEN: u32 = 1 << 30;
+
+/// Opération enregistrée dans le journal de réplication.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeOp {
+    /// Création d'une collection ; sans effet si elle existe déjà.
+    CreateCollection {
+        collection: String,
+        config: CollectionConfig,
+    },
+    /// Insertion ou mise à jour d'un document, avec sa charge utile éventuelle.
+    Upsert {
+        collection: String,
+        key: Uuid,
+        vector: Vec<f32>,
+        payload: Option<Payload>,
+    },
+    /// Suppression d'un document ; sans effet s'il n'existe pas.
+    Delete { collection: String, key: Uuid },
+    /// Lot d'opérations appliqué en bloc à une collection simple ([`Collection::apply`]).
+    ///
+    /// [`Collection::apply`]: crate::Collection::apply
+    Apply { collection: String, ops: Vec<Op> },
+}
+
+impl ChangeOp {
+    /// Retourne le nom de la collection visée.
+    fn collection(&self) -> &str {
+        match self {
+            ChangeOp::CreateCollection { collection, .. }
+            | ChangeOp::Upsert { collection, .. }
+            | ChangeOp::Delete { collection, .. }
+            | ChangeOp::Apply { collection, .. } => collection,
+        }
+    }
+}
+
+/// Enregistrement numéroté du journal de réplication.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeRecord {
+    /// Numéro de l'enregistrement, à partir de 1 et sans trou.
+    pub sequence: u64,
+    /// Opération enregistrée.
+    pub op: ChangeOp,
+}
+
+/// Conservation en mémoire du journal d'un [`Primary`].
+///
+/// Les enregistrements les plus anciens sont oubliés dès qu'une des limites est
+/// dépassée ; le fichier de [`Primary::with_log_file`], lui, les garde tous. Un
+/// lecteur ou un réplica qui demande un enregistrement oublié reçoit
+/// `Error::CursorTooOld` et doit repartir d'une sauvegarde. Par défaut, tout est
+/// conservé.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChangeRetention {
+    /// Nombre maximal d'enregistrements conservés.
+    pub max_records: Option<usize>,
+    /// Âge maximal des enregistrements conservés, depuis leur écriture.
+    pub max_age: Option<Duration>,
+}
+
+/// État de la réplication vu d'un primaire ou d'un réplica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReplicationStatus {
+    /// Dernier numéro écrit par le primaire (connu du réplica, côté réplica).
+    pub last_sequence: u64,
+    /// Côté réplica, dernier numéro appliqué ; côté primaire, dernier numéro envoyé au
+    /// réplica connecté le plus en retard, ou `last_sequence` si aucun n'est connecté.
+    pub applied_sequence: u64,
+}
+
+impl ReplicationStatus {
+    /// Nombre d'enregistrements écrits mais pas encore appliqués ou envoyés.
+    pub fn lag(&self) -> u64 {
+        self.last_sequence.saturating_sub(self.applied_sequence)
+    }
+}
+
+/// Base primaire dont les écritures alimentent le journal de réplication.
+///
+/// Le journal est conservé en mémoire selon [`Primary::with_retention`], entièrement par
+/// défaut, et recopié dans un fichier si [`Primary::with_log_file`] est utilisé. Les
+/// clones partagent la même base et le même journal.
+#[derive(Clone)]
+pub struct Primary {
+    db: BaseDeDonneesPartagee,
+    inner: Arc<PrimaryInner>,
+}
+
+struct PrimaryInner {
+    log: Mutex<PrimaryLog>,
+    appended: Condvar,
+}
+
+struct PrimaryLog {
+    /// Enregistrements conservés, à partir du numéro `evicted + 1`.
+    records: VecDeque<ChangeRecord>,
+    /// Date d'écriture de chaque enregistrement de `records`.
+    written: VecDeque<Instant>,
+    /// Nombre d'enregistrements oubliés, les plus anciens.
+    evicted: u64,
+    retention: ChangeRetention,
+    file: Option<BufWriter<Box<dyn StorageFile>>>,
+    /// Clé de chiffrement des trames écrites dans `file`.
+    #[cfg(feature = "encryption")]
+    file_key: Option<[u8; 32]>,
+    /// Numéro du dernier enregistrement envoyé à chaque réplica connecté.
+    replicas: Vec<Option<u64>>,
+    /// Adresses écoutées par [`Primary::serve`], pour réveiller les threads d'acceptation
+    /// à l'arrêt.
+    listeners: Vec<SocketAddr>,
+    /// Nombre de threads de diffusion encore actifs, d'acceptation compris.
+    running: usize,
+    /// Vrai après [`Primary::shutdown`].
+    closed: bool,
+}
+
+impl PrimaryLog {
+    /// Numéro du dernier enregistrement écrit.
+    fn last_sequence(&self) -> u64 {
+        self.evicted + self.records.len() as u64
+    }
+
+    /// Oublie les enregistrements que la conservation n'admet plus.
+    fn evict(&mut self) {
+        let now = Instant::now();
+        while let Some(written) = self.written.front() {
+            let too_many = (self.retention.max_records).is_some_and(|max| self.records.len() > max);
+            let too_old = (self.retention.max_age).is_some_and(|max| now - *written > max);
+            if !too_many && !too_old {
+                break;
+            }
+            self.records.pop_front();
+            self.written.pop_front();
+            self.evicted += 1;
+        }
+    }
+
+    /// Retourne les enregistrements conservés à partir du numéro `next`.
+    ///
+    /// # Erreurs
+    /// * `Error::CursorTooOld` - Si l'enregistrement `next` a été oublié.
+    fn since(&self, next: u64) -> Result<impl Iterator<Item = &ChangeRecord>> {
+        if next <= self.evicted {
+            return Err(Error::CursorTooOld {
+                requested: next,
+                oldest: self.evicted + 1,
+            });
+        }
+        let start = usize::try_from(next - self.evicted - 1).unwrap_or(usize::MAX);
+        Ok(self.records.range(start.min(self.records.len())..))
+    }
+}
+
+impl Drop for PrimaryLog {
+    /// Écrit au mieux ce qui reste du journal ; seul [`Primary::shutdown`] rapporte les
+    /// échecs.
+    fn drop(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.flush();
+            let _ = file.get_mut().sync();
+        }
+    }
+}
+
+/// Bilan de [`Primary::shutdown`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ShutdownReport {
+    /// Numéro du dernier enregistrement du journal.
+    pub last_sequence: u64,
+    /// Vrai si la sauvegarde finale a été écrite.
+    pub snapshot_saved: bool,
+    /// Nombre de threads de diffusion encore actifs à l'expiration du délai, par exemple
+    /// bloqués sur un réplica qui ne lit plus.
+    pub threads_still_running: usize,
+    /// Erreurs rencontrées en écrivant le journal ou la sauvegarde finale.
+    pub errors: Vec<Error>,
+}
+
+impl ShutdownReport {
+    /// Indique si tout a été écrit et tous les threads arrêtés.
+    pub fn is_clean(&self) -> bool {
+        self.threads_still_running == 0 && self.errors.is_empty()
+    }
+}
+
+impl Primary {
+    /// Crée un primaire sur une base partagée.
+    ///
+    /// Les écritures faites directement sur `db`, sans passer par le primaire, ne sont
+    /// pas répliquées.
+    ///
+    /// # Arguments
+    /// * `db` - Base à répliquer.
+    pub fn new(db: BaseDeDonneesPartagee) -> Self {
+        Primary {
+            db,
+            inner: Arc::new(PrimaryInner {
+                log: Mutex::new(PrimaryLog {
+                    records: VecDeque::new(),
+                    written: VecDeque::new(),
+                    evicted: 0,
+                    retention: ChangeRetention::default(),
+                    file: None,
+                    #[cfg(feature = "encryption")]
+                    file_key: None,
+                    replicas: Vec::new(),
+                    listeners: Vec::new(),
+                    running: 0,
+                    closed: false,
+                }),
+                appended: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Fixe la conservation en mémoire du journal ; les enregistrements qu'elle n'admet
+    /// plus sont oubliés aussitôt.
+    ///
+    /// # Arguments
+    /// * `retention` - Nombre et âge maximaux des enregistrements conservés.
+    pub fn with_retention(self, retention: ChangeRetention) -> Self {
+        {
+            let mut log = self.lock();
+            log.retention = retention;
+            log.evict();
+        }
+        self
+    }
+
+    /// Recopie désormais chaque enregistrement à la fin du fichier `path`, ouvert par
+    /// l'accès aux fichiers de la base ([`BaseDeDonnees::with_storage`]).
+    ///
+    /// Une écriture du fichier qui échoue arrête le primaire comme [`Primary::shutdown`] :
+    /// l'opération en cours, déjà appliquée à la base, n'est pas inscrite au journal, et
+    /// les écritures suivantes sont refusées avec `Error::ShutDown`. Le fichier reste
+    /// relisible jusqu'au dernier enregistrement complet.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être ouvert.
+    pub fn with_log_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let file = self.db.read().storage().append(path.as_ref())?;
+        self.lock().file = Some(BufWriter::new(file));
+        Ok(self)
+    }
+
+    /// Recopie désormais chaque enregistrement à la fin du fichier `path`, chiffré par
+    /// `key` (fonctionnalité `encryption`) ; le fichier se relit avec
+    /// [`Replica::consume_encrypted_file`].
+    ///
+    /// Chaque trame est authentifiée séparément avec son propre nonce : une trame modifiée
+    /// fait échouer la relecture, et les numéros d'enregistrement, chiffrés avec elles,
+    /// révèlent les trames retirées ou déplacées, sauf les dernières du fichier. `path`
+    /// doit être un nouveau fichier ou un journal chiffré par la même clé.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être ouvert.
+    #[cfg(feature = "encryption")]
+    pub fn with_encrypted_log_file(self, path: impl AsRef<Path>, key: &[u8; 32]) -> Result<Self> {
+        let file = self.db.read().storage().append(path.as_ref())?;
+        {
+            let mut log = self.lock();
+            log.file = Some(BufWriter::new(file));
+            log.file_key = Some(*key);
+        }
+        Ok(self)
+    }
+
+    /// Retourne la base répliquée.
+    pub fn db(&self) -> &BaseDeDonneesPartagee {
+        &self.db
+    }
+
+    /// Crée une collection si elle n'existe pas.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Numéro de l'enregistrement.
+    pub fn create_collection(&self, collection: &str) -> Result<u64> {
+        self.create_collection_with_config(collection, CollectionConfig::default())
+    }
+
+    /// Crée une collection configurée par `config` si elle n'existe pas.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Numéro de l'enregistrement.
+    pub fn create_collection_with_config(
+        &self,
+        collection: &str,
+        config: CollectionConfig,
+    ) -> Result<u64> {
+        self.record(ChangeOp::CreateCollection {
+            collection: collection.to_string(),
+            config,
+        })
+    }
+
+    /// Insère ou met à jour un document.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Numéro de l'enregistrement.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si la collection n'existe pas.
+    /// * `Error::CollectionPoisoned` - Si la collection est en quarantaine
+    ///   ([`BaseDeDonneesPartagee::quarantined`]).
+    /// * Les erreurs de [`Collection::upsert`] ; rien n'est alors enregistré.
+    pub fn upsert(&self, collection: &str, key: Uuid, vector: Vec<f32>) -> Result<u64> {
+        self.record(ChangeOp::Upsert {
+            collection: collection.to_string(),
+            key,
+            vector,
+            payload: None,
+        })
+    }
+
+    /// Insère ou met à jour un document et sa charge utile.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Numéro de l'enregistrement.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si la collection n'existe pas.
+    /// * `Error::CollectionPoisoned` - Si la collection est en quarantaine
+    ///   ([`BaseDeDonneesPartagee::quarantined`]).
+    /// * Les erreurs de [`Collection::upsert`] ; rien n'est alors enregistré.
+    pub fn upsert_with_payload(
+        &self,
+        collection: &str,
+        key: Uuid,
+        vector: Vec<f32>,
+        payload: Payload,
+    ) -> Result<u64> {
+        self.record(ChangeOp::Upsert {
+            collection: collection.to_string(),
+            key,
+            vector,
+            payload: Some(payload),
+        })
+    }
+
+    /// Insère ou met à jour un document d'une collection simple comme
+    /// [`Collection::upsert_if_changed`] : un document inchangé n'est pas inscrit dans le
+    /// journal, les lecteurs de [`Primary::changes_since`] ne le voient pas et le numéro
+    /// de séquence de la base ([`BaseDeDonnees::sequence`]) n'avance pas.
+    ///
+    /// # Retourne
+    /// * Result<(UpsertOutcome, Option<u64>)> - L'effet de l'appel et le numéro de
+    ///   l'enregistrement, `None` pour un document inchangé.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si la collection simple n'existe pas.
+    /// * `Error::CollectionPoisoned` - Si la collection est en quarantaine
+    ///   ([`BaseDeDonneesPartagee::quarantined`]).
+    /// * Les erreurs de [`Collection::upsert_if_changed`] ; rien n'est alors enregistré.
+    ///
+    /// [`Collection::upsert_if_changed`]: crate::Collection::upsert_if_changed
+    pub fn upsert_if_changed(
+        &self,
+        collection: &str,
+        key: Uuid,
+        vector: Vec<f32>,
+        epsilon: f32,
+    ) -> Result<(UpsertOutcome, Option<u64>)> {
+        let mut log = self.lock();
+        if log.closed {
+            return Err(Error::ShutDown);
+        }
+        let not_found = || Error::CollectionNotFound(collection.to_string());
+        let mut db = self.db.write();
+        self.db.quarantine.check(&db, collection)?;
+        // Un document inchangé est vérifié en lecture : il ne fait pas avancer
+        // `BaseDeDonnees::sequence`.
+        if db
+            .get(collection)
+            .ok_or_else(not_found)?
+            .is_unchanged(key, vector.clone(), epsilon)?
+        {
+            return Ok((UpsertOutcome::Unchanged, None));
+        }
+        let outcome = db
+            .get_mut(collection)
+            .ok_or_else(not_found)?
+            .upsert_if_changed(key, vector.clone(), epsilon)?;
+        drop(db);
+        let op = ChangeOp::Upsert {
+            collection: collection.to_string(),
+            key,
+            vector,
+            payload: None,
+        };
+        Ok((outcome, Some(self.append(&mut log, op)?)))
+    }
+
+    /// Supprime un document.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Numéro de l'enregistrement.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si la collection n'existe pas.
+    /// * `Error::CollectionPoisoned` - Si la collection est en quarantaine
+    ///   ([`BaseDeDonneesPartagee::quarantined`]).
+    pub fn delete(&self, collection: &str, key: Uuid) -> Result<u64> {
+        self.record(ChangeOp::Delete {
+            collection: collection.to_string(),
+            key,
+        })
+    }
+
+    /// Applique un lot d'opérations en bloc à une collection simple, comme
+    /// [`Collection::apply`], et l'inscrit en un seul enregistrement : un réplica qui
+    /// rejoue le journal applique le lot entier ou rien.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Numéro de l'enregistrement.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si la collection simple n'existe pas.
+    /// * `Error::CollectionPoisoned` - Si la collection est en quarantaine
+    ///   ([`BaseDeDonneesPartagee::quarantined`]).
+    /// * Les erreurs de [`Collection::apply`] ; rien n'est alors enregistré.
+    ///
+    /// [`Collection::apply`]: crate::Collection::apply
+    pub fn apply(&self, collection: &str, ops: Vec<Op>) -> Result<u64> {
+        self.record(ChangeOp::Apply {
+            collection: collection.to_string(),
+            ops,
+        })
+    }
+
+    /// Retourne au plus `limit` enregistrements à partir du numéro `from`, pour suivre
+    /// les changements de la base depuis un curseur.
+    ///
+    /// Un lecteur commence à 1, ou au numéro qui suit le dernier enregistrement reflété
+    /// par sa copie, puis passe à chaque appel le curseur retourné par le précédent.
+    ///
+    /// # Arguments
+    /// * `from` - Numéro du premier enregistrement attendu ; 0 est traité comme 1.
+    /// * `limit` - Nombre maximal d'enregistrements retournés.
+    ///
+    /// # Retourne
+    /// * Result<(Vec<ChangeRecord>, u64)> - Les enregistrements, dans l'ordre, et le
+    ///   numéro à demander ensuite ; une liste vide signale que le lecteur est à jour.
+    ///
+    /// # Erreurs
+    /// * `Error::CursorTooOld` - Si l'enregistrement `from` a été oublié selon la
+    ///   conservation du journal ([`Primary::with_retention`]).
+    pub fn changes_since(&self, from: u64, limit: usize) -> Result<(Vec<ChangeRecord>, u64)> {
+        let mut log = self.lock();
+        log.evict();
+        let from = from.max(1);
+        let records: Vec<ChangeRecord> = log.since(from)?.take(limit).cloned().collect();
+        let next = from + records.len() as u64;
+        Ok((records, next))
+    }
+
+    /// Retourne le dernier numéro écrit et la position du réplica connecté le plus en retard.
+    pub fn replication_status(&self) -> ReplicationStatus {
+        let log = self.lock();
+        let last_sequence = log.last_sequence();
+        ReplicationStatus {
+            last_sequence,
+            applied_sequence: log
+                .replicas
+                .iter()
+                .flatten()
+                .copied()
+                .min()
+                .unwrap_or(last_sequence),
+        }
+    }
+
+    /// Diffuse le journal aux réplicas qui se connectent à `listener`.
+    ///
+    /// Chaque connexion est servie par son propre thread, qui envoie les enregistrements
+    /// demandés puis les nouveaux au fil de l'eau, jusqu'à la déconnexion du réplica ou
+    /// l'arrêt du primaire. Un réplica qui demande ou attend un enregistrement oublié
+    /// selon [`Primary::with_retention`] est déconnecté.
+    ///
+    /// # Retourne
+    /// * JoinHandle<()> - Thread d'acceptation, actif jusqu'à [`Primary::shutdown`] tant
+    ///   que `listener` est valide.
+    pub fn serve(&self, listener: TcpListener) -> JoinHandle<()> {
+        let primary = self.clone();
+        {
+            let mut log = self.lock();
+            if let Ok(addr) = listener.local_addr() {
+                log.listeners.push(addr);
+            }
+            log.running += 1;
+        }
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if primary.lock().closed {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let primary = primary.clone();
+                primary.lock().running += 1;
+                thread::spawn(move || {
+                    let _ = primary.stream_to(stream);
+                    primary.lock().running -= 1;
+                });
+            }
+            primary.lock().running -= 1;
+        })
+    }
+
+    /// Arrête le primaire : refuse les écritures suivantes avec `Error::ShutDown`, écrit
+    /// le journal sur le disque, enregistre une sauvegarde finale de la base puis attend
+    /// jusqu'à `timeout` la fin des threads de [`Primary::serve`], après leur avoir laissé
+    /// envoyer les enregistrements en attente.
+    ///
+    /// C'est le chemin fiable pour arrêter un primaire : à la destruction du dernier
+    /// clone, le journal n'est écrit qu'au mieux, sans rapporter d'erreur. Les étapes sont
+    /// toutes tentées même si l'une échoue, et un deuxième appel les reprend.
+    ///
+    /// # Arguments
+    /// * `snapshot` - Fichier de la sauvegarde finale, ou `None` pour s'en passer.
+    /// * `timeout` - Attente maximale des threads de diffusion.
+    ///
+    /// # Retourne
+    /// * ShutdownReport - Ce qui a été écrit et ce qui n'a pas pu l'être.
+    pub fn shutdown(&self, snapshot: Option<&Path>, timeout: Duration) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        let listeners = {
+            let mut log = self.lock();
+            log.closed = true;
+            report.last_sequence = log.last_sequence();
+            if let Some(file) = log.file.as_mut() {
+                if let Err(error) = file.flush().and_then(|_| file.get_mut().sync()) {
+                    report.errors.push(error.into());
+                }
+            }
+            self.inner.appended.notify_all();
+            log.listeners.clone()
+        };
+        if let Some(path) = snapshot {
+            match self.db.read().save(path) {
+                Ok(()) => report.snapshot_saved = true,
+                Err(error) => report.errors.push(error),
+            }
+        }
+        // Une connexion réveille chaque thread d'acceptation, qui voit l'arrêt et se termine.
+        for mut addr in listeners {
+            if addr.ip().is_unspecified() {
+                addr.set_ip(Ipv4Addr::LOCALHOST.into());
+            }
+            let _ = TcpStream::connect_timeout(&addr, timeout);
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            let running = self.lock().running;
+            if running == 0 || Instant::now() >= deadline {
+                report.threads_still_running = running;
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        report
+    }
+
+    fn stream_to(&self, mut stream: TcpStream) -> Result<()> {
+        let mut start = [0; 8];
+        stream.read_exact(&mut start)?;
+        let mut next = u64::from_le_bytes(start).max(1);
+        let slot = {
+            let mut log = self.lock();
+            log.replicas.push(Some(next - 1));
+            log.replicas.len() - 1
+        };
+        let result = (|| loop {
+            let (pending, head) = {
+                let mut log = self.lock();
+                while log.last_sequence() < next {
+                    if log.closed {
+                        return Ok(());
+                    }
+                    log = self
+                        .inner
+                        .appended
+                        .wait(log)
+                        .expect("verrou du journal empoisonné");
+                }
+                let pending: Vec<ChangeRecord> = log.since(next)?.cloned().collect();
+                (pending, log.last_sequence())
+            };
+            let mut frames = Vec::new();
+            for record in &pending {
+                write_frame(&mut frames, record, head);
+            }
+            stream.write_all(&frames)?;
+            next = head + 1;
+            self.lock().replicas[slot] = Some(head);
+        })();
+        self.lock().replicas[slot] = None;
+        result
+    }
+
+    fn record(&self, op: ChangeOp) -> Result<u64> {
+        let mut log = self.lock();
+        if log.closed {
+            return Err(Error::ShutDown);
+        }
+        let mut db = self.db.write();
+        self.db.quarantine.check(&db, op.collection())?;
+        apply_op(&mut db, &op, false)?;
+        drop(db);
+        self.append(&mut log, op)
+    }
+
+    /// Inscrit dans le journal `op`, déjà appliquée à la base, et réveille les lecteurs
+    /// qui l'attendent.
+    fn append(&self, log: &mut PrimaryLog, op: ChangeOp) -> Result<u64> {
+        let sequence = log.last_sequence() + 1;
+        let record = ChangeRecord { sequence, op };
+        #[cfg(feature = "encryption")]
+        let file_key = log.file_key;
+        if let Some(file) = log.file.as_mut() {
+            let mut frame = Vec::new();
+            write_frame(&mut frame, &record, sequence);
+            #[cfg(feature = "encryption")]
+            if let Some(key) = &file_key {
+                frame = encryption::seal_frame(key, &frame)?;
+            }
+            if let Err(error) = file.write_all(&frame).and_then(|_| file.flush()) {
+                // La trame a pu être écrite en partie : la relecture s'arrête à cette trame
+                // tronquée, et rien ne doit plus s'écrire après elle. Le tampon est jeté
+                // pour ne pas la recopier à la fermeture.
+                if let Some(file) = log.file.take() {
+                    drop(file.into_parts());
+                }
+                log.closed = true;
+                self.inner.appended.notify_all();
+                return Err(error.into());
+            }
+        }
+        log.records.push_back(record);
+        log.written.push_back(Instant::now());
+        log.evict();
+        self.inner.appended.notify_all();
+        Ok(sequence)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PrimaryLog> {
+        self.inner.log.lock().expect("verrou du journal empoisonné")
+    }
+}
+
+/// Base répliquée à partir du journal d'un [`Primary`].
+///
+/// Les clones partagent la même base et la même position dans le journal.
+#[derive(Clone)]
+pub struct Replica {
+    db: BaseDeDonneesPartagee,
+    status: Arc<Mutex<ReplicationStatus>>,
+}
+
+impl Replica {
+    /// Crée un réplica qui applique les enregistrements à `db`, à partir du numéro 1.
+    pub fn new(db: BaseDeDonneesPartagee) -> Self {
+        Replica {
+            db,
+            status: Arc::default(),
+        }
+    }
+
+    /// Retourne la base répliquée.
+    pub fn db(&self) -> &BaseDeDonneesPartagee {
+        &self.db
+    }
+
+    /// Retourne le dernier numéro appliqué et le dernier numéro connu du primaire.
+    pub fn replication_status(&self) -> ReplicationStatus {
+        *self.status.lock().expect("verrou du réplica empoisonné")
+    }
+
+    /// Applique un enregistrement.
+    ///
+    /// Les enregistrements déjà appliqués sont ignorés, ce qui rend le rejeu d'un journal
+    /// idempotent. Une collection absente est créée plutôt que de faire échouer le rejeu.
+    ///
+    /// # Arguments
+    /// * `record` - Enregistrement à appliquer.
+    /// * `head` - Dernier numéro écrit par le primaire, pour le calcul du retard.
+    ///
+    /// # Erreurs
+    /// * `Error::ReplicationGap` - Si des enregistrements précédents n'ont pas été appliqués.
+    /// * Les erreurs de [`Collection::upsert`].
+    pub fn apply(&self, record: &ChangeRecord, head: u64) -> Result<()> {
+        let mut status = self.status.lock().expect("verrou du réplica empoisonné");
+        status.last_sequence = status.last_sequence.max(head).max(record.sequence);
+        if record.sequence <= status.applied_sequence {
+            return Ok(());
+        }
+        if record.sequence != status.applied_sequence + 1 {
+            return Err(Error::ReplicationGap {
+                expected: status.applied_sequence + 1,
+                got: record.sequence,
+            });
+        }
+        apply_op(&mut self.db.write(), &record.op, true)?;
+        status.applied_sequence = record.sequence;
+        Ok(())
+    }
+
+    /// Applique les enregistrements d'un fichier de journal écrit par [`Primary::with_log_file`],
+    /// lu par l'accès aux fichiers de la base du réplica.
+    ///
+    /// # Retourne
+    /// * Result<u64> - Dernier numéro appliqué.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    /// * `Error::InvalidSnapshot` - Si une trame est invalide. Une dernière trame tronquée,
+    ///   laissée par une écriture interrompue, est ignorée.
+    pub fn consume_file(&self, path: impl AsRef<Path>) -> Result<u64> {
+        let mut reader = BufReader::new(self.db.read().storage().open(path.as_ref())?);
+        while let Some((record, head)) = read_frame(&mut reader)? {
+            self.apply(&record, head)?;
+        }
+        Ok(self.replication_status().applied_sequence)
+    }
+
+    /// Applique les enregistrements d'un fichier de journal écrit par
+    /// [`Primary::with_encrypted_log_file`] (fonctionnalité `encryption`).
+    ///
+    /// # Retourne
+    /// * Result<u64> - Dernier numéro appliqué.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    /// * `Error::AuthenticationFailed` - Si la clé est incorrecte ou si une trame a été
+    ///   modifiée ; les enregistrements précédents restent appliqués.
+    /// * `Error::InvalidSnapshot` - Si une trame déchiffrée est invalide. Une dernière
+    ///   trame tronquée est ignorée.
+    #[cfg(feature = "encryption")]
+    pub fn consume_encrypted_file(&self, path: impl AsRef<Path>, key: &[u8; 32]) -> Result<u64> {
+        let mut reader = BufReader::new(self.db.read().storage().open(path.as_ref())?);
+        while let Some(sealed) = read_block(&mut reader)? {
+            let frame = encryption::open_frame(key, sealed)?;
+            let Some((record, head)) = read_frame(&mut frame.as_slice())? else {
+                return Err(snapshot::invalid("trame de journal chiffrée tronquée"));
+            };
+            self.apply(&record, head)?;
+        }
+        Ok(self.replication_status().applied_sequence)
+    }
+
+    /// Se connecte au primaire et applique ses enregistrements jusqu'à la déconnexion.
+    ///
+    /// Le réplica demande les enregistrements qui suivent le dernier appliqué : appeler
+    /// de nouveau cette méthode après une déconnexion reprend donc là où elle s'était arrêtée.
+    ///
+    /// # Arguments
+    /// * `addr` - Adresse sur laquelle le primaire appelle [`Primary::serve`].
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si la connexion échoue ou est interrompue au milieu d'une trame.
+    pub fn follow(&self, addr: impl ToSocketAddrs) -> Result<()> {
+        let mut stream = TcpStream::connect(addr)?;
+        let start = self.replication_status().applied_sequence + 1;
+        stream.write_all(&start.to_le_bytes())?;
+        let mut reader = BufReader::new(stream);
+        while let Some((record, head)) = read_frame(&mut reader)? {
+            self.apply(&record, head)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applique une opération à une base.
+///
+/// Avec `create_missing`, une collection absente est créée au lieu de retourner
+/// `Error::CollectionNotFound`.
+fn apply_op(db: &mut BaseDeDonnees, op: &ChangeOp, create_missing: bool) -> Result<()> {
+    match op {
+        ChangeOp::CreateCollection {
+            collection: nom,
+            config,
+        } => {
+            db.add_with_config(nom.clone(), config.clone());
+        }
+        ChangeOp::Upsert {
+            collection: nom,
+            key,
+            vector,
+            payload,
+        } => {
+            let collection = target(db, nom, create_missing)?;
+            match payload {
+                Some(payload) => {
+                    collection.upsert_with_payload(*key, vector.clone(), payload.clone())?
+                }
+                None => collection.upsert(*key, vector.clone())?,
+            }
+        }
+        ChangeOp::Delete {
+            collection: nom,
+            key,
+        } => target(db, nom, create_missing)?.delete(key),
+        ChangeOp::Apply {
+            collection: nom,
+            ops,
+        } => {
+            if create_missing {
+                db.add(nom.clone());
+            }
+            db.get_mut(nom)
+                .ok_or_else(|| Error::CollectionNotFound(nom.clone()))?
+                .apply(ops.clone())?;
+        }
+    }
+    Ok(())
+}
+
+/// Retourne la collection visée par une opération, en la créant si `create_missing`.
+fn target<'a>(
+    db: &'a mut BaseDeDonnees,
+    nom: &str,
+    create_missing: bool,
+) -> Result<&'a mut dyn VectorStoreMut> {
+    if create_missing {
+        db.add(nom.to_string());
+    }
+    db.store_mut(nom)
+        .ok_or_else(|| Error::CollectionNotFound(nom.to_string()))
+}
+
+fn write_frame(out: &mut Vec<u8>, record: &ChangeRecord, head: u64) {
+    let mut body = Vec::new();
+    snapshot::put_u64(&mut body, record.sequence);
+    snapshot::put_u64(&mut body, head);
+    match &record.op {
+        ChangeOp::CreateCollection { collection, config } => {
+            body.push(3);
+            snapshot::put_str(&mut body, collection);
+            snapshot::put_u32(&mut body, snapshot::VERSION);
+            snapshot::write_config(&mut body, config);
+        }
+        ChangeOp::Upsert {
+            collection,
+            key,
+            vector,
+            payload,
+        } => {
+            body.push(1);
+            snapshot::put_str(&mut body, collection);
+            body.extend_from_slice(key.as_bytes());
+            snapshot::put_len(&mut body, vector.len());
+            snapshot::put_f32s(&mut body, vector);
+            write_payload(&mut body, payload.as_ref());
+        }
+        ChangeOp::Delete { collection, key } => {
+            body.push(2);
+            snapshot::put_str(&mut body, collection);
+            body.extend_from_slice(key.as_bytes());
+        }
+        ChangeOp::Apply { collection, ops } => {
+            body.push(4);
+            snapshot::put_str(&mut body, collection);
+            snapshot::put_len(&mut body, ops.len());
+            for op in ops {
+                write_op(&mut body, op);
+            }
+        }
+    }
+    snapshot::put_u32(out, body.len() as u32);
+    out.extend_from_slice(&body);
+}
+
+/// Lit la trame suivante, ou `None` à la fin du flux ou sur une trame tronquée.
+fn read_frame(input: &mut impl Read) -> Result<Option<(ChangeRecord, u64)>> {
+    let Some(body) = read_block(input)? else {
+        return Ok(None);
+    };
+    parse_frame(&body).map(Some)
+}
+
+/// Lit un bloc précédé de sa longueur, ou `None` à la fin du flux ou sur un bloc tronqué.
+fn read_block(input: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(snapshot::invalid("trame de journal trop grande"));
+    }
+    let mut body = vec![0; len as usize];
+    match input.read_exact(&mut body) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Some(body))
+}
+
+fn parse_frame(body: &[u8]) -> Result<(ChangeRecord, u64)> {
+    let mut reader = Reader::new(body);
+    let sequence = reader.u64()?;
+    let head = reader.u64()?;
+    let op = match reader.u8()? {
+        0 => ChangeOp::CreateCollection {
+            collection: reader.string()?,
+            config: CollectionConfig::default(),
+        },
+        3 => {
+            let collection = reader.string()?;
+            let version = reader.u32()?;
+            ChangeOp::CreateCollection {
+                collection,
+                config: snapshot::read_config(&mut reader, version)?,
+            }
+        }
+        1 => {
+            let collection = reader.string()?;
+            let key = reader.uuid()?;
+            let dimension = reader.len(4)?;
+            let vector = reader.f32s(dimension)?;
+            let payload = read_payload(&mut reader)?;
+            ChangeOp::Upsert {
+                collection,
+                key,
+                vector,
+                payload,
+            }
+        }
+        2 => ChangeOp::Delete {
+            collection: reader.string()?,
+            key: reader.uuid()?,
+        },
+        4 => {
+            let collection = reader.string()?;
+            let count = reader.len(17)?;
+            let ops = (0..count)
+                .map(|_| read_op(&mut reader))
+                .collect::<Result<_>>()?;
+            ChangeOp::Apply { collection, ops }
+        }
+        tag => {
+            return Err(snapshot::invalid(format!(
+                "opération de journal inconnue ({})",
+                tag
+            )))
+        }
+    };
+    if !reader.is_at_end() {
+        return Err(snapshot::invalid(
+            "octets inattendus dans une trame de journal",
+        ));
+    }
+    Ok((ChangeRecord { sequence, op }, head))
+}
+
+fn write_payload(out: &mut Vec<u8>, payload: Option<&Payload>) {
+    match payload {
+        None => out.push(0),
+        Some(payload) => {
+            out.push(1);
+            snapshot::write_object(out, payload);
+        }
+    }
+}
+
+fn read_payload(reader: &mut Reader) -> Result<Option<Payload>> {
+    match reader.u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(snapshot::read_object(reader, 0)?)),
+        code => Err(snapshot::invalid(format!(
+            "marqueur de charge utile invalide ({})",
+            code
+        ))),
+    }
+}
+
+/// Écrit une opération d'un lot : `type: u8 | Uuid`, suivis du vecteur et de la charge
+/// utile optionnelle d'une insertion, ou de la charge utile d'un remplacement.
+fn write_op(out: &mut Vec<u8>, op: &Op) {
+    match op {
+        Op::Upsert {
+            key,
+            vector,
+            payload,
+        } => {
+            out.push(1);
+            out.extend_from_slice(key.as_bytes());
+            snapshot::put_len(out, vector.len());
+            snapshot::put_f32s(out, vector);
+            write_payload(out, payload.as_ref());
+        }
+        Op::Delete { key } => {
+            out.push(2);
+            out.extend_from_slice(key.as_bytes());
+        }
+        Op::SetPayload { key, payload } => {
+            out.push(3);
+            out.extend_from_slice(key.as_bytes());
+            snapshot::write_object(out, payload);
+        }
+    }
+}
+
+fn read_op(reader: &mut Reader) -> Result<Op> {
+    let tag = reader.u8()?;
+    let key = reader.uuid()?;
+    match tag {
+        1 => {
+            let dimension = reader.len(4)?;
+            Ok(Op::Upsert {
+                key,
+                vector: reader.f32s(dimension)?,
+                payload: read_payload(reader)?,
+            })
+        }
+        2 => Ok(Op::Delete { key }),
+        3 => Ok(Op::SetPayload {
+            key,
+            payload: snapshot::read_object(reader, 0)?,
+        }),
+        tag => Err(snapshot::invalid(format!(
+            "opération de lot inconnue ({})",
+            tag
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_upsert_leaves_log_and_sequence_alone() {
+        let primary = Primary::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+        primary.create_collection("docs").unwrap();
+        let key = Uuid::new_v4();
+        let (outcome, inserted) = primary
+            .upsert_if_changed("docs", key, vec![1.0, 2.0], 0.0)
+            .unwrap();
+        assert_eq!((outcome, inserted), (UpsertOutcome::Inserted, Some(2)));
+        let sequence = primary.db().read().sequence();
+
+        for _ in 0..3 {
+            assert_eq!(
+                primary.upsert_if_changed("docs", key, vec![1.0, 2.0], 0.0).unwrap(),
+                (UpsertOutcome::Unchanged, None)
+            );
+        }
+        let (records, next) = primary.changes_since(1, 100).unwrap();
+        assert_eq!((records.len(), next), (2, 3));
+        assert_eq!(primary.db().read().sequence(), sequence);
+
+        let (outcome, updated) = primary
+            .upsert_if_changed("docs", key, vec![1.0, 3.0], 0.0)
+            .unwrap();
+        assert_eq!((outcome, updated), (UpsertOutcome::Updated, Some(3)));
+        assert!(primary.db().read().sequence() > sequence);
+    }
+
+    #[test]
+    fn refused_upsert_is_not_recorded() {
+        let primary = Primary::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+        assert!(matches!(
+            primary.upsert_if_changed("absente", Uuid::new_v4(), vec![1.0], 0.0),
+            Err(Error::CollectionNotFound(_))
+        ));
+        primary.create_collection("docs").unwrap();
+        assert!(primary
+            .upsert_if_changed("docs", Uuid::new_v4(), vec![1.0], -1.0)
+            .is_err());
+        assert_eq!(primary.changes_since(1, 100).unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn changes_are_tailed_page_by_page() {
+        let primary = Primary::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+        primary.create_collection("docs").unwrap();
+        let keys: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            primary.upsert("docs", *key, vec![1.0, i as f32]).unwrap();
+        }
+        primary.delete("docs", keys[0]).unwrap();
+
+        let mut cursor = 1;
+        let mut seen = Vec::new();
+        loop {
+            let (records, next) = primary.changes_since(cursor, 2).unwrap();
+            if records.is_empty() {
+                assert_eq!(next, cursor);
+                break;
+            }
+            assert!(records.len() <= 2);
+            seen.extend(records);
+            cursor = next;
+        }
+        let sequences: Vec<u64> = seen.iter().map(|record| record.sequence).collect();
+        assert_eq!(sequences, (1..=7).collect::<Vec<_>>());
+        assert!(matches!(&seen[1].op, ChangeOp::Upsert { key, vector, .. }
+            if *key == keys[0] && vector[..] == [1.0, 0.0]));
+        assert!(matches!(&seen[6].op, ChangeOp::Delete { collection, key }
+            if collection == "docs" && *key == keys[0]));
+        // Un nouvel enregistrement apparaît au curseur suivant.
+        primary.upsert("docs", Uuid::new_v4(), vec![2.0, 2.0]).unwrap();
+        let (records, next) = primary.changes_since(cursor, 10).unwrap();
+        assert_eq!((records.len(), records[0].sequence, next), (1, 8, 9));
+    }
+
+    #[test]
+    fn evicted_cursors_ask_for_a_resync() {
+        let retention = ChangeRetention {
+            max_records: Some(3),
+            max_age: None,
+        };
+        let primary = Primary::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()))
+            .with_retention(retention);
+        primary.create_collection("docs").unwrap();
+        for i in 0..5 {
+            primary.upsert("docs", Uuid::new_v4(), vec![1.0, i as f32]).unwrap();
+        }
+        assert!(matches!(
+            primary.changes_since(1, 10),
+            Err(Error::CursorTooOld { requested: 1, oldest: 4 })
+        ));
+        let (records, next) = primary.changes_since(4, 10).unwrap();
+        assert_eq!((records.len(), next), (3, 7));
+
+        // Le consommateur repart d'une sauvegarde, puis suit le journal à partir du
+        // numéro qui la suit.
+        let snapshot = BaseDeDonnees::from_bytes(&primary.db().read().to_bytes()).unwrap();
+        assert_eq!(snapshot.get("docs").unwrap().len(), 5);
+        assert!(primary.changes_since(7, 10).unwrap().0.is_empty());
+
+        let aged = Primary::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new())).with_retention(
+            ChangeRetention {
+                max_records: None,
+                max_age: Some(Duration::from_millis(20)),
+            },
+        );
+        aged.create_collection("docs").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(matches!(
+            aged.changes_since(1, 10),
+            Err(Error::CursorTooOld { requested: 1, oldest: 2 })
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+//! Identifiant opaque d'une requête, pour relier une recherche aux journaux de
+//! l'appelant.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// Longueur maximale, en octets, d'un identifiant de requête.
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Contexte d'une recherche, fourni par l'appelant dans
+/// [`SearchParams::context`](crate::SearchParams::context) : un identifiant opaque, par
+/// exemple celui qu'une passerelle a reçu dans un en-tête `X-Request-Id`, ou généré.
+///
+/// Il ne change pas la recherche. Il est renvoyé dans
+/// [`SearchResults::context`](crate::SearchResults::context) et joint aux erreurs des
+/// recherches d'une base ([`ErrorContext::request_id`](crate::ErrorContext::request_id)).
+///
+/// `RequestContext::new("gw-7f3a9c")?` ou `RequestContext::generate()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestContext {
+    request_id: String,
+}
+
+impl RequestContext {
+    /// Crée un contexte portant l'identifiant `request_id`.
+    ///
+    /// # Arguments
+    /// * `request_id` - Identifiant choisi par l'appelant.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si l'identifiant est vide, dépasse
+    ///   [`MAX_REQUEST_ID_LEN`] octets ou contient autre chose que des caractères ASCII
+    ///   visibles, qu'un en-tête HTTP ou une ligne de journal transporteraient mal.
+    pub fn new(request_id: impl Into<String>) -> Result<Self> {
+        let request_id = request_id.into();
+        if request_id.is_empty()
+            || request_id.len() > MAX_REQUEST_ID_LEN
+            || !request_id.bytes().all(|byte| byte.is_ascii_graphic())
+        {
+            return Err(Error::InvalidConfig(format!(
+                "identifiant de requête {:?} invalide : 1 à {} caractères ASCII visibles attendus",
+                request_id, MAX_REQUEST_ID_LEN
+            )));
+        }
+        Ok(RequestContext { request_id })
+    }
+
+    /// Crée un contexte portant un identifiant aléatoire, un `Uuid` v4 de 32 chiffres
+    /// hexadécimaux, pour une requête arrivée sans identifiant.
+    pub fn generate() -> Self {
+        RequestContext {
+            request_id: Uuid::new_v4().simple().to_string(),
+        }
+    }
+
+    /// Retourne l'identifiant de la requête.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
+impl fmt::Display for RequestContext {
+    /// Affiche l'identifiant de la requête.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.request_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::search::SearchParams;
+
+    #[test]
+    fn identifiers_are_validated() {
+        assert_eq!(RequestContext::new("gw-7f3a9c").unwrap().request_id(), "gw-7f3a9c");
+        let longest = "a".repeat(MAX_REQUEST_ID_LEN);
+        assert!(RequestContext::new(longest.clone()).is_ok());
+        for id in [String::new(), longest + "a", "a b".to_string(), "é".to_string(), "a\n".to_string()] {
+            assert!(matches!(RequestContext::new(id), Err(Error::InvalidConfig(_))));
+        }
+        let generated = RequestContext::generate();
+        assert_eq!(generated.request_id().len(), 32);
+        assert!(generated.request_id().bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(generated, RequestContext::generate());
+    }
+
+    #[test]
+    fn contexts_are_echoed_in_results_and_errors() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        db.get_mut("docs").unwrap().upsert(Uuid::new_v4(), [1.0, 0.0]).unwrap();
+        let context = RequestContext::new("gw-42").unwrap();
+        let params = SearchParams {
+            k: 1,
+            context: Some(context.clone()),
+            ..Default::default()
+        };
+        let results = db.search_with("docs", [1.0, 0.0], &params).unwrap();
+        assert_eq!(results.context, Some(context));
+        assert!(results.to_string().contains("(requête gw-42)"));
+        let plain = db.search_with("docs", [1.0, 0.0], &SearchParams::default()).unwrap();
+        assert_eq!(plain.context, None);
+        assert_eq!(plain.hits, results.hits);
+
+        let error = db.search_with("docs", [1.0, 0.0, 0.0], &params).unwrap_err();
+        assert_eq!(error.context().unwrap().request_id.as_deref(), Some("gw-42"));
+        assert!(error.to_string().contains("requête gw-42"));
+        let error = db.search_with("docs", [1.0, 0.0, 0.0], &SearchParams::default()).unwrap_err();
+        assert_eq!(error.context().unwrap().request_id, None);
+    }
+}
@@ -0,0 +1,189 @@
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::payload::Payload;
+use crate::poison;
+use crate::search::{LowConfidence, Margin, ScoreMode, SearchParams, SearchResults};
+
+/// Recalcule le score des meilleurs candidats d'une recherche, par exemple pour
+/// favoriser les documents récents ou certaines sources
+/// ([`Collection::search_rescored`]).
+///
+/// Toute fermeture `Fn(Uuid, f32, &[f32], Option<&Payload>) -> f32` l'implémente.
+pub trait Rescorer {
+    /// Retourne le nouveau score d'un candidat ; les plus grands scores sont classés en
+    /// premier, quelle que soit la mesure de la collection.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du document.
+    /// * `score` - Score brut de la mesure.
+    /// * `vector` - Vecteur stocké du document, emprunté sans copie.
+    /// * `payload` - Charge utile du document, s'il en possède une.
+    fn rescore(&self, key: Uuid, score: f32, vector: &[f32], payload: Option<&Payload>) -> f32;
+}
+
+impl<F> Rescorer for F
+where
+    F: Fn(Uuid, f32, &[f32], Option<&Payload>) -> f32,
+{
+    fn rescore(&self, key: Uuid, score: f32, vector: &[f32], payload: Option<&Payload>) -> f32 {
+        self(key, score, vector, payload)
+    }
+}
+
+impl Collection {
+    /// Recherche selon `params`, puis reclasse les `window` meilleurs candidats selon
+    /// `rescorer` avant de ne garder que les `params.k` premiers.
+    ///
+    /// Les scores retournés sont ceux de `rescorer`, dont le sens est défini par
+    /// l'appelant : ils sont classés du plus grand au plus petit, les égalités par `Uuid`
+    /// croissant, et un score NaN est remplacé par `-inf`. `params.score_threshold`
+    /// s'applique au score brut, avant le reclassement ; `ScoreMode::Rank` remplace les
+    /// nouveaux scores par les rangs, les autres modes les laissent tels quels.
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `params` - Paramètres de la recherche.
+    /// * `window` - Nombre de candidats reclassés, porté à `params.k` s'il est inférieur.
+    /// * `rescorer` - Calcul du nouveau score.
+    ///
+    /// # Retourne
+    /// * Result<SearchResults> - Documents retenus avec leur nouveau score ; `available`
+    ///   compte les candidats avant la fenêtre.
+    ///
+    /// # Erreurs
+    /// * `Error::Panicked` - Si `rescorer` panique ; la collection n'est pas affectée.
+    /// * Celles de [`Collection::search_with`].
+    pub fn search_rescored(
+        &self,
+        request: impl AsRef<[f32]>,
+        params: &SearchParams,
+        window: usize,
+        rescorer: &dyn Rescorer,
+    ) -> Result<SearchResults> {
+        params.check()?;
+        let candidates = SearchParams {
+            k: window.max(params.k),
+            score_mode: ScoreMode::Raw,
+            require_exact_k: false,
+            payload_selector: None,
+            with_vector: false,
+            ..params.clone()
+        };
+        let mut results = self.search_query(request.as_ref(), &candidates)?;
+        if params.require_exact_k && results.available < params.k {
+            return Err(Error::InsufficientCandidates {
+                requested: params.k,
+                available: results.available,
+            });
+        }
+        for (key, score) in &mut results.hits {
+            let vector = self.documents.get(key).map_or(&[][..], |v| v.as_slice());
+            let payload = self.payloads.get(key);
+            let rescored = poison::catch(|| rescorer.rescore(*key, *score, vector, payload))?;
+            *score = if rescored.is_nan() {
+                f32::NEG_INFINITY
+            } else {
+                rescored
+            };
+        }
+        results
+            .hits
+            .sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        results.hits.truncate(params.k);
+        results.margin = Margin::of(true, &results.hits);
+        results.low_confidence = LowConfidence::check(results.margin, params.min_margin);
+        if params.score_mode == ScoreMode::Rank {
+            ScoreMode::Rank.apply(
+                self.config.metric,
+                &mut results.hits,
+                params.deterministic_math,
+            );
+        }
+        results.requested_k = params.k;
+        self.record_access(&results.hits);
+        results.attach(
+            params,
+            |key| self.payloads.get(key),
+            |key| self.documents.get(key).cloned(),
+        );
+        self.inflate_results(&mut results)?;
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Value;
+    use crate::similarity::Metric;
+
+    /// Dix documents dont le document `i` obtient le score brut `i` pour `[1.0, 0.0]`.
+    fn collection() -> (Collection, Vec<Uuid>) {
+        let mut collection = Collection::new().with_metric(Metric::Dot);
+        let keys: Vec<Uuid> = (0..10).map(Uuid::from_u128).collect();
+        for (i, key) in keys.iter().enumerate() {
+            let mut payload = Payload::new();
+            payload.insert("poids".to_string(), Value::Number(i as f64));
+            collection
+                .upsert_with_payload(*key, [i as f32, 1.0], payload)
+                .unwrap();
+        }
+        (collection, keys)
+    }
+
+    #[test]
+    fn a_hook_can_invert_the_ordering_within_the_window() {
+        let (collection, keys) = collection();
+        let invert = |_: Uuid, score: f32, _: &[f32], _: Option<&Payload>| -score;
+        let results = collection
+            .search_rescored([1.0, 0.0], &SearchParams::new(3), 6, &invert)
+            .unwrap();
+        // Les six meilleurs candidats bruts (9 à 4), dont les plus faibles sont gardés.
+        assert_eq!(results.hits, [(keys[4], -4.0), (keys[5], -5.0), (keys[6], -6.0)]);
+        assert_eq!(results.requested_k, 3);
+        assert_eq!(results.available, 10);
+
+        // Une fenêtre plus petite que k est portée à k.
+        let narrow = collection
+            .search_rescored([1.0, 0.0], &SearchParams::new(3), 1, &invert)
+            .unwrap();
+        assert_eq!(narrow.hits[0].0, keys[7]);
+    }
+
+    #[test]
+    fn the_hook_sees_the_stored_vector_and_payload() {
+        let (collection, keys) = collection();
+        let weighted = |key: Uuid, score: f32, vector: &[f32], payload: Option<&Payload>| {
+            assert_eq!(vector[0], key.as_u128() as f32);
+            match payload.and_then(|payload| payload.get("poids")) {
+                Some(Value::Number(weight)) => score - 2.0 * *weight as f32,
+                _ => f32::NAN,
+            }
+        };
+        let results = collection
+            .search_rescored([1.0, 0.0], &SearchParams::new(2), 10, &weighted)
+            .unwrap();
+        assert_eq!(results.hits, [(keys[0], 0.0), (keys[1], -1.0)]);
+
+        let nan = |_: Uuid, _: f32, _: &[f32], _: Option<&Payload>| f32::NAN;
+        let results = collection
+            .search_rescored([1.0, 0.0], &SearchParams::new(2), 10, &nan)
+            .unwrap();
+        assert_eq!(results.hits, [(keys[0], f32::NEG_INFINITY), (keys[1], f32::NEG_INFINITY)]);
+    }
+
+    #[test]
+    fn a_panicking_hook_is_reported() {
+        let (collection, _) = collection();
+        let panicking = |_: Uuid, _: f32, _: &[f32], _: Option<&Payload>| -> f32 {
+            panic!("reclassement défaillant")
+        };
+        assert!(matches!(
+            collection.search_rescored([1.0, 0.0], &SearchParams::new(2), 5, &panicking),
+            Err(Error::Panicked(_))
+        ));
+        assert_eq!(collection.len(), 10);
+    }
+}
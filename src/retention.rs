@@ -0,0 +1,346 @@
+//! Vues figées de la base conservées sous une étiquette, pour interroger un état passé.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::collection::{Collection, Document};
+use crate::database::BaseDeDonnees;
+use crate::database_snapshot::DatabaseSnapshot;
+use crate::error::{Error, Result};
+use crate::memory;
+use crate::search::{SearchParams, SearchResults};
+use crate::sharded::ShardedCollection;
+
+/// Nombre de vues conservées par défaut par une base.
+pub const DEFAULT_MAX_RETAINED_SNAPSHOTS: usize = 8;
+
+/// Vue conservée par [`BaseDeDonnees::retain_snapshot`], décrite par
+/// [`BaseDeDonnees::list_snapshots`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetainedSnapshot {
+    /// Étiquette de la vue.
+    pub label: String,
+    /// Numéro de séquence de la base au moment de la capture.
+    pub sequence: u64,
+    /// Moment de la capture.
+    pub created: SystemTime,
+    /// Octets que la vue partage avec la base ou avec d'autres vues conservées.
+    pub shared_bytes: usize,
+    /// Octets que seule cette vue garde en mémoire : ceux que libérerait
+    /// [`BaseDeDonnees::drop_snapshot`].
+    pub exclusive_bytes: usize,
+}
+
+/// Vues conservées d'une base, de la plus ancienne à la plus récente.
+pub(crate) struct Retention {
+    snapshots: Vec<(String, SystemTime, DatabaseSnapshot)>,
+    max: usize,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            snapshots: Vec::new(),
+            max: DEFAULT_MAX_RETAINED_SNAPSHOTS,
+        }
+    }
+}
+
+impl Retention {
+    /// Oublie les vues les plus anciennes au-delà du maximum et retourne leurs étiquettes.
+    fn trim(&mut self) -> Vec<String> {
+        let excess = self.snapshots.len().saturating_sub(self.max);
+        self.snapshots
+            .drain(..excess)
+            .map(|(label, _, _)| label)
+            .collect()
+    }
+}
+
+impl BaseDeDonnees {
+    /// Conserve une vue figée de toute la base ([`BaseDeDonnees::snapshot`]) sous
+    /// l'étiquette `label`, pour l'interroger plus tard avec [`BaseDeDonnees::search_at`].
+    ///
+    /// La capture ne copie rien. Ensuite, une collection modifiée dans la base est copiée
+    /// avant sa première modification, mais ses vecteurs restent partagés avec la vue
+    /// jusqu'à ce qu'ils soient remplacés ou supprimés : le coût d'une vue croît avec
+    /// les modifications faites depuis (voir [`BaseDeDonnees::list_snapshots`]). Une
+    /// vue portant déjà cette étiquette est remplacée. Au-delà du maximum
+    /// ([`BaseDeDonnees::set_max_snapshots`]), la vue la plus ancienne est oubliée.
+    ///
+    /// Les vues ne sont pas sauvegardées avec la base et ne comptent pas dans sa limite
+    /// de mémoire.
+    ///
+    /// # Arguments
+    /// * `label` - Étiquette de la vue.
+    ///
+    /// # Retourne
+    /// * Option<String> - L'étiquette de la vue oubliée pour faire de la place, s'il y en
+    ///   a une.
+    pub fn retain_snapshot(&mut self, label: impl Into<String>) -> Option<String> {
+        let label = label.into();
+        self.drop_snapshot(&label);
+        let snapshot = self.snapshot();
+        self.retention
+            .snapshots
+            .push((label, SystemTime::now(), snapshot));
+        self.retention.trim().pop()
+    }
+
+    /// Oublie la vue conservée sous l'étiquette `label`.
+    ///
+    /// # Retourne
+    /// * bool - Vrai si une vue portait cette étiquette.
+    pub fn drop_snapshot(&mut self, label: &str) -> bool {
+        let before = self.retention.snapshots.len();
+        self.retention
+            .snapshots
+            .retain(|(retained, _, _)| retained != label);
+        self.retention.snapshots.len() != before
+    }
+
+    /// Fixe le nombre maximal de vues conservées, par défaut
+    /// [`DEFAULT_MAX_RETAINED_SNAPSHOTS`] ; les plus anciennes au-delà sont oubliées.
+    ///
+    /// # Arguments
+    /// * `max` - Nombre maximal de vues ; 0 n'en conserve aucune.
+    ///
+    /// # Retourne
+    /// * Vec<String> - Étiquettes des vues oubliées, de la plus ancienne à la plus récente.
+    pub fn set_max_snapshots(&mut self, max: usize) -> Vec<String> {
+        self.retention.max = max;
+        self.retention.trim()
+    }
+
+    /// Retourne le nombre maximal de vues conservées.
+    pub fn max_snapshots(&self) -> usize {
+        self.retention.max
+    }
+
+    /// Retourne la vue conservée sous l'étiquette `label`, pour toutes les lectures de
+    /// [`DatabaseSnapshot`].
+    pub fn retained_snapshot(&self, label: &str) -> Option<&DatabaseSnapshot> {
+        self.retention
+            .snapshots
+            .iter()
+            .find(|(retained, _, _)| retained == label)
+            .map(|(_, _, snapshot)| snapshot)
+    }
+
+    /// Décrit les vues conservées, de la plus ancienne à la plus récente, avec la
+    /// mémoire qu'elles partagent ou gardent pour elles seules.
+    ///
+    /// Les octets sont comptés comme [`MemoryUsage`](crate::MemoryUsage). Une collection
+    /// inchangée depuis la capture est entièrement partagée ; pour une collection copiée
+    /// depuis, ses charges utiles et index sont propres à la vue, et chaque vecteur est
+    /// partagé tant que la base ou une autre vue l'utilise encore. Le calcul parcourt
+    /// les documents de toutes les vues.
+    pub fn list_snapshots(&self) -> Vec<RetainedSnapshot> {
+        let live = units(&self.collections, &self.sharded);
+        let retained: Vec<Vec<Unit>> = self
+            .retention
+            .snapshots
+            .iter()
+            .map(|(_, _, snapshot)| units(&snapshot.collections, &snapshot.sharded))
+            .collect();
+
+        // Détenteurs distincts (la base ou une vue) de chaque collection et de chaque vecteur.
+        let mut unit_holders: HashMap<usize, usize> = HashMap::new();
+        let mut vector_holders: HashMap<*const Vec<f32>, usize> = HashMap::new();
+        for holder in std::iter::once(&live).chain(&retained) {
+            let mut vectors = HashSet::new();
+            for unit in holder {
+                *unit_holders.entry(unit.id).or_default() += 1;
+                for collection in &unit.collections {
+                    vectors.extend(collection.documents.values().map(Arc::as_ptr));
+                }
+            }
+            for vector in vectors {
+                *vector_holders.entry(vector).or_default() += 1;
+            }
+        }
+
+        self.retention
+            .snapshots
+            .iter()
+            .zip(&retained)
+            .map(|((label, created, snapshot), units)| {
+                let mut info = RetainedSnapshot {
+                    label: label.clone(),
+                    sequence: snapshot.sequence,
+                    created: *created,
+                    shared_bytes: 0,
+                    exclusive_bytes: 0,
+                };
+                for unit in units {
+                    for collection in &unit.collections {
+                        let total = collection.memory_usage().total();
+                        if unit_holders[&unit.id] > 1 {
+                            info.shared_bytes += total;
+                            continue;
+                        }
+                        let shared: usize = collection
+                            .documents
+                            .iter()
+                            .filter(|(_, vector)| vector_holders[&Arc::as_ptr(vector)] > 1)
+                            .map(|(_, vector)| memory::vector_bytes(vector))
+                            .sum();
+                        info.shared_bytes += shared;
+                        info.exclusive_bytes += total.saturating_sub(shared);
+                    }
+                }
+                info
+            })
+            .collect()
+    }
+
+    /// Recherche les `k` documents les plus proches dans la collection `cname` telle
+    /// qu'elle était au moment de la vue `label`, comme [`BaseDeDonnees::search`].
+    ///
+    /// # Erreurs
+    /// * `Error::SnapshotNotFound` - Si aucune vue conservée ne porte cette étiquette.
+    /// * Celles de [`DatabaseSnapshot::search`].
+    pub fn search_at(
+        &self,
+        label: &str,
+        cname: &str,
+        request: impl AsRef<[f32]>,
+        k: usize,
+    ) -> Result<Document> {
+        self.retained(label)?.search(cname, request, k)
+    }
+
+    /// Effectue une recherche paramétrée dans la collection `cname` telle qu'elle était
+    /// au moment de la vue `label`, comme [`BaseDeDonnees::search_with`].
+    ///
+    /// # Erreurs
+    /// * `Error::SnapshotNotFound` - Si aucune vue conservée ne porte cette étiquette.
+    /// * Celles de [`DatabaseSnapshot::search_with`].
+    pub fn search_at_with(
+        &self,
+        label: &str,
+        cname: &str,
+        request: impl AsRef<[f32]>,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        self.retained(label)?.search_with(cname, request, params)
+    }
+
+    fn retained(&self, label: &str) -> Result<&DatabaseSnapshot> {
+        self.retained_snapshot(label)
+            .ok_or_else(|| Error::SnapshotNotFound(label.to_string()))
+    }
+}
+
+/// Collection simple ou partitionnée d'une base ou d'une vue, identifiée par l'adresse
+/// de son stockage partagé.
+struct Unit<'a> {
+    id: usize,
+    collections: Vec<&'a Collection>,
+}
+
+fn units<'a>(
+    collections: &'a HashMap<String, Arc<Collection>>,
+    sharded: &'a HashMap<String, Arc<ShardedCollection>>,
+) -> Vec<Unit<'a>> {
+    collections
+        .values()
+        .map(|collection| Unit {
+            id: Arc::as_ptr(collection) as usize,
+            collections: vec![&**collection],
+        })
+        .chain(sharded.values().map(|collection| Unit {
+            id: Arc::as_ptr(collection) as usize,
+            collections: collection.shards().iter().collect(),
+        }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::VectorGenerator;
+    use uuid::Uuid;
+
+    fn database(generator: &mut VectorGenerator) -> (BaseDeDonnees, Vec<Uuid>) {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let docs = db.get_mut("docs").unwrap();
+        let keys: Vec<Uuid> = (0..100).map(|_| generator.uuid()).collect();
+        for key in &keys {
+            docs.upsert(*key, generator.vector()).unwrap();
+        }
+        (db, keys)
+    }
+
+    #[test]
+    fn retained_results_survive_later_writes() {
+        let mut generator = VectorGenerator::new(6, 175);
+        let (mut db, keys) = database(&mut generator);
+        let query = generator.vector();
+        let before = db.search("docs", &query, 10).unwrap();
+        db.retain_snapshot("hier");
+
+        let docs = db.get_mut("docs").unwrap();
+        docs.upsert(generator.uuid(), query.clone()).unwrap();
+        docs.upsert(before[1].0, generator.vector()).unwrap();
+        docs.delete(&before[0].0);
+        for key in &keys[50..] {
+            docs.delete(key);
+        }
+        assert_ne!(db.search("docs", &query, 10).unwrap(), before);
+        assert_eq!(db.search_at("hier", "docs", &query, 10).unwrap(), before);
+        let params = SearchParams::new(10);
+        assert_eq!(db.search_at_with("hier", "docs", &query, &params).unwrap().hits, before);
+        assert_eq!(db.retained_snapshot("hier").unwrap().get("docs").unwrap().len(), 100);
+        assert!(matches!(
+            db.search_at("demain", "docs", &query, 10),
+            Err(Error::SnapshotNotFound(label)) if label == "demain"
+        ));
+    }
+
+    #[test]
+    fn the_oldest_snapshots_are_dropped_beyond_the_maximum() {
+        let mut generator = VectorGenerator::new(4, 1);
+        let (mut db, _) = database(&mut generator);
+        db.set_max_snapshots(2);
+        assert_eq!(db.retain_snapshot("a"), None);
+        assert_eq!(db.retain_snapshot("b"), None);
+        assert_eq!(db.retain_snapshot("c"), Some("a".to_string()));
+        // Une étiquette reprise remplace la vue et la rend la plus récente.
+        assert_eq!(db.retain_snapshot("b"), None);
+        let labels = |db: &BaseDeDonnees| -> Vec<String> {
+            db.list_snapshots().into_iter().map(|info| info.label).collect()
+        };
+        assert_eq!(labels(&db), ["c", "b"]);
+        assert_eq!(db.set_max_snapshots(1), ["c"]);
+        assert!(db.drop_snapshot("b"));
+        assert!(!db.drop_snapshot("b"));
+        assert!(labels(&db).is_empty());
+        assert_eq!(db.max_snapshots(), 1);
+    }
+
+    #[test]
+    fn snapshot_memory_is_shared_until_the_base_changes() {
+        let mut generator = VectorGenerator::new(8, 2);
+        let (mut db, keys) = database(&mut generator);
+        let total = db.get("docs").unwrap().memory_usage().total();
+        db.retain_snapshot("vue");
+        let info = &db.list_snapshots()[0];
+        assert_eq!((info.shared_bytes, info.exclusive_bytes), (total, 0));
+
+        // Les vecteurs remplacés ne sont plus gardés que par la vue.
+        let docs = db.get_mut("docs").unwrap();
+        for key in &keys[..10] {
+            docs.upsert(*key, generator.vector()).unwrap();
+        }
+        let info = &db.list_snapshots()[0];
+        assert_eq!(info.shared_bytes, 90 * memory::vector_bytes(&[0.0; 8]));
+        assert_eq!(info.shared_bytes + info.exclusive_bytes, total);
+
+        db.get_mut("docs").unwrap().clear(false);
+        let info = &db.list_snapshots()[0];
+        assert_eq!((info.shared_bytes, info.exclusive_bytes), (0, total));
+    }
+}
@@ -0,0 +1,236 @@
+//! Aiguillage des requêtes vers les collections de même dimension.
+
+use uuid::Uuid;
+
+use crate::database::{self, BaseDeDonnees};
+use crate::error::Result;
+use crate::search::{SearchOverrides, SearchParams};
+
+/// Résultat de [`BaseDeDonnees::search_auto`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedHit {
+    /// Nom de la collection qui a retourné le document.
+    pub collection: String,
+    /// Identifiant du document.
+    pub key: Uuid,
+    /// Score du document dans sa collection, selon sa mesure et son mode de score.
+    pub score: f32,
+    /// Rang du document dans les résultats de sa collection, à partir de 0.
+    pub rank: usize,
+}
+
+/// Résultats de [`BaseDeDonnees::search_auto`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RoutedResults {
+    /// Collections interrogées, celles dont la dimension est celle de la requête, dans
+    /// l'ordre alphabétique. Vide si aucune ne convient.
+    pub collections: Vec<String>,
+    /// Documents retenus, fusionnés par rang (voir [`BaseDeDonnees::search_auto`]).
+    pub hits: Vec<RoutedHit>,
+    /// Vrai si le budget de temps a interrompu le parcours de l'une des collections.
+    pub truncated: bool,
+}
+
+impl RoutedResults {
+    /// Indique si plusieurs collections avaient la dimension de la requête : les
+    /// résultats mêlent alors des documents de collections différentes.
+    pub fn is_ambiguous(&self) -> bool {
+        self.collections.len() > 1
+    }
+}
+
+impl BaseDeDonnees {
+    /// Retourne les collections, simples ou partitionnées, dont la dimension est `dim`,
+    /// dans l'ordre alphabétique.
+    ///
+    /// Une collection sans dimension, vide et sans dimension configurée, n'en fait pas
+    /// partie ; les alias non plus.
+    ///
+    /// # Arguments
+    /// * `dim` - Dimension recherchée.
+    pub fn collections_for_dimension(&self, dim: usize) -> Vec<&str> {
+        let mut noms: Vec<&str> = (self.collections.iter())
+            .filter(|(_, collection)| collection.dimension() == Some(dim))
+            .map(|(nom, _)| nom.as_str())
+            .chain(
+                (self.sharded.iter())
+                    .filter(|(_, collection)| collection.dimension() == Some(dim))
+                    .map(|(nom, _)| nom.as_str()),
+            )
+            .collect();
+        noms.sort_unstable();
+        noms
+    }
+
+    /// Recherche dans toutes les collections dont la dimension est la longueur de la
+    /// requête, en parallèle, et fusionne leurs résultats.
+    ///
+    /// Chaque collection simple est interrogée avec ses paramètres par défaut
+    /// ([`Collection::set_default_params`](crate::Collection::set_default_params)), `k`
+    /// excepté. Les scores de collections différentes, de mesures ou de modes de score
+    /// différents, ne sont pas comparables : les résultats sont fusionnés par rang, les
+    /// premiers de chaque collection d'abord, dans l'ordre alphabétique des collections,
+    /// puis les deuxièmes, et ainsi de suite. Aucune collection de cette dimension
+    /// n'est pas une erreur : les résultats sont vides, comme
+    /// [`RoutedResults::collections`].
+    ///
+    /// # Arguments
+    /// * `request` - Vecteur de requête.
+    /// * `k` - Nombre maximal de résultats, et de documents retenus par collection.
+    ///
+    /// # Retourne
+    /// * Result<RoutedResults> - Collections interrogées et documents retenus.
+    ///
+    /// # Erreurs
+    /// * Celles de [`BaseDeDonnees::search_with`], pour la première collection en
+    ///   erreur.
+    pub fn search_auto(&self, request: impl AsRef<[f32]>, k: usize) -> Result<RoutedResults> {
+        self.search_auto_prefixed("", request, k)
+    }
+
+    /// Recherche comme [`BaseDeDonnees::search_auto`], parmi les seules collections dont
+    /// le nom commence par `prefix`.
+    ///
+    /// # Erreurs
+    /// * Celles de [`BaseDeDonnees::search_auto`].
+    pub fn search_auto_prefixed(
+        &self,
+        prefix: &str,
+        request: impl AsRef<[f32]>,
+        k: usize,
+    ) -> Result<RoutedResults> {
+        let request = request.as_ref();
+        let collections: Vec<String> = (self.collections_for_dimension(request.len()))
+            .into_iter()
+            .filter(|nom| nom.starts_with(prefix))
+            .map(str::to_string)
+            .collect();
+        let overrides = SearchOverrides {
+            k: Some(k),
+            ..Default::default()
+        };
+        let lists = self.runtime.map_chunks(&collections, 2, |chunk| {
+            (chunk.iter())
+                .map(|nom| {
+                    let params = match self.get(nom) {
+                        Some(collection) => overrides.resolve(collection.default_params()),
+                        None => SearchParams::new(k),
+                    };
+                    database::search_with(self.store(nom), nom, request, &params)
+                })
+                .collect()
+        });
+        let mut routed = RoutedResults::default();
+        for (nom, list) in collections.iter().zip(lists) {
+            let results = list?;
+            routed.truncated |= results.truncated;
+            routed
+                .hits
+                .extend(
+                    (results.hits.into_iter().enumerate()).map(|(rank, (key, score))| RoutedHit {
+                        collection: nom.clone(),
+                        key,
+                        score,
+                        rank,
+                    }),
+                );
+        }
+        // Tri stable : à rang égal, les collections restent dans l'ordre alphabétique.
+        routed.hits.sort_by_key(|hit| hit.rank);
+        routed.hits.truncate(k);
+        routed.collections = collections;
+        Ok(routed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::search::ScoreMode;
+    use crate::sharded::ShardedCollection;
+    use crate::similarity::Metric;
+    use crate::store::VectorStoreMut;
+
+    /// Base de collections de dimension 2 (`en_titres`, `fr_titres`, `parts`), 3
+    /// (`images`) et sans dimension (`vide`).
+    fn database() -> BaseDeDonnees {
+        let mut bdd = BaseDeDonnees::new();
+        for (nom, offset) in [("en_titres", 0), ("fr_titres", 10)] {
+            let mut collection = Collection::new().with_metric(Metric::Dot);
+            for i in 0..3 {
+                let key = Uuid::from_u128(offset + i);
+                collection.upsert(key, [1.0, i as f32]).unwrap();
+            }
+            bdd.replace(nom.to_string(), collection);
+        }
+        let mut parts = ShardedCollection::new(2).unwrap();
+        VectorStoreMut::upsert(&mut parts, Uuid::from_u128(20), vec![1.0, 1.0]).unwrap();
+        bdd.replace_sharded("parts".to_string(), parts);
+        bdd.add("images".to_string());
+        (bdd.get_mut("images").unwrap())
+            .upsert(Uuid::from_u128(30), [1.0, 0.0, 0.0])
+            .unwrap();
+        bdd.add("vide".to_string());
+        bdd
+    }
+
+    #[test]
+    fn collections_are_found_by_dimension() {
+        let bdd = database();
+        assert_eq!(
+            bdd.collections_for_dimension(2),
+            ["en_titres", "fr_titres", "parts"]
+        );
+        assert_eq!(bdd.collections_for_dimension(3), ["images"]);
+        assert!(bdd.collections_for_dimension(4).is_empty());
+    }
+
+    #[test]
+    fn no_matching_collection_gives_empty_results() {
+        let bdd = database();
+        let results = bdd.search_auto([1.0; 5], 10).unwrap();
+        assert_eq!(results, RoutedResults::default());
+        assert!(!results.is_ambiguous());
+        let results = bdd.search_auto_prefixed("de_", [1.0, 0.0], 10).unwrap();
+        assert!(results.collections.is_empty() && results.hits.is_empty());
+
+        let results = bdd.search_auto([1.0, 0.0, 0.0], 10).unwrap();
+        assert!(!results.is_ambiguous());
+        assert_eq!(results.hits.len(), 1);
+        assert_eq!(results.hits[0].collection, "images");
+    }
+
+    #[test]
+    fn several_collections_are_merged_by_rank() {
+        let mut bdd = database();
+        (bdd.get_mut("fr_titres").unwrap()).set_default_params(SearchParams {
+            score_mode: ScoreMode::Rank,
+            ..SearchParams::default()
+        });
+        let results = bdd.search_auto([0.0, 1.0], 5).unwrap();
+        assert!(results.is_ambiguous());
+        assert_eq!(results.collections, ["en_titres", "fr_titres", "parts"]);
+        let hits: Vec<(&str, usize)> = (results.hits.iter())
+            .map(|hit| (hit.collection.as_str(), hit.rank))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                ("en_titres", 0),
+                ("fr_titres", 0),
+                ("parts", 0),
+                ("en_titres", 1),
+                ("fr_titres", 1)
+            ]
+        );
+        assert_eq!(results.hits[0].key, Uuid::from_u128(2));
+        // Les paramètres par défaut de chaque collection s'appliquent.
+        assert_eq!((results.hits[0].score, results.hits[1].score), (2.0, 1.0));
+
+        let results = bdd.search_auto_prefixed("fr_", [0.0, 1.0], 2).unwrap();
+        assert_eq!(results.collections, ["fr_titres"]);
+        let keys: Vec<Uuid> = results.hits.iter().map(|hit| hit.key).collect();
+        assert_eq!(keys, [Uuid::from_u128(12), Uuid::from_u128(11)]);
+    }
+}
@@ -0,0 +1,161 @@
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::error::{Error, Result};
+use crate::oplog::OperationKind;
+
+impl Collection {
+    /// Insère ou met à jour des documents dont les vecteurs sont les lignes d'une matrice
+    /// contiguë, rangée ligne par ligne : la ligne `i` de `data` est le vecteur de
+    /// `ids[i]`.
+    ///
+    /// Chaque ligne est copiée une seule fois, dans le `Vec<f32>` qui devient le vecteur
+    /// stocké ; seules une projection ou une normalisation de la collection en produisent
+    /// un autre. Comme pour [`Collection::upsert_batch`], l'insertion s'arrête à la
+    /// première ligne refusée, les précédentes restant insérées.
+    ///
+    /// # Arguments
+    /// * `ids` - Identifiants des documents, un par ligne.
+    /// * `data` - Coordonnées des lignes, mises bout à bout.
+    /// * `dim` - Nombre de coordonnées par ligne.
+    ///
+    /// # Retourne
+    /// * Result<usize> - Nombre de lignes stockées, sans celles ignorées comme doublons
+    ///   (`DedupPolicy::Skip`).
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `dim` est nul ou si `data` ne contient pas
+    ///   exactement `ids.len()` lignes ; rien n'est alors inséré.
+    /// * Celles de [`Collection::upsert`].
+    pub fn upsert_rows(&mut self, ids: &[Uuid], data: &[f32], dim: usize) -> Result<usize> {
+        check_rows(data, dim, Some(ids.len()))?;
+        self.logged(
+            OperationKind::Batch,
+            |collection| {
+                let mut stored = 0;
+                for (key, row) in ids.iter().zip(data.chunks_exact(dim)) {
+                    stored += usize::from(collection.upsert_vector(*key, row.to_vec())?);
+                }
+                Ok(stored)
+            },
+            |result| result.as_ref().ok().copied(),
+        )
+    }
+
+    /// Recherche les `k` documents les plus proches de chaque ligne de `queries`, rangées
+    /// comme pour [`Collection::upsert_rows`].
+    ///
+    /// Les requêtes sont lues en place ; chacune suit [`Collection::search`].
+    ///
+    /// # Arguments
+    /// * `queries` - Coordonnées des requêtes, mises bout à bout.
+    /// * `dim` - Nombre de coordonnées par requête.
+    /// * `k` - Nombre de résultats par requête.
+    ///
+    /// # Retourne
+    /// * Result<Vec<Document>> - Les résultats de chaque requête, dans l'ordre des lignes.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `dim` est nul ou ne divise pas `queries.len()`.
+    /// * Celles de [`Collection::search`], pour la première requête refusée.
+    pub fn search_rows(&self, queries: &[f32], dim: usize, k: usize) -> Result<Vec<Document>> {
+        check_rows(queries, dim, None)?;
+        queries
+            .chunks_exact(dim)
+            .map(|query| self.search(query, k))
+            .collect()
+    }
+}
+
+/// Vérifie que `data` se découpe en lignes de `dim` coordonnées, au nombre de `rows` si
+/// celui-ci est fourni.
+pub(crate) fn check_rows(data: &[f32], dim: usize, rows: Option<usize>) -> Result<()> {
+    if dim == 0 {
+        return Err(Error::InvalidConfig(
+            "la dimension des lignes doit être strictement positive".to_string(),
+        ));
+    }
+    let expected = match rows {
+        Some(rows) => rows.checked_mul(dim),
+        None => Some(data.len() - data.len() % dim),
+    };
+    if expected != Some(data.len()) {
+        return Err(Error::InvalidConfig(match rows {
+            Some(rows) => format!(
+                "{} coordonnées pour {} lignes de dimension {}",
+                data.len(),
+                rows,
+                dim
+            ),
+            None => format!(
+                "{} coordonnées ne forment pas des lignes de dimension {}",
+                data.len(),
+                dim
+            ),
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::DedupPolicy;
+
+    fn keys(n: usize) -> Vec<Uuid> {
+        (0..n).map(|i| Uuid::from_u128(i as u128 + 1)).collect()
+    }
+
+    #[test]
+    fn mismatched_lengths_and_a_null_dimension_insert_nothing() {
+        let mut collection = Collection::new();
+        for (data, dim) in [(&[1.0, 2.0, 3.0][..], 2), (&[1.0, 2.0, 3.0, 4.0][..], 1), (&[][..], 0)] {
+            assert!(matches!(
+                collection.upsert_rows(&keys(2), data, dim),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        assert!(matches!(collection.search_rows(&[1.0, 2.0, 3.0], 2, 1), Err(Error::InvalidConfig(_))));
+        assert!(matches!(collection.search_rows(&[1.0], 0, 1), Err(Error::InvalidConfig(_))));
+        assert!(collection.is_empty());
+        assert!(collection.recent_operations().is_empty());
+    }
+
+    #[test]
+    fn insertion_stops_at_the_first_rejected_row() {
+        let mut collection = Collection::new();
+        let ids = keys(3);
+        let result = collection.upsert_rows(&ids, &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0], 2);
+        assert!(matches!(result, Err(Error::ZeroVector { key: Some(key) }) if key == ids[1]));
+        assert!(collection.read(&ids[0]).is_some());
+        assert!(collection.read(&ids[1]).is_none());
+        assert!(collection.read(&ids[2]).is_none());
+    }
+
+    #[test]
+    fn the_logged_count_excludes_skipped_duplicates() {
+        let mut collection = Collection::new().with_dedup(0.99, DedupPolicy::Skip);
+        let stored = collection
+            .upsert_rows(&keys(3), &[1.0, 0.0, 1.0, 0.001, 0.0, 1.0], 2)
+            .unwrap();
+        assert_eq!(stored, 2);
+        assert_eq!(collection.len(), 2);
+        let operation = collection.recent_operations().pop().unwrap();
+        assert_eq!((operation.kind, operation.items), (OperationKind::Batch, 2));
+    }
+
+    #[test]
+    fn search_rows_matches_one_search_per_query() {
+        let mut collection = Collection::new();
+        let data: Vec<f32> = (0..12).map(|i| (i % 5) as f32 + 1.0).collect();
+        collection.upsert_rows(&keys(4), &data, 3).unwrap();
+        let queries = [1.0, 0.0, 0.0, 0.0, 1.0, 1.0];
+        let results = collection.search_rows(&queries, 3, 2).unwrap();
+        let expected: Vec<Document> = queries
+            .chunks_exact(3)
+            .map(|query| collection.search(query, 2).unwrap())
+            .collect();
+        assert_eq!(results, expected);
+        assert!(collection.search_rows(&[], 3, 2).unwrap().is_empty());
+    }
+}
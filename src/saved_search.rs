@@ -0,0 +1,289 @@
+//! Requêtes enregistrées d'une collection, confrontées aux documents nouvellement insérés.
+
+use std::collections::{BTreeMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::search::{SearchParams, SearchResults};
+
+/// Requêtes enregistrées d'une collection et documents écrits depuis la dernière
+/// confrontation automatique.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SavedSearches {
+    pub(crate) queries: BTreeMap<String, (Vec<f32>, SearchParams)>,
+    /// Vrai si les écritures sont retenues pour [`Collection::take_new_matches`].
+    pub(crate) watching: bool,
+    /// Documents insérés ou mis à jour depuis la dernière confrontation, dans l'ordre
+    /// de leur première écriture.
+    pending: Vec<Uuid>,
+}
+
+impl SavedSearches {
+    /// Retient un document écrit, si les écritures sont suivies.
+    pub(crate) fn record(&mut self, key: Uuid) {
+        if self.watching {
+            self.pending.push(key);
+        }
+    }
+
+    /// Oublie les documents écrits depuis la dernière confrontation.
+    pub(crate) fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+}
+
+impl Collection {
+    /// Enregistre la requête `vector` sous le nom `name`, avec les paramètres de sa
+    /// recherche ; une requête portant déjà ce nom est remplacée.
+    ///
+    /// Les requêtes enregistrées sont sauvegardées avec la collection. Elles servent à
+    /// relancer une recherche ([`Collection::run_saved`]) et, dans l'autre sens, à
+    /// signaler les nouveaux documents qui leur correspondent
+    /// ([`Collection::match_new_documents`]).
+    ///
+    /// # Arguments
+    /// * `name` - Nom de la requête.
+    /// * `vector` - Vecteur de la requête, exprimé comme une requête de
+    ///   [`Collection::search_with`].
+    /// * `params` - Paramètres de la recherche, dont le seuil des correspondances.
+    ///
+    /// # Erreurs
+    /// * Celles qu'une recherche retournerait pour ce vecteur et ces paramètres :
+    ///   `Error::DimensionMismatch`, `Error::ZeroVector`, `Error::InvalidConfig`... La
+    ///   requête n'est alors pas enregistrée.
+    pub fn save_query(
+        &mut self,
+        name: impl Into<String>,
+        vector: impl Into<Vec<f32>>,
+        params: SearchParams,
+    ) -> Result<()> {
+        let vector = vector.into();
+        params.check()?;
+        self.prepare_query(&vector)?;
+        self.saved.queries.insert(name.into(), (vector, params));
+        Ok(())
+    }
+
+    /// Supprime la requête enregistrée `name`.
+    ///
+    /// # Retourne
+    /// * bool - Vrai si une requête portait ce nom.
+    pub fn remove_saved_query(&mut self, name: &str) -> bool {
+        self.saved.queries.remove(name).is_some()
+    }
+
+    /// Retourne le vecteur et les paramètres de la requête enregistrée `name`.
+    pub fn saved_query(&self, name: &str) -> Option<(&[f32], &SearchParams)> {
+        self.saved
+            .queries
+            .get(name)
+            .map(|(vector, params)| (vector.as_slice(), params))
+    }
+
+    /// Retourne les noms des requêtes enregistrées, dans l'ordre alphabétique.
+    pub fn saved_queries(&self) -> Vec<&str> {
+        self.saved.queries.keys().map(String::as_str).collect()
+    }
+
+    /// Exécute la requête enregistrée `name` avec ses paramètres, comme
+    /// [`Collection::search_with`].
+    ///
+    /// # Erreurs
+    /// * `Error::SavedQueryNotFound` - Si aucune requête ne porte ce nom.
+    /// * Celles de [`Collection::search_with`].
+    pub fn run_saved(&self, name: &str) -> Result<SearchResults> {
+        let (vector, params) = self.saved_or_err(name)?;
+        self.search_with(vector, params)
+    }
+
+    /// Confronte les documents `new_ids` aux requêtes enregistrées `saved_names`, sans
+    /// parcourir le reste de la collection : c'est l'opération inverse d'une recherche,
+    /// pour savoir quelles requêtes permanentes un lot de documents satisfait.
+    ///
+    /// Un document correspond à une requête s'il respecte son espace de noms et son seuil
+    /// (`score_threshold`, sur le score brut) ; sans seuil, chaque document correspond
+    /// avec son score. `k` et le mode de score ne s'appliquent pas. Les documents absents
+    /// de la collection, par exemple supprimés depuis, sont ignorés.
+    ///
+    /// # Arguments
+    /// * `saved_names` - Noms des requêtes enregistrées à confronter.
+    /// * `new_ids` - Documents à évaluer.
+    ///
+    /// # Retourne
+    /// * Result<Vec<(String, Uuid, f32)>> - Couples requête et document retenus avec leur
+    ///   score brut, dans l'ordre de `saved_names`, puis du meilleur score au moins bon.
+    ///
+    /// # Erreurs
+    /// * `Error::SavedQueryNotFound` - Si une requête n'existe pas.
+    /// * Celles de la préparation des requêtes ([`Collection::save_query`]), si la
+    ///   collection a changé depuis leur enregistrement.
+    /// * `Error::ModelMismatch` - Si une requête désigne un autre modèle que la
+    ///   collection.
+    pub fn match_new_documents(
+        &self,
+        saved_names: &[&str],
+        new_ids: &[Uuid],
+    ) -> Result<Vec<(String, Uuid, f32)>> {
+        let metric = self.scorer().metric;
+        let mut matches = Vec::new();
+        for name in saved_names {
+            let (vector, params) = self.saved_or_err(name)?;
+            self.check_search_model(params)?;
+            let request = self.prepare_query(vector)?;
+            let masked = self.masked_weights(params, request.len())?;
+            let scorer = self.masked_scorer(masked.as_deref());
+            let mut seen = HashSet::new();
+            let mut hits: Vec<(Uuid, f32)> = new_ids
+                .iter()
+                .filter(|key| seen.insert(**key))
+                .filter(|key| match params.namespace.as_deref() {
+                    Some(namespace) => self.namespaces.get(key) == Some(namespace),
+                    None => true,
+                })
+                .filter_map(|key| {
+                    let stored = self.documents.get(key)?;
+                    (stored.len() == request.len())
+                        .then(|| (*key, scorer.score_unchecked(&request, stored)))
+                })
+                .filter(|(_, score)| {
+                    params
+                        .score_threshold
+                        .is_none_or(|threshold| metric.passes(*score, threshold))
+                })
+                .collect();
+            hits.sort_by(|a, b| metric.compare(a.1, b.1).then_with(|| a.0.cmp(&b.0)));
+            matches.extend(
+                hits.into_iter()
+                    .map(|(key, score)| (name.to_string(), key, score)),
+            );
+        }
+        Ok(matches)
+    }
+
+    /// Retient les documents insérés ou mis à jour pour les confronter ensuite à toutes
+    /// les requêtes enregistrées avec [`Collection::take_new_matches`], sans avoir à
+    /// tenir soi-même la liste des nouveaux documents. Désactiver le suivi oublie les
+    /// documents retenus.
+    ///
+    /// # Arguments
+    /// * `watch` - Vrai pour suivre les écritures.
+    pub fn watch_new_documents(&mut self, watch: bool) {
+        self.saved.watching = watch;
+        if !watch {
+            self.saved.clear_pending();
+        }
+    }
+
+    /// Indique si les écritures sont suivies pour [`Collection::take_new_matches`].
+    pub fn watches_new_documents(&self) -> bool {
+        self.saved.watching
+    }
+
+    /// Confronte à toutes les requêtes enregistrées les documents écrits depuis l'appel
+    /// précédent (voir [`Collection::watch_new_documents`]), comme
+    /// [`Collection::match_new_documents`], puis les oublie.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::match_new_documents`] ; les documents restent alors
+    ///   retenus.
+    pub fn take_new_matches(&mut self) -> Result<Vec<(String, Uuid, f32)>> {
+        let names: Vec<&str> = self.saved_queries();
+        let matches = self.match_new_documents(&names, &self.saved.pending)?;
+        self.saved.clear_pending();
+        Ok(matches)
+    }
+
+    fn saved_or_err(&self, name: &str) -> Result<(&[f32], &SearchParams)> {
+        self.saved_query(name)
+            .ok_or_else(|| Error::SavedQueryNotFound(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::similarity::Metric;
+
+    /// Collection de produits scalaires avec une requête « alerte » au seuil de 0.5.
+    fn collection() -> (Collection, Vec<Uuid>) {
+        let mut collection = Collection::new().with_metric(Metric::Dot);
+        let old: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for key in &old {
+            collection.upsert(*key, [1.0, 0.0]).unwrap();
+        }
+        let params = SearchParams {
+            score_threshold: Some(0.5),
+            ..SearchParams::new(10)
+        };
+        collection.save_query("alerte", [1.0, 0.0], params).unwrap();
+        collection.save_query("tout", [0.0, 1.0], SearchParams::new(10)).unwrap();
+        (collection, old)
+    }
+
+    #[test]
+    fn only_the_new_documents_are_matched_above_the_threshold() {
+        let (mut collection, old) = collection();
+        let new: Vec<Uuid> = (0..3).map(|i| Uuid::from_u128(i + 1)).collect();
+        collection.upsert(new[0], [0.9, 0.1]).unwrap();
+        collection.upsert(new[1], [0.2, 0.8]).unwrap();
+        collection.upsert(new[2], [0.6, 0.4]).unwrap();
+
+        let matches = collection.match_new_documents(&["alerte"], &new).unwrap();
+        assert_eq!(
+            matches,
+            [("alerte".to_string(), new[0], 0.9), ("alerte".to_string(), new[2], 0.6)]
+        );
+        // Sans seuil, chaque nouveau document correspond ; un absent est ignoré.
+        let mut ids = new.clone();
+        ids.push(Uuid::new_v4());
+        let matches = collection.match_new_documents(&["tout"], &ids).unwrap();
+        let keys: Vec<Uuid> = matches.iter().map(|(_, key, _)| *key).collect();
+        assert_eq!(keys, [new[1], new[2], new[0]]);
+        assert!(keys.iter().all(|key| !old.contains(key)));
+        // La recherche enregistrée, elle, parcourt toute la collection.
+        assert_eq!(collection.run_saved("alerte").unwrap().hits.len(), 5);
+        assert!(matches!(
+            collection.match_new_documents(&["absente"], &new),
+            Err(Error::SavedQueryNotFound(name)) if name == "absente"
+        ));
+    }
+
+    #[test]
+    fn watched_writes_are_matched_once() {
+        let (mut collection, old) = collection();
+        collection.watch_new_documents(true);
+        let key = Uuid::new_v4();
+        collection.upsert(key, [0.7, 0.7]).unwrap();
+        let matches = collection.take_new_matches().unwrap();
+        assert_eq!(
+            matches,
+            [("alerte".to_string(), key, 0.7), ("tout".to_string(), key, 0.7)]
+        );
+        assert!(collection.take_new_matches().unwrap().is_empty());
+        // Une mise à jour compte comme une nouvelle écriture.
+        collection.upsert(old[0], [0.0, 2.0]).unwrap();
+        assert_eq!(collection.take_new_matches().unwrap(), [("tout".to_string(), old[0], 2.0)]);
+        collection.upsert(Uuid::new_v4(), [1.0, 0.0]).unwrap();
+        collection.watch_new_documents(false);
+        assert!(collection.take_new_matches().unwrap().is_empty());
+    }
+
+    #[test]
+    fn saved_queries_are_validated_and_saved() {
+        let (mut collection, _) = collection();
+        assert!(matches!(
+            collection.save_query("courte", [1.0], SearchParams::new(1)),
+            Err(Error::DimensionMismatch { .. })
+        ));
+        let saved = collection.saved_query("alerte").map(|(v, p)| (v.to_vec(), p.clone()));
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let docs = loaded.get("docs").unwrap();
+        assert_eq!(docs.saved_queries(), ["alerte", "tout"]);
+        assert_eq!(docs.saved_query("alerte").map(|(v, p)| (v.to_vec(), p.clone())), saved);
+    }
+}
@@ -0,0 +1,260 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::float;
+use crate::payload::{Payload, Value};
+
+/// Type attendu d'un champ de charge utile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl FieldType {
+    /// Retourne le type de `value`, ou `None` pour `null`.
+    pub fn of(value: &Value) -> Option<FieldType> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(FieldType::Bool),
+            Value::Number(_) => Some(FieldType::Number),
+            Value::String(_) => Some(FieldType::String),
+            Value::Array(_) => Some(FieldType::Array),
+            Value::Object(_) => Some(FieldType::Object),
+        }
+    }
+
+    /// Convertit `value` dans ce type lorsque la conversion est évidente : chaîne
+    /// numérique vers nombre, `"true"` / `"false"` vers booléen, nombre ou booléen vers
+    /// chaîne.
+    fn coerce(self, value: &Value) -> Option<Value> {
+        match (self, value) {
+            (FieldType::Number, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(Value::Number),
+            (FieldType::Bool, Value::String(s)) => match s.trim() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (FieldType::String, Value::Number(n)) if n.is_finite() => {
+                Some(Value::String(float::shortest_f64(*n)))
+            }
+            (FieldType::String, Value::Bool(b)) => Some(Value::String(b.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldType::Bool => "booléen",
+            FieldType::Number => "nombre",
+            FieldType::String => "chaîne",
+            FieldType::Array => "tableau",
+            FieldType::Object => "objet",
+        })
+    }
+}
+
+/// Traitement des valeurs qui ne respectent pas un [`PayloadSchema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaMode {
+    /// Refuse toute valeur d'un autre type que celui attendu.
+    #[default]
+    Strict,
+    /// Convertit les cas évidents, comme `"2021"` pour un nombre, en les comptant dans
+    /// [`Collection::schema_warnings`] ; les autres valeurs sont refusées comme en mode
+    /// strict.
+    Lenient,
+}
+
+/// Contrainte sur un champ de charge utile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSchema {
+    /// Type attendu des valeurs non nulles.
+    pub kind: FieldType,
+    /// Vrai si chaque charge utile doit contenir le champ avec une valeur non nulle.
+    pub required: bool,
+}
+
+/// Schéma des charges utiles d'une collection : le type attendu de chaque champ
+/// déclaré, et s'il est obligatoire.
+///
+/// `PayloadSchema::new().field("annee", FieldType::Number, true).field("langue", FieldType::String, false)`.
+/// Les champs non déclarés sont acceptés quel que soit leur type, et `null` convient à
+/// tout champ facultatif.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PayloadSchema {
+    fields: BTreeMap<String, FieldSchema>,
+    mode: SchemaMode,
+}
+
+impl PayloadSchema {
+    /// Crée un schéma strict sans champ déclaré.
+    pub fn new() -> Self {
+        PayloadSchema::default()
+    }
+
+    /// Déclare le champ `name`, en remplaçant une déclaration précédente.
+    ///
+    /// # Arguments
+    /// * `name` - Nom du champ.
+    /// * `kind` - Type attendu.
+    /// * `required` - Vrai si le champ est obligatoire.
+    pub fn field(mut self, name: impl Into<String>, kind: FieldType, required: bool) -> Self {
+        self.fields
+            .insert(name.into(), FieldSchema { kind, required });
+        self
+    }
+
+    /// Fixe le traitement des valeurs non conformes.
+    pub fn with_mode(mut self, mode: SchemaMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Retourne le traitement des valeurs non conformes.
+    pub fn mode(&self) -> SchemaMode {
+        self.mode
+    }
+
+    /// Retourne la contrainte du champ `name`, s'il est déclaré.
+    pub fn get(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.get(name)
+    }
+
+    /// Parcourt les champs déclarés par nom croissant.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &FieldSchema)> {
+        self.fields
+            .iter()
+            .map(|(name, field)| (name.as_str(), field))
+    }
+
+    /// Vérifie la charge utile du document `key` et lui applique les conversions du mode
+    /// souple.
+    ///
+    /// # Retourne
+    /// * Result<(Payload, u64)> - La charge utile conforme et le nombre de valeurs converties.
+    ///
+    /// # Erreurs
+    /// * `Error::SchemaViolation` - Pour le premier champ, par nom croissant, absent alors
+    ///   qu'il est obligatoire ou d'un type qui ne convient pas.
+    pub(crate) fn conform(&self, key: Uuid, mut payload: Payload) -> Result<(Payload, u64)> {
+        let mut coerced = 0;
+        for (name, field) in &self.fields {
+            let violation = |value: Option<&Value>| Error::SchemaViolation {
+                key,
+                field: name.clone(),
+                expected: field.kind,
+                value: value.cloned(),
+            };
+            let Some(value) = payload.get_mut(name) else {
+                if field.required {
+                    return Err(violation(None));
+                }
+                continue;
+            };
+            match FieldType::of(value) {
+                None if field.required => return Err(violation(Some(value))),
+                None => {}
+                Some(kind) if kind == field.kind => {}
+                Some(_) => match field.kind.coerce(value) {
+                    Some(converted) if self.mode == SchemaMode::Lenient => {
+                        *value = converted;
+                        coerced += 1;
+                    }
+                    _ => return Err(violation(Some(value))),
+                },
+            }
+        }
+        Ok((payload, coerced))
+    }
+}
+
+impl Collection {
+    /// Fixe le schéma des charges utiles de la collection, ou le retire avec `None`.
+    ///
+    /// Le schéma est vérifié par [`Collection::upsert_with_payload`],
+    /// [`Collection::upsert_id_with_payload`], [`Collection::set_payload`] et
+    /// [`Collection::import_jsonl`] avant toute modification du document. Les charges
+    /// utiles déjà présentes ne sont pas vérifiées : [`Collection::infer_schema`]
+    /// propose un schéma qui leur convient.
+    ///
+    /// # Arguments
+    /// * `schema` - Schéma à appliquer.
+    pub fn set_payload_schema(&mut self, schema: Option<PayloadSchema>) {
+        self.schema = schema;
+    }
+
+    /// Retourne le schéma des charges utiles de la collection, s'il y en a un.
+    pub fn payload_schema(&self) -> Option<&PayloadSchema> {
+        self.schema.as_ref()
+    }
+
+    /// Retourne le nombre de valeurs converties par le mode souple du schéma depuis la
+    /// création ou le chargement de la collection.
+    pub fn schema_warnings(&self) -> u64 {
+        self.schema_warnings
+    }
+
+    /// Propose un schéma strict d'après les charges utiles existantes.
+    ///
+    /// Chaque champ reçoit le type le plus fréquent de ses valeurs non nulles, les chaînes
+    /// numériques comptant comme des nombres ; il est obligatoire s'il a une valeur non
+    /// nulle dans chaque document de la collection. Les documents supprimés en attente de
+    /// compactage sont ignorés.
+    ///
+    /// # Retourne
+    /// * PayloadSchema - Le schéma proposé, à ajuster avant
+    ///   [`Collection::set_payload_schema`].
+    pub fn infer_schema(&self) -> PayloadSchema {
+        let mut seen: BTreeMap<&str, (HashMap<FieldType, usize>, usize)> = BTreeMap::new();
+        for payload in self.payloads.values() {
+            for (name, value) in payload {
+                let (kinds, present) = seen.entry(name).or_default();
+                let Some(kind) = FieldType::of(value) else {
+                    continue;
+                };
+                *present += 1;
+                let kind = match FieldType::Number.coerce(value) {
+                    Some(_) if kind == FieldType::String => FieldType::Number,
+                    _ => kind,
+                };
+                *kinds.entry(kind).or_insert(0) += 1;
+            }
+        }
+        let mut schema = PayloadSchema::new();
+        for (name, (kinds, present)) in seen {
+            let Some(kind) = kinds
+                .into_iter()
+                .max_by_key(|(kind, count)| (*count, Reverse(*kind as u8)))
+                .map(|(kind, _)| kind)
+            else {
+                continue;
+            };
+            schema = schema.field(name, kind, present == self.documents.len());
+        }
+        schema
+    }
+
+    /// Vérifie `payload` pour le document `key` selon le schéma de la collection.
+    pub(crate) fn conform_payload(&self, key: Uuid, payload: Payload) -> Result<(Payload, u64)> {
+        match &self.schema {
+            Some(schema) => schema.conform(key, payload),
+            None => Ok((payload, 0)),
+        }
+    }
+}
@@ -0,0 +1,1327 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::collection::Document;
+use crate::csv;
+use crate::decay::DecayParams;
+use crate::error::{Error, Result};
+use crate::filter::{FilterPlan, FilterStrategy};
+use crate::float;
+use crate::json;
+use crate::model::ModelFingerprint;
+use crate::payload::{self, Payload, Value};
+use crate::request_context::RequestContext;
+use crate::rng::Rng;
+use crate::similarity::Metric;
+
+/// Paramètres d'une recherche.
+///
+/// Les champs non renseignés prennent leur valeur par défaut :
+/// `SearchParams { k: 5, ..Default::default() }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchParams {
+    /// Nombre maximal de résultats à retourner.
+    pub k: usize,
+    /// Score qu'un document doit atteindre pour être retourné : score minimal pour les
+    /// similarités, distance maximale pour les distances.
+    pub score_threshold: Option<f32>,
+    /// Durée maximale du parcours ; une fois dépassée, la recherche retourne les meilleurs
+    /// documents trouvés jusque-là et marque le résultat comme tronqué.
+    pub time_budget: Option<Duration>,
+    /// Valeur retournée comme score de chaque résultat. Le seuil `score_threshold`
+    /// s'applique toujours au score brut.
+    pub score_mode: ScoreMode,
+    /// Espace de noms auquel la recherche est limitée (voir
+    /// [`Collection::upsert_in`](crate::Collection::upsert_in)) ; `None` parcourt tous
+    /// les documents, avec ou sans espace de noms.
+    pub namespace: Option<String>,
+    /// Vrai pour exiger exactement `k` résultats : une recherche qui en trouve moins
+    /// échoue avec `Error::InsufficientCandidates` au lieu de retourner une liste courte,
+    /// et `k == 0` est refusé. Par défaut, la recherche retourne jusqu'à `k` résultats.
+    pub require_exact_k: bool,
+    /// Vrai pour mesurer la durée de chaque étape et la joindre au résultat dans
+    /// [`SearchResults::timing`] ; sans cette option, aucune horloge n'est lue.
+    pub with_timing: bool,
+    /// Empreinte du modèle qui a produit la requête : si la collection porte celle d'un
+    /// autre modèle ([`Collection::model_fingerprint`](crate::Collection::model_fingerprint)),
+    /// la recherche échoue avec `Error::ModelMismatch`. `None` ne vérifie rien, de même
+    /// qu'une collection sans empreinte ou une [`MmapCollection`](crate::MmapCollection).
+    pub model: Option<ModelFingerprint>,
+    /// Vrai pour rechercher malgré une empreinte de modèle différente, par exemple
+    /// pendant une migration d'un modèle à l'autre.
+    pub allow_model_mismatch: bool,
+    /// Champs des charges utiles à joindre aux résultats dans
+    /// [`SearchResults::payloads`] ; `None` n'en joint aucune. Un nom pointé comme
+    /// `"auteur.nom"` désigne un champ d'un objet imbriqué, joint sous la même
+    /// imbrication, à moins qu'un champ de premier niveau ne porte ce nom exact.
+    pub payload_selector: Option<Vec<String>>,
+    /// Vrai pour joindre le vecteur stocké de chaque résultat dans
+    /// [`SearchResults::vectors`].
+    pub with_vector: bool,
+    /// Atténuation des scores selon l'âge des documents, appliquée avant le classement :
+    /// la limite à `k` porte sur les scores atténués, qui sont ceux retournés. Le seuil
+    /// `score_threshold` s'applique au score brut.
+    pub decay: Option<DecayParams>,
+    /// Score entre deux résultats à partir duquel le moins bien classé est écarté comme
+    /// doublon du premier, dans la mesure de la collection : similarité minimale pour
+    /// les similarités, distance maximale pour les distances. Les résultats sont
+    /// parcourus du meilleur au moins bon, chacun comparé à ceux déjà retenus, jusqu'à
+    /// en retenir `k` parmi les `k × dedup_pool_factor` meilleurs candidats. Si la
+    /// réserve s'épuise avant, la liste est plus courte que `k` et
+    /// [`SearchResults::available`] vaut sa longueur ; sinon, les doublons écartés ne
+    /// comptent pas dans `available`. `None` garde tous les résultats.
+    pub dedup_results: Option<f32>,
+    /// Multiplicateur de `k` donnant la réserve de candidats parcourue par
+    /// `dedup_results`, par défaut [`DEFAULT_DEDUP_POOL_FACTOR`] ; 0 est traité comme 1.
+    pub dedup_pool_factor: usize,
+    /// Vrai pour des scores identiques bit à bit sur toutes les plateformes, au prix de
+    /// transformations plus lentes.
+    ///
+    /// Les scores bruts le sont toujours : chaque score est une somme de produits ou
+    /// d'écarts calculée séquentiellement, dans l'ordre des dimensions, sans instruction
+    /// vectorielle ni multiplication-addition fusionnée, et le parcours réparti sur
+    /// plusieurs threads ne change ni les scores ni l'ordre des résultats. Seules les
+    /// exponentielles et les puissances de l'atténuation (`decay`), de la normalisation
+    /// du produit scalaire et de la calibration passent par la bibliothèque
+    /// mathématique du système, dont le dernier bit varie d'une plateforme à l'autre ;
+    /// cette option les remplace par un calcul fait des seules opérations arithmétiques
+    /// de base, arrondies partout de la même façon.
+    pub deterministic_math: bool,
+    /// Part des documents, dans ]0, 1], évalués par une recherche exploratoire : seuls
+    /// ceux de l'échantillon sont notés, et le résultat est marqué
+    /// [`SearchResults::sampled`]. Chaque document y entre selon une valeur tirée de son
+    /// `Uuid` et de `sample_seed`, indépendamment de l'ordre de stockage : l'échantillon
+    /// est uniforme, et le même pour la même graine, dans tous les types de collection.
+    /// Avec un seuil, [`SearchResults::estimated_total_matches`] extrapole le nombre de
+    /// documents qui l'atteignent dans toute la collection. `None` évalue tout.
+    pub sample_fraction: Option<f32>,
+    /// Graine de l'échantillon de `sample_fraction`.
+    pub sample_seed: u64,
+    /// Écart minimal entre les scores des deux premiers résultats
+    /// ([`SearchResults::top_margin`]) : en dessous, le résultat porte le marqueur
+    /// [`SearchResults::low_confidence`] au lieu d'échouer. Une liste de moins de deux
+    /// résultats n'est jamais marquée. `None` ne marque rien.
+    pub min_margin: Option<f32>,
+    /// Dimensions ignorées par le calcul des scores, comme si elles valaient 0 dans la
+    /// requête et dans chaque document, par exemple celles qu'un modèle corrèle à la
+    /// longueur du texte plutôt qu'à son sens. Les vecteurs stockés ne sont pas
+    /// modifiés : les dimensions masquées reçoivent un poids nul, et la similarité
+    /// cosinus est calculée avec les normes masquées. Les indices portent sur les
+    /// vecteurs stockés, après l'éventuelle projection ; un indice hors de leur
+    /// dimension fait échouer la recherche avant le parcours avec
+    /// `Error::RangeOutOfBounds`. `None` ne masque rien.
+    pub dimension_mask: Option<Vec<usize>>,
+    /// Exécution des recherches filtrées ([`Collection::search_filtered`](crate::Collection::search_filtered)) :
+    /// par défaut choisie d'après la sélectivité du filtre, elle peut être imposée pour
+    /// comparer les plans. Les résultats ne changent pas.
+    pub filter_strategy: FilterStrategy,
+    /// Contexte de la requête, renvoyé tel quel dans [`SearchResults::context`] et joint
+    /// aux erreurs de [`BaseDeDonnees::search_with`](crate::BaseDeDonnees::search_with).
+    /// Il ne change pas la recherche et n'est pas sauvegardé avec les paramètres par
+    /// défaut ni avec les recherches enregistrées.
+    pub context: Option<RequestContext>,
+}
+
+/// Multiplicateur par défaut de la réserve de candidats de
+/// [`SearchParams::dedup_results`].
+pub const DEFAULT_DEDUP_POOL_FACTOR: usize = 4;
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            k: 10,
+            score_threshold: None,
+            time_budget: None,
+            score_mode: ScoreMode::Raw,
+            namespace: None,
+            require_exact_k: false,
+            with_timing: false,
+            model: None,
+            allow_model_mismatch: false,
+            payload_selector: None,
+            with_vector: false,
+            decay: None,
+            dedup_results: None,
+            dedup_pool_factor: DEFAULT_DEDUP_POOL_FACTOR,
+            deterministic_math: false,
+            sample_fraction: None,
+            sample_seed: 0,
+            min_margin: None,
+            dimension_mask: None,
+            filter_strategy: FilterStrategy::Auto,
+            context: None,
+        }
+    }
+}
+
+impl SearchParams {
+    /// Crée des paramètres retournant au plus `k` résultats.
+    ///
+    /// # Arguments
+    /// * `k` - Nombre maximal de résultats à retourner.
+    pub fn new(k: usize) -> Self {
+        SearchParams {
+            k,
+            ..Default::default()
+        }
+    }
+
+    /// Vérifie les paramètres avant la recherche.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `k == 0` alors que `require_exact_k` est actif, si
+    ///   le seuil des doublons n'est pas fini, si la part échantillonnée n'est pas dans
+    ///   ]0, 1], si l'écart minimal est négatif ou infini, ou si l'atténuation est
+    ///   invalide.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.require_exact_k && self.k == 0 {
+            return Err(Error::InvalidConfig(
+                "k doit être strictement positif lorsque require_exact_k est actif".to_string(),
+            ));
+        }
+        if let Some(threshold) = self.dedup_results.filter(|t| !t.is_finite()) {
+            return Err(Error::InvalidConfig(format!(
+                "le seuil des doublons vaut {} ; il doit être fini",
+                threshold
+            )));
+        }
+        if let Some(fraction) = self.sample_fraction.filter(|f| !(*f > 0.0 && *f <= 1.0)) {
+            return Err(Error::InvalidConfig(format!(
+                "la part échantillonnée vaut {} ; elle doit être comprise entre 0 exclu et 1",
+                fraction
+            )));
+        }
+        if let Some(margin) = self.min_margin.filter(|m| !(m.is_finite() && *m >= 0.0)) {
+            return Err(Error::InvalidConfig(format!(
+                "l'écart minimal vaut {} ; il doit être positif et fini",
+                margin
+            )));
+        }
+        self.decay.as_ref().map_or(Ok(()), DecayParams::check)
+    }
+
+    /// Indique si le document `key` appartient à l'échantillon de la recherche ; tous
+    /// en font partie sans `sample_fraction`.
+    pub(crate) fn samples(&self, key: &Uuid) -> bool {
+        let Some(fraction) = self.sample_fraction else {
+            return true;
+        };
+        let bits = key.as_u128();
+        let mixed = self.sample_seed ^ (bits >> 64) as u64 ^ (bits as u64).rotate_left(32);
+        Rng::new(mixed).next_f64() < f64::from(fraction)
+    }
+
+    /// Vérifie les paramètres d'une recherche dans un magasin sans calibration des scores.
+    ///
+    /// # Erreurs
+    /// * Celles de [`SearchParams::check`].
+    /// * `Error::InvalidConfig` - Si `score_mode` vaut `ScoreMode::Calibrated`.
+    pub(crate) fn check_uncalibrated(&self) -> Result<()> {
+        if self.score_mode == ScoreMode::Calibrated {
+            return Err(Error::InvalidConfig(
+                "seules les collections simples calibrent les scores".to_string(),
+            ));
+        }
+        self.check()
+    }
+}
+
+/// Paramètres d'une recherche dont les champs non renseignés prennent la valeur par
+/// défaut de la collection interrogée (voir [`Collection::set_default_params`]).
+///
+/// Les valeurs renseignées l'emportent toujours :
+/// `SearchOverrides { k: Some(3), ..Default::default() }` garde le seuil, le budget et
+/// le mode de score par défaut de la collection. Un seuil par défaut peut être remplacé
+/// mais pas retiré ; [`Collection::search_with`] n'utilise aucune valeur par défaut.
+///
+/// [`Collection::set_default_params`]: crate::Collection::set_default_params
+/// [`Collection::search_with`]: crate::Collection::search_with
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchOverrides {
+    /// Nombre maximal de résultats à retourner.
+    pub k: Option<usize>,
+    /// Score qu'un document doit atteindre pour être retourné.
+    pub score_threshold: Option<f32>,
+    /// Durée maximale du parcours.
+    pub time_budget: Option<Duration>,
+    /// Valeur retournée comme score de chaque résultat.
+    pub score_mode: Option<ScoreMode>,
+    /// Espace de noms auquel la recherche est limitée.
+    pub namespace: Option<String>,
+    /// Vrai pour exiger exactement `k` résultats.
+    pub require_exact_k: Option<bool>,
+    /// Vrai pour mesurer la durée de chaque étape.
+    pub with_timing: Option<bool>,
+    /// Empreinte du modèle qui a produit la requête.
+    pub model: Option<ModelFingerprint>,
+    /// Vrai pour rechercher malgré une empreinte de modèle différente.
+    pub allow_model_mismatch: Option<bool>,
+    /// Champs des charges utiles à joindre aux résultats.
+    pub payload_selector: Option<Vec<String>>,
+    /// Vrai pour joindre le vecteur stocké de chaque résultat.
+    pub with_vector: Option<bool>,
+    /// Atténuation des scores selon l'âge des documents.
+    pub decay: Option<DecayParams>,
+    /// Score entre deux résultats à partir duquel le second est un doublon.
+    pub dedup_results: Option<f32>,
+    /// Multiplicateur de `k` donnant la réserve de candidats des doublons.
+    pub dedup_pool_factor: Option<usize>,
+    /// Vrai pour des scores identiques bit à bit sur toutes les plateformes.
+    pub deterministic_math: Option<bool>,
+    /// Part des documents évalués par une recherche exploratoire.
+    pub sample_fraction: Option<f32>,
+    /// Graine de l'échantillon.
+    pub sample_seed: Option<u64>,
+    /// Écart minimal entre les scores des deux premiers résultats.
+    pub min_margin: Option<f32>,
+    /// Dimensions ignorées par le calcul des scores.
+    pub dimension_mask: Option<Vec<usize>>,
+    /// Exécution des recherches filtrées.
+    pub filter_strategy: Option<FilterStrategy>,
+    /// Contexte de la requête.
+    pub context: Option<RequestContext>,
+}
+
+impl SearchOverrides {
+    /// Complète ces paramètres par `defaults`.
+    ///
+    /// # Arguments
+    /// * `defaults` - Paramètres utilisés pour les champs non renseignés.
+    ///
+    /// # Retourne
+    /// * SearchParams - Les paramètres complets de la recherche.
+    pub fn resolve(&self, defaults: &SearchParams) -> SearchParams {
+        SearchParams {
+            k: self.k.unwrap_or(defaults.k),
+            score_threshold: self.score_threshold.or(defaults.score_threshold),
+            time_budget: self.time_budget.or(defaults.time_budget),
+            score_mode: self.score_mode.unwrap_or(defaults.score_mode),
+            namespace: self
+                .namespace
+                .clone()
+                .or_else(|| defaults.namespace.clone()),
+            require_exact_k: self.require_exact_k.unwrap_or(defaults.require_exact_k),
+            with_timing: self.with_timing.unwrap_or(defaults.with_timing),
+            model: self.model.clone().or_else(|| defaults.model.clone()),
+            allow_model_mismatch: self
+                .allow_model_mismatch
+                .unwrap_or(defaults.allow_model_mismatch),
+            payload_selector: self
+                .payload_selector
+                .clone()
+                .or_else(|| defaults.payload_selector.clone()),
+            with_vector: self.with_vector.unwrap_or(defaults.with_vector),
+            decay: self.decay.clone().or_else(|| defaults.decay.clone()),
+            dedup_results: self.dedup_results.or(defaults.dedup_results),
+            dedup_pool_factor: self.dedup_pool_factor.unwrap_or(defaults.dedup_pool_factor),
+            deterministic_math: self
+                .deterministic_math
+                .unwrap_or(defaults.deterministic_math),
+            sample_fraction: self.sample_fraction.or(defaults.sample_fraction),
+            sample_seed: self.sample_seed.unwrap_or(defaults.sample_seed),
+            min_margin: self.min_margin.or(defaults.min_margin),
+            dimension_mask: self
+                .dimension_mask
+                .clone()
+                .or_else(|| defaults.dimension_mask.clone()),
+            filter_strategy: self.filter_strategy.unwrap_or(defaults.filter_strategy),
+            context: self.context.clone().or_else(|| defaults.context.clone()),
+        }
+    }
+}
+
+/// Score associé à chaque résultat d'une recherche.
+///
+/// Quel que soit le mode, les résultats sont classés selon le score brut de la mesure :
+/// seule la valeur retournée change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreMode {
+    /// Score brut de la mesure : similarité, produit scalaire ou distance.
+    #[default]
+    Raw,
+    /// Score ramené dans [0, 1] par [`Metric::normalize`], 1 désignant le plus proche.
+    Normalized,
+    /// Rang du résultat, à partir de 1 pour le meilleur.
+    Rank,
+    /// Probabilité de pertinence selon la calibration de la collection
+    /// ([`Collection::fit_calibration`](crate::Collection::fit_calibration)). Seules les
+    /// collections simples calibrées acceptent ce mode.
+    Calibrated,
+}
+
+impl ScoreMode {
+    /// Remplace les scores bruts de résultats déjà classés selon ce mode.
+    pub(crate) fn apply(self, metric: Metric, hits: &mut Document, deterministic_math: bool) {
+        match self {
+            ScoreMode::Raw => {}
+            ScoreMode::Normalized => hits
+                .iter_mut()
+                .for_each(|(_, score)| *score = metric.normalize_with(*score, deterministic_math)),
+            ScoreMode::Rank => hits
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, (_, score))| *score = (i + 1) as f32),
+            // Appliqué par la collection, qui détient la calibration.
+            ScoreMode::Calibrated => {}
+        }
+    }
+}
+
+/// Résultats d'une recherche.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchResults {
+    /// Documents retenus avec leur score, du meilleur au moins bon.
+    pub hits: Document,
+    /// Vrai si le budget de temps a interrompu le parcours avant la fin : les résultats
+    /// sont alors les meilleurs parmi les documents évalués.
+    pub truncated: bool,
+    /// Nombre de résultats demandé (`k`).
+    pub requested_k: usize,
+    /// Nombre de documents qui satisfaisaient la recherche avant la limite à `k` :
+    /// `available < requested_k` signale une liste plus courte que demandé.
+    pub available: usize,
+    /// Durées des étapes de la recherche, si [`SearchParams::with_timing`] est actif.
+    pub timing: Option<SearchTiming>,
+    /// Charges utiles des résultats, dans l'ordre de `hits` et réduites aux champs de
+    /// [`SearchParams::payload_selector`] ; vide pour un document sans charge utile ni
+    /// aucun des champs. `None` sans sélecteur.
+    pub payloads: Option<Vec<Payload>>,
+    /// Vecteurs stockés des résultats, dans l'ordre de `hits`, si
+    /// [`SearchParams::with_vector`] est actif. Ils sont partagés avec la collection,
+    /// sans copie, sauf pour une [`MmapCollection`](crate::MmapCollection).
+    pub vectors: Option<Vec<Arc<Vec<f32>>>>,
+    /// Vrai si seul un échantillon des documents a été évalué
+    /// ([`SearchParams::sample_fraction`]) : les résultats sont les meilleurs de
+    /// l'échantillon, pas forcément de la collection, et `available` ne compte que
+    /// l'échantillon.
+    pub sampled: bool,
+    /// Nombre de documents de toute la collection qui atteindraient le seuil, extrapolé
+    /// de l'échantillon : `available` divisé par la part échantillonnée. Renseigné
+    /// seulement pour une recherche échantillonnée avec `score_threshold` ; une recherche
+    /// aussi interrompue par le budget de temps le sous-estime.
+    pub estimated_total_matches: Option<usize>,
+    /// Écart du meilleur résultat sur le deuxième, `None` pour moins de deux résultats
+    /// (voir [`SearchResults::top_margin`]).
+    pub margin: Option<Margin>,
+    /// Marqueur posé lorsque l'écart est inférieur à [`SearchParams::min_margin`].
+    pub low_confidence: Option<LowConfidence>,
+    /// Plan d'exécution d'une recherche filtrée
+    /// ([`Collection::search_filtered`](crate::Collection::search_filtered)), `None`
+    /// pour les autres recherches.
+    pub plan: Option<FilterPlan>,
+    /// Noms des transformations appliquées à la requête par le pipeline de la collection
+    /// ([`Collection::set_query_pipeline`](crate::Collection::set_query_pipeline)), dans
+    /// l'ordre ; vide sans pipeline.
+    pub query_transforms: Vec<String>,
+    /// Contexte de la requête, repris de [`SearchParams::context`].
+    pub context: Option<RequestContext>,
+}
+
+/// Écart entre les scores des deux premiers résultats d'une recherche.
+///
+/// Il est mesuré sur les scores de classement, avant le mode de score et la
+/// calibration, ou sur les nouveaux scores d'une recherche reclassée
+/// ([`Collection::search_rescored`](crate::Collection::search_rescored)), et orienté
+/// selon la mesure : positif lorsque le premier est meilleur,
+/// y compris pour une distance où le meilleur score est le plus petit. Il est nul pour
+/// deux premiers résultats à égalité.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Margin {
+    /// Écart des scores : `score₁ − score₂` pour une similarité, `score₂ − score₁`
+    /// pour une distance.
+    pub absolute: f32,
+    /// Écart rapporté à la plus grande valeur absolue des deux scores, `None` si les
+    /// deux sont nuls.
+    pub relative: Option<f32>,
+}
+
+impl Margin {
+    /// Mesure l'écart entre les deux premiers résultats de `hits`, déjà classés du
+    /// meilleur au moins bon, le meilleur score étant le plus grand si
+    /// `higher_is_better`.
+    pub(crate) fn of(higher_is_better: bool, hits: &Document) -> Option<Margin> {
+        let [(_, first), (_, second), ..] = hits.as_slice() else {
+            return None;
+        };
+        let absolute = match higher_is_better {
+            true => first - second,
+            false => second - first,
+        };
+        let scale = first.abs().max(second.abs());
+        Some(Margin {
+            absolute,
+            relative: (scale > 0.0).then(|| absolute / scale),
+        })
+    }
+}
+
+/// Marqueur d'une recherche dont le meilleur résultat devance trop peu le deuxième
+/// ([`SearchParams::min_margin`]) : l'appelant peut, par exemple, demander une
+/// précision plutôt que répondre directement.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LowConfidence {
+    /// Écart mesuré entre les deux premiers résultats.
+    pub margin: f32,
+    /// Écart minimal demandé.
+    pub min_margin: f32,
+}
+
+impl LowConfidence {
+    /// Retourne le marqueur si l'écart `margin` est inférieur à `min_margin`.
+    pub(crate) fn check(margin: Option<Margin>, min_margin: Option<f32>) -> Option<Self> {
+        let (margin, min_margin) = (margin?.absolute, min_margin?);
+        (margin < min_margin).then_some(LowConfidence { margin, min_margin })
+    }
+}
+
+/// Durées des étapes d'une recherche, en microsecondes.
+///
+/// `filter_us + scoring_us + topk_us` ne dépasse pas `total_us`, sauf pour une collection
+/// partitionnée : le filtrage et le score y sont la somme des durées de chaque
+/// partition, parcourues en parallèle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchTiming {
+    /// Sélection des documents candidats : espace de noms, index et filtre.
+    pub filter_us: u64,
+    /// Calcul des scores des candidats.
+    pub scoring_us: u64,
+    /// Nombre de documents évalués, moins que les candidats si le budget de temps a
+    /// interrompu le parcours.
+    pub candidates_scored: usize,
+    /// Classement et limite à `k` des documents retenus.
+    pub topk_us: u64,
+    /// Durée totale de la recherche.
+    pub total_us: u64,
+}
+
+impl fmt::Display for SearchTiming {
+    /// Affiche les durées sur une ligne : `filtre 12 µs, score 340 µs (1000 documents), …`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "filtre {} µs, score {} µs ({} documents), classement {} µs, total {} µs",
+            self.filter_us, self.scoring_us, self.candidates_scored, self.topk_us, self.total_us
+        )
+    }
+}
+
+/// Chronomètre des étapes d'une recherche, créé seulement si
+/// [`SearchParams::with_timing`] est actif.
+pub(crate) struct Stopwatch {
+    started: Instant,
+    lap: Instant,
+    pub(crate) timing: SearchTiming,
+}
+
+impl Stopwatch {
+    /// Démarre le chronomètre si `params` le demande.
+    pub(crate) fn start(params: &SearchParams) -> Option<Stopwatch> {
+        params.with_timing.then(|| {
+            let now = Instant::now();
+            Stopwatch {
+                started: now,
+                lap: now,
+                timing: SearchTiming::default(),
+            }
+        })
+    }
+
+    /// Retourne le nombre de microsecondes écoulées depuis l'étape précédente et commence
+    /// la suivante.
+    pub(crate) fn lap(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = micros(now - self.lap);
+        self.lap = now;
+        elapsed
+    }
+
+    /// Arrête le chronomètre et retourne les durées mesurées.
+    pub(crate) fn stop(mut self) -> SearchTiming {
+        self.timing.total_us = micros(self.started.elapsed());
+        self.timing
+    }
+}
+
+pub(crate) fn micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+impl SearchResults {
+    /// Retourne le nombre de résultats.
+    pub fn len(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// Indique si aucun document n'a été retenu.
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    /// Parcourt les résultats du meilleur au moins bon.
+    pub fn iter(&self) -> std::slice::Iter<'_, (Uuid, f32)> {
+        self.hits.iter()
+    }
+
+    /// Retourne l'écart de score du meilleur résultat sur le deuxième, positif lorsque
+    /// le premier est meilleur quelle que soit la mesure ([`Margin::absolute`]), ou
+    /// `None` pour moins de deux résultats.
+    pub fn top_margin(&self) -> Option<f32> {
+        self.margin.map(|margin| margin.absolute)
+    }
+
+    /// Retourne l'écart de [`SearchResults::top_margin`] rapporté à la plus grande
+    /// valeur absolue des deux scores ([`Margin::relative`]).
+    pub fn relative_margin(&self) -> Option<f32> {
+        self.margin.and_then(|margin| margin.relative)
+    }
+
+    /// Indique si l'écart entre les deux premiers résultats est inférieur à
+    /// [`SearchParams::min_margin`].
+    pub fn is_low_confidence(&self) -> bool {
+        self.low_confidence.is_some()
+    }
+
+    /// Joint aux résultats, une fois classés, les charges utiles et les vecteurs demandés
+    /// par `params`.
+    ///
+    /// # Arguments
+    /// * `params` - Paramètres de la recherche.
+    /// * `payload` - Charge utile d'un document.
+    /// * `vector` - Vecteur stocké d'un document.
+    pub(crate) fn attach<'a>(
+        &mut self,
+        params: &SearchParams,
+        payload: impl Fn(&Uuid) -> Option<&'a Payload>,
+        vector: impl Fn(&Uuid) -> Option<Arc<Vec<f32>>>,
+    ) {
+        if let Some(selector) = &params.payload_selector {
+            self.payloads = Some(
+                (self.hits.iter())
+                    .map(|(key, _)| {
+                        payload(key).map_or_else(Payload::new, |p| payload::select(p, selector))
+                    })
+                    .collect(),
+            );
+        }
+        if params.with_vector {
+            self.vectors = Some(
+                (self.hits.iter())
+                    .map(|(key, _)| vector(key).unwrap_or_default())
+                    .collect(),
+            );
+        }
+    }
+
+    /// Sérialise les résultats en JSON compact.
+    ///
+    /// # Retourne
+    /// * String - Tableau `[{"id":"…","score":0.93},…]`, du meilleur au moins bon ; un
+    ///   score non fini devient `null`. Les charges utiles et vecteurs joints
+    ///   ([`SearchResults::payloads`], [`SearchResults::vectors`]) s'y ajoutent sous
+    ///   `"payload"` et `"vector"`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        json::write_array(&mut out, 0..self.hits.len(), |out, i| {
+            self.write_hit_json(out, i, false)
+        });
+        out
+    }
+
+    /// Sérialise les résultats en JSON indenté, un résultat par ligne.
+    ///
+    /// # Retourne
+    /// * String - Même contenu que [`SearchResults::to_json`].
+    pub fn to_json_pretty(&self) -> String {
+        if self.hits.is_empty() {
+            return "[]".to_string();
+        }
+        let mut out = String::from("[\n");
+        for i in 0..self.hits.len() {
+            out.push_str("  ");
+            self.write_hit_json(&mut out, i, true);
+            out.push_str(if i + 1 < self.hits.len() { ",\n" } else { "\n" });
+        }
+        out.push(']');
+        out
+    }
+
+    /// Écrit le résultat de rang `i` en objet JSON.
+    fn write_hit_json(&self, out: &mut String, i: usize, pretty: bool) {
+        let separator = if pretty { ": " } else { ":" };
+        let (key, score) = &self.hits[i];
+        out.push_str("{\"id\"");
+        out.push_str(separator);
+        json::write_string(out, &key.to_string());
+        out.push_str(if pretty { ", \"score\"" } else { ",\"score\"" });
+        out.push_str(separator);
+        json::write_f32(out, *score);
+        if let Some(payload) = self.payloads.as_ref().and_then(|payloads| payloads.get(i)) {
+            out.push_str(if pretty {
+                ", \"payload\""
+            } else {
+                ",\"payload\""
+            });
+            out.push_str(separator);
+            json::write_value(out, &Value::Object(payload.clone()));
+        }
+        if let Some(vector) = self.vectors.as_ref().and_then(|vectors| vectors.get(i)) {
+            out.push_str(if pretty {
+                ", \"vector\""
+            } else {
+                ",\"vector\""
+            });
+            out.push_str(separator);
+            json::write_array(out, vector.as_slice(), |out, x| json::write_f32(out, *x));
+        }
+        out.push('}');
+    }
+
+    /// Sérialise les résultats en CSV.
+    ///
+    /// # Retourne
+    /// * String - En-tête `id,score` puis une ligne par résultat ; un score non fini
+    ///   donne un champ vide.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        csv::write_record(&mut out, ["id", "score"]);
+        for (key, score) in &self.hits {
+            let score = if score.is_finite() {
+                float::shortest_f32(*score)
+            } else {
+                String::new()
+            };
+            csv::write_record(&mut out, [key.to_string().as_str(), score.as_str()]);
+        }
+        out
+    }
+}
+
+impl fmt::Display for SearchResults {
+    /// Affiche un tableau rang / identifiant / score, une ligne par résultat.
+    ///
+    /// Les scores ont [`float::DISPLAY_DIGITS`] chiffres significatifs, ou autant que la
+    /// précision demandée : `format!("{:.6}", results)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = f.precision().unwrap_or(float::DISPLAY_DIGITS);
+        if self.hits.is_empty() {
+            writeln!(f, "aucun résultat")?;
+        } else {
+            writeln!(f, "{:>4}  {:<36}  {:>10}", "rang", "identifiant", "score")?;
+            for (rank, (key, score)) in self.hits.iter().enumerate() {
+                let score = float::significant(f64::from(*score), digits);
+                writeln!(f, "{:>4}  {:<36}  {:>10}", rank + 1, key, score)?;
+            }
+        }
+        if self.truncated {
+            writeln!(f, "(recherche interrompue par le budget de temps)")?;
+        }
+        if self.sampled {
+            match self.estimated_total_matches {
+                Some(estimate) => writeln!(
+                    f,
+                    "(recherche sur un échantillon ; environ {} documents atteignent le seuil)",
+                    estimate
+                )?,
+                None => writeln!(f, "(recherche sur un échantillon)")?,
+            }
+        }
+        if let Some(context) = &self.context {
+            writeln!(f, "(requête {})", context)?;
+        }
+        if !self.query_transforms.is_empty() {
+            writeln!(f, "(requête transformée : {})", self.query_transforms.join(" → "))?;
+        }
+        if let Some(low) = &self.low_confidence {
+            writeln!(
+                f,
+                "(confiance faible : écart de {} entre les deux premiers, {} demandé)",
+                float::significant(f64::from(low.margin), digits),
+                float::significant(f64::from(low.min_margin), digits)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl IntoIterator for SearchResults {
+    type Item = (Uuid, f32);
+    type IntoIter = std::vec::IntoIter<(Uuid, f32)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hits.into_iter()
+    }
+}
+
+/// Traitement des documents qui ne possèdent pas le champ de regroupement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingGroupField {
+    /// Chaque document sans le champ forme un groupe à lui seul.
+    #[default]
+    Singleton,
+    /// Les documents sans le champ sont ignorés.
+    Skip,
+}
+
+/// Groupe de résultats partageant la même valeur d'un champ de charge utile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    /// Valeur commune du champ, ou `None` pour un groupe singleton sans le champ.
+    pub value: Option<Value>,
+    /// Meilleurs résultats du groupe, par score décroissant.
+    pub hits: Document,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::database::BaseDeDonnees;
+    use crate::filter::Filter;
+    use crate::payload::{Payload, Value};
+    use crate::sharded::ShardedCollection;
+    use crate::store::{VectorStore, VectorStoreMut};
+    use crate::synthetic::VectorGenerator;
+
+    const METRICS: [Metric; 4] = [Metric::Cosine, Metric::Dot, Metric::Euclidean, Metric::Manhattan];
+
+    fn params(k: usize, score_mode: ScoreMode) -> SearchParams {
+        SearchParams {
+            k,
+            score_mode,
+            ..Default::default()
+        }
+    }
+
+    fn keys(hits: &Document) -> Vec<Uuid> {
+        hits.iter().map(|(key, _)| *key).collect()
+    }
+
+    #[test]
+    fn score_modes_keep_the_order_of_raw_scores() {
+        for metric in METRICS {
+            let mut collection = Collection::new().with_metric(metric);
+            let mut sharded = ShardedCollection::new(3).unwrap().with_metric(metric);
+            let mut generator = VectorGenerator::new(5, 17);
+            for _ in 0..200 {
+                let (key, vector) = (generator.uuid(), generator.vector());
+                collection.upsert(key, vector.clone()).unwrap();
+                VectorStoreMut::upsert(&mut sharded, key, vector).unwrap();
+            }
+            let query = generator.vector();
+            let stores: [&dyn VectorStore; 2] = [&collection, &sharded];
+            for store in stores {
+                let raw = store.search_with(&query, &params(20, ScoreMode::Raw)).unwrap().hits;
+                let normalized = (store.search_with(&query, &params(20, ScoreMode::Normalized)))
+                    .unwrap()
+                    .hits;
+                let ranked = store.search_with(&query, &params(20, ScoreMode::Rank)).unwrap().hits;
+                assert_eq!(keys(&normalized), keys(&raw), "{:?}", metric);
+                assert_eq!(keys(&ranked), keys(&raw), "{:?}", metric);
+                for ((_, raw), (_, normalized)) in raw.iter().zip(&normalized) {
+                    assert_eq!(*normalized, metric.normalize(*raw));
+                    assert!((0.0..=1.0).contains(normalized));
+                }
+                // Les scores normalisés décroissent du meilleur au moins bon.
+                assert!(normalized.windows(2).all(|w| w[0].1 >= w[1].1), "{:?}", metric);
+                let ranks: Vec<f32> = ranked.iter().map(|(_, score)| *score).collect();
+                assert_eq!(ranks, (1..=20).map(|rank| rank as f32).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn threshold_applies_to_the_raw_score() {
+        let mut collection = Collection::new().with_metric(Metric::Euclidean);
+        let keys: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for (key, x) in keys.iter().zip([1.0, 2.0, 3.0]) {
+            collection.upsert(*key, [x]).unwrap();
+        }
+        let params = SearchParams {
+            k: 3,
+            score_threshold: Some(1.5),
+            score_mode: ScoreMode::Normalized,
+            ..Default::default()
+        };
+        // Distances 1, 2 et 3 à l'origine : seule la première passe le seuil de 1.5.
+        let hits = collection.search_with([0.0], &params).unwrap().hits;
+        assert_eq!(hits, [(keys[0], 0.5)]);
+    }
+
+    /// Résultats d'une recherche sur des documents dont la charge utile contient des
+    /// caractères à échapper.
+    fn results_with_payloads() -> SearchResults {
+        let mut collection = Collection::new();
+        let mut generator = VectorGenerator::new(4, 5);
+        for i in 0..12 {
+            let mut payload = Payload::new();
+            let title = format!("titre \"{}\", avec virgule\net retour à la ligne", i);
+            payload.insert("titre".to_string(), Value::String(title));
+            collection
+                .upsert_with_payload(generator.uuid(), generator.vector(), payload)
+                .unwrap();
+        }
+        let params = SearchParams {
+            k: 8,
+            payload_selector: Some(vec!["titre".to_string()]),
+            with_vector: true,
+            ..Default::default()
+        };
+        collection.search_with(generator.vector(), &params).unwrap()
+    }
+
+    /// Relit la sortie JSON et retourne, pour chaque résultat, son identifiant, son score,
+    /// sa charge utile et son vecteur.
+    fn parse_json(text: &str) -> Vec<(Uuid, f32, Payload, Vec<f32>)> {
+        let Value::Array(hits) = json::parse(text).unwrap() else {
+            panic!("tableau attendu : {}", text);
+        };
+        (hits.into_iter())
+            .map(|hit| {
+                let Value::Object(mut hit) = hit else { panic!("objet attendu") };
+                let id = hit["id"].as_str().unwrap().parse().unwrap();
+                let score = hit["score"].as_f64().unwrap() as f32;
+                let Some(Value::Object(payload)) = hit.remove("payload") else { panic!() };
+                let Some(Value::Array(vector)) = hit.remove("vector") else { panic!() };
+                let vector = vector.iter().map(|x| x.as_f64().unwrap() as f32).collect();
+                (id, score, payload, vector)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn json_output_parses_back_to_the_results() {
+        let results = results_with_payloads();
+        let expected: Vec<(Uuid, f32, Payload, Vec<f32>)> = (0..results.hits.len())
+            .map(|i| {
+                let (key, score) = results.hits[i];
+                let payload = results.payloads.as_ref().unwrap()[i].clone();
+                let vector = results.vectors.as_ref().unwrap()[i].to_vec();
+                (key, score, payload, vector)
+            })
+            .collect();
+        assert_eq!(expected.len(), 8);
+        assert_eq!(parse_json(&results.to_json()), expected);
+        assert_eq!(parse_json(&results.to_json_pretty()), expected);
+        assert_eq!(results.to_json_pretty().lines().count(), 8 + 2);
+
+        let empty = Collection::new().search_with([1.0], &SearchParams::default()).unwrap();
+        assert_eq!((empty.to_json(), empty.to_json_pretty()), ("[]".into(), "[]".into()));
+    }
+
+    #[test]
+    fn csv_output_has_a_header_and_one_row_per_hit() {
+        let results = results_with_payloads();
+        let csv = results.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("id,score"));
+        let rows: Vec<(Uuid, f32)> = lines
+            .map(|line| {
+                let (id, score) = line.split_once(',').unwrap();
+                (id.parse().unwrap(), score.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(rows, results.hits);
+    }
+
+    /// Collection de produits scalaires dont le document `i` obtient le score `i` pour la
+    /// requête `[1.0, 0.0]`.
+    fn scored(count: usize) -> (Collection, Vec<Uuid>) {
+        let mut collection = Collection::new().with_metric(Metric::Dot);
+        let keys: Vec<Uuid> = (0..count as u128).map(Uuid::from_u128).collect();
+        for (i, key) in keys.iter().enumerate() {
+            collection.upsert(*key, [i as f32, 1.0]).unwrap();
+        }
+        (collection, keys)
+    }
+
+    #[test]
+    fn explicit_values_win_over_the_collection_defaults() {
+        let (mut collection, keys) = scored(10);
+        collection.set_default_params(SearchParams {
+            k: 3,
+            score_threshold: Some(5.0),
+            score_mode: ScoreMode::Rank,
+            ..Default::default()
+        });
+        let request = [1.0, 0.0];
+        let found = |results: SearchResults| -> Vec<Uuid> {
+            results.hits.iter().map(|(key, _)| *key).collect()
+        };
+
+        let defaults = collection
+            .search_with_defaults(request, &SearchOverrides::default())
+            .unwrap();
+        assert_eq!(found(defaults.clone()), [keys[9], keys[8], keys[7]]);
+        assert_eq!(defaults.hits[0].1, 1.0);
+        // k précisé, seuil et mode par défaut.
+        let hits = collection.search(request, 8).unwrap();
+        assert_eq!(hits.len(), 5);
+        assert_eq!(hits[4], (keys[5], 5.0));
+        let overrides = SearchOverrides {
+            k: Some(10),
+            score_threshold: Some(2.0),
+            score_mode: Some(ScoreMode::Raw),
+            ..Default::default()
+        };
+        let results = collection.search_with_defaults(request, &overrides).unwrap();
+        assert_eq!(found(results.clone()).len(), 8);
+        assert_eq!(results.hits[0], (keys[9], 9.0));
+        // search_with ignore les valeurs par défaut.
+        let all = collection.search_with(request, &SearchParams::new(10)).unwrap();
+        assert_eq!(all.hits.len(), 10);
+    }
+
+    #[test]
+    fn collection_defaults_survive_a_save() {
+        let (mut collection, _) = scored(4);
+        let defaults = SearchParams {
+            k: 20,
+            score_threshold: Some(0.35),
+            time_budget: Some(Duration::from_millis(15)),
+            score_mode: ScoreMode::Normalized,
+            ..Default::default()
+        };
+        collection.set_default_params(defaults.clone());
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap();
+        assert_eq!(loaded.default_params(), &defaults);
+        assert!(format!("{:?}", loaded).contains("score_threshold: Some(0.35)"));
+    }
+
+    #[test]
+    fn short_lists_are_reported_or_refused() {
+        let (collection, _) = scored(3);
+        let request = [1.0, 0.0];
+        let search = |k, require_exact_k| {
+            let params = SearchParams {
+                k,
+                require_exact_k,
+                ..Default::default()
+            };
+            collection.search_with(request, &params)
+        };
+        // Assez de documents, avec ou sans exigence.
+        for exact in [false, true] {
+            let results = search(2, exact).unwrap();
+            assert_eq!((results.hits.len(), results.requested_k, results.available), (2, 2, 3));
+        }
+        // Trop peu : liste courte signalée, ou erreur.
+        let short = search(10, false).unwrap();
+        assert_eq!((short.hits.len(), short.requested_k, short.available), (3, 10, 3));
+        assert!(matches!(
+            search(10, true),
+            Err(Error::InsufficientCandidates {
+                requested: 10,
+                available: 3
+            })
+        ));
+        // k = 0 n'est refusé qu'avec l'exigence.
+        let empty = search(0, false).unwrap();
+        assert!(empty.hits.is_empty());
+        assert_eq!(empty.available, 3);
+        assert!(matches!(search(0, true), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn sharded_searches_count_the_candidates_of_every_shard() {
+        let mut sharded = ShardedCollection::new(3).unwrap();
+        for i in 0..5u128 {
+            VectorStoreMut::upsert(&mut sharded, Uuid::from_u128(i), vec![1.0, i as f32]).unwrap();
+        }
+        let params = SearchParams {
+            k: 4,
+            require_exact_k: true,
+            ..Default::default()
+        };
+        let results = sharded.search_with(&[1.0, 0.0], &params).unwrap();
+        assert_eq!((results.hits.len(), results.available), (4, 5));
+        let params = SearchParams { k: 6, ..params };
+        assert!(matches!(
+            sharded.search_with(&[1.0, 0.0], &params),
+            Err(Error::InsufficientCandidates {
+                requested: 6,
+                available: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn timing_is_measured_only_on_request() {
+        let mut collection = Collection::new();
+        let mut sharded = ShardedCollection::new(3).unwrap();
+        let mut generator = VectorGenerator::new(32, 158);
+        for i in 0..4000 {
+            let (key, vector) = (generator.uuid(), generator.vector());
+            let payload = Payload::from([("pair".to_string(), Value::Bool(i % 2 == 0))]);
+            collection
+                .upsert_with_payload(key, vector.clone(), payload)
+                .unwrap();
+            VectorStoreMut::upsert(&mut sharded, key, vector).unwrap();
+        }
+        let query = generator.vector();
+        assert_eq!(collection.search_with(&query, &SearchParams::new(10)).unwrap().timing, None);
+
+        let params = SearchParams {
+            with_timing: true,
+            ..SearchParams::new(10)
+        };
+        let plausible = |timing: SearchTiming, scored: usize| {
+            assert_eq!(timing.candidates_scored, scored);
+            assert!(timing.total_us > 0, "{}", timing);
+            assert!(
+                timing.filter_us + timing.scoring_us + timing.topk_us <= timing.total_us,
+                "{}",
+                timing
+            );
+        };
+        let results = collection.search_with(&query, &params).unwrap();
+        plausible(results.timing.unwrap(), 4000);
+        let filter = Filter::eq("pair", true);
+        let results = collection.search_filtered(&query, &filter, &params).unwrap();
+        plausible(results.timing.unwrap(), 2000);
+        // Les partitions additionnent leurs documents évalués.
+        let timing = sharded.search_with(&query, &params).unwrap().timing.unwrap();
+        assert_eq!(timing.candidates_scored, 4000);
+        assert!(timing.total_us >= timing.topk_us);
+    }
+
+    #[test]
+    fn hits_carry_only_the_selected_fields_and_shared_vectors() {
+        let mut collection = Collection::new();
+        let mut generator = VectorGenerator::new(4, 184);
+        let auteur = Value::Object(
+            [
+                ("nom".to_string(), Value::String("Ada".to_string())),
+                ("pays".to_string(), Value::String("UK".to_string())),
+            ]
+            .into(),
+        );
+        for i in 0..20 {
+            let payload = Payload::from([
+                ("titre".to_string(), Value::String(format!("t{}", i))),
+                ("corps".to_string(), Value::String("long".repeat(100))),
+                ("auteur".to_string(), auteur.clone()),
+            ]);
+            collection
+                .upsert_with_payload(generator.uuid(), generator.vector(), payload)
+                .unwrap();
+        }
+        let query = generator.vector();
+
+        let results = collection.search_with(&query, &SearchParams::new(5)).unwrap();
+        assert_eq!((results.payloads, results.vectors), (None, None));
+        // Aucun résultat ne garde de référence aux vecteurs stockés.
+        assert!(collection.documents.values().all(|v| Arc::strong_count(v) == 1));
+
+        let params = SearchParams {
+            payload_selector: Some(vec!["titre".to_string(), "auteur.nom".to_string()]),
+            with_vector: true,
+            ..SearchParams::new(5)
+        };
+        let results = collection.search_with(&query, &params).unwrap();
+        let payloads = results.payloads.as_ref().unwrap();
+        let vectors = results.vectors.as_ref().unwrap();
+        assert_eq!((payloads.len(), vectors.len()), (5, 5));
+        for (((key, _), payload), vector) in results.hits.iter().zip(payloads).zip(vectors) {
+            assert_eq!(payload.keys().collect::<Vec<_>>(), ["auteur", "titre"]);
+            assert_eq!(
+                payload["auteur"],
+                Value::Object([("nom".to_string(), Value::String("Ada".to_string()))].into())
+            );
+            // Le vecteur joint est celui de la collection, sans copie.
+            assert!(Arc::ptr_eq(vector, &collection.documents[key]));
+        }
+        assert!(results.to_json().contains(r#""payload":{"auteur":{"nom":"Ada"}"#));
+        assert!(!results.to_json().contains("corps"));
+    }
+
+    #[test]
+    fn deterministic_scores_do_not_depend_on_the_thread_count() {
+        let mut generator = VectorGenerator::new(24, 196);
+        let items: Vec<(Uuid, Vec<f32>, Payload)> = (0..6000)
+            .map(|i| {
+                let date = Value::Number(1_700_000_000.0 - 3600.0 * i as f64);
+                let payload = Payload::from([("date".to_string(), date)]);
+                (generator.uuid(), generator.vector(), payload)
+            })
+            .collect();
+        let query = generator.vector();
+        let decay = DecayParams::new("date", std::time::Duration::from_secs(86_400))
+            .at(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000));
+        for (metric, score_mode) in [
+            (Metric::Dot, ScoreMode::Normalized),
+            (Metric::Cosine, ScoreMode::Raw),
+            (Metric::Euclidean, ScoreMode::Normalized),
+        ] {
+            let params = SearchParams {
+                deterministic_math: true,
+                score_mode,
+                decay: Some(decay.clone()),
+                ..SearchParams::new(50)
+            };
+            let bits = |threads: usize| {
+                let mut collection = Collection::new()
+                    .with_metric(metric)
+                    .with_runtime(crate::parallel::SearchRuntime::new(threads));
+                for (key, vector, payload) in &items {
+                    collection
+                        .upsert_with_payload(*key, vector.clone(), payload.clone())
+                        .unwrap();
+                }
+                let hits = collection.search_with(&query, &params).unwrap().hits;
+                hits.iter()
+                    .map(|(key, score)| (*key, score.to_bits()))
+                    .collect::<Vec<_>>()
+            };
+            let expected = bits(1);
+            assert_eq!(expected.len(), 50);
+            for threads in [1, 2, 3, 8] {
+                assert_eq!(bits(threads), expected, "{:?}, {} threads", metric, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_cover_the_requested_fraction_reproducibly() {
+        let mut generator = VectorGenerator::new(4, 199);
+        let mut collection = Collection::new().with_metric(Metric::Dot);
+        let mut sharded = ShardedCollection::new(4).unwrap().with_metric(Metric::Dot);
+        // Des identifiants consécutifs puis aléatoires : l'échantillon ne dépend pas de
+        // leur forme.
+        let ids: Vec<Uuid> = (0..10_000)
+            .map(Uuid::from_u128)
+            .chain((0..10_000).map(|_| generator.uuid()))
+            .collect();
+        for key in &ids {
+            let vector = generator.vector();
+            collection.upsert(*key, vector.clone()).unwrap();
+            VectorStoreMut::upsert(&mut sharded, *key, vector).unwrap();
+        }
+        let query = generator.vector();
+        let sampled = |seed, threshold| SearchParams {
+            sample_fraction: Some(0.25),
+            sample_seed: seed,
+            score_threshold: threshold,
+            ..SearchParams::new(ids.len())
+        };
+
+        let results = collection.search_with(&query, &sampled(7, None)).unwrap();
+        assert!(results.sampled);
+        assert_eq!(results.estimated_total_matches, None);
+        // Écart de plus de quatre écarts-types (4 × 61) autour de 5000 : improbable.
+        assert!(results.available.abs_diff(5000) < 250, "{}", results.available);
+        let in_sample = |range: std::ops::Range<usize>| {
+            (results.hits.iter())
+                .filter(|(key, _)| ids[range.clone()].contains(key))
+                .count()
+        };
+        assert!(in_sample(0..10_000).abs_diff(2500) < 180);
+        assert!(in_sample(10_000..20_000).abs_diff(2500) < 180);
+
+        // La même graine donne le même échantillon, dans tous les types de collection.
+        let again = collection.search_with(&query, &sampled(7, None)).unwrap();
+        assert_eq!(again.hits, results.hits);
+        let sharded_results = VectorStore::search_with(&sharded, &query, &sampled(7, None));
+        assert_eq!(sharded_results.unwrap().hits, results.hits);
+        let other = collection.search_with(&query, &sampled(8, None)).unwrap();
+        assert_ne!(keys(&other.hits), keys(&results.hits));
+
+        // Avec un seuil, le nombre de documents qui l'atteignent est extrapolé.
+        let exact = collection.search_with(&query, &SearchParams {
+            score_threshold: Some(0.0),
+            ..SearchParams::new(ids.len())
+        });
+        let exact = exact.unwrap().available;
+        let estimate = collection.search_with(&query, &sampled(7, Some(0.0))).unwrap();
+        let estimate = estimate.estimated_total_matches.unwrap();
+        assert!(estimate.abs_diff(exact) < exact / 10, "{} pour {}", estimate, exact);
+
+        let budgeted = SearchParams {
+            time_budget: Some(std::time::Duration::from_secs(10)),
+            ..sampled(7, None)
+        };
+        assert_eq!(collection.search_with(&query, &budgeted).unwrap().hits, results.hits);
+        for fraction in [0.0, 1.5, f32::NAN] {
+            let params = SearchParams {
+                sample_fraction: Some(fraction),
+                ..SearchParams::new(1)
+            };
+            assert!(matches!(
+                collection.search_with(&query, &params),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+    }
+
+    fn margin_search(metric: Metric, points: &[f32], min_margin: Option<f32>) -> SearchResults {
+        let mut collection = Collection::new().with_metric(metric);
+        for (i, x) in points.iter().enumerate() {
+            collection.upsert(Uuid::from_u128(i as u128), [*x]).unwrap();
+        }
+        let params = SearchParams {
+            k: 3,
+            min_margin,
+            ..Default::default()
+        };
+        collection.search_with([1.0], &params).unwrap()
+    }
+
+    #[test]
+    fn margins_follow_the_metric_direction() {
+        // Produit scalaire : 4 puis 2, le premier devance le deuxième de 2.
+        let results = margin_search(Metric::Dot, &[2.0, 4.0, 1.0], None);
+        assert_eq!(results.top_margin(), Some(2.0));
+        assert_eq!(results.relative_margin(), Some(0.5));
+        // Distance : 1 puis 3 ; l'écart reste positif bien que le meilleur score soit
+        // le plus petit.
+        let results = margin_search(Metric::Euclidean, &[4.0, 2.0, 10.0], None);
+        assert_eq!(keys(&results.hits)[..2], [Uuid::from_u128(1), Uuid::from_u128(0)]);
+        assert_eq!(results.top_margin(), Some(2.0));
+        assert_eq!(results.relative_margin(), Some(2.0 / 3.0));
+        let results = margin_search(Metric::Euclidean, &[4.0, 2.0], Some(2.5));
+        assert_eq!(
+            results.low_confidence,
+            Some(LowConfidence {
+                margin: 2.0,
+                min_margin: 2.5
+            })
+        );
+    }
+
+    #[test]
+    fn ties_and_single_hits_have_no_usable_margin() {
+        for metric in METRICS {
+            let tied = margin_search(metric, &[3.0, 3.0], Some(0.1));
+            assert_eq!(tied.top_margin(), Some(0.0), "{:?}", metric);
+            assert_eq!(tied.low_confidence.map(|low| low.margin), Some(0.0));
+            assert!(margin_search(metric, &[3.0, 3.0], Some(0.0)).low_confidence.is_none());
+
+            let single = margin_search(metric, &[3.0], Some(0.1));
+            assert_eq!((single.top_margin(), single.relative_margin()), (None, None));
+            assert!(single.low_confidence.is_none());
+        }
+        // Deux scores nuls : l'écart relatif n'est pas défini.
+        let zero = margin_search(Metric::Dot, &[0.0, 0.0], None);
+        assert_eq!((zero.top_margin(), zero.relative_margin()), (Some(0.0), None));
+    }
+
+    #[test]
+    fn negative_or_infinite_min_margin_is_refused() {
+        let mut collection = Collection::new();
+        collection.upsert(Uuid::new_v4(), [1.0]).unwrap();
+        for min_margin in [-0.1, f32::INFINITY, f32::NAN] {
+            let params = SearchParams {
+                min_margin: Some(min_margin),
+                ..Default::default()
+            };
+            assert!(matches!(
+                collection.search_with([1.0], &params),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+    }
+}
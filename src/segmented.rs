@@ -0,0 +1,348 @@
+//! Table par `Uuid` découpée en segments partagés, pour les tables d'une collection qui
+//! grandissent avec ses documents : cloner la table ne copie que les pointeurs de ses
+//! segments, et une écriture sur une table partagée avec une vue figée
+//! ([`BaseDeDonnees::snapshot`](crate::BaseDeDonnees::snapshot)) ne copie que le
+//! segment qu'elle touche, soit 1/256 de la table. Une petite table n'a qu'un segment,
+//! copié en entier, tant qu'elle ne dépasse pas [`SPLIT_LEN`] éléments.
+
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::iter::FusedIterator;
+use std::mem;
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+/// Nombre de segments d'une grande table, choisis par le dernier octet de l'`Uuid`.
+const SEGMENTS: usize = 256;
+
+/// Nombre d'éléments au-delà duquel l'unique segment d'une table est découpé ; le
+/// découpage n'a lieu qu'une fois et n'est pas défait.
+const SPLIT_LEN: usize = 4096;
+
+/// Segment d'une [`SegmentedMap`], partagé par ses clones.
+type Segment<V> = Arc<HashMap<Uuid, V>>;
+
+/// Table `Uuid -> V` dont les segments sont partagés par les clones jusqu'à leur première
+/// modification.
+#[derive(Debug)]
+pub(crate) struct SegmentedMap<V> {
+    /// Aucun segment tant que la table n'a jamais reçu d'élément, un seul jusqu'à
+    /// `SPLIT_LEN` éléments, `SEGMENTS` ensuite.
+    segments: Vec<Segment<V>>,
+    len: usize,
+}
+
+impl<V> Default for SegmentedMap<V> {
+    fn default() -> Self {
+        SegmentedMap {
+            segments: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<V> Clone for SegmentedMap<V> {
+    /// Ne copie que les pointeurs des segments.
+    fn clone(&self) -> Self {
+        SegmentedMap {
+            segments: self.segments.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<V: PartialEq> PartialEq for SegmentedMap<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<V> SegmentedMap<V> {
+    /// Segment de la clé `key`.
+    fn segment(&self, key: &Uuid) -> usize {
+        match self.segments.len() {
+            SEGMENTS => usize::from(key.as_bytes()[15]),
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn get(&self, key: &Uuid) -> Option<&V> {
+        self.segments.get(self.segment(key))?.get(key)
+    }
+
+    pub(crate) fn get_key_value(&self, key: &Uuid) -> Option<(&Uuid, &V)> {
+        self.segments.get(self.segment(key))?.get_key_value(key)
+    }
+
+    pub(crate) fn contains_key(&self, key: &Uuid) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            segments: self.segments.iter(),
+            current: None,
+            remaining: self.len,
+        }
+    }
+
+    pub(crate) fn keys(&self) -> impl ExactSizeIterator<Item = &Uuid> + Clone + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub(crate) fn values(&self) -> impl ExactSizeIterator<Item = &V> + Clone + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Vide la table ; les segments partagés restent à leurs autres détenteurs.
+    pub(crate) fn clear(&mut self) {
+        self.segments = Vec::new();
+        self.len = 0;
+    }
+
+    /// Nombre d'éléments que la table peut contenir sans réallocation.
+    pub(crate) fn capacity(&self) -> usize {
+        self.segments.iter().map(|segment| segment.capacity()).sum()
+    }
+
+    /// Octets réservés par les segments au-delà de leurs éléments.
+    pub(crate) fn unused_bytes(&self) -> usize {
+        (self.capacity() - self.len) * mem::size_of::<(Uuid, V)>()
+    }
+
+    /// Nombre de segments partagés avec `other`.
+    #[cfg(test)]
+    pub(crate) fn shared_segments(&self, other: &Self) -> usize {
+        (self.segments.iter().zip(&other.segments))
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count()
+    }
+
+    /// Rend la place inutilisée des segments que la table ne partage pas.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        (self.segments.iter_mut())
+            .filter_map(Arc::get_mut)
+            .for_each(HashMap::shrink_to_fit);
+    }
+}
+
+impl<V: Clone> SegmentedMap<V> {
+    /// Segment de `key`, copié s'il est partagé.
+    fn segment_mut(&mut self, key: &Uuid) -> &mut HashMap<Uuid, V> {
+        if self.segments.is_empty() {
+            self.segments.push(Arc::default());
+        }
+        let segment = self.segment(key);
+        Arc::make_mut(&mut self.segments[segment])
+    }
+
+    /// Répartit l'unique segment entre `SEGMENTS` segments.
+    fn split(&mut self) {
+        let Some(single) = self.segments.pop() else {
+            return;
+        };
+        let mut segments: Vec<HashMap<Uuid, V>> = (0..SEGMENTS)
+            .map(|_| HashMap::with_capacity(self.len.div_ceil(SEGMENTS)))
+            .collect();
+        for (key, value) in Arc::unwrap_or_clone(single) {
+            segments[usize::from(key.as_bytes()[15])].insert(key, value);
+        }
+        self.segments = segments.into_iter().map(Arc::new).collect();
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &Uuid) -> Option<&mut V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.segment_mut(key).get_mut(key)
+    }
+
+    pub(crate) fn insert(&mut self, key: Uuid, value: V) -> Option<V> {
+        let previous = self.segment_mut(&key).insert(key, value);
+        self.len += usize::from(previous.is_none());
+        if self.len > SPLIT_LEN && self.segments.len() == 1 {
+            self.split();
+        }
+        previous
+    }
+
+    pub(crate) fn remove(&mut self, key: &Uuid) -> Option<V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        let removed = self.segment_mut(key).remove(key);
+        self.len -= usize::from(removed.is_some());
+        removed
+    }
+
+    /// Parcourt les valeurs pour les modifier ; tous les segments partagés sont copiés.
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut V> + '_ {
+        (self.segments.iter_mut()).flat_map(|segment| Arc::make_mut(segment).values_mut())
+    }
+
+    /// Réserve la place de `additional` éléments, répartis entre les segments.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+        if self.segments.is_empty() {
+            self.segments.push(Arc::default());
+        }
+        if self.segments.len() == 1 && self.len + additional > SPLIT_LEN {
+            self.split();
+        }
+        let share = additional.div_ceil(self.segments.len());
+        (self.segments.iter_mut()).for_each(|segment| Arc::make_mut(segment).reserve(share));
+    }
+}
+
+impl<V> std::ops::Index<&Uuid> for SegmentedMap<V> {
+    type Output = V;
+
+    fn index(&self, key: &Uuid) -> &V {
+        self.get(key).expect("clé absente de la table")
+    }
+}
+
+impl<V: Clone> FromIterator<(Uuid, V)> for SegmentedMap<V> {
+    fn from_iter<I: IntoIterator<Item = (Uuid, V)>>(iter: I) -> Self {
+        let mut map = SegmentedMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<V: Clone> Extend<(Uuid, V)> for SegmentedMap<V> {
+    fn extend<I: IntoIterator<Item = (Uuid, V)>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|(key, value)| {
+            self.insert(key, value);
+        });
+    }
+}
+
+impl<V: Clone> IntoIterator for SegmentedMap<V> {
+    type Item = (Uuid, V);
+    type IntoIter = std::iter::FlatMap<
+        std::vec::IntoIter<Segment<V>>,
+        hash_map::IntoIter<Uuid, V>,
+        fn(Segment<V>) -> hash_map::IntoIter<Uuid, V>,
+    >;
+
+    /// Parcourt les éléments en les déplaçant ; les segments partagés sont copiés.
+    fn into_iter(self) -> Self::IntoIter {
+        let segment: fn(Segment<V>) -> hash_map::IntoIter<Uuid, V> =
+            |segment| Arc::unwrap_or_clone(segment).into_iter();
+        self.segments.into_iter().flat_map(segment)
+    }
+}
+
+impl<'a, V> IntoIterator for &'a SegmentedMap<V> {
+    type Item = (&'a Uuid, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Parcours de [`SegmentedMap::iter`], dans un ordre arbitraire comme celui d'une `HashMap`.
+pub(crate) struct Iter<'a, V> {
+    segments: std::slice::Iter<'a, Segment<V>>,
+    current: Option<hash_map::Iter<'a, Uuid, V>>,
+    remaining: usize,
+}
+
+impl<V> Clone for Iter<'_, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            segments: self.segments.clone(),
+            current: self.current.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a Uuid, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(item);
+            }
+            self.current = Some(self.segments.next()?.iter());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<V> ExactSizeIterator for Iter<'_, V> {}
+
+impl<V> FusedIterator for Iter<'_, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn behaves_like_a_hash_map() {
+        let keys: Vec<Uuid> = (0..1000).map(|_| Uuid::new_v4()).collect();
+        let mut map: SegmentedMap<usize> = keys.iter().copied().zip(0..).collect();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.iter().len(), 1000);
+        assert_eq!(map.get(&keys[10]), Some(&10));
+        assert_eq!(map.insert(keys[10], 11), Some(10));
+        assert_eq!(map.remove(&keys[20]), Some(20));
+        assert_eq!(map.remove(&keys[20]), None);
+        assert!(map.get_mut(&Uuid::new_v4()).is_none());
+        assert_eq!(map.len(), 999);
+        assert_eq!(map.keys().count(), 999);
+        let sum: usize = map.values().sum();
+        assert_eq!(sum, (0..1000).sum::<usize>() + 1 - 20);
+        map.clear();
+        assert!(map.is_empty() && map.get(&keys[0]).is_none());
+    }
+
+    #[test]
+    fn small_tables_keep_a_single_segment() {
+        let mut map: SegmentedMap<usize> = (0..SPLIT_LEN).map(|i| (Uuid::new_v4(), i)).collect();
+        assert_eq!(map.segments.len(), 1);
+        let key = Uuid::new_v4();
+        map.insert(key, 0);
+        assert_eq!(map.segments.len(), SEGMENTS);
+        assert_eq!(map.len(), SPLIT_LEN + 1);
+        assert_eq!(map.iter().count(), SPLIT_LEN + 1);
+        assert_eq!(map.get(&key), Some(&0));
+
+        let mut reserved = SegmentedMap::<usize>::default();
+        reserved.reserve(SPLIT_LEN * 2);
+        assert_eq!(reserved.segments.len(), SEGMENTS);
+        assert!(reserved.capacity() >= SPLIT_LEN * 2);
+    }
+
+    #[test]
+    fn writes_copy_only_the_touched_segment() {
+        let map: SegmentedMap<usize> = (0..SPLIT_LEN * 2).map(|i| (Uuid::new_v4(), i)).collect();
+        let frozen = map.clone();
+        let mut map = map;
+        let key = *map.keys().next().unwrap();
+        *map.get_mut(&key).unwrap() += 1;
+
+        assert_eq!(map.shared_segments(&frozen), SEGMENTS - 1);
+        assert_eq!(frozen.get(&key).map(|v| v + 1), map.get(&key).copied());
+        assert!(map != frozen);
+    }
+}
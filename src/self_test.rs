@@ -0,0 +1,110 @@
+//! Vérification de bout en bout du chemin d'écriture et de recherche d'une base.
+
+use std::fmt;
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, Result};
+use crate::search::micros;
+
+/// Dimension du document témoin de [`BaseDeDonnees::self_test`].
+const CANARY_DIMENSION: usize = 8;
+
+/// Durées des étapes de [`BaseDeDonnees::self_test`], en microsecondes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SelfTestReport {
+    /// Insertion du document témoin.
+    pub insert_us: u64,
+    /// Recherche qui doit le retrouver.
+    pub search_us: u64,
+    /// Suppression, vérifiée par une seconde recherche.
+    pub delete_us: u64,
+    /// Durée totale du test.
+    pub total_us: u64,
+}
+
+impl fmt::Display for SelfTestReport {
+    /// Affiche les durées sur une ligne : `insertion 3 µs, recherche 5 µs, …`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "insertion {} µs, recherche {} µs, suppression {} µs, total {} µs",
+            self.insert_us, self.search_us, self.delete_us, self.total_us
+        )
+    }
+}
+
+impl BaseDeDonnees {
+    /// Insère un document témoin dans une collection de travail, le recherche puis le
+    /// supprime, et mesure chaque étape : une sonde de surveillance vérifie ainsi que la
+    /// base répond, pas seulement que le processus tourne.
+    ///
+    /// La collection de travail utilise le réglage des parcours de la base mais n'y est
+    /// jamais ajoutée : elle n'apparaît ni dans les collections, ni dans les sauvegardes,
+    /// et [`BaseDeDonnees::sequence`] ne change pas.
+    ///
+    /// # Retourne
+    /// * Result<SelfTestReport> - Les durées de chaque étape.
+    ///
+    /// # Erreurs
+    /// * `Error::InvariantViolation` - Si la recherche ne retrouve pas le document témoin,
+    ///   ou le retrouve après sa suppression.
+    /// * Celles de [`Collection::upsert`] et de [`Collection::search`].
+    pub fn self_test(&self) -> Result<SelfTestReport> {
+        let started = Instant::now();
+        let mut collection = Collection::new().with_runtime(self.runtime);
+        let key = Uuid::new_v4();
+        let vector: Vec<f32> = (1..=CANARY_DIMENSION).map(|i| i as f32).collect();
+
+        let lap = Instant::now();
+        collection.upsert(key, vector.clone())?;
+        let insert_us = micros(lap.elapsed());
+
+        let lap = Instant::now();
+        let found = collection.search(&vector, 1)?;
+        let search_us = micros(lap.elapsed());
+        if found.first().map(|(hit, _)| *hit) != Some(key) {
+            return Err(Error::InvariantViolation(
+                "le document témoin n'est pas retrouvé par la recherche".to_string(),
+            ));
+        }
+
+        let lap = Instant::now();
+        collection.delete(&key);
+        let remaining = collection.search(&vector, 1)?;
+        let delete_us = micros(lap.elapsed());
+        if !remaining.is_empty() {
+            return Err(Error::InvariantViolation(
+                "le document témoin est retrouvé après sa suppression".to_string(),
+            ));
+        }
+
+        Ok(SelfTestReport {
+            insert_us,
+            search_us,
+            delete_us,
+            total_us: micros(started.elapsed()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canary_leaves_no_trace_in_the_database() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.add("docs".to_string());
+        let (sequence, bytes) = (bdd.sequence(), bdd.to_bytes());
+        let report = bdd.self_test().unwrap();
+        assert!(report.total_us >= report.insert_us + report.search_us + report.delete_us);
+        assert!(report.to_string().starts_with("insertion "));
+        assert_eq!(bdd.sequence(), sequence);
+        assert_eq!(bdd.to_bytes(), bytes);
+        assert!(bdd.summary().starts_with("1 collection(s)\n"));
+    }
+}
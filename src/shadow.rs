@@ -0,0 +1,482 @@
+//! Requêtes fantômes : une part des recherches d'une collection primaire est rejouée
+//! sur une collection secondaire, par exemple calculée avec un nouveau modèle, pour
+//! mesurer l'écart des résultats avant de basculer.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::database::{self, BaseDeDonnees};
+use crate::error::{Error, Result};
+use crate::float;
+use crate::rng::Rng;
+use crate::search::SearchParams;
+use crate::sharded::ShardedCollection;
+use crate::store::VectorStore;
+
+/// Graine du tirage des recherches rejouées, fixe pour qu'une même suite de recherches
+/// rejoue toujours les mêmes.
+const SHADOW_SEED: u64 = 0x5348_4144_4f57;
+
+/// Bilan cumulé des recherches rejouées par [`BaseDeDonnees::shadow`].
+///
+/// Seules les recherches rejouées avec succès entrent dans les moyennes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ShadowReport {
+    /// Collection primaire, qui sert les résultats.
+    pub primary: String,
+    /// Collection secondaire, interrogée en plus.
+    pub secondary: String,
+    /// Nombre de recherches rejouées et comparées.
+    pub compared: usize,
+    /// Nombre de recherches rejouées en échec sur la collection secondaire : collection
+    /// absente, dimension différente, ou toute autre erreur.
+    pub failures: usize,
+    /// Somme des recouvrements des résultats (voir [`ShadowReport::mean_overlap`]).
+    pub overlap_sum: f64,
+    /// Somme des corrélations de rang (voir [`ShadowReport::mean_rank_correlation`]).
+    pub rank_correlation_sum: f64,
+    /// Nombre de recherches dont la corrélation de rang est définie.
+    pub rank_correlated: usize,
+    /// Durée cumulée des recherches comparées sur la collection primaire.
+    pub primary_latency: Duration,
+    /// Durée cumulée des recherches comparées sur la collection secondaire.
+    pub secondary_latency: Duration,
+}
+
+impl ShadowReport {
+    /// Retourne le recouvrement moyen des résultats, `None` si aucune recherche n'a été
+    /// comparée.
+    ///
+    /// Le recouvrement d'une recherche est le nombre de documents retournés par les
+    /// deux collections, divisé par le plus grand des deux nombres de résultats : `k`
+    /// le plus souvent. Deux résultats vides se recouvrent entièrement.
+    pub fn mean_overlap(&self) -> Option<f64> {
+        (self.compared > 0).then(|| self.overlap_sum / self.compared as f64)
+    }
+
+    /// Retourne la corrélation de rang moyenne, `None` si elle n'est définie pour
+    /// aucune recherche.
+    ///
+    /// La corrélation d'une recherche est celle de Spearman entre les rangs, dans
+    /// chaque collection, des documents retournés par les deux : 1 s'ils sont dans le
+    /// même ordre, -1 dans l'ordre inverse. Elle n'est définie qu'à partir de deux
+    /// documents communs.
+    pub fn mean_rank_correlation(&self) -> Option<f64> {
+        (self.rank_correlated > 0).then(|| self.rank_correlation_sum / self.rank_correlated as f64)
+    }
+
+    /// Retourne la durée moyenne d'une recherche comparée sur la collection primaire.
+    pub fn mean_primary_latency(&self) -> Option<Duration> {
+        mean(self.primary_latency, self.compared)
+    }
+
+    /// Retourne la durée moyenne d'une recherche comparée sur la collection secondaire.
+    pub fn mean_secondary_latency(&self) -> Option<Duration> {
+        mean(self.secondary_latency, self.compared)
+    }
+
+    /// Ajoute au bilan la comparaison d'une recherche.
+    fn record(&mut self, primary: &[Uuid], secondary: &[Uuid], latencies: (Duration, Duration)) {
+        self.compared += 1;
+        self.overlap_sum += overlap(primary, secondary);
+        if let Some(correlation) = rank_correlation(primary, secondary) {
+            self.rank_correlation_sum += correlation;
+            self.rank_correlated += 1;
+        }
+        self.primary_latency += latencies.0;
+        self.secondary_latency += latencies.1;
+    }
+}
+
+impl fmt::Display for ShadowReport {
+    /// Affiche le bilan sur une ligne, les moyennes avec [`float::DISPLAY_DIGITS`]
+    /// chiffres significatifs ou la précision demandée :
+    /// `primaire -> secondaire : 10 recherches, 0 échec, recouvrement 0.8000, …`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = f.precision().unwrap_or(float::DISPLAY_DIGITS);
+        write!(
+            f,
+            "{} -> {} : {} recherches, {} échecs",
+            self.primary, self.secondary, self.compared, self.failures
+        )?;
+        if let Some(overlap) = self.mean_overlap() {
+            write!(f, ", recouvrement {}", float::significant(overlap, digits))?;
+        }
+        if let Some(correlation) = self.mean_rank_correlation() {
+            write!(f, ", corrélation {}", float::significant(correlation, digits))?;
+        }
+        if let (Some(primary), Some(secondary)) =
+            (self.mean_primary_latency(), self.mean_secondary_latency())
+        {
+            write!(
+                f,
+                ", durée {} µs / {} µs",
+                primary.as_micros(),
+                secondary.as_micros()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Couple primaire et secondaire installé par [`BaseDeDonnees::shadow`].
+pub(crate) struct Shadow {
+    primary: String,
+    secondary: String,
+    sample_rate: f32,
+    state: Mutex<ShadowState>,
+    idle: Condvar,
+}
+
+struct ShadowState {
+    report: ShadowReport,
+    rng: Rng,
+    in_flight: usize,
+}
+
+/// Recherche à rejouer : celle de [`BaseDeDonnees::search`] ou de
+/// [`BaseDeDonnees::search_with`].
+pub(crate) enum ShadowQuery<'a> {
+    K(usize),
+    Params(&'a SearchParams),
+}
+
+/// Collection secondaire partagée avec le thread qui rejoue la recherche.
+enum SharedStore {
+    Simple(Arc<Collection>),
+    Sharded(Arc<ShardedCollection>),
+}
+
+impl SharedStore {
+    fn get(&self) -> &dyn VectorStore {
+        match self {
+            SharedStore::Simple(collection) => &**collection,
+            SharedStore::Sharded(collection) => &**collection,
+        }
+    }
+}
+
+impl Shadow {
+    fn lock(&self) -> MutexGuard<'_, ShadowState> {
+        // Un thread interrompu pendant la mise à jour du bilan ne doit pas interrompre
+        // les recherches de la collection primaire.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Tire au sort si la recherche courante est rejouée.
+    fn sampled(&self) -> bool {
+        self.sample_rate > 0.0 && self.lock().rng.next_f64() < f64::from(self.sample_rate)
+    }
+}
+
+impl BaseDeDonnees {
+    /// Rejoue une part des recherches de la collection `primary` sur la collection
+    /// `secondary`, pour comparer leurs résultats dans [`BaseDeDonnees::shadow_report`].
+    ///
+    /// Seules les recherches de [`BaseDeDonnees::search`] et
+    /// [`BaseDeDonnees::search_with`] sont concernées, avec les mêmes paramètres. Les
+    /// résultats retournés viennent toujours de la collection primaire : la recherche
+    /// secondaire est faite dans un thread à part, après la recherche primaire, et ses
+    /// erreurs ne sont comptées que dans le bilan. Ce thread partage la collection
+    /// secondaire telle qu'au moment de la recherche : une écriture qui la modifie
+    /// pendant ce temps la copie, comme après [`BaseDeDonnees::snapshot`].
+    ///
+    /// Un nouvel appel remplace le couple précédent et remet le bilan à zéro.
+    ///
+    /// # Arguments
+    /// * `primary` - Nom de la collection primaire, ou alias.
+    /// * `secondary` - Nom de la collection secondaire, ou alias, résolu à chaque
+    ///   recherche rejouée.
+    /// * `sample_rate` - Part des recherches rejouées, entre 0 et 1, tirées au sort de
+    ///   façon reproductible.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si l'une des collections n'existe pas.
+    /// * `Error::InvalidConfig` - Si `sample_rate` n'est pas entre 0 et 1, ou si les
+    ///   deux noms désignent la même collection.
+    pub fn shadow(&mut self, primary: &str, secondary: &str, sample_rate: f32) -> Result<()> {
+        for nom in [primary, secondary] {
+            if self.store(nom).is_none() {
+                return Err(Error::CollectionNotFound(nom.to_string()));
+            }
+        }
+        if !(0.0..=1.0).contains(&sample_rate) {
+            return Err(Error::InvalidConfig(format!(
+                "part des recherches rejouées hors de [0, 1] : {}",
+                sample_rate
+            )));
+        }
+        if database::resolve(&self.aliases, primary) == database::resolve(&self.aliases, secondary)
+        {
+            return Err(Error::InvalidConfig(format!(
+                "collection primaire et secondaire identiques : {}",
+                primary
+            )));
+        }
+        self.shadow = Some(Arc::new(Shadow {
+            primary: primary.to_string(),
+            secondary: secondary.to_string(),
+            sample_rate,
+            state: Mutex::new(ShadowState {
+                report: ShadowReport {
+                    primary: primary.to_string(),
+                    secondary: secondary.to_string(),
+                    ..Default::default()
+                },
+                rng: Rng::new(SHADOW_SEED),
+                in_flight: 0,
+            }),
+            idle: Condvar::new(),
+        }));
+        Ok(())
+    }
+
+    /// Arrête les requêtes fantômes et retourne le bilan final, après la fin des
+    /// recherches rejouées en cours ; `None` si aucune n'était installée.
+    pub fn stop_shadow(&mut self) -> Option<ShadowReport> {
+        let report = self.shadow_report();
+        self.shadow = None;
+        report
+    }
+
+    /// Retourne le bilan des requêtes fantômes installées par [`BaseDeDonnees::shadow`],
+    /// `None` si aucune ne l'est.
+    ///
+    /// L'appel attend la fin des recherches rejouées en cours : le bilan compte toutes
+    /// les recherches servies avant lui.
+    pub fn shadow_report(&self) -> Option<ShadowReport> {
+        let shadow = self.shadow.as_ref()?;
+        let mut state = shadow.lock();
+        while state.in_flight > 0 {
+            state = (shadow.idle.wait(state)).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        Some(state.report.clone())
+    }
+
+    /// Recherche dans `cname` comme `search`, puis rejoue la recherche sur la collection
+    /// secondaire si `cname` est la collection primaire et que le tirage la retient.
+    pub(crate) fn shadowed<T>(
+        &self,
+        cname: &str,
+        request: &[f32],
+        query: ShadowQuery<'_>,
+        search: impl FnOnce() -> Result<T>,
+        hits: impl Fn(&T) -> Vec<Uuid>,
+    ) -> Result<T> {
+        let shadow = match &self.shadow {
+            Some(shadow)
+                if database::resolve(&self.aliases, cname)
+                    == database::resolve(&self.aliases, &shadow.primary)
+                    && shadow.sampled() =>
+            {
+                Arc::clone(shadow)
+            }
+            _ => return search(),
+        };
+        let started = Instant::now();
+        let results = search()?;
+        let primary_latency = started.elapsed();
+        let primary = hits(&results);
+        let nom = database::resolve(&self.aliases, &shadow.secondary);
+        let store = match self.collections.get(nom) {
+            Some(collection) => Some(SharedStore::Simple(Arc::clone(collection))),
+            None => (self.sharded.get(nom)).map(|c| SharedStore::Sharded(Arc::clone(c))),
+        };
+        let Some(store) = store else {
+            shadow.lock().report.failures += 1;
+            return Ok(results);
+        };
+        let request = request.to_vec();
+        let query = match query {
+            ShadowQuery::K(k) => OwnedQuery::K(k),
+            ShadowQuery::Params(params) => OwnedQuery::Params(Box::new(params.clone())),
+        };
+        shadow.lock().in_flight += 1;
+        thread::spawn(move || {
+            let started = Instant::now();
+            let outcome =
+                panic::catch_unwind(AssertUnwindSafe(|| query.run(store.get(), &request)));
+            let secondary_latency = started.elapsed();
+            let mut state = shadow.lock();
+            match outcome {
+                Ok(Ok(secondary)) => (state.report).record(
+                    &primary,
+                    &secondary,
+                    (primary_latency, secondary_latency),
+                ),
+                _ => state.report.failures += 1,
+            }
+            state.in_flight -= 1;
+            shadow.idle.notify_all();
+        });
+        Ok(results)
+    }
+}
+
+/// Recherche rejouée, copiée pour le thread qui la fait.
+enum OwnedQuery {
+    K(usize),
+    Params(Box<SearchParams>),
+}
+
+impl OwnedQuery {
+    fn run(&self, store: &dyn VectorStore, request: &[f32]) -> Result<Vec<Uuid>> {
+        match self {
+            OwnedQuery::K(k) => Ok(keys(&store.search(request, *k)?)),
+            OwnedQuery::Params(params) => Ok(keys(&store.search_with(request, params)?.hits)),
+        }
+    }
+}
+
+/// Retourne les identifiants des documents de `hits`, dans l'ordre.
+pub(crate) fn keys(hits: &Document) -> Vec<Uuid> {
+    hits.iter().map(|(key, _)| *key).collect()
+}
+
+fn mean(total: Duration, count: usize) -> Option<Duration> {
+    (count > 0).then(|| total / u32::try_from(count).unwrap_or(u32::MAX))
+}
+
+/// Part des documents communs aux deux listes, rapportée à la plus longue.
+fn overlap(primary: &[Uuid], secondary: &[Uuid]) -> f64 {
+    let longest = primary.len().max(secondary.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let common = primary.iter().filter(|key| secondary.contains(key)).count();
+    common as f64 / longest as f64
+}
+
+/// Corrélation de Spearman des rangs des documents communs aux deux listes, `None` s'ils
+/// sont moins de deux.
+fn rank_correlation(primary: &[Uuid], secondary: &[Uuid]) -> Option<f64> {
+    let positions: HashMap<&Uuid, usize> =
+        secondary.iter().enumerate().map(|(i, k)| (k, i)).collect();
+    // Rangs dans la liste secondaire des documents communs, dans l'ordre de la primaire.
+    let common: Vec<usize> = primary
+        .iter()
+        .filter_map(|key| positions.get(key).copied())
+        .collect();
+    let n = common.len();
+    if n < 2 {
+        return None;
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_unstable_by_key(|&i| common[i]);
+    let squared: f64 = (order.iter().enumerate())
+        .map(|(rank, &i)| (rank as f64 - i as f64).powi(2))
+        .sum();
+    let n = n as f64;
+    Some(1.0 - 6.0 * squared / (n * (n * n - 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::Metric;
+
+    fn collection(points: &[(u128, f32)]) -> Collection {
+        let mut collection = Collection::new().with_metric(Metric::Euclidean);
+        for (key, x) in points {
+            collection.upsert(Uuid::from_u128(*key), [*x]).unwrap();
+        }
+        collection
+    }
+
+    /// Base dont la collection `v2` inverse les deux premiers documents de `v1` vus de
+    /// l'origine et éloigne le troisième.
+    fn database() -> BaseDeDonnees {
+        let mut bdd = BaseDeDonnees::new();
+        let v1 = collection(&[(1, 0.0), (2, 1.0), (3, 2.0), (4, 3.0), (5, 4.0)]);
+        let v2 = collection(&[(1, 1.5), (2, 0.5), (3, 10.0), (4, 2.0), (5, 4.0)]);
+        bdd.replace("v1".to_string(), v1);
+        bdd.replace("v2".to_string(), v2);
+        bdd
+    }
+
+    #[test]
+    fn report_matches_the_hand_computed_overlap() {
+        let mut bdd = database();
+        bdd.set_alias("suivante".to_string(), "v2").unwrap();
+        bdd.shadow("v1", "suivante", 1.0).unwrap();
+        let served = bdd.search("v1", [0.0], 3).unwrap();
+        assert_eq!(served, bdd.get("v1").unwrap().search([0.0], 3).unwrap());
+        let params = SearchParams {
+            k: 3,
+            ..Default::default()
+        };
+        bdd.search_with("v1", [4.0], &params).unwrap();
+        // Les recherches des autres collections ne sont pas rejouées.
+        bdd.search("v2", [0.0], 3).unwrap();
+
+        // [1, 2, 3] contre [2, 1, 4] : deux communs dans l'ordre inverse ;
+        // [5, 4, 3] contre [5, 4, 1] : deux communs dans le même ordre.
+        let report = bdd.shadow_report().unwrap();
+        assert_eq!((report.compared, report.failures), (2, 0));
+        assert!((report.mean_overlap().unwrap() - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(report.rank_correlated, 2);
+        assert_eq!(report.rank_correlation_sum, 0.0);
+        assert!(report.mean_primary_latency().is_some());
+        assert!(report.to_string().starts_with(
+            "v1 -> suivante : 2 recherches, 0 échecs, recouvrement 0.6667, corrélation 0.000, durée"
+        ));
+        assert!(format!("{:.2}", report).contains("recouvrement 0.67, corrélation 0.0, durée"));
+    }
+
+    #[test]
+    fn secondary_failures_are_only_counted() {
+        let mut bdd = database();
+        bdd.replace("v3".to_string(), Collection::new());
+        bdd.get_mut("v3").unwrap().upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        bdd.set_alias("suivante".to_string(), "v3").unwrap();
+        bdd.shadow("v1", "suivante", 1.0).unwrap();
+        let served = bdd.search("v1", [0.0], 2).unwrap();
+        assert_eq!(keys(&served), [Uuid::from_u128(1), Uuid::from_u128(2)]);
+        let report = bdd.stop_shadow().unwrap();
+        assert_eq!((report.compared, report.failures), (0, 1));
+        assert_eq!(report.mean_overlap(), None);
+        assert!(bdd.shadow_report().is_none());
+    }
+
+    #[test]
+    fn sample_rate_selects_a_reproducible_share() {
+        let mut bdd = database();
+        let run = |bdd: &mut BaseDeDonnees, rate| {
+            bdd.shadow("v1", "v2", rate).unwrap();
+            for i in 0..200 {
+                bdd.search("v1", [i as f32 / 50.0], 2).unwrap();
+            }
+            bdd.shadow_report().unwrap().compared
+        };
+        assert_eq!(run(&mut bdd, 0.0), 0);
+        let compared = run(&mut bdd, 0.25);
+        assert!((30..=70).contains(&compared), "{}", compared);
+        assert_eq!(run(&mut bdd, 0.25), compared);
+    }
+
+    #[test]
+    fn invalid_pairs_are_refused() {
+        let mut bdd = database();
+        bdd.set_alias("actuelle".to_string(), "v1").unwrap();
+        assert!(matches!(
+            bdd.shadow("v1", "absente", 0.5),
+            Err(Error::CollectionNotFound(_))
+        ));
+        for (secondary, rate) in [("v2", 1.5), ("v2", f32::NAN), ("actuelle", 0.5)] {
+            assert!(matches!(
+                bdd.shadow("v1", secondary, rate),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        assert!(bdd.shadow_report().is_none());
+    }
+}
@@ -0,0 +1,451 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::collection::{self, Collection, ZeroVectorPolicy};
+use crate::config::CollectionConfig;
+use crate::error::{Error, Result};
+use crate::memory::{Budget, BudgetSlot, MemoryUsage};
+use crate::parallel::SearchRuntime;
+use crate::payload::Payload;
+use crate::search::{ScoreMode, SearchParams, SearchResults, Stopwatch};
+use crate::similarity::Metric;
+use crate::store::{VectorStore, VectorStoreMut};
+
+/// Collection répartie sur un nombre fixe de sous-collections.
+///
+/// Chaque document appartient à la sous-collection désignée par un hachage de son `Uuid`,
+/// stable d'une exécution à l'autre. Les recherches interrogent toutes les
+/// sous-collections en parallèle puis fusionnent leurs résultats : elles retournent
+/// exactement les mêmes documents, dans le même ordre, qu'une [`Collection`] unique
+/// contenant les mêmes données.
+#[derive(Clone)]
+pub struct ShardedCollection {
+    shards: Vec<Collection>,
+    pub(crate) budget: BudgetSlot,
+}
+
+impl ShardedCollection {
+    /// Crée une collection vide répartie sur `shard_count` sous-collections.
+    ///
+    /// # Arguments
+    /// * `shard_count` - Nombre de sous-collections, fixé pour toute la vie de la collection.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `shard_count` vaut 0.
+    pub fn new(shard_count: usize) -> Result<Self> {
+        if shard_count == 0 {
+            return Err(Error::InvalidConfig(
+                "une collection partitionnée doit avoir au moins une partition".to_string(),
+            ));
+        }
+        Ok(ShardedCollection {
+            shards: (0..shard_count).map(|_| Collection::new()).collect(),
+            budget: BudgetSlot::default(),
+        })
+    }
+
+    /// Crée une collection vide répartie sur `shard_count` sous-collections configurées
+    /// par `config`.
+    ///
+    /// # Arguments
+    /// * `shard_count` - Nombre de sous-collections, fixé pour toute la vie de la collection.
+    /// * `config` - Configuration commune des sous-collections.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `shard_count` vaut 0, ou si `config` transforme les
+    ///   requêtes ([`CollectionConfigBuilder::query_pipeline`](crate::CollectionConfigBuilder::query_pipeline)) :
+    ///   chaque sous-collection les transformerait selon ses seuls documents, par
+    ///   exemple autour de son propre centroïde.
+    pub fn from_config(shard_count: usize, config: CollectionConfig) -> Result<Self> {
+        if !config.query_pipeline().is_empty() {
+            return Err(Error::InvalidConfig(
+                "une collection partitionnée ne peut pas transformer ses requêtes".to_string(),
+            ));
+        }
+        let mut collection = ShardedCollection::new(shard_count)?;
+        for shard in &mut collection.shards {
+            *shard = Collection::from_config(config.clone());
+        }
+        Ok(collection)
+    }
+
+    /// Retourne la configuration commune des sous-collections.
+    pub fn config(&self) -> &CollectionConfig {
+        self.shards[0].config()
+    }
+
+    /// Retourne la dimension des vecteurs insérés et des requêtes : celle de la
+    /// configuration, ou celle fixée par la première insertion dans l'une des
+    /// sous-collections, qui s'impose alors à toutes (voir [`Collection::dimension`]).
+    pub fn dimension(&self) -> Option<usize> {
+        self.shards.iter().find_map(Collection::dimension)
+    }
+
+    /// Reconstruit une collection à partir de ses sous-collections déjà réparties.
+    pub(crate) fn from_shards(shards: Vec<Collection>) -> Self {
+        ShardedCollection {
+            shards,
+            budget: BudgetSlot::default(),
+        }
+    }
+
+    /// Fixe la mesure utilisée par toutes les sous-collections.
+    ///
+    /// # Arguments
+    /// * `metric` - Mesure de similarité ou de distance.
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.with_metric(metric))
+            .collect();
+        self
+    }
+
+    /// Fixe le traitement des vecteurs nuls de toutes les sous-collections.
+    ///
+    /// # Arguments
+    /// * `policy` - Politique à appliquer.
+    pub fn with_zero_vector_policy(mut self, policy: ZeroVectorPolicy) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.with_zero_vector_policy(policy))
+            .collect();
+        self
+    }
+
+    /// Choisit le traitement des documents de dimension différente dans toutes les
+    /// sous-collections (voir [`Collection::with_strict_dimensions`]).
+    ///
+    /// # Arguments
+    /// * `strict` - Vrai pour faire échouer les recherches.
+    pub fn with_strict_dimensions(mut self, strict: bool) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.with_strict_dimensions(strict))
+            .collect();
+        self
+    }
+
+    /// Fixe le réglage des parcours parallèles de toutes les sous-collections. Il règle
+    /// aussi la recherche simultanée dans les sous-collections.
+    ///
+    /// # Arguments
+    /// * `runtime` - Nombre de threads et taille des tranches des parcours.
+    pub fn with_runtime(mut self, runtime: SearchRuntime) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.with_runtime(runtime))
+            .collect();
+        self
+    }
+
+    /// Retourne le réglage des parcours parallèles des sous-collections.
+    pub fn runtime(&self) -> SearchRuntime {
+        self.shards[0].runtime()
+    }
+
+    /// Retourne le nombre de sous-collections.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Retourne les sous-collections, dans l'ordre de répartition.
+    pub fn shards(&self) -> &[Collection] {
+        &self.shards
+    }
+
+    /// Lit le vecteur partagé d'un document, sans copie, comme
+    /// [`Collection::read_arc`].
+    ///
+    /// # Arguments
+    /// * `key` - Référence à l'identifiant unique du document.
+    pub fn read_arc(&self, key: &Uuid) -> Option<Arc<Vec<f32>>> {
+        self.shards[self.shard_of(key)].read_arc(key)
+    }
+
+    pub(crate) fn shards_mut(&mut self) -> &mut [Collection] {
+        &mut self.shards
+    }
+
+    /// Vérifie la cohérence de chaque sous-collection et la répartition des documents.
+    ///
+    /// # Erreurs
+    /// * `Error::InvariantViolation` - Décrit la première incohérence trouvée.
+    pub fn check_invariants(&self) -> Result<()> {
+        for (index, shard) in self.shards.iter().enumerate() {
+            shard.check_invariants()?;
+            if let Some(key) = shard
+                .documents
+                .keys()
+                .find(|key| self.shard_of(key) != index)
+            {
+                return Err(Error::InvariantViolation(format!(
+                    "le document {} est dans la partition {} au lieu de {}",
+                    key,
+                    index,
+                    self.shard_of(key)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Indice de la sous-collection qui possède `key`.
+    pub(crate) fn shard_of(&self, key: &Uuid) -> usize {
+        let bits = key.as_u128();
+        let mut z = (bits >> 64) as u64 ^ bits as u64;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z % self.shards.len() as u64) as usize
+    }
+
+    /// Retourne la mémoire occupée par toutes les sous-collections.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.shards.iter().map(Collection::memory_usage).sum()
+    }
+
+    /// Rend la place réservée inutilisée de chaque sous-collection
+    /// ([`Collection::shrink_to_fit`]).
+    ///
+    /// # Retourne
+    /// * usize - Octets rendus.
+    pub fn shrink_to_fit(&mut self) -> usize {
+        self.shards.iter_mut().map(Collection::shrink_to_fit).sum()
+    }
+
+    /// Retourne les octets réservés sans être utilisés par toutes les sous-collections
+    /// ([`Collection::unused_capacity_bytes`]).
+    pub fn unused_capacity_bytes(&self) -> usize {
+        self.shards
+            .iter()
+            .map(Collection::unused_capacity_bytes)
+            .sum()
+    }
+
+    /// Retourne la sous-collection qui possède `key`, avec sa part de la limite de
+    /// mémoire de la base et la dimension fixée par les autres sous-collections.
+    fn shard_mut(&mut self, key: &Uuid) -> &mut Collection {
+        let index = self.shard_of(key);
+        let locked = self.dimension();
+        let budget = self.budget.0.map(|budget| Budget {
+            others: budget.others + self.memory_usage().total()
+                - self.shards[index].memory_usage().total(),
+            ..budget
+        });
+        let shard = &mut self.shards[index];
+        shard.budget = BudgetSlot(budget);
+        if shard.config.dimension.is_none() {
+            shard.locked_dimension = shard.locked_dimension.or(locked);
+        }
+        shard
+    }
+}
+
+impl VectorStore for ShardedCollection {
+    fn read(&self, key: &Uuid) -> Option<&[f32]> {
+        self.shards[self.shard_of(key)].read(key).map(Vec::as_slice)
+    }
+
+    fn payload(&self, key: &Uuid) -> Option<&Payload> {
+        self.shards[self.shard_of(key)].payload(key)
+    }
+
+    fn len(&self) -> usize {
+        self.shards.iter().map(Collection::len).sum()
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &Uuid> + '_> {
+        Box::new(self.shards.iter().flat_map(|shard| shard.documents.keys()))
+    }
+
+    fn warm_up(&self) {
+        self.shards.iter().for_each(Collection::warm_up);
+    }
+
+    fn metric(&self) -> Metric {
+        self.shards[0].metric()
+    }
+
+    /// Recherche dans toutes les sous-collections en parallèle puis conserve les `k`
+    /// meilleurs résultats. Le résultat est tronqué si l'une des recherches l'est ;
+    /// `require_exact_k` porte sur l'ensemble des sous-collections.
+    fn search_with(&self, request: &[f32], params: &SearchParams) -> Result<SearchResults> {
+        params.check_uncalibrated()?;
+        let mut watch = Stopwatch::start(params);
+        // Les doublons sont écartés après la fusion, parmi les réserves de toutes les
+        // partitions : deux quasi-doublons peuvent venir de partitions différentes.
+        let raw = SearchParams {
+            k: match params.dedup_results {
+                Some(_) => params.k.saturating_mul(params.dedup_pool_factor.max(1)),
+                None => params.k,
+            },
+            score_mode: ScoreMode::Raw,
+            require_exact_k: false,
+            payload_selector: None,
+            with_vector: false,
+            dedup_results: None,
+            ..params.clone()
+        };
+        let partial = self.runtime().map_chunks(&self.shards, 2, |shards| {
+            shards
+                .iter()
+                .map(|shard| shard.search_with(request, &raw))
+                .collect()
+        });
+        let mut merged = SearchResults::default();
+        for results in partial {
+            let results = results?;
+            merged.hits.extend(results.hits);
+            merged.truncated |= results.truncated;
+            merged.available += results.available;
+            if let (Some(watch), Some(timing)) = (&mut watch, results.timing) {
+                watch.timing.filter_us += timing.filter_us;
+                watch.timing.scoring_us += timing.scoring_us;
+                watch.timing.candidates_scored += timing.candidates_scored;
+            }
+        }
+        if let Some(watch) = &mut watch {
+            watch.lap();
+        }
+        let dimension = (merged.hits.first()).and_then(|(key, _)| self.read(key));
+        let masked = match dimension {
+            Some(vector) => self.shards[0].masked_weights(params, vector.len())?,
+            None => None,
+        };
+        let available = collection::deduplicate(
+            self.shards[0].masked_scorer(masked.as_deref()),
+            &mut merged.hits,
+            merged.available,
+            params,
+            |key| self.read(key),
+        );
+        let mut results = collection::finish(
+            self.metric(),
+            merged.hits,
+            available,
+            merged.truncated,
+            params,
+            watch,
+        )?;
+        results.attach(
+            params,
+            |key| self.shards[self.shard_of(key)].payloads.get(key),
+            |key| self.shards[self.shard_of(key)].documents.get(key).cloned(),
+        );
+        Ok(results)
+    }
+}
+
+impl VectorStoreMut for ShardedCollection {
+    fn upsert(&mut self, key: Uuid, vector: Vec<f32>) -> Result<()> {
+        self.shard_mut(&key).upsert(key, vector).map(drop)
+    }
+
+    fn upsert_with_payload(&mut self, key: Uuid, vector: Vec<f32>, payload: Payload) -> Result<()> {
+        self.shard_mut(&key)
+            .upsert_with_payload(key, vector, payload)
+            .map(drop)
+    }
+
+    fn delete(&mut self, key: &Uuid) {
+        self.shard_mut(key).delete(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::store::VectorStoreMut;
+    use crate::synthetic::VectorGenerator;
+
+    /// Même contenu dans une collection simple et dans une collection de `shard_count`
+    /// partitions.
+    fn pair(metric: Metric, shard_count: usize) -> (Collection, ShardedCollection, VectorGenerator) {
+        let mut generator = VectorGenerator::new(6, 31);
+        let mut single = Collection::new().with_metric(metric);
+        let mut sharded = ShardedCollection::new(shard_count).unwrap().with_metric(metric);
+        for _ in 0..400 {
+            let (key, vector) = (generator.uuid(), generator.vector());
+            single.upsert(key, vector.clone()).unwrap();
+            sharded.upsert(key, vector).unwrap();
+        }
+        (single, sharded, generator)
+    }
+
+    #[test]
+    fn results_match_an_unsharded_collection() {
+        for metric in [Metric::Cosine, Metric::Dot, Metric::Euclidean, Metric::Manhattan] {
+            for shard_count in [1, 3, 8] {
+                let (single, sharded, mut generator) = pair(metric, shard_count);
+                for _ in 0..20 {
+                    let query = generator.vector();
+                    assert_eq!(
+                        VectorStore::search(&sharded, &query, 15).unwrap(),
+                        single.search(&query, 15).unwrap(),
+                        "{:?}, {} partitions",
+                        metric,
+                        shard_count
+                    );
+                }
+                // Le seuil retient une cinquantaine de documents.
+                let query = generator.vector();
+                let params = SearchParams {
+                    k: 400,
+                    score_threshold: Some(single.search(&query, 50).unwrap()[49].1),
+                    ..Default::default()
+                };
+                assert_eq!(
+                    VectorStore::search_with(&sharded, &query, &params).unwrap().hits,
+                    single.search_with(&query, &params).unwrap().hits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn writes_go_to_the_owning_shard() {
+        let (single, mut sharded, _) = pair(Metric::Cosine, 4);
+        assert_eq!(VectorStore::len(&sharded), single.len());
+        assert!(sharded.shards().iter().all(|shard| !shard.is_empty()));
+        let keys: Vec<Uuid> = VectorStore::keys(&single).copied().collect();
+        for key in &keys {
+            let owner = sharded.shard_of(key);
+            for (i, shard) in sharded.shards().iter().enumerate() {
+                assert_eq!(VectorStore::read(shard, key).is_some(), i == owner);
+            }
+            assert_eq!(VectorStore::read(&sharded, key), VectorStore::read(&single, key));
+        }
+        for key in &keys[..100] {
+            VectorStoreMut::delete(&mut sharded, key);
+        }
+        assert_eq!(VectorStore::len(&sharded), keys.len() - 100);
+        assert!(VectorStore::read(&sharded, &keys[0]).is_none());
+        sharded.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn shard_count_is_saved() {
+        let (_, sharded, mut generator) = pair(Metric::Euclidean, 5);
+        let query = generator.vector();
+        let expected = VectorStore::search(&sharded, &query, 10).unwrap();
+        let mut db = BaseDeDonnees::new();
+        db.replace_sharded("docs".to_string(), sharded);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let docs = loaded.get_sharded("docs").unwrap();
+        assert_eq!(docs.shard_count(), 5);
+        assert_eq!(VectorStore::metric(docs), Metric::Euclidean);
+        assert_eq!(VectorStore::search(docs, &query, 10).unwrap(), expected);
+    }
+
+    #[test]
+    fn zero_shards_are_refused() {
+        assert!(matches!(ShardedCollection::new(0), Err(Error::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,536 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::config::CollectionConfig;
+use crate::database::{self, BaseDeDonnees};
+use crate::database_snapshot::DatabaseSnapshot;
+use crate::error::{Error, Result};
+use crate::memory::CompactReport;
+use crate::poison::Quarantine;
+use crate::progress::{BatchReport, ProgressSink, Tracker};
+use crate::relaxed::{SharedView, StalenessBound};
+
+/// Poignée partagée vers une `BaseDeDonnees`, utilisable depuis plusieurs threads.
+///
+/// Les recherches prennent le verrou en lecture, les modifications le verrou en écriture.
+/// Cloner la poignée ne copie pas la base : tous les clones pointent vers les mêmes données.
+///
+/// [`BaseDeDonneesPartagee::search_relaxed`] lit une vue figée de la base, quitte à être
+/// légèrement en retard, sans jamais attendre un écrivain.
+///
+/// Une panique sous le verrou en écriture ne rend pas la base inutilisable : le verrou
+/// est repris au prochain appel, et les collections laissées incohérentes sont mises en
+/// quarantaine ([`BaseDeDonneesPartagee::quarantined`]). Les recherches continuent d'y
+/// lire ; les écritures de la poignée, de ses [`WriteHandle`](crate::WriteHandle) et
+/// de son [`Primary`](crate::Primary) y sont refusées avec `Error::CollectionPoisoned`.
+#[derive(Clone, Default)]
+pub struct BaseDeDonneesPartagee {
+    pub(crate) inner: Arc<RwLock<BaseDeDonnees>>,
+    /// Vue des lectures relâchées, partagée par les clones.
+    pub(crate) relaxed: SharedView,
+    pub(crate) bound: StalenessBound,
+    pub(crate) quarantine: Quarantine,
+}
+
+impl BaseDeDonneesPartagee {
+    /// Crée une poignée partagée à partir d'une base existante.
+    ///
+    /// # Arguments
+    /// * `bdd` - Base de données à partager.
+    pub fn new(bdd: BaseDeDonnees) -> Self {
+        BaseDeDonneesPartagee {
+            inner: Arc::new(RwLock::new(bdd)),
+            relaxed: SharedView::default(),
+            bound: StalenessBound::default(),
+            quarantine: Quarantine::default(),
+        }
+    }
+
+    /// Prend le verrou en lecture sur la base, même empoisonné par une panique.
+    pub fn read(&self) -> RwLockReadGuard<'_, BaseDeDonnees> {
+        (self.quarantine).recover(self.inner.read(), || self.inner.clear_poison())
+    }
+
+    /// Prend le verrou en écriture sur la base, même empoisonné par une panique.
+    ///
+    /// Le gardien donne accès à toute la base, collections en quarantaine comprises :
+    /// c'est par lui qu'une collection en quarantaine peut être réparée avant
+    /// [`BaseDeDonneesPartagee::release`].
+    pub fn write(&self) -> RwLockWriteGuard<'_, BaseDeDonnees> {
+        (self.quarantine).recover(self.inner.write(), || self.inner.clear_poison())
+    }
+
+    /// Retourne les collections mises en quarantaine après une panique sous le verrou en
+    /// écriture, avec la première incohérence trouvée dans chacune.
+    ///
+    /// # Retourne
+    /// * BTreeMap<String, String> - Description de l'incohérence, par nom de collection.
+    pub fn quarantined(&self) -> BTreeMap<String, String> {
+        // Le verrou est pris pour inspecter la base s'il vient d'être empoisonné.
+        drop(self.read());
+        self.quarantine.list()
+    }
+
+    /// Sort la collection `nom` de quarantaine si ses invariants sont de nouveau
+    /// respectés, par exemple après une réparation sous [`BaseDeDonneesPartagee::write`].
+    ///
+    /// Une collection en quarantaine remplacée par [`CollectionBuilder::commit`] en sort
+    /// aussi.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection.
+    ///
+    /// # Erreurs
+    /// * `Error::CollectionNotFound` - Si aucune collection ne porte ce nom.
+    /// * `Error::InvariantViolation` - Si la collection est toujours incohérente ; elle
+    ///   reste en quarantaine.
+    pub fn release(&self, nom: &str) -> Result<()> {
+        let bdd = self.read();
+        match (bdd.get(nom), bdd.get_sharded(nom)) {
+            (Some(collection), _) => collection.check_invariants()?,
+            (None, Some(sharded)) => sharded.check_invariants()?,
+            (None, None) => return Err(Error::CollectionNotFound(nom.to_string())),
+        }
+        self.quarantine.release(database::resolve(&bdd.aliases, nom));
+        Ok(())
+    }
+
+    /// Effectue une recherche dans une collection spécifique sous le verrou en lecture.
+    ///
+    /// # Arguments
+    /// * `cname` - Nom de la collection.
+    /// * `request` - Vecteur de requête.
+    /// * `k` - Nombre de résultats à retourner.
+    ///
+    /// # Retourne
+    /// * Result<Document> - Résultats de la recherche dans la collection spécifiée.
+    pub fn search(&self, cname: &str, request: impl AsRef<[f32]>, k: usize) -> Result<Document> {
+        self.read().search(cname, request, k)
+    }
+
+    /// Capture une vue figée de la base sous le verrou en lecture, libéré aussitôt.
+    ///
+    /// Les requêtes faites ensuite sur la vue ne prennent plus aucun verrou et ne
+    /// bloquent pas les écritures (voir [`BaseDeDonnees::snapshot`]).
+    pub fn snapshot(&self) -> DatabaseSnapshot {
+        self.read().snapshot()
+    }
+
+    /// Compacte toutes les collections comme [`BaseDeDonnees::compact_all`], en prenant
+    /// le verrou en écriture pour chaque collection séparément : les recherches
+    /// reprennent entre deux collections au lieu d'attendre la fin de l'opération. Les
+    /// collections en quarantaine ne sont pas compactées.
+    ///
+    /// # Arguments
+    /// * `progress` - Puits de progression optionnel, appelé hors du verrou.
+    ///
+    /// # Retourne
+    /// * CompactReport - Nombre de collections compactées, octets rendus et indication
+    ///   d'annulation.
+    pub fn compact_all(&self, progress: Option<&dyn ProgressSink>) -> CompactReport {
+        let noms = self.read().collection_names();
+        let mut tracker = Tracker::new(progress, Some(noms.len() as u64));
+        let mut report = CompactReport::default();
+        for nom in &noms {
+            if tracker.cancelled() {
+                report.cancelled = true;
+                break;
+            }
+            let mut bdd = self.write();
+            if self.quarantine.check(&bdd, nom).is_ok() {
+                report.bytes_reclaimed += bdd.compact_collection(nom);
+            }
+            // Le puits de progression est appelé verrou rendu : il ne bloque pas les
+            // recherches et ne peut pas empoisonner le verrou.
+            drop(bdd);
+            tracker.step();
+        }
+        report.collections = tracker.finish(report.cancelled).applied;
+        report
+    }
+
+    /// Commence la reconstruction de la collection `nom`.
+    ///
+    /// La nouvelle collection est remplie à l'écart, sans prendre aucun verrou : les
+    /// recherches continuent de voir l'ancienne collection jusqu'à l'appel de
+    /// [`CollectionBuilder::commit`]. Elle reprend la configuration, les réglages
+    /// d'exécution et les paramètres de recherche par défaut de la collection actuelle,
+    /// comme [`BaseDeDonnees::begin_rebuild`], et `nom` n'a pas besoin d'exister encore.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection à reconstruire.
+    ///
+    /// # Retourne
+    /// * CollectionBuilder<BaseDeDonneesPartagee> - Constructeur de la nouvelle
+    ///   collection, qui connaît sa base.
+    pub fn begin_rebuild(&self, nom: &str) -> CollectionBuilder<BaseDeDonneesPartagee> {
+        let CollectionBuilder { nom, collection, .. } = self.read().begin_rebuild(nom);
+        CollectionBuilder {
+            nom,
+            collection,
+            cible: self.clone(),
+        }
+    }
+}
+
+impl BaseDeDonnees {
+    /// Commence la reconstruction de la collection `nom`, remplacée ensuite par
+    /// [`BaseDeDonnees::commit_rebuild`].
+    ///
+    /// La nouvelle collection reprend la configuration, les réglages d'exécution et les
+    /// paramètres de recherche par défaut de la collection actuelle, si elle existe et
+    /// n'est pas partitionnée, sauf sa dimension : celle-ci est fixée par le premier
+    /// vecteur inséré, et peut changer d'une version à l'autre.
+    /// [`CollectionBuilder::with_config`] remplace toute la configuration. La base n'est
+    /// pas empruntée pendant la reconstruction et sert toujours l'ancienne collection.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection à reconstruire.
+    ///
+    /// # Retourne
+    /// * CollectionBuilder - Constructeur de la nouvelle collection.
+    pub fn begin_rebuild(&self, nom: &str) -> CollectionBuilder {
+        let mut collection = Collection::new();
+        if let Some(live) = self.get(nom) {
+            collection.config = CollectionConfig {
+                dimension: None,
+                ..live.config.clone()
+            };
+            collection.runtime = live.runtime;
+            collection.default_params = live.default_params.clone();
+        }
+        CollectionBuilder {
+            nom: nom.to_string(),
+            collection,
+            cible: (),
+        }
+    }
+
+    /// Remplace la collection de `builder` par sa nouvelle version, en temps constant :
+    /// seul le pointeur partagé de la collection est échangé.
+    ///
+    /// Les vues figées ([`BaseDeDonnees::snapshot`]) prises avant l'échange continuent
+    /// de voir l'ancienne collection.
+    ///
+    /// # Arguments
+    /// * `builder` - Constructeur de [`BaseDeDonnees::begin_rebuild`] ou de
+    ///   [`BaseDeDonneesPartagee::begin_rebuild`].
+    ///
+    /// # Retourne
+    /// * Option<Arc<Collection>> - L'ancienne collection si elle existait.
+    pub fn commit_rebuild<C>(&mut self, builder: CollectionBuilder<C>) -> Option<Arc<Collection>> {
+        let nom = database::resolve(&self.aliases, &builder.nom).to_string();
+        self.sequence += 1;
+        self.sharded.remove(&nom);
+        self.collections.insert(nom, Arc::new(builder.collection))
+    }
+}
+
+/// Collection en cours de reconstruction, créée par [`BaseDeDonnees::begin_rebuild`]
+/// ou [`BaseDeDonneesPartagee::begin_rebuild`].
+///
+/// `C` est la base que le constructeur remplace lui-même : seul celui de
+/// [`BaseDeDonneesPartagee::begin_rebuild`] a donc [`CollectionBuilder::commit`], les
+/// autres se terminent par [`BaseDeDonnees::commit_rebuild`].
+pub struct CollectionBuilder<C = ()> {
+    nom: String,
+    collection: Collection,
+    cible: C,
+}
+
+impl<C> CollectionBuilder<C> {
+    /// Nom de la collection qui sera remplacée.
+    pub fn nom(&self) -> &str {
+        &self.nom
+    }
+
+    /// Remplace la configuration de la collection en construction, qui doit encore être
+    /// vide.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration obtenue avec [`CollectionConfig::builder`].
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si des documents ont déjà été insérés.
+    pub fn with_config(mut self, config: CollectionConfig) -> Result<Self> {
+        if !self.collection.is_empty() {
+            return Err(Error::InvalidConfig(
+                "la configuration d'une reconstruction se fixe avant la première insertion"
+                    .to_string(),
+            ));
+        }
+        self.collection.config = config;
+        Ok(self)
+    }
+
+    /// Insère ou met à jour un document dans la collection en construction.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document.
+    ///
+    /// # Retourne
+    /// * Result<bool> - Comme [`Collection::upsert`].
+    pub fn upsert(&mut self, key: Uuid, vector: Vec<f32>) -> Result<bool> {
+        self.collection.upsert(key, vector)
+    }
+
+    /// Insère un lot de documents dans la collection en construction.
+    ///
+    /// Voir [`Collection::upsert_batch`] pour la progression et l'annulation.
+    ///
+    /// # Arguments
+    /// * `items` - Documents à insérer, sous forme de couples (Uuid, vecteur).
+    /// * `progress` - Puits de progression optionnel.
+    ///
+    /// # Retourne
+    /// * Result<BatchReport> - Nombre de documents appliqués et indication d'annulation.
+    pub fn upsert_batch<I>(
+        &mut self,
+        items: I,
+        progress: Option<&dyn ProgressSink>,
+    ) -> Result<BatchReport>
+    where
+        I: IntoIterator<Item = (Uuid, Vec<f32>)>,
+    {
+        self.collection.upsert_batch(items, progress)
+    }
+
+    /// Retourne le nombre de documents déjà accumulés.
+    pub fn len(&self) -> usize {
+        self.collection.len()
+    }
+
+    /// Indique si aucun document n'a encore été accumulé.
+    pub fn is_empty(&self) -> bool {
+        self.collection.is_empty()
+    }
+
+    /// Abandonne la reconstruction ; la collection vivante reste inchangée.
+    pub fn abort(self) {}
+}
+
+impl CollectionBuilder<BaseDeDonneesPartagee> {
+    /// Remplace atomiquement la collection vivante par la collection construite, comme
+    /// [`BaseDeDonnees::commit_rebuild`].
+    ///
+    /// Le verrou en écriture n'est tenu que le temps d'échanger les deux pointeurs ;
+    /// l'ancienne collection est libérée après lui, quand le dernier pointeur retourné
+    /// ou tenu par une vue figée disparaît. Si elle était en quarantaine, la nouvelle
+    /// n'y est pas.
+    ///
+    /// # Retourne
+    /// * Option<Arc<Collection>> - L'ancienne collection si elle existait.
+    pub fn commit(self) -> Option<Arc<Collection>> {
+        let cible = self.cible.clone();
+        let mut bdd = cible.write();
+        let nom = database::resolve(&bdd.aliases, &self.nom).to_string();
+        cible.quarantine.release(&nom);
+        let previous = bdd.commit_rebuild(self);
+        drop(bdd);
+        previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    use super::*;
+    use crate::parallel::SearchRuntime;
+    use crate::search::SearchParams;
+    use crate::similarity::Metric;
+
+    fn filled(count: usize, dimension: usize) -> Collection {
+        let mut collection = Collection::new();
+        for i in 0..count {
+            let mut vector = vec![0.0; dimension];
+            vector[i % dimension] = 1.0 + i as f32;
+            collection.upsert(Uuid::new_v4(), vector).unwrap();
+        }
+        collection
+    }
+
+    #[test]
+    fn rebuild_inherits_the_live_settings() {
+        let config = CollectionConfig::builder().metric(Metric::Dot).build().unwrap();
+        let mut live = Collection::from_config(config).with_runtime(SearchRuntime::new(3));
+        live.set_default_params(SearchParams {
+            k: 7,
+            ..Default::default()
+        });
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), live);
+
+        let builder = bdd.begin_rebuild("c");
+        assert_eq!(builder.collection.config().metric(), Metric::Dot);
+        assert_eq!(builder.collection.runtime().threads(), 3);
+        assert_eq!(builder.collection.default_params().k, 7);
+        assert_eq!(bdd.begin_rebuild("absente").collection.default_params(), &SearchParams::default());
+    }
+
+    #[test]
+    fn commit_swaps_the_pointer() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), filled(10, 2));
+        let snapshot = bdd.snapshot();
+        let mut builder = bdd.begin_rebuild("c");
+        builder.upsert(Uuid::new_v4(), vec![1.0, 0.0, 0.0]).unwrap();
+
+        let previous = bdd.commit_rebuild(builder).unwrap();
+        assert!(Arc::ptr_eq(&previous, &snapshot.collections["c"]));
+        assert_eq!(snapshot.get("c").unwrap().len(), 10);
+        assert_eq!(bdd.get("c").unwrap().len(), 1);
+        assert_eq!(bdd.get("c").unwrap().dimension(), Some(3));
+    }
+
+    #[test]
+    fn config_is_fixed_before_the_first_upsert() {
+        let bdd = BaseDeDonnees::new();
+        let config = CollectionConfig::builder().dimension(3).build().unwrap();
+        let mut builder = bdd.begin_rebuild("c").with_config(config.clone()).unwrap();
+        builder.upsert(Uuid::new_v4(), vec![1.0, 0.0, 0.0]).unwrap();
+        assert!(builder.with_config(config).is_err());
+    }
+
+    #[test]
+    fn a_rebuild_may_change_a_fixed_dimension() {
+        let config = CollectionConfig::builder().dimension(2).metric(Metric::Dot).build().unwrap();
+        let mut live = Collection::from_config(config);
+        live.upsert(Uuid::new_v4(), [1.0, 0.0]).unwrap();
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), live);
+        let shared = BaseDeDonneesPartagee::new(bdd);
+
+        let mut builder = shared.begin_rebuild("c");
+        assert_eq!(builder.collection.config().dimension(), None);
+        assert_eq!(builder.collection.config().metric(), Metric::Dot);
+        builder.upsert(Uuid::new_v4(), vec![1.0, 0.0, 0.0]).unwrap();
+        assert!(builder.upsert(Uuid::new_v4(), vec![1.0, 0.0]).is_err());
+        builder.commit().unwrap();
+        assert_eq!(shared.read().get("c").unwrap().dimension(), Some(3));
+    }
+
+    /// Puits qui panique à chaque appel.
+    struct Panicking;
+
+    impl ProgressSink for Panicking {
+        fn on_progress(&self, _: u64, _: Option<u64>) {
+            panic!("puits de progression défaillant");
+        }
+
+        fn should_cancel(&self) -> bool {
+            panic!("puits de progression défaillant");
+        }
+    }
+
+    #[test]
+    fn panicking_sink_cancels_without_poisoning() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("a".to_string(), filled(10, 2));
+        bdd.replace("b".to_string(), filled(10, 2));
+        let shared = BaseDeDonneesPartagee::new(bdd);
+
+        let report = shared.compact_all(Some(&Panicking));
+        assert!(report.cancelled);
+        assert!(!shared.inner.is_poisoned());
+        assert!(shared.quarantined().is_empty());
+        let mut bdd = shared.write();
+        bdd.get_mut("a").unwrap().upsert(Uuid::new_v4(), vec![1.0, 1.0]).unwrap();
+        assert_eq!(bdd.get("a").unwrap().len(), 11);
+    }
+
+    #[test]
+    fn panicking_rescorer_leaves_the_collection_usable() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), filled(10, 2));
+        let shared = BaseDeDonneesPartagee::new(bdd);
+        let rescorer = |_: Uuid, _: f32, _: &[f32], _: Option<&crate::payload::Payload>| -> f32 {
+            panic!("reclassement défaillant")
+        };
+        {
+            let bdd = shared.write();
+            let collection = bdd.get("c").unwrap();
+            let params = SearchParams::default();
+            let error = collection.search_rescored([1.0, 0.0], &params, 5, &rescorer).unwrap_err();
+            assert!(matches!(error.root(), Error::Panicked(_)));
+        }
+        assert!(!shared.inner.is_poisoned());
+        assert!(shared.quarantined().is_empty());
+        assert_eq!(shared.search("c", [1.0, 0.0], 3).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn commit_through_an_alias_leaves_quarantine() {
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), filled(10, 2));
+        bdd.set_alias("courante".to_string(), "c").unwrap();
+        let shared = BaseDeDonneesPartagee::new(bdd);
+        let broken = shared.clone();
+        let _ = thread::spawn(move || {
+            let mut bdd = broken.write();
+            let collection = bdd.get_mut("c").unwrap();
+            collection.payloads.insert(Uuid::new_v4(), Default::default());
+            panic!("écriture interrompue");
+        })
+        .join();
+        assert!(shared.quarantined().contains_key("c"));
+
+        let mut builder = shared.begin_rebuild("courante");
+        builder.upsert(Uuid::new_v4(), vec![1.0, 0.0]).unwrap();
+        builder.commit();
+        assert!(shared.quarantined().is_empty());
+        assert_eq!(shared.read().get("c").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn readers_never_see_a_half_built_collection() {
+        const OLD: usize = 200;
+        const NEW: usize = 500;
+        let mut bdd = BaseDeDonnees::new();
+        bdd.replace("c".to_string(), filled(OLD, 2));
+        let shared = BaseDeDonneesPartagee::new(bdd);
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (shared, done) = (shared.clone(), Arc::clone(&done));
+                thread::spawn(move || {
+                    let mut seen_new = false;
+                    while !done.load(Ordering::Acquire) || !seen_new {
+                        let bdd = shared.read();
+                        let collection = bdd.get("c").unwrap();
+                        match (collection.len(), collection.dimension()) {
+                            (OLD, Some(2)) => assert_eq!(bdd.search("c", [1.0, 1.0], 3).unwrap().len(), 3),
+                            (NEW, Some(3)) => {
+                                assert_eq!(bdd.search("c", [1.0, 1.0, 1.0], 3).unwrap().len(), 3);
+                                seen_new = true;
+                            }
+                            seen => panic!("collection à moitié construite : {:?}", seen),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut builder = shared.begin_rebuild("c");
+        for i in 0..NEW {
+            let mut vector = vec![0.0; 3];
+            vector[i % 3] = 1.0 + i as f32;
+            builder.upsert(Uuid::new_v4(), vector).unwrap();
+            if i % 100 == 0 {
+                thread::yield_now();
+            }
+        }
+        let previous = builder.commit().unwrap();
+        done.store(true, Ordering::Release);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(previous.len(), OLD);
+        assert_eq!(shared.read().get("c").unwrap().len(), NEW);
+    }
+}
@@ -0,0 +1,509 @@
+//! Fonctions de similarité et de distance entre vecteurs.
+//!
+//! Toutes les fonctions publiques vérifient que les deux vecteurs ont la même dimension
+//! et retournent [`Error::DimensionMismatch`] sinon. Les vecteurs vides sont acceptés :
+//! leur produit scalaire et leurs distances valent 0.0.
+//!
+//! Les produits et les sommes sont calculés en `f64` : les carrés de coordonnées `f32`
+//! y sont toujours finis et jamais sous-normaux, si bien que des vecteurs aux
+//! coordonnées extrêmes mais finies, proches de `f32::MAX` ou de `f32::MIN_POSITIVE`,
+//! ont des similarités cosinus exactes et des distances correctement ordonnées, sans
+//! ralentissement dû aux nombres sous-normaux. Seul le score final est ramené en `f32`,
+//! borné à `±f32::MAX` : un produit scalaire ou une distance qui dépasse l'étendue des
+//! `f32` reste fini, à égalité avec les autres scores qui la dépassent.
+
+use crate::core::{self as kernel, check_dimensions, cosine_from, dot_f64, weighted_unchecked};
+use crate::error::Result;
+use crate::parallel::SearchRuntime;
+
+pub(crate) use crate::core::norm_f64;
+pub use crate::core::Metric;
+
+/// Écart maximal entre [`cos_chunked`] et [`cosine`] pour des plongements usuels, aux
+/// coordonnées de signes variés : les deux additionnent les mêmes produits en `f64`
+/// dans un ordre différent, et l'écart mesuré sur des vecteurs aléatoires jusqu'à la
+/// dimension 100 000 reste en deçà. Si toutes les coordonnées ont le même signe et un
+/// grand décalage commun, les erreurs d'arrondi ne se compensent plus : l'écart n'est
+/// alors borné que par `dimension × f64::EPSILON`, avant l'arrondi en `f32`.
+pub const CHUNKED_COSINE_EPSILON: f32 = 1e-5;
+
+impl Metric {
+    /// Calcule le score de cette mesure entre deux vecteurs.
+    ///
+    /// # Arguments
+    /// * `a` - Premier vecteur.
+    /// * `b` - Deuxième vecteur.
+    ///
+    /// # Retourne
+    /// * Result<f32> - Le score, ou une erreur si les dimensions diffèrent.
+    pub fn score(self, a: &[f32], b: &[f32]) -> Result<f32> {
+        Ok(kernel::score(self, a, b)?)
+    }
+
+    /// Calcule le score de cette mesure entre deux vecteurs, chaque dimension `i` comptant
+    /// avec le poids `weights[i]` (voir
+    /// [`CollectionConfigBuilder::dimension_weights`](crate::CollectionConfigBuilder::dimension_weights)).
+    ///
+    /// Le produit scalaire devient `Σ wᵢ aᵢ bᵢ` et les distances `√Σ wᵢ (aᵢ - bᵢ)²` et
+    /// `Σ wᵢ |aᵢ - bᵢ|` ; la similarité cosinus divise le produit pondéré par les normes
+    /// pondérées. Le score est celui de [`Metric::score`] entre les vecteurs multipliés
+    /// par `√wᵢ`, ou par `wᵢ` pour la distance de Manhattan.
+    ///
+    /// # Arguments
+    /// * `weights` - Poids de chaque dimension.
+    /// * `a` - Premier vecteur.
+    /// * `b` - Deuxième vecteur.
+    ///
+    /// # Retourne
+    /// * Result<f32> - Le score, ou une erreur si les dimensions des vecteurs et des
+    ///   poids diffèrent.
+    pub fn score_weighted(self, weights: &[f32], a: &[f32], b: &[f32]) -> Result<f32> {
+        Ok(kernel::score_weighted(self, weights, a, b)?)
+    }
+
+    /// Ramène un score de cette mesure dans [0, 1], 1 désignant les vecteurs les plus proches.
+    ///
+    /// La transformation est croissante pour les similarités et décroissante pour les
+    /// distances : elle ne change jamais l'ordre des résultats.
+    /// * `Cosine` - `(s + 1) / 2`.
+    /// * `Dot` - Sigmoïde `1 / (1 + e^-s)`, qui vaut 0.5 pour un produit nul.
+    /// * `Euclidean`, `Manhattan` - `1 / (1 + d)`, qui vaut 1 pour une distance nulle.
+    ///
+    /// # Arguments
+    /// * `score` - Score brut retourné par [`Metric::score`].
+    pub fn normalize(self, score: f32) -> f32 {
+        self.normalize_with(score, false)
+    }
+
+    /// Comme [`Metric::normalize`], la sigmoïde du produit scalaire étant calculée par
+    /// [`portable_exp`] si `deterministic_math` est vrai.
+    pub(crate) fn normalize_with(self, score: f32, deterministic_math: bool) -> f32 {
+        match self {
+            Metric::Cosine => ((score + 1.0) / 2.0).clamp(0.0, 1.0),
+            Metric::Dot if deterministic_math => {
+                (1.0 / (1.0 + portable_exp(-f64::from(score)))) as f32
+            }
+            Metric::Dot => 1.0 / (1.0 + (-score).exp()),
+            Metric::Euclidean | Metric::Manhattan => 1.0 / (1.0 + score.max(0.0)),
+        }
+    }
+}
+
+/// Mesure avec laquelle une collection calcule ses scores, et ses poids par dimension
+/// éventuels.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Scorer<'a> {
+    pub(crate) metric: Metric,
+    pub(crate) weights: Option<&'a [f32]>,
+}
+
+impl Scorer<'_> {
+    /// Calcule le score sans vérifier les dimensions, qui doivent être égales à celle des
+    /// poids.
+    #[inline]
+    pub(crate) fn score_unchecked(self, a: &[f32], b: &[f32]) -> f32 {
+        match self.weights {
+            Some(weights) => weighted_unchecked(self.metric, weights, a, b),
+            None => self.metric.score_unchecked(a, b),
+        }
+    }
+}
+
+impl From<Metric> for Scorer<'static> {
+    fn from(metric: Metric) -> Self {
+        Scorer {
+            metric,
+            weights: None,
+        }
+    }
+}
+
+/// Calcule la similarité cosinus entre deux vecteurs.
+///
+/// Si l'un des deux vecteurs est nul, la similarité n'est pas définie et vaut 0.0.
+///
+/// # Arguments
+/// * `a` - Premier vecteur.
+/// * `b` - Deuxième vecteur.
+///
+/// # Retourne
+/// * Result<f32> - Similarité cosinus dans [-1, 1].
+#[inline]
+pub fn cosine(a: &[f32], b: &[f32]) -> Result<f32> {
+    Ok(kernel::cosine(a, b)?)
+}
+
+/// Calcule la similarité cosinus comme [`cosine`], en répartissant la dimension en
+/// tranches de `chunk_size` coordonnées traitées en parallèle, dont les produits et les
+/// normes partiels sont additionnés à la fin.
+///
+/// Les threads sont créés pour chaque appel ([`SearchRuntime`]) : le découpage ne paie
+/// qu'en très grande dimension (voir `cargo bench --bench core -- cos_chunked`), ce que
+/// [`SearchRuntime::cosine`] décide d'après [`SearchRuntime::chunked_dimension`]. Le
+/// résultat s'écarte de celui de [`cosine`] d'au plus [`CHUNKED_COSINE_EPSILON`].
+///
+/// # Arguments
+/// * `a` - Premier vecteur.
+/// * `b` - Deuxième vecteur.
+/// * `chunk_size` - Nombre de coordonnées par tranche ; 0 est traité comme 1.
+///
+/// # Retourne
+/// * Result<f32> - Similarité cosinus dans [-1, 1].
+pub fn cos_chunked(a: &[f32], b: &[f32], chunk_size: usize) -> Result<f32> {
+    check_dimensions(a, b)?;
+    Ok(cosine_chunked(SearchRuntime::default(), a, b, chunk_size))
+}
+
+/// Calcule le produit scalaire de deux vecteurs.
+///
+/// # Arguments
+/// * `a` - Premier vecteur.
+/// * `b` - Deuxième vecteur.
+///
+/// # Retourne
+/// * Result<f32> - Produit scalaire.
+#[inline]
+pub fn dot(a: &[f32], b: &[f32]) -> Result<f32> {
+    Ok(kernel::dot(a, b)?)
+}
+
+/// Calcule la distance euclidienne entre deux vecteurs.
+///
+/// # Arguments
+/// * `a` - Premier vecteur.
+/// * `b` - Deuxième vecteur.
+///
+/// # Retourne
+/// * Result<f32> - Distance euclidienne, positive ou nulle.
+#[inline]
+pub fn euclidean(a: &[f32], b: &[f32]) -> Result<f32> {
+    Ok(kernel::euclidean(a, b)?)
+}
+
+/// Calcule la distance de Manhattan entre deux vecteurs.
+///
+/// # Arguments
+/// * `a` - Premier vecteur.
+/// * `b` - Deuxième vecteur.
+///
+/// # Retourne
+/// * Result<f32> - Somme des écarts absolus, positive ou nulle.
+#[inline]
+pub fn manhattan(a: &[f32], b: &[f32]) -> Result<f32> {
+    Ok(kernel::manhattan(a, b)?)
+}
+
+/// Calcule la norme euclidienne d'un vecteur.
+///
+/// La norme d'un vecteur qui dépasse l'étendue des `f32` vaut `f32::INFINITY`.
+#[inline]
+pub fn norm(a: &[f32]) -> f32 {
+    kernel::norm(a)
+}
+
+/// Calcule `e^x` par les seules opérations arithmétiques de base, dans un ordre fixé.
+///
+/// `f64::exp` dépend de la bibliothèque mathématique de la plateforme, dont le dernier
+/// bit varie d'un système à l'autre. Les additions, multiplications et divisions sont
+/// au contraire arrondies de la même façon partout (IEEE 754) et Rust ne les fusionne
+/// jamais : le résultat est identique bit à bit sur toutes les plateformes, à quelques
+/// ulps de la valeur exacte.
+pub(crate) fn portable_exp(x: f64) -> f64 {
+    portable_exp2(x * std::f64::consts::LOG2_E)
+}
+
+/// Calcule `2^x` comme [`portable_exp`].
+pub(crate) fn portable_exp2(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    if x >= 1024.0 {
+        return f64::INFINITY;
+    }
+    if x < -1075.0 {
+        return 0.0;
+    }
+    let whole = x.floor();
+    // 2^f = e^(f ln 2) pour f dans [0, 1) : la série de Taylor, évaluée par Horner,
+    // converge en deçà de l'ulp au 18e terme.
+    let t = (x - whole) * std::f64::consts::LN_2;
+    let mut fraction = 1.0;
+    for n in (1..=18).rev() {
+        fraction = 1.0 + fraction * t / n as f64;
+    }
+    // 2^whole en deux facteurs, pour couvrir les résultats sous-normaux.
+    let whole = whole as i64;
+    let half = whole / 2;
+    let power = |e: i64| f64::from_bits(((e + 1023) as u64) << 52);
+    fraction * power(half) * power(whole - half)
+}
+
+/// Calcule la similarité cosinus par tranches sur les threads de `runtime`.
+pub(crate) fn cosine_chunked(
+    runtime: SearchRuntime,
+    a: &[f32],
+    b: &[f32],
+    chunk_size: usize,
+) -> f32 {
+    let chunk_size = chunk_size.max(1);
+    let chunks: Vec<(&[f32], &[f32])> = a.chunks(chunk_size).zip(b.chunks(chunk_size)).collect();
+    let partials = runtime.with_chunk_size(1).map_chunks(&chunks, 2, |chunks| {
+        chunks
+            .iter()
+            .map(|(a, b)| [dot_f64(a, b), dot_f64(a, a), dot_f64(b, b)])
+            .collect()
+    });
+    let [dot, norm_a, norm_b] = partials.iter().fold([0.0f64; 3], |sum, partial| {
+        [
+            sum[0] + partial[0],
+            sum[1] + partial[1],
+            sum[2] + partial[2],
+        ]
+    });
+    cosine_from(dot, norm_a, norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    const METRICS: [Metric; 4] = [
+        Metric::Cosine,
+        Metric::Dot,
+        Metric::Euclidean,
+        Metric::Manhattan,
+    ];
+
+    fn assert_close(got: f32, expected: f32) {
+        assert!(
+            (got - expected).abs() <= 1e-6 * expected.abs().max(1.0),
+            "{} au lieu de {}",
+            got,
+            expected
+        );
+    }
+
+    #[test]
+    fn portable_exp2_handles_the_extremes() {
+        assert_eq!(portable_exp2(10.0), 1024.0);
+        assert_eq!(portable_exp2(-1074.0), f64::from_bits(1));
+        assert_eq!(portable_exp2(2000.0), f64::INFINITY);
+        assert_eq!(portable_exp2(-2000.0), 0.0);
+        assert!(portable_exp(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn orthogonal_vectors() {
+        let (a, b) = ([1.0, 0.0, 0.0], [0.0, 2.0, 0.0]);
+        assert_eq!(cosine(&a, &b).unwrap(), 0.0);
+        assert_eq!(dot(&a, &b).unwrap(), 0.0);
+        assert_close(euclidean(&a, &b).unwrap(), 5.0f32.sqrt());
+        assert_eq!(manhattan(&a, &b).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn identical_vectors() {
+        let a = [0.3, -1.2, 4.5, 0.0];
+        assert_close(cosine(&a, &a).unwrap(), 1.0);
+        assert_close(dot(&a, &a).unwrap(), 0.09 + 1.44 + 20.25);
+        assert_eq!(euclidean(&a, &a).unwrap(), 0.0);
+        assert_eq!(manhattan(&a, &a).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn opposite_vectors() {
+        let a = [1.0, -2.0, 3.0];
+        let b = a.map(|x: f32| -2.0 * x);
+        assert_close(cosine(&a, &b).unwrap(), -1.0);
+        assert_close(dot(&a, &b).unwrap(), -28.0);
+        assert_close(euclidean(&a, &b).unwrap(), 3.0 * 14.0f32.sqrt());
+        assert_eq!(manhattan(&a, &b).unwrap(), 18.0);
+    }
+
+    #[test]
+    fn zero_vectors() {
+        let (zero, a) = ([0.0; 3], [3.0, 4.0, 0.0]);
+        // La similarité cosinus d'un vecteur nul n'est pas définie : elle vaut 0.0.
+        assert_eq!(cosine(&zero, &a).unwrap(), 0.0);
+        assert_eq!(cosine(&a, &zero).unwrap(), 0.0);
+        assert_eq!(cosine(&zero, &zero).unwrap(), 0.0);
+        assert_eq!(dot(&zero, &a).unwrap(), 0.0);
+        assert_eq!(euclidean(&zero, &a).unwrap(), 5.0);
+        assert_eq!(manhattan(&zero, &a).unwrap(), 7.0);
+        assert_eq!(norm(&zero), 0.0);
+        assert_eq!(norm(&a), 5.0);
+    }
+
+    #[test]
+    fn empty_vectors_score_zero() {
+        for metric in METRICS {
+            assert_eq!(metric.score(&[], &[]).unwrap(), 0.0, "{:?}", metric);
+        }
+        assert_eq!(norm(&[]), 0.0);
+    }
+
+    #[test]
+    fn mismatched_lengths_are_errors() {
+        let (a, b) = ([1.0, 2.0, 3.0], [1.0, 2.0]);
+        let mismatch = |result: Result<f32>| {
+            matches!(
+                result,
+                Err(Error::DimensionMismatch {
+                    expected: 3,
+                    got: 2
+                })
+            )
+        };
+        assert!(mismatch(cosine(&a, &b)));
+        assert!(mismatch(cos_chunked(&a, &b, 1)));
+        assert!(mismatch(dot(&a, &b)));
+        assert!(mismatch(euclidean(&a, &b)));
+        assert!(mismatch(manhattan(&a, &b)));
+        for metric in METRICS {
+            assert!(mismatch(metric.score(&a, &b)), "{:?}", metric);
+            assert!(metric.score_weighted(&[1.0; 2], &a, &a).is_err(), "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn metric_score_routes_to_the_kernels() {
+        let (a, b) = ([0.5, -1.0, 2.0, 0.25], [1.5, 0.5, -0.5, 1.0]);
+        assert_eq!(Metric::Cosine.score(&a, &b).unwrap(), cosine(&a, &b).unwrap());
+        assert_eq!(Metric::Dot.score(&a, &b).unwrap(), dot(&a, &b).unwrap());
+        assert_eq!(Metric::Euclidean.score(&a, &b).unwrap(), euclidean(&a, &b).unwrap());
+        assert_eq!(Metric::Manhattan.score(&a, &b).unwrap(), manhattan(&a, &b).unwrap());
+        for metric in METRICS {
+            let scorer = Scorer::from(metric);
+            assert_eq!(scorer.score_unchecked(&a, &b), metric.score(&a, &b).unwrap());
+        }
+    }
+
+    #[test]
+    fn unit_weights_change_nothing_and_weights_scale_dimensions() {
+        let (a, b) = ([0.5, -1.0, 2.0], [1.5, 0.5, -0.5]);
+        for metric in METRICS {
+            assert_close(
+                metric.score_weighted(&[1.0; 3], &a, &b).unwrap(),
+                metric.score(&a, &b).unwrap(),
+            );
+        }
+        let weights = [4.0, 1.0, 0.0];
+        let scaled = |v: [f32; 3]| [2.0 * v[0], v[1], 0.0];
+        for metric in [Metric::Cosine, Metric::Dot, Metric::Euclidean] {
+            assert_close(
+                metric.score_weighted(&weights, &a, &b).unwrap(),
+                metric.score(&scaled(a), &scaled(b)).unwrap(),
+            );
+        }
+        assert_close(
+            Metric::Manhattan.score_weighted(&weights, &a, &b).unwrap(),
+            4.0 * 1.0 + 1.5,
+        );
+    }
+
+    #[test]
+    fn extreme_finite_coordinates_stay_exact_and_finite() {
+        let big = [f32::MAX, f32::MAX];
+        assert_close(cosine(&big, &big).unwrap(), 1.0);
+        assert_eq!(dot(&big, &big).unwrap(), f32::MAX);
+        assert_eq!(euclidean(&big, &big.map(|x| -x)).unwrap(), f32::MAX);
+        assert_eq!(norm(&big), f32::INFINITY);
+        let tiny = [f32::MIN_POSITIVE, 0.0];
+        assert_close(cosine(&tiny, &[f32::MIN_POSITIVE, f32::MIN_POSITIVE]).unwrap(), 0.5f32.sqrt());
+        assert!(euclidean(&tiny, &[0.0, 0.0]).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn chunked_cosine_matches_cosine() {
+        let a: Vec<f32> = (0..1000).map(|i| ((i * 37 % 101) as f32 - 50.0) / 7.0).collect();
+        let b: Vec<f32> = (0..1000).map(|i| ((i * 53 % 97) as f32 - 48.0) / 5.0).collect();
+        let expected = cosine(&a, &b).unwrap();
+        for chunk_size in [0, 1, 7, 64, 999, 1000, 5000] {
+            let got = cos_chunked(&a, &b, chunk_size).unwrap();
+            assert!((got - expected).abs() <= CHUNKED_COSINE_EPSILON, "tranches de {}", chunk_size);
+        }
+        let runtime = SearchRuntime::new(4).with_chunked_dimension(10);
+        assert!((runtime.cosine(&a, &b).unwrap() - expected).abs() <= CHUNKED_COSINE_EPSILON);
+        assert!(runtime.cosine(&a, &b[1..]).is_err());
+    }
+
+    #[test]
+    fn normalized_scores_are_in_unit_range_and_keep_order() {
+        assert_eq!(Metric::Cosine.normalize(-1.0), 0.0);
+        assert_eq!(Metric::Cosine.normalize(1.0), 1.0);
+        assert_eq!(Metric::Dot.normalize(0.0), 0.5);
+        assert_eq!(Metric::Euclidean.normalize(0.0), 1.0);
+        assert_eq!(Metric::Manhattan.normalize(1.0), 0.5);
+        for metric in METRICS {
+            let scores = [-3.0f32, -0.5, 0.0, 0.25, 1.0, 8.0];
+            let normalized: Vec<f32> = scores.iter().map(|&s| metric.normalize(s)).collect();
+            assert!(normalized.iter().all(|n| (0.0..=1.0).contains(n)), "{:?}", metric);
+            let nondecreasing = normalized.windows(2).all(|w| w[0] <= w[1]);
+            let nonincreasing = normalized.windows(2).all(|w| w[0] >= w[1]);
+            assert!(nondecreasing || nonincreasing, "{:?}", metric);
+            assert_close(
+                metric.normalize_with(0.75, true),
+                metric.normalize_with(0.75, false),
+            );
+        }
+    }
+
+    #[test]
+    fn portable_exp_tracks_exp() {
+        for x in [-700.0, -20.5, -1.0, -1e-9, 0.0, 0.5, 1.0, 10.25, 700.0] {
+            let (got, expected) = (portable_exp(x), x.exp());
+            // L'arrondi de `x * log2(e)` compte pour une erreur relative proportionnelle à x.
+            let tolerance = 4.0 * f64::EPSILON * f64::max(x.abs(), 1.0);
+            assert!((got - expected).abs() <= tolerance * expected, "e^{}", x);
+        }
+        assert_eq!(portable_exp2(10.0), 1024.0);
+        assert_eq!(portable_exp2(-1074.0), f64::from_bits(1));
+        assert_eq!(portable_exp2(1024.0), f64::INFINITY);
+        assert_eq!(portable_exp2(-2000.0), 0.0);
+        assert!(portable_exp(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn chunked_cosine_stays_within_its_epsilon() {
+        let mut generator = crate::synthetic::VectorGenerator::new(100_000, 178);
+        let (long_a, long_b) = (generator.vector(), generator.vector());
+        for dimension in [1, 7, 1000, 12_288, 100_000] {
+            let (a, b) = (&long_a[..dimension], &long_b[..dimension]);
+            let expected = cosine(a, b).unwrap();
+            for chunk_size in [0, 1, 3, 1000, dimension, dimension + 1] {
+                let got = cos_chunked(a, b, chunk_size).unwrap();
+                assert!(
+                    (got - expected).abs() <= CHUNKED_COSINE_EPSILON,
+                    "dimension {}, tranches de {} : {} au lieu de {}",
+                    dimension,
+                    chunk_size,
+                    got,
+                    expected
+                );
+            }
+        }
+        assert_eq!(cos_chunked(&[0.0; 4], &[1.0; 4], 2).unwrap(), 0.0);
+        assert!(matches!(
+            cos_chunked(&[1.0; 4], &[1.0; 3], 2),
+            Err(Error::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn the_runtime_chunks_only_from_its_threshold() {
+        let mut generator = crate::synthetic::VectorGenerator::new(4096, 9);
+        let (a, b) = (generator.vector(), generator.vector());
+        let runtime = SearchRuntime::new(4).with_chunked_dimension(2048);
+        assert_eq!(runtime.chunked_dimension(), 2048);
+        assert_eq!(SearchRuntime::new(4).chunked_dimension(), crate::parallel::DEFAULT_CHUNKED_DIMENSION);
+        // En deçà du seuil, le calcul est celui d'un seul passage, au bit près.
+        assert_eq!(
+            runtime.cosine(&a[..2047], &b[..2047]).unwrap().to_bits(),
+            cosine(&a[..2047], &b[..2047]).unwrap().to_bits()
+        );
+        let chunked = runtime.cosine(&a, &b).unwrap();
+        assert_eq!(chunked.to_bits(), cosine_chunked(runtime, &a, &b, 1024).to_bits());
+        assert!((chunked - cosine(&a, &b).unwrap()).abs() <= CHUNKED_COSINE_EPSILON);
+    }
+}
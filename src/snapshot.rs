@@ -0,0 +1,1659 @@
+//! Sauvegarde et chargement d'une base de données dans un fichier binaire.
+//!
+//! Format (entiers et flottants en petit-boutiste) :
+//!
+//! ```text
+//! MAGIC (8 octets) | version: u32 | longueur de la table: u64 | table des matières
+//!                  | nombre de collections: u64 | (nom | collection)*
+//!                  | nombre de collections partitionnées: u64
+//!                  | (nom | nombre de partitions: u64 | collection*)*
+//!                  | nombre d'alias: u64 | (alias | nom de la collection)*
+//! collection = configuration | projection optionnelle | documents | charges utiles
+//!              | identifiants (Uuid | type: u8 | entier: i64 ou chaîne)*
+//!              | paramètres de recherche par défaut | déduplication
+//!              | espaces de noms (Uuid | espace de noms)*
+//!              | index de charge utile (champ | type: u8)* | schéma
+//!              | dimension fixée par la première insertion: u64 (0 si aucune)
+//!              | adaptateur de requêtes optionnel: projection sans centre
+//!              | calibration optionnelle: (u8 | mesure: u8 | pente: f64 | ordonnée: f64)
+//!              | tolérance de norme unitaire: f32
+//!              | requêtes enregistrées (nom | dimension: u64 | f32* | paramètres)*
+//!              | suivi des nouveaux documents: u8
+//!              | documents en attente de vecteur (Uuid | charge utile)*
+//!              | espace de noms des clés externes: Uuid
+//!              | champ des identifiants externes optionnel: (u8 | chaîne)
+//! configuration = mesure: u8 | politique des vecteurs nuls: u8 | dimensions strictes: u8
+//!                 | dimension: u64 (0 si libre) | normalisation: u8
+//!                 | suppression différée: u8 | éviction: u8 | partage des vecteurs: u8
+//!                 | vecteurs déclarés unitaires: u8 | modèle optionnel: (u8 | modèle)
+//!                 | poids des dimensions: (nombre: u64 | f32*)
+//!                 | plage des normes optionnelle: (u8 | min: f32 | max: f32)
+//!                 | suivi des accès: u8 | bruit des agrégats optionnel: (u8 | epsilon: f64)
+//! paramètres = k: u64 | seuil optionnel: (u8 | f32) | budget optionnel: (u8 | ns: u64)
+//!              | mode de score: u8 | espace de noms optionnel: (u8 | chaîne)
+//!              | nombre exact de résultats: u8 | mesure des durées: u8
+//!              | modèle optionnel: (u8 | modèle) | modèle différent accepté: u8
+//!              | sélection optionnelle: (u8 | nombre: u64 | champ*) | vecteurs joints: u8
+//!              | atténuation optionnelle: (u8 | atténuation)
+//!              | seuil des doublons optionnel: (u8 | f32) | réserve des doublons: u64
+//!              | calcul déterministe: u8
+//!              | part échantillonnée optionnelle: (u8 | f32) | graine de l'échantillon: u64
+//!              | écart minimal optionnel: (u8 | f32)
+//!              | masque des dimensions optionnel: (u8 | nombre: u64 | dimension: u64*)
+//!              | stratégie de filtrage: u8
+//! atténuation = champ: chaîne | demi-vie ns: u64 | forme: u8
+//!               | sans date: u8 (0 sans atténuation, 1 pénalité | facteur: f32)
+//!               | date de référence optionnelle: (u8 | ns depuis l'époque Unix: u64)
+//! table des matières = nombre d'entrées: u64
+//!                      | (nom | partitionnée: u8 | position: u64 | longueur: u64
+//!                         | empreinte: u64)*
+//! modèle = nom: chaîne | empreinte des poids optionnelle: (u8 | chaîne)
+//! déduplication = politique: u8 (0 si inactive, 1 refus, 2 ignorer) | seuil: f32 si active
+//! schéma = mode: u8 (0 sans schéma, 1 strict, 2 souple)
+//!          | (champ | type: u8 | obligatoire: u8)* si présent
+//! ```
+//!
+//! Les fichiers des versions précédentes restent lisibles : ceux de version 1, antérieurs
+//! aux collections partitionnées, s'arrêtent après les collections simples ; la
+//! configuration s'arrête après la politique des vecteurs nuls en versions 1 et 2, et
+//! après les dimensions strictes en version 3. Les identifiants entiers et textuels
+//! n'apparaissent qu'à partir de la version 5, les paramètres de recherche par défaut
+//! à partir de la version 6, la déduplication à partir de la version 7, les alias de
+//! collections à partir de la version 8, la suppression différée à partir de la
+//! version 9, les espaces de noms à partir de la version 10, les index de charge utile
+//! à partir de la version 11, l'exigence d'un nombre exact de résultats à partir de
+//! la version 12, l'éviction à partir de la version 13, le schéma des charges utiles
+//! à partir de la version 14, la mesure des durées de recherche à partir de la
+//! version 15, la dimension fixée par la première insertion à partir de la version
+//! 16 (une collection plus ancienne dont tous les documents ont la même dimension la
+//! reçoit au chargement), l'adaptateur de requêtes à partir de la version 17, la
+//! calibration des scores à partir de la version 18, le partage des vecteurs
+//! identiques à partir de la version 19, la déclaration de vecteurs unitaires, avec
+//! sa tolérance, à partir de la version 20, l'empreinte du modèle de plongement à
+//! partir de la version 21, les requêtes enregistrées à partir de la version 22, les
+//! poids des dimensions à partir de la version 23, la sélection des charges utiles
+//! et des vecteurs joints aux résultats à partir de la version 24, l'atténuation des
+//! scores selon l'âge des documents à partir de la version 25, l'élimination des
+//! doublons parmi les résultats à partir de la version 26, le calcul déterministe
+//! des scores à partir de la version 27, les documents en attente de vecteur à
+//! partir de la version 28, l'échantillonnage des recherches à partir de la version
+//! 29, la plage des normes admises à partir de la version 30, la dérivation des clés
+//! externes à partir de la version 31, l'écart minimal entre les deux premiers
+//! résultats à partir de la version 32, le suivi des accès à partir de la version 33,
+//! le bruit des statistiques agrégées à partir de la version 34, le masque des
+//! dimensions des recherches à partir de la version 35, la stratégie des recherches
+//! filtrées à partir de la version 36, le pipeline des requêtes à partir de la
+//! version 37 et la table des matières à partir de la version 38. Les compteurs d'accès
+//! eux-mêmes ne sont pas sauvegardés.
+//!
+//! La table des matières a une entrée par collection, simples puis partitionnées, dans
+//! l'ordre du fichier : la position depuis le début du fichier et la longueur de la
+//! collection, nombre de partitions compris pour une collection partitionnée mais sans
+//! son nom, et l'empreinte FNV-1a 64 bits de ces octets. Elle permet de n'en charger que
+//! certaines ([`BaseDeDonnees::load_collections`]) ; un chargement complet vérifie
+//! chaque entrée.
+//!
+//! Les vecteurs partagés sont écrits pour chaque document : le partage est reconstruit
+//! au chargement.
+//!
+//! Seule la définition des index de charge utile est sauvegardée : ils sont reconstruits
+//! au chargement.
+//!
+//! Les documents supprimés en attente de compactage ne sont pas sauvegardés : une
+//! collection rechargée est toujours compactée.
+//!
+//! Les collections, documents et charges utiles sont écrits triés par clé : deux bases
+//! identiques produisent des fichiers identiques.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+use crate::access::AccessLog;
+use crate::adapter::QueryAdapter;
+use crate::calibration::Calibration;
+use crate::collection::{Collection, ZeroVectorPolicy};
+use crate::config::{CollectionConfig, NormRange};
+use crate::contents::{self, Toc, TocWriter};
+use crate::database::BaseDeDonnees;
+use crate::decay::{DecayParams, DecayShape, MissingTimestamp};
+use crate::dedup::DedupPolicy;
+use crate::document_id::DocumentId;
+use crate::filter::FilterStrategy;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::keys::ExternalKeys;
+use crate::memory::{BudgetSlot, MemoryUsage, Recency};
+use crate::model::ModelFingerprint;
+use crate::namespace::Namespaces;
+use crate::oplog::OperationLog;
+use crate::parallel::SearchRuntime;
+use crate::payload::{Payload, Value};
+use crate::payload_index::IndexKind;
+use crate::privacy::{self, AggregateNoise};
+use crate::projection::Projection;
+use crate::query_pipeline::{BuiltinTransform, QueryPipeline, Unresolved};
+use crate::saved_search::SavedSearches;
+use crate::schema::{FieldSchema, FieldType, PayloadSchema, SchemaMode};
+use crate::search::{ScoreMode, SearchParams, DEFAULT_DEDUP_POOL_FACTOR};
+use crate::segmented::SegmentedMap;
+use crate::sharded::ShardedCollection;
+use crate::similarity::Metric;
+use crate::storage;
+use crate::unit_norm::DEFAULT_UNIT_NORM_TOLERANCE;
+use crate::vector_pool::VectorPool;
+use crate::vector_stats::VectorStats;
+use crate::weights::{self, Weights};
+
+pub(crate) const MAGIC: &[u8; 8] = b"EMBEDDB\0";
+/// Signature des sauvegardes chiffrées, connue même sans la fonctionnalité
+/// `encryption` pour les reconnaître.
+pub(crate) const ENCRYPTED_MAGIC: &[u8; 8] = b"EMBEDENC";
+pub(crate) const VERSION: u32 = 38;
+
+/// Profondeur maximale d'imbrication des valeurs de charge utile acceptée au chargement.
+const MAX_VALUE_DEPTH: usize = 64;
+
+/// Longueur maximale, en octets, d'un nom de collection ou d'alias accepté au chargement.
+const MAX_NAME_LEN: usize = 4096;
+
+impl BaseDeDonnees {
+    /// Enregistre la base de données dans un fichier.
+    ///
+    /// Le fichier est d'abord écrit et synchronisé sur le disque à côté de sa
+    /// destination, puis renommé : en cas d'interruption, l'ancien fichier reste intact.
+    /// L'écriture passe par l'accès aux fichiers de la base
+    /// ([`BaseDeDonnees::with_storage`]).
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier à créer ou remplacer.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être écrit.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        storage::replace(self.storage(), path.as_ref(), &self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Charge une base de données enregistrée avec [`BaseDeDonnees::save`].
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    ///
+    /// # Retourne
+    /// * Result<BaseDeDonnees> - La base de données lue.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le fichier ne peut pas être lu.
+    /// * `Error::InvalidSnapshot` - Si le contenu du fichier est invalide.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Charge une base de données comme [`BaseDeDonnees::load`], en remplaçant la mesure
+    /// de certaines collections.
+    ///
+    /// La configuration complète de chaque collection est sauvegardée et rétablie au
+    /// chargement. Changer de mesure n'est pas anodin : les vecteurs stockés ont pu être
+    /// normalisés ou choisis pour la mesure d'origine, et les résultats ne sont pas
+    /// comparables. Le changement doit donc être accepté explicitement avec
+    /// [`LoadOverrides::allow_metric_change`].
+    ///
+    /// # Arguments
+    /// * `path` - Chemin du fichier.
+    /// * `overrides` - Mesures à imposer.
+    ///
+    /// # Erreurs
+    /// * Celles de [`BaseDeDonnees::load`].
+    /// * `Error::CollectionNotFound` - Si une mesure vise une collection absente du fichier.
+    /// * `Error::InvalidConfig` - Si une mesure diffère de celle sauvegardée sans que le
+    ///   changement soit accepté.
+    pub fn load_with_overrides(path: impl AsRef<Path>, overrides: &LoadOverrides) -> Result<Self> {
+        let mut bdd = Self::load(path)?;
+        for (nom, metric) in &overrides.metrics {
+            let saved = match (bdd.collections.get(nom), bdd.sharded.get(nom)) {
+                (Some(collection), _) => collection.metric(),
+                (None, Some(collection)) => collection.shards()[0].metric(),
+                (None, None) => return Err(Error::CollectionNotFound(nom.clone())),
+            };
+            if saved == *metric {
+                continue;
+            }
+            if !overrides.allow_metric_change {
+                return Err(Error::InvalidConfig(format!(
+                    "la collection '{}' a été sauvegardée avec la mesure {:?} et non {:?} ; \
+                     acceptez le changement avec LoadOverrides::allow_metric_change",
+                    nom, saved, metric
+                )));
+            }
+            if let Some(collection) = bdd.collections.get_mut(nom) {
+                Arc::make_mut(collection).config.metric = *metric;
+            } else if let Some(collection) = bdd.sharded.get_mut(nom) {
+                for shard in Arc::make_mut(collection).shards_mut() {
+                    shard.config.metric = *metric;
+                }
+            }
+        }
+        Ok(bdd)
+    }
+
+    /// Sérialise la base de données au format des fichiers de sauvegarde.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        put_u32(&mut out, VERSION);
+        let mut collections: Vec<(&String, &Collection)> = self
+            .collections
+            .iter()
+            .map(|(nom, collection)| (nom, &**collection))
+            .collect();
+        collections.sort_unstable_by_key(|(nom, _)| *nom);
+        let mut sharded: Vec<(&String, &ShardedCollection)> = self
+            .sharded
+            .iter()
+            .map(|(nom, collection)| (nom, &**collection))
+            .collect();
+        sharded.sort_unstable_by_key(|(nom, _)| *nom);
+        let names = (collections.iter().map(|(nom, _)| (nom.as_str(), false)))
+            .chain(sharded.iter().map(|(nom, _)| (nom.as_str(), true)));
+        let mut toc = TocWriter::new(&mut out, names);
+        put_len(&mut out, collections.len());
+        for (nom, collection) in collections {
+            put_str(&mut out, nom);
+            let start = out.len();
+            write_collection(&mut out, collection);
+            toc.fill(&mut out, start);
+        }
+        put_len(&mut out, sharded.len());
+        for (nom, collection) in sharded {
+            put_str(&mut out, nom);
+            let start = out.len();
+            put_len(&mut out, collection.shard_count());
+            for shard in collection.shards() {
+                write_collection(&mut out, shard);
+            }
+            toc.fill(&mut out, start);
+        }
+        let aliases = self.aliases();
+        put_len(&mut out, aliases.len());
+        for (alias, target) in aliases {
+            put_str(&mut out, alias);
+            put_str(&mut out, target);
+        }
+        out
+    }
+
+    /// Lit une base de données sérialisée avec [`BaseDeDonnees::to_bytes`].
+    ///
+    /// Les octets peuvent venir d'une source non fiable : une entrée corrompue ou forgée
+    /// produit une erreur, jamais une panique, et les longueurs annoncées sont bornées par
+    /// la taille des données avant toute allocation. Chaque collection chargée est
+    /// vérifiée par [`Collection::check_invariants`].
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidSnapshot` - Si les octets ne forment pas une sauvegarde valide.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut bdd = BaseDeDonnees::new();
+        read_database(bytes, &mut bdd, &mut Verify)?;
+        Ok(bdd)
+    }
+}
+
+/// Réglages imposés au chargement par [`BaseDeDonnees::load_with_overrides`].
+///
+/// `LoadOverrides::new().metric("articles", Metric::Dot).allow_metric_change(true)`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LoadOverrides {
+    metrics: BTreeMap<String, Metric>,
+    allow_metric_change: bool,
+}
+
+impl LoadOverrides {
+    /// Crée des réglages qui ne changent rien.
+    pub fn new() -> Self {
+        LoadOverrides::default()
+    }
+
+    /// Impose la mesure `metric` à la collection `nom`, simple ou partitionnée.
+    pub fn metric(mut self, nom: impl Into<String>, metric: Metric) -> Self {
+        self.metrics.insert(nom.into(), metric);
+        self
+    }
+
+    /// Accepte qu'une mesure imposée diffère de celle sauvegardée.
+    pub fn allow_metric_change(mut self, allow: bool) -> Self {
+        self.allow_metric_change = allow;
+        self
+    }
+}
+
+/// Vérification de chaque collection lue par [`read_database`], avant son ajout à la base.
+pub(crate) trait Admit {
+    fn collection(&mut self, nom: &str, collection: &mut Collection) -> Result<()>;
+    fn sharded(&mut self, nom: &str, collection: &mut ShardedCollection) -> Result<()>;
+}
+
+/// Refuse toute collection incohérente : c'est la vérification du chargement normal.
+pub(crate) struct Verify;
+
+impl Admit for Verify {
+    fn collection(&mut self, nom: &str, collection: &mut Collection) -> Result<()> {
+        collection
+            .check_invariants()
+            .map_err(|e| invalid(format!("collection '{}' : {}", nom, e)))
+    }
+
+    fn sharded(&mut self, nom: &str, collection: &mut ShardedCollection) -> Result<()> {
+        collection
+            .check_invariants()
+            .map_err(|e| invalid(format!("collection '{}' : {}", nom, e)))
+    }
+}
+
+/// Lit une sauvegarde dans `bdd`, en soumettant chaque collection à `admit`.
+///
+/// En cas d'erreur, `bdd` contient les collections lues avant celle qui a échoué.
+///
+/// # Retourne
+/// * Result<u32> - La version du format de la sauvegarde.
+pub(crate) fn read_database(
+    bytes: &[u8],
+    bdd: &mut BaseDeDonnees,
+    admit: &mut dyn Admit,
+) -> Result<u32> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("signature de fichier inconnue"));
+    }
+    let version = reader.u32()?;
+    if version == 0 || version > VERSION {
+        return Err(invalid(format!("version {} non prise en charge", version)));
+    }
+    let toc = match version >= 38 {
+        true => {
+            let len = reader.len(1)?;
+            Some(contents::read_toc(reader.take(len)?)?)
+        }
+        false => None,
+    };
+    let mut sections = 0;
+    for _ in 0..reader.len(1)? {
+        let nom = reader.name()?;
+        let context = ErrorContext::new(Operation::Load).collection(&nom);
+        let collection = read_entry(&mut reader, toc.as_ref(), sections, &nom, false, |r| {
+            read_collection(r, version)
+        })
+        .and_then(|mut collection| {
+            admit.collection(&nom, &mut collection)?;
+            Ok(collection)
+        })
+        .map_err(|e| e.with_context(context))?;
+        if bdd.collections.contains_key(&nom) {
+            return Err(invalid(format!("collection '{}' en double", nom)));
+        }
+        bdd.collections.insert(nom, Arc::new(collection));
+        sections += 1;
+    }
+    if version >= 2 {
+        for _ in 0..reader.len(1)? {
+            let nom = reader.name()?;
+            let context = ErrorContext::new(Operation::Load).collection(&nom);
+            let collection = read_entry(&mut reader, toc.as_ref(), sections, &nom, true, |r| {
+                read_sharded(r, version, &nom)
+            })
+            .and_then(|mut collection| {
+                admit.sharded(&nom, &mut collection)?;
+                Ok(collection)
+            })
+            .map_err(|e| e.with_context(context))?;
+            if bdd.collections.contains_key(&nom) || bdd.sharded.contains_key(&nom) {
+                return Err(invalid(format!("collection '{}' en double", nom)));
+            }
+            bdd.sharded.insert(nom, Arc::new(collection));
+            sections += 1;
+        }
+    }
+    if toc.is_some_and(|toc| toc.len() != sections) {
+        return Err(invalid(
+            "la table des matières ne correspond pas aux collections du fichier",
+        ));
+    }
+    let count = if version >= 8 { reader.len(16)? } else { 0 };
+    for _ in 0..count {
+        let alias = reader.name()?;
+        let target = reader.name()?;
+        bdd.set_alias(alias.clone(), &target)
+            .map_err(|e| invalid(format!("alias '{}' : {}", alias, e)))?;
+    }
+    if !reader.is_at_end() {
+        return Err(invalid("octets inattendus après la dernière collection"));
+    }
+    Ok(version)
+}
+
+/// Lit avec `read` la collection suivante de `reader`, délimitée et vérifiée par
+/// l'entrée `index` de la table des matières s'il y en a une.
+fn read_entry<T>(
+    reader: &mut Reader,
+    toc: Option<&Toc>,
+    index: usize,
+    nom: &str,
+    sharded: bool,
+    read: impl FnOnce(&mut Reader) -> Result<T>,
+) -> Result<T> {
+    match toc {
+        Some(toc) => contents::read_section(toc.section(index, nom, sharded, reader)?, read),
+        None => read(reader),
+    }
+}
+
+/// Lit les partitions d'une collection partitionnée `nom`.
+pub(crate) fn read_sharded(
+    reader: &mut Reader,
+    version: u32,
+    nom: &str,
+) -> Result<ShardedCollection> {
+    let shard_count = reader.len(1)?;
+    if shard_count == 0 {
+        return Err(invalid(format!("collection '{}' sans partition", nom)));
+    }
+    let shards = (0..shard_count)
+        .map(|_| read_collection(reader, version))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ShardedCollection::from_shards(shards))
+}
+
+pub(crate) fn invalid(description: impl Into<String>) -> Error {
+    Error::InvalidSnapshot(description.into())
+}
+
+fn write_collection(out: &mut Vec<u8>, collection: &Collection) {
+    write_config(out, &collection.config);
+    write_projection(out, collection.projection.as_ref());
+
+    let mut documents: Vec<(&Uuid, &Arc<Vec<f32>>)> = collection.documents.iter().collect();
+    documents.sort_unstable_by_key(|(key, _)| **key);
+    put_len(out, documents.len());
+    for (key, vector) in documents {
+        out.extend_from_slice(key.as_bytes());
+        put_len(out, vector.len());
+        put_f32s(out, vector);
+    }
+
+    let mut payloads: Vec<(&Uuid, &Payload)> = collection.payloads.iter().collect();
+    payloads.sort_unstable_by_key(|(key, _)| **key);
+    put_len(out, payloads.len());
+    for (key, payload) in payloads {
+        out.extend_from_slice(key.as_bytes());
+        write_object(out, payload);
+    }
+
+    let mut aliases: Vec<(&Uuid, &DocumentId)> = collection.aliases.iter().collect();
+    aliases.sort_unstable_by_key(|(key, _)| **key);
+    put_len(out, aliases.len());
+    for (key, id) in aliases {
+        out.extend_from_slice(key.as_bytes());
+        match id {
+            DocumentId::Uuid(_) => unreachable!("un Uuid n'a pas d'alias"),
+            DocumentId::Int(n) => {
+                out.push(1);
+                put_u64(out, *n as u64);
+            }
+            DocumentId::Str(s) => {
+                out.push(2);
+                put_str(out, s);
+            }
+        }
+    }
+
+    write_search_params(out, &collection.default_params);
+    match collection.dedup_threshold {
+        None => out.push(0),
+        Some(threshold) => {
+            out.push(match collection.dedup_policy {
+                DedupPolicy::Reject => 1,
+                DedupPolicy::Skip => 2,
+            });
+            put_f32s(out, &[threshold]);
+        }
+    }
+
+    let mut namespaces: Vec<(&Uuid, &str)> = collection.namespaces.iter().collect();
+    namespaces.sort_unstable_by_key(|(key, _)| **key);
+    put_len(out, namespaces.len());
+    for (key, namespace) in namespaces {
+        out.extend_from_slice(key.as_bytes());
+        put_str(out, namespace);
+    }
+
+    let indexes = collection.payload_indexes();
+    put_len(out, indexes.len());
+    for (field, kind) in indexes {
+        put_str(out, field);
+        out.push(match kind {
+            IndexKind::Keyword => 0,
+            IndexKind::Numeric => 1,
+        });
+    }
+
+    match &collection.schema {
+        None => out.push(0),
+        Some(schema) => {
+            out.push(match schema.mode() {
+                SchemaMode::Strict => 1,
+                SchemaMode::Lenient => 2,
+            });
+            let fields: Vec<(&str, &FieldSchema)> = schema.fields().collect();
+            put_len(out, fields.len());
+            for (name, field) in fields {
+                put_str(out, name);
+                out.push(match field.kind {
+                    FieldType::Bool => 0,
+                    FieldType::Number => 1,
+                    FieldType::String => 2,
+                    FieldType::Array => 3,
+                    FieldType::Object => 4,
+                });
+                out.push(u8::from(field.required));
+            }
+        }
+    }
+    put_len(out, collection.locked_dimension.unwrap_or(0));
+    write_projection(
+        out,
+        collection.adapter.as_ref().map(QueryAdapter::projection),
+    );
+    match &collection.calibration {
+        None => out.push(0),
+        Some(calibration) => {
+            out.push(1);
+            out.push(metric_code(calibration.metric()));
+            put_u64(out, calibration.slope().to_bits());
+            put_u64(out, calibration.intercept().to_bits());
+        }
+    }
+    put_f32s(out, &[collection.unit_tolerance]);
+    put_len(out, collection.saved.queries.len());
+    for (name, (vector, params)) in &collection.saved.queries {
+        put_str(out, name);
+        put_len(out, vector.len());
+        put_f32s(out, vector);
+        write_search_params(out, params);
+    }
+    out.push(u8::from(collection.saved.watching));
+    let mut pending: Vec<(&Uuid, &Payload)> = collection.pending.iter().collect();
+    pending.sort_unstable_by_key(|(key, _)| **key);
+    put_len(out, pending.len());
+    for (key, payload) in pending {
+        out.extend_from_slice(key.as_bytes());
+        write_object(out, payload);
+    }
+    out.extend_from_slice(collection.external_keys.namespace.as_bytes());
+    match &collection.external_keys.field {
+        None => out.push(0),
+        Some(field) => {
+            out.push(1);
+            put_str(out, field);
+        }
+    }
+}
+
+fn write_projection(out: &mut Vec<u8>, projection: Option<&Projection>) {
+    match projection {
+        None => out.push(0),
+        Some(projection) => {
+            out.push(1);
+            put_len(out, projection.input_dim());
+            put_len(out, projection.output_dim());
+            put_f32s(out, projection.matrix());
+            match projection.center() {
+                None => out.push(0),
+                Some(center) => {
+                    out.push(1);
+                    put_f32s(out, center);
+                }
+            }
+        }
+    }
+}
+
+fn read_projection(reader: &mut Reader) -> Result<Option<Projection>> {
+    Ok(match reader.u8()? {
+        0 => None,
+        1 => {
+            let input_dim = reader.len(0)?;
+            let output_dim = reader.len(0)?;
+            let size = input_dim
+                .checked_mul(output_dim)
+                .ok_or_else(|| invalid("dimensions de projection trop grandes"))?;
+            let matrix = reader.f32s(size)?;
+            let projection = Projection::from_matrix(input_dim, output_dim, matrix)?;
+            Some(match reader.u8()? {
+                0 => projection,
+                1 => projection.with_center(reader.f32s(input_dim)?)?,
+                code => return Err(invalid(format!("marqueur de centre invalide ({})", code))),
+            })
+        }
+        code => {
+            return Err(invalid(format!(
+                "marqueur de projection invalide ({})",
+                code
+            )))
+        }
+    })
+}
+
+pub(crate) fn read_collection(reader: &mut Reader, version: u32) -> Result<Collection> {
+    let config = read_config(reader, version)?;
+    let projection = read_projection(reader)?;
+
+    let count = reader.len(24)?;
+    let mut documents = SegmentedMap::default();
+    documents.reserve(count);
+    for _ in 0..count {
+        let key = reader.uuid()?;
+        let dimension = reader.len(4)?;
+        if documents
+            .insert(key, Arc::new(reader.f32s(dimension)?))
+            .is_some()
+        {
+            return Err(invalid(format!("document {} en double", key)));
+        }
+    }
+
+    let count = reader.len(24)?;
+    let mut payloads = SegmentedMap::default();
+    payloads.reserve(count);
+    for _ in 0..count {
+        let key = reader.uuid()?;
+        if !documents.contains_key(&key) {
+            return Err(invalid(format!("charge utile du document absent {}", key)));
+        }
+        if payloads.insert(key, read_object(reader, 0)?).is_some() {
+            return Err(invalid(format!("charge utile de {} en double", key)));
+        }
+    }
+
+    let count = if version >= 5 { reader.len(25)? } else { 0 };
+    let mut aliases = SegmentedMap::default();
+    aliases.reserve(count);
+    for _ in 0..count {
+        let key = reader.uuid()?;
+        let id = match reader.u8()? {
+            1 => DocumentId::Int(reader.u64()? as i64),
+            2 => DocumentId::Str(reader.string()?),
+            code => return Err(invalid(format!("type d'identifiant invalide ({})", code))),
+        };
+        if !documents.contains_key(&key) || id.to_uuid() != key {
+            return Err(invalid(format!("identifiant {} sans document {}", id, key)));
+        }
+        if aliases.insert(key, id).is_some() {
+            return Err(invalid(format!("identifiant de {} en double", key)));
+        }
+    }
+
+    let default_params = match version >= 6 {
+        true => read_search_params(reader, version)?,
+        false => SearchParams::default(),
+    };
+    let dedup_code = if version >= 7 { reader.u8()? } else { 0 };
+    let (dedup_threshold, dedup_policy) = match dedup_code {
+        0 => (None, DedupPolicy::default()),
+        1 => (Some(reader.f32s(1)?[0]), DedupPolicy::Reject),
+        2 => (Some(reader.f32s(1)?[0]), DedupPolicy::Skip),
+        code => {
+            return Err(invalid(format!(
+                "marqueur de déduplication invalide ({})",
+                code
+            )))
+        }
+    };
+
+    let count = if version >= 10 { reader.len(24)? } else { 0 };
+    let mut namespaces = Namespaces::default();
+    for _ in 0..count {
+        let key = reader.uuid()?;
+        let namespace = reader.string()?;
+        if !documents.contains_key(&key) {
+            return Err(invalid(format!(
+                "espace de noms du document absent {}",
+                key
+            )));
+        }
+        if namespaces.get(&key).is_some() {
+            return Err(invalid(format!("espace de noms de {} en double", key)));
+        }
+        namespaces.insert(key, namespace);
+    }
+
+    let mut collection = Collection {
+        documents,
+        payloads,
+        pending: SegmentedMap::default(),
+        config,
+        projection,
+        aliases,
+        runtime: SearchRuntime::default(),
+        default_params,
+        dedup_threshold,
+        dedup_policy,
+        tombstones: SegmentedMap::default(),
+        namespaces,
+        indexes: HashMap::new(),
+        memory: MemoryUsage::default(),
+        budget: BudgetSlot::default(),
+        recency: Recency::default(),
+        schema: None,
+        schema_warnings: 0,
+        locked_dimension: None,
+        adapter: None,
+        calibration: None,
+        pool: VectorPool::default(),
+        operations: OperationLog::default(),
+        unit_tolerance: DEFAULT_UNIT_NORM_TOLERANCE,
+        assume_unit: false,
+        stats: VectorStats::default(),
+        saved: SavedSearches::default(),
+        external_keys: ExternalKeys::default(),
+        access: AccessLog::default(),
+        blobs: None,
+    };
+    let count = if version >= 11 { reader.len(9)? } else { 0 };
+    for _ in 0..count {
+        let field = reader.string()?;
+        let kind = match reader.u8()? {
+            0 => IndexKind::Keyword,
+            1 => IndexKind::Numeric,
+            code => return Err(invalid(format!("type d'index invalide ({})", code))),
+        };
+        collection.create_payload_index(&field, kind);
+    }
+    let mode = match if version >= 14 { reader.u8()? } else { 0 } {
+        0 => None,
+        1 => Some(SchemaMode::Strict),
+        2 => Some(SchemaMode::Lenient),
+        code => return Err(invalid(format!("marqueur de schéma invalide ({})", code))),
+    };
+    if let Some(mode) = mode {
+        let mut schema = PayloadSchema::new().with_mode(mode);
+        let count = reader.len(10)?;
+        for _ in 0..count {
+            let name = reader.string()?;
+            let kind = match reader.u8()? {
+                0 => FieldType::Bool,
+                1 => FieldType::Number,
+                2 => FieldType::String,
+                3 => FieldType::Array,
+                4 => FieldType::Object,
+                code => return Err(invalid(format!("type de champ invalide ({})", code))),
+            };
+            let required = match reader.u8()? {
+                0 => false,
+                1 => true,
+                code => return Err(invalid(format!("marqueur de champ invalide ({})", code))),
+            };
+            if schema.get(&name).is_some() {
+                return Err(invalid(format!("champ de schéma « {} » en double", name)));
+            }
+            schema = schema.field(name, kind, required);
+        }
+        collection.schema = Some(schema);
+    }
+    match version >= 16 {
+        true => {
+            collection.locked_dimension = Some(reader.len(0)?).filter(|dimension| *dimension > 0)
+        }
+        false => collection.infer_locked_dimension(),
+    }
+    if version >= 17 {
+        if let Some(projection) = read_projection(reader)? {
+            if projection.center().is_some()
+                || collection.dimension() != Some(projection.output_dim())
+                || projection.input_dim() == projection.output_dim()
+            {
+                return Err(invalid(format!(
+                    "adaptateur de requêtes invalide ({} → {})",
+                    projection.input_dim(),
+                    projection.output_dim()
+                )));
+            }
+            collection.adapter = Some(QueryAdapter::from_projection(projection));
+        }
+    }
+    let pipeline = &collection.config.query_pipeline;
+    if !pipeline.is_empty() {
+        let Some(dimension) = collection.stored_dimension() else {
+            return Err(invalid("pipeline de requêtes sans dimension"));
+        };
+        (pipeline.check(dimension))
+            .map_err(|e| invalid(format!("pipeline de requêtes invalide : {}", e)))?;
+    }
+    if version >= 18 && flag(reader, "calibration")? {
+        let metric = metric_from_code(reader.u8()?)?;
+        let slope = f64::from_bits(reader.u64()?);
+        let intercept = f64::from_bits(reader.u64()?);
+        if !slope.is_finite()
+            || !intercept.is_finite()
+            || (slope > 0.0) != metric.higher_is_better()
+        {
+            return Err(invalid("calibration des scores invalide"));
+        }
+        collection.calibration = Some(Calibration::from_parts(slope, intercept, metric));
+    }
+    if version >= 20 {
+        let tolerance = reader.f32s(1)?[0];
+        if !(tolerance >= 0.0 && tolerance.is_finite()) {
+            return Err(invalid(format!(
+                "tolérance de norme unitaire invalide ({})",
+                tolerance
+            )));
+        }
+        collection.unit_tolerance = tolerance;
+    }
+    if version >= 22 {
+        for _ in 0..reader.len(16)? {
+            let name = reader.string()?;
+            let dimension = reader.len(4)?;
+            let vector = reader.f32s(dimension)?;
+            let params = read_search_params(reader, version)?;
+            collection.saved.queries.insert(name, (vector, params));
+        }
+        collection.saved.watching = flag(reader, "suivi des nouveaux documents")?;
+    }
+    if version >= 28 {
+        for _ in 0..reader.len(24)? {
+            let key = reader.uuid()?;
+            if collection.documents.contains_key(&key) {
+                return Err(invalid(format!(
+                    "document {} à la fois complet et en attente",
+                    key
+                )));
+            }
+            if collection
+                .pending
+                .insert(key, read_object(reader, 0)?)
+                .is_some()
+            {
+                return Err(invalid(format!("document en attente {} en double", key)));
+            }
+        }
+    }
+    if version >= 31 {
+        collection.external_keys.namespace = reader.uuid()?;
+        if flag(reader, "champ des identifiants externes")? {
+            collection.external_keys.field = Some(reader.string()?);
+        }
+    }
+    if collection.config.shared_vectors {
+        collection.reshare_vectors();
+    }
+    collection.memory = collection.measure_memory();
+    collection.recompute_stats();
+    collection.reset_recency();
+    Ok(collection)
+}
+
+fn write_search_params(out: &mut Vec<u8>, params: &SearchParams) {
+    put_len(out, params.k);
+    match params.score_threshold {
+        None => out.push(0),
+        Some(threshold) => {
+            out.push(1);
+            put_f32s(out, &[threshold]);
+        }
+    }
+    match params.time_budget {
+        None => out.push(0),
+        Some(budget) => {
+            out.push(1);
+            put_u64(out, nanos(budget));
+        }
+    }
+    out.push(match params.score_mode {
+        ScoreMode::Raw => 0,
+        ScoreMode::Normalized => 1,
+        ScoreMode::Rank => 2,
+        ScoreMode::Calibrated => 3,
+    });
+    match &params.namespace {
+        None => out.push(0),
+        Some(namespace) => {
+            out.push(1);
+            put_str(out, namespace);
+        }
+    }
+    out.push(u8::from(params.require_exact_k));
+    out.push(u8::from(params.with_timing));
+    write_model(out, params.model.as_ref());
+    out.push(u8::from(params.allow_model_mismatch));
+    match &params.payload_selector {
+        None => out.push(0),
+        Some(selector) => {
+            out.push(1);
+            put_len(out, selector.len());
+            for name in selector {
+                put_str(out, name);
+            }
+        }
+    }
+    out.push(u8::from(params.with_vector));
+    match &params.decay {
+        None => out.push(0),
+        Some(decay) => {
+            out.push(1);
+            write_decay(out, decay);
+        }
+    }
+    match params.dedup_results {
+        None => out.push(0),
+        Some(threshold) => {
+            out.push(1);
+            put_f32s(out, &[threshold]);
+        }
+    }
+    put_len(out, params.dedup_pool_factor);
+    out.push(u8::from(params.deterministic_math));
+    match params.sample_fraction {
+        None => out.push(0),
+        Some(fraction) => {
+            out.push(1);
+            put_f32s(out, &[fraction]);
+        }
+    }
+    put_u64(out, params.sample_seed);
+    match params.min_margin {
+        None => out.push(0),
+        Some(margin) => {
+            out.push(1);
+            put_f32s(out, &[margin]);
+        }
+    }
+    match &params.dimension_mask {
+        None => out.push(0),
+        Some(mask) => {
+            out.push(1);
+            put_len(out, mask.len());
+            for index in mask {
+                put_len(out, *index);
+            }
+        }
+    }
+    out.push(match params.filter_strategy {
+        FilterStrategy::Auto => 0,
+        FilterStrategy::IndexFirst => 1,
+        FilterStrategy::ScanFirst => 2,
+    });
+}
+
+fn write_decay(out: &mut Vec<u8>, decay: &DecayParams) {
+    put_str(out, &decay.field);
+    put_u64(out, nanos(decay.half_life));
+    out.push(match decay.shape {
+        DecayShape::Exponential => 0,
+        DecayShape::Linear => 1,
+        DecayShape::Gaussian => 2,
+    });
+    match decay.missing {
+        MissingTimestamp::NoDecay => out.push(0),
+        MissingTimestamp::Penalize(factor) => {
+            out.push(1);
+            put_f32s(out, &[factor]);
+        }
+    }
+    match decay.now {
+        None => out.push(0),
+        Some(now) => {
+            out.push(1);
+            put_u64(
+                out,
+                nanos(now.duration_since(UNIX_EPOCH).unwrap_or_default()),
+            );
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn read_search_params(reader: &mut Reader, version: u32) -> Result<SearchParams> {
+    let k = reader.len(0)?;
+    let score_threshold = match flag(reader, "seuil")? {
+        false => None,
+        true => Some(reader.f32s(1)?[0]),
+    };
+    let time_budget = match flag(reader, "budget")? {
+        false => None,
+        true => Some(Duration::from_nanos(reader.u64()?)),
+    };
+    let score_mode = match reader.u8()? {
+        0 => ScoreMode::Raw,
+        1 => ScoreMode::Normalized,
+        2 => ScoreMode::Rank,
+        3 if version >= 18 => ScoreMode::Calibrated,
+        code => return Err(invalid(format!("mode de score invalide ({})", code))),
+    };
+    let namespace = match version >= 10 && flag(reader, "espace de noms")? {
+        false => None,
+        true => Some(reader.string()?),
+    };
+    let require_exact_k = version >= 12 && flag(reader, "nombre exact de résultats")?;
+    let with_timing = version >= 15 && flag(reader, "mesure des durées")?;
+    let (model, allow_model_mismatch) = match version >= 21 {
+        true => (
+            read_model(reader)?,
+            flag(reader, "modèle différent accepté")?,
+        ),
+        false => (None, false),
+    };
+    let payload_selector = match version >= 24 && flag(reader, "sélection des charges utiles")? {
+        false => None,
+        true => {
+            let count = reader.len(8)?;
+            Some((0..count).map(|_| reader.string()).collect::<Result<_>>()?)
+        }
+    };
+    let with_vector = version >= 24 && flag(reader, "vecteurs joints")?;
+    let decay = match version >= 25 && flag(reader, "atténuation")? {
+        false => None,
+        true => Some(read_decay(reader)?),
+    };
+    let (dedup_results, dedup_pool_factor) = match version >= 26 {
+        true => (
+            match flag(reader, "seuil des doublons")? {
+                false => None,
+                true => Some(reader.f32s(1)?[0]),
+            },
+            reader.len(0)?,
+        ),
+        false => (None, DEFAULT_DEDUP_POOL_FACTOR),
+    };
+    let deterministic_math = version >= 27 && flag(reader, "calcul déterministe")?;
+    let (sample_fraction, sample_seed) = match version >= 29 {
+        true => (
+            match flag(reader, "part échantillonnée")? {
+                false => None,
+                true => Some(reader.f32s(1)?[0]),
+            },
+            reader.u64()?,
+        ),
+        false => (None, 0),
+    };
+    let min_margin = match version >= 32 && flag(reader, "écart minimal")? {
+        false => None,
+        true => Some(reader.f32s(1)?[0]),
+    };
+    let dimension_mask = match version >= 35 && flag(reader, "masque des dimensions")? {
+        false => None,
+        true => {
+            let count = reader.len(8)?;
+            Some((0..count).map(|_| reader.len(0)).collect::<Result<_>>()?)
+        }
+    };
+    let filter_strategy = match version >= 36 {
+        false => FilterStrategy::Auto,
+        true => match reader.u8()? {
+            0 => FilterStrategy::Auto,
+            1 => FilterStrategy::IndexFirst,
+            2 => FilterStrategy::ScanFirst,
+            code => return Err(invalid(format!("stratégie de filtrage invalide ({})", code))),
+        },
+    };
+    Ok(SearchParams {
+        k,
+        score_threshold,
+        time_budget,
+        score_mode,
+        namespace,
+        require_exact_k,
+        with_timing,
+        model,
+        allow_model_mismatch,
+        payload_selector,
+        with_vector,
+        decay,
+        dedup_results,
+        dedup_pool_factor,
+        deterministic_math,
+        sample_fraction,
+        sample_seed,
+        min_margin,
+        dimension_mask,
+        filter_strategy,
+        // Le contexte d'une requête ne lui survit pas.
+        context: None,
+    })
+}
+
+fn read_decay(reader: &mut Reader) -> Result<DecayParams> {
+    let field = reader.string()?;
+    let half_life = Duration::from_nanos(reader.u64()?);
+    let shape = match reader.u8()? {
+        0 => DecayShape::Exponential,
+        1 => DecayShape::Linear,
+        2 => DecayShape::Gaussian,
+        code => return Err(invalid(format!("forme d'atténuation invalide ({})", code))),
+    };
+    let missing = match reader.u8()? {
+        0 => MissingTimestamp::NoDecay,
+        1 => MissingTimestamp::Penalize(reader.f32s(1)?[0]),
+        code => {
+            return Err(invalid(format!(
+                "traitement des documents sans date invalide ({})",
+                code
+            )))
+        }
+    };
+    let now = match flag(reader, "date de référence")? {
+        false => None,
+        true => Some(UNIX_EPOCH + Duration::from_nanos(reader.u64()?)),
+    };
+    Ok(DecayParams {
+        field,
+        half_life,
+        shape,
+        missing,
+        now,
+    })
+}
+
+/// Écrit la configuration d'une collection, au format de la version courante.
+pub(crate) fn write_config(out: &mut Vec<u8>, config: &CollectionConfig) {
+    out.push(metric_code(config.metric));
+    out.push(policy_code(config.zero_vector_policy));
+    out.push(u8::from(config.strict_dimensions));
+    put_len(out, config.dimension.unwrap_or(0));
+    out.push(u8::from(config.normalized));
+    out.push(u8::from(config.soft_delete));
+    out.push(u8::from(config.memory_eviction));
+    out.push(u8::from(config.shared_vectors));
+    out.push(u8::from(config.unit_vectors));
+    write_model(out, config.model.as_ref());
+    let weights = config.dimension_weights().unwrap_or_default();
+    put_len(out, weights.len());
+    put_f32s(out, weights);
+    match config.norm_range {
+        None => out.push(0),
+        Some(range) => {
+            out.push(1);
+            put_f32s(out, &[range.min, range.max]);
+        }
+    }
+    out.push(u8::from(config.access_tracking));
+    match config.aggregate_noise {
+        None => out.push(0),
+        Some(noise) => {
+            out.push(1);
+            put_u64(out, noise.epsilon.to_bits());
+        }
+    }
+    let pipeline = &config.query_pipeline;
+    put_len(out, pipeline.len());
+    for (index, transform) in pipeline.transforms().iter().enumerate() {
+        match pipeline.builtin(index) {
+            Some(BuiltinTransform::Normalize) => out.push(0),
+            Some(BuiltinTransform::CenterOnCentroid) => out.push(1),
+            Some(BuiltinTransform::Mask(dimensions)) => {
+                out.push(2);
+                put_len(out, dimensions.len());
+                for dimension in dimensions {
+                    put_len(out, *dimension);
+                }
+            }
+            Some(BuiltinTransform::Project(projection)) => {
+                out.push(3);
+                write_projection(out, Some(projection));
+            }
+            // Seul le nom d'une transformation de l'appelant est sauvegardé.
+            None => {
+                out.push(4);
+                put_str(out, &transform.name());
+            }
+        }
+    }
+}
+
+/// Lit une configuration écrite par [`write_config`] dans un fichier de version `version` ;
+/// les champs absents des anciennes versions prennent leur valeur par défaut.
+pub(crate) fn read_config(reader: &mut Reader, version: u32) -> Result<CollectionConfig> {
+    if version == 0 || version > VERSION {
+        return Err(invalid(format!("version {} non prise en charge", version)));
+    }
+    let mut config = CollectionConfig {
+        metric: metric_from_code(reader.u8()?)?,
+        zero_vector_policy: policy_from_code(reader.u8()?)?,
+        ..CollectionConfig::default()
+    };
+    if version >= 3 {
+        config.strict_dimensions = flag(reader, "dimensions strictes")?;
+    }
+    if version >= 4 {
+        config.dimension = Some(reader.len(0)?).filter(|dimension| *dimension > 0);
+        config.normalized = flag(reader, "normalisation")?;
+    }
+    if version >= 9 {
+        config.soft_delete = flag(reader, "suppression différée")?;
+    }
+    if version >= 13 {
+        config.memory_eviction = flag(reader, "éviction")?;
+    }
+    if version >= 19 {
+        config.shared_vectors = flag(reader, "partage des vecteurs")?;
+    }
+    if version >= 20 {
+        config.unit_vectors = flag(reader, "vecteurs unitaires")?;
+    }
+    if version >= 21 {
+        config.model = read_model(reader)?;
+    }
+    if version >= 23 {
+        let n = reader.len(4)?;
+        if n > 0 {
+            let weights = reader.f32s(n)?;
+            weights::check(&weights).map_err(|_| invalid("poids des dimensions invalides"))?;
+            config.weights = Some(Weights(weights));
+        }
+    }
+    if version >= 30 && flag(reader, "plage des normes")? {
+        let bounds = reader.f32s(2)?;
+        let (min, max) = (bounds[0], bounds[1]);
+        if !(0.0 <= min && min <= max) {
+            return Err(invalid("plage des normes invalide"));
+        }
+        config.norm_range = Some(NormRange { min, max });
+    }
+    if version >= 33 {
+        config.access_tracking = flag(reader, "suivi des accès")?;
+    }
+    if version >= 34 && flag(reader, "bruit des agrégats")? {
+        let epsilon = f64::from_bits(reader.u64()?);
+        privacy::check(epsilon).map_err(|_| invalid("bruit des agrégats invalide"))?;
+        config.aggregate_noise = Some(AggregateNoise { epsilon });
+    }
+    if version >= 37 {
+        let mut pipeline = QueryPipeline::new();
+        for _ in 0..reader.len(1)? {
+            pipeline = match reader.u8()? {
+                0 => pipeline.then(BuiltinTransform::Normalize),
+                1 => pipeline.then(BuiltinTransform::CenterOnCentroid),
+                2 => {
+                    let count = reader.len(8)?;
+                    let dimensions = (0..count).map(|_| reader.len(0)).collect::<Result<_>>()?;
+                    pipeline.then(BuiltinTransform::Mask(dimensions))
+                }
+                3 => match read_projection(reader)? {
+                    Some(projection) => pipeline.then(BuiltinTransform::Project(projection)),
+                    None => return Err(invalid("projection du pipeline de requêtes manquante")),
+                },
+                4 => pipeline.then(Unresolved {
+                    name: reader.string()?,
+                }),
+                code => {
+                    return Err(invalid(format!(
+                        "transformation de requête invalide ({})",
+                        code
+                    )))
+                }
+            };
+        }
+        config.query_pipeline = pipeline;
+    }
+    Ok(config)
+}
+
+fn write_model(out: &mut Vec<u8>, model: Option<&ModelFingerprint>) {
+    let Some(model) = model else {
+        out.push(0);
+        return;
+    };
+    out.push(1);
+    put_str(out, model.name());
+    match model.hash() {
+        None => out.push(0),
+        Some(hash) => {
+            out.push(1);
+            put_str(out, hash);
+        }
+    }
+}
+
+fn read_model(reader: &mut Reader) -> Result<Option<ModelFingerprint>> {
+    if !flag(reader, "modèle")? {
+        return Ok(None);
+    }
+    let model = ModelFingerprint::new(reader.string()?);
+    Ok(Some(match flag(reader, "empreinte des poids")? {
+        true => model.with_hash(reader.string()?),
+        false => model,
+    }))
+}
+
+fn flag(reader: &mut Reader, name: &str) -> Result<bool> {
+    match reader.u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        code => Err(invalid(format!("marqueur de {} invalide ({})", name, code))),
+    }
+}
+
+pub(crate) fn metric_code(metric: Metric) -> u8 {
+    match metric {
+        Metric::Cosine => 0,
+        Metric::Dot => 1,
+        Metric::Euclidean => 2,
+        Metric::Manhattan => 3,
+    }
+}
+
+pub(crate) fn metric_from_code(code: u8) -> Result<Metric> {
+    match code {
+        0 => Ok(Metric::Cosine),
+        1 => Ok(Metric::Dot),
+        2 => Ok(Metric::Euclidean),
+        3 => Ok(Metric::Manhattan),
+        code => Err(invalid(format!("mesure inconnue ({})", code))),
+    }
+}
+
+pub(crate) fn policy_code(policy: ZeroVectorPolicy) -> u8 {
+    match policy {
+        ZeroVectorPolicy::Reject => 0,
+        ZeroVectorPolicy::ScoreZero => 1,
+    }
+}
+
+pub(crate) fn policy_from_code(code: u8) -> Result<ZeroVectorPolicy> {
+    match code {
+        0 => Ok(ZeroVectorPolicy::Reject),
+        1 => Ok(ZeroVectorPolicy::ScoreZero),
+        code => Err(invalid(format!(
+            "politique de vecteurs nuls inconnue ({})",
+            code
+        ))),
+    }
+}
+
+pub(crate) fn write_object(out: &mut Vec<u8>, object: &BTreeMap<String, Value>) {
+    put_len(out, object.len());
+    for (field, value) in object {
+        put_str(out, field);
+        write_value(out, value);
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0),
+        Value::Bool(b) => {
+            out.push(1);
+            out.push(*b as u8);
+        }
+        Value::Number(n) => {
+            out.push(2);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(3);
+            put_str(out, s);
+        }
+        Value::Array(values) => {
+            out.push(4);
+            put_len(out, values.len());
+            for value in values {
+                write_value(out, value);
+            }
+        }
+        Value::Object(object) => {
+            out.push(5);
+            write_object(out, object);
+        }
+    }
+}
+
+pub(crate) fn read_object(reader: &mut Reader, depth: usize) -> Result<BTreeMap<String, Value>> {
+    let mut object = BTreeMap::new();
+    for _ in 0..reader.len(9)? {
+        let field = reader.string()?;
+        let value = read_value(reader, depth)?;
+        object.insert(field, value);
+    }
+    Ok(object)
+}
+
+fn read_value(reader: &mut Reader, depth: usize) -> Result<Value> {
+    if depth >= MAX_VALUE_DEPTH {
+        return Err(invalid("charge utile trop profondément imbriquée"));
+    }
+    Ok(match reader.u8()? {
+        0 => Value::Null,
+        1 => Value::Bool(reader.u8()? != 0),
+        2 => Value::Number(f64::from_le_bytes(reader.array()?)),
+        3 => Value::String(reader.string()?),
+        4 => {
+            let count = reader.len(1)?;
+            let mut values = Vec::with_capacity(count);
+            for _ in 0..count {
+                values.push(read_value(reader, depth + 1)?);
+            }
+            Value::Array(values)
+        }
+        5 => Value::Object(read_object(reader, depth + 1)?),
+        tag => return Err(invalid(format!("type de valeur inconnu ({})", tag))),
+    })
+}
+
+pub(crate) fn put_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+pub(crate) fn put_u64(out: &mut Vec<u8>, n: u64) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+pub(crate) fn put_len(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u64).to_le_bytes());
+}
+
+pub(crate) fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+pub(crate) fn put_f32s(out: &mut Vec<u8>, values: &[f32]) {
+    for x in values {
+        out.extend_from_slice(&x.to_le_bytes());
+    }
+}
+
+/// Lecteur d'octets dont chaque accès vérifie qu'il reste assez de données.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pub(crate) position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, position: 0 }
+    }
+
+    pub(crate) fn is_at_end(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() - self.position < n {
+            return Err(invalid("fichier tronqué"));
+        }
+        let slice = &self.bytes[self.position..self.position + n];
+        self.position += n;
+        Ok(slice)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    /// Lit une longueur, en refusant celles que les octets restants ne peuvent pas contenir
+    /// à raison de `min_item_size` octets par élément : un fichier corrompu ne peut donc pas
+    /// provoquer d'allocation démesurée.
+    pub(crate) fn len(&mut self, min_item_size: usize) -> Result<usize> {
+        let n = u64::from_le_bytes(self.array()?);
+        let remaining = (self.bytes.len() - self.position) as u64;
+        if min_item_size > 0 && n > remaining / min_item_size as u64 {
+            return Err(invalid("longueur incohérente avec la taille du fichier"));
+        }
+        usize::try_from(n).map_err(|_| invalid("longueur trop grande"))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String> {
+        let n = self.len(1)?;
+        String::from_utf8(self.take(n)?.to_vec()).map_err(|_| invalid("chaîne non UTF-8"))
+    }
+
+    /// Lit un nom de collection ou d'alias, d'au plus `MAX_NAME_LEN` octets.
+    pub(crate) fn name(&mut self) -> Result<String> {
+        let name = self.string()?;
+        if name.len() > MAX_NAME_LEN {
+            return Err(invalid(format!(
+                "nom de {} octets, au-delà de la limite de {}",
+                name.len(),
+                MAX_NAME_LEN
+            )));
+        }
+        Ok(name)
+    }
+
+    pub(crate) fn uuid(&mut self) -> Result<Uuid> {
+        Ok(Uuid::from_bytes(self.array()?))
+    }
+
+    pub(crate) fn f32s(&mut self, n: usize) -> Result<Vec<f32>> {
+        let size = n
+            .checked_mul(4)
+            .ok_or_else(|| invalid("vecteur trop grand"))?;
+        Ok(self
+            .take(size)?
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{VectorStore, VectorStoreMut};
+
+    /// Vecteurs de normes différentes : le produit scalaire préfère `long`, le cosinus
+    /// `aligned`.
+    fn dot_database() -> (BaseDeDonnees, Uuid, Uuid) {
+        let (aligned, long) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let config = CollectionConfig::builder().metric(Metric::Dot).build().unwrap();
+        let mut bdd = BaseDeDonnees::new();
+        bdd.add_with_config("docs".to_string(), config);
+        let docs = bdd.get_mut("docs").unwrap();
+        docs.upsert(aligned, [1.0, 0.0]).unwrap();
+        docs.upsert(long, [3.0, 3.0]).unwrap();
+        let mut sharded = ShardedCollection::new(2).unwrap().with_metric(Metric::Dot);
+        VectorStoreMut::upsert(&mut sharded, aligned, vec![1.0, 0.0]).unwrap();
+        VectorStoreMut::upsert(&mut sharded, long, vec![3.0, 3.0]).unwrap();
+        bdd.replace_sharded("partagee".to_string(), sharded);
+        (bdd, aligned, long)
+    }
+
+    fn saved(bdd: &BaseDeDonnees) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("embeddingproject-snapshot-{}.snap", Uuid::new_v4()));
+        bdd.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn a_dot_product_collection_loads_as_dot_product() {
+        let (bdd, _, long) = dot_database();
+        let loaded = BaseDeDonnees::from_bytes(&bdd.to_bytes()).unwrap();
+        let docs = loaded.get("docs").unwrap();
+        assert_eq!(docs.metric(), Metric::Dot);
+        assert_eq!(docs.search([1.0, 0.0], 1).unwrap(), [(long, 3.0)]);
+        let sharded = loaded.get_sharded("partagee").unwrap();
+        assert_eq!(sharded.shards()[0].metric(), Metric::Dot);
+        assert_eq!(VectorStore::search(sharded, &[1.0, 0.0], 1).unwrap()[0].0, long);
+    }
+
+    #[test]
+    fn the_whole_configuration_is_restored() {
+        let configs = [
+            CollectionConfig::builder()
+                .dimension(3)
+                .metric(Metric::Manhattan)
+                .zero_vector_policy(ZeroVectorPolicy::ScoreZero)
+                .strict_dimensions(true)
+                .soft_delete(true)
+                .memory_eviction(true)
+                .build(),
+            CollectionConfig::builder()
+                .metric(Metric::Euclidean)
+                .normalized(true)
+                .shared_vectors(true)
+                .build(),
+        ];
+        for config in configs {
+            let config = config.unwrap();
+            let mut bdd = BaseDeDonnees::new();
+            bdd.add_with_config("docs".to_string(), config.clone());
+            let loaded = BaseDeDonnees::from_bytes(&bdd.to_bytes()).unwrap();
+            assert_eq!(loaded.get("docs").unwrap().config(), &config);
+        }
+    }
+
+    #[test]
+    fn a_metric_change_must_be_acknowledged() {
+        let (bdd, aligned, long) = dot_database();
+        let path = saved(&bdd);
+        let overrides = LoadOverrides::new().metric("docs", Metric::Cosine);
+        assert!(matches!(
+            BaseDeDonnees::load_with_overrides(&path, &overrides),
+            Err(Error::InvalidConfig(_))
+        ));
+        let same = LoadOverrides::new().metric("docs", Metric::Dot);
+        assert_eq!(
+            BaseDeDonnees::load_with_overrides(&path, &same).unwrap().get("docs").unwrap().metric(),
+            Metric::Dot
+        );
+
+        let overrides = overrides
+            .metric("partagee", Metric::Cosine)
+            .allow_metric_change(true);
+        let loaded = BaseDeDonnees::load_with_overrides(&path, &overrides).unwrap();
+        let docs = loaded.get("docs").unwrap();
+        assert_eq!(docs.metric(), Metric::Cosine);
+        assert_eq!(docs.search([1.0, 0.0], 1).unwrap()[0].0, aligned);
+        let sharded = loaded.get_sharded("partagee").unwrap();
+        assert!(sharded.shards().iter().all(|shard| shard.metric() == Metric::Cosine));
+        assert_ne!(VectorStore::search(sharded, &[1.0, 0.0], 1).unwrap()[0].0, long);
+
+        let missing = LoadOverrides::new().metric("absente", Metric::Dot);
+        assert!(matches!(
+            BaseDeDonnees::load_with_overrides(&path, &missing),
+            Err(Error::CollectionNotFound(nom)) if nom == "absente"
+        ));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,303 @@
+//! Accès aux fichiers des sauvegardes et des journaux de réplication.
+//!
+//! Toutes les écritures et lectures durables passent par un [`Storage`], par défaut
+//! [`FileSystem`]. Une base en reçoit un autre avec [`BaseDeDonnees::with_storage`] ;
+//! avec la fonctionnalité `fault-injection`, [`FaultInjector`] en enveloppe un pour faire
+//! échouer ou tronquer une opération précise et vérifier la reprise après un arrêt brutal.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+#[cfg(feature = "fault-injection")]
+use std::sync::Mutex;
+
+use crate::database::BaseDeDonnees;
+use crate::error::Result;
+
+/// Opérations sur les fichiers utilisées par les sauvegardes et les journaux.
+pub trait Storage: Send + Sync {
+    /// Ouvre `path` en lecture séquentielle.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Crée `path`, ou le vide s'il existe, et l'ouvre en écriture.
+    fn create(&self, path: &Path) -> io::Result<Box<dyn StorageFile>>;
+
+    /// Ouvre `path` en écriture à la fin du fichier, en le créant s'il n'existe pas.
+    fn append(&self, path: &Path) -> io::Result<Box<dyn StorageFile>>;
+
+    /// Renomme `from` en `to`, en remplaçant `to` s'il existe.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Lit tout le contenu de `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.open(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Fichier ouvert en écriture par un [`Storage`].
+pub trait StorageFile: Write + Send {
+    /// Attend que le contenu écrit soit sur le disque (`fsync`).
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+/// Accès direct au système de fichiers, par `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystem;
+
+impl Storage for FileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn StorageFile>> {
+        Ok(Box::new(File::create(path)?))
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn StorageFile>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+}
+
+impl StorageFile for File {
+    fn sync(&mut self) -> io::Result<()> {
+        self.sync_all()
+    }
+}
+
+/// Remplace le contenu de `path` par `bytes` sans jamais laisser de fichier à moitié
+/// écrit : le contenu est écrit et synchronisé à côté de sa destination, puis renommé.
+pub(crate) fn replace(storage: &dyn Storage, path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = Path::new(&temporary);
+    let mut file = storage.create(temporary)?;
+    file.write_all(bytes)?;
+    file.sync()?;
+    drop(file);
+    storage.rename(temporary, path)
+}
+
+impl BaseDeDonnees {
+    /// Fait passer les sauvegardes de la base, et les journaux des primaires et réplicas
+    /// construits sur elle, par `storage` plutôt que par [`FileSystem`].
+    ///
+    /// # Arguments
+    /// * `storage` - Accès aux fichiers à utiliser.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Retourne l'accès aux fichiers de la base.
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_deref().unwrap_or(&FileSystem)
+    }
+
+    /// Charge une base de données comme [`BaseDeDonnees::load`], en lisant le fichier
+    /// par `storage`, que la base conserve ensuite ([`BaseDeDonnees::with_storage`]).
+    ///
+    /// # Erreurs
+    /// * Celles de [`BaseDeDonnees::load`].
+    pub fn load_from(storage: Arc<dyn Storage>, path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = storage.read(path.as_ref())?;
+        Ok(Self::from_bytes(&bytes)?.with_storage(storage))
+    }
+}
+
+/// Opération d'un [`Storage`] comptée par [`FaultInjector`].
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageOp {
+    /// Appel à `write` sur un fichier ouvert en écriture.
+    Write,
+    /// Appel à [`StorageFile::sync`].
+    Sync,
+    /// Appel à [`Storage::rename`].
+    Rename,
+}
+
+#[cfg(feature = "fault-injection")]
+impl StorageOp {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Comportement d'une opération sabotée par [`FaultInjector`].
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// L'opération échoue sans effet.
+    Fail,
+    /// L'écriture ne transmet que ses `n` premiers octets puis échoue, comme un arrêt
+    /// brutal au milieu d'une écriture ; pour une autre opération, équivaut à `Fail`.
+    Truncate(usize),
+}
+
+#[cfg(feature = "fault-injection")]
+#[derive(Default)]
+struct Faults {
+    calls: [u64; 3],
+    planned: Vec<(StorageOp, u64, Fault)>,
+    triggered: Vec<(StorageOp, u64, Fault)>,
+}
+
+#[cfg(feature = "fault-injection")]
+impl Faults {
+    /// Compte un appel à `op` et retourne la panne prévue pour lui, s'il y en a une.
+    fn call(&mut self, op: StorageOp) -> Option<Fault> {
+        self.calls[op.index()] += 1;
+        let call = self.calls[op.index()];
+        let index = (self.planned.iter()).position(|(o, n, _)| *o == op && *n == call)?;
+        let planned = self.planned.remove(index);
+        self.triggered.push(planned);
+        Some(planned.2)
+    }
+}
+
+/// [`Storage`] qui délègue à un autre et fait échouer ou tronquer le N-ième appel d'une
+/// opération (fonctionnalité `fault-injection`, réservée aux tests).
+///
+/// Les clones partagent les mêmes compteurs et pannes prévues : un test garde un clone
+/// pour programmer les pannes et en confie un autre à la base
+/// ([`BaseDeDonnees::with_storage`]), puis relit les fichiers par le stockage enveloppé
+/// pour vérifier ce qu'un redémarrage retrouverait.
+#[cfg(feature = "fault-injection")]
+#[derive(Clone)]
+pub struct FaultInjector {
+    inner: Arc<dyn Storage>,
+    faults: Arc<Mutex<Faults>>,
+}
+
+#[cfg(feature = "fault-injection")]
+impl FaultInjector {
+    /// Enveloppe `inner`, sans panne prévue.
+    pub fn new(inner: Arc<dyn Storage>) -> Self {
+        FaultInjector {
+            inner,
+            faults: Arc::default(),
+        }
+    }
+
+    /// Prévoit la panne `fault` au `n`-ième appel à `op`, en comptant depuis la création
+    /// ou le dernier [`FaultInjector::reset`] ; les appels déjà faits comptent.
+    ///
+    /// # Arguments
+    /// * `op` - Opération visée.
+    /// * `n` - Rang de l'appel saboté, à partir de 1.
+    /// * `fault` - Comportement de l'appel saboté.
+    pub fn inject(&self, op: StorageOp, n: u64, fault: Fault) -> &Self {
+        self.lock().planned.push((op, n, fault));
+        self
+    }
+
+    /// Retourne le nombre d'appels à `op` depuis la création ou le dernier
+    /// [`FaultInjector::reset`].
+    pub fn calls(&self, op: StorageOp) -> u64 {
+        self.lock().calls[op.index()]
+    }
+
+    /// Retourne les pannes déclenchées, dans l'ordre.
+    pub fn triggered(&self) -> Vec<(StorageOp, u64, Fault)> {
+        self.lock().triggered.clone()
+    }
+
+    /// Remet les compteurs à zéro et oublie les pannes prévues et déclenchées.
+    pub fn reset(&self) {
+        *self.lock() = Faults::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Faults> {
+        self.faults.lock().expect("verrou des pannes empoisonné")
+    }
+
+    fn check(&self, op: StorageOp) -> io::Result<()> {
+        match self.lock().call(op) {
+            Some(_) => Err(injected(op)),
+            None => Ok(()),
+        }
+    }
+
+    fn wrap(&self, file: Box<dyn StorageFile>) -> Box<dyn StorageFile> {
+        Box::new(InjectedFile {
+            inner: file,
+            injector: self.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+fn injected(op: StorageOp) -> io::Error {
+    io::Error::other(format!("panne injectée ({:?})", op))
+}
+
+#[cfg(feature = "fault-injection")]
+impl Storage for FaultInjector {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        self.inner.open(path)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn StorageFile>> {
+        Ok(self.wrap(self.inner.create(path)?))
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn StorageFile>> {
+        Ok(self.wrap(self.inner.append(path)?))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(StorageOp::Rename)?;
+        self.inner.rename(from, to)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+}
+
+/// Fichier ouvert par un [`FaultInjector`].
+#[cfg(feature = "fault-injection")]
+struct InjectedFile {
+    inner: Box<dyn StorageFile>,
+    injector: FaultInjector,
+}
+
+#[cfg(feature = "fault-injection")]
+impl Write for InjectedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fault = self.injector.lock().call(StorageOp::Write);
+        match fault {
+            None => self.inner.write(buf),
+            Some(Fault::Fail) => Err(injected(StorageOp::Write)),
+            Some(Fault::Truncate(n)) => {
+                self.inner.write_all(&buf[..n.min(buf.len())])?;
+                self.inner.flush()?;
+                Err(injected(StorageOp::Write))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+impl StorageFile for InjectedFile {
+    fn sync(&mut self) -> io::Result<()> {
+        self.injector.check(StorageOp::Sync)?;
+        self.inner.sync()
+    }
+}
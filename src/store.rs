@@ -0,0 +1,131 @@
+use uuid::Uuid;
+
+use crate::collection::{Collection, Document};
+use crate::error::Result;
+use crate::payload::Payload;
+use crate::search::{SearchParams, SearchResults};
+use crate::similarity::Metric;
+
+/// Lecture et recherche communes aux stockages de vecteurs.
+///
+/// Implémenté par [`Collection`], [`ShardedCollection`](crate::ShardedCollection) et les
+/// collections en lecture seule, ce trait permet de lire et d'interroger une collection
+/// sans connaître sa représentation. Les écritures sont décrites par [`VectorStoreMut`].
+pub trait VectorStore: Send + Sync {
+    /// Lit le vecteur d'un document.
+    fn read(&self, key: &Uuid) -> Option<&[f32]>;
+
+    /// Lit la charge utile d'un document.
+    fn payload(&self, key: &Uuid) -> Option<&Payload>;
+
+    /// Retourne le nombre de documents qui ont un vecteur.
+    fn len(&self) -> usize;
+
+    /// Indique si le stockage ne contient aucun document.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parcourt les identifiants des documents, dans un ordre quelconque.
+    fn keys(&self) -> Box<dyn Iterator<Item = &Uuid> + '_>;
+
+    /// Retourne la mesure utilisée par les recherches.
+    fn metric(&self) -> Metric;
+
+    /// Recherche les documents les plus proches de la requête selon `params`.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search_with`].
+    fn search_with(&self, request: &[f32], params: &SearchParams) -> Result<SearchResults>;
+
+    /// Recherche les `k` documents les plus proches de la requête.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::search`].
+    fn search(&self, request: &[f32], k: usize) -> Result<Document> {
+        Ok(self.search_with(request, &SearchParams::new(k))?.hits)
+    }
+
+    /// Lit tous les vecteurs pour que la première recherche ne paie pas leur chargement
+    /// en mémoire. Ne modifie rien : un second appel refait le même parcours.
+    fn warm_up(&self) {
+        for key in self.keys() {
+            if let Some(vector) = self.read(key) {
+                touch(vector);
+            }
+        }
+    }
+}
+
+/// Lit toutes les coordonnées de `vector` sans que le compilateur puisse omettre la lecture.
+pub(crate) fn touch(vector: &[f32]) {
+    std::hint::black_box(vector.iter().sum::<f32>());
+}
+
+/// Écritures communes aux stockages de vecteurs modifiables d'une
+/// [`BaseDeDonnees`](crate::BaseDeDonnees).
+pub trait VectorStoreMut: VectorStore {
+    /// Insère ou met à jour un document, en conservant sa charge utile.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert`].
+    fn upsert(&mut self, key: Uuid, vector: Vec<f32>) -> Result<()>;
+
+    /// Insère ou met à jour un document avec sa charge utile.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Collection::upsert_with_payload`].
+    fn upsert_with_payload(&mut self, key: Uuid, vector: Vec<f32>, payload: Payload) -> Result<()>;
+
+    /// Supprime un document et sa charge utile.
+    fn delete(&mut self, key: &Uuid);
+}
+
+impl VectorStore for Collection {
+    fn read(&self, key: &Uuid) -> Option<&[f32]> {
+        Collection::read(self, key).map(Vec::as_slice)
+    }
+
+    fn payload(&self, key: &Uuid) -> Option<&Payload> {
+        Collection::payload(self, key)
+    }
+
+    fn len(&self) -> usize {
+        Collection::searchable_len(self)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &Uuid> + '_> {
+        Box::new(self.documents.keys())
+    }
+
+    fn metric(&self) -> Metric {
+        Collection::metric(self)
+    }
+
+    fn search_with(&self, request: &[f32], params: &SearchParams) -> Result<SearchResults> {
+        Collection::search_with(self, request, params)
+    }
+
+    fn warm_up(&self) {
+        Collection::warm_up(self)
+    }
+
+    /// Utilise les paramètres par défaut de la collection, comme [`Collection::search`].
+    fn search(&self, request: &[f32], k: usize) -> Result<Document> {
+        Collection::search(self, request, k)
+    }
+}
+
+impl VectorStoreMut for Collection {
+    fn upsert(&mut self, key: Uuid, vector: Vec<f32>) -> Result<()> {
+        Collection::upsert(self, key, vector).map(drop)
+    }
+
+    fn upsert_with_payload(&mut self, key: Uuid, vector: Vec<f32>, payload: Payload) -> Result<()> {
+        Collection::upsert_with_payload(self, key, vector, payload).map(drop)
+    }
+
+    fn delete(&mut self, key: &Uuid) {
+        Collection::delete(self, key)
+    }
+}
@@ -0,0 +1,218 @@
+//! Génération de données de test reproductibles : vecteurs aléatoires, éventuellement
+//! regroupés en amas, et collections remplies, pour les exemples, les mesures de
+//! performance et les essais.
+//!
+//! Pour une même graine, les vecteurs et les identifiants générés sont toujours les
+//! mêmes, d'une exécution et d'une machine à l'autre.
+
+use uuid::{Builder, Uuid};
+
+use crate::collection::Collection;
+use crate::rng::Rng;
+
+/// Générateur de vecteurs et d'identifiants aléatoires.
+///
+/// Les coordonnées suivent une loi normale centrée réduite : normalisés, les vecteurs
+/// sont répartis uniformément sur la sphère.
+#[derive(Debug, Clone)]
+pub struct VectorGenerator {
+    rng: Rng,
+    dimension: usize,
+}
+
+impl VectorGenerator {
+    /// Crée un générateur de vecteurs de dimension `dimension`.
+    ///
+    /// # Arguments
+    /// * `dimension` - Dimension des vecteurs générés.
+    /// * `seed` - Graine du générateur.
+    pub fn new(dimension: usize, seed: u64) -> Self {
+        VectorGenerator {
+            rng: Rng::new(seed),
+            dimension,
+        }
+    }
+
+    /// Retourne la dimension des vecteurs générés.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Génère un vecteur.
+    pub fn vector(&mut self) -> Vec<f32> {
+        (0..self.dimension)
+            .map(|_| self.rng.gaussian() as f32)
+            .collect()
+    }
+
+    /// Génère `count` vecteurs.
+    pub fn vectors(&mut self, count: usize) -> Vec<Vec<f32>> {
+        (0..count).map(|_| self.vector()).collect()
+    }
+
+    /// Génère un vecteur voisin de `center` : chaque coordonnée s'en écarte selon une loi
+    /// normale d'écart type `spread`.
+    pub fn vector_near(&mut self, center: &[f32], spread: f32) -> Vec<f32> {
+        center
+            .iter()
+            .map(|x| x + spread * self.rng.gaussian() as f32)
+            .collect()
+    }
+
+    /// Génère un `Uuid` de version 4.
+    pub fn uuid(&mut self) -> Uuid {
+        let bits = (u128::from(self.rng.next_u64()) << 64) | u128::from(self.rng.next_u64());
+        Builder::from_random_bytes(bits.to_be_bytes()).into_uuid()
+    }
+
+    /// Remplit `collection` de `count` documents aléatoires.
+    ///
+    /// # Retourne
+    /// * Vec<Uuid> - Identifiants des documents insérés, dans l'ordre d'insertion.
+    ///
+    /// # Panique
+    /// * Si la collection refuse les vecteurs générés, par exemple à cause d'une
+    ///   dimension imposée différente.
+    pub fn fill(&mut self, collection: &mut Collection, count: usize) -> Vec<Uuid> {
+        (0..count)
+            .map(|_| {
+                let key = self.uuid();
+                collection
+                    .upsert(key, self.vector())
+                    .expect("vecteur généré refusé par la collection");
+                key
+            })
+            .collect()
+    }
+}
+
+/// Génère `n` vecteurs aléatoires de dimension `dim`.
+///
+/// # Arguments
+/// * `n` - Nombre de vecteurs.
+/// * `dim` - Dimension des vecteurs.
+/// * `seed` - Graine du générateur.
+pub fn random_vectors(n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+    VectorGenerator::new(dim, seed).vectors(n)
+}
+
+/// Génère des vecteurs regroupés en `n_clusters` amas de `per_cluster` vecteurs.
+///
+/// Les centres des amas sont des vecteurs aléatoires ([`random_vectors`]) ; chaque
+/// coordonnée d'un vecteur s'écarte de celle de son centre selon une loi normale d'écart
+/// type `spread`. Les vecteurs sont rangés amas par amas.
+///
+/// # Arguments
+/// * `n_clusters` - Nombre d'amas.
+/// * `per_cluster` - Nombre de vecteurs par amas.
+/// * `dim` - Dimension des vecteurs.
+/// * `spread` - Dispersion autour du centre ; 0 donne des vecteurs confondus avec lui.
+/// * `seed` - Graine du générateur.
+///
+/// # Retourne
+/// * (Vec<Vec<f32>>, Vec<usize>) - Les vecteurs et, pour chacun, le numéro de son amas.
+pub fn clustered_vectors(
+    n_clusters: usize,
+    per_cluster: usize,
+    dim: usize,
+    spread: f32,
+    seed: u64,
+) -> (Vec<Vec<f32>>, Vec<usize>) {
+    let mut generator = VectorGenerator::new(dim, seed);
+    let centers = generator.vectors(n_clusters);
+    let mut vectors = Vec::with_capacity(n_clusters * per_cluster);
+    let mut labels = Vec::with_capacity(n_clusters * per_cluster);
+    for (label, center) in centers.iter().enumerate() {
+        for _ in 0..per_cluster {
+            vectors.push(generator.vector_near(center, spread));
+            labels.push(label);
+        }
+    }
+    (vectors, labels)
+}
+
+/// Insère `n` documents aléatoires de dimension `dim` dans `collection`.
+///
+/// # Arguments
+/// * `collection` - Collection à remplir.
+/// * `n` - Nombre de documents.
+/// * `dim` - Dimension des vecteurs.
+/// * `seed` - Graine du générateur, qui fixe aussi les identifiants.
+///
+/// # Retourne
+/// * Vec<Uuid> - Identifiants des documents insérés, dans l'ordre d'insertion.
+///
+/// # Panique
+/// * Si la collection refuse les vecteurs générés.
+pub fn populate_collection(
+    collection: &mut Collection,
+    n: usize,
+    dim: usize,
+    seed: u64,
+) -> Vec<Uuid> {
+    VectorGenerator::new(dim, seed).fill(collection, n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seed_always_gives_the_same_data() {
+        // Valeurs figées : un changement du générateur invaliderait les jeux de test
+        // et les mesures enregistrés.
+        let expected = [
+            [0.939_916_55, -1.273_622_6, -0.805_394_65],
+            [0.265_679_84, -0.984_651_1, -0.891_596_5],
+        ];
+        let vectors = random_vectors(2, 3, 144);
+        for (vector, expected) in vectors.iter().zip(&expected) {
+            assert!(vector.iter().zip(expected).all(|(x, y)| (x - y).abs() < 1e-6));
+        }
+        assert_eq!(
+            VectorGenerator::new(3, 144).uuid().to_string(),
+            "6855ce56-43ee-4108-af71-ba2e1235cc1b"
+        );
+        assert_eq!(random_vectors(50, 8, 7), random_vectors(50, 8, 7));
+        assert_ne!(random_vectors(50, 8, 7), random_vectors(50, 8, 8));
+        assert_eq!(
+            clustered_vectors(4, 5, 8, 0.1, 3),
+            clustered_vectors(4, 5, 8, 0.1, 3)
+        );
+    }
+
+    #[test]
+    fn clustered_vectors_stay_near_their_center() {
+        let (vectors, labels) = clustered_vectors(5, 20, 16, 0.05, 144);
+        assert_eq!((vectors.len(), labels.len()), (100, 100));
+        assert_eq!(labels[..20], [0; 20]);
+        assert_eq!(labels[80..], [4; 20]);
+        let centers = VectorGenerator::new(16, 144).vectors(5);
+        let distance = |a: &[f32], b: &[f32]| -> f32 {
+            a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt()
+        };
+        for (vector, label) in vectors.iter().zip(&labels) {
+            let nearest = (0..5)
+                .min_by(|a, b| {
+                    distance(vector, &centers[*a]).total_cmp(&distance(vector, &centers[*b]))
+                })
+                .unwrap();
+            assert_eq!(nearest, *label);
+        }
+        let (exact, _) = clustered_vectors(2, 3, 4, 0.0, 1);
+        assert_eq!(exact[0], VectorGenerator::new(4, 1).vector());
+    }
+
+    #[test]
+    fn populated_collections_are_reproducible() {
+        let mut first = Collection::new();
+        let mut second = Collection::new();
+        let keys = populate_collection(&mut first, 30, 6, 144);
+        assert_eq!(populate_collection(&mut second, 30, 6, 144), keys);
+        assert_eq!(first.len(), 30);
+        for key in &keys {
+            assert_eq!(key.get_version_num(), 4);
+            assert_eq!(first.read(key), second.read(key));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::document_id::DocumentId;
+use crate::memory;
+use crate::oplog::OperationKind;
+use crate::payload::Payload;
+
+/// Document supprimé en suppression différée, conservé jusqu'au compactage.
+#[derive(Debug, Clone)]
+pub(crate) struct Tombstone {
+    pub(crate) vector: Vec<f32>,
+    pub(crate) payload: Option<Payload>,
+    pub(crate) id: Option<DocumentId>,
+    pub(crate) namespace: Option<String>,
+}
+
+impl Collection {
+    /// Choisit le mode de suppression de la collection.
+    ///
+    /// En suppression différée, [`Collection::delete`] retire le document de toutes les
+    /// lectures, recherches et du décompte [`Collection::len`], mais le conserve à
+    /// l'écart : [`Collection::restore`] peut l'annuler, et la mémoire n'est libérée
+    /// qu'au [`Collection::compact`]. Désactiver la suppression différée ne compacte pas
+    /// la collection.
+    ///
+    /// # Arguments
+    /// * `soft_delete` - Vrai pour différer les suppressions.
+    pub fn with_soft_delete(mut self, soft_delete: bool) -> Self {
+        self.config.soft_delete = soft_delete;
+        self
+    }
+
+    /// Indique si les suppressions sont différées.
+    pub fn soft_delete(&self) -> bool {
+        self.config.soft_delete
+    }
+
+    /// Retourne le nombre de documents supprimés en attente de compactage.
+    ///
+    /// Comparé à [`Collection::len`], il indique si un compactage vaut la peine.
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// Annule la suppression différée du document `key`, avec sa charge utile et son
+    /// identifiant d'origine.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du document supprimé.
+    ///
+    /// # Retourne
+    /// * bool - `true` si le document a été restauré, `false` s'il n'était pas en attente
+    ///   de compactage.
+    pub fn restore(&mut self, key: &Uuid) -> bool {
+        let Some(tombstone) = self.discard_tombstone(key) else {
+            return false;
+        };
+        self.memory.vectors += memory::vector_bytes(&tombstone.vector);
+        self.stats.add(&tombstone.vector);
+        let vector = self.share_vector(tombstone.vector);
+        self.documents.insert(*key, vector);
+        if self.config.memory_eviction {
+            self.recency.touch(*key);
+        }
+        if let Some(payload) = tombstone.payload {
+            self.put_payload(*key, payload);
+        }
+        if let Some(id) = tombstone.id {
+            self.aliases.insert(*key, id);
+        }
+        if let Some(namespace) = tombstone.namespace {
+            self.namespaces.insert(*key, namespace);
+        }
+        true
+    }
+
+    /// Supprime définitivement les documents en attente de compactage.
+    ///
+    /// Les index de charge utile ne référencent que les documents vivants : le compactage
+    /// ne fait que libérer la mémoire des documents supprimés.
+    ///
+    /// # Retourne
+    /// * usize - Nombre de documents supprimés définitivement.
+    pub fn compact(&mut self) -> usize {
+        self.logged(
+            OperationKind::Compact,
+            |collection| {
+                let tombstones = std::mem::take(&mut collection.tombstones);
+                for (key, tombstone) in &tombstones {
+                    collection.memory.vectors -= memory::vector_bytes(&tombstone.vector);
+                    if let Some(payload) = &tombstone.payload {
+                        collection.memory.payloads -= memory::payload_bytes(payload);
+                        collection.release_blobs(*key, payload, None);
+                    }
+                }
+                tombstones.len()
+            },
+            |count| Some(*count),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::payload::Value;
+
+    fn collection() -> (Collection, Uuid, Uuid) {
+        let mut collection = Collection::new().with_soft_delete(true);
+        let (kept, deleted) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        collection.upsert(kept, [1.0, 0.0]).unwrap();
+        let mut payload = Payload::new();
+        payload.insert("titre".to_string(), Value::String("brouillon".to_string()));
+        collection
+            .upsert_with_payload(deleted, [1.0, 0.1], payload.clone())
+            .unwrap();
+        collection.delete(&deleted);
+        (collection, kept, deleted)
+    }
+
+    #[test]
+    fn deleted_documents_are_gone_until_restored() {
+        let (mut collection, kept, deleted) = collection();
+        assert_eq!((collection.len(), collection.tombstone_count()), (1, 1));
+        assert!(collection.read(&deleted).is_none());
+        assert!(collection.payload(&deleted).is_none());
+        let hits = collection.search([1.0, 0.1], 5).unwrap();
+        assert_eq!(hits.iter().map(|(key, _)| *key).collect::<Vec<_>>(), [kept]);
+
+        assert!(collection.restore(&deleted));
+        assert!(!collection.restore(&deleted));
+        assert_eq!((collection.len(), collection.tombstone_count()), (2, 0));
+        assert_eq!(collection.search([1.0, 0.1], 1).unwrap()[0].0, deleted);
+        assert_eq!(
+            collection.payload(&deleted).unwrap()["titre"],
+            Value::String("brouillon".to_string())
+        );
+        collection.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn compaction_frees_the_deleted_documents() {
+        let (mut collection, _, deleted) = collection();
+        let before = collection.memory_usage();
+        assert_eq!(collection.compact(), 1);
+        assert_eq!(collection.compact(), 0);
+        assert!(!collection.restore(&deleted));
+        assert_eq!((collection.len(), collection.tombstone_count()), (1, 0));
+        assert!(collection.memory_usage().total() < before.total());
+        collection.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn saves_keep_the_mode_but_not_the_deleted_documents() {
+        let (collection, kept, deleted) = collection();
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let mut loaded = loaded.get("docs").unwrap().clone();
+        assert!(loaded.soft_delete());
+        assert_eq!((loaded.len(), loaded.tombstone_count()), (1, 0));
+        assert!(loaded.read(&kept).is_some());
+        assert!(!loaded.restore(&deleted));
+    }
+
+    #[test]
+    fn hard_deletes_leave_no_tombstone() {
+        let mut collection = Collection::new();
+        let key = Uuid::new_v4();
+        collection.upsert(key, [1.0]).unwrap();
+        collection.delete(&key);
+        assert_eq!((collection.len(), collection.tombstone_count()), (0, 0));
+        assert!(!collection.restore(&key));
+    }
+}
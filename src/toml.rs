@@ -0,0 +1,338 @@
+//! Lecture minimale de TOML, sans dépendance externe.
+//!
+//! Couvre ce qu'écrit un fichier de configuration : tables `[a.b]`, clés simples,
+//! entre guillemets ou pointées, chaînes, nombres, booléens, tableaux et tables en ligne.
+//! Les tableaux de tables `[[a]]`, les chaînes sur plusieurs lignes et les dates sont
+//! refusés.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::payload::Value;
+
+/// Profondeur maximale d'imbrication des valeurs acceptée par [`parse`].
+const MAX_PARSE_DEPTH: usize = 64;
+
+/// Lit un document TOML complet.
+///
+/// # Retourne
+/// * Result<Value, String> - La table racine, en `Value::Object`, ou la description de la
+///   première erreur.
+pub(crate) fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        position: 0,
+        line: 1,
+    };
+    let mut root = BTreeMap::new();
+    let mut current = Vec::new();
+    let mut headers = HashSet::new();
+    loop {
+        parser.skip_blank_lines();
+        match parser.bytes.get(parser.position) {
+            None => return Ok(Value::Object(root)),
+            Some(b'[') => {
+                parser.position += 1;
+                if parser.bytes.get(parser.position) == Some(&b'[') {
+                    return Err(parser.error("tableaux de tables non pris en charge"));
+                }
+                let path = parser.key()?;
+                parser.skip_spaces();
+                parser.expect(b']')?;
+                if !headers.insert(path.clone()) {
+                    return Err(parser.error("table déclarée deux fois"));
+                }
+                table_at(&mut root, &path).map_err(|message| parser.error(&message))?;
+                current = path;
+            }
+            Some(_) => {
+                let mut path = parser.key()?;
+                parser.skip_spaces();
+                parser.expect(b'=')?;
+                let value = parser.value(0)?;
+                let Some(name) = path.pop() else {
+                    return Err(parser.error("clé attendue"));
+                };
+                let full: Vec<String> = current.iter().chain(&path).cloned().collect();
+                let table = table_at(&mut root, &full).map_err(|message| parser.error(&message))?;
+                if table.insert(name, value).is_some() {
+                    return Err(parser.error("clé définie deux fois"));
+                }
+            }
+        }
+        parser.end_of_line()?;
+    }
+}
+
+/// Retourne la table désignée par `path`, en créant les tables manquantes.
+fn table_at<'a>(
+    root: &'a mut BTreeMap<String, Value>,
+    path: &[String],
+) -> Result<&'a mut BTreeMap<String, Value>, String> {
+    let mut table = root;
+    for (depth, name) in path.iter().enumerate() {
+        let entry = table
+            .entry(name.clone())
+            .or_insert_with(|| Value::Object(BTreeMap::new()));
+        table = match entry {
+            Value::Object(fields) => fields,
+            _ => {
+                return Err(format!(
+                    "« {} » n'est pas une table",
+                    path[..=depth].join(".")
+                ))
+            }
+        };
+    }
+    Ok(table)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} (ligne {})", message, self.line)
+    }
+
+    fn skip_spaces(&mut self) {
+        while let Some(b' ' | b'\t') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    /// Passe un commentaire éventuel, jusqu'à la fin de la ligne non comprise.
+    fn skip_comment(&mut self) {
+        if self.bytes.get(self.position) == Some(&b'#') {
+            while !matches!(self.bytes.get(self.position), None | Some(b'\n')) {
+                self.position += 1;
+            }
+        }
+    }
+
+    /// Passe les espaces, commentaires et sauts de ligne.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.bytes.get(self.position) {
+                Some(b'\n') => {
+                    self.position += 1;
+                    self.line += 1;
+                }
+                Some(b'\r') if self.bytes.get(self.position + 1) == Some(&b'\n') => {
+                    self.position += 1;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Vérifie qu'il ne reste qu'un commentaire éventuel sur la ligne.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.bytes.get(self.position) {
+            None | Some(b'\n' | b'\r') => Ok(()),
+            Some(_) => Err(self.error("fin de ligne attendue")),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.bytes.get(self.position) != Some(&byte) {
+            return Err(self.error(&format!("« {} » attendu", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Lit une clé, éventuellement pointée : `a`, `"a b"` ou `a."b".c`.
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.bytes.get(self.position) {
+                Some(b'"') => path.push(self.basic_string()?),
+                Some(b'\'') => path.push(self.literal_string()?),
+                _ => {
+                    let start = self.position;
+                    while let Some(b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-') =
+                        self.bytes.get(self.position)
+                    {
+                        self.position += 1;
+                    }
+                    if start == self.position {
+                        return Err(self.error("clé attendue"));
+                    }
+                    path.push(self.text(start).to_string());
+                }
+            }
+            self.skip_spaces();
+            if self.bytes.get(self.position) != Some(&b'.') {
+                return Ok(path);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn text(&self, start: usize) -> &str {
+        // Le texte d'origine est UTF-8 et les coupures tombent sur des octets ASCII.
+        std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or("")
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_PARSE_DEPTH {
+            return Err(self.error("imbrication trop profonde"));
+        }
+        self.skip_spaces();
+        match self.bytes.get(self.position) {
+            None | Some(b'\n' | b'\r' | b'#') => Err(self.error("valeur attendue")),
+            Some(b'"') if self.bytes[self.position..].starts_with(b"\"\"\"") => {
+                Err(self.error("chaînes sur plusieurs lignes non prises en charge"))
+            }
+            Some(b'"') => Ok(Value::String(self.basic_string()?)),
+            Some(b'\'') => Ok(Value::String(self.literal_string()?)),
+            Some(b'[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank_lines();
+                    if self.bytes.get(self.position) == Some(&b']') {
+                        break;
+                    }
+                    items.push(self.value(depth + 1)?);
+                    self.skip_blank_lines();
+                    match self.bytes.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Value::Array(items))
+            }
+            Some(b'{') => {
+                self.position += 1;
+                let mut fields = BTreeMap::new();
+                self.skip_spaces();
+                if self.bytes.get(self.position) == Some(&b'}') {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    let mut path = self.key()?;
+                    self.expect(b'=')?;
+                    let value = self.value(depth + 1)?;
+                    let name = path.pop().unwrap_or_default();
+                    let table = table_at(&mut fields, &path).map_err(|m| self.error(&m))?;
+                    if table.insert(name, value).is_some() {
+                        return Err(self.error("clé définie deux fois"));
+                    }
+                    self.skip_spaces();
+                    match self.bytes.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Value::Object(fields))
+            }
+            Some(b't') if self.bytes[self.position..].starts_with(b"true") => {
+                self.position += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(b'f') if self.bytes[self.position..].starts_with(b"false") => {
+                self.position += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' | b'_') =
+            self.bytes.get(self.position)
+        {
+            self.position += 1;
+        }
+        let text = self.text(start).replace('_', "");
+        text.parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite() && !text.is_empty())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("valeur invalide"))
+    }
+
+    /// Lit une chaîne littérale `'...'`, sans échappement.
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let start = self.position;
+        while let Some(&byte) = self.bytes.get(self.position) {
+            match byte {
+                b'\'' => {
+                    let text = self.text(start).to_string();
+                    self.position += 1;
+                    return Ok(text);
+                }
+                b'\n' => break,
+                _ => self.position += 1,
+            }
+        }
+        Err(self.error("chaîne non terminée"))
+    }
+
+    /// Lit une chaîne `"..."`, guillemet ouvrant compris.
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.position;
+            while let Some(&byte) = self.bytes.get(self.position) {
+                if byte == b'"' || byte == b'\\' || byte == b'\n' {
+                    break;
+                }
+                self.position += 1;
+            }
+            out.push_str(self.text(start));
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = self.bytes.get(self.position).copied();
+                    self.position += 1;
+                    match escaped {
+                        Some(b'"') => out.push('"'),
+                        Some(b'\\') => out.push('\\'),
+                        Some(b'b') => out.push('\u{8}'),
+                        Some(b'f') => out.push('\u{c}'),
+                        Some(b'n') => out.push('\n'),
+                        Some(b'r') => out.push('\r'),
+                        Some(b't') => out.push('\t'),
+                        Some(b'u') => out.push(self.unicode_escape(4)?),
+                        Some(b'U') => out.push(self.unicode_escape(8)?),
+                        _ => return Err(self.error("échappement invalide")),
+                    }
+                }
+                _ => return Err(self.error("chaîne non terminée")),
+            }
+        }
+    }
+
+    /// Lit les `digits` chiffres hexadécimaux d'un échappement `\u` ou `\U`.
+    fn unicode_escape(&mut self, digits: usize) -> Result<char, String> {
+        let code = self
+            .bytes
+            .get(self.position..self.position + digits)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("échappement unicode invalide"))?;
+        self.position += digits;
+        Ok(code)
+    }
+}
@@ -0,0 +1,102 @@
+//! Meilleurs résultats d'un flux de scores, en mémoire bornée par `k`.
+
+use uuid::Uuid;
+
+use crate::collection::Document;
+use crate::error::Error;
+use crate::similarity::Metric;
+
+/// Accumulateur des `k` meilleurs couples `Uuid` et score, quel que soit le nombre de
+/// scores reçus : sa mémoire est proportionnelle à `k`.
+///
+/// Les résultats sont classés comme ceux d'une recherche : du meilleur au moins bon selon
+/// la mesure, les égalités départagées par `Uuid` croissant. Les scores NaN sont ignorés.
+/// L'accumulateur générique [`core::TopK`](crate::core::TopK) accepte d'autres
+/// identifiants, sans la bibliothèque standard.
+pub type TopK = crate::core::TopK<Uuid>;
+
+/// Retourne les `k` vecteurs d'un flux les plus proches de `query`, sans conserver le
+/// flux : la mémoire utilisée est proportionnelle à `k`, quelle que soit sa longueur.
+///
+/// Un vecteur d'une autre dimension que la requête n'interrompt pas le flux : il est
+/// écarté et signalé à `on_error` avec `Error::DimensionMismatch`.
+///
+/// # Arguments
+/// * `query` - Vecteur de la requête.
+/// * `vectors` - Couples identifiant et vecteur à évaluer.
+/// * `k` - Nombre de résultats.
+/// * `metric` - Mesure des scores.
+/// * `on_error` - Appelée pour chaque vecteur écarté, avec son identifiant.
+///
+/// # Retourne
+/// * Document - Les résultats, classés comme ceux d'une recherche.
+pub fn search_stream(
+    query: &[f32],
+    vectors: impl IntoIterator<Item = (Uuid, Vec<f32>)>,
+    k: usize,
+    metric: Metric,
+    mut on_error: impl FnMut(Uuid, Error),
+) -> Document {
+    let mut top = TopK::for_metric(k, metric);
+    for (key, vector) in vectors {
+        match metric.score(query, &vector) {
+            Ok(score) => top.push(key, score),
+            Err(error) => on_error(key, error),
+        }
+    }
+    top.into_sorted_hits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+    use crate::synthetic::VectorGenerator;
+
+    #[test]
+    fn stream_ranks_like_a_collection_search() {
+        for metric in [Metric::Cosine, Metric::Dot, Metric::Euclidean, Metric::Manhattan] {
+            let mut generator = VectorGenerator::new(6, 185);
+            let items: Vec<(Uuid, Vec<f32>)> = (0..500)
+                .map(|_| (generator.uuid(), generator.vector()))
+                .collect();
+            let mut collection = Collection::new().with_metric(metric);
+            collection.upsert_batch(items.clone(), None).unwrap();
+            let query = generator.vector();
+            let hits = search_stream(&query, items, 10, metric, |key, error| {
+                panic!("{} : {}", key, error)
+            });
+            assert_eq!(hits, collection.search(&query, 10).unwrap());
+        }
+    }
+
+    #[test]
+    fn wrong_dimensions_are_reported_without_stopping_the_stream() {
+        let (short, long) = (Uuid::new_v4(), Uuid::new_v4());
+        let items = vec![
+            (Uuid::new_v4(), vec![1.0, 0.0]),
+            (short, vec![1.0]),
+            (Uuid::new_v4(), vec![0.0, 1.0]),
+            (long, vec![1.0, 0.0, 0.0]),
+            (Uuid::new_v4(), vec![1.0, 1.0]),
+        ];
+        let mut rejected = Vec::new();
+        let hits = search_stream(&[1.0, 0.0], items, 10, Metric::Dot, |key, error| {
+            assert!(matches!(error, Error::DimensionMismatch { .. }));
+            rejected.push(key);
+        });
+        assert_eq!(rejected, [short, long]);
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[test]
+    fn accumulator_keeps_the_best_k_and_ignores_nan() {
+        let mut top = TopK::for_metric(2, Metric::Euclidean);
+        let keys: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for (key, score) in keys.iter().zip([3.0, f32::NAN, 1.0, 2.0]) {
+            top.push(*key, score);
+        }
+        assert_eq!(top.len(), 2);
+        assert_eq!(top.into_sorted_hits(), [(keys[2], 1.0), (keys[3], 2.0)]);
+    }
+}
@@ -0,0 +1,423 @@
+//! Application en bloc d'un lot d'opérations mixtes : toutes ou aucune.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::memory::BudgetSlot;
+use crate::oplog::OperationKind;
+use crate::payload::Payload;
+
+/// Opération d'un lot appliqué par [`Collection::apply`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Insertion ou mise à jour d'un document, comme [`Collection::upsert`], ou
+    /// [`Collection::upsert_with_payload`] si une charge utile est fournie.
+    Upsert {
+        key: Uuid,
+        vector: Vec<f32>,
+        payload: Option<Payload>,
+    },
+    /// Suppression d'un document, comme [`Collection::delete`] ; sans effet s'il
+    /// n'existe pas.
+    Delete { key: Uuid },
+    /// Remplacement de la charge utile d'un document, comme [`Collection::set_payload`] :
+    /// il doit exister, ou avoir été inséré plus tôt dans le lot.
+    SetPayload { key: Uuid, payload: Payload },
+}
+
+impl Op {
+    /// Retourne l'identifiant du document visé.
+    pub fn key(&self) -> Uuid {
+        match self {
+            Op::Upsert { key, .. } | Op::Delete { key } | Op::SetPayload { key, .. } => *key,
+        }
+    }
+}
+
+/// Bilan de [`Collection::apply`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ApplyReport {
+    /// Documents insérés ou mis à jour.
+    pub upserted: usize,
+    /// Documents supprimés.
+    pub deleted: usize,
+    /// Charges utiles remplacées par [`Op::SetPayload`].
+    pub payloads_set: usize,
+    /// Suppressions sans effet, le document n'existant pas.
+    pub not_found: usize,
+    /// Insertions écartées comme doublons (`DedupPolicy::Skip`), avec les remplacements
+    /// de charge utile qui visaient ces documents jamais écrits.
+    pub skipped: usize,
+}
+
+impl ApplyReport {
+    /// Nombre de documents écrits ou supprimés.
+    fn items(&self) -> usize {
+        self.upserted + self.deleted + self.payloads_set
+    }
+}
+
+impl fmt::Display for ApplyReport {
+    /// Affiche le bilan sur une ligne :
+    /// `50 document(s) écrit(s), 3 supprimé(s), 10 charge(s) utile(s) remplacée(s), 0 absent(s)`,
+    /// suivi de `, 2 doublon(s) ignoré(s)` si des insertions ont été écartées.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} document(s) écrit(s), {} supprimé(s), {} charge(s) utile(s) remplacée(s), \
+             {} absent(s)",
+            self.upserted, self.deleted, self.payloads_set, self.not_found
+        )?;
+        if self.skipped > 0 {
+            write!(f, ", {} doublon(s) ignoré(s)", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Opération validée, prête à être appliquée sans échec possible.
+enum Prepared {
+    Upsert {
+        key: Uuid,
+        /// Vecteur projeté et normalisé comme il sera stocké.
+        vector: Vec<f32>,
+        /// Dimension du vecteur fourni, qui fixe celle de la collection s'il est le premier.
+        dimension: usize,
+        /// Charge utile conforme au schéma, et nombre de champs convertis.
+        payload: Option<(Payload, u64)>,
+    },
+    Delete(Uuid),
+    SetPayload {
+        key: Uuid,
+        payload: Payload,
+        coerced: u64,
+    },
+}
+
+/// Présence d'un document au fil d'un lot validé.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Presence {
+    Absent,
+    /// Document enregistré sans vecteur ([`Collection::register`]).
+    Pending,
+    Stored,
+}
+
+impl Collection {
+    /// Applique un lot d'opérations mixtes en bloc : soit toutes réussissent, soit la
+    /// collection n'est pas modifiée.
+    ///
+    /// Le lot entier est validé avant la première écriture, dans l'ordre des opérations
+    /// et en tenant compte des précédentes : une insertion qui fixe la dimension de la
+    /// collection s'impose aux suivantes, et une charge utile peut être remplacée sur un
+    /// document inséré plus haut dans le lot. Les opérations sont ensuite appliquées
+    /// dans l'ordre, et les recherches enregistrées voient leurs nouveaux documents
+    /// comme pour des écritures séparées. Dans une base dont la limite de mémoire
+    /// pourrait encore refuser une insertion, ou dans une collection qui déduplique ses
+    /// insertions ([`Collection::with_dedup`]), le lot est appliqué à une copie de la
+    /// collection, qui partage ses vecteurs, puis substitué à celle-ci s'il réussit.
+    /// Chaque nouveau document y est comparé aux documents déjà écrits, y compris ceux
+    /// insérés plus haut dans le lot, exactement comme par [`Collection::upsert`].
+    /// Les champs volumineux conservés dans un magasin de blobs
+    /// ([`Collection::set_blob_store`]) ne remplacent les blobs en place, et les blobs
+    /// libérés ne sont supprimés, que si le lot entier réussit.
+    ///
+    /// L'historique des opérations enregistre le lot comme une seule opération
+    /// [`OperationKind::Apply`] ; [`Primary::apply`](crate::Primary::apply) l'inscrit
+    /// de même en un seul enregistrement du journal de réplication.
+    ///
+    /// # Arguments
+    /// * `ops` - Opérations à appliquer, dans l'ordre.
+    ///
+    /// # Retourne
+    /// * Result<ApplyReport> - Nombre de documents écrits, supprimés, absents et
+    ///   ignorés comme doublons.
+    ///
+    /// # Erreurs
+    /// Toutes enveloppées dans `Error::WithContext` avec l'identifiant du document et la
+    /// position de l'opération dans le lot, à partir de 1 (`ErrorContext::line`) :
+    /// * Celles de [`Collection::upsert`] et de [`Collection::upsert_with_payload`].
+    /// * `Error::DocumentNotFound` - Si [`Op::SetPayload`] vise un document absent.
+    /// * `Error::SchemaViolation` - Si une charge utile ne respecte pas le schéma.
+    /// * `Error::MemoryLimitExceeded` - Si les insertions dépassent la limite de mémoire
+    ///   de la base.
+    pub fn apply(&mut self, ops: Vec<Op>) -> Result<ApplyReport> {
+        self.logged(
+            OperationKind::Apply,
+            |collection| {
+                collection.write_blobs(|collection| {
+                    let prepared = collection.prepare_ops(ops)?;
+                    if collection.budget.0.is_none() && collection.dedup_threshold.is_none() {
+                        return collection.commit_ops(prepared);
+                    }
+                    let mut staged = collection.clone();
+                    staged.budget = BudgetSlot(collection.budget.0);
+                    let report = staged.commit_ops(prepared)?;
+                    *collection = staged;
+                    Ok(report)
+                })
+            },
+            |result| result.as_ref().ok().map(ApplyReport::items),
+        )
+    }
+
+    /// Valide chaque opération de `ops` sans modifier la collection.
+    fn prepare_ops(&self, ops: Vec<Op>) -> Result<Vec<Prepared>> {
+        let mut presence: HashMap<Uuid, Presence> = HashMap::new();
+        let mut stored = self.documents.len();
+        let mut locked = self.locked_dimension;
+        let mut prepared = Vec::with_capacity(ops.len());
+        for (index, op) in ops.into_iter().enumerate() {
+            let key = op.key();
+            let context = ErrorContext::new(Operation::Apply)
+                .key(key)
+                .line(index + 1);
+            let current = *presence.entry(key).or_insert_with(|| {
+                match (self.documents.contains_key(&key), self.pending.contains_key(&key)) {
+                    (true, _) => Presence::Stored,
+                    (false, true) => Presence::Pending,
+                    (false, false) => Presence::Absent,
+                }
+            });
+            let (next, op) = match op {
+                Op::Upsert {
+                    key,
+                    vector,
+                    payload,
+                } => {
+                    let dimension = vector.len();
+                    let step = (|| {
+                        if let Some(locked) = locked.filter(|_| self.locked_dimension.is_none()) {
+                            if self.config.dimension.is_none() && dimension != locked {
+                                return Err(Error::DimensionLocked {
+                                    key,
+                                    locked,
+                                    got: dimension,
+                                });
+                            }
+                        }
+                        let payload = payload
+                            .map(|payload| self.admit_payload(key, payload))
+                            .transpose()?;
+                        let vector = self.prepare_vector(key, vector)?;
+                        Ok(Prepared::Upsert {
+                            key,
+                            vector,
+                            dimension,
+                            payload,
+                        })
+                    })();
+                    let step = step.map_err(|e| e.with_context(context))?;
+                    if current != Presence::Stored {
+                        stored += 1;
+                    }
+                    if self.config.dimension.is_none() && locked.is_none() && stored == 1 {
+                        locked = Some(dimension);
+                    }
+                    (Presence::Stored, step)
+                }
+                Op::Delete { key } => {
+                    if current == Presence::Stored {
+                        stored -= 1;
+                    }
+                    (Presence::Absent, Prepared::Delete(key))
+                }
+                Op::SetPayload { key, payload } => {
+                    if current == Presence::Absent {
+                        return Err(Error::DocumentNotFound(key).with_context(context));
+                    }
+                    let (payload, coerced) = self
+                        .admit_payload(key, payload)
+                        .map_err(|e| e.with_context(context))?;
+                    (
+                        current,
+                        Prepared::SetPayload {
+                            key,
+                            payload,
+                            coerced,
+                        },
+                    )
+                }
+            };
+            presence.insert(key, next);
+            prepared.push(op);
+        }
+        Ok(prepared)
+    }
+
+    /// Applique des opérations validées par [`Collection::prepare_ops`] ; seules la
+    /// limite de mémoire de la base et la déduplication peuent encore en refuser une.
+    fn commit_ops(&mut self, prepared: Vec<Prepared>) -> Result<ApplyReport> {
+        let mut report = ApplyReport::default();
+        for (index, op) in prepared.into_iter().enumerate() {
+            let context = || ErrorContext::new(Operation::Apply).line(index + 1);
+            match op {
+                Prepared::Upsert {
+                    key,
+                    vector,
+                    dimension,
+                    payload,
+                } => {
+                    if !self.documents.contains_key(&key) {
+                        let screened = self.screen_duplicate(key, &vector);
+                        if screened.map_err(|e| e.with_context(context().key(key)))?.is_some() {
+                            report.skipped += 1;
+                            continue;
+                        }
+                    }
+                    self.store_vector(key, vector)
+                        .map_err(|e| e.with_context(context().key(key)))?;
+                    self.lock_dimension(dimension);
+                    if let Some((payload, coerced)) = payload {
+                        self.put_payload(key, payload);
+                        self.schema_warnings += coerced;
+                    }
+                    report.upserted += 1;
+                }
+                Prepared::Delete(key) => match self.remove_document(&key) {
+                    true => report.deleted += 1,
+                    false => report.not_found += 1,
+                },
+                Prepared::SetPayload {
+                    key,
+                    payload,
+                    coerced,
+                } => {
+                    match (self.pending.contains_key(&key), self.documents.contains_key(&key)) {
+                        (true, _) => self.put_pending(key, payload),
+                        (false, true) => self.put_payload(key, payload),
+                        (false, false) => {
+                            report.skipped += 1;
+                            continue;
+                        }
+                    }
+                    self.schema_warnings += coerced;
+                    report.payloads_set += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::blob::{BlobOffload, BlobStore, FileBlobStore};
+    use crate::database::BaseDeDonnees;
+    use crate::payload::Value;
+
+    fn body(text: &str) -> Payload {
+        Payload::from([("body".to_string(), Value::String(text.to_string()))])
+    }
+
+    fn with_blobs(key: Uuid) -> (Collection, Arc<FileBlobStore>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("embeddingproject-apply-{}", Uuid::new_v4()));
+        let store = Arc::new(FileBlobStore::new(&dir));
+        let mut collection = Collection::new();
+        let offload = BlobOffload {
+            fields: vec!["body".to_string()],
+            threshold: 8,
+        };
+        collection.set_blob_store(store.clone(), offload);
+        collection.upsert_with_payload(key, vec![1.0, 0.0], body("version d'origine")).unwrap();
+        (collection, store, dir)
+    }
+
+    #[test]
+    fn rejected_batch_writes_no_blob() {
+        let key = Uuid::new_v4();
+        let (mut collection, store, dir) = with_blobs(key);
+        let added = Uuid::new_v4();
+        let ops = vec![
+            Op::SetPayload {
+                key,
+                payload: body("remplacée par le lot"),
+            },
+            Op::Upsert {
+                key: added,
+                vector: vec![0.0, 1.0],
+                payload: Some(body("nouvelle et longue")),
+            },
+            Op::Upsert {
+                key: Uuid::new_v4(),
+                vector: vec![1.0],
+                payload: None,
+            },
+        ];
+        let error = collection.apply(ops).unwrap_err();
+        assert_eq!(error.context().and_then(|context| context.line), Some(3));
+
+        assert_eq!(collection.payload_with_blobs(&key).unwrap(), Some(body("version d'origine")));
+        assert_eq!(store.keys().unwrap(), vec![(key, "body".to_string())]);
+        assert!(!store.path(added, "body").exists());
+        assert!(collection.check_blobs().unwrap().is_consistent());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn batch_over_memory_limit_keeps_released_blobs() {
+        let key = Uuid::new_v4();
+        let (collection, store, dir) = with_blobs(key);
+        let mut db = BaseDeDonnees::new();
+        db.replace("c".to_string(), collection);
+        db.set_memory_limit(Some(db.memory_usage().total() + 64));
+        let ops = vec![
+            Op::SetPayload {
+                key,
+                payload: body("court"),
+            },
+            Op::Upsert {
+                key: Uuid::new_v4(),
+                vector: vec![0.5; 2],
+                payload: None,
+            },
+            Op::Upsert {
+                key: Uuid::new_v4(),
+                vector: vec![0.5; 2],
+                payload: Some(body(&"x".repeat(256))),
+            },
+            Op::Upsert {
+                key: Uuid::new_v4(),
+                vector: vec![0.5; 2],
+                payload: None,
+            },
+        ];
+        let collection = db.get_mut("c").unwrap();
+        let error = collection.apply(ops).unwrap_err();
+        assert!(matches!(error.root(), Error::MemoryLimitExceeded { .. }));
+
+        assert_eq!(collection.payload_with_blobs(&key).unwrap(), Some(body("version d'origine")));
+        assert_eq!(store.keys().unwrap(), vec![(key, "body".to_string())]);
+        assert!(collection.check_blobs().unwrap().is_consistent());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn accepted_batch_publishes_final_blobs() {
+        let key = Uuid::new_v4();
+        let (mut collection, store, dir) = with_blobs(key);
+        let ops = vec![
+            Op::SetPayload {
+                key,
+                payload: body("court"),
+            },
+            Op::SetPayload {
+                key,
+                payload: body("de nouveau longue"),
+            },
+        ];
+        collection.apply(ops).unwrap();
+
+        assert_eq!(collection.payload_with_blobs(&key).unwrap(), Some(body("de nouveau longue")));
+        assert_eq!(store.keys().unwrap(), vec![(key, "body".to_string())]);
+        assert!(collection.check_blobs().unwrap().is_consistent());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,238 @@
+//! Contrôle de la norme des vecteurs à l'insertion : vecteurs déclarés unitaires et
+//! plage de normes admise.
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::similarity::{self, Metric};
+
+/// Écart maximal par défaut entre la norme d'un vecteur déclaré unitaire et 1.
+pub const DEFAULT_UNIT_NORM_TOLERANCE: f32 = 1e-3;
+
+impl Collection {
+    /// Fixe l'écart maximal entre 1 et la norme des vecteurs insérés dans une collection
+    /// qui déclare des vecteurs unitaires
+    /// ([`CollectionConfigBuilder::vectors_are_normalized`](crate::CollectionConfigBuilder::vectors_are_normalized)),
+    /// par défaut [`DEFAULT_UNIT_NORM_TOLERANCE`]. Les documents présents ne sont pas
+    /// vérifiés de nouveau.
+    ///
+    /// # Arguments
+    /// * `tolerance` - Écart maximal, positif.
+    pub fn with_unit_norm_tolerance(mut self, tolerance: f32) -> Self {
+        self.unit_tolerance = tolerance;
+        self
+    }
+
+    /// Retourne l'écart maximal entre 1 et la norme des vecteurs déclarés unitaires.
+    pub fn unit_norm_tolerance(&self) -> f32 {
+        self.unit_tolerance
+    }
+
+    /// Exécute `op` sans vérifier la norme des vecteurs insérés, pour un import en masse
+    /// de vecteurs dont la source garantit déjà qu'ils sont unitaires.
+    ///
+    /// Rien ne rattrape un vecteur qui ne l'est pas : en similarité cosinus, ses scores
+    /// sont faux, sans erreur. Seul [`Collection::check_invariants`] le signale ensuite.
+    /// Sans déclaration de vecteurs unitaires, `op` s'exécute normalement.
+    ///
+    /// # Arguments
+    /// * `op` - Opération d'écriture à exécuter sans vérification.
+    ///
+    /// # Retourne
+    /// * T - Le résultat de `op`.
+    pub fn assume_normalized<T>(&mut self, op: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.assume_unit, true);
+        let result = op(self);
+        self.assume_unit = previous;
+        result
+    }
+
+    /// Mesure utilisée pour calculer les scores : un produit scalaire, égal à la
+    /// similarité cosinus sans la division par les normes, si la collection déclare des
+    /// vecteurs unitaires et ne pondère pas ses dimensions.
+    pub(crate) fn scoring_metric(&self) -> Metric {
+        match self.config.metric {
+            Metric::Cosine if self.config.unit_vectors && self.config.weights.is_none() => {
+                Metric::Dot
+            }
+            metric => metric,
+        }
+    }
+
+    /// Indique si `vector` s'écarte de la norme 1 de plus que la tolérance.
+    pub(crate) fn off_unit(&self, vector: &[f32]) -> bool {
+        (similarity::norm(vector) - 1.0).abs() > self.unit_tolerance
+    }
+
+    /// Vérifie la norme d'un vecteur à stocker si la collection déclare des vecteurs
+    /// unitaires, sauf dans [`Collection::assume_normalized`].
+    pub(crate) fn check_unit(&self, vector: &[f32], key: Uuid) -> Result<()> {
+        if !self.config.unit_vectors || self.assume_unit || !self.off_unit(vector) {
+            return Ok(());
+        }
+        Err(Error::NotNormalized {
+            key,
+            norm: similarity::norm(vector),
+            tolerance: self.unit_tolerance,
+        })
+    }
+
+    /// Vérifie la norme d'un vecteur fourni si la collection borne les normes
+    /// ([`CollectionConfigBuilder::norm_range`](crate::CollectionConfigBuilder::norm_range)).
+    pub(crate) fn check_norm_range(&self, vector: &[f32], key: Uuid) -> Result<()> {
+        let Some(range) = self.config.norm_range else {
+            return Ok(());
+        };
+        let norm = similarity::norm_f64(vector);
+        if f64::from(range.min) <= norm && norm <= f64::from(range.max) {
+            return Ok(());
+        }
+        Err(Error::NormOutOfRange {
+            key,
+            norm,
+            min: range.min,
+            max: range.max,
+        })
+    }
+}
+
+/// Ramène une requête de norme non nulle à une norme de 1, une seule fois par recherche,
+/// lorsque la similarité cosinus est calculée par un produit scalaire.
+pub(crate) fn unit_query(mut request: Vec<f32>) -> Vec<f32> {
+    let norm = similarity::norm_f64(&request);
+    if norm > 0.0 {
+        (request.iter_mut()).for_each(|x| *x = (f64::from(*x) / norm) as f32);
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+
+    fn unit_collection() -> Collection {
+        let config = CollectionConfig::builder()
+            .metric(Metric::Cosine)
+            .vectors_are_normalized(true)
+            .build()
+            .unwrap();
+        Collection::from_config(config)
+    }
+
+    #[test]
+    fn default_keeps_the_unit_tolerance() {
+        assert_eq!(Collection::default().unit_norm_tolerance(), DEFAULT_UNIT_NORM_TOLERANCE);
+    }
+
+    #[test]
+    fn rejects_vectors_off_the_unit_sphere() {
+        let mut collection = unit_collection();
+        let key = Uuid::new_v4();
+        assert!(collection.upsert(key, vec![0.6, 0.8]).is_ok());
+        let error = collection.upsert(Uuid::new_v4(), vec![1.0, 1.0]).unwrap_err();
+        assert!(matches!(error.root(), Error::NotNormalized { .. }));
+        assert_eq!(collection.scoring_metric(), Metric::Dot);
+    }
+
+    #[test]
+    fn tolerance_is_configurable() {
+        let mut collection = unit_collection().with_unit_norm_tolerance(0.1);
+        assert!(collection.upsert(Uuid::new_v4(), vec![1.05, 0.0]).is_ok());
+        assert!(collection.upsert(Uuid::new_v4(), vec![1.2, 0.0]).is_err());
+    }
+
+    #[test]
+    fn assume_normalized_skips_the_check() {
+        let mut collection = unit_collection();
+        let key = Uuid::new_v4();
+        collection.assume_normalized(|collection| collection.upsert(key, vec![2.0, 0.0]).unwrap());
+        assert!(collection.upsert(Uuid::new_v4(), vec![2.0, 0.0]).is_err());
+        assert!(collection.check_invariants().is_err());
+    }
+
+    #[test]
+    fn dot_scores_match_cosine() {
+        let mut collection = unit_collection();
+        let key = Uuid::new_v4();
+        collection.upsert(key, vec![0.6, 0.8]).unwrap();
+        // La requête n'est pas unitaire : elle est ramenée à la norme 1.
+        let hits = collection.search([3.0, 4.0], 1).unwrap();
+        assert_eq!(hits[0].0, key);
+        assert!((hits[0].1 - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn patch_keeps_vectors_unit() {
+        let mut collection = unit_collection();
+        let key = Uuid::new_v4();
+        collection.upsert(key, vec![0.6, 0.8]).unwrap();
+
+        let error = collection.patch_vector(key, 0, &[1.0]).unwrap_err();
+        assert!(matches!(error.root(), Error::NotNormalized { .. }));
+        assert_eq!(collection.read(&key), Some(&vec![0.6, 0.8]));
+
+        collection.patch_vector(key, 0, &[0.8, 0.6]).unwrap();
+        assert_eq!(collection.read(&key), Some(&vec![0.8, 0.6]));
+        assert!(collection.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn patch_respects_the_norm_range() {
+        let config = CollectionConfig::builder().norm_range(0.5, 2.0).build().unwrap();
+        let mut collection = Collection::from_config(config);
+        let key = Uuid::new_v4();
+        collection.upsert(key, vec![1.0, 0.0]).unwrap();
+
+        let error = collection.patch_vector(key, 1, &[3.0]).unwrap_err();
+        assert!(matches!(error.root(), Error::NormOutOfRange { .. }));
+        assert_eq!(collection.read(&key), Some(&vec![1.0, 0.0]));
+        assert!(collection.patch_vector(key, 1, &[1.0]).is_ok());
+    }
+
+    #[test]
+    fn upserts_outside_the_norm_range_are_refused() {
+        let config = CollectionConfig::builder()
+            .norm_range(0.1, 100.0)
+            .build()
+            .unwrap();
+        let mut collection = Collection::from_config(config);
+        collection.upsert(Uuid::new_v4(), vec![3.0, 4.0]).unwrap();
+        for vector in [vec![3e20, 4e20], vec![f32::MIN_POSITIVE, 0.0]] {
+            let key = Uuid::new_v4();
+            let error = collection.upsert(key, vector).unwrap_err();
+            assert!(matches!(
+                error.root(),
+                Error::NormOutOfRange { key: k, min, max, .. } if *k == key && *min == 0.1 && *max == 100.0
+            ));
+        }
+        assert_eq!(collection.len(), 1);
+    }
+
+    #[test]
+    fn extreme_and_subnormal_vectors_rank_like_their_unscaled_copies() {
+        let base: Vec<[f32; 3]> = vec![[1.0, 0.2, 0.0], [0.5, 0.5, 0.5], [0.0, 1.0, 0.3], [0.9, -0.4, 0.1]];
+        let query = [1.0, 0.1, 0.05];
+        // 1e20 fait déborder les sommes de carrés en f32 ; 1e-40 rend les coordonnées
+        // sous-normales.
+        for scale in [1e20f32, 3e-40] {
+            for metric in [Metric::Cosine, Metric::Euclidean] {
+                let keys: Vec<Uuid> = (0..base.len() as u128).map(Uuid::from_u128).collect();
+                let (mut plain, mut scaled) = (
+                    Collection::new().with_metric(metric),
+                    Collection::new().with_metric(metric),
+                );
+                for (key, vector) in keys.iter().zip(&base) {
+                    plain.upsert(*key, *vector).unwrap();
+                    scaled.upsert(*key, vector.map(|x| x * scale)).unwrap();
+                }
+                let expected = plain.search(query, 4).unwrap();
+                let hits = scaled.search(query.map(|x| x * scale), 4).unwrap();
+                assert!(hits.iter().all(|(_, score)| score.is_finite()), "{:?} × {}", metric, scale);
+                let order = |hits: &[(Uuid, f32)]| hits.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+                assert_eq!(order(&hits), order(&expected), "{:?} × {}", metric, scale);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::collection::Collection;
+
+/// Résultat de [`Collection::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationReport {
+    /// Dimension de référence de la collection : celle de la majorité des documents, la
+    /// plus petite en cas d'égalité. `None` pour une collection vide.
+    pub dimension: Option<usize>,
+    /// Documents dont la dimension diffère de la dimension de référence, avec leur
+    /// dimension, triés par `Uuid` croissant.
+    pub mismatched: Vec<(Uuid, usize)>,
+}
+
+impl ValidationReport {
+    /// Indique si tous les documents ont la dimension de référence.
+    pub fn is_valid(&self) -> bool {
+        self.mismatched.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    /// Affiche la dimension de référence puis une ligne par document incohérent.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dimension {
+            None => writeln!(f, "collection vide")?,
+            Some(dimension) => writeln!(
+                f,
+                "dimension {} : {} document(s) incohérent(s)",
+                dimension,
+                self.mismatched.len()
+            )?,
+        }
+        for (key, dimension) in &self.mismatched {
+            writeln!(f, "  {} : dimension {}", key, dimension)?;
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Recherche les documents dont la dimension diffère de celle de la collection.
+    ///
+    /// De tels documents ne peuvent être insérés que par les API qui ne vérifient pas les
+    /// dimensions ; une recherche non stricte les ignore silencieusement, une recherche
+    /// stricte échoue (voir [`Collection::with_strict_dimensions`]).
+    ///
+    /// # Retourne
+    /// * ValidationReport - Dimension de référence et documents incohérents.
+    pub fn validate(&self) -> ValidationReport {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for vector in self.documents.values() {
+            *counts.entry(vector.len()).or_insert(0) += 1;
+        }
+        let dimension = counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+            .map(|(dimension, _)| dimension);
+        let mut mismatched: Vec<(Uuid, usize)> = self
+            .documents
+            .iter()
+            .filter(|(_, vector)| Some(vector.len()) != dimension)
+            .map(|(key, vector)| (*key, vector.len()))
+            .collect();
+        mismatched.sort_unstable();
+        ValidationReport {
+            dimension,
+            mismatched,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BaseDeDonnees;
+    use crate::error::Error;
+    use crate::similarity::Metric;
+    use crate::snapshot::{self, put_f32s, put_len, put_str, put_u32};
+
+    /// Sauvegarde au format 1, antérieur au verrouillage des dimensions, d'une collection
+    /// `docs` contenant `documents`.
+    fn legacy_snapshot(documents: &[(Uuid, Vec<f32>)]) -> Vec<u8> {
+        let mut out = snapshot::MAGIC.to_vec();
+        put_u32(&mut out, 1);
+        put_len(&mut out, 1);
+        put_str(&mut out, "docs");
+        out.push(snapshot::metric_code(Metric::Euclidean));
+        out.push(0);
+        // Ni projection, ni charge utile.
+        out.push(0);
+        put_len(&mut out, documents.len());
+        for (key, vector) in documents {
+            out.extend_from_slice(key.as_bytes());
+            put_len(&mut out, vector.len());
+            put_f32s(&mut out, vector);
+        }
+        put_len(&mut out, 0);
+        out
+    }
+
+    #[test]
+    fn legacy_mixed_dimensions_are_reported_and_refused_by_strict_search() {
+        let mut documents: Vec<(Uuid, Vec<f32>)> = (0..4)
+            .map(|i| (Uuid::new_v4(), vec![i as f32, 0.0, 1.0]))
+            .collect();
+        let odd = Uuid::new_v4();
+        documents.push((odd, vec![0.0, 0.0]));
+        let db = BaseDeDonnees::from_bytes(&legacy_snapshot(&documents)).unwrap();
+        let collection = db.get("docs").unwrap();
+
+        let report = collection.validate();
+        assert_eq!(report.dimension, Some(3));
+        assert_eq!(report.mismatched, [(odd, 2)]);
+        assert!(!report.is_valid());
+        assert!(report.to_string().contains(&odd.to_string()));
+
+        // Une recherche non stricte ignore le document incohérent...
+        assert!(!collection.strict_dimensions());
+        let hits = collection.search([0.0, 0.0, 1.0], 10).unwrap();
+        assert_eq!(hits.len(), 4);
+        // ... une recherche stricte le désigne.
+        let strict = collection.clone().with_strict_dimensions(true);
+        assert!(matches!(
+            strict.search([0.0, 0.0, 1.0], 10),
+            Err(Error::InconsistentDimension { key, expected: 3, got: 2 }) if key == odd
+        ));
+    }
+
+    #[test]
+    fn consistent_collections_are_valid() {
+        let mut collection = Collection::new();
+        assert_eq!(collection.validate(), ValidationReport::default());
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        let report = collection.validate();
+        assert_eq!(report.dimension, Some(2));
+        assert!(report.is_valid());
+    }
+}
@@ -0,0 +1,173 @@
+//! Stockage unique des vecteurs identiques d'une collection.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::sync::{Arc, Weak};
+
+use crate::collection::Collection;
+use crate::memory;
+
+/// Nombre d'empreintes suivies au-delà duquel les vecteurs libérés sont oubliés, en plus
+/// du double du nombre de documents.
+const PURGE_SLACK: usize = 64;
+
+/// Occupation du stockage des vecteurs d'une collection ([`Collection::storage_stats`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StorageStats {
+    /// Nombre de documents.
+    pub documents: usize,
+    /// Nombre de vecteurs distincts réellement stockés.
+    pub unique_vectors: usize,
+    /// Octets de coordonnées économisés par le partage des vecteurs identiques.
+    pub bytes_saved: usize,
+}
+
+/// Vecteurs stockés d'une collection en partage des vecteurs, indexés par l'empreinte de
+/// leurs bits.
+///
+/// Le pool ne garde qu'une référence faible : un vecteur est libéré dès que plus aucun
+/// document ne l'utilise, et son entrée est oubliée à la purge suivante.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VectorPool {
+    slots: HashMap<u64, Vec<Weak<Vec<f32>>>>,
+}
+
+impl VectorPool {
+    /// Retourne le vecteur stocké identique bit à bit à `vector`, ou stocke `vector`.
+    fn intern(&mut self, vector: Vec<f32>) -> Arc<Vec<f32>> {
+        let bucket = self.slots.entry(bits_hash(&vector)).or_default();
+        bucket.retain(|slot| slot.strong_count() > 0);
+        if let Some(shared) = bucket
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|shared| same_bits(shared, &vector))
+        {
+            return shared;
+        }
+        let shared = Arc::new(vector);
+        bucket.push(Arc::downgrade(&shared));
+        shared
+    }
+
+    /// Oublie les vecteurs libérés si le pool suit trop d'empreintes pour `documents`
+    /// documents.
+    fn purge(&mut self, documents: usize) {
+        if self.slots.len() > 2 * documents + PURGE_SLACK {
+            self.slots.retain(|_, bucket| {
+                bucket.retain(|slot| slot.strong_count() > 0);
+                !bucket.is_empty()
+            });
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// Oublie tous les vecteurs libérés et rend la place inutilisée de la table.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.slots.retain(|_, bucket| {
+            bucket.retain(|slot| slot.strong_count() > 0);
+            bucket.shrink_to_fit();
+            !bucket.is_empty()
+        });
+        self.slots.shrink_to_fit();
+    }
+
+    /// Octets réservés par la table sans être utilisés.
+    pub(crate) fn unused_bytes(&self) -> usize {
+        memory::unused_table_bytes(&self.slots)
+            + (self.slots.values())
+                .map(|bucket| (bucket.capacity() - bucket.len()) * mem::size_of::<Weak<Vec<f32>>>())
+                .sum::<usize>()
+    }
+}
+
+/// Empreinte des bits des coordonnées : `0.0` et `-0.0`, comme deux `NaN` de bits
+/// différents, sont des vecteurs distincts.
+fn bits_hash(vector: &[f32]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    vector.len().hash(&mut hasher);
+    for x in vector {
+        x.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Indique si deux vecteurs sont identiques bit à bit.
+pub(crate) fn same_bits(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.to_bits() == y.to_bits())
+}
+
+impl Collection {
+    /// Active le partage des vecteurs identiques entre documents.
+    ///
+    /// Un vecteur identique bit à bit à celui d'un autre document, par exemple celui d'un
+    /// paragraphe répété, n'est alors stocké qu'une fois ; il est libéré lorsque le
+    /// dernier document qui l'utilise est supprimé ou mis à jour, et
+    /// [`Collection::patch_vector`] copie un vecteur partagé avant de le modifier. Les
+    /// lectures et les recherches sont inchangées. La comparaison porte sur les bits :
+    /// `0.0` et `-0.0` ne sont pas partagés. Activer le partage l'applique aux documents
+    /// présents ; le désactiver rend à chaque document sa propre copie.
+    ///
+    /// [`Collection::memory_usage`] et la limite de mémoire de la base comptent toujours
+    /// chaque document avec sa copie ; l'économie réelle est donnée par
+    /// [`Collection::storage_stats`].
+    ///
+    /// # Arguments
+    /// * `shared` - Vrai pour partager les vecteurs identiques.
+    pub fn with_shared_vectors(mut self, shared: bool) -> Self {
+        self.config.shared_vectors = shared;
+        self.reshare_vectors();
+        self
+    }
+
+    /// Indique si la collection partage les vecteurs identiques entre documents.
+    pub fn shared_vectors(&self) -> bool {
+        self.config.shared_vectors
+    }
+
+    /// Retourne l'occupation du stockage des vecteurs.
+    ///
+    /// Le calcul parcourt tous les documents.
+    pub fn storage_stats(&self) -> StorageStats {
+        let mut seen = HashSet::with_capacity(self.documents.len());
+        let mut stats = StorageStats {
+            documents: self.documents.len(),
+            ..StorageStats::default()
+        };
+        for vector in self.documents.values() {
+            if seen.insert(Arc::as_ptr(vector)) {
+                stats.unique_vectors += 1;
+            } else {
+                stats.bytes_saved += mem::size_of_val(vector.as_slice());
+            }
+        }
+        stats
+    }
+
+    /// Prépare `vector` pour le stockage, en le partageant avec un vecteur identique si la
+    /// collection partage les vecteurs.
+    pub(crate) fn share_vector(&mut self, vector: Vec<f32>) -> Arc<Vec<f32>> {
+        if !self.config.shared_vectors {
+            return Arc::new(vector);
+        }
+        self.pool.purge(self.documents.len());
+        self.pool.intern(vector)
+    }
+
+    /// Reconstruit le partage des vecteurs de tous les documents selon la configuration.
+    pub(crate) fn reshare_vectors(&mut self) {
+        self.pool.clear();
+        let documents = mem::take(&mut self.documents);
+        self.documents = documents
+            .into_iter()
+            .map(|(key, vector)| {
+                let vector = Arc::unwrap_or_clone(vector);
+                (key, self.share_vector(vector))
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,302 @@
+//! Statistiques par dimension des vecteurs stockés, tenues à jour à chaque écriture.
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+
+/// Nombre minimal de retraits entre deux recalculs exacts des statistiques ; au-delà, un
+/// recalcul a lieu tous les [`Collection::len`] retraits, soit un coût amorti constant
+/// par retrait.
+const MIN_RECOMPUTE_INTERVAL: usize = 4096;
+
+/// Statistiques d'une dimension des vecteurs stockés, retournées par
+/// [`Collection::dimension_stats`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DimStats {
+    /// Moyenne des coordonnées.
+    pub mean: f32,
+    /// Écart type des coordonnées (de la population, divisé par le nombre de documents).
+    pub std: f32,
+    /// Plus petite coordonnée.
+    pub min: f32,
+    /// Plus grande coordonnée.
+    pub max: f32,
+}
+
+/// Sommes et carrés cumulés en `f64` des coordonnées des vecteurs stockés.
+///
+/// Retirer un vecteur soustrait ses coordonnées des sommes : les erreurs d'arrondi s'y
+/// accumulent, d'où un recalcul exact périodique. Un retrait peut aussi emporter un
+/// extrême ; les bornes sont alors recalculées au prochain recalcul exact, et d'ici là
+/// par un parcours des documents dans [`Collection::dimension_stats`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VectorStats {
+    count: usize,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+    min: Vec<f32>,
+    max: Vec<f32>,
+    /// Vrai si `min` et `max` peuvent contenir une valeur retirée.
+    stale_bounds: bool,
+    /// Vrai si les vecteurs comptés n'ont pas tous la même dimension.
+    mixed: bool,
+    /// Retraits depuis le dernier recalcul exact.
+    removals: usize,
+}
+
+impl VectorStats {
+    /// Calcule exactement les statistiques de `vectors`.
+    pub(crate) fn of<'a>(vectors: impl IntoIterator<Item = &'a [f32]>) -> Self {
+        let mut stats = VectorStats::default();
+        vectors.into_iter().for_each(|vector| stats.add(vector));
+        stats
+    }
+
+    /// Compte un vecteur ajouté.
+    pub(crate) fn add(&mut self, vector: &[f32]) {
+        if self.count == 0 {
+            *self = VectorStats {
+                sum: vec![0.0; vector.len()],
+                sum_sq: vec![0.0; vector.len()],
+                min: vec![f32::INFINITY; vector.len()],
+                max: vec![f32::NEG_INFINITY; vector.len()],
+                ..VectorStats::default()
+            };
+        }
+        self.count += 1;
+        self.mixed |= vector.len() != self.sum.len();
+        self.accumulate(0, vector, 1.0);
+    }
+
+    /// Retire un vecteur compté.
+    pub(crate) fn remove(&mut self, vector: &[f32]) {
+        self.count = self.count.saturating_sub(1);
+        if self.count == 0 {
+            *self = VectorStats::default();
+            return;
+        }
+        self.mixed |= vector.len() != self.sum.len();
+        self.accumulate(0, vector, -1.0);
+        self.stale_bounds |= self.touches_bounds(0, vector);
+        self.removals += 1;
+    }
+
+    /// Remplace les coordonnées `old` d'un vecteur compté par `new`, à partir de `offset`.
+    pub(crate) fn patch(&mut self, offset: usize, old: &[f32], new: &[f32]) {
+        self.accumulate(offset, old, -1.0);
+        self.stale_bounds |= self.touches_bounds(offset, old);
+        self.accumulate(offset, new, 1.0);
+        self.removals += 1;
+    }
+
+    /// Indique si assez de retraits ont eu lieu pour justifier un recalcul exact.
+    pub(crate) fn needs_recompute(&self) -> bool {
+        self.removals >= self.count.max(MIN_RECOMPUTE_INTERVAL)
+    }
+
+    /// Moyenne de chaque dimension, ou `None` sans vecteur ou si les dimensions diffèrent.
+    pub(crate) fn mean(&self) -> Option<Vec<f64>> {
+        if self.count == 0 || self.mixed {
+            return None;
+        }
+        let n = self.count as f64;
+        Some(self.sum.iter().map(|s| s / n).collect())
+    }
+
+    fn accumulate(&mut self, offset: usize, values: &[f32], sign: f64) {
+        let sums = self.sum.iter_mut().zip(&mut self.sum_sq).skip(offset);
+        for ((sum, sum_sq), x) in sums.zip(values) {
+            let x = *x as f64;
+            *sum += sign * x;
+            *sum_sq += sign * x * x;
+        }
+        if sign > 0.0 {
+            let bounds = self.min.iter_mut().zip(&mut self.max).skip(offset);
+            for ((min, max), x) in bounds.zip(values) {
+                *min = min.min(*x);
+                *max = max.max(*x);
+            }
+        }
+    }
+
+    /// Indique si une des coordonnées `values` atteint une borne de sa dimension.
+    fn touches_bounds(&self, offset: usize, values: &[f32]) -> bool {
+        let bounds = self.min.iter().zip(&self.max).skip(offset);
+        bounds
+            .zip(values)
+            .any(|((min, max), x)| x <= min || x >= max)
+    }
+}
+
+impl Collection {
+    /// Retourne la moyenne, l'écart type et les bornes de chaque dimension des vecteurs
+    /// stockés (après l'éventuelle projection), sans parcourir les documents.
+    ///
+    /// Les sommes sont tenues à jour à chaque écriture et recalculées exactement de temps
+    /// en temps ; après la suppression d'un document qui portait un extrême, les bornes
+    /// sont recalculées par un parcours des documents jusqu'au prochain recalcul exact.
+    ///
+    /// # Retourne
+    /// * Vec<DimStats> - Une entrée par dimension, ou aucune si la collection est vide ou
+    ///   si ses vecteurs n'ont pas tous la même dimension.
+    pub fn dimension_stats(&self) -> Vec<DimStats> {
+        let stats = &self.stats;
+        let Some(mean) = stats.mean() else {
+            return Vec::new();
+        };
+        let mut min = stats.min.clone();
+        let mut max = stats.max.clone();
+        if stats.stale_bounds {
+            min.fill(f32::INFINITY);
+            max.fill(f32::NEG_INFINITY);
+            for vector in self.documents.values() {
+                for ((min, max), x) in min.iter_mut().zip(&mut max).zip(vector.iter()) {
+                    *min = min.min(*x);
+                    *max = max.max(*x);
+                }
+            }
+        }
+        let n = stats.count as f64;
+        mean.iter()
+            .zip(&stats.sum_sq)
+            .zip(min.iter().zip(&max))
+            .map(|((mean, sum_sq), (min, max))| DimStats {
+                mean: *mean as f32,
+                std: (sum_sq / n - mean * mean).max(0.0).sqrt() as f32,
+                min: *min,
+                max: *max,
+            })
+            .collect()
+    }
+
+    /// Recalcule exactement les statistiques par dimension à partir des documents.
+    pub(crate) fn recompute_stats(&mut self) {
+        self.stats = VectorStats::of(self.documents.values().map(|vector| vector.as_slice()));
+    }
+
+    /// Retire des statistiques le vecteur d'un document déjà retiré des documents, avec un
+    /// recalcul exact lorsqu'il est dû.
+    pub(crate) fn forget_stats(&mut self, vector: &[f32]) {
+        self.stats.remove(vector);
+        if self.stats.needs_recompute() {
+            self.recompute_stats();
+        }
+    }
+
+    /// Vérifie que les statistiques tenues à jour correspondent à un recalcul exact.
+    pub(crate) fn check_stats(&self) -> Result<()> {
+        let exact = VectorStats::of(self.documents.values().map(|vector| vector.as_slice()));
+        let differs = |a: f64, b: f64| (a - b).abs() > 1e-6 * (1.0 + b.abs());
+        let drifted = self.stats.count != exact.count
+            || self.stats.mixed != exact.mixed
+            || self.stats.sum.len() != exact.sum.len()
+            || self
+                .stats
+                .sum
+                .iter()
+                .zip(&exact.sum)
+                .any(|(a, b)| differs(*a, *b))
+            || (self.stats.sum_sq.iter())
+                .zip(&exact.sum_sq)
+                .any(|(a, b)| differs(*a, *b));
+        if drifted {
+            return Err(Error::InvariantViolation(format!(
+                "statistiques par dimension comptant {} documents au lieu de {}, ou \
+                 s'écartant d'un recalcul exact",
+                self.stats.count, exact.count
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::VectorGenerator;
+    use uuid::Uuid;
+
+    /// Statistiques calculées par un parcours complet des documents.
+    fn from_scratch(collection: &Collection) -> Vec<DimStats> {
+        let vectors: Vec<&[f32]> = collection.documents.values().map(|v| v.as_slice()).collect();
+        let n = vectors.len() as f64;
+        (0..vectors.first().map_or(0, |v| v.len()))
+            .map(|d| {
+                let values = || vectors.iter().map(|v| v[d]);
+                let mean = values().map(f64::from).sum::<f64>() / n;
+                let variance = values().map(|x| (f64::from(x) - mean).powi(2)).sum::<f64>() / n;
+                DimStats {
+                    mean: mean as f32,
+                    std: variance.sqrt() as f32,
+                    min: values().fold(f32::INFINITY, f32::min),
+                    max: values().fold(f32::NEG_INFINITY, f32::max),
+                }
+            })
+            .collect()
+    }
+
+    fn assert_close(collection: &Collection, step: usize) {
+        let (incremental, exact) = (collection.dimension_stats(), from_scratch(collection));
+        assert_eq!(incremental.len(), exact.len(), "étape {}", step);
+        for (a, b) in incremental.iter().zip(&exact) {
+            assert!((a.mean - b.mean).abs() <= 1e-4, "étape {} : {:?} {:?}", step, a, b);
+            assert!((a.std - b.std).abs() <= 1e-3, "étape {} : {:?} {:?}", step, a, b);
+            assert_eq!((a.min, a.max), (b.min, b.max), "étape {}", step);
+        }
+    }
+
+    #[test]
+    fn incremental_stats_match_a_full_scan() {
+        let mut generator = VectorGenerator::new(5, 176);
+        let mut collection = Collection::new();
+        let mut keys: Vec<Uuid> = Vec::new();
+        for step in 0..3000 {
+            // Tirage déterministe de l'opération à partir d'une coordonnée générée.
+            let choice = (generator.vector()[0].abs() * 1000.0) as usize % 10;
+            match choice {
+                0..=4 => {
+                    let key = generator.uuid();
+                    collection.upsert(key, generator.vector()).unwrap();
+                    keys.push(key);
+                }
+                5 | 6 if !keys.is_empty() => {
+                    let key = keys[step % keys.len()];
+                    collection.upsert(key, generator.vector()).unwrap();
+                }
+                7 | 8 if !keys.is_empty() => {
+                    let key = keys.swap_remove(step % keys.len());
+                    collection.delete(&key);
+                }
+                9 if !keys.is_empty() && step % 500 != 9 => {
+                    let key = keys[step % keys.len()];
+                    collection.patch_vector(key, 1, &generator.vector()[..3]).unwrap();
+                }
+                _ if step % 500 == 9 => {
+                    collection.clear(false);
+                    keys.clear();
+                }
+                _ => {}
+            }
+            if step % 25 == 0 {
+                assert_close(&collection, step);
+            }
+            collection.check_stats().unwrap();
+        }
+        assert_close(&collection, 3000);
+    }
+
+    #[test]
+    fn removing_an_extreme_recomputes_the_bounds() {
+        let mut collection = Collection::new();
+        let (low, high) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert(low, [-5.0, 0.0]).unwrap();
+        collection.upsert(high, [5.0, 1.0]).unwrap();
+        collection.upsert(Uuid::new_v4(), [1.0, 2.0]).unwrap();
+        collection.delete(&high);
+        let stats = collection.dimension_stats();
+        assert_eq!((stats[0].min, stats[0].max), (-5.0, 1.0));
+        assert_eq!((stats[0].mean, stats[1].mean), (-2.0, 1.0));
+        assert_eq!(stats[0].std, 3.0);
+        collection.clear(false);
+        assert!(collection.dimension_stats().is_empty());
+    }
+}
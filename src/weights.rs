@@ -0,0 +1,326 @@
+//! Poids des dimensions dans le calcul des scores d'une collection.
+
+use crate::collection::Collection;
+use crate::error::{Error, Result};
+use crate::search::SearchParams;
+use crate::similarity::Scorer;
+
+/// Poids validés des dimensions d'une configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Weights(pub(crate) Vec<f32>);
+
+// Les poids sont finis : l'égalité des `f32` est alors une relation d'équivalence.
+impl Eq for Weights {}
+
+/// Vérifie que des poids de dimensions sont utilisables.
+pub(crate) fn check(weights: &[f32]) -> Result<()> {
+    if weights.is_empty() {
+        return Err(Error::InvalidConfig(
+            "les poids des dimensions ne peuvent pas être vides".to_string(),
+        ));
+    }
+    if let Some((i, w)) = (weights.iter().enumerate()).find(|(_, w)| !w.is_finite() || **w < 0.0) {
+        return Err(Error::InvalidConfig(format!(
+            "le poids de la dimension {} vaut {} ; il doit être fini et positif ou nul",
+            i, w
+        )));
+    }
+    Ok(())
+}
+
+/// Retourne `weights`, ou des poids unitaires sans pondération, annulés sur les
+/// dimensions de `mask` : les poids d'une recherche qui masque ces dimensions.
+///
+/// # Erreurs
+/// * `Error::RangeOutOfBounds` - Pour la première dimension de `mask` hors de
+///   `dimension`.
+pub(crate) fn masked(weights: Option<&[f32]>, mask: &[usize], dimension: usize) -> Result<Vec<f32>> {
+    let mut masked = weights.map_or_else(|| vec![1.0; dimension], <[f32]>::to_vec);
+    for &index in mask {
+        *masked.get_mut(index).ok_or(Error::RangeOutOfBounds {
+            offset: index,
+            len: 1,
+            dimension,
+        })? = 0.0;
+    }
+    Ok(masked)
+}
+
+impl Collection {
+    /// Retourne les poids des dimensions dans le calcul des scores, s'il y en a (voir
+    /// [`CollectionConfigBuilder::dimension_weights`](crate::CollectionConfigBuilder::dimension_weights)).
+    pub fn dimension_weights(&self) -> Option<&[f32]> {
+        self.config.dimension_weights()
+    }
+
+    /// Remplace les poids des dimensions, ou les retire avec `None`, sans modifier les
+    /// documents.
+    ///
+    /// Les scores calculés sous les anciens poids ne valent plus : la calibration
+    /// éventuelle de la collection ([`Collection::fit_calibration`]) est retirée, et
+    /// doit être ajustée de nouveau.
+    ///
+    /// # Arguments
+    /// * `weights` - Nouveaux poids, finis et positifs ou nuls, un par dimension des
+    ///   vecteurs stockés.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si les poids sont invalides.
+    /// * `Error::DimensionMismatch` - Si le nombre de poids n'est pas la dimension des
+    ///   vecteurs stockés ; les poids précédents sont alors conservés.
+    pub fn set_dimension_weights(&mut self, weights: Option<Vec<f32>>) -> Result<()> {
+        if let Some(weights) = &weights {
+            check(weights)?;
+            let stored = match &self.projection {
+                Some(projection) => Some(projection.output_dim()),
+                None => self.dimension(),
+            };
+            let mismatch = stored
+                .filter(|dimension| *dimension != weights.len())
+                .or_else(|| {
+                    (self.documents.values())
+                        .map(|vector| vector.len())
+                        .find(|dimension| *dimension != weights.len())
+                });
+            if let Some(dimension) = mismatch {
+                return Err(Error::DimensionMismatch {
+                    expected: dimension,
+                    got: weights.len(),
+                });
+            }
+        }
+        self.config.weights = weights.map(Weights);
+        self.calibration = None;
+        Ok(())
+    }
+
+    /// Mesure et poids avec lesquels la collection calcule ses scores.
+    pub(crate) fn scorer(&self) -> Scorer<'_> {
+        Scorer {
+            metric: self.scoring_metric(),
+            weights: self.dimension_weights(),
+        }
+    }
+
+    /// Retourne les poids d'une recherche dont les paramètres masquent des dimensions
+    /// ([`SearchParams::dimension_mask`]), pour des vecteurs de dimension `dimension` ;
+    /// `None` sans masque.
+    ///
+    /// # Erreurs
+    /// * `Error::RangeOutOfBounds` - Si une dimension masquée est hors de `dimension`.
+    pub(crate) fn masked_weights(
+        &self,
+        params: &SearchParams,
+        dimension: usize,
+    ) -> Result<Option<Vec<f32>>> {
+        (params.dimension_mask.as_deref())
+            .map(|mask| masked(self.dimension_weights(), mask, dimension))
+            .transpose()
+    }
+
+    /// Mesure et poids d'une recherche : ceux de [`Collection::scorer`], ou les poids
+    /// masqués de [`Collection::masked_weights`]. Les vecteurs unitaires ne le sont plus
+    /// une fois masqués : la similarité cosinus est alors calculée avec leurs normes
+    /// masquées, et non ramenée à un produit scalaire.
+    pub(crate) fn masked_scorer<'a>(&'a self, masked: Option<&'a [f32]>) -> Scorer<'a> {
+        match masked {
+            Some(weights) => Scorer {
+                metric: self.config.metric,
+                weights: Some(weights),
+            },
+            None => self.scorer(),
+        }
+    }
+
+    /// Vérifie qu'un vecteur à stocker ou une requête, après l'éventuelle projection, a
+    /// autant de coordonnées que de poids.
+    pub(crate) fn check_weighted_dimension(&self, got: usize) -> Result<()> {
+        match self.dimension_weights() {
+            Some(weights) if weights.len() != got => Err(Error::DimensionMismatch {
+                expected: weights.len(),
+                got,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionConfig;
+    use crate::database::BaseDeDonnees;
+    use crate::sharded::ShardedCollection;
+    use crate::similarity::Metric;
+    use crate::store::{VectorStore, VectorStoreMut};
+    use crate::synthetic::VectorGenerator;
+    use uuid::Uuid;
+
+    const WEIGHTS: [f32; 4] = [4.0, 0.25, 1.0, 9.0];
+
+    /// Multiplie chaque coordonnée par le facteur qui reproduit la pondération de
+    /// `metric` sans poids.
+    fn scaled(metric: Metric, vector: &[f32]) -> Vec<f32> {
+        (vector.iter().zip(WEIGHTS))
+            .map(|(x, w)| match metric {
+                Metric::Manhattan => x * w,
+                _ => x * w.sqrt(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weighted_scores_equal_those_of_prescaled_vectors() {
+        for metric in [Metric::Cosine, Metric::Dot, Metric::Euclidean, Metric::Manhattan] {
+            let config = CollectionConfig::builder()
+                .metric(metric)
+                .dimension_weights(WEIGHTS.to_vec())
+                .build()
+                .unwrap();
+            let mut weighted = Collection::from_config(config);
+            let mut prescaled = Collection::new().with_metric(metric);
+            let mut generator = VectorGenerator::new(4, 181);
+            for _ in 0..300 {
+                let (key, vector) = (generator.uuid(), generator.vector());
+                prescaled.upsert(key, scaled(metric, &vector)).unwrap();
+                weighted.upsert(key, vector).unwrap();
+            }
+            for _ in 0..10 {
+                let query = generator.vector();
+                let expected = prescaled.search(scaled(metric, &query), 10).unwrap();
+                let got = weighted.search(&query, 10).unwrap();
+                let keys = |hits: &[(Uuid, f32)]| -> Vec<Uuid> { hits.iter().map(|(k, _)| *k).collect() };
+                assert_eq!(keys(&got), keys(&expected), "{:?}", metric);
+                for ((_, a), (_, b)) in got.iter().zip(&expected) {
+                    assert!((a - b).abs() <= 1e-4 * b.abs().max(1.0), "{:?} : {} {}", metric, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn changing_the_weights_changes_the_scores_and_drops_the_calibration() {
+        let mut collection = Collection::new().with_metric(Metric::Dot);
+        let (x, y) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert(x, [1.0, 0.0, 0.0, 0.0]).unwrap();
+        collection.upsert(y, [0.0, 0.0, 0.0, 0.5]).unwrap();
+        let query = [1.0, 0.0, 0.0, 1.0];
+        assert_eq!(collection.search(query, 1).unwrap()[0].0, x);
+        let labeled: Vec<(Vec<f32>, Uuid, bool)> = vec![(query.to_vec(), x, true), (query.to_vec(), y, false)];
+        collection.fit_calibration(&labeled).unwrap();
+
+        collection.set_dimension_weights(Some(WEIGHTS.to_vec())).unwrap();
+        assert_eq!(collection.search(query, 2).unwrap(), [(y, 4.5), (x, 4.0)]);
+        assert!(collection.calibration().is_none());
+        for weights in [vec![1.0; 3], vec![1.0, -1.0, 1.0, 1.0], Vec::new()] {
+            assert!(collection.set_dimension_weights(Some(weights)).is_err());
+        }
+        assert_eq!(collection.dimension_weights(), Some(&WEIGHTS[..]));
+        collection.set_dimension_weights(None).unwrap();
+        assert_eq!(collection.search(query, 1).unwrap()[0], (x, 1.0));
+    }
+
+    #[test]
+    fn the_weights_are_saved_and_summarized() {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        let docs = db.get_mut("docs").unwrap();
+        docs.upsert(Uuid::new_v4(), [1.0; 4]).unwrap();
+        docs.set_dimension_weights(Some(WEIGHTS.to_vec())).unwrap();
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        assert_eq!(loaded.get("docs").unwrap().dimension_weights(), Some(&WEIGHTS[..]));
+        assert!(loaded.summary().contains(", dimensions pondérées"));
+    }
+
+    const MASK: [usize; 2] = [1, 3];
+
+    /// Copie de `vector` dont les dimensions de `MASK` valent 0.
+    fn zeroed(vector: &[f32]) -> Vec<f32> {
+        let mut vector = vector.to_vec();
+        MASK.iter().for_each(|&i| vector[i] = 0.0);
+        vector
+    }
+
+    #[test]
+    fn masked_scores_equal_those_of_zeroed_vectors() {
+        for metric in [Metric::Cosine, Metric::Dot, Metric::Euclidean, Metric::Manhattan] {
+            let mut masked = Collection::new().with_metric(metric);
+            let mut physical = Collection::new().with_metric(metric);
+            let mut sharded = ShardedCollection::new(3).unwrap().with_metric(metric);
+            let mut generator = VectorGenerator::new(4, 218);
+            for _ in 0..300 {
+                let (key, vector) = (generator.uuid(), generator.vector());
+                physical.upsert(key, zeroed(&vector)).unwrap();
+                VectorStoreMut::upsert(&mut sharded, key, vector.clone()).unwrap();
+                masked.upsert(key, vector).unwrap();
+            }
+            let params = SearchParams {
+                k: 10,
+                dimension_mask: Some(MASK.to_vec()),
+                ..Default::default()
+            };
+            for _ in 0..10 {
+                let query = generator.vector();
+                let expected = physical.search(zeroed(&query), 10).unwrap();
+                for got in [
+                    masked.search_with(&query, &params).unwrap().hits,
+                    VectorStore::search_with(&sharded, &query, &params).unwrap().hits,
+                ] {
+                    let keys = |hits: &[(Uuid, f32)]| -> Vec<Uuid> { hits.iter().map(|(k, _)| *k).collect() };
+                    assert_eq!(keys(&got), keys(&expected), "{:?}", metric);
+                    for ((_, a), (_, b)) in got.iter().zip(&expected) {
+                        assert!((a - b).abs() <= 1e-5 * b.abs().max(1.0), "{:?} : {} {}", metric, a, b);
+                    }
+                }
+            }
+            // Les vecteurs stockés ne sont pas modifiés.
+            let (key, _) = masked.search(generator.vector(), 1).unwrap()[0];
+            assert_ne!(physical.read(&key), masked.read(&key));
+        }
+    }
+
+    #[test]
+    fn the_default_mask_combines_with_the_weights() {
+        let config = CollectionConfig::builder()
+            .metric(Metric::Dot)
+            .dimension_weights(WEIGHTS.to_vec())
+            .build()
+            .unwrap();
+        let mut collection = Collection::from_config(config);
+        let (x, y) = (Uuid::new_v4(), Uuid::new_v4());
+        collection.upsert(x, [1.0, 0.0, 1.0, 0.0]).unwrap();
+        collection.upsert(y, [0.0, 1.0, 0.0, 1.0]).unwrap();
+        let query = [1.0; 4];
+        assert_eq!(collection.search(query, 2).unwrap(), [(y, 9.25), (x, 5.0)]);
+        collection.set_default_params(SearchParams {
+            dimension_mask: Some(MASK.to_vec()),
+            ..Default::default()
+        });
+        assert_eq!(collection.search(query, 2).unwrap(), [(x, 5.0), (y, 0.0)]);
+
+        let mut db = BaseDeDonnees::new();
+        db.replace("docs".to_string(), collection);
+        let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+        let loaded = loaded.get("docs").unwrap();
+        assert_eq!(loaded.default_params().dimension_mask.as_deref(), Some(&MASK[..]));
+        assert_eq!(loaded.search(query, 1).unwrap(), [(x, 5.0)]);
+    }
+
+    #[test]
+    fn out_of_range_mask_fails_before_the_scan() {
+        let mut collection = Collection::new();
+        collection.upsert(Uuid::new_v4(), [1.0; 4]).unwrap();
+        let params = SearchParams {
+            dimension_mask: Some(vec![0, 4]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            collection.search_with([1.0; 4], &params),
+            Err(Error::RangeOutOfBounds {
+                offset: 4,
+                dimension: 4,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,504 @@
+//! Espace de travail regroupant plusieurs bases nommées d'un même processus, entre
+//! lesquelles une collection peut être déplacée ou copiée sans passer par le disque.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::database::{self, BaseDeDonnees};
+use crate::error::{Error, Result};
+use crate::memory::MemoryUsage;
+use crate::shared::BaseDeDonneesPartagee;
+
+/// Extension des fichiers écrits par [`Workspace::save_all`].
+pub const WORKSPACE_FILE_EXTENSION: &str = "snap";
+
+/// Statistiques d'une base de l'espace de travail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseStats {
+    /// Nom de la base dans l'espace de travail.
+    pub name: String,
+    /// Nombre de collections, simples et partitionnées.
+    pub collections: usize,
+    /// Nombre de documents de toutes les collections.
+    pub documents: usize,
+    /// Mémoire occupée par toutes les collections ([`BaseDeDonnees::memory_usage`]).
+    pub memory: MemoryUsage,
+    /// Noms des collections en quarantaine
+    /// ([`BaseDeDonneesPartagee::quarantined`]), par ordre croissant.
+    pub quarantined: Vec<String>,
+}
+
+/// Bilan de [`Workspace::stats`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WorkspaceStats {
+    /// Statistiques de chaque base, par nom croissant.
+    pub databases: Vec<DatabaseStats>,
+}
+
+impl WorkspaceStats {
+    /// Retourne le nombre de collections de toutes les bases.
+    pub fn collections(&self) -> usize {
+        self.databases.iter().map(|stats| stats.collections).sum()
+    }
+
+    /// Retourne le nombre de documents de toutes les bases.
+    pub fn documents(&self) -> usize {
+        self.databases.iter().map(|stats| stats.documents).sum()
+    }
+
+    /// Retourne la mémoire occupée par toutes les bases.
+    pub fn memory(&self) -> MemoryUsage {
+        self.databases.iter().map(|stats| stats.memory).sum()
+    }
+}
+
+impl fmt::Display for WorkspaceStats {
+    /// Affiche une ligne par base puis le total :
+    /// `production : 3 collection(s), 12000 document(s), 5242880 octets`, suivie des
+    /// collections en quarantaine s'il y en a.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stats in &self.databases {
+            write!(
+                f,
+                "{} : {} collection(s), {} document(s), {} octets",
+                stats.name,
+                stats.collections,
+                stats.documents,
+                stats.memory.total()
+            )?;
+            if !stats.quarantined.is_empty() {
+                write!(f, ", en quarantaine : {}", stats.quarantined.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(
+            f,
+            "total : {} base(s), {} collection(s), {} document(s), {} octets",
+            self.databases.len(),
+            self.collections(),
+            self.documents(),
+            self.memory().total()
+        )
+    }
+}
+
+/// Ensemble de bases nommées d'un même processus, par exemple `"staging"` et
+/// `"production"`, chacune derrière sa poignée partagée.
+///
+/// Les bases restent utilisables directement par leurs poignées
+/// ([`Workspace::get`]) ; l'espace de travail ajoute les opérations qui en touchent
+/// plusieurs à la fois. Celles-ci prennent les verrous des bases concernées dans l'ordre
+/// de leurs noms, et toujours ensemble : une recherche sous verrou voit l'état d'avant ou
+/// celui d'après, jamais un état intermédiaire.
+#[derive(Clone, Default)]
+pub struct Workspace {
+    databases: BTreeMap<String, BaseDeDonneesPartagee>,
+}
+
+impl Workspace {
+    /// Crée un espace de travail vide.
+    pub fn new() -> Self {
+        Workspace::default()
+    }
+
+    /// Ajoute une base à l'espace de travail.
+    ///
+    /// # Arguments
+    /// * `name` - Nom de la base, qui sert aussi de nom de fichier à
+    ///   [`Workspace::save_all`].
+    /// * `bdd` - Poignée partagée vers la base ; ses clones restent utilisables.
+    ///
+    /// # Erreurs
+    /// * `Error::InvalidConfig` - Si `name` est vide, commence par un point, contient un
+    ///   séparateur de chemin ou est déjà pris, ou si `bdd` est déjà dans l'espace de
+    ///   travail sous un autre nom.
+    pub fn add(&mut self, name: String, bdd: BaseDeDonneesPartagee) -> Result<()> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(Error::InvalidConfig(format!(
+                "'{}' ne peut pas nommer une base : le nom sert de nom de fichier",
+                name
+            )));
+        }
+        if self.databases.contains_key(&name) {
+            return Err(Error::InvalidConfig(format!(
+                "la base '{}' existe déjà dans l'espace de travail",
+                name
+            )));
+        }
+        if let Some(other) = self.name_of(&bdd) {
+            return Err(Error::InvalidConfig(format!(
+                "cette base est déjà dans l'espace de travail sous le nom '{}'",
+                other
+            )));
+        }
+        self.databases.insert(name, bdd);
+        Ok(())
+    }
+
+    /// Retire la base `name` de l'espace de travail et retourne sa poignée.
+    pub fn remove(&mut self, name: &str) -> Option<BaseDeDonneesPartagee> {
+        self.databases.remove(name)
+    }
+
+    /// Retourne la poignée de la base `name`.
+    pub fn get(&self, name: &str) -> Option<&BaseDeDonneesPartagee> {
+        self.databases.get(name)
+    }
+
+    /// Retourne les noms des bases, par ordre croissant.
+    pub fn names(&self) -> Vec<&str> {
+        self.databases.keys().map(String::as_str).collect()
+    }
+
+    /// Déplace la collection `name` de la base `from` vers la base `to`, sans copier
+    /// ses vecteurs : seule la collection change de base.
+    ///
+    /// Les verrous en écriture des deux bases sont tenus ensemble le temps du
+    /// déplacement : une lecture qui tient les verrous des deux bases, comme
+    /// [`Workspace::stats`], trouve la collection dans exactement l'une d'elles, et une
+    /// recherche dans `to` qui suit un échec dans `from` la trouve. Les lectures relâchées
+    /// ([`BaseDeDonneesPartagee::search_relaxed`]) suivent leur propre borne de retard.
+    /// Les alias de `from` qui désignent la collection la suivent, comme dans une
+    /// restauration partielle ([`BaseDeDonnees::restore_backup`]).
+    ///
+    /// Le nom de la collection et de ses alias doit être libre dans `to` : comme pour
+    /// [`BaseDeDonnees::add`], un nom déjà pris par une collection, simple ou
+    /// partitionnée, ou par un alias n'est jamais écrasé.
+    ///
+    /// # Arguments
+    /// * `from` - Base qui contient la collection.
+    /// * `to` - Base de destination.
+    /// * `name` - Nom de la collection dans `from`, ou l'un de ses alias.
+    ///
+    /// # Erreurs
+    /// * `Error::DatabaseNotFound` - Si `from` ou `to` n'est pas dans l'espace de travail.
+    /// * `Error::InvalidConfig` - Si `from` et `to` désignent la même base.
+    /// * `Error::CollectionNotFound` - Si `from` ne contient pas la collection.
+    /// * `Error::CollectionExists` - Si le nom de la collection ou de l'un de ses alias
+    ///   est déjà pris dans `to`.
+    /// * `Error::MemoryLimitExceeded` - Si la collection ne tient pas dans la limite de
+    ///   mémoire de `to` ([`BaseDeDonnees::set_memory_limit`]).
+    ///
+    /// Aucune des deux bases n'est modifiée en cas d'erreur.
+    pub fn move_collection(&self, from: &str, to: &str, name: &str) -> Result<()> {
+        self.transfer(from, to, name, true)
+    }
+
+    /// Copie la collection `name` de la base `from` dans la base `to`, comme
+    /// [`Workspace::move_collection`] mais sans la retirer de `from`.
+    ///
+    /// La copie partage ses vecteurs avec l'original jusqu'à la première écriture dans
+    /// l'une des deux, qui la rend indépendante ; la limite de mémoire de `to` compte
+    /// néanmoins la copie entière.
+    ///
+    /// # Erreurs
+    /// * Celles de [`Workspace::move_collection`] ; `from` n'est jamais modifiée.
+    pub fn copy_collection(&self, from: &str, to: &str, name: &str) -> Result<()> {
+        self.transfer(from, to, name, false)
+    }
+
+    /// Retourne les statistiques de chaque base, lues ensemble sous leurs verrous en
+    /// lecture : une collection en cours de déplacement est comptée une seule fois.
+    pub fn stats(&self) -> WorkspaceStats {
+        let guards = self.read_all();
+        let databases = (guards.iter())
+            .map(|(name, bdd)| {
+                let documents = (bdd.collection_names().iter())
+                    .filter_map(|nom| bdd.store(nom))
+                    .map(|store| store.len())
+                    .sum();
+                DatabaseStats {
+                    name: name.to_string(),
+                    collections: bdd.collections.len() + bdd.sharded.len(),
+                    documents,
+                    memory: bdd.memory_usage(),
+                    // Le verrou déjà tenu a inspecté la base ; le reprendre attendrait
+                    // derrière un déplacement en attente de l'écriture.
+                    quarantined: self.databases[*name].quarantine.list().into_keys().collect(),
+                }
+            })
+            .collect();
+        WorkspaceStats { databases }
+    }
+
+    /// Enregistre chaque base dans `dir`, créé s'il n'existe pas, au format de
+    /// [`BaseDeDonnees::save`] : la base `production` va dans `production.snap`.
+    ///
+    /// Les collections de toutes les bases sont capturées ensemble sous leurs verrous en
+    /// lecture, libérés avant l'écriture des fichiers : les fichiers décrivent un même
+    /// instant de l'espace de travail, sans bloquer les écritures pendant
+    /// l'enregistrement. Chaque fichier passe par l'accès aux fichiers de sa base
+    /// ([`BaseDeDonnees::with_storage`]) et remplace le précédent d'un bloc ; un fichier
+    /// d'une base retirée de l'espace de travail n'est pas supprimé.
+    ///
+    /// # Arguments
+    /// * `dir` - Répertoire des fichiers.
+    ///
+    /// # Retourne
+    /// * Result<Vec<PathBuf>> - Chemins des fichiers écrits, par nom de base croissant.
+    ///
+    /// # Erreurs
+    /// * `Error::Io` - Si le répertoire ou un fichier ne peut pas être écrit ; les
+    ///   fichiers des bases précédentes restent écrits.
+    pub fn save_all(&self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let captured: Vec<(String, BaseDeDonnees)> = (self.read_all().iter())
+            .map(|(name, bdd)| (name.to_string(), capture(bdd)))
+            .collect();
+        fs::create_dir_all(dir)?;
+        let mut paths = Vec::with_capacity(captured.len());
+        for (name, bdd) in captured {
+            let path = dir.join(format!("{}.{}", name, WORKSPACE_FILE_EXTENSION));
+            bdd.save(&path)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Retourne le nom sous lequel l'espace de travail contient déjà la base `bdd`.
+    fn name_of(&self, bdd: &BaseDeDonneesPartagee) -> Option<&str> {
+        (self.databases.iter())
+            .find(|(_, other)| Arc::ptr_eq(&other.inner, &bdd.inner))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Retourne la poignée de la base `name`.
+    fn handle(&self, name: &str) -> Result<&BaseDeDonneesPartagee> {
+        (self.databases.get(name)).ok_or_else(|| Error::DatabaseNotFound(name.to_string()))
+    }
+
+    /// Prend les verrous en lecture de toutes les bases, dans l'ordre de leurs noms.
+    fn read_all(&self) -> Vec<(&str, RwLockReadGuard<'_, BaseDeDonnees>)> {
+        (self.databases.iter())
+            .map(|(name, bdd)| (name.as_str(), bdd.read()))
+            .collect()
+    }
+
+    /// Place la collection `name` de `from` dans `to`, et l'y retire si `remove`.
+    fn transfer(&self, from: &str, to: &str, name: &str, remove: bool) -> Result<()> {
+        let source = self.handle(from)?;
+        let target = self.handle(to)?;
+        if Arc::ptr_eq(&source.inner, &target.inner) {
+            return Err(Error::InvalidConfig(format!(
+                "la collection '{}' ne peut pas passer de la base '{}' à elle-même",
+                name, from
+            )));
+        }
+        let (mut source, mut target) = lock_pair(from, source, to, target);
+        let nom = database::resolve(&source.aliases, name).to_string();
+        let collection = source.collections.get(&nom).cloned();
+        let sharded = source.sharded.get(&nom).cloned();
+        let usage = match (&collection, &sharded) {
+            (Some(collection), _) => collection.memory_usage(),
+            (None, Some(sharded)) => sharded.memory_usage(),
+            (None, None) => return Err(Error::CollectionNotFound(name.to_string())),
+        };
+        let mut aliases: Vec<String> = (source.aliases.iter())
+            .filter(|(_, cible)| **cible == nom)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        aliases.sort_unstable();
+        let taken = |nom: &String| {
+            target.collections.contains_key(nom)
+                || target.sharded.contains_key(nom)
+                || target.aliases.contains_key(nom)
+        };
+        if let Some(nom) = std::iter::once(&nom).chain(&aliases).find(|nom| taken(nom)) {
+            return Err(Error::CollectionExists(nom.clone()));
+        }
+        if let Some(limit) = target.memory_limit {
+            let required = target.memory_usage().total() + usage.total();
+            if required > limit {
+                return Err(Error::MemoryLimitExceeded { limit, required });
+            }
+        }
+        if remove {
+            source.collections.remove(&nom);
+            source.sharded.remove(&nom);
+            for alias in &aliases {
+                source.aliases.remove(alias);
+            }
+            source.sequence += 1;
+        }
+        if let Some(collection) = collection {
+            target.collections.insert(nom.clone(), collection);
+        }
+        if let Some(sharded) = sharded {
+            target.sharded.insert(nom.clone(), sharded);
+        }
+        for alias in aliases {
+            target.aliases.insert(alias, nom.clone());
+        }
+        target.sequence += 1;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Workspace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Workspace")
+            .field("databases", &self.names())
+            .finish()
+    }
+}
+
+/// Prend ensemble les verrous en écriture de deux bases distinctes, dans l'ordre de
+/// leurs noms pour que deux transferts croisés ne s'attendent jamais l'un l'autre.
+fn lock_pair<'a>(
+    from: &str,
+    source: &'a BaseDeDonneesPartagee,
+    to: &str,
+    target: &'a BaseDeDonneesPartagee,
+) -> (
+    RwLockWriteGuard<'a, BaseDeDonnees>,
+    RwLockWriteGuard<'a, BaseDeDonnees>,
+) {
+    if from < to {
+        let source = source.write();
+        (source, target.write())
+    } else {
+        let target = target.write();
+        (source.write(), target)
+    }
+}
+
+/// Copie la base `bdd` sans copier ses collections, qui restent partagées, pour
+/// l'enregistrer après la libération de son verrou.
+fn capture(bdd: &BaseDeDonnees) -> BaseDeDonnees {
+    BaseDeDonnees {
+        collections: bdd.collections.clone(),
+        sharded: bdd.sharded.clone(),
+        aliases: bdd.aliases.clone(),
+        storage: bdd.storage.clone(),
+        ..BaseDeDonnees::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+
+    use uuid::Uuid;
+
+    use crate::collection::Collection;
+
+    /// Espace de travail `production` et `staging`, ce dernier contenant la collection
+    /// `docs` de 100 documents, avec l'alias `courante`.
+    fn workspace() -> Workspace {
+        let mut staging = BaseDeDonnees::new();
+        let mut docs = Collection::new();
+        for i in 0..100 {
+            docs.upsert(Uuid::new_v4(), [1.0, i as f32]).unwrap();
+        }
+        staging.replace("docs".to_string(), docs);
+        staging.set_alias("courante".to_string(), "docs").unwrap();
+        let mut workspace = Workspace::new();
+        let production = BaseDeDonneesPartagee::new(BaseDeDonnees::new());
+        workspace.add("production".to_string(), production).unwrap();
+        workspace
+            .add("staging".to_string(), BaseDeDonneesPartagee::new(staging))
+            .unwrap();
+        workspace
+    }
+
+    #[test]
+    fn moves_are_never_seen_half_done() {
+        let workspace = workspace();
+        let done = AtomicBool::new(false);
+        let checks = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        // Les verrous sont pris dans l'ordre des noms, comme ceux d'un
+                        // déplacement.
+                        let production = workspace.get("production").unwrap().read();
+                        let staging = workspace.get("staging").unwrap().read();
+                        let found = [&production, &staging]
+                            .iter()
+                            .filter(|bdd| bdd.search("courante", [1.0, 0.0], 1).is_ok())
+                            .count();
+                        assert_eq!(found, 1);
+                        drop((production, staging));
+                        assert_eq!(workspace.stats().documents(), 100);
+                        checks.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+            // Des allers-retours, jusqu'à ce que les lecteurs aient vérifié l'état
+            // assez souvent pendant les déplacements.
+            let mut moves = 0;
+            while moves < 200 || checks.load(Ordering::Relaxed) < 300 {
+                workspace.move_collection("staging", "production", "courante").unwrap();
+                workspace.move_collection("production", "staging", "courante").unwrap();
+                moves += 2;
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        let staging = workspace.get("staging").unwrap().read();
+        assert_eq!(staging.get("docs").unwrap().len(), 100);
+        assert_eq!(staging.aliases().len(), 1);
+    }
+
+    #[test]
+    fn collisions_at_the_destination_are_refused() {
+        let workspace = workspace();
+        let production = workspace.get("production").unwrap();
+        production.write().add("courante".to_string());
+        let sequence = production.read().sequence();
+        // L'alias de la collection est un nom déjà pris dans la destination.
+        assert!(matches!(
+            workspace.move_collection("staging", "production", "docs"),
+            Err(Error::CollectionExists(nom)) if nom == "courante"
+        ));
+        let other = self::workspace();
+        other.get("production").unwrap().write().add("docs".to_string());
+        assert!(matches!(
+            other.copy_collection("staging", "production", "docs"),
+            Err(Error::CollectionExists(nom)) if nom == "docs"
+        ));
+        assert_eq!(workspace.stats().documents(), 100);
+        assert!(workspace.get("staging").unwrap().read().get("docs").is_some());
+        assert_eq!(production.read().sequence(), sequence);
+
+        assert!(matches!(
+            workspace.move_collection("staging", "absente", "docs"),
+            Err(Error::DatabaseNotFound(_))
+        ));
+        assert!(matches!(
+            workspace.move_collection("staging", "staging", "docs"),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            workspace.move_collection("production", "staging", "absente"),
+            Err(Error::CollectionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn copies_are_independent_and_everything_is_saved() {
+        let workspace = workspace();
+        workspace.copy_collection("staging", "production", "docs").unwrap();
+        let production = workspace.get("production").unwrap();
+        production.write().get_mut("docs").unwrap().clear(false);
+        assert_eq!(workspace.get("staging").unwrap().read().get("docs").unwrap().len(), 100);
+        let stats = workspace.stats();
+        assert_eq!((stats.collections(), stats.documents()), (2, 100));
+        let display = stats.to_string();
+        assert!(display.starts_with("production : 1 collection(s), 0 document(s)"));
+        assert!(display.contains("\ntotal : 2 base(s), 2 collection(s), 100 document(s)"));
+
+        let dir = std::env::temp_dir().join(format!("embeddingproject-workspace-{}", Uuid::new_v4()));
+        let paths = workspace.save_all(&dir).unwrap();
+        assert_eq!(paths, [dir.join("production.snap"), dir.join("staging.snap")]);
+        let staging = BaseDeDonnees::load(&paths[1]).unwrap();
+        assert_eq!(staging.get("docs").unwrap().len(), 100);
+        assert_eq!(BaseDeDonnees::load(&paths[0]).unwrap().get("docs").unwrap().len(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
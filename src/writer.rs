@@ -0,0 +1,291 @@
+use std::mem;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use uuid::Uuid;
+
+use crate::database::BaseDeDonnees;
+use crate::error::{Error, ErrorContext, Operation, Result};
+use crate::poison::Quarantine;
+use crate::shared::BaseDeDonneesPartagee;
+use crate::store::VectorStoreMut;
+
+/// Nombre maximal d'opérations appliquées sous une même prise du verrou en écriture.
+const MAX_BATCH: usize = 256;
+
+/// Opération en attente dans la file d'une [`WriteHandle`].
+enum WriteOp {
+    Upsert(Uuid, Vec<f32>),
+    Delete(Uuid),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Bilan des écritures appliquées par le thread d'écriture depuis le précédent
+/// [`WriteHandle::flush`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WriteReport {
+    /// Nombre d'opérations appliquées.
+    pub applied: usize,
+    /// Opérations refusées, dans l'ordre, avec l'identifiant du document visé.
+    pub failures: Vec<(Uuid, Error)>,
+}
+
+/// Poignée d'écriture vers une collection d'une [`BaseDeDonneesPartagee`], créée par
+/// [`BaseDeDonneesPartagee::writer`].
+///
+/// Les opérations sont placées dans une file bornée et appliquées dans l'ordre par un
+/// unique thread d'écriture, qui prend le verrou en écriture une fois par lot plutôt
+/// qu'une fois par document. Lorsque la file est pleine, `enqueue_*` attend que le
+/// thread l'ait vidée. Cloner la poignée partage la même file ; le thread s'arrête après
+/// avoir appliqué les dernières opérations, lorsque tous les clones sont détruits.
+#[derive(Clone)]
+pub struct WriteHandle {
+    sender: SyncSender<WriteOp>,
+    report: Arc<Mutex<WriteReport>>,
+}
+
+impl WriteHandle {
+    /// Place l'insertion ou la mise à jour d'un document dans la file.
+    ///
+    /// Une erreur d'application, par exemple de dimension, n'apparaît que dans le bilan
+    /// de [`WriteHandle::flush`].
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant unique du document (Uuid).
+    /// * `vector` - Vecteur représentant le document.
+    pub fn enqueue_upsert(&self, key: Uuid, vector: Vec<f32>) {
+        self.send(WriteOp::Upsert(key, vector));
+    }
+
+    /// Place la suppression d'un document dans la file ; sans effet s'il n'existe pas.
+    ///
+    /// # Arguments
+    /// * `key` - Identifiant du document à supprimer.
+    pub fn enqueue_delete(&self, key: Uuid) {
+        self.send(WriteOp::Delete(key));
+    }
+
+    /// Attend que toutes les opérations placées dans la file avant l'appel, par cette
+    /// poignée ou ses clones, soient appliquées.
+    ///
+    /// Le thread d'écriture prend le verrou en écriture de la base : appeler `flush` en
+    /// tenant un verrou de la même base bloque indéfiniment.
+    ///
+    /// # Retourne
+    /// * WriteReport - Bilan des opérations appliquées depuis le précédent appel, par
+    ///   n'importe quel clone.
+    pub fn flush(&self) -> WriteReport {
+        let (done, wait) = mpsc::channel();
+        self.send(WriteOp::Flush(done));
+        let _ = wait.recv();
+        mem::take(&mut *self.report.lock().expect("bilan d'écriture empoisonné"))
+    }
+
+    fn send(&self, op: WriteOp) {
+        self.sender
+            .send(op)
+            .expect("le thread d'écriture s'est arrêté");
+    }
+}
+
+impl BaseDeDonneesPartagee {
+    /// Démarre un thread d'écriture dédié à la collection `nom` et retourne une poignée
+    /// pour lui transmettre des écritures.
+    ///
+    /// Utile lorsque de nombreux appelants écrivent en même temps : les recherches
+    /// n'attendent plus qu'un verrou par lot au lieu d'un verrou par document. La
+    /// collection est cherchée à chaque lot ; si elle n'existe pas, les opérations du lot
+    /// échouent avec `Error::CollectionNotFound`, et si elle est en quarantaine
+    /// ([`BaseDeDonneesPartagee::quarantined`]), avec `Error::CollectionPoisoned`.
+    ///
+    /// # Arguments
+    /// * `nom` - Nom de la collection, simple ou partitionnée, ou alias.
+    /// * `capacity` - Nombre d'opérations en attente au-delà duquel `enqueue_*` bloque.
+    ///
+    /// # Retourne
+    /// * WriteHandle - Poignée, clonable, vers la file du thread d'écriture.
+    pub fn writer(&self, nom: &str, capacity: usize) -> WriteHandle {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let report = Arc::new(Mutex::new(WriteReport::default()));
+        let db = self.clone();
+        let nom = nom.to_string();
+        let shared = Arc::clone(&report);
+        thread::spawn(move || run_writer(&db, &nom, &receiver, &shared));
+        WriteHandle { sender, report }
+    }
+}
+
+/// Applique les opérations reçues par lots, jusqu'à la destruction de toutes les poignées.
+fn run_writer(
+    db: &BaseDeDonneesPartagee,
+    nom: &str,
+    receiver: &Receiver<WriteOp>,
+    report: &Mutex<WriteReport>,
+) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH {
+            match receiver.try_recv() {
+                Ok(op) => batch.push(op),
+                Err(_) => break,
+            }
+        }
+        let mut applied = 0;
+        let mut failures = Vec::new();
+        let mut flushes = Vec::new();
+        {
+            let quarantine = &db.quarantine;
+            let mut db = db.write();
+            for op in batch {
+                let (key, operation, result) = match op {
+                    WriteOp::Flush(done) => {
+                        flushes.push(done);
+                        continue;
+                    }
+                    WriteOp::Upsert(key, vector) => (
+                        key,
+                        Operation::Upsert,
+                        apply(&mut db, quarantine, nom, |store| store.upsert(key, vector)),
+                    ),
+                    WriteOp::Delete(key) => (
+                        key,
+                        Operation::Delete,
+                        apply(&mut db, quarantine, nom, |store| {
+                            store.delete(&key);
+                            Ok(())
+                        }),
+                    ),
+                };
+                match result {
+                    Ok(()) => applied += 1,
+                    Err(error) => {
+                        let context = ErrorContext::new(operation).collection(nom).key(key);
+                        failures.push((key, error.with_context(context)));
+                    }
+                }
+            }
+        }
+        {
+            let mut report = report.lock().expect("bilan d'écriture empoisonné");
+            report.applied += applied;
+            report.failures.extend(failures);
+        }
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// Applique `op` à la collection `nom` de `db`, si elle n'est pas en quarantaine.
+fn apply(
+    db: &mut BaseDeDonnees,
+    quarantine: &Quarantine,
+    nom: &str,
+    op: impl FnOnce(&mut dyn VectorStoreMut) -> Result<()>,
+) -> Result<()> {
+    quarantine.check(db, nom)?;
+    let store = db
+        .store_mut(nom)
+        .ok_or_else(|| Error::CollectionNotFound(nom.to_string()))?;
+    op(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::Collection;
+
+    const THREADS: usize = 8;
+    const WRITES: usize = 300;
+
+    /// Opérations du thread `thread` sur ses propres documents : l'ordre entre threads
+    /// n'influe donc pas sur l'état final.
+    fn operations(thread: usize) -> Vec<(Uuid, Option<Vec<f32>>)> {
+        (0..WRITES)
+            .map(|i| {
+                let key = Uuid::from_u128((thread * WRITES + i % 50) as u128);
+                match i % 7 {
+                    3 => (key, None),
+                    _ => (key, Some(vec![thread as f32, i as f32, 1.0])),
+                }
+            })
+            .collect()
+    }
+
+    fn shared() -> BaseDeDonneesPartagee {
+        let mut db = BaseDeDonnees::new();
+        db.add("docs".to_string());
+        BaseDeDonneesPartagee::new(db)
+    }
+
+    #[test]
+    fn concurrent_enqueues_match_a_serial_application() {
+        let db = shared();
+        let writer = db.writer("docs", 16);
+        let threads: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    for (key, vector) in operations(thread) {
+                        match vector {
+                            Some(vector) => writer.enqueue_upsert(key, vector),
+                            None => writer.enqueue_delete(key),
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let report = writer.flush();
+        assert_eq!(report.applied, THREADS * WRITES);
+        assert!(report.failures.is_empty());
+
+        let mut serial = Collection::new();
+        for (key, vector) in (0..THREADS).flat_map(operations) {
+            match vector {
+                Some(vector) => assert!(serial.upsert(key, vector).unwrap()),
+                None => serial.delete(&key),
+            }
+        }
+        {
+            let db = db.read();
+            let docs = db.get("docs").unwrap();
+            assert_eq!(docs.len(), serial.len());
+            for (key, vector) in serial.documents.iter() {
+                assert_eq!(docs.documents.get(key), Some(vector));
+            }
+        }
+        // Le bilan repart de zéro après chaque flush.
+        assert_eq!(writer.flush(), WriteReport::default());
+    }
+
+    #[test]
+    fn failures_are_reported_with_their_document() {
+        let db = shared();
+        let writer = db.writer("docs", 1);
+        let (good, bad) = (Uuid::new_v4(), Uuid::new_v4());
+        writer.enqueue_upsert(good, vec![1.0, 2.0]);
+        writer.enqueue_upsert(bad, vec![1.0, 2.0, 3.0]);
+        writer.enqueue_delete(Uuid::new_v4());
+        let report = writer.flush();
+        assert_eq!(report.applied, 2);
+        let [(key, error)] = &report.failures[..] else {
+            panic!("{:?}", report.failures);
+        };
+        assert_eq!(*key, bad);
+        assert!(matches!(error.root(), Error::DimensionLocked { .. }));
+        assert_eq!(error.context().unwrap().key, Some(bad));
+
+        let missing = db.writer("absente", 4);
+        missing.enqueue_delete(good);
+        let report = missing.flush();
+        assert!(matches!(
+            report.failures[0].1.root(),
+            Error::CollectionNotFound(nom) if nom == "absente"
+        ));
+        assert_eq!(db.read().get("docs").unwrap().len(), 1);
+    }
+}
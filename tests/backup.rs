@@ -0,0 +1,156 @@
+//! Cycles de sauvegarde, de rotation et de restauration dans un répertoire temporaire.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use embeddingproject::{
+    list_backups, BaseDeDonnees, Collection, Error, Metric, RestoreOptions, BACKUP_MANIFEST,
+};
+use uuid::Uuid;
+
+/// Répertoire temporaire supprimé à la fin du test.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("embeddingproject-backup-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Base de deux collections, `articles` de `count` documents et `images`, avec un alias
+/// `courant` vers `articles`.
+fn database(count: u128) -> BaseDeDonnees {
+    let mut db = BaseDeDonnees::new();
+    db.replace(
+        "articles".to_string(),
+        Collection::new().with_metric(Metric::Dot),
+    );
+    db.add("images".to_string());
+    let articles = db.get_mut("articles").unwrap();
+    for i in 0..count {
+        articles
+            .upsert(Uuid::from_u128(i), [1.0, i as f32])
+            .unwrap();
+    }
+    let images = db.get_mut("images").unwrap();
+    images
+        .upsert(Uuid::from_u128(100), [0.5, 0.5, 0.5])
+        .unwrap();
+    db.set_alias("courant".to_string(), "articles").unwrap();
+    db
+}
+
+fn articles(db: &BaseDeDonnees) -> usize {
+    db.get("articles").map_or(0, Collection::len)
+}
+
+#[test]
+fn backups_rotate_and_restore_the_whole_database() {
+    let dir = TempDir::new();
+    let mut paths = Vec::new();
+    for count in 1..=3 {
+        let report = database(count).backup(dir.path(), Some(2)).unwrap();
+        assert_eq!(report.manifest.entries.len(), 2);
+        assert_eq!(report.pruned.len(), usize::from(count == 3));
+        paths.push(report.path);
+    }
+    // La plus ancienne sauvegarde est supprimée, les deux dernières restent dans l'ordre.
+    assert_eq!(list_backups(dir.path()).unwrap(), paths[1..]);
+    assert!(!paths[0].exists());
+
+    let mut db = BaseDeDonnees::new();
+    let manifest = db
+        .restore_backup(&paths[2], &RestoreOptions::default())
+        .unwrap();
+    assert_eq!(
+        manifest.aliases,
+        [("courant".to_string(), "articles".to_string())]
+    );
+    assert_eq!(articles(&db), 3);
+    assert_eq!(db.get("images").unwrap().len(), 1);
+    assert_eq!(db.aliases(), [("courant", "articles")]);
+    assert_eq!(db.get("articles").unwrap().metric(), Metric::Dot);
+
+    // Une base non vide n'est remplacée qu'avec `force`.
+    assert!(matches!(
+        db.restore_backup(&paths[1], &RestoreOptions::default()),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert_eq!(articles(&db), 3);
+    let force = RestoreOptions {
+        force: true,
+        ..RestoreOptions::default()
+    };
+    db.restore_backup(&paths[1], &force).unwrap();
+    assert_eq!(articles(&db), 2);
+}
+
+#[test]
+fn a_single_collection_can_be_restored() {
+    let dir = TempDir::new();
+    let path = database(4).backup(dir.path(), None).unwrap().path;
+    let mut db = database(1);
+    db.get_mut("images").unwrap().clear(false);
+
+    let only = |collection: &str, force| RestoreOptions {
+        collection: Some(collection.to_string()),
+        force,
+    };
+    assert!(matches!(
+        db.restore_backup(&path, &only("articles", false)),
+        Err(Error::InvalidConfig(_))
+    ));
+    db.restore_backup(&path, &only("articles", true)).unwrap();
+    assert_eq!(articles(&db), 4);
+    // Les autres collections ne sont pas touchées.
+    assert!(db.get("images").unwrap().is_empty());
+    assert!(matches!(
+        db.restore_backup(&path, &only("absente", true)),
+        Err(Error::CollectionNotFound(nom)) if nom == "absente"
+    ));
+}
+
+#[test]
+fn the_manifest_refuses_an_incomplete_or_altered_backup() {
+    let dir = TempDir::new();
+    let path = database(3).backup(dir.path(), None).unwrap().path;
+    let manifest = fs::read_to_string(path.join(BACKUP_MANIFEST)).unwrap();
+    assert!(manifest.contains("articles") && manifest.contains("images"));
+    let file = path.join("001.snap");
+    let bytes = fs::read(&file).unwrap();
+
+    let mut altered = bytes.clone();
+    let last = altered.len() - 1;
+    altered[last] ^= 0xff;
+    fs::write(&file, &altered).unwrap();
+    let mut db = BaseDeDonnees::new();
+    assert!(matches!(
+        db.restore_backup(&path, &RestoreOptions::default()),
+        Err(Error::InvalidSnapshot(_))
+    ));
+
+    fs::remove_file(&file).unwrap();
+    assert!(matches!(
+        db.restore_backup(&path, &RestoreOptions::default()),
+        Err(Error::InvalidSnapshot(_))
+    ));
+    // Rien n'est restauré, pas même la collection dont le fichier est intact.
+    assert!(db.get("articles").is_none());
+
+    fs::write(&file, &bytes).unwrap();
+    db.restore_backup(&path, &RestoreOptions::default())
+        .unwrap();
+    assert_eq!(articles(&db), 3);
+}
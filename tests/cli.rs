@@ -0,0 +1,328 @@
+//! Commandes de l'exécutable sur une sauvegarde écrite par la bibliothèque.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use embeddingproject::{BaseDeDonnees, Collection, Metric};
+use uuid::Uuid;
+
+/// Sauvegarde d'une collection `docs` de produits scalaires supprimée à la fin du test.
+struct Snapshot(PathBuf);
+
+impl Snapshot {
+    fn new(documents: &[(Uuid, [f32; 2])]) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("embeddingproject-cli-{}.snap", Uuid::new_v4()));
+        let mut db = BaseDeDonnees::new();
+        let mut docs = Collection::new().with_metric(Metric::Dot);
+        for (key, vector) in documents {
+            docs.upsert(*key, *vector).unwrap();
+        }
+        db.replace("docs".to_string(), docs);
+        db.save(&path).unwrap();
+        Snapshot(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_embeddingProject"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn search_by_expression_prints_the_nearest_documents() {
+    let (a, b, c) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+    let snapshot = Snapshot::new(&[(a, [1.0, 0.0]), (b, [0.0, 1.0]), (c, [0.6, 0.6])]);
+    let path = snapshot.path().to_str().unwrap();
+    // 2·a - b + [0, 0.5] = [2, -0.5] : a (2), c (0.9), b (-0.5).
+    let expr = format!("2*id:{} - id:{} + [0, 0.5]", a, b);
+    let output = cli(&[
+        "search", path, "docs", "3", "--expr", &expr, "--output", "csv",
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<(Uuid, f32)> = (stdout.lines().skip(1))
+        .map(|line| {
+            let (id, score) = line.split_once(',').unwrap();
+            (id.parse().unwrap(), score.parse().unwrap())
+        })
+        .collect();
+    assert_eq!(stdout.lines().next(), Some("id,score"));
+    let keys: Vec<Uuid> = rows.iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys, [a, c, b]);
+    assert!((rows[1].1 - 0.9).abs() < 1e-6);
+}
+
+#[test]
+fn search_by_expression_reports_bad_expressions_and_unknown_ids() {
+    let a = Uuid::from_u128(1);
+    let snapshot = Snapshot::new(&[(a, [1.0, 0.0])]);
+    let path = snapshot.path().to_str().unwrap();
+
+    let unknown = Uuid::from_u128(0xdead);
+    let output = cli(&[
+        "search",
+        path,
+        "docs",
+        "1",
+        "--expr",
+        &format!("id:{}", unknown),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&unknown.to_string()));
+
+    let output = cli(&["search", path, "docs", "1", "--expr", "0.5 [1, 0]"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("InvalidExpression") && stderr.contains("offset: 4"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn import_reads_a_qdrant_export_into_a_new_collection() {
+    let snapshot = Snapshot::new(&[(Uuid::from_u128(1), [1.0, 0.0])]);
+    let path = snapshot.path().to_str().unwrap();
+    let fixture = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/qdrant_scroll.json"
+    );
+    let output = cli(&["import", path, "villes", fixture, "--format", "qdrant"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("3 document(s) importé(s), 1 valeur(s)"),
+        "{}",
+        stdout
+    );
+    let db = BaseDeDonnees::load(snapshot.path()).unwrap();
+    assert_eq!(db.get("villes").unwrap().len(), 3);
+    assert_eq!(db.get("docs").unwrap().len(), 1);
+}
+
+#[test]
+fn delete_dry_run_leaves_the_snapshot_byte_identical() {
+    let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+    let snapshot = Snapshot::new(&[(a, [1.0, 0.0]), (b, [0.0, 1.0])]);
+    let path = snapshot.path().to_str().unwrap();
+    let ids = snapshot.path().with_extension("ids");
+    std::fs::write(&ids, format!("{}\nligne invalide\n", a)).unwrap();
+    let ids_path = ids.to_str().unwrap();
+    let before = std::fs::read(snapshot.path()).unwrap();
+
+    let output = cli(&["delete", path, "docs", "--ids-file", ids_path, "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.starts_with("1 document(s) à supprimer, 0 absent(s), 1 ligne(s) invalide(s)"));
+    assert_eq!(std::fs::read(snapshot.path()).unwrap(), before);
+
+    let output = cli(&["delete", path, "docs", "--ids-file", ids_path]);
+    let _ = std::fs::remove_file(&ids);
+    assert!(output.status.success());
+    let db = BaseDeDonnees::load(snapshot.path()).unwrap();
+    let docs = db.get("docs").unwrap();
+    assert_eq!(docs.len(), 1);
+    assert!(docs.read(&a).is_none() && docs.read(&b).is_some());
+}
+
+#[test]
+fn streamed_searches_answer_every_line_in_order() {
+    let snapshot = Snapshot::new(&[
+        (Uuid::from_u128(1), [1.0, 0.0]),
+        (Uuid::from_u128(2), [0.0, 1.0]),
+    ]);
+    let path = snapshot.path().to_str().unwrap();
+    let mut input = String::new();
+    for i in 0..3000 {
+        match i % 250 {
+            0 => input.push_str("pas du json\n"),
+            _ => input.push_str(&format!(
+                "{{\"id\": {}, \"vector\": [{}, 1.0]}}\n",
+                i,
+                i % 7
+            )),
+        }
+    }
+    let mut child = Command::new(env!("CARGO_BIN_EXE_embeddingProject"))
+        .args([
+            "search",
+            path,
+            "docs",
+            "1",
+            "--stream",
+            "--parallel",
+            "4",
+            "--errors",
+            "stderr",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()).unwrap());
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2988);
+    let expected = (1..=3000).filter(|line| (line - 1) % 250 != 0);
+    for (text, line) in lines.iter().zip(expected) {
+        let prefix = format!(
+            "{{\"line\":{},\"id\":{},\"hits\":[{{\"id\":",
+            line,
+            line - 1
+        );
+        assert!(text.starts_with(&prefix), "{}", text);
+    }
+    // Les lignes invalides vont sur la sortie d'erreur, suivies du bilan.
+    let errors: Vec<&str> = stderr.lines().collect();
+    assert_eq!(errors.len(), 13);
+    assert!(errors[..12].iter().all(|line| line.contains("\"error\":")));
+    assert!(errors[1].starts_with("{\"line\":251,\"error\":"));
+    assert_eq!(errors[12], "3000 requête(s), 12 en erreur");
+}
+
+#[test]
+fn bench_prints_a_json_report_and_refuses_unforced_collections() {
+    let output = cli(&[
+        "bench",
+        "--documents",
+        "100",
+        "--dimension",
+        "4",
+        "--queries",
+        "20",
+        "--concurrency",
+        "2",
+        "--filtered",
+        "--index",
+        "numeric",
+        "--output",
+        "json",
+    ]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.starts_with("{\"documents\":100,\"dimension\":4,\"queries\":20,\"concurrency\":2,")
+    );
+    assert!(stdout.contains("\"filtered\":true"));
+
+    let snapshot = Snapshot::new(&[(Uuid::from_u128(1), [1.0, 0.0])]);
+    let path = snapshot.path().to_str().unwrap();
+    let before = std::fs::read(snapshot.path()).unwrap();
+    let output = cli(&[
+        "bench",
+        "--queries",
+        "5",
+        "--from",
+        path,
+        "--collection",
+        "docs",
+    ]);
+    assert!(!output.status.success());
+    let output = cli(&[
+        "bench",
+        "--queries",
+        "5",
+        "--from",
+        path,
+        "--collection",
+        "docs",
+        "--force",
+    ]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.starts_with("1 document(s) de dimension 2, 5 recherche(s)"),
+        "{}",
+        stdout
+    );
+    assert_eq!(std::fs::read(snapshot.path()).unwrap(), before);
+}
+
+#[test]
+fn doctor_and_apply_stop_on_fatal_diagnostics() {
+    let snapshot = Snapshot::new(&[(Uuid::from_u128(1), [1.0, 0.0])]);
+    let path = snapshot.path().to_str().unwrap();
+    let config = snapshot.path().with_extension("toml");
+    let config_path = config.to_str().unwrap();
+
+    let output = cli(&["doctor", path]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "aucun problème trouvé\n"
+    );
+
+    std::fs::write(&config, "[collections.docs]\nmetric = \"euclidean\"\n").unwrap();
+    let output = cli(&["doctor", path, "--config", config_path]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout.starts_with("fatal : la déclaration contredit la sauvegarde"),
+        "{}",
+        stdout
+    );
+    let output = cli(&["doctor", "/inexistant/base.snap"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // apply refuse de démarrer sur un problème fatal, sauf avec --ignore-preflight : la
+    // commande échoue alors plus loin, sur l'enregistrement.
+    std::fs::write(
+        &config,
+        "[collections.docs]\nmetric = \"dot\"\n[collections.autres]\ndimension = 2\n",
+    )
+    .unwrap();
+    let missing = snapshot.path().with_extension("absent").join("base.snap");
+    let output = cli(&["apply", missing.to_str().unwrap(), "--config", config_path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--ignore-preflight"));
+    let output = cli(&[
+        "apply",
+        missing.to_str().unwrap(),
+        "--config",
+        config_path,
+        "--ignore-preflight",
+    ]);
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("démarrage refusé"));
+    let output = cli(&["apply", path, "--config", config_path]);
+    let _ = std::fs::remove_file(&config);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(BaseDeDonnees::load(snapshot.path())
+        .unwrap()
+        .get("autres")
+        .is_some());
+}
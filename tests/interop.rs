@@ -0,0 +1,112 @@
+//! Import d'exports de Qdrant et de Chroma, à partir d'échantillons au format réel
+//! rangés dans `tests/fixtures`.
+
+use embeddingproject::{
+    Collection, DocumentId, Error, ImportFormat, Payload, Value, CHROMA_DOCUMENT_FIELD,
+};
+use uuid::Uuid;
+
+const QDRANT_SCROLL: &[u8] = include_bytes!("fixtures/qdrant_scroll.json");
+const CHROMA_GET: &[u8] = include_bytes!("fixtures/chroma_get.json");
+const CHROMA_JSONL: &[u8] = include_bytes!("fixtures/chroma.jsonl");
+
+fn payload(collection: &Collection, id: impl Into<DocumentId>) -> &Payload {
+    let key = id.into().to_uuid();
+    collection.payload(&key).expect("charge utile importée")
+}
+
+#[test]
+fn qdrant_scroll_export_is_imported_with_translated_ids() {
+    let mut collection = Collection::new();
+    let report = collection
+        .import_from(QDRANT_SCROLL, ImportFormat::Qdrant, None)
+        .unwrap();
+    assert_eq!(
+        (report.applied, report.degraded, report.cancelled),
+        (3, 1, false)
+    );
+    assert_eq!(collection.len(), 3);
+
+    // Un identifiant entier devient un Uuid déterministe, une chaîne Uuid le reste.
+    assert_eq!(
+        collection.read_id(1).map(Vec::as_slice),
+        Some(&[0.05, 0.61, 0.76, 0.74][..])
+    );
+    let london: Uuid = "5c56c793-69f3-4fbf-87e6-c4bf54c28c26".parse().unwrap();
+    assert!(collection.read_id(london).is_some());
+    assert!(collection.payload(&DocumentId::Int(3).to_uuid()).is_none());
+
+    let berlin = payload(&collection, 1);
+    assert_eq!(
+        berlin.get("city"),
+        Some(&Value::String("Berlin".to_string()))
+    );
+    assert!(matches!(berlin.get("tags"), Some(Value::Array(tags)) if tags.len() == 2));
+    // Un tableau d'objets est conservé sous forme de texte JSON.
+    let Some(Value::String(districts)) = payload(&collection, london).get("districts") else {
+        panic!("quartiers non convertis en texte");
+    };
+    assert!(districts.contains("Camden"));
+
+    // Réimporter le même export met à jour les mêmes documents.
+    collection
+        .import_from(QDRANT_SCROLL, ImportFormat::Qdrant, None)
+        .unwrap();
+    assert_eq!(collection.len(), 3);
+}
+
+#[test]
+fn chroma_exports_keep_string_ids_and_document_texts() {
+    for (export, count) in [(CHROMA_GET, 3), (CHROMA_JSONL, 2)] {
+        let mut collection = Collection::new();
+        let report = collection
+            .import_from(export, ImportFormat::Chroma, None)
+            .unwrap();
+        assert_eq!((report.applied, report.degraded), (count, 0));
+        let first = payload(&collection, "doc-1");
+        assert_eq!(
+            first.get("source"),
+            Some(&Value::String("wiki".to_string()))
+        );
+        assert_eq!(
+            first.get(CHROMA_DOCUMENT_FIELD),
+            Some(&Value::String("premier texte".to_string()))
+        );
+        assert_eq!(
+            collection.document_id(&DocumentId::from("doc-2").to_uuid()),
+            "doc-2".into()
+        );
+    }
+
+    let mut collection = Collection::new();
+    collection
+        .import_from(CHROMA_GET, ImportFormat::Chroma, None)
+        .unwrap();
+    // Le champ `document` des métadonnées l'emporte sur le texte du document.
+    let third: Uuid = "6f1c1a52-7a3e-4f4e-9b8e-2a6a0f3b9d11".parse().unwrap();
+    assert_eq!(
+        payload(&collection, third).get(CHROMA_DOCUMENT_FIELD),
+        Some(&Value::String("résumé".to_string()))
+    );
+}
+
+#[test]
+fn malformed_exports_are_refused_before_any_insertion() {
+    let mut collection = Collection::new();
+    let truncated = &QDRANT_SCROLL[..QDRANT_SCROLL.len() / 2];
+    assert!(matches!(
+        collection.import_from(truncated, ImportFormat::Qdrant, None),
+        Err(Error::Parse { .. })
+    ));
+    let uneven = br#"{"ids": ["a", "b"], "embeddings": [[1.0, 0.0]]}"#;
+    assert!(matches!(
+        collection.import_from(&uneven[..], ImportFormat::Chroma, None),
+        Err(Error::Parse { line: 1, .. })
+    ));
+    let named = br#"[{"id": 1, "vector": {"texte": [1.0], "image": [2.0]}}]"#;
+    assert!(matches!(
+        collection.import_from(&named[..], ImportFormat::Qdrant, None),
+        Err(Error::Parse { .. })
+    ));
+    assert!(collection.is_empty());
+}
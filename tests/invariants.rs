@@ -0,0 +1,290 @@
+//! Suites aléatoires reproductibles : des écritures, suppressions, compactages et
+//! sauvegardes entremêlés, confrontés après chaque étape à un modèle en mémoire et à
+//! [`Collection::check_invariants`], puis les mêmes recherches sur une collection simple,
+//! une collection partitionnée et un parcours parallèle adaptatif.
+
+use std::collections::BTreeMap;
+
+use embeddingproject::similarity;
+use embeddingproject::synthetic::VectorGenerator;
+use embeddingproject::{
+    AdaptiveChunks, BaseDeDonnees, Collection, CollectionConfig, Filter, FilterStrategy, IndexKind,
+    Metric, Payload, SearchParams, SearchRuntime, ShardedCollection, Value, VectorStore,
+    VectorStoreMut,
+};
+use uuid::Uuid;
+
+const DIMENSION: usize = 8;
+const GROUPS: u64 = 4;
+
+/// Tirages des opérations (SplitMix64), indépendants de ceux des vecteurs.
+struct Dice(u64);
+
+impl Dice {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, mut keys: impl ExactSizeIterator<Item = &'a Uuid>) -> Option<Uuid> {
+        let len = keys.len();
+        (len > 0).then(|| *keys.nth(self.below(len)).expect("clé tirée"))
+    }
+}
+
+type Document = (Vec<f32>, Option<Payload>);
+
+/// Contenu attendu de la collection.
+#[derive(Default)]
+struct Model {
+    live: BTreeMap<Uuid, Document>,
+    deleted: BTreeMap<Uuid, Document>,
+}
+
+fn payload(group: u64) -> Payload {
+    let mut payload = Payload::new();
+    payload.insert("groupe".to_string(), Value::String(format!("g{}", group)));
+    payload
+}
+
+/// Vérifie la collection contre le modèle après une étape.
+fn check(collection: &Collection, model: &Model, step: usize) {
+    if let Err(e) = collection.check_invariants() {
+        panic!("étape {} : {}", step, e);
+    }
+    assert_eq!(collection.len(), model.live.len(), "étape {}", step);
+    assert_eq!(
+        collection.tombstone_count(),
+        model.deleted.len(),
+        "étape {}",
+        step
+    );
+    for (key, (vector, payload)) in &model.live {
+        assert_eq!(
+            VectorStore::read(collection, key),
+            Some(&vector[..]),
+            "étape {}",
+            step
+        );
+        assert_eq!(collection.payload(key), payload.as_ref(), "étape {}", step);
+    }
+    for key in model.deleted.keys() {
+        assert!(
+            VectorStore::read(collection, key).is_none(),
+            "étape {}",
+            step
+        );
+    }
+}
+
+/// Vérifie qu'une recherche ne retourne que des documents vivants, dans l'ordre d'un
+/// parcours exhaustif du modèle.
+fn check_search(collection: &Collection, model: &Model, query: &[f32], step: usize) {
+    let hits = collection.search(query, 10).unwrap();
+    let mut expected: Vec<(Uuid, f32)> = (model.live.iter())
+        .map(|(key, (vector, _))| (*key, similarity::euclidean(query, vector).unwrap()))
+        .collect();
+    expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    expected.truncate(10);
+    let keys: Vec<Uuid> = hits.iter().map(|(key, _)| *key).collect();
+    let expected_keys: Vec<Uuid> = expected.iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys, expected_keys, "étape {}", step);
+    for ((_, score), (_, expected)) in hits.iter().zip(&expected) {
+        assert!(
+            (score - expected).abs() <= 1e-5 * expected.max(1.0),
+            "étape {}",
+            step
+        );
+    }
+
+    let group = Value::String(format!("g{}", step as u64 % GROUPS));
+    let filter = Filter::eq("groupe", group.clone());
+    for strategy in [FilterStrategy::IndexFirst, FilterStrategy::ScanFirst] {
+        let params = SearchParams {
+            k: 5,
+            filter_strategy: strategy,
+            ..Default::default()
+        };
+        let filtered = collection.search_filtered(query, &filter, &params).unwrap();
+        for (key, _) in filtered.hits.iter() {
+            let (_, payload) = model.live.get(key).expect("résultat supprimé");
+            let payload = payload.as_ref().expect("résultat sans charge utile");
+            assert_eq!(payload.get("groupe"), Some(&group), "étape {}", step);
+        }
+    }
+}
+
+/// Recharge la collection depuis une sauvegarde de la base qui la contient.
+fn reload(collection: Collection) -> Collection {
+    let mut db = BaseDeDonnees::new();
+    db.replace("docs".to_string(), collection);
+    let loaded = BaseDeDonnees::from_bytes(&db.to_bytes()).unwrap();
+    loaded.get("docs").expect("collection sauvegardée").clone()
+}
+
+fn run(seed: u64, steps: usize) {
+    let mut dice = Dice(seed);
+    let mut generator = VectorGenerator::new(DIMENSION, seed);
+    let config = CollectionConfig::builder()
+        .metric(Metric::Euclidean)
+        .build()
+        .unwrap();
+    let mut collection = Collection::from_config(config).with_soft_delete(true);
+    collection.create_payload_index("groupe", IndexKind::Keyword);
+    let mut model = Model::default();
+
+    for step in 0..steps {
+        match dice.below(100) {
+            0..=29 => {
+                let (key, vector) = (generator.uuid(), generator.vector());
+                collection.upsert(key, vector.clone()).unwrap();
+                model.live.insert(key, (vector, None));
+            }
+            30..=44 => {
+                if let Some(key) = dice.pick(model.live.keys()) {
+                    let vector = generator.vector();
+                    collection.upsert(key, vector.clone()).unwrap();
+                    // Une mise à jour du vecteur conserve la charge utile.
+                    model.live.get_mut(&key).expect("clé tirée").0 = vector;
+                }
+            }
+            45..=59 => {
+                let key = match dice.below(2) {
+                    0 => dice.pick(model.live.keys()),
+                    _ => None,
+                }
+                .unwrap_or_else(|| generator.uuid());
+                let (vector, payload) = (generator.vector(), payload(dice.next() % GROUPS));
+                collection
+                    .upsert_with_payload(key, vector.clone(), payload.clone())
+                    .unwrap();
+                model.live.insert(key, (vector, Some(payload)));
+            }
+            60..=74 => {
+                if let Some(key) = dice.pick(model.live.keys()) {
+                    collection.delete(&key);
+                    let document = model.live.remove(&key).expect("clé tirée");
+                    model.deleted.insert(key, document);
+                }
+            }
+            75..=77 => collection.delete(&generator.uuid()),
+            78..=83 => match dice.pick(model.deleted.keys()) {
+                Some(key) => {
+                    assert!(collection.restore(&key));
+                    let document = model.deleted.remove(&key).expect("clé tirée");
+                    model.live.insert(key, document);
+                }
+                None => assert!(!collection.restore(&generator.uuid())),
+            },
+            84..=87 => {
+                assert_eq!(collection.compact(), model.deleted.len());
+                model.deleted.clear();
+            }
+            88 => {
+                collection.clear(false);
+                model.live.clear();
+                model.deleted.clear();
+            }
+            89..=92 => {
+                collection = reload(collection);
+                // Les documents en attente de compactage ne sont pas sauvegardés.
+                model.deleted.clear();
+                assert!(collection.soft_delete());
+                assert_eq!(
+                    collection.payload_indexes(),
+                    [("groupe", IndexKind::Keyword)]
+                );
+            }
+            93..=95 => {
+                // Une vue figée ne voit pas les écritures qui la suivent.
+                let mut db = BaseDeDonnees::new();
+                db.replace("docs".to_string(), collection);
+                let frozen = db.snapshot();
+                let before = frozen.get("docs").expect("vue").len();
+                let (key, vector) = (generator.uuid(), generator.vector());
+                let live = db.get_mut("docs").expect("collection");
+                live.upsert(key, vector.clone()).unwrap();
+                assert_eq!(frozen.get("docs").expect("vue").len(), before);
+                assert!(VectorStore::read(frozen.get("docs").expect("vue"), &key).is_none());
+                frozen.get("docs").expect("vue").check_invariants().unwrap();
+                collection = db.get("docs").expect("collection").clone();
+                model.live.insert(key, (vector, None));
+            }
+            _ => check_search(&collection, &model, &generator.vector(), step),
+        }
+        check(&collection, &model, step);
+    }
+}
+
+#[test]
+fn interleaved_writes_keep_the_collection_consistent() {
+    for seed in [1, 7, 2024] {
+        run(seed, 600);
+    }
+}
+
+/// Au-delà de ce nombre de documents, les recherches sont réparties sur les threads.
+const PARALLEL_DOCUMENTS: usize = 5000;
+
+#[test]
+fn sharded_and_parallel_searches_agree_with_a_single_collection() {
+    let mut dice = Dice(42);
+    let mut generator = VectorGenerator::new(DIMENSION, 42);
+    let adaptive = SearchRuntime::new(4).with_adaptive_chunks(AdaptiveChunks {
+        min_size: 16,
+        ..AdaptiveChunks::default()
+    });
+    let mut single = Collection::new().with_runtime(SearchRuntime::new(1));
+    let mut parallel = Collection::new().with_runtime(adaptive);
+    let mut sharded = ShardedCollection::new(3).unwrap().with_runtime(adaptive);
+    let mut keys = Vec::new();
+
+    for step in 0..PARALLEL_DOCUMENTS + 300 {
+        let action = match step < PARALLEL_DOCUMENTS {
+            true => 0,
+            false => dice.below(10),
+        };
+        match action {
+            0..=5 => {
+                let (key, vector) = (generator.uuid(), generator.vector());
+                single.upsert(key, vector.clone()).unwrap();
+                parallel.upsert(key, vector.clone()).unwrap();
+                VectorStoreMut::upsert(&mut sharded, key, vector).unwrap();
+                keys.push(key);
+            }
+            6 | 7 => {
+                let key = keys.swap_remove(dice.below(keys.len()));
+                single.delete(&key);
+                parallel.delete(&key);
+                VectorStoreMut::delete(&mut sharded, &key);
+            }
+            _ => {
+                let query = generator.vector();
+                let expected = single.search(&query, 20).unwrap();
+                assert_eq!(
+                    parallel.search(&query, 20).unwrap(),
+                    expected,
+                    "étape {}",
+                    step
+                );
+                assert_eq!(
+                    VectorStore::search(&sharded, &query, 20).unwrap(),
+                    expected,
+                    "étape {}",
+                    step
+                );
+            }
+        }
+    }
+    assert_eq!(VectorStore::len(&sharded), single.len());
+    single.check_invariants().unwrap();
+    parallel.check_invariants().unwrap();
+    sharded.check_invariants().unwrap();
+}
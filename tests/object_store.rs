@@ -0,0 +1,369 @@
+//! Sauvegardes dans un stockage objet simulé dans le processus (fonctionnalité
+//! `object-store`) : un petit serveur HTTP qui répond comme S3 aux requêtes de la
+//! bibliothèque, et dont on peut interrompre les envois.
+#![cfg(feature = "object-store")]
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use embeddingproject::synthetic::VectorGenerator;
+use embeddingproject::{BaseDeDonnees, Error, ObjectStoreCredentials, OBJECT_PART_BYTES};
+
+/// Contenu du seau simulé.
+#[derive(Default)]
+struct State {
+    objects: HashMap<String, Vec<u8>>,
+    /// Parties reçues de chaque envoi en cours, par identifiant d'envoi.
+    uploads: HashMap<String, BTreeMap<usize, Vec<u8>>>,
+    next_upload: usize,
+    /// Les parties de ce numéro ou plus sont refusées, comme par un accès révoqué.
+    refuse_parts_from: Option<usize>,
+    /// Nombre de réponses 503 à renvoyer avant de traiter les requêtes suivantes.
+    unavailable: usize,
+    /// Parties effectivement reçues, dans l'ordre.
+    received_parts: Vec<usize>,
+}
+
+/// Stockage compatible S3 réduit aux requêtes qu'envoie la bibliothèque.
+#[derive(Clone)]
+struct MockStore {
+    state: Arc<Mutex<State>>,
+    address: String,
+}
+
+struct Request {
+    method: String,
+    key: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl MockStore {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let store = MockStore {
+            state: Arc::default(),
+            address: listener.local_addr().unwrap().to_string(),
+        };
+        let server = store.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                server.serve(stream);
+            }
+        });
+        store
+    }
+
+    fn uri(&self, key: &str) -> String {
+        format!("http://{}/seau/{}", self.address, key)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn serve(&self, stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let Some(request) = read_request(&mut reader) else {
+            return;
+        };
+        let (status, headers, body) = self.answer(request);
+        let mut stream = stream;
+        let mut head = format!(
+            "HTTP/1.1 {} X\r\ncontent-length: {}\r\n",
+            status,
+            body.len()
+        );
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("connection: close\r\n\r\n");
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(&body);
+    }
+
+    fn answer(&self, request: Request) -> (u16, Vec<(&'static str, String)>, Vec<u8>) {
+        let mut state = self.state();
+        assert!(request.headers["authorization"].starts_with("AWS4-HMAC-SHA256 Credential=cle/"));
+        let upload_id = request.query.get("uploadId").cloned();
+        if state.unavailable > 0 {
+            state.unavailable -= 1;
+            return (
+                503,
+                Vec::new(),
+                b"<Error><Code>SlowDown</Code></Error>".to_vec(),
+            );
+        }
+        match (request.method.as_str(), upload_id) {
+            ("POST", None) if request.query.contains_key("uploads") => {
+                state.next_upload += 1;
+                let id = format!("envoi-{}", state.next_upload);
+                state.uploads.insert(id.clone(), BTreeMap::new());
+                let body = format!("<InitiateMultipartUploadResult><UploadId>{}</UploadId></InitiateMultipartUploadResult>", id);
+                (200, Vec::new(), body.into_bytes())
+            }
+            ("PUT", Some(id)) => {
+                let number: usize = request.query["partNumber"].parse().unwrap();
+                if state.refuse_parts_from.is_some_and(|from| number >= from) {
+                    return (
+                        403,
+                        Vec::new(),
+                        b"<Error><Code>AccessDenied</Code></Error>".to_vec(),
+                    );
+                }
+                let Some(parts) = state.uploads.get_mut(&id) else {
+                    return (404, Vec::new(), Vec::new());
+                };
+                parts.insert(number, request.body);
+                state.received_parts.push(number);
+                (
+                    200,
+                    vec![("etag", format!("\"{}-{}\"", id, number))],
+                    Vec::new(),
+                )
+            }
+            ("GET", Some(id)) => {
+                let Some(parts) = state.uploads.get(&id) else {
+                    return (404, Vec::new(), Vec::new());
+                };
+                let mut body = String::from("<ListPartsResult><IsTruncated>false</IsTruncated>");
+                for (number, part) in parts {
+                    body.push_str(&format!(
+                        "<Part><PartNumber>{}</PartNumber><Size>{}</Size><ETag>&quot;{}-{}&quot;</ETag></Part>",
+                        number,
+                        part.len(),
+                        id,
+                        number
+                    ));
+                }
+                body.push_str("</ListPartsResult>");
+                (200, Vec::new(), body.into_bytes())
+            }
+            ("POST", Some(id)) => {
+                let Some(mut parts) = state.uploads.remove(&id) else {
+                    return (404, Vec::new(), Vec::new());
+                };
+                let listed = String::from_utf8(request.body).unwrap();
+                let mut object = Vec::new();
+                for number in listed.split("<PartNumber>").skip(1) {
+                    let number: usize = number.split('<').next().unwrap().parse().unwrap();
+                    object.extend(parts.remove(&number).unwrap());
+                }
+                state.objects.insert(request.key, object);
+                (
+                    200,
+                    Vec::new(),
+                    b"<CompleteMultipartUploadResult/>".to_vec(),
+                )
+            }
+            ("DELETE", Some(id)) => {
+                state.uploads.remove(&id);
+                (204, Vec::new(), Vec::new())
+            }
+            ("PUT", None) => {
+                state.objects.insert(request.key, request.body);
+                (200, Vec::new(), Vec::new())
+            }
+            ("GET", None) => match state.objects.get(&request.key) {
+                Some(object) => match request.headers.get("range") {
+                    Some(range) => {
+                        let (start, end) = range["bytes=".len()..].split_once('-').unwrap();
+                        let (start, end): (usize, usize) =
+                            (start.parse().unwrap(), end.parse().unwrap());
+                        (
+                            206,
+                            Vec::new(),
+                            object[start..=end.min(object.len() - 1)].to_vec(),
+                        )
+                    }
+                    None => (200, Vec::new(), object.clone()),
+                },
+                None => (
+                    404,
+                    Vec::new(),
+                    b"<Error><Code>NoSuchKey</Code></Error>".to_vec(),
+                ),
+            },
+            ("DELETE", None) => {
+                state.objects.remove(&request.key);
+                (204, Vec::new(), Vec::new())
+            }
+            _ => (400, Vec::new(), Vec::new()),
+        }
+    }
+}
+
+/// Lit une requête HTTP dont le corps est délimité par `content-length`.
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut words = line.split_whitespace();
+    let method = words.next()?.to_string();
+    let target = words.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let key = path.strip_prefix("/seau/")?.to_string();
+    let query = (query.split('&'))
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (name.to_string(), value.to_string())
+        })
+        .collect();
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+    let length = headers.get("content-length")?.parse().ok()?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some(Request {
+        method,
+        key,
+        query,
+        headers,
+        body,
+    })
+}
+
+fn credentials() -> ObjectStoreCredentials {
+    ObjectStoreCredentials::new("cle", "secret").with_region("eu-west-3")
+}
+
+/// Base d'une collection `docs` dont la sauvegarde occupe un peu plus de deux parties.
+fn database(seed: u64) -> BaseDeDonnees {
+    let dimension = 1024;
+    let count = 2 * OBJECT_PART_BYTES / (dimension * 4) + 100;
+    let mut generator = VectorGenerator::new(dimension, seed);
+    let mut db = BaseDeDonnees::new();
+    db.add("docs".to_string());
+    let docs = db.get_mut("docs").unwrap();
+    for _ in 0..count {
+        docs.upsert(generator.uuid(), generator.vector()).unwrap();
+    }
+    db
+}
+
+#[test]
+fn interrupted_uploads_resume_without_resending_parts() {
+    let store = MockStore::start();
+    let uri = store.uri("bases/main.snap");
+    let db = database(1);
+    store.state().refuse_parts_from = Some(3);
+    assert!(matches!(
+        db.save_to_object_store(&uri, &credentials()),
+        Err(Error::Io(_))
+    ));
+    // Sans manifeste, l'envoi partiel n'est pas une sauvegarde.
+    assert!(matches!(
+        BaseDeDonnees::load_from_object_store(&uri, &credentials()),
+        Err(Error::InvalidSnapshot(_))
+    ));
+
+    // L'envoi reprend à la troisième partie, après une erreur passagère.
+    {
+        let mut state = store.state();
+        state.refuse_parts_from = None;
+        state.received_parts.clear();
+        state.unavailable = 1;
+    }
+    let report = db.save_to_object_store(&uri, &credentials()).unwrap();
+    assert_eq!((report.parts, report.resumed_parts), (3, 2));
+    assert_eq!(store.state().received_parts, [3]);
+    assert_eq!(report.bytes, db.to_bytes().len() as u64);
+    let loaded = BaseDeDonnees::load_from_object_store(&uri, &credentials()).unwrap();
+    assert_eq!(loaded.to_bytes(), db.to_bytes());
+    // Seuls le manifeste et les données restent dans le seau.
+    let mut objects: Vec<String> = store.state().objects.keys().cloned().collect();
+    objects.sort();
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[0], "bases/main.snap");
+    assert!(objects[1].starts_with("bases/main.snap.") && objects[1].ends_with(".data"));
+    assert!(store.state().uploads.is_empty());
+}
+
+#[test]
+fn a_failed_upload_keeps_the_previous_snapshot() {
+    let store = MockStore::start();
+    let uri = store.uri("main.snap");
+    let (first, second, third) = (database(2), database(3), database(4));
+    first.save_to_object_store(&uri, &credentials()).unwrap();
+    store.state().refuse_parts_from = Some(2);
+    assert!(second.save_to_object_store(&uri, &credentials()).is_err());
+    let loaded = BaseDeDonnees::load_from_object_store(&uri, &credentials()).unwrap();
+    assert_eq!(loaded.to_bytes(), first.to_bytes());
+
+    // Un contenu différent abandonne l'envoi interrompu et recommence.
+    store.state().refuse_parts_from = None;
+    let report = third.save_to_object_store(&uri, &credentials()).unwrap();
+    assert_eq!(report.resumed_parts, 0);
+    let loaded = BaseDeDonnees::load_from_object_store(&uri, &credentials()).unwrap();
+    assert_eq!(loaded.to_bytes(), third.to_bytes());
+    assert_eq!(store.state().objects.len(), 2);
+    assert!(store.state().uploads.is_empty());
+}
+
+#[test]
+fn invalid_manifests_and_altered_parts_are_refused() {
+    let store = MockStore::start();
+    let uri = store.uri("main.snap");
+    let mut db = BaseDeDonnees::new();
+    db.add("docs".to_string());
+    db.save_to_object_store(&uri, &credentials()).unwrap();
+    let manifest = store.state().objects["main.snap"].clone();
+    let manifest = String::from_utf8(manifest).unwrap();
+
+    let invalid = [
+        "pas du json".to_string(),
+        manifest.replace("\"format\":1", "\"format\":9"),
+        manifest.replace("\"parts\":[", "\"parts\":[\"00\","),
+    ];
+    for text in invalid {
+        store
+            .state()
+            .objects
+            .insert("main.snap".to_string(), text.into_bytes());
+        assert!(matches!(
+            BaseDeDonnees::load_from_object_store(&uri, &credentials()),
+            Err(Error::InvalidSnapshot(_))
+        ));
+    }
+
+    store
+        .state()
+        .objects
+        .insert("main.snap".to_string(), manifest.into_bytes());
+    let data = {
+        let state = store.state();
+        state
+            .objects
+            .keys()
+            .find(|key| key.ends_with(".data"))
+            .cloned()
+            .unwrap()
+    };
+    store.state().objects.get_mut(&data).unwrap()[0] ^= 1;
+    assert!(matches!(
+        BaseDeDonnees::load_from_object_store(&uri, &credentials()),
+        Err(Error::InvalidSnapshot(_))
+    ));
+    for uri in [
+        "https://hote/seau/cle",
+        "http://hote/seau",
+        "ftp://hote/seau/cle",
+    ] {
+        assert!(matches!(
+            db.save_to_object_store(uri, &credentials()),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,243 @@
+//! Primaire et réplica dans le même processus : le réplica suit le primaire par TCP ou
+//! par son fichier de journal, et doit converger vers le même contenu après une rafale
+//! d'écritures concurrentes.
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use embeddingproject::{
+    BaseDeDonnees, BaseDeDonneesPartagee, Error, Payload, Primary, Replica, Value,
+};
+use uuid::Uuid;
+
+const WRITERS: usize = 4;
+const WRITES: usize = 200;
+
+fn primary() -> Primary {
+    Primary::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()))
+}
+
+fn replica() -> Replica {
+    Replica::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()))
+}
+
+/// Écrit depuis `WRITERS` threads des documents dans `docs`, en supprime un sur cinq,
+/// et retourne toutes les clés écrites.
+fn burst(primary: &Primary) -> Vec<Uuid> {
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|writer| {
+            let primary = primary.clone();
+            thread::spawn(move || {
+                let mut keys = Vec::new();
+                for i in 0..WRITES {
+                    let key = Uuid::new_v4();
+                    let vector = vec![1.0, writer as f32, i as f32];
+                    match i % 3 {
+                        0 => {
+                            let mut payload = Payload::new();
+                            payload.insert("rang".to_string(), Value::Number(i as f64));
+                            primary.upsert_with_payload("docs", key, vector, payload)
+                        }
+                        _ => primary.upsert("docs", key, vector),
+                    }
+                    .unwrap();
+                    if i % 5 == 0 {
+                        primary.delete("docs", key).unwrap();
+                    }
+                    keys.push(key);
+                }
+                keys
+            })
+        })
+        .collect();
+    writers
+        .into_iter()
+        .flat_map(|writer| writer.join().unwrap())
+        .collect()
+}
+
+/// Vérifie que le réplica contient exactement les documents `keys` du primaire.
+fn assert_converged(primary: &Primary, replica: &Replica, keys: &[Uuid]) {
+    let (primary_db, replica_db) = (primary.db().read(), replica.db().read());
+    let (expected, docs) = (
+        primary_db.get("docs").unwrap(),
+        replica_db.get("docs").unwrap(),
+    );
+    docs.check_invariants().unwrap();
+    assert_eq!(docs.len(), expected.len());
+    assert_eq!(docs.len(), WRITERS * WRITES * 4 / 5);
+    for key in keys {
+        assert_eq!(docs.read(key), expected.read(key));
+        assert_eq!(docs.payload(key), expected.payload(key));
+    }
+    let status = replica.replication_status();
+    assert_eq!(
+        status.applied_sequence,
+        primary.replication_status().last_sequence
+    );
+    assert_eq!(status.lag(), 0);
+}
+
+/// Attend que le réplica ait appliqué `sequence`.
+fn wait_for(replica: &Replica, sequence: u64) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while replica.replication_status().applied_sequence < sequence {
+        assert!(
+            Instant::now() < deadline,
+            "le réplica n'a pas rattrapé le primaire"
+        );
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn replica_following_over_tcp_converges_after_a_burst() {
+    let primary = primary();
+    primary.create_collection("docs").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = primary.serve(listener);
+
+    let replica = replica();
+    let follower = {
+        let replica = replica.clone();
+        thread::spawn(move || replica.follow(addr))
+    };
+    let keys = burst(&primary);
+    let head = primary.replication_status().last_sequence;
+    assert_eq!(head, 1 + (WRITERS * WRITES + WRITERS * WRITES / 5) as u64);
+    wait_for(&replica, head);
+    assert_converged(&primary, &replica, &keys);
+
+    // L'arrêt du primaire termine la diffusion, et le réplica s'arrête proprement.
+    let report = primary.shutdown(None, Duration::from_secs(5));
+    assert!(report.is_clean(), "{:?}", report);
+    follower.join().unwrap().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn reconnecting_replica_resumes_after_the_last_applied_record() {
+    let primary = primary();
+    primary.create_collection("docs").unwrap();
+    let keys = burst(&primary);
+    let head = primary.replication_status().last_sequence;
+
+    // Une première moitié appliquée, puis une déconnexion.
+    let replica = replica();
+    let (records, _) = primary.changes_since(1, head as usize / 2).unwrap();
+    for record in &records {
+        replica.apply(record, head).unwrap();
+    }
+    assert_eq!(
+        replica.replication_status().lag(),
+        head - records.len() as u64
+    );
+    // Le rejeu d'enregistrements déjà appliqués ne change rien.
+    for record in &records[..10] {
+        replica.apply(record, head).unwrap();
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    primary.serve(listener);
+    let follower = {
+        let replica = replica.clone();
+        thread::spawn(move || replica.follow(addr))
+    };
+    wait_for(&replica, head);
+    assert_converged(&primary, &replica, &keys);
+    assert!(primary.shutdown(None, Duration::from_secs(5)).is_clean());
+    follower.join().unwrap().unwrap();
+}
+
+fn temp_path(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "embeddingproject-replication-{}.{}",
+        Uuid::new_v4(),
+        extension
+    ))
+}
+
+#[test]
+fn replica_consuming_the_log_file_converges() {
+    let path = temp_path("log");
+    let primary = primary().with_log_file(&path).unwrap();
+    primary.create_collection("docs").unwrap();
+    let keys = burst(&primary);
+    assert!(primary.shutdown(None, Duration::from_secs(1)).is_clean());
+
+    let replica = replica();
+    let head = primary.replication_status().last_sequence;
+    assert_eq!(replica.consume_file(&path).unwrap(), head);
+    // Un deuxième passage sur le même fichier est sans effet.
+    assert_eq!(replica.consume_file(&path).unwrap(), head);
+    std::fs::remove_file(&path).unwrap();
+    assert_converged(&primary, &replica, &keys);
+}
+
+#[test]
+fn shutdown_leaves_everything_for_the_next_process() {
+    let (log, snapshot) = (temp_path("log"), temp_path("snap"));
+    let primary = primary().with_log_file(&log).unwrap();
+    primary.create_collection("docs").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = primary.serve(listener);
+    let replica = replica();
+    let follower = {
+        let replica = replica.clone();
+        thread::spawn(move || replica.follow(addr))
+    };
+    let keys = burst(&primary);
+    let head = primary.replication_status().last_sequence;
+
+    let report = primary.shutdown(Some(&snapshot), Duration::from_secs(5));
+    assert!(report.is_clean(), "{:?}", report);
+    assert!(report.snapshot_saved);
+    assert_eq!(report.last_sequence, head);
+    follower.join().unwrap().unwrap();
+    server.join().unwrap();
+    assert_converged(&primary, &replica, &keys);
+    assert!(matches!(
+        primary.upsert("docs", Uuid::new_v4(), vec![1.0, 0.0, 0.0]),
+        Err(Error::ShutDown)
+    ));
+    // Un deuxième arrêt reprend les étapes sans erreur.
+    assert!(primary.shutdown(None, Duration::from_secs(1)).is_clean());
+    drop(primary);
+
+    // Nouveau processus : la sauvegarde finale et le journal contiennent tout.
+    let restored = BaseDeDonnees::load(&snapshot).unwrap();
+    let docs = restored.get("docs").unwrap();
+    assert_eq!(docs.len(), WRITERS * WRITES * 4 / 5);
+    let replica_db = replica.db().read();
+    let expected = replica_db.get("docs").unwrap();
+    for key in &keys {
+        assert_eq!(docs.read(key), expected.read(key));
+    }
+    let from_log = self::replica();
+    assert_eq!(from_log.consume_file(&log).unwrap(), head);
+    assert_eq!(from_log.db().read().get("docs").unwrap().len(), docs.len());
+    std::fs::remove_file(&log).unwrap();
+    std::fs::remove_file(&snapshot).unwrap();
+}
+
+#[test]
+fn dropping_the_primary_flushes_the_log() {
+    let path = temp_path("log");
+    let primary = primary().with_log_file(&path).unwrap();
+    primary.create_collection("docs").unwrap();
+    for i in 0..10 {
+        primary
+            .upsert("docs", Uuid::new_v4(), vec![1.0, i as f32])
+            .unwrap();
+    }
+    drop(primary);
+    let replica = replica();
+    assert_eq!(replica.consume_file(&path).unwrap(), 11);
+    assert_eq!(replica.db().read().get("docs").unwrap().len(), 10);
+    std::fs::remove_file(&path).unwrap();
+}
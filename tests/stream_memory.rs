@@ -0,0 +1,64 @@
+//! Mémoire de [`search_stream`] sur un long flux, mesurée par un allocateur qui compte
+//! les octets alloués. Ce fichier ne contient qu'un test, pour que d'autres tests
+//! exécutés en parallèle ne faussent pas la mesure.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use embeddingproject::synthetic::VectorGenerator;
+use embeddingproject::{search_stream, Metric};
+
+/// Allocateur système qui suit les octets alloués et leur maximum.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(live, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const DIMENSION: usize = 8;
+const STREAM: usize = 1_000_000;
+
+/// Octets alloués au plus fort de la recherche de `k` résultats dans un flux de
+/// `STREAM` vecteurs, au-delà de ceux déjà alloués avant.
+fn peak_for(k: usize) -> usize {
+    let mut generator = VectorGenerator::new(DIMENSION, 185);
+    let query = generator.vector();
+    let before = LIVE.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let stream = (0..STREAM).map(|_| (generator.uuid(), generator.vector()));
+    let hits = search_stream(&query, stream, k, Metric::Cosine, |_, _| {
+        unreachable!("même dimension")
+    });
+    assert_eq!(hits.len(), k);
+    PEAK.load(Ordering::SeqCst) - before
+}
+
+#[test]
+fn peak_memory_is_proportional_to_k() {
+    // Un résultat occupe 20 octets (Uuid et score) ; le vecteur en cours, 32 octets.
+    let per_hit = 64;
+    let small = peak_for(10);
+    assert!(small <= 10 * per_hit + 1024, "{} octets pour k = 10", small);
+    let large = peak_for(1000);
+    assert!(
+        large <= 1000 * per_hit + 1024,
+        "{} octets pour k = 1000",
+        large
+    );
+    assert!(large > small);
+}
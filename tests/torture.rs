@@ -0,0 +1,244 @@
+//! Pannes injectées dans les sauvegardes et le journal de réplication (fonctionnalité
+//! `fault-injection`) : après chaque panne, on relit les fichiers comme le ferait un
+//! redémarrage et on vérifie qu'ils décrivent un état cohérent.
+#![cfg(feature = "fault-injection")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use embeddingproject::{
+    BaseDeDonnees, BaseDeDonneesPartagee, Collection, Error, Fault, FaultInjector, FileSystem, Op,
+    Primary, Replica, Storage, StorageOp,
+};
+use uuid::Uuid;
+
+/// Répertoire temporaire supprimé à la fin du test.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("embeddingproject-torture-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn injector() -> FaultInjector {
+    FaultInjector::new(Arc::new(FileSystem))
+}
+
+/// Base d'une collection `docs` de `count` documents.
+fn database(storage: &FaultInjector, count: usize) -> BaseDeDonnees {
+    let mut db = BaseDeDonnees::new().with_storage(Arc::new(storage.clone()));
+    db.add("docs".to_string());
+    let docs = db.get_mut("docs").unwrap();
+    for i in 0..count {
+        docs.upsert(Uuid::new_v4(), [1.0, i as f32]).unwrap();
+    }
+    db
+}
+
+fn documents(path: &PathBuf) -> usize {
+    let db = BaseDeDonnees::load(path).unwrap();
+    let docs = db.get("docs").unwrap();
+    docs.check_invariants().unwrap();
+    docs.len()
+}
+
+#[test]
+fn interrupted_saves_leave_the_previous_save_intact() {
+    let dir = TempDir::new();
+    let path = dir.path("base.snap");
+    let storage = injector();
+    database(&storage, 10).save(&path).unwrap();
+    let db = database(&storage, 25);
+
+    for (op, fault) in [
+        (StorageOp::Write, Fault::Truncate(100)),
+        (StorageOp::Write, Fault::Fail),
+        (StorageOp::Sync, Fault::Fail),
+        (StorageOp::Rename, Fault::Fail),
+    ] {
+        storage.reset();
+        storage.inject(op, 1, fault);
+        assert!(matches!(db.save(&path), Err(Error::Io(_))), "{:?}", op);
+        assert_eq!(storage.triggered(), [(op, 1, fault)]);
+        // Un redémarrage retrouve la sauvegarde précédente, complète.
+        assert_eq!(documents(&path), 10, "{:?}", op);
+    }
+
+    storage.reset();
+    db.save(&path).unwrap();
+    assert_eq!(documents(&path), 25);
+    assert!(!dir.path("base.snap.tmp").exists());
+}
+
+#[test]
+fn crash_during_a_log_append_keeps_the_complete_records() {
+    let dir = TempDir::new();
+    let path = dir.path("journal.log");
+    let storage = injector();
+    let db = BaseDeDonnees::new().with_storage(Arc::new(storage.clone()));
+    let primary = Primary::new(BaseDeDonneesPartagee::new(db))
+        .with_log_file(&path)
+        .unwrap();
+    primary.create_collection("docs").unwrap();
+    let keys: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for key in &keys[..4] {
+        primary.upsert("docs", *key, vec![1.0, 2.0]).unwrap();
+    }
+
+    // Arrêt brutal au milieu de l'écriture de la trame suivante.
+    let writes = storage.calls(StorageOp::Write);
+    storage.inject(StorageOp::Write, writes + 1, Fault::Truncate(7));
+    assert!(primary.upsert("docs", keys[4], vec![3.0, 4.0]).is_err());
+    drop(primary);
+
+    let replica = Replica::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+    assert_eq!(replica.consume_file(&path).unwrap(), 5);
+    let db = replica.db().read();
+    let docs = db.get("docs").unwrap();
+    docs.check_invariants().unwrap();
+    assert_eq!(docs.len(), 4);
+    assert!(keys[..4].iter().all(|key| docs.read(key).is_some()));
+    assert!(docs.read(&keys[4]).is_none());
+}
+
+#[test]
+fn failed_append_stops_the_primary_and_keeps_the_log_readable() {
+    let dir = TempDir::new();
+    let path = dir.path("journal.log");
+    let storage = injector();
+    let db = BaseDeDonnees::new().with_storage(Arc::new(storage.clone()));
+    let primary = Primary::new(BaseDeDonneesPartagee::new(db))
+        .with_log_file(&path)
+        .unwrap();
+    primary.create_collection("docs").unwrap();
+    let writes = storage.calls(StorageOp::Write);
+    storage.inject(StorageOp::Write, writes + 1, Fault::Truncate(7));
+    assert!(primary.upsert("docs", Uuid::new_v4(), vec![1.0]).is_err());
+
+    // Le primaire s'arrête : rien ne s'écrit après la trame tronquée.
+    assert!(matches!(
+        primary.upsert("docs", Uuid::new_v4(), vec![2.0]),
+        Err(Error::ShutDown)
+    ));
+    assert!(primary
+        .shutdown(None, Duration::from_secs(1))
+        .errors
+        .is_empty());
+    drop(primary);
+    let replica = Replica::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+    assert_eq!(replica.consume_file(&path).unwrap(), 1);
+    assert!(replica.db().read().get("docs").unwrap().is_empty());
+}
+
+#[test]
+fn failed_log_sync_is_reported_by_shutdown() {
+    let dir = TempDir::new();
+    let path = dir.path("journal.log");
+    let storage = injector();
+    let db = BaseDeDonnees::new().with_storage(Arc::new(storage.clone()));
+    let primary = Primary::new(BaseDeDonneesPartagee::new(db))
+        .with_log_file(&path)
+        .unwrap();
+    primary.create_collection("docs").unwrap();
+    primary
+        .upsert("docs", Uuid::new_v4(), vec![1.0, 2.0])
+        .unwrap();
+
+    // Panne entre l'écriture des trames et leur fsync.
+    storage.inject(StorageOp::Sync, 1, Fault::Fail);
+    let report = primary.shutdown(None, Duration::from_secs(1));
+    assert!(matches!(report.errors[..], [Error::Io(_)]));
+    assert_eq!(report.last_sequence, 2);
+    // Les trames écrites avant la panne se relisent.
+    let replica = Replica::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+    assert_eq!(replica.consume_file(&path).unwrap(), 2);
+}
+
+#[test]
+fn interrupted_batch_is_replayed_entirely_or_not_at_all() {
+    let dir = TempDir::new();
+    let path = dir.path("journal.log");
+    let storage = injector();
+    let db = BaseDeDonnees::new().with_storage(Arc::new(storage.clone()));
+    let primary = Primary::new(BaseDeDonneesPartagee::new(db))
+        .with_log_file(&path)
+        .unwrap();
+    primary.create_collection("docs").unwrap();
+    let batch = |n: usize| -> Vec<Op> {
+        (0..n)
+            .map(|i| Op::Upsert {
+                key: Uuid::new_v4(),
+                vector: vec![1.0, i as f32],
+                payload: None,
+            })
+            .collect()
+    };
+    primary.apply("docs", batch(50)).unwrap();
+    let writes = storage.calls(StorageOp::Write);
+    storage.inject(StorageOp::Write, writes + 1, Fault::Truncate(300));
+    assert!(primary.apply("docs", batch(50)).is_err());
+    drop(primary);
+
+    let replica = Replica::new(BaseDeDonneesPartagee::new(BaseDeDonnees::new()));
+    replica.consume_file(&path).unwrap();
+    let db = replica.db().read();
+    db.get("docs").unwrap().check_invariants().unwrap();
+    assert_eq!(db.get("docs").unwrap().len(), 50);
+}
+
+#[test]
+fn batch_upsert_stopped_by_a_bad_document_keeps_a_consistent_prefix() {
+    let mut collection = Collection::new();
+    let mut items: Vec<(Uuid, Vec<f32>)> = (0..100)
+        .map(|i| (Uuid::new_v4(), vec![1.0, i as f32]))
+        .collect();
+    items[60].1 = vec![1.0, 2.0, 3.0];
+    assert!(matches!(
+        collection.upsert_batch(items.clone(), None),
+        Err(Error::DimensionLocked { .. })
+    ));
+    collection.check_invariants().unwrap();
+    assert_eq!(collection.len(), 60);
+
+    // Un lot appliqué en bloc est refusé entièrement.
+    let mut collection = Collection::new();
+    let ops: Vec<Op> = (items.into_iter())
+        .map(|(key, vector)| Op::Upsert {
+            key,
+            vector,
+            payload: None,
+        })
+        .collect();
+    assert!(collection.apply(ops).is_err());
+    collection.check_invariants().unwrap();
+    assert!(collection.is_empty());
+}
+
+#[test]
+fn storage_errors_reach_the_caller_unchanged() {
+    let storage = injector();
+    storage.inject(StorageOp::Rename, 1, Fault::Fail);
+    let dir = TempDir::new();
+    let (from, to) = (dir.path("a"), dir.path("b"));
+    fs::write(&from, b"contenu").unwrap();
+    assert!(storage.rename(&from, &to).is_err());
+    assert!(from.exists() && !to.exists());
+    storage.rename(&from, &to).unwrap();
+    assert_eq!(storage.read(&to).unwrap(), b"contenu");
+    assert_eq!(storage.calls(StorageOp::Rename), 2);
+}